@@ -0,0 +1,49 @@
+//! Where `clock()` and `time` blocks read the time from, so that tests can control it.
+
+use std::{
+  cell::Cell,
+  fmt::Debug,
+  rc::Rc,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A source of the time `clock()` returns, in seconds. Install with the `clock` field of either
+/// engine.
+pub trait Clock: Debug {
+  fn now(&self) -> f64;
+}
+
+/// Reads the system time, as seconds since the Unix epoch.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64()
+  }
+}
+
+/// A clock that only moves when told to. Clones share the time, so a test can keep one to move
+/// the clock it handed to an engine.
+#[derive(Debug, Default, Clone)]
+pub struct MockClock(Rc<Cell<f64>>);
+
+impl MockClock {
+  pub fn new(now: f64) -> Self {
+    Self(Rc::new(Cell::new(now)))
+  }
+
+  pub fn set(&self, now: f64) {
+    self.0.set(now);
+  }
+
+  pub fn advance(&self, seconds: f64) {
+    self.0.set(self.0.get() + seconds);
+  }
+}
+
+impl Clock for MockClock {
+  fn now(&self) -> f64 {
+    self.0.get()
+  }
+}
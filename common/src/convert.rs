@@ -0,0 +1,146 @@
+//! Conversions between Rust values and Lox values, so that natives and hosts need not match on
+//! the value type of either engine themselves. Each engine implements [`Value`] for its own type.
+
+/// The value type of an engine, as far as the conversions build and read it.
+pub trait Value: Clone {
+  fn nil() -> Self;
+
+  fn boolean(b: bool) -> Self;
+
+  fn number(n: f64) -> Self;
+
+  fn string(s: String) -> Self;
+
+  fn is_nil(&self) -> bool;
+
+  fn as_boolean(&self) -> Option<bool>;
+
+  fn as_number(&self) -> Option<f64>;
+
+  fn as_string(&self) -> Option<&str>;
+}
+
+/// A Rust value that can be handed to Lox code as a `V`.
+pub trait ToLox<V> {
+  fn to_lox(self) -> V;
+}
+
+/// A Rust value that can be read from a `V` of Lox code.
+pub trait FromLox<V>: Sized {
+  /// Name of the expected Lox type, for error messages.
+  const TYPE: &'static str;
+
+  /// Returns the Rust value, or `None` if `value` does not hold one.
+  fn from_lox(value: &V) -> Option<Self>;
+}
+
+impl<V: Value> ToLox<V> for V {
+  fn to_lox(self) -> V {
+    self
+  }
+}
+
+impl<V: Value> FromLox<V> for V {
+  const TYPE: &'static str = "any";
+
+  fn from_lox(value: &V) -> Option<Self> {
+    Some(value.clone())
+  }
+}
+
+impl<V: Value> ToLox<V> for () {
+  fn to_lox(self) -> V {
+    V::nil()
+  }
+}
+
+impl<V: Value> ToLox<V> for bool {
+  fn to_lox(self) -> V {
+    V::boolean(self)
+  }
+}
+
+impl<V: Value> FromLox<V> for bool {
+  const TYPE: &'static str = "boolean";
+
+  fn from_lox(value: &V) -> Option<Self> {
+    value.as_boolean()
+  }
+}
+
+impl<V: Value> ToLox<V> for f64 {
+  fn to_lox(self) -> V {
+    V::number(self)
+  }
+}
+
+impl<V: Value> FromLox<V> for f64 {
+  const TYPE: &'static str = "number";
+
+  fn from_lox(value: &V) -> Option<Self> {
+    value.as_number()
+  }
+}
+
+/// Integers convert to numbers exactly while they fit in the 53 bits of a float's mantissa, and
+/// only whole numbers in range convert back.
+macro_rules! integer {
+  ($($ty:ty),*) => {$(
+    impl<V: Value> ToLox<V> for $ty {
+      fn to_lox(self) -> V {
+        V::number(self as f64)
+      }
+    }
+
+    impl<V: Value> FromLox<V> for $ty {
+      const TYPE: &'static str = "integer";
+
+      fn from_lox(value: &V) -> Option<Self> {
+        value
+          .as_number()
+          .filter(|n| n.fract() == 0.0 && *n >= <$ty>::MIN as f64 && *n <= <$ty>::MAX as f64)
+          .map(|n| n as $ty)
+      }
+    }
+  )*};
+}
+
+integer!(i32, i64, u32, u64, usize);
+
+impl<V: Value> ToLox<V> for String {
+  fn to_lox(self) -> V {
+    V::string(self)
+  }
+}
+
+impl<V: Value> ToLox<V> for &str {
+  fn to_lox(self) -> V {
+    V::string(self.into())
+  }
+}
+
+impl<V: Value> FromLox<V> for String {
+  const TYPE: &'static str = "string";
+
+  fn from_lox(value: &V) -> Option<Self> {
+    value.as_string().map(Into::into)
+  }
+}
+
+/// `None` is `nil`.
+impl<V: Value, T: ToLox<V>> ToLox<V> for Option<T> {
+  fn to_lox(self) -> V {
+    self.map_or_else(V::nil, T::to_lox)
+  }
+}
+
+impl<V: Value, T: FromLox<V>> FromLox<V> for Option<T> {
+  const TYPE: &'static str = T::TYPE;
+
+  fn from_lox(value: &V) -> Option<Self> {
+    match value.is_nil() {
+      true => Some(None),
+      false => T::from_lox(value).map(Some),
+    }
+  }
+}
@@ -0,0 +1,83 @@
+//! Conditional compilation. Code between `#if NAME` and `#end` is only scanned into tokens when
+//! `NAME` is defined with `--define NAME` or `--define NAME=VALUE` for any value but `false`. An
+//! `#else` line starts the code kept otherwise, and blocks nest.
+//!
+//! Directives are lines whose first character other than whitespace is `#`. Code left out is
+//! still scanned, so that a `#` in a string or a block comment is never taken for a directive,
+//! but it reads as whitespace.
+//!
+//! Both scanners keep a [`Directives`] with their own span type, and turn a [`DirectiveError`]
+//! into their own scan error.
+
+use std::collections::HashMap;
+
+/// A directive that cannot be applied where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveError {
+  /// A second `#else` for the same `#if`.
+  ExtraElse,
+  ElseWithoutIf,
+  EndWithoutIf,
+  /// A line starting with `#` that is not `#if NAME`, `#else` or `#end`.
+  InvalidDirective,
+}
+
+/// An `#if` whose `#end` has not been seen yet.
+struct Open<S> {
+  /// Whether the code of the current branch is kept, ignoring enclosing blocks.
+  kept: bool,
+  in_else: bool,
+  span: S,
+}
+
+/// The defines a source is scanned with, and the blocks open at the current position.
+pub struct Directives<S> {
+  defines: HashMap<String, String>,
+  open: Vec<Open<S>>,
+}
+
+impl<S> Default for Directives<S> {
+  fn default() -> Self {
+    Self::new(HashMap::new())
+  }
+}
+
+impl<S> Directives<S> {
+  pub fn new(defines: HashMap<String, String>) -> Self {
+    Self { defines, open: Vec::new() }
+  }
+
+  /// Whether code at the current position is kept.
+  pub fn kept(&self) -> bool {
+    self.open.iter().all(|block| block.kept)
+  }
+
+  /// Applies the directive at `span`, whose text after the `#` is `text`.
+  pub fn apply<E: From<DirectiveError>>(&mut self, text: &str, span: S) -> Result<(), E> {
+    match text.split_whitespace().collect::<Vec<_>>()[..] {
+      ["if", name] => {
+        let kept = self.defines.get(name).is_some_and(|value| value != "false");
+        self.open.push(Open { kept, in_else: false, span });
+      }
+      ["else"] => match self.open.last_mut() {
+        Some(block) if !block.in_else => {
+          block.kept = !block.kept;
+          block.in_else = true;
+        }
+        Some(_) => return Err(DirectiveError::ExtraElse.into()),
+        None => return Err(DirectiveError::ElseWithoutIf.into()),
+      },
+      ["end"] => {
+        self.open.pop().ok_or(DirectiveError::EndWithoutIf)?;
+      }
+      _ => return Err(DirectiveError::InvalidDirective.into()),
+    }
+    Ok(())
+  }
+
+  /// Closes the innermost block still open at the end of the source, returning the span of its
+  /// `#if`.
+  pub fn close_unended(&mut self) -> Option<S> {
+    self.open.pop().map(|block| block.span)
+  }
+}
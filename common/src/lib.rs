@@ -0,0 +1,69 @@
+//! Code shared by both interpreters and the tools built on them: diagnostics and how they are
+//! rendered for people and for other tools, the profiles and timings the engines record, the
+//! clocks and value conversions that natives are written against, and the parts of scanning that
+//! do not depend on the tokens: streamed sources and conditional compilation directives.
+
+use std::ops::Range;
+
+pub mod clock;
+pub mod convert;
+pub mod directive;
+pub mod profile;
+pub mod render;
+pub mod sarif;
+pub mod stream;
+pub mod timings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+  Error,
+  Warning,
+  /// Context for the preceding error, such as a stack frame.
+  Note,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+  pub level: Level,
+  /// Engine-specific message. Its wording is not expected to match across engines.
+  pub message: String,
+  /// The message without the level and position the engine frames it with, for tools that show
+  /// those apart. The same as `message` unless set with [`Diagnostic::with_summary`].
+  pub summary: String,
+  /// Byte range in the source, if the message points at one.
+  pub span: Option<Range<usize>>,
+  /// Name of the warning, for those that `lox-ignore` comments silence.
+  pub lint: Option<String>,
+  /// Other places in the source that explain the message.
+  pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+  pub fn new(level: Level, message: impl Into<String>, span: Range<usize>) -> Self {
+    let message = message.into();
+    Self { level, summary: message.clone(), message, span: Some(span), lint: None, labels: Vec::new() }
+  }
+
+  /// A note on how to fix the error before it, which points at no span of its own.
+  pub fn note(message: impl Into<String>) -> Self {
+    let message = message.into();
+    Self { level: Level::Note, summary: message.clone(), message, span: None, lint: None, labels: Vec::new() }
+  }
+
+  pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
+    self.summary = summary.into();
+    self
+  }
+
+  pub fn with_labels(mut self, labels: impl IntoIterator<Item = Label>) -> Self {
+    self.labels.extend(labels);
+    self
+  }
+}
+
+/// A secondary span of a diagnostic, such as the declaration that a name clashes with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+  pub span: Range<usize>,
+  pub message: String,
+}
@@ -0,0 +1,85 @@
+//! Call profiles written by `--profile-calls`.
+
+use std::{
+  cell::RefCell,
+  collections::BTreeMap,
+  fmt::Write,
+  rc::Rc,
+  time::{Duration, Instant},
+};
+
+/// Time spent in each stack of calls. Clones share the same record, so one can be installed in
+/// the hooks of an engine while another is kept to read the result.
+#[derive(Debug, Clone)]
+pub struct CallProfile(Rc<RefCell<Stacks>>);
+
+#[derive(Debug)]
+struct Stacks {
+  /// Calls still running, outermost first. The script itself is the bottom one.
+  running: Vec<Frame>,
+  /// Time spent in each finished stack, excluding its callees, keyed by the folded stack.
+  totals: BTreeMap<String, Duration>,
+}
+
+#[derive(Debug)]
+struct Frame {
+  name: String,
+  start: Instant,
+  /// Time taken by the calls this one made.
+  callees: Duration,
+}
+
+impl CallProfile {
+  /// Starts profiling a script that begins running now.
+  pub fn new() -> Self {
+    let script = Frame { name: "<script>".into(), start: Instant::now(), callees: Duration::ZERO };
+    Self(Rc::new(RefCell::new(Stacks { running: vec![script], totals: BTreeMap::new() })))
+  }
+
+  /// Records a call to `name` starting now.
+  pub fn enter(&self, name: &str) {
+    let frame = Frame { name: name.into(), start: Instant::now(), callees: Duration::ZERO };
+    self.0.borrow_mut().running.push(frame);
+  }
+
+  /// Records the innermost running call returning now. The script itself only ends with
+  /// `finish`.
+  pub fn leave(&self) {
+    let mut stacks = self.0.borrow_mut();
+    if stacks.running.len() > 1 {
+      stacks.end_call();
+    }
+  }
+
+  /// Ends the calls still running, including the script, and returns the profile in the folded
+  /// format that flamegraph tools read: a `<script>;outer;inner <microseconds>` line per stack.
+  pub fn finish(&self) -> String {
+    let mut stacks = self.0.borrow_mut();
+    while !stacks.running.is_empty() {
+      stacks.end_call();
+    }
+    stacks.totals.iter().fold(String::new(), |mut out, (stack, time)| {
+      let _ = writeln!(out, "{stack} {}", time.as_micros());
+      out
+    })
+  }
+}
+
+impl Default for CallProfile {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Stacks {
+  fn end_call(&mut self) {
+    let Some(frame) = self.running.pop() else { return };
+    let elapsed = frame.start.elapsed();
+    let mut stack: Vec<&str> = self.running.iter().map(|frame| frame.name.as_str()).collect();
+    stack.push(&frame.name);
+    *self.totals.entry(stack.join(";")).or_default() += elapsed.saturating_sub(frame.callees);
+    if let Some(caller) = self.running.last_mut() {
+      caller.callees += elapsed;
+    }
+  }
+}
@@ -0,0 +1,126 @@
+//! Plain-text rendering of diagnostics, shared by the snapshot tests and the `rlox` binary.
+
+use std::{fmt::Write, ops::Range};
+
+use crate::{Diagnostic, Level};
+
+/// Writes the message followed by the source line it points at, with the span underlined, then
+/// the lines each label points at, with its span underlined by dashes and followed by its text.
+pub fn render(out: &mut String, src: &str, diagnostic: &Diagnostic) {
+  let level = match diagnostic.level {
+    Level::Error => "error",
+    Level::Warning => "warning",
+    Level::Note => "note",
+  };
+  writeln!(out, "{level}: {}", diagnostic.message).unwrap();
+
+  if let Some(span) = &diagnostic.span {
+    snippet(out, src, span.clone(), '^', "");
+  }
+  for label in &diagnostic.labels {
+    snippet(out, src, label.span.clone(), '-', &label.message);
+  }
+}
+
+/// Lines shown of a span running over more lines than this: the first ones and the last one.
+const MAX_LINES: usize = 4;
+
+/// Writes the lines `start..end` is on, underlined with `mark`, and `text` after the last underline.
+fn snippet(out: &mut String, src: &str, Range { start, end }: Range<usize>, mark: char, text: &str) {
+  let start = start.min(src.len());
+  let end = end.clamp(start, src.len());
+
+  // every line holding part of the span, except one the span only reaches the start of
+  let mut lines = Vec::new();
+  let mut line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+  let mut line_no = src[..start].matches('\n').count() + 1;
+  loop {
+    let line_end = src[line_start..].find('\n').map_or(src.len(), |i| line_start + i);
+    lines.push((line_no, line_start, line_end));
+    if line_end >= end || line_end == src.len() || line_end + 1 >= end {
+      break;
+    }
+    line_start = line_end + 1;
+    line_no += 1;
+  }
+
+  let elided = lines.len() > MAX_LINES;
+  if elided {
+    lines.drain(MAX_LINES - 1..lines.len() - 1);
+  }
+  let gutter = lines.last().map_or(0, |(line_no, ..)| line_no.to_string().len());
+
+  for (i, &(line_no, line_start, line_end)) in lines.iter().enumerate() {
+    if elided && i == lines.len() - 1 {
+      writeln!(out, "{:gutter$} | ...", "").unwrap();
+    }
+    // empty spans still get a caret
+    let from = start.max(line_start);
+    let col = src[line_start..from].chars().count();
+    let width = src[from..end.min(line_end)].chars().count().max(usize::from(i == 0));
+
+    let line = format!("{line_no:>gutter$} | {}", &src[line_start..line_end]);
+    writeln!(out, "{}", line.trim_end()).unwrap();
+    let text = if i == lines.len() - 1 { text } else { "" };
+    if width > 0 || !text.is_empty() {
+      let underline = format!("{:gutter$} | {}{} {text}", "", " ".repeat(col), mark.to_string().repeat(width));
+      writeln!(out, "{}", underline.trim_end()).unwrap();
+    }
+  }
+}
+
+/// Renders the diagnostics of a run one after another, counting the errors and warnings and
+/// leaving out everything after the first `max_errors` errors, so that a badly broken file does
+/// not flood the terminal.
+#[derive(Debug, Default)]
+pub struct Sink {
+  /// Errors rendered before the rest are left out, or `None` to render them all.
+  pub max_errors: Option<usize>,
+  pub errors: usize,
+  pub warnings: usize,
+  /// Errors and warnings left out because of the cap.
+  pub omitted: usize,
+}
+
+impl Sink {
+  pub fn new(max_errors: Option<usize>) -> Self {
+    Self { max_errors, ..Self::default() }
+  }
+
+  /// Renders `diagnostic` to `out`, unless the cap has been reached. Notes are left out along
+  /// with the error they follow.
+  pub fn render(&mut self, out: &mut String, src: &str, diagnostic: &Diagnostic) {
+    match diagnostic.level {
+      Level::Error => self.errors += 1,
+      Level::Warning => self.warnings += 1,
+      Level::Note => {}
+    }
+    if self.max_errors.is_some_and(|max| self.errors > max) {
+      if diagnostic.level != Level::Note {
+        self.omitted += 1;
+      }
+      return;
+    }
+    render(out, src, diagnostic);
+  }
+
+  /// Line summing up the run, such as `2 errors, 1 warning emitted`, or `None` if it reported
+  /// nothing.
+  pub fn summary(&self) -> Option<String> {
+    if self.errors + self.warnings == 0 {
+      return None;
+    }
+    let mut summary = format!("{}, {} emitted", count(self.errors, "error"), count(self.warnings, "warning"));
+    if let (Some(max), 1..) = (self.max_errors, self.omitted) {
+      write!(summary, " ({} not shown after the first {})", self.omitted, count(max, "error")).unwrap();
+    }
+    Some(summary)
+  }
+}
+
+fn count(n: usize, noun: &str) -> String {
+  match n {
+    1 => format!("1 {noun}"),
+    n => format!("{n} {noun}s"),
+  }
+}
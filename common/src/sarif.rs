@@ -0,0 +1,143 @@
+//! SARIF form of diagnostics, printed by `rlox --output-format=sarif` so that code scanning
+//! services and other tools can ingest them.
+//!
+//! The log holds a single run of `rlox`, with one result per error or warning. Warnings that
+//! `lox-ignore` comments silence name their rule. Regions give lines and columns from 1, with
+//! columns counted in code points, as well as the byte range of the span. Messages leave out the
+//! level and position that the engines frame them with, since the result gives both.
+
+use std::{
+  collections::BTreeSet,
+  env,
+  fmt::Write,
+  ops::Range,
+  path::{Component, Path},
+};
+
+use crate::{Diagnostic, Level};
+
+const VERSION: &str = "2.1.0";
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Encodes the diagnostics reported for `src`, read from `uri`, as a SARIF log. Notes are left
+/// out, as they only add to the error before them.
+pub fn to_sarif(uri: &str, src: &str, diagnostics: &[Diagnostic]) -> String {
+  let rules: BTreeSet<&str> = diagnostics.iter().filter_map(|diag| diag.lint.as_deref()).collect();
+  let rules: Vec<String> = rules.into_iter().map(|id| format!("{{\"id\":{}}}", string(id))).collect();
+  let results: Vec<String> = diagnostics
+    .iter()
+    .filter(|diag| diag.level != Level::Note)
+    .map(|diag| result(uri, src, diag))
+    .collect();
+  format!(
+    "{{\"version\":\"{VERSION}\",\"$schema\":\"{SCHEMA}\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"rlox\",\"rules\":[{}]}}}},\"columnKind\":\"unicodeCodePoints\",\"results\":[{}]}}]}}",
+    rules.join(","),
+    results.join(",")
+  )
+}
+
+/// The URI that results for the script at `path` point at: relative to the current directory if
+/// the script is inside it, since tools resolve results against the root they scanned, and a
+/// `file` URI otherwise.
+pub fn uri(path: &Path) -> String {
+  let cwd = env::current_dir().ok();
+  let path = cwd.as_deref().map_or(path.to_path_buf(), |cwd| cwd.join(path));
+  match cwd.as_deref().and_then(|cwd| path.strip_prefix(cwd).ok()) {
+    Some(relative) => encode(relative),
+    None => format!("file://{}", encode(&path)),
+  }
+}
+
+/// Joins the components of `path` with `/`, percent-encoding all but unreserved characters.
+fn encode(path: &Path) -> String {
+  let segments: Vec<String> = path
+    .components()
+    .filter_map(|component| match component {
+      Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().into_owned()),
+      Component::RootDir | Component::CurDir => None,
+      Component::ParentDir => Some("..".into()),
+      Component::Normal(name) => Some(name.to_string_lossy().bytes().map(encode_byte).collect()),
+    })
+    .collect();
+  let root = if path.has_root() { "/" } else { "" };
+  format!("{root}{}", segments.join("/"))
+}
+
+fn encode_byte(byte: u8) -> String {
+  match byte {
+    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+    _ => format!("%{byte:02X}"),
+  }
+}
+
+fn result(uri: &str, src: &str, diagnostic: &Diagnostic) -> String {
+  let level = match diagnostic.level {
+    Level::Error => "error",
+    Level::Warning => "warning",
+    Level::Note => "note",
+  };
+  let mut out = format!("{{\"level\":\"{level}\",\"message\":{{\"text\":{}}}", string(&diagnostic.summary));
+  if let Some(lint) = &diagnostic.lint {
+    write!(out, ",\"ruleId\":{}", string(lint)).unwrap();
+  }
+  let locations: Vec<String> = diagnostic
+    .span
+    .iter()
+    .map(|span| format!("{{\"physicalLocation\":{}}}", location(uri, src, span.clone())))
+    .collect();
+  write!(out, ",\"locations\":[{}]", locations.join(",")).unwrap();
+  if !diagnostic.labels.is_empty() {
+    let related: Vec<String> = diagnostic
+      .labels
+      .iter()
+      .enumerate()
+      .map(|(id, label)| {
+        format!(
+          "{{\"id\":{id},\"message\":{{\"text\":{}}},\"physicalLocation\":{}}}",
+          string(&label.message),
+          location(uri, src, label.span.clone())
+        )
+      })
+      .collect();
+    write!(out, ",\"relatedLocations\":[{}]", related.join(",")).unwrap();
+  }
+  out.push('}');
+  out
+}
+
+fn location(uri: &str, src: &str, Range { start, end }: Range<usize>) -> String {
+  let start = start.min(src.len());
+  let end = end.clamp(start, src.len());
+  let (start_line, start_column) = position(src, start);
+  let (end_line, end_column) = position(src, end);
+  format!(
+    "{{\"artifactLocation\":{{\"uri\":{}}},\"region\":{{\"startLine\":{start_line},\"startColumn\":{start_column},\"endLine\":{end_line},\"endColumn\":{end_column},\"byteOffset\":{start},\"byteLength\":{}}}}}",
+    string(uri),
+    end - start
+  )
+}
+
+/// Line and column of the byte at `offset`, both counted from 1.
+fn position(src: &str, offset: usize) -> (usize, usize) {
+  let before = &src[..offset];
+  let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+  (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+fn string(s: &str) -> String {
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
@@ -0,0 +1,97 @@
+use std::{
+  io::{ErrorKind, Read},
+  str,
+};
+
+/// Bytes read from the input at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Characters decoded from a reader a chunk at a time, so that scanning holds the chunk and the
+/// lexeme being scanned rather than the whole source. Offsets count bytes of the input, as they
+/// do for a `&str` source. Bytes that are not UTF-8 decode to U+FFFD, one byte at a time, and a
+/// read error ends the input.
+pub struct Stream<'src> {
+  reader: Box<dyn Read + 'src>,
+  buf: Vec<u8>,
+  /// Offset in `buf` of the next character.
+  pos: usize,
+  /// Offset in the input of the start of `buf`.
+  base: usize,
+  eof: bool,
+  /// Text of the lexeme being scanned, which the scanner clears at the start of each token.
+  pub lexeme: String,
+}
+
+impl<'src> Stream<'src> {
+  pub fn new(reader: impl Read + 'src) -> Self {
+    Self {
+      reader: Box::new(reader),
+      buf: Vec::with_capacity(CHUNK_SIZE),
+      pos: 0,
+      base: 0,
+      eof: false,
+      lexeme: String::new(),
+    }
+  }
+
+  /// Returns the next character and its offset without moving past it.
+  pub fn peek(&mut self) -> Option<(usize, char)> {
+    self.decode().map(|(at, c, _)| (at, c))
+  }
+
+  /// Offset of the end of the input, once it has been reached.
+  pub fn end(&self) -> usize {
+    self.base + self.pos
+  }
+
+  /// Decodes the character at `pos`, with the number of bytes it takes.
+  fn decode(&mut self) -> Option<(usize, char, usize)> {
+    // the longest character takes 4 bytes
+    if self.buf.len() - self.pos < 4 {
+      self.fill();
+    }
+    let bytes = &self.buf[self.pos..];
+    let first = *bytes.first()?;
+    let len = match first {
+      0xF0.. => 4,
+      0xE0.. => 3,
+      0xC0.. => 2,
+      _ => 1,
+    };
+    let at = self.base + self.pos;
+    match bytes.get(..len).and_then(|bytes| str::from_utf8(bytes).ok()) {
+      Some(text) => Some((at, text.chars().next().unwrap(), len)),
+      None => Some((at, char::REPLACEMENT_CHARACTER, 1)),
+    }
+  }
+
+  /// Drops the bytes scanned so far and reads the next chunk after the rest.
+  fn fill(&mut self) {
+    self.buf.drain(..self.pos);
+    self.base += self.pos;
+    self.pos = 0;
+    while !self.eof && self.buf.len() < 4 {
+      let start = self.buf.len();
+      self.buf.resize(start + CHUNK_SIZE, 0);
+      let read = loop {
+        match self.reader.read(&mut self.buf[start..]) {
+          Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+          read => break read.unwrap_or(0),
+        }
+      };
+      self.buf.truncate(start + read);
+      self.eof = read == 0;
+    }
+  }
+}
+
+impl Iterator for Stream<'_> {
+  type Item = (usize, char);
+
+  /// Returns the next character and its offset, and moves past it.
+  fn next(&mut self) -> Option<Self::Item> {
+    let (at, c, len) = self.decode()?;
+    self.pos += len;
+    Some((at, c))
+  }
+}
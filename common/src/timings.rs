@@ -0,0 +1,44 @@
+//! Phase durations of the last run, printed by `--time`.
+
+use std::{
+  fmt::{self, Display},
+  fs,
+  time::Duration,
+};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Timings {
+  /// Phases in the order they ran, named by the engine that ran them.
+  pub phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+  pub fn record(&mut self, phase: &'static str, duration: Duration) {
+    self.phases.push((phase, duration));
+  }
+
+  pub fn total(&self) -> Duration {
+    self.phases.iter().map(|(_, duration)| *duration).sum()
+  }
+}
+
+impl Display for Timings {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("[time] ")?;
+    for (phase, duration) in &self.phases {
+      write!(f, "{phase} {}us, ", duration.as_micros())?;
+    }
+    write!(f, "total {}us", self.total().as_micros())?;
+    if let Some(kib) = peak_memory() {
+      write!(f, "; peak memory {kib} KiB")?;
+    }
+    Ok(())
+  }
+}
+
+/// Peak resident memory of the process in KiB, where the platform reports it.
+fn peak_memory() -> Option<u64> {
+  let status = fs::read_to_string("/proc/self/status").ok()?;
+  let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+  line["VmHWM:".len()..].trim().trim_end_matches("kB").trim().parse().ok()
+}
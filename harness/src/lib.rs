@@ -0,0 +1,410 @@
+//! Common interface over the two interpreters, so the same Lox programs can be checked against
+//! both the tree-walker (`rtlox`) and the bytecode VM (`rblox`).
+
+use std::{
+  collections::HashMap,
+  fmt::Display,
+  iter,
+  path::{Path, PathBuf},
+  rc::Rc,
+};
+
+use rblox::ErrorType;
+use rtlox::{
+  parser::Parser,
+  resolver::{error::ErrorType as ResolveErrorType, Resolver},
+  span::Span,
+};
+
+use rtlox::{ast::stmt::Stmt, data::LoxValue, interpreter::Interpreter};
+
+pub use rblox::VM;
+
+pub use lox_common::{clock::MockClock, Diagnostic, Label, Level};
+
+pub mod snapshot;
+
+/// Lines written by `print`.
+pub type Output = Vec<String>;
+
+/// Phase in which a program was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+  /// Scanning, parsing or resolution; nothing ran.
+  Compile,
+  Runtime,
+}
+
+/// Converts a label of the tree-walker.
+fn tree_label(label: &rtlox::span::Label) -> Label {
+  Label { span: label.span.range(), message: label.message.clone() }
+}
+
+/// Converts a label of the VM.
+fn vm_label(label: &rblox::Label) -> Label {
+  Label { span: label.span.0..label.span.1, message: label.message.clone() }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+  pub stage: Stage,
+  pub messages: Vec<Diagnostic>,
+  /// Lines printed before a runtime error.
+  pub output: Output,
+}
+
+pub trait LoxEngine {
+  /// Short name used in failure messages and snapshot files.
+  const NAME: &'static str;
+
+  /// The globals and their values at some point, see [`LoxEngine::globals_snapshot`].
+  type Globals;
+
+  fn new() -> Self;
+
+  /// Runs `src`, keeping globals from previous runs.
+  fn run(&mut self, src: &str) -> Result<Output, Diagnostics>;
+
+  /// Scans, parses and resolves `src` without running it, for tools that only want its
+  /// diagnostics. Warnings are left for [`LoxEngine::take_warnings`].
+  fn check(&mut self, src: &str) -> Result<(), Diagnostics>;
+
+  /// Makes arithmetic that produces `inf` or `NaN` a runtime error.
+  fn set_checked_arith(&mut self, on: bool);
+
+  /// Writes `print` output to stdout as the program runs, instead of returning it from `run`.
+  fn stream_output(&mut self);
+
+  /// Names the file the source comes from, so that its imports are found relative to it.
+  fn set_file(&mut self, path: &Path);
+
+  /// Returns the warnings raised since the last call, whether or not the program ran.
+  fn take_warnings(&mut self) -> Vec<Diagnostic> {
+    Vec::new()
+  }
+
+  /// Stops the engine from printing anything but the program's output, such as debug listings.
+  fn set_quiet(&mut self, _on: bool) {}
+
+  /// Sets the arguments the script reads with `process.arg`.
+  fn set_args(&mut self, args: &[String]);
+
+  /// Sets the names that `#if` directives test, in the source and its imports.
+  fn set_defines(&mut self, defines: &HashMap<String, String>);
+
+  /// Code the script asked the process to exit with, 0 unless it set another.
+  fn exit_code(&self) -> i32;
+
+  /// Removes a global, returning whether it was defined.
+  fn unset_global(&mut self, name: &str) -> bool;
+
+  /// Takes the globals and their values, to compare with after running more code.
+  fn globals_snapshot(&self) -> Self::Globals;
+
+  /// How the globals changed since `before` was taken, one line per global in sorted order:
+  /// `+ name = value` for a new global, `~ name = value` for one holding another value, and
+  /// `- name` for one that was removed.
+  fn globals_diff(&self, before: &Self::Globals) -> Vec<String>;
+
+  /// Makes `clock()` read `clock` instead of the system time.
+  fn set_clock(&mut self, clock: MockClock);
+
+  /// Turns the compiler's optimizations, such as inlining, on or off. They are on by default and
+  /// must not change what a program does.
+  fn set_optimize(&mut self, _on: bool) {}
+}
+
+/// The tree-walking interpreter, together with the resolver that runs before it.
+pub struct TreeWalker {
+  interpreter: Interpreter,
+  warnings: Vec<Diagnostic>,
+  file: Option<PathBuf>,
+}
+
+impl TreeWalker {
+  fn fail(&mut self, stage: Stage, messages: Vec<Diagnostic>) -> Diagnostics {
+    Diagnostics { stage, messages, output: self.interpreter.take_output() }
+  }
+
+  /// Parses and resolves `src`, returning the statements to run.
+  fn compile(&mut self, src: &str) -> Result<Vec<Stmt>, Diagnostics> {
+    let mut parser = Parser::new(src);
+    parser.options.file = self.file.clone();
+    parser.options.defines = self.interpreter.loader.defines.clone();
+    let ((stmts, errors), ignores) = parser.parse_with_ignores();
+    if !errors.is_empty() {
+      let messages = errors
+        .iter()
+        .flat_map(|err| {
+          let error = Diagnostic::new(Level::Error, err.to_string(), err.primary_span().range())
+            .with_summary(summary(err, err.primary_span()))
+            .with_labels(err.labels().iter().map(tree_label));
+          iter::once(error).chain(err.notes().iter().map(Diagnostic::note))
+        })
+        .collect();
+      return Err(self.fail(Stage::Compile, messages));
+    }
+
+    let mut resolver = Resolver::new(&mut self.interpreter);
+    if let Some(file) = &self.file {
+      resolver.set_file(file);
+    }
+    resolver.set_ignores(ignores);
+    let (_, errors) = resolver.resolve(&stmts);
+    let (errors, warnings): (Vec<_>, Vec<_>) = errors
+      .into_iter()
+      .filter_map(|err| {
+        let level = match err.kind {
+          ResolveErrorType::Error => Level::Error,
+          ResolveErrorType::Warning => Level::Warning,
+          ResolveErrorType::Suppressed => return None,
+        };
+        let message = format!("{}; at position {}", err.message, err.span);
+        let mut diagnostic = Diagnostic::new(level, message, err.span.range()).with_summary(&err.message);
+        diagnostic.lint = err.lint.map(|lint| lint.name().into());
+        Some(diagnostic.with_labels(err.labels.iter().map(tree_label)))
+      })
+      .partition(|diag| diag.level == Level::Error);
+    self.warnings.extend(warnings);
+    if !errors.is_empty() {
+      return Err(self.fail(Stage::Compile, errors));
+    }
+    Ok(stmts)
+  }
+}
+
+impl LoxEngine for TreeWalker {
+  const NAME: &'static str = "tree";
+  type Globals = HashMap<String, LoxValue>;
+
+  fn new() -> Self {
+    let mut interpreter = Interpreter::new();
+    interpreter.capture_output();
+    Self { interpreter, warnings: Vec::new(), file: None }
+  }
+
+  fn run(&mut self, src: &str) -> Result<Output, Diagnostics> {
+    let stmts = self.compile(src)?;
+    match self.interpreter.interpret(&stmts) {
+      Ok(()) => Ok(self.interpreter.take_output()),
+      Err(err) => {
+        let message = Diagnostic::new(Level::Error, err.to_string(), err.primary_span().range())
+          .with_summary(summary(&err, err.primary_span()))
+          .with_labels(err.labels().iter().map(tree_label));
+        Err(self.fail(Stage::Runtime, vec![message]))
+      }
+    }
+  }
+
+  fn check(&mut self, src: &str) -> Result<(), Diagnostics> {
+    self.compile(src).map(|_| ())
+  }
+
+  fn set_checked_arith(&mut self, on: bool) {
+    self.interpreter.checked_arith = on;
+  }
+
+  fn stream_output(&mut self) {
+    self.interpreter.print_output();
+  }
+
+  fn set_file(&mut self, path: &Path) {
+    self.file = Some(path.into());
+  }
+
+  fn take_warnings(&mut self) -> Vec<Diagnostic> {
+    std::mem::take(&mut self.warnings)
+  }
+
+  fn set_args(&mut self, args: &[String]) {
+    self.interpreter.args = args.to_vec();
+  }
+
+  fn set_defines(&mut self, defines: &HashMap<String, String>) {
+    self.interpreter.loader.defines = defines.clone();
+  }
+
+  fn exit_code(&self) -> i32 {
+    self.interpreter.exit_code()
+  }
+
+  fn unset_global(&mut self, name: &str) -> bool {
+    self.interpreter.unset_global(name)
+  }
+
+  fn globals_snapshot(&self) -> Self::Globals {
+    self.interpreter.globals_snapshot()
+  }
+
+  fn globals_diff(&self, before: &Self::Globals) -> Vec<String> {
+    self.interpreter.globals_diff(before)
+  }
+
+  fn set_clock(&mut self, clock: MockClock) {
+    self.interpreter.clock = Rc::new(clock);
+  }
+}
+
+impl LoxEngine for VM {
+  const NAME: &'static str = "vm";
+  type Globals = HashMap<String, rblox::Value>;
+
+  fn new() -> Self {
+    let mut vm = VM::new();
+    vm.capture_output();
+    vm.capture_warnings();
+    vm
+  }
+
+  fn run(&mut self, src: &str) -> Result<Output, Diagnostics> {
+    let (kind, messages) = match self.execute(src) {
+      Ok(()) => return Ok(self.take_output()),
+      Err(err) => err,
+    };
+
+    let stage = match kind {
+      ErrorType::RuntimeError => Stage::Runtime,
+      _ => Stage::Compile,
+    };
+    // a runtime error is followed by one line per stack frame
+    let messages = messages
+      .into_iter()
+      .enumerate()
+      .flat_map(|(i, message)| {
+        let level = if stage == Stage::Runtime && i > 0 { Level::Note } else { Level::Error };
+        diagnostics(level, message)
+      })
+      .collect();
+    Err(Diagnostics {
+      stage,
+      messages,
+      output: self.take_output(),
+    })
+  }
+
+  fn set_checked_arith(&mut self, on: bool) {
+    self.checked_arith = on;
+  }
+
+  fn check(&mut self, src: &str) -> Result<(), Diagnostics> {
+    VM::check(self, src).map_err(|messages| Diagnostics {
+      stage: Stage::Compile,
+      messages: messages.into_iter().flat_map(|message| diagnostics(Level::Error, message)).collect(),
+      output: Vec::new(),
+    })
+  }
+
+  fn take_warnings(&mut self) -> Vec<Diagnostic> {
+    VM::take_warnings(self).into_iter().flat_map(|warning| diagnostics(Level::Warning, warning)).collect()
+  }
+
+  fn stream_output(&mut self) {
+    self.print_output();
+  }
+
+  fn set_file(&mut self, path: &Path) {
+    self.options.file = Some(path.into());
+  }
+
+  fn set_quiet(&mut self, on: bool) {
+    self.quiet = on;
+  }
+
+  fn set_args(&mut self, args: &[String]) {
+    self.args = args.to_vec();
+  }
+
+  fn set_defines(&mut self, defines: &HashMap<String, String>) {
+    self.options.defines = defines.clone();
+  }
+
+  fn exit_code(&self) -> i32 {
+    VM::exit_code(self)
+  }
+
+  fn unset_global(&mut self, name: &str) -> bool {
+    VM::unset_global(self, name)
+  }
+
+  fn globals_snapshot(&self) -> Self::Globals {
+    VM::globals_snapshot(self)
+  }
+
+  fn globals_diff(&self, before: &Self::Globals) -> Vec<String> {
+    VM::globals_diff(self, before)
+  }
+
+  fn set_clock(&mut self, clock: MockClock) {
+    self.clock = Rc::new(clock);
+  }
+
+  fn set_optimize(&mut self, on: bool) {
+    self.options.no_inline = !on;
+    self.options.no_hoist = !on;
+  }
+}
+
+/// An error of the tree-walker without the position at `span` that it ends with.
+fn summary(err: &impl Display, span: Span) -> String {
+  let text = err.to_string();
+  let position = format!("; at position {span}");
+  text.strip_suffix(&position).unwrap_or(&text).to_string()
+}
+
+/// Converts a message of the VM, followed by its notes.
+fn diagnostics(level: Level, message: rblox::Message) -> Vec<Diagnostic> {
+  let mut diagnostic =
+    Diagnostic::new(level, message.text, message.span.0..message.span.1).with_summary(message.summary);
+  diagnostic.lint = message.lint.map(Into::into);
+  let diagnostic = diagnostic.with_labels(message.labels.iter().map(vm_label));
+  iter::once(diagnostic).chain(message.notes.into_iter().map(Diagnostic::note)).collect()
+}
+
+/// Runs `src` on a fresh engine and returns its output, panicking on any error.
+pub fn output_of<E: LoxEngine>(src: &str) -> Output {
+  E::new()
+    .run(src)
+    .unwrap_or_else(|err| panic!("[{}] unexpected {:?} error: {:?}", E::NAME, err.stage, err.messages))
+}
+
+/// Runs `src` on a fresh engine, panicking if it succeeds.
+pub fn error_of<E: LoxEngine>(src: &str) -> Diagnostics {
+  match E::new().run(src) {
+    Ok(output) => panic!("[{}] expected an error, got output {:?}", E::NAME, output),
+    Err(err) => err,
+  }
+}
+
+/// Declares generic test functions and instantiates each of them once per engine, so every
+/// program is run against both the tree-walker and the VM.
+///
+/// ```ignore
+/// parity_tests! {
+///   fn prints<E>() {
+///     assert_eq!(output_of::<E>("print 1;"), ["1"]);
+///   }
+/// }
+/// ```
+#[macro_export]
+macro_rules! parity_tests {
+  ($(fn $name:ident<$engine:ident>() $body:block)*) => {
+    $(
+      mod $name {
+        #[allow(unused_imports)]
+        use super::*;
+
+        fn check<$engine: $crate::LoxEngine>() $body
+
+        #[test]
+        fn tree() {
+          check::<$crate::TreeWalker>()
+        }
+
+        #[test]
+        fn vm() {
+          check::<$crate::VM>()
+        }
+      }
+    )*
+  };
+}
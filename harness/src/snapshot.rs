@@ -0,0 +1,91 @@
+//! Snapshot tests for diagnostics.
+//!
+//! Each program in a corpus directory is run on an engine and everything it reports is rendered
+//! to text: output, warnings, and errors with the offending source line underlined. The text is
+//! compared against `<program>.<engine>.snap` next to the program. Set `UPDATE_SNAPSHOTS=1` to
+//! write the snapshots instead, then review the diff.
+
+use std::{env, fmt::Write, fs, path::Path};
+
+use lox_common::render::render;
+
+use crate::{LoxEngine, Stage, MockClock};
+
+/// Runs `src` on a fresh engine and renders everything it reported. The clock is stopped at zero,
+/// so that programs reading it have stable snapshots.
+pub fn report<E: LoxEngine>(src: &str) -> String {
+  let mut engine = E::new();
+  engine.set_clock(MockClock::default());
+  let result = engine.run(src);
+  let mut out = String::new();
+
+  for warning in engine.take_warnings() {
+    render(&mut out, src, &warning);
+  }
+
+  let output = match result {
+    Ok(output) => output,
+    Err(err) => {
+      let stage = match err.stage {
+        Stage::Compile => "compile",
+        Stage::Runtime => "runtime",
+      };
+      writeln!(out, "-- {stage} error").unwrap();
+      for message in &err.messages {
+        render(&mut out, src, message);
+      }
+      err.output
+    }
+  };
+
+  if !output.is_empty() {
+    out += "-- output\n";
+    for line in output {
+      writeln!(out, "{line}").unwrap();
+    }
+  }
+  out
+}
+
+/// Checks every `.lox` program in `dir` against its snapshot, and panics listing the ones that
+/// changed.
+pub fn check_dir<E: LoxEngine>(dir: impl AsRef<Path>) {
+  let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+  let mut paths: Vec<_> = fs::read_dir(dir)
+    .unwrap()
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+    .collect();
+  paths.sort();
+
+  let mut failed = Vec::new();
+  for path in paths {
+    let src = fs::read_to_string(&path).unwrap();
+    let actual = report::<E>(&src);
+    let snap = path.with_extension(format!("{}.snap", E::NAME));
+
+    if update {
+      fs::write(&snap, &actual).unwrap();
+      continue;
+    }
+    match fs::read_to_string(&snap) {
+      Ok(expected) if expected == actual => {}
+      Ok(expected) => {
+        eprintln!("--- {}\n{expected}+++ actual\n{actual}", snap.display());
+        failed.push(snap);
+      }
+      Err(_) => {
+        eprintln!("--- {} is missing\n+++ actual\n{actual}", snap.display());
+        failed.push(snap);
+      }
+    }
+  }
+
+  assert!(
+    failed.is_empty(),
+    "[{}] {} snapshot(s) differ, rerun with UPDATE_SNAPSHOTS=1 to accept: {failed:?}",
+    E::NAME,
+    failed.len(),
+  );
+}
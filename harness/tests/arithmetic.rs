@@ -0,0 +1,118 @@
+use harness::{error_of, output_of, parity_tests, Stage};
+
+/// Source of one operand of each type, its type, and the text it joins a string as. Functions
+/// print differently in each engine, so they have no text to compare.
+const OPERANDS: [(&str, &str, Option<&str>); 7] = [
+  ("1.5", "number", Some("1.5")),
+  ("\"s\"", "string", Some("s")),
+  ("true", "boolean", Some("true")),
+  ("nil", "nil", Some("nil")),
+  ("f", "function", None),
+  ("A", "class", Some("<class A>")),
+  ("A()", "instance", Some("<instance A>")),
+];
+
+/// Multiplies until the result no longer fits in a double.
+const OVERFLOW: &str = "
+var x = 1;
+for (var i = 0; i < 400; i = i + 1) x = x * 10;
+print x;
+";
+
+parity_tests! {
+  fn process_arith<E>() {
+    assert_eq!(output_of::<E>("print 1+2-3*-4/(5-6);"), ["-9"]);
+  }
+
+  fn process_literals<E>() {
+    assert_eq!(output_of::<E>("print true; print false; print nil;"), ["true", "false", "nil"]);
+  }
+
+  fn process_types<E>() {
+    assert_eq!(output_of::<E>("print !(5 - 4 > 3 * 2 == !nil);"), ["true"]);
+  }
+
+  fn concat_strings<E>() {
+    assert_eq!(output_of::<E>("print \"st\" + \"ri\" + \"ng\";"), ["string"]);
+  }
+
+  fn add_coerces_when_either_side_is_a_string<E>() {
+    for (left, left_type, left_text) in OPERANDS {
+      for (right, right_type, right_text) in OPERANDS {
+        let src = format!("fun f() {{}} class A {{}} print {left} + {right};");
+        match (left_type, right_type) {
+          ("number", "number") => assert_eq!(output_of::<E>(&src), ["3"]),
+          ("string", _) | (_, "string") => {
+            let out = output_of::<E>(&src).remove(0);
+            match (left_text, right_text) {
+              (Some(left), Some(right)) => assert_eq!(out, format!("{left}{right}"), "{src}"),
+              (Some(left), None) => assert!(out.starts_with(&format!("{left}<")), "{src}: {out}"),
+              (None, Some(right)) => assert!(out.starts_with('<') && out.ends_with(right), "{src}: {out}"),
+              (None, None) => unreachable!(),
+            }
+          }
+          _ => {
+            let err = error_of::<E>(&src);
+            assert_eq!(err.stage, Stage::Runtime, "{src}");
+            assert!(err.messages[0].message.contains("a string and any value"), "{src}");
+          }
+        }
+      }
+    }
+  }
+
+  fn compare_strings<E>() {
+    let src = "
+      var b = \"b\";
+      print \"a\" < b;
+      print \"ab\" > \"a\" + \"a\";
+      print \"B\" < \"a\";
+      print b <= \"b\";
+      print \"\" >= \"a\";
+    ";
+    assert_eq!(output_of::<E>(src), ["true", "true", "true", "true", "false"]);
+  }
+
+  fn compare_mixed_types<E>() {
+    let err = error_of::<E>("print \"1\" < 2;");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("can only compare two numbers or two strings"));
+  }
+
+  fn negate_non_number<E>() {
+    assert_eq!(error_of::<E>("print -\"a\";").stage, Stage::Runtime);
+  }
+
+  fn missing_operand<E>() {
+    let err = error_of::<E>("print 1 +;");
+    assert_eq!(err.stage, Stage::Compile);
+    assert!(err.output.is_empty());
+  }
+
+  fn division_by_zero<E>() {
+    let err = error_of::<E>("print 2; print 1/0; print 3;");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert_eq!(err.output, ["2"]);
+  }
+
+  fn overflow_is_infinite<E>() {
+    assert_eq!(output_of::<E>(OVERFLOW), ["inf"]);
+  }
+
+  fn checked_overflow<E>() {
+    let mut engine = E::new();
+    engine.set_checked_arith(true);
+    let err = engine.run(OVERFLOW).unwrap_err();
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("inf"), "{:?}", err.messages);
+  }
+
+  fn checked_nan<E>() {
+    let mut engine = E::new();
+    engine.set_checked_arith(true);
+    // `0 / 0` is a division by zero, so start from the `NaN` literal
+    let err = engine.run("var n = NaN; print n + 1;").unwrap_err();
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("`NaN` is not a finite number"), "{:?}", err.messages);
+  }
+}
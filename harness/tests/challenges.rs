@@ -0,0 +1,41 @@
+use harness::{output_of, parity_tests};
+
+parity_tests! {
+  fn challenge_17_1<E>() {
+    assert_eq!(output_of::<E>("print (-1+2)*3--4;"), ["7"]);
+  }
+
+  fn closure_counter<E>() {
+    let src = "
+      fun makeCounter() {
+        var i = 0;
+        fun count() {
+          i = i + 1;
+          return i;
+        }
+        return count;
+      }
+      var counter = makeCounter();
+      print counter();
+      print counter();
+    ";
+    assert_eq!(output_of::<E>(src), ["1", "2"]);
+  }
+
+  fn recursion<E>() {
+    let src = "
+      fun fib(n) {
+        if (n < 2) return n;
+        return fib(n - 2) + fib(n - 1);
+      }
+      print fib(10);
+    ";
+    assert_eq!(output_of::<E>(src), ["55"]);
+  }
+
+  fn globals_persist_between_runs<E>() {
+    let mut engine = E::new();
+    assert_eq!(engine.run("var a = 1;"), Ok(vec![]));
+    assert_eq!(engine.run("print a + 1;"), Ok(vec!["2".to_string()]));
+  }
+}
@@ -0,0 +1,142 @@
+use harness::{error_of, output_of, parity_tests, Stage};
+
+parity_tests! {
+  fn fields_and_methods<E>() {
+    let src = "
+      class Counter {
+        init(start) { this.count = start; }
+        bump() { this.count = this.count + 1; return this; }
+      }
+      var c = Counter(1);
+      c.bump().bump();
+      print c.count;
+      print c;
+      print Counter;
+    ";
+    assert_eq!(output_of::<E>(src), ["3", "<instance Counter>", "<class Counter>"]);
+  }
+
+  fn fields_shadow_methods<E>() {
+    let src = "
+      class A { name() { return \"method\"; } }
+      var a = A();
+      print a.name();
+      a.name = \"field\";
+      print a.name;
+    ";
+    assert_eq!(output_of::<E>(src), ["method", "field"]);
+  }
+
+  fn bound_methods_keep_their_instance<E>() {
+    let src = "
+      class Greeter {
+        init(name) { this.name = name; }
+        greet() { return \"hi \" + this.name; }
+      }
+      var greet = Greeter(\"bob\").greet;
+      print greet();
+      fun later() { return greet; }
+      print later()();
+    ";
+    assert_eq!(output_of::<E>(src), ["hi bob", "hi bob"]);
+  }
+
+  fn closures_capture_this<E>() {
+    let src = "
+      class Box {
+        init(value) { this.value = value; }
+        getter() {
+          fun get() { return this.value; }
+          return get;
+        }
+      }
+      print Box(7).getter()();
+    ";
+    assert_eq!(output_of::<E>(src), ["7"]);
+  }
+
+  fn initializer_returns_this<E>() {
+    let src = "
+      class A {
+        init() { this.x = 1; return; }
+      }
+      var a = A();
+      a.x = 2;
+      print a.init().x;
+      print a.x;
+    ";
+    assert_eq!(output_of::<E>(src), ["1", "1"]);
+  }
+
+  fn subclasses_inherit_and_override<E>() {
+    let src = "
+      class Animal {
+        init(name) { this.name = name; }
+        speak() { return this.name + \" makes a sound\"; }
+        kind() { return \"animal\"; }
+      }
+      class Dog < Animal {
+        speak() { return super.speak() + \", woof\"; }
+      }
+      var d = Dog(\"rex\");
+      print d.speak();
+      print d.kind();
+    ";
+    assert_eq!(output_of::<E>(src), ["rex makes a sound, woof", "animal"]);
+  }
+
+  fn super_skips_overrides_in_between<E>() {
+    let src = "
+      class A { name() { return \"A\"; } }
+      class B < A { name() { return \"B\"; } test() { return super.name(); } }
+      class C < B {}
+      print C().test();
+    ";
+    assert_eq!(output_of::<E>(src), ["A"]);
+  }
+
+  fn classes_in_local_scopes<E>() {
+    let src = "
+      fun make() {
+        class Base { get() { return 1; } }
+        class Local < Base { get() { return super.get() + 1; } }
+        return Local();
+      }
+      print make().get();
+    ";
+    assert_eq!(output_of::<E>(src), ["2"]);
+  }
+
+  fn constructor_arity_is_checked<E>() {
+    let err = error_of::<E>("class A { init(a, b) {} } A(1);");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("Expected 2 arguments, but got 1"));
+
+    let err = error_of::<E>("class A {} A(1);");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("Expected 0 arguments, but got 1"));
+  }
+
+  fn property_errors<E>() {
+    let err = error_of::<E>("var x = 1; print x.y;");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("Only objects can have properties"));
+
+    let err = error_of::<E>("class A {} print A().missing;");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("Undefined property `missing`"));
+  }
+
+  fn superclass_must_be_a_class<E>() {
+    let err = error_of::<E>("var NotAClass = 1; class A < NotAClass {}");
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.messages[0].message.contains("Superclass must be a class"));
+  }
+
+  fn illegal_this_and_super<E>() {
+    assert_eq!(error_of::<E>("print this;").stage, Stage::Compile);
+    assert_eq!(error_of::<E>("fun f() { return super.g(); }").stage, Stage::Compile);
+    assert_eq!(error_of::<E>("class A { f() { return super.f(); } }").stage, Stage::Compile);
+    assert_eq!(error_of::<E>("class A < A {}").stage, Stage::Compile);
+  }
+}
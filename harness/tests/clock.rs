@@ -0,0 +1,55 @@
+use harness::{output_of, parity_tests, MockClock};
+
+parity_tests! {
+  fn clock_reads_the_installed_clock<E>() {
+    let clock = MockClock::new(100.0);
+    let mut engine = E::new();
+    engine.set_clock(clock.clone());
+    engine.run("var start = clock();").unwrap();
+
+    clock.advance(1.5);
+    assert_eq!(engine.run("print clock() - start;").unwrap(), ["1.5"]);
+    clock.set(0.0);
+    assert_eq!(engine.run("print clock();").unwrap(), ["0"]);
+  }
+}
+
+parity_tests! {
+  fn time_blocks_print_the_time_taken<E>() {
+    let clock = MockClock::new(100.0);
+    let mut engine = E::new();
+    engine.set_clock(clock.clone());
+    let output = engine.run("time { var a = 1; print a + 1; }").unwrap();
+    assert_eq!(output[0], "2");
+    assert!(output[1].starts_with("time: 0.000 ms"), "{output:?}");
+  }
+
+  fn time_blocks_left_early_print_nothing<E>() {
+    let src = "
+      for (var i = 0; i < 3; i = i + 1) {
+        time { var a = i; if (a == 0) continue; if (a == 2) break; print a; }
+      }
+      fun f() { time { return 1; } }
+      print f();
+    ";
+    let output = output_of::<E>(src);
+    assert_eq!(output.len(), 3, "{output:?}");
+    assert_eq!(output[0], "1");
+    assert!(output[1].starts_with("time: "), "{output:?}");
+    assert_eq!(output[2], "1");
+  }
+  fn time_is_still_a_name_outside_time_blocks<E>() {
+    let src = "
+      var time = 1;
+      fun elapsed(time) { return time + 1; }
+      time = elapsed(time);
+      print time;
+      class Clock { time() { return \"now\"; } }
+      print Clock().time();
+      time /* a block follows */ { print time; }
+    ";
+    let output = output_of::<E>(src);
+    assert_eq!(output[..3], ["2", "now", "2"], "{output:?}");
+    assert!(output[3].starts_with("time: "), "{output:?}");
+  }
+}
@@ -0,0 +1,60 @@
+use harness::{output_of, parity_tests};
+
+/// Declares the values compared below, so that each name refers to one object.
+const SETUP: &str = "
+fun f() {}
+class A { m() {} }
+var a = A();
+";
+
+/// Expressions that are each equal only to themselves.
+const VALUES: [&str; 11] = ["nil", "true", "false", "0", "1", "\"\"", "\"1\"", "f", "A", "a", "clock"];
+
+/// Multiplies past the largest double and subtracts the result from itself.
+const NAN: &str = "
+var inf = 1;
+for (var i = 0; i < 400; i = i + 1) inf = inf * 10;
+var nan = inf - inf;
+";
+
+parity_tests! {
+  fn equality_matrix<E>() {
+    for (i, left) in VALUES.iter().enumerate() {
+      let mut src = SETUP.to_string();
+      for right in VALUES {
+        src += &format!("print {left} == {right}; print {left} != {right};");
+      }
+      let expected: Vec<_> = (0..VALUES.len())
+        .flat_map(|j| [(i == j).to_string(), (i != j).to_string()])
+        .collect();
+      assert_eq!(output_of::<E>(&src), expected, "{left}");
+    }
+  }
+
+  fn strings_compare_by_value<E>() {
+    let src = "var s = \"ab\"; print \"a\" + \"b\" == s; print s != \"a\" + \"b\";";
+    assert_eq!(output_of::<E>(src), ["true", "false"]);
+  }
+
+  fn objects_compare_by_identity<E>() {
+    let src = format!("{SETUP}
+      print A() == A();
+      fun make() {{ fun g() {{}} return g; }}
+      print make() == make();
+      var g = make();
+      print g == g;
+      print a.m == a.m;
+      print a.m == A().m;
+    ");
+    assert_eq!(output_of::<E>(&src), ["false", "false", "true", "true", "false"]);
+  }
+
+  fn nan_is_never_equal<E>() {
+    let src = format!("{NAN} print nan == nan; print nan != nan; var n = nan; print n == nan;");
+    assert_eq!(output_of::<E>(&src), ["false", "true", "false"]);
+  }
+
+  fn zeroes_are_equal<E>() {
+    assert_eq!(output_of::<E>("print 0 == -0; print -0 != 0;"), ["true", "false"]);
+  }
+}
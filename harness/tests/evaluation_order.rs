@@ -0,0 +1,99 @@
+//! The order in which both engines evaluate expressions. Programs may rely on it, so every
+//! optimization has to keep it:
+//!
+//! - Operands of a binary operator are evaluated left to right, and the operator is applied,
+//!   and may fail, only once both are.
+//! - `and` and `or` evaluate their left operand first, and the right one only if it decides the
+//!   result.
+//! - A call evaluates the callee, then its arguments left to right, and only then checks that
+//!   the callee can be called with them.
+//! - An assignment evaluates its value before it looks up the variable, so assigning to an
+//!   undefined variable fails after the value's side effects. Chained assignments assign right
+//!   to left.
+//! - The operands of a comma sequence are evaluated left to right.
+
+use harness::{error_of, parity_tests, LoxEngine, Output, Stage};
+
+/// Functions that print their name when called, so that the output shows the order of calls.
+const TRACED: &str = "
+fun a() { print \"a\"; return 1; }
+fun b() { print \"b\"; return 2; }
+fun c() { print \"c\"; return 3; }
+fun s() { print \"s\"; return \"s\"; }
+";
+
+/// Runs `src` after [`TRACED`], with optimizations on and off, and returns its output, which
+/// must not depend on them.
+fn traced<E: LoxEngine>(src: &str) -> Output {
+  let src = format!("{TRACED}{src}");
+  let run = |optimize| {
+    let mut engine = E::new();
+    engine.set_optimize(optimize);
+    engine.run(&src).unwrap_or_else(|err| panic!("[{}] unexpected error: {:?}", E::NAME, err.messages))
+  };
+  let output = run(true);
+  assert_eq!(output, run(false), "[{}] optimizations changed the output", E::NAME);
+  output
+}
+
+parity_tests! {
+  fn binary_operands_left_to_right<E>() {
+    assert_eq!(traced::<E>("print a() + b();"), ["a", "b", "3"]);
+    assert_eq!(traced::<E>("print b() - a() * c();"), ["b", "a", "c", "-1"]);
+    assert_eq!(traced::<E>("print a() >= b();"), ["a", "b", "false"]);
+    assert_eq!(traced::<E>("print b() == a();"), ["b", "a", "false"]);
+    assert_eq!(traced::<E>("print s() + a();"), ["s", "a", "s1"]);
+  }
+
+  fn failing_operator_runs_both_operands<E>() {
+    let err = error_of::<E>(&format!("{TRACED} print s() - a(); c();"));
+    assert_eq!(err.stage, Stage::Runtime);
+    assert_eq!(err.output, ["s", "a"]);
+  }
+
+  fn logical_operators_short_circuit<E>() {
+    assert_eq!(traced::<E>("print a() or b();"), ["a", "1"]);
+    assert_eq!(traced::<E>("print nil and a();"), ["nil"]);
+    assert_eq!(traced::<E>("print a() and b() and c();"), ["a", "b", "c", "3"]);
+  }
+
+  fn arguments_left_to_right<E>() {
+    let src = "fun sub(x, y) { return x - y; } fun add(x, y) { return x + y; }";
+    assert_eq!(traced::<E>(&format!("{src} print sub(a(), b());")), ["a", "b", "-1"]);
+    // a call small enough to be inlined keeps the order
+    assert_eq!(traced::<E>(&format!("{src} print add(c(), sub(b(), a()));")), ["c", "b", "a", "4"]);
+  }
+
+  fn callee_before_arguments<E>() {
+    let err = error_of::<E>(&format!("{TRACED} missing(a());"));
+    assert_eq!(err.stage, Stage::Runtime);
+    assert!(err.output.is_empty());
+
+    let err = error_of::<E>(&format!("{TRACED} var n = 1; n(a());"));
+    assert_eq!(err.output, ["a"]);
+  }
+
+  fn arity_checked_after_arguments<E>() {
+    let err = error_of::<E>(&format!("{TRACED} fun one(x) {{ return x; }} one(a(), b());"));
+    assert_eq!(err.stage, Stage::Runtime);
+    assert_eq!(err.output, ["a", "b"]);
+  }
+
+  fn assignment_evaluates_value_first<E>() {
+    let err = error_of::<E>(&format!("{TRACED} missing = a();"));
+    assert_eq!(err.stage, Stage::Runtime);
+    assert_eq!(err.output, ["a"]);
+
+    assert_eq!(traced::<E>("var x; var y; x = y = b(); print x + y;"), ["b", "4"]);
+  }
+
+  fn sequence_left_to_right<E>() {
+    assert_eq!(traced::<E>("print (a(), b(), c());"), ["a", "b", "c", "3"]);
+  }
+
+  fn loops_see_values_in_order<E>() {
+    let src = "var limit = 3; var n = 0; fun step() { print n; return 1; }
+while (n < limit) n = n + step();";
+    assert_eq!(traced::<E>(src), ["0", "1", "2"]);
+  }
+}
@@ -0,0 +1,32 @@
+use harness::{parity_tests, Stage};
+
+parity_tests! {
+  fn unset_global_is_undefined_afterwards<E>() {
+    let mut engine = E::new();
+    engine.run("var x = 1; fun f() { return x; }").unwrap();
+    assert!(engine.unset_global("x"));
+    assert!(!engine.unset_global("x"));
+    assert_eq!(engine.run("print f();").unwrap_err().stage, Stage::Runtime);
+    assert_eq!(engine.run("x = 2;").unwrap_err().stage, Stage::Runtime);
+    assert_eq!(engine.run("print 3;").unwrap(), ["3"]);
+  }
+
+  fn functions_see_a_global_defined_again<E>() {
+    let mut engine = E::new();
+    engine.run("var x = 1; fun f() { return x; }").unwrap();
+    engine.unset_global("x");
+    assert_eq!(engine.run("var x = \"again\"; print f();").unwrap(), ["again"]);
+  }
+
+  fn globals_diff_lists_added_changed_and_removed_globals<E>() {
+    let mut engine = E::new();
+    engine.run("var x = 1; var y = \"y\"; var z; fun f() {}").unwrap();
+    let before = engine.globals_snapshot();
+    engine.run("x = 2; y = \"y\"; var w = true; fun f() {}").unwrap();
+    engine.unset_global("z");
+    let diff = engine.globals_diff(&before);
+    // a function declared again is another function, which each engine prints its own way
+    assert!(diff[0].starts_with("~ f = "), "{diff:?}");
+    assert_eq!(diff[1..], ["+ w = true", "~ x = 2", "- z"]);
+  }
+}
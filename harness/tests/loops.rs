@@ -0,0 +1,75 @@
+use harness::{error_of, output_of, parity_tests, Stage};
+
+parity_tests! {
+  fn break_leaves_the_loop<E>() {
+    let src = "
+      var i = 0;
+      while (true) {
+        if (i == 3) break;
+        print i;
+        i = i + 1;
+      }
+      for (;;) { print \"once\"; break; }
+      print \"done\";
+    ";
+    assert_eq!(output_of::<E>(src), ["0", "1", "2", "once", "done"]);
+  }
+
+  fn continue_runs_the_increment<E>() {
+    let src = "
+      for (var i = 0; i < 5; i = i + 1) {
+        if (i == 1 or i == 3) continue;
+        print i;
+      }
+      var j = 0;
+      while (j < 4) {
+        j = j + 1;
+        if (j == 2) continue;
+        print j;
+      }
+    ";
+    assert_eq!(output_of::<E>(src), ["0", "2", "4", "1", "3", "4"]);
+  }
+
+  fn only_the_innermost_loop_is_left<E>() {
+    let src = "
+      for (var i = 0; i < 3; i = i + 1) {
+        for (var j = 0; j < 3; j = j + 1) {
+          if (j == 1) continue;
+          if (j > i) break;
+          print i + j;
+        }
+      }
+    ";
+    assert_eq!(output_of::<E>(src), ["0", "1", "2", "4"]);
+  }
+
+  fn locals_of_the_body_are_dropped<E>() {
+    let src = "
+      {
+        var outer = \"outer\";
+        var last;
+        for (var i = 0; i < 3; i = i + 1) {
+          var a = i;
+          fun get() { return a; }
+          last = get;
+          var b = a * 2;
+          if (b == 2) break;
+          { var c = b; continue; }
+        }
+        print outer;
+        print last();
+      }
+    ";
+    assert_eq!(output_of::<E>(src), ["outer", "1"]);
+  }
+
+  fn break_and_continue_need_a_loop<E>() {
+    assert_eq!(error_of::<E>("break;").stage, Stage::Compile);
+    assert_eq!(error_of::<E>("if (true) continue;").stage, Stage::Compile);
+    assert_eq!(error_of::<E>("while (true) { fun f() { break; } }").stage, Stage::Compile);
+
+    let err = error_of::<E>("{ continue; }");
+    assert!(err.messages[0].message.contains("Can't use `continue` outside of a loop"));
+  }
+}
@@ -0,0 +1,23 @@
+use harness::{output_of, parity_tests};
+
+parity_tests! {
+  fn process_reads_the_script_arguments<E>() {
+    let mut engine = E::new();
+    engine.set_args(&["one".into(), "two".into()]);
+    let src = "for (var i = 0; i <= process.argc(); i = i + 1) print process.arg(i);";
+    assert_eq!(engine.run(src).unwrap(), ["one", "two", "nil"]);
+    assert_eq!(output_of::<E>("print process.argc();"), ["0"]);
+  }
+
+  fn process_reads_the_environment<E>() {
+    let src = "print process.getenv(\"CARGO_PKG_NAME\"); print process.getenv(\"LOX_UNSET_VARIABLE\");";
+    assert_eq!(output_of::<E>(src), ["harness", "nil"]);
+  }
+
+  fn process_sets_the_exit_code<E>() {
+    let mut engine = E::new();
+    assert_eq!(engine.exit_code(), 0);
+    engine.run("process.setExitCode(3);").unwrap();
+    assert_eq!(engine.exit_code(), 3);
+  }
+}
@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use harness::{output_of, parity_tests};
+
+/// Logs through a helper, which reports where it was called from. Line 1 is blank.
+const LOG: &str = "
+fun log(msg) {
+  print callerInfo() + \" \" + msg;
+}
+fun main() {
+  log(\"main\");
+}
+class A {
+  m() { log(\"method\"); }
+}
+main();
+A().m();
+log(\"top\");
+print callerInfo();
+";
+
+parity_tests! {
+  fn line_expands_to_the_line_it_is_on<E>() {
+    assert_eq!(output_of::<E>("print __line__;\n\nprint __line__ +\n  __line__;"), ["1", "7"]);
+  }
+
+  fn file_expands_to_the_script_path<E>() {
+    assert_eq!(output_of::<E>("print __file__;"), ["nil"]);
+    let mut engine = E::new();
+    engine.set_file(Path::new("scripts/main.lox"));
+    assert_eq!(engine.run("print __file__;").unwrap(), ["scripts/main.lox"]);
+  }
+
+  fn caller_info_names_the_caller_and_line<E>() {
+    let expected = ["main:6 main", "m:9 method", "script:13 top", "nil"];
+    assert_eq!(output_of::<E>(LOG), expected);
+  }
+}
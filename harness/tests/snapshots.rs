@@ -0,0 +1,7 @@
+use harness::{parity_tests, snapshot::check_dir};
+
+parity_tests! {
+  fn diagnostics<E>() {
+    check_dir::<E>(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/diagnostics"));
+  }
+}
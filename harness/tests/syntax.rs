@@ -0,0 +1,96 @@
+use harness::{error_of, output_of, parity_tests, Stage};
+
+parity_tests! {
+  fn trailing_comma_in_arguments<E>() {
+    let src = "
+      fun add(a, b) { return a + b; }
+      print add(1, 2,);
+    ";
+    assert_eq!(output_of::<E>(src), ["3"]);
+  }
+
+  fn trailing_comma_in_parameters<E>() {
+    let src = "
+      fun add(a, b,) { return a + b; }
+      print add(1, 2);
+    ";
+    assert_eq!(output_of::<E>(src), ["3"]);
+  }
+
+  fn lone_comma_is_not_an_argument_list<E>() {
+    let src = "
+      fun f() {}
+      f(,);
+    ";
+    assert_eq!(error_of::<E>(src).stage, Stage::Compile);
+  }
+
+  fn sequence_in_parentheses<E>() {
+    assert_eq!(output_of::<E>("print (1, 2, 3);"), ["3"]);
+    assert_eq!(output_of::<E>("print (1, -2);"), ["-2"]);
+  }
+
+  fn sequence_in_expression_statement<E>() {
+    let src = "
+      var a = 0;
+      var b = 0;
+      a = 1, b = 2;
+      print a + b;
+    ";
+    assert_eq!(output_of::<E>(src), ["3"]);
+  }
+
+  fn sequence_in_for_increment<E>() {
+    let src = "
+      var j = 0;
+      for (var i = 0; i < 3; i = i + 1, j = j + 2) {}
+      print j;
+    ";
+    assert_eq!(output_of::<E>(src), ["6"]);
+  }
+
+  fn no_sequence_in_print<E>() {
+    assert_eq!(error_of::<E>("print 1, 2;").stage, Stage::Compile);
+  }
+
+  fn no_sequence_in_initializer<E>() {
+    assert_eq!(error_of::<E>("var a = 1, 2;").stage, Stage::Compile);
+  }
+
+  fn recovery_stops_at_statement_keyword<E>() {
+    // the missing `;` is reported once, and parsing resumes at `print`
+    let errors = error_of::<E>("var a = 1 print a; var b = ;").messages;
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(errors[0].summary.contains("Expected `;` after variable declaration"), "{errors:?}");
+    assert!(errors[1].summary.contains("unexpected token `;`"), "{errors:?}");
+  }
+
+  fn recovery_skips_past_semicolon<E>() {
+    let errors = error_of::<E>("1 + ; print 2; 3 + ;").messages;
+    assert_eq!(errors.len(), 2, "{errors:?}");
+  }
+
+  fn recovery_stops_at_closing_brace<E>() {
+    // the block is closed by its own `}`, so `print` is not read as part of the function
+    let errors = error_of::<E>("fun f() { return 1 }\nprint 2;").messages;
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(errors[0].summary.starts_with("Expected `;` after return"), "{errors:?}");
+  }
+
+  fn recovery_leaves_the_enclosing_loop<E>() {
+    let errors = error_of::<E>("while (true) { fun f() { continue; } break; }").messages;
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(errors[0].summary.contains("`continue` outside of a loop"), "{errors:?}");
+  }
+
+  fn recovery_skips_a_stray_closing_brace<E>() {
+    let errors = error_of::<E>("} print 1; }").messages;
+    assert_eq!(errors.len(), 2, "{errors:?}");
+  }
+
+  fn reports_missing_left_operand<E>() {
+    let errors = error_of::<E>("print * 2;").messages;
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(errors[0].summary.starts_with("Expected left operand before `*`"), "{errors:?}");
+  }
+}
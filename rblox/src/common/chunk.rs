@@ -1,15 +1,37 @@
-use std::{fmt::Display, iter::Zip, slice::Iter};
+use std::{collections::HashMap, fmt::Display, iter::Zip, ops::Range, slice::Iter};
 
-use crate::common::{Ins, Span};
+use crate::common::{data::LoxObject, Ins, Span, Value};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Chunk {
   pub name: String,
   pub code: Vec<Ins>,
+  /// Values loaded by `Ins::Constant`, each stored once per chunk.
+  pub constants: Vec<Value>,
+  /// Slots of the constants that can be shared, by what makes them the same constant.
+  shared: HashMap<ConstantKey, usize>,
+  /// How many of `constants` are in `shared`. Constants set directly, as loading an image does on
+  /// a fresh chunk, are indexed by the next `add_constant`.
+  indexed: usize,
   spans: Vec<Span>,
+  /// How many jumps land on each offset, see [`Chunk::jumps_to`].
+  landings: HashMap<usize, usize>,
+  /// Calls compiled to the body of the function they called, innermost first where they nest.
+  pub inlined: Vec<Inlined>,
   // lines: Vec<(usize, u32)>
 }
 
+/// A call that was replaced with the body of the function it called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inlined {
+  /// Offsets of the body in the chunk.
+  pub code: Range<usize>,
+  /// Name of the function called.
+  pub name: String,
+  /// Span of the call.
+  pub span: Span,
+}
+
 impl Chunk {
   pub fn new(name: impl Into<String>) -> Self {
     // let mut lines = Vec::new();
@@ -17,17 +39,95 @@ impl Chunk {
     Self {
       name: name.into(),
       code: Vec::new(),
+      constants: Vec::new(),
+      shared: HashMap::new(),
+      indexed: 0,
       spans: Vec::new(),
+      landings: HashMap::new(),
+      inlined: Vec::new(),
       // lines
     }
   }
 
   /// Write an instruction to the chunk
   pub fn write(&mut self, ins: Ins, span: Span) {
+    land(&mut self.landings, self.code.len(), &ins, 1);
     self.code.push(ins);
     self.spans.push(span);
   }
 
+  /// Adds `value` to the constant table, reusing the slot of an identical constant, and returns
+  /// its index.
+  pub fn add_constant(&mut self, value: Value) -> usize {
+    for (idx, constant) in self.constants.iter().enumerate().skip(self.indexed) {
+      if let Some(key) = ConstantKey::of(constant) {
+        self.shared.entry(key).or_insert(idx);
+      }
+    }
+
+    let idx = self.constants.len();
+    let idx = match ConstantKey::of(&value) {
+      Some(key) => *self.shared.entry(key).or_insert(idx),
+      None => idx,
+    };
+    if idx == self.constants.len() {
+      self.constants.push(value);
+    }
+    self.indexed = self.constants.len();
+    idx
+  }
+
+  /// Write an instruction loading `value` to the chunk
+  pub fn write_constant(&mut self, value: Value, span: Span) {
+    let idx = self.add_constant(value);
+    self.write(Ins::Constant(idx), span);
+  }
+
+  /// Removes the instruction at `offset`. Jumps over it are not adjusted.
+  pub fn remove(&mut self, offset: usize) -> Ins {
+    for call in self.inlined.iter_mut().filter(|call| call.code.start > offset) {
+      call.code = call.code.start - 1..call.code.end - 1;
+    }
+    self.spans.remove(offset);
+    self.land_from(offset, -1);
+    let ins = self.code.remove(offset);
+    self.land_from(offset, 1);
+    ins
+  }
+
+  /// Inserts `code` before the instruction at `offset`. Jumps over it are not adjusted.
+  pub fn insert(&mut self, offset: usize, code: Vec<(Ins, Span)>) {
+    let n = code.len();
+    for call in self.inlined.iter_mut().filter(|call| call.code.start >= offset) {
+      call.code = call.code.start + n..call.code.end + n;
+    }
+    let (code, spans): (Vec<_>, Vec<_>) = code.into_iter().unzip();
+    self.land_from(offset, -1);
+    self.code.splice(offset..offset, code);
+    self.land_from(offset, 1);
+    self.spans.splice(offset..offset, spans);
+  }
+
+  /// Replaces the instruction at `offset` with `ins`, as when a jump is patched.
+  pub fn replace(&mut self, offset: usize, ins: Ins) {
+    land(&mut self.landings, offset, &self.code[offset], -1);
+    land(&mut self.landings, offset, &ins, 1);
+    self.code[offset] = ins;
+  }
+
+  /// Whether a jump already written lands on `offset`.
+  pub fn jumps_to(&self, offset: usize) -> bool {
+    self.landings.contains_key(&offset)
+  }
+
+  /// Adds `n` to the landings of the jumps from `offset` on, which move when code before them
+  /// is inserted or removed.
+  fn land_from(&mut self, offset: usize, n: isize) {
+    for (at, ins) in self.code.iter().enumerate().skip(offset) {
+      land(&mut self.landings, at, ins, n);
+    }
+  }
+
   pub fn get(&self, offset: usize) -> Option<(&Ins, &Span)> {
     if offset >= self.len() {
       return None
@@ -44,7 +144,7 @@ impl Chunk {
   //   self.lines[line].1
   // }
 
-  pub fn _iter_zip(&self) -> Zip<Iter<Ins>, Iter<Span>> {
+  pub fn iter_zip(&self) -> Zip<Iter<'_, Ins>, Iter<'_, Span>> {
     self.code.iter().zip(self.spans.iter())
   }
 
@@ -52,6 +152,68 @@ impl Chunk {
     self.code.len()
   }
 
+  /// Disassembly of `ins`, with the value of a constant next to its index.
+  pub fn describe(&self, ins: &Ins) -> String {
+    match ins {
+      Ins::Constant(idx) => match self.constants.get(*idx) {
+        Some(val) => format!("{ins:?} ({val:?})"),
+        None => format!("{ins:?} (missing)"),
+      },
+      ins => format!("{ins:?}"),
+    }
+  }
+}
+
+/// Adds `n` to the jumps landing where `ins`, at `at`, lands if it is a jump.
+fn land(landings: &mut HashMap<usize, usize>, at: usize, ins: &Ins, n: isize) {
+  let (Ins::Jump(jump) | Ins::JumpIfFalse(jump) | Ins::JumpIfTrue(jump)) = ins else {
+    return
+  };
+  let Ok(target) = usize::try_from(at as isize + 1 + jump) else {
+    return
+  };
+  let count = landings.entry(target).or_default();
+  *count = count.saturating_add_signed(n);
+  if *count == 0 {
+    landings.remove(&target);
+  }
+}
+
+impl PartialEq for Chunk {
+  fn eq(&self, other: &Self) -> bool {
+    self.name == other.name
+      && self.code == other.code
+      && self.constants == other.constants
+      && self.spans == other.spans
+      && self.inlined == other.inlined
+  }
+}
+
+/// What two constants that can share a slot have in common. Numbers are keyed by their bits, so
+/// `0` and `-0` stay apart and a `NaN` is reused.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum ConstantKey {
+  Boolean(bool),
+  Nil,
+  Number(u64),
+  String(String),
+  Symbol(String),
+}
+
+impl ConstantKey {
+  /// The key of `value`, or `None` for objects that always get a slot of their own.
+  fn of(value: &Value) -> Option<Self> {
+    Some(match value {
+      Value::Boolean(b) => Self::Boolean(*b),
+      Value::Nil => Self::Nil,
+      Value::Number(n) => Self::Number(n.to_bits()),
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::String(s) => Self::String(s.clone()),
+        LoxObject::Symbol(s) => Self::Symbol(s.clone()),
+        _ => return None,
+      },
+    })
+  }
 }
 
 // TODO: improve disassembly of OP_CLOSURE
@@ -67,7 +229,7 @@ impl Display for Chunk {
       } else {
         f.write_str("  .")?;
       }
-      writeln!(f, " | {ins:?}")?;
+      writeln!(f, " | {}", self.describe(ins))?;
     }
     Ok(())
   }
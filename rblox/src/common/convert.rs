@@ -0,0 +1,81 @@
+//! Conversions between Rust values and Lox values, so that natives and hosts need not match on
+//! `Value` themselves. The conversions are shared with the tree-walker through
+//! [`lox_common::convert`]; these traits fix them to `Value`.
+
+use lox_common::convert;
+
+use crate::common::{data::LoxObject, Value};
+
+/// A Rust value that can be handed to Lox code. Implement [`convert::ToLox<Value>`] to add one.
+pub trait ToLox {
+  fn to_lox(self) -> Value;
+}
+
+/// A Rust value that can be read from Lox code. Implement [`convert::FromLox<Value>`] to add one.
+pub trait FromLox: Sized {
+  /// Name of the expected Lox type, for error messages.
+  const TYPE: &'static str;
+
+  /// Returns the Rust value, or `None` if `value` does not hold one.
+  fn from_lox(value: &Value) -> Option<Self>;
+}
+
+impl<T: convert::ToLox<Value>> ToLox for T {
+  fn to_lox(self) -> Value {
+    convert::ToLox::to_lox(self)
+  }
+}
+
+impl<T: convert::FromLox<Value>> FromLox for T {
+  const TYPE: &'static str = <T as convert::FromLox<Value>>::TYPE;
+
+  fn from_lox(value: &Value) -> Option<Self> {
+    convert::FromLox::from_lox(value)
+  }
+}
+
+impl convert::Value for Value {
+  fn nil() -> Self {
+    Value::Nil
+  }
+
+  fn boolean(b: bool) -> Self {
+    Value::Boolean(b)
+  }
+
+  fn number(n: f64) -> Self {
+    Value::Number(n)
+  }
+
+  fn string(s: String) -> Self {
+    LoxObject::String(s).into()
+  }
+
+  fn is_nil(&self) -> bool {
+    matches!(self, Value::Nil)
+  }
+
+  fn as_boolean(&self) -> Option<bool> {
+    match self {
+      Value::Boolean(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  fn as_number(&self) -> Option<f64> {
+    match self {
+      Value::Number(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  fn as_string(&self) -> Option<&str> {
+    match self {
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::String(s) => Some(s),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+}
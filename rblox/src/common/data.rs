@@ -1,16 +1,15 @@
-use std::{cell::RefCell, fmt::{Debug, Display}, mem, rc::Rc};
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt::{Debug, Display}, mem, ptr, rc::Rc};
 
 use crate::{
   common::{
     Chunk, 
-    error::ErrorLevel,
     Span,
     Value
   },
   compiler::{
     parser::error::ParseError,
     scanner::token::{Token, TokenType}
-  }, vm::error::RuntimeError
+  }, vm::{error::RuntimeError, VM}
 };
 
 pub trait Push<T> {
@@ -21,9 +20,14 @@ pub trait Push<T> {
 pub enum LoxObject {
   Identifier(String),
   String(String),
+  Symbol(String),
   Function(String, usize),
   Native(String, usize),
-  Closure(String, usize)
+  Closure(String, usize),
+  Class(Rc<LoxClass>),
+  Instance(Rc<LoxInstance>),
+  BoundMethod(BoundMethod),
+  Foreign(Foreign)
 }
 
 impl LoxObject {
@@ -33,10 +37,12 @@ impl LoxObject {
     match self {
       Identifier(_) => "<ident>",
       String(_) => "string",
-      Function(_, _) | Closure(_, _) => "<func>",
+      Symbol(_) => "symbol",
+      Function(_, _) | Closure(_, _) | BoundMethod(_) => "<func>",
       Native(_, _) => "<native fn>",
-      // Class(_) => "<class>",
-      // Object(_) => "<instance>",
+      Foreign(_) => "<foreign>",
+      Class(_) => "<class>",
+      Instance(_) => "<instance>",
     }
   }
 
@@ -45,10 +51,15 @@ impl LoxObject {
     match self {
       Identifier(s) | 
       String(s) | 
+      Symbol(s) |
       Function(s, _) |
       Native(s, _) |
       Closure(s, _)
-      => s
+      => s,
+      Class(class) => &class.name,
+      Instance(instance) => &instance.class.name,
+      BoundMethod(bound) => &bound.name,
+      Foreign(handle) => &handle.tag
     }
   }
 
@@ -58,10 +69,7 @@ impl LoxObject {
 
   pub fn is_callable(&self) -> bool {
     use LoxObject::*;
-    match self {
-      Function(_, _) | Native(_, _) | Closure(_, _) => true,
-      _ => false
-    }
+    matches!(self, Function(_, _) | Native(_, _) | Closure(_, _) | Class(_) | BoundMethod(_))
   }
 }
 
@@ -71,9 +79,14 @@ impl Debug for LoxObject {
     match self {
       Identifier(s) => write!(f, "{s}"),
       String(s) => write!(f, "\"{s}\""),
+      Symbol(s) => write!(f, ":{s}"),
       Function(name, n) => write!(f, "<fn {name} {n}>"),
       Native(name, _) => write!(f, "<std {name}>"),
       Closure(name, n) => write!(f, "<fn'{name} {n}>"),
+      Class(class) => write!(f, "<class {}>", class.name),
+      Instance(instance) => write!(f, "<instance {}>", instance.class.name),
+      BoundMethod(bound) => write!(f, "<fn'{} {}>", bound.name, bound.closure),
+      Foreign(handle) => write!(f, "<foreign {}>", handle.tag),
     }
   }
 }
@@ -92,7 +105,7 @@ impl TryFrom<Token> for LoxObject {
   type Error = ParseError;
   fn try_from(value: Token) -> Result<Self, Self::Error> {
     match value.kind {
-      TokenType::Identifier(s) => Ok(LoxObject::Identifier(s)),
+      TokenType::Identifier(s) => Ok(LoxObject::Identifier(s.to_string())),
       _ => Err(ParseError::UnexpectedToken { 
         message: "Expected identifier".into(), 
         offending: value, 
@@ -102,12 +115,104 @@ impl TryFrom<Token> for LoxObject {
   }
 }
 
+/// An opaque handle to a host resource. Natives create and accept them, while scripts can only
+/// pass them around. Copies share the resource, so equality is identity.
+#[derive(Clone)]
+pub struct Foreign {
+  /// Names the kind of resource, for messages and for checking handles passed back to natives.
+  pub tag: String,
+  data: Rc<dyn Any>,
+  /// Bytes of the resource itself, for memory accounting.
+  size: usize,
+}
+
+impl Foreign {
+  pub fn new<T: Any>(tag: &str, data: T) -> Self {
+    Self { tag: tag.into(), data: Rc::new(data), size: mem::size_of::<T>() }
+  }
+
+  /// Whether the handle holds a `T`.
+  pub fn is<T: Any>(&self) -> bool {
+    self.data.is::<T>()
+  }
+
+  /// The resource, if it is a `T`.
+  pub fn downcast<T: Any>(&self) -> Option<&T> {
+    self.data.downcast_ref()
+  }
+
+  pub fn size(&self) -> usize {
+    self.size
+  }
+}
+
+impl PartialEq for Foreign {
+  fn eq(&self, other: &Self) -> bool {
+    Rc::ptr_eq(&self.data, &other.data)
+  }
+}
+
+/// A class, shared by the value naming it and its instances. Methods are only added while its
+/// declaration runs, and a subclass starts with copies of its superclass's methods, so a lookup
+/// never walks the chain. Equality is identity.
+pub struct LoxClass {
+  pub name: String,
+  /// Methods by name, as indices into the module's closures.
+  pub methods: RefCell<HashMap<String, usize>>,
+}
+
+impl LoxClass {
+  pub fn new(name: &str) -> Self {
+    Self { name: name.into(), methods: RefCell::new(HashMap::new()) }
+  }
+
+  /// Closure of the method `name`, if the class or a superclass defines one.
+  pub fn method(&self, name: &str) -> Option<usize> {
+    self.methods.borrow().get(name).copied()
+  }
+}
+
+impl PartialEq for LoxClass {
+  fn eq(&self, other: &Self) -> bool {
+    ptr::eq(self, other)
+  }
+}
+
+/// An instance of a class. Fields shadow methods of the same name. Equality is identity.
+pub struct LoxInstance {
+  pub class: Rc<LoxClass>,
+  pub fields: RefCell<HashMap<String, Value>>,
+}
+
+impl LoxInstance {
+  pub fn new(class: Rc<LoxClass>) -> Self {
+    Self { class, fields: RefCell::new(HashMap::new()) }
+  }
+}
+
+impl PartialEq for LoxInstance {
+  fn eq(&self, other: &Self) -> bool {
+    ptr::eq(self, other)
+  }
+}
+
+/// A method read off an instance, which runs with the instance in the slot of `this`.
+#[derive(Clone, PartialEq)]
+pub struct BoundMethod {
+  pub name: String,
+  pub receiver: Value,
+  /// Index of the method's closure in the module.
+  pub closure: usize,
+}
+
 #[derive(PartialEq)]
 pub struct LoxFunction {
   pub name: String,
   pub arity: usize,
   pub chunk: Chunk,
   pub upvalues: usize,
+  /// Name of the function where it is declared, or nothing for the script.
+  pub span: Span,
 }
 
 impl LoxFunction {
@@ -116,7 +221,8 @@ impl LoxFunction {
       name: name.into(),
       arity: 0,
       chunk: Chunk::new(name),
-      upvalues: 0
+      upvalues: 0,
+      span: Span::default(),
     }
   }
 }
@@ -131,24 +237,17 @@ impl Debug for LoxFunction {
 pub struct NativeFunction {
   pub name: &'static str,
   pub arity: usize,
-  pub fn_ptr: fn(&[Value]) -> Result<Value, RuntimeError>
+  /// Natives get read access to the VM so that they can inspect its state.
+  pub fn_ptr: fn(&VM, &[Value]) -> Result<Value, RuntimeError>
 }
 
 impl NativeFunction {
-  pub fn call(&self, args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+  pub fn call(&self, vm: &VM, args: &[Value], span: Span) -> Result<Value, RuntimeError> {
     if args.len() != self.arity {
-      return Err(RuntimeError::UnsupportedType {  
-        message: format!(
-          "Expected {} arguments, but got {}",
-          self.arity,
-          args.len()
-        ), 
-        span, 
-        level: ErrorLevel::Error
-      })
+      return Err(RuntimeError::Arity { expected: self.arity, got: args.len(), span, defined: None })
     }
 
-    (self.fn_ptr)(args)
+    (self.fn_ptr)(vm, args)
   }
 }
 
@@ -6,13 +6,18 @@ use std::{
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
 /// Represents a string fragment.
-/// The bounds are over its byte representation.
-pub struct Span(pub usize, pub usize, pub u32);
+/// The bounds are over its byte representation, followed by the lines it starts and ends on.
+pub struct Span(pub usize, pub usize, pub u32, pub u32);
 
 impl Span {
-  /// Create a new span.
+  /// Create a new span on a single line.
   pub fn new(lo: usize, hi: usize, line: u32) -> Span {
-    Span(min(lo, hi), max(lo, hi), line)
+    Span::lines(lo, hi, line, line)
+  }
+
+  /// Create a new span running from `line` to `end_line`.
+  pub fn lines(lo: usize, hi: usize, line: u32, end_line: u32) -> Span {
+    Span(min(lo, hi), max(lo, hi), min(line, end_line), max(line, end_line))
   }
 
   #[cfg(test)]
@@ -20,9 +25,9 @@ impl Span {
     Span::new(0,0,line)
   }
 
-  /// Create a new span encompassing `self` and `other`.
+  /// Create a new span encompassing `self` and `other`, in either order.
   pub fn to(&self, other: Span) -> Span {
-    Span::new(min(self.0, other.0), max(self.1, other.1), min(self.2, other.2))
+    Span::lines(min(self.0, other.0), max(self.1, other.1), min(self.2, other.2), max(self.3, other.3))
   }
 
   /// Check if the span contains the given position.
@@ -36,7 +41,7 @@ impl Span {
     let hi = self.1 as isize + hi;
     assert!(lo >= 0, "New lower bound can't be negative.");
     assert!(lo <= hi, "Lower bound can not pass the higher.");
-    Span::new(lo as _, hi as _, self.2)
+    Span::lines(lo as _, hi as _, self.2, self.3)
   }
 
   /// Return the span range.
@@ -1,6 +1,6 @@
 use std::{
   error::Error as StdError,
-  fmt::Debug
+  fmt::{self, Debug, Display}
 };
 
 use super::Span;
@@ -23,6 +23,7 @@ impl Debug for ErrorLevel {
   }
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum ErrorType {
   _Error,
   CompileError,
@@ -40,13 +41,104 @@ impl Debug for ErrorType {
   }
 }
 
+/// Another span a diagnostic points at, with what it shows there, such as the start of the loop
+/// that a jump is too long for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+  pub span: Span,
+  pub message: String,
+}
+
+impl Label {
+  pub fn new(span: Span, message: impl Into<String>) -> Self {
+    Self { span, message: message.into() }
+  }
+}
+
+impl Display for Label {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "[line {}] {}; at position {}", self.span.2, self.message, self.span)
+  }
+}
+
+/// A diagnostic as the VM hands it to the host: the text it prints, the span it points at, and
+/// the labels that explain it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+  pub text: String,
+  /// The text without the level, line and position it is framed with, see [`LoxError::summary`].
+  pub summary: String,
+  pub span: Span,
+  pub labels: Vec<Label>,
+  /// Notes on how to fix the error, printed after it and its labels.
+  pub notes: Vec<String>,
+  /// Name of the warning, for those that `lox-ignore` comments silence.
+  pub lint: Option<&'static str>,
+}
+
+impl Message {
+  pub fn new(text: impl Into<String>, span: Span) -> Self {
+    let text = text.into();
+    Self { summary: text.clone(), text, span, labels: Vec::new(), notes: Vec::new(), lint: None }
+  }
+}
+
+impl Display for Message {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.text)?;
+    for label in &self.labels {
+      write!(f, "\n  {label}")?;
+    }
+    for note in &self.notes {
+      write!(f, "\n  note: {note}")?;
+    }
+    Ok(())
+  }
+}
+
+impl<E: LoxError + ?Sized> From<&E> for Message {
+  fn from(err: &E) -> Self {
+    Self {
+      text: err.message(),
+      summary: err.summary(),
+      span: err.get_span(),
+      labels: err.labels(),
+      notes: err.notes(),
+      lint: None,
+    }
+  }
+}
+
 pub trait LoxError: StdError {
   fn get_level(&self) -> ErrorLevel;
   fn get_type(&self) -> ErrorType;
   fn get_span(&self) -> Span;
 
+  /// Other spans that explain the error.
+  fn labels(&self) -> Vec<Label> {
+    Vec::new()
+  }
+
+  /// Notes on how to fix the error.
+  fn notes(&self) -> Vec<String> {
+    Vec::new()
+  }
+
+  /// The error as printed by `report`, without its labels.
+  fn message(&self) -> String {
+    format!("[{:?} line {}] {:?}: {}", self.get_level(), self.get_span().2, self.get_type(), self)
+  }
+
+  /// The error alone, without the position it ends with, for hosts that show its span apart.
+  fn summary(&self) -> String {
+    let text = self.to_string();
+    let position = format!("; at position {}", self.get_span());
+    text.strip_suffix(&position).unwrap_or(&text).to_string()
+  }
+
+  /// Prints the error, followed by its labels.
   fn report(&self) {
-    eprintln!("[{:?} line {}] {:?}: {}", self.get_level(), self.get_span().2, self.get_type(), self)
+    eprintln!("{}", Message::from(self));
   }
 }
 
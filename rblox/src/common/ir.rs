@@ -0,0 +1,131 @@
+//! JSON form of a compiled module, printed by `--emit=ir` for tools that want the compiler's
+//! output without parsing the disassembly.
+//!
+//! The document is `{"version": 1, "functions": [...]}`, with functions in module order so that
+//! closure operands index into the same array. Each function lists its constants and its code,
+//! and every instruction carries its opcode name, its operands and the span it was compiled from.
+
+use std::{fmt::Write, rc::Rc};
+
+use crate::common::{data::{LoxFunction, LoxObject}, Ins, Span, Value};
+
+/// Bumped whenever the shape of the document changes.
+const VERSION: u32 = 1;
+
+/// Encodes `functions` as a JSON document.
+pub fn to_json(functions: &[Rc<LoxFunction>]) -> String {
+  let functions: Vec<String> = functions.iter().enumerate().map(|(idx, fun)| function(idx, fun)).collect();
+  format!("{{\"version\":{VERSION},\"functions\":[{}]}}", functions.join(","))
+}
+
+fn function(idx: usize, fun: &LoxFunction) -> String {
+  let constants: Vec<String> = fun.chunk.constants.iter().map(constant).collect();
+  let code: Vec<String> = fun.chunk.iter_zip().map(|(ins, span)| instruction(ins, span)).collect();
+  format!(
+    "{{\"index\":{idx},\"name\":{},\"arity\":{},\"upvalues\":{},\"constants\":[{}],\"code\":[{}]}}",
+    string(&fun.name),
+    fun.arity,
+    fun.upvalues,
+    constants.join(","),
+    code.join(",")
+  )
+}
+
+fn constant(val: &Value) -> String {
+  let (kind, value) = match val {
+    Value::Boolean(b) => ("boolean", b.to_string()),
+    Value::Nil => ("nil", "null".into()),
+    Value::Number(n) => ("number", number(*n)),
+    Value::Object(obj) => match obj.as_ref() {
+      LoxObject::String(s) => ("string", string(s)),
+      LoxObject::Symbol(s) => ("symbol", string(s)),
+      other => (other.type_name(), string(&format!("{other:?}"))),
+    },
+  };
+  format!("{{\"type\":\"{kind}\",\"value\":{value}}}")
+}
+
+fn instruction(ins: &Ins, span: &Span) -> String {
+  use Ins::*;
+  let (op, operands) = match ins {
+    Constant(idx) => ("constant", vec![idx.to_string()]),
+    True => ("true", vec![]),
+    False => ("false", vec![]),
+    Nil => ("nil", vec![]),
+    Add => ("add", vec![]),
+    Subtract => ("subtract", vec![]),
+    Multiply => ("multiply", vec![]),
+    Divide => ("divide", vec![]),
+    Negate => ("negate", vec![]),
+    Not => ("not", vec![]),
+    Equal => ("equal", vec![]),
+    Greater => ("greater", vec![]),
+    Less => ("less", vec![]),
+    DefGlobal(name) => ("def_global", vec![string(name)]),
+    GetGlobal(name) => ("get_global", vec![string(name)]),
+    SetGlobal(name) => ("set_global", vec![string(name)]),
+    GetLocal(slot) => ("get_local", vec![slot.to_string()]),
+    SetLocal(slot) => ("set_local", vec![slot.to_string()]),
+    GetUpval(slot) => ("get_upvalue", vec![slot.to_string()]),
+    SetUpval(slot) => ("set_upvalue", vec![slot.to_string()]),
+    CloseUpval => ("close_upvalue", vec![]),
+    Call(args) => ("call", vec![args.to_string()]),
+    Closure(idx, upvals) => {
+      let captures: Vec<String> = upvals
+        .iter()
+        .map(|(is_local, idx)| format!("{{\"local\":{is_local},\"index\":{idx}}}"))
+        .collect();
+      ("closure", vec![idx.to_string(), format!("[{}]", captures.join(","))])
+    }
+    Class(name) => ("class", vec![string(name)]),
+    Method(name) => ("method", vec![string(name)]),
+    Inherit => ("inherit", vec![]),
+    GetProperty(name) => ("get_property", vec![string(name)]),
+    SetProperty(name) => ("set_property", vec![string(name)]),
+    GetSuper(name) => ("get_super", vec![string(name)]),
+    Jump(offset) => ("jump", vec![offset.to_string()]),
+    JumpIfFalse(offset) => ("jump_if_false", vec![offset.to_string()]),
+    JumpIfTrue(offset) => ("jump_if_true", vec![offset.to_string()]),
+    Print => ("print", vec![]),
+    Echo => ("echo", vec![]),
+    StartTimer => ("start_timer", vec![]),
+    StopTimer => ("stop_timer", vec![]),
+    Pop => ("pop", vec![]),
+    PopN(n) => ("pop_n", vec![n.to_string()]),
+    Return => ("return", vec![]),
+  };
+  format!(
+    "{{\"op\":\"{op}\",\"operands\":[{}],\"span\":{{\"start\":{},\"end\":{},\"line\":{},\"end_line\":{}}}}}",
+    operands.join(","),
+    span.0,
+    span.1,
+    span.2,
+    span.3
+  )
+}
+
+/// JSON has no literal for `inf` or `NaN`, so those become `null`.
+fn number(n: f64) -> String {
+  match n.is_finite() {
+    true => n.to_string(),
+    false => "null".into(),
+  }
+}
+
+fn string(s: &str) -> String {
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
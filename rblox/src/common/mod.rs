@@ -2,14 +2,18 @@
 mod tests;
 
 pub mod chunk;
+pub mod convert;
 pub mod debug;
 pub mod opcode;
 pub mod value;
 pub mod data;
 
 pub mod error;
+pub mod ir;
+pub mod serialize;
+pub mod verify;
 
 pub use opcode::Ins;
-pub use chunk::Chunk;
+pub use chunk::{Chunk, Inlined};
 pub use value::Value;
 pub use debug::span::Span;
\ No newline at end of file
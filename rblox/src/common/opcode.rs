@@ -1,11 +1,9 @@
 use std::{fmt::Debug, rc::Rc};
 
-use crate::common::{Value, data::LoxObject};
-
 #[derive(Clone, PartialEq)]
 pub enum Ins {
   // literals
-  Constant(Value), True, False, Nil,
+  Constant(usize), True, False, Nil,
 
   // arithmetic
   Add, Subtract, Multiply, Divide,
@@ -28,11 +26,22 @@ pub enum Ins {
   Call(usize),
   Closure(usize, Rc<Vec<(bool, usize)>>),
 
+  Class(String),
+  Method(String),
+  Inherit,
+  GetProperty(String),
+  SetProperty(String),
+  GetSuper(String),
+
   Jump(isize),
   JumpIfFalse(isize),
+  JumpIfTrue(isize),
   // Loop(usize),
 
-  Print, Pop, PopN(usize),
+  // `time` blocks, which keep the start time and instruction count on the stack
+  StartTimer, StopTimer,
+
+  Print, Echo, Pop, PopN(usize),
   Return,
 }
 
@@ -41,7 +50,7 @@ impl Debug for Ins {
     const PAD: usize = 15;
     use Ins::*;
     match self {
-      Constant(val) => write!(f, "{:PAD$}{val:?}", "OP_CONST"),
+      Constant(idx) => write!(f, "{:PAD$}{idx}", "OP_CONST"),
       True => write!(f, "OP_TRUE"),
       False => write!(f, "OP_FALSE"),
       Nil => write!(f, "OP_NIL"),
@@ -78,25 +87,24 @@ impl Debug for Ins {
         Ok(())
       },
 
+      Class(name) => write!(f, "{:PAD$}{name}", "OP_CLASS"),
+      Method(name) => write!(f, "{:PAD$}{name}", "OP_METHOD"),
+      Inherit => write!(f, "OP_INHERIT"),
+      GetProperty(name) => write!(f, "{:PAD$}{name}", "OP_GET_PROP"),
+      SetProperty(name) => write!(f, "{:PAD$}{name}", "OP_SET_PROP"),
+      GetSuper(name) => write!(f, "{:PAD$}{name}", "OP_GET_SUPER"),
+
       Jump(n) => write!(f, "{:PAD$}{n}", "OP_JMP"),
       JumpIfFalse(n) => write!(f, "{:PAD$}{n}", "OP_JMPF"),
+      JumpIfTrue(n) => write!(f, "{:PAD$}{n}", "OP_JMPT"),
 
       Print => write!(f, "OP_PRINT"),
+      Echo => write!(f, "OP_ECHO"),
+      StartTimer => write!(f, "OP_START_TIMER"),
+      StopTimer => write!(f, "OP_STOP_TIMER"),
       Pop => write!(f, "OP_POP"),
       PopN(n) => write!(f, "{:PAD$}{n}", "OP_POPN"),
       Return => write!(f, "OP_RETURN"),
     }
   }
 }
-
-impl From<f64> for Ins {
-  fn from(value: f64) -> Self {
-    Self::Constant(Value::from(value))
-  }
-}
-
-impl From<LoxObject> for Ins {
-  fn from(value: LoxObject) -> Self {
-    Self::Constant(Value::Object(Rc::new(value)))
-  }
-}
@@ -0,0 +1,534 @@
+//! Binary encoding of compiled functions, used to cache bytecode on disk and to pack programs
+//! into bundles.
+//!
+//! All integers are little-endian. Function indices inside `OP_CLOSURE` are stored relative to
+//! the first encoded function, and relocated against the target module when decoded.
+
+use std::{path::PathBuf, rc::Rc};
+
+use crate::{
+  common::{data::{LoxFunction, LoxObject}, Ins, Span, Value},
+  compiler::{parser::state::ParserOptions, scope::Module},
+};
+
+const MAGIC: &[u8; 4] = b"LOXC";
+/// Version of the image format. Bump it with every change to the encoding, such as a new
+/// instruction, so that VMs reject images they would misread.
+pub(crate) const VERSION: u32 = 6;
+const BUNDLE_MAGIC: &[u8; 4] = b"LOXB";
+/// Ends an executable with a bundle appended, after the length of the bundle.
+const PAYLOAD_MAGIC: &[u8; 8] = b"LOXBUNDL";
+
+/// Version of the compiler, recorded in each image. The encoding can stay the same while the code
+/// compiled for a source changes, so images from other versions are stale.
+pub const COMPILER: &str = env!("CARGO_PKG_VERSION");
+
+/// FNV-1a hash. Stable across platforms and toolchains, unlike `DefaultHasher`.
+pub fn hash(bytes: &[u8]) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+  for byte in bytes {
+    hash ^= *byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  hash
+}
+
+/// Compiler options that change the bytecode compiled for a source.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Flags {
+  pub strict_globals: bool,
+  pub no_inline: bool,
+  pub no_hoist: bool,
+}
+
+impl Flags {
+  fn bits(self) -> u32 {
+    self.strict_globals as u32 | (self.no_inline as u32) << 1 | (self.no_hoist as u32) << 2
+  }
+
+  fn from_bits(bits: u32) -> Option<Self> {
+    (bits < 1 << 3).then_some(Self {
+      strict_globals: bits & 1 != 0,
+      no_inline: bits & 1 << 1 != 0,
+      no_hoist: bits & 1 << 2 != 0,
+    })
+  }
+}
+
+impl From<&ParserOptions> for Flags {
+  fn from(options: &ParserOptions) -> Self {
+    Self {
+      strict_globals: options.strict_globals,
+      no_inline: options.no_inline,
+      no_hoist: options.no_hoist,
+    }
+  }
+}
+
+/// Serialized form of a compiled source file.
+#[derive(Debug, PartialEq)]
+pub struct Image {
+  /// Hash of the source the functions were compiled from.
+  pub hash: u64,
+  /// Version of the compiler that compiled the functions.
+  pub compiler: String,
+  /// Options the functions were compiled with.
+  pub flags: Flags,
+  /// Files pulled in while compiling, with their source hashes and the index of their script
+  /// function among `functions`.
+  pub deps: Vec<(String, u64, usize)>,
+  pub functions: Vec<LoxFunction>,
+}
+
+impl Image {
+  /// Encodes `functions`, compiled from a source with `hash` by this compiler with `flags`. They
+  /// must be a contiguous slice of a module starting at `base`.
+  pub fn encode(
+    hash: u64,
+    flags: Flags,
+    deps: &[(String, u64, usize)],
+    functions: &[Rc<LoxFunction>],
+    base: usize
+  ) -> Option<Vec<u8>> {
+    let mut w = Writer(Vec::new());
+    w.0.extend_from_slice(MAGIC);
+    w.u32(VERSION);
+    w.str(COMPILER);
+    w.u32(flags.bits());
+    w.u64(hash);
+
+    w.len(deps.len());
+    for (path, hash, main) in deps {
+      w.str(path);
+      w.u64(*hash);
+      w.len(*main);
+    }
+
+    w.len(functions.len());
+    for function in functions {
+      w.str(&function.name);
+      w.len(function.arity);
+      w.len(function.upvalues);
+      w.span(&function.span);
+      w.str(&function.chunk.name);
+      w.len(function.chunk.constants.len());
+      for val in &function.chunk.constants {
+        w.value(val)?;
+      }
+      w.len(function.chunk.len());
+      for (ins, span) in function.chunk.iter_zip() {
+        w.ins(ins, base)?;
+        w.span(span);
+      }
+    }
+    Some(w.0)
+  }
+
+  /// Decodes an image, returning `None` if the bytes are malformed or from another version.
+  pub fn decode(bytes: &[u8]) -> Option<Self> {
+    let mut r = Reader(bytes);
+    if r.take(4)? != MAGIC || r.u32()? != VERSION {
+      return None;
+    }
+    let compiler = r.str()?;
+    let flags = Flags::from_bits(r.u32()?)?;
+    let hash = r.u64()?;
+
+    let deps = (0..r.len()?)
+      .map(|_| Some((r.str()?, r.u64()?, r.len()?)))
+      .collect::<Option<Vec<_>>>()?;
+
+    let mut functions = Vec::new();
+    for _ in 0..r.len()? {
+      let mut function = LoxFunction::new(&r.str()?);
+      function.arity = r.len()?;
+      function.upvalues = r.len()?;
+      function.span = r.span()?;
+      function.chunk.name = r.str()?;
+      function.chunk.constants = (0..r.len()?)
+        .map(|_| r.value())
+        .collect::<Option<Vec<_>>>()?;
+      for _ in 0..r.len()? {
+        let ins = r.ins()?;
+        let span = r.span()?;
+        function.chunk.write(ins, span);
+      }
+      functions.push(function);
+    }
+
+    let linked = deps.iter().all(|(_, _, main)| *main < functions.len());
+    (linked && r.0.is_empty()).then_some(Self { hash, compiler, flags, deps, functions })
+  }
+
+  /// Whether the image holds what this compiler would compile with `flags` from a source with
+  /// `hash`. Files it imported are not checked.
+  pub fn is_current(&self, hash: u64, flags: Flags) -> bool {
+    self.hash == hash && self.compiler == COMPILER && self.flags == flags
+  }
+
+  /// Appends the functions to `module`, relocating closure indices and interning symbols, and
+  /// records the files it imported as imported. Returns the index of the last function, i.e. the
+  /// script body.
+  pub fn link(self, module: &mut Module) -> usize {
+    let base = module.functions.len();
+    for function in self.functions {
+      let function = relink(function, module, |idx| idx + base);
+      module.functions.push(Rc::new(function));
+    }
+    for (dep, _, main) in self.deps {
+      module.imported.entry(PathBuf::from(dep)).or_insert(base + main);
+    }
+    module.functions.len() - 1
+  }
+}
+
+/// Prepares `function` to join `module`: the closures it creates are pointed at the functions that
+/// `slot` maps their indices to, its symbols are interned and the globals it defines declared.
+pub fn relink(mut function: LoxFunction, module: &mut Module, slot: impl Fn(usize) -> usize) -> LoxFunction {
+  for ins in function.chunk.code.iter_mut() {
+    match ins {
+      Ins::Closure(idx, _) => *idx = slot(*idx),
+      Ins::DefGlobal(name) => {
+        module.globals.insert(name.clone());
+      }
+      _ => {}
+    }
+  }
+  for val in function.chunk.constants.iter_mut() {
+    if let Value::Object(obj) = val {
+      if let LoxObject::Symbol(name) = obj.as_ref() {
+        *obj = module.intern(name);
+      }
+    }
+  }
+  function
+}
+
+/// A program packed into one file, which runs without its sources.
+#[derive(Debug, PartialEq)]
+pub struct Bundle {
+  /// Name of the script the program starts from.
+  pub entry: String,
+  /// Files the entry script imports, directly or not, relative to its directory when inside it.
+  pub modules: Vec<String>,
+  /// Image of the entry script, which holds the code of every module it imports.
+  pub image: Image,
+}
+
+impl Bundle {
+  /// Packs `image`, an encoded image of the script `entry` that imports `modules`.
+  pub fn encode(entry: &str, modules: &[String], image: &[u8]) -> Vec<u8> {
+    let mut w = Writer(Vec::new());
+    w.0.extend_from_slice(BUNDLE_MAGIC);
+    w.u32(VERSION);
+    w.str(entry);
+    w.len(modules.len());
+    for module in modules {
+      w.str(module);
+    }
+    w.len(image.len());
+    w.0.extend_from_slice(image);
+    w.0
+  }
+
+  /// Decodes a bundle, returning `None` if the bytes are malformed or from another version.
+  pub fn decode(bytes: &[u8]) -> Option<Self> {
+    let mut r = Reader(bytes);
+    if r.take(4)? != BUNDLE_MAGIC || r.u32()? != VERSION {
+      return None;
+    }
+    let entry = r.str()?;
+    let modules = (0..r.len()?).map(|_| r.str()).collect::<Option<Vec<_>>>()?;
+    let len = r.len()?;
+    let image = Image::decode(r.take(len)?)?;
+    r.0.is_empty().then_some(Self { entry, modules, image })
+  }
+}
+
+/// Bytes that follow a bundle appended to an executable: its length and a marker.
+pub const TRAILER_LEN: usize = 16;
+
+/// Appends the encoded `bundle` to `exe`, the bytes of an executable, so that the executable can
+/// find it in its own file with [`payload_len`].
+pub fn embed(exe: &mut Vec<u8>, bundle: &[u8]) {
+  exe.extend_from_slice(bundle);
+  exe.extend_from_slice(&(bundle.len() as u64).to_le_bytes());
+  exe.extend_from_slice(PAYLOAD_MAGIC);
+}
+
+/// Length of the bundle before `trailer`, the last [`TRAILER_LEN`] bytes of an executable, if
+/// one was appended with [`embed`].
+pub fn payload_len(trailer: &[u8; TRAILER_LEN]) -> Option<u64> {
+  let (len, magic) = trailer.split_at(8);
+  (magic == PAYLOAD_MAGIC).then(|| u64::from_le_bytes(len.try_into().unwrap()))
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+  fn u8(&mut self, val: u8) {
+    self.0.push(val);
+  }
+
+  fn u32(&mut self, val: u32) {
+    self.0.extend_from_slice(&val.to_le_bytes());
+  }
+
+  fn u64(&mut self, val: u64) {
+    self.0.extend_from_slice(&val.to_le_bytes());
+  }
+
+  fn len(&mut self, val: usize) {
+    self.u64(val as u64);
+  }
+
+  fn str(&mut self, val: &str) {
+    self.len(val.len());
+    self.0.extend_from_slice(val.as_bytes());
+  }
+
+  fn span(&mut self, span: &Span) {
+    self.len(span.0);
+    self.len(span.1);
+    self.u32(span.2);
+    self.u32(span.3);
+  }
+
+  fn value(&mut self, val: &Value) -> Option<()> {
+    match val {
+      Value::Nil => self.u8(0),
+      Value::Boolean(b) => {
+        self.u8(1);
+        self.u8(*b as u8);
+      }
+      Value::Number(n) => {
+        self.u8(2);
+        self.u64(n.to_bits());
+      }
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::String(s) => {
+          self.u8(3);
+          self.str(s);
+        }
+        LoxObject::Symbol(s) => {
+          self.u8(4);
+          self.str(s);
+        }
+        // Only literals are ever emitted as constants.
+        _ => return None,
+      },
+    }
+    Some(())
+  }
+
+  fn ins(&mut self, ins: &Ins, base: usize) -> Option<()> {
+    use Ins::*;
+    match ins {
+      Constant(idx) => {
+        self.u8(0);
+        self.len(*idx);
+      }
+      True => self.u8(1),
+      False => self.u8(2),
+      Nil => self.u8(3),
+      Add => self.u8(4),
+      Subtract => self.u8(5),
+      Multiply => self.u8(6),
+      Divide => self.u8(7),
+      Negate => self.u8(8),
+      Not => self.u8(9),
+      Equal => self.u8(10),
+      Greater => self.u8(11),
+      Less => self.u8(12),
+      DefGlobal(name) => {
+        self.u8(13);
+        self.str(name);
+      }
+      GetGlobal(name) => {
+        self.u8(14);
+        self.str(name);
+      }
+      SetGlobal(name) => {
+        self.u8(15);
+        self.str(name);
+      }
+      GetLocal(slot) => {
+        self.u8(16);
+        self.len(*slot);
+      }
+      SetLocal(slot) => {
+        self.u8(17);
+        self.len(*slot);
+      }
+      GetUpval(slot) => {
+        self.u8(18);
+        self.len(*slot);
+      }
+      SetUpval(slot) => {
+        self.u8(19);
+        self.len(*slot);
+      }
+      CloseUpval => self.u8(20),
+      Call(args) => {
+        self.u8(21);
+        self.len(*args);
+      }
+      Closure(idx, upvals) => {
+        self.u8(22);
+        self.len(idx.checked_sub(base)?);
+        self.len(upvals.len());
+        for (is_local, idx) in upvals.iter() {
+          self.u8(*is_local as u8);
+          self.len(*idx);
+        }
+      }
+      Jump(offset) => {
+        self.u8(23);
+        self.u64(*offset as u64);
+      }
+      JumpIfFalse(offset) => {
+        self.u8(24);
+        self.u64(*offset as u64);
+      }
+      Print => self.u8(25),
+      Pop => self.u8(26),
+      PopN(n) => {
+        self.u8(27);
+        self.len(*n);
+      }
+      Return => self.u8(28),
+      JumpIfTrue(offset) => {
+        self.u8(29);
+        self.u64(*offset as u64);
+      }
+      Class(name) => {
+        self.u8(30);
+        self.str(name);
+      }
+      Method(name) => {
+        self.u8(31);
+        self.str(name);
+      }
+      Inherit => self.u8(32),
+      GetProperty(name) => {
+        self.u8(33);
+        self.str(name);
+      }
+      SetProperty(name) => {
+        self.u8(34);
+        self.str(name);
+      }
+      GetSuper(name) => {
+        self.u8(35);
+        self.str(name);
+      }
+      Echo => self.u8(36),
+      StartTimer => self.u8(37),
+      StopTimer => self.u8(38),
+    }
+    Some(())
+  }
+}
+
+struct Reader<'b>(&'b [u8]);
+
+impl Reader<'_> {
+  fn take(&mut self, n: usize) -> Option<&[u8]> {
+    if self.0.len() < n {
+      return None;
+    }
+    let (head, tail) = self.0.split_at(n);
+    self.0 = tail;
+    Some(head)
+  }
+
+  fn u8(&mut self) -> Option<u8> {
+    self.take(1).map(|b| b[0])
+  }
+
+  fn u32(&mut self) -> Option<u32> {
+    self.take(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+  }
+
+  fn u64(&mut self) -> Option<u64> {
+    self.take(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+  }
+
+  fn len(&mut self) -> Option<usize> {
+    self.u64().and_then(|n| usize::try_from(n).ok())
+  }
+
+  fn str(&mut self) -> Option<String> {
+    let len = self.len()?;
+    String::from_utf8(self.take(len)?.to_vec()).ok()
+  }
+
+  fn span(&mut self) -> Option<Span> {
+    Some(Span(self.len()?, self.len()?, self.u32()?, self.u32()?))
+  }
+
+  fn value(&mut self) -> Option<Value> {
+    let val = match self.u8()? {
+      0 => Value::Nil,
+      1 => Value::Boolean(self.u8()? != 0),
+      2 => Value::Number(f64::from_bits(self.u64()?)),
+      3 => Value::Object(Rc::new(LoxObject::String(self.str()?))),
+      4 => Value::Object(Rc::new(LoxObject::Symbol(self.str()?))),
+      _ => return None,
+    };
+    Some(val)
+  }
+
+  fn ins(&mut self) -> Option<Ins> {
+    use Ins::*;
+    let ins = match self.u8()? {
+      0 => Constant(self.len()?),
+      1 => True,
+      2 => False,
+      3 => Nil,
+      4 => Add,
+      5 => Subtract,
+      6 => Multiply,
+      7 => Divide,
+      8 => Negate,
+      9 => Not,
+      10 => Equal,
+      11 => Greater,
+      12 => Less,
+      13 => DefGlobal(self.str()?),
+      14 => GetGlobal(self.str()?),
+      15 => SetGlobal(self.str()?),
+      16 => GetLocal(self.len()?),
+      17 => SetLocal(self.len()?),
+      18 => GetUpval(self.len()?),
+      19 => SetUpval(self.len()?),
+      20 => CloseUpval,
+      21 => Call(self.len()?),
+      22 => {
+        let idx = self.len()?;
+        let upvals = (0..self.len()?)
+          .map(|_| Some((self.u8()? != 0, self.len()?)))
+          .collect::<Option<Vec<_>>>()?;
+        Closure(idx, Rc::new(upvals))
+      }
+      23 => Jump(self.u64()? as isize),
+      24 => JumpIfFalse(self.u64()? as isize),
+      25 => Print,
+      26 => Pop,
+      27 => PopN(self.len()?),
+      28 => Return,
+      29 => JumpIfTrue(self.u64()? as isize),
+      30 => Class(self.str()?),
+      31 => Method(self.str()?),
+      32 => Inherit,
+      33 => GetProperty(self.str()?),
+      34 => SetProperty(self.str()?),
+      35 => GetSuper(self.str()?),
+      36 => Echo,
+      37 => StartTimer,
+      38 => StopTimer,
+      _ => return None,
+    };
+    Some(ins)
+  }
+}
@@ -4,4 +4,10 @@ use super::*;
 mod display;
 
 #[cfg(test)]
-mod value;
\ No newline at end of file
+mod value;
+#[cfg(test)]
+mod serialize;
+#[cfg(test)]
+mod verify;
+#[cfg(test)]
+mod ir;
@@ -18,13 +18,56 @@ fn correct_return() {
 fn correct_constant() {
   use Value::Number;
   let mut chunk = Chunk::new("constant");
-  chunk.write(Ins::Constant(Number(1.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Number(1.2)), Span::dummy(4));
-  chunk.write(Ins::Constant(Number(2.13)), Span::dummy(4));
+  chunk.write_constant(Number(1.0), Span::dummy(2));
+  chunk.write_constant(Number(1.2), Span::dummy(4));
+  chunk.write_constant(Number(2.13), Span::dummy(4));
+  chunk.write_constant(Number(1.2), Span::dummy(5));
 
   assert_eq!(chunk.to_string(), 
   "===== constant =====
-  2 | OP_CONST       1
-  4 | OP_CONST       1.2
-  . | OP_CONST       2.13\n");
+  2 | OP_CONST       0 (1)
+  4 | OP_CONST       1 (1.2)
+  . | OP_CONST       2 (2.13)
+  5 | OP_CONST       1 (1.2)\n");
+}
+
+#[test]
+fn constants_are_pooled() {
+  use data::LoxObject;
+  let mut chunk = Chunk::new("pool");
+  assert_eq!(chunk.add_constant(Value::Number(0.0)), 0);
+  assert_eq!(chunk.add_constant(Value::Number(-0.0)), 1);
+  assert_eq!(chunk.add_constant(Value::Number(f64::NAN)), 2);
+  assert_eq!(chunk.add_constant(Value::Number(f64::NAN)), 2);
+  assert_eq!(chunk.add_constant(Value::from(LoxObject::String("a".into()))), 3);
+  assert_eq!(chunk.add_constant(Value::from(LoxObject::Symbol("a".into()))), 4);
+  assert_eq!(chunk.add_constant(Value::from(LoxObject::String("a".into()))), 3);
+  assert_eq!(chunk.add_constant(Value::Number(0.0)), 0);
+  assert_eq!(chunk.constants.len(), 5);
+}
+
+#[test]
+fn constants_set_directly_are_pooled() {
+  let mut chunk = Chunk::new("loaded");
+  chunk.constants = vec![Value::Nil, Value::Number(1.0)];
+  assert_eq!(chunk.add_constant(Value::Number(1.0)), 1);
+  assert_eq!(chunk.add_constant(Value::Boolean(true)), 2);
+  assert_eq!(chunk.add_constant(Value::Nil), 0);
+}
+
+#[test]
+fn landings_follow_patches_and_moves() {
+  let span = Span::default();
+  let mut chunk = Chunk::new("jumps");
+  chunk.write(Ins::JumpIfFalse(-1), span);
+  chunk.write(Ins::Nil, span);
+  assert!(!chunk.jumps_to(2));
+  chunk.replace(0, Ins::JumpIfFalse(1));
+  assert!(chunk.jumps_to(2));
+  chunk.insert(0, vec![(Ins::True, span)]);
+  assert!(chunk.jumps_to(3) && !chunk.jumps_to(2));
+  chunk.remove(0);
+  assert!(chunk.jumps_to(2));
+  chunk.remove(1);
+  assert!(chunk.jumps_to(2) && !chunk.jumps_to(1));
 }
@@ -0,0 +1,42 @@
+use crate::{
+  common::ir::to_json,
+  compiler::{compile, scope::Module},
+};
+
+fn emitted(src: &str) -> String {
+  let module = Module::new();
+  assert!(compile(src, module.clone(), &Default::default()).is_empty());
+  let json = to_json(&module.borrow().functions);
+  json
+}
+
+
+#[test]
+fn emits_functions_constants_and_code() {
+  let json = emitted("print 1.5;");
+  assert_eq!(json, concat!(
+    "{\"version\":1,\"functions\":[{\"index\":0,\"name\":\"<script>\",\"arity\":0,\"upvalues\":0,",
+    "\"constants\":[{\"type\":\"number\",\"value\":1.5}],\"code\":[",
+    "{\"op\":\"constant\",\"operands\":[0],\"span\":{\"start\":6,\"end\":9,\"line\":1,\"end_line\":1}},",
+    "{\"op\":\"print\",\"operands\":[],\"span\":{\"start\":0,\"end\":10,\"line\":1,\"end_line\":1}},",
+    "{\"op\":\"nil\",\"operands\":[],\"span\":{\"start\":9,\"end\":10,\"line\":1,\"end_line\":1}},",
+    "{\"op\":\"return\",\"operands\":[],\"span\":{\"start\":9,\"end\":10,\"line\":1,\"end_line\":1}}]}]}",
+  ));
+}
+
+#[test]
+fn emits_closures_with_captures() {
+  // functions are added once compiled, so `g` comes before `f`
+  let json = emitted("fun f() { var x = 1; fun g() { return x; } return g; }");
+  assert!(json.contains("{\"index\":0,\"name\":\"g\",\"arity\":0,\"upvalues\":1,"), "{json}");
+  assert!(json.contains("{\"op\":\"closure\",\"operands\":[0,[{\"local\":true,\"index\":1}]]"), "{json}");
+  assert!(json.contains("{\"op\":\"get_upvalue\",\"operands\":[0]"), "{json}");
+}
+
+#[test]
+fn escapes_strings() {
+  // Lox strings have no escapes, but may hold backslashes and line breaks
+  let json = emitted("var s = \"a\\b\n\tc\";");
+  assert!(json.contains("{\"type\":\"string\",\"value\":\"a\\\\b\\n\\tc\"}"), "{json}");
+  assert!(json.contains("{\"op\":\"def_global\",\"operands\":[\"s\"]"), "{json}");
+}
@@ -0,0 +1,172 @@
+use std::rc::Rc;
+
+use data::{LoxFunction, LoxObject};
+use crate::common::serialize::{self, Bundle, Flags, Image, COMPILER};
+
+use super::*;
+
+fn sample(closure_idx: usize) -> Vec<Rc<LoxFunction>> {
+  let mut inner = LoxFunction::new("inner");
+  inner.arity = 1;
+  inner.upvalues = 1;
+  inner.chunk.write(Ins::GetUpval(0), Span::new(0, 1, 1));
+  inner.chunk.write(Ins::Return, Span::new(1, 2, 1));
+
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write_constant(Value::from(1.5), Span::new(0, 3, 1));
+  main.chunk.write_constant(Value::from(LoxObject::String("str".into())), Span::new(4, 9, 2));
+  main.chunk.write_constant(Value::from(LoxObject::Symbol("sym".into())), Span::new(10, 14, 2));
+  main.chunk.write(Ins::JumpIfFalse(-3), Span::new(15, 16, 3));
+  main.chunk.write(Ins::Closure(closure_idx, Rc::new(vec![(true, 1), (false, 0)])), Span::new(17, 20, 3));
+  main.chunk.write(Ins::DefGlobal("f".into()), Span::new(17, 20, 3));
+  main.chunk.write(Ins::Return, Span::new(20, 21, 4));
+
+  vec![Rc::new(inner), Rc::new(main)]
+}
+
+#[test]
+fn image_roundtrip() {
+  let functions = sample(5);
+  let deps = vec![("dep.lox".to_string(), 42, 0)];
+  let bytes = Image::encode(7, Flags::default(), &deps, &functions, 5).unwrap();
+  let image = Image::decode(&bytes).unwrap();
+
+  assert_eq!(image.hash, 7);
+  assert_eq!(image.deps, deps);
+  assert_eq!(image.functions.len(), 2);
+  // closure indices are stored relative to the first function
+  assert_eq!(image.functions[1].chunk.code[4], Ins::Closure(0, Rc::new(vec![(true, 1), (false, 0)])));
+  assert_eq!(image.functions[0], *functions[0]);
+  assert_eq!(image.functions[1].chunk.iter_zip().nth(3), functions[1].chunk.iter_zip().nth(3));
+}
+
+#[test]
+fn image_header_records_the_compiler_and_flags() {
+  let flags = Flags { no_inline: true, ..Default::default() };
+  let image = Image::decode(&Image::encode(7, flags, &[], &sample(0), 0).unwrap()).unwrap();
+  assert_eq!(image.compiler, COMPILER);
+  assert_eq!(image.flags, flags);
+  assert!(image.is_current(7, flags));
+  assert!(!image.is_current(8, flags));
+  assert!(!image.is_current(7, Flags::default()));
+
+  let stale = Image { compiler: "0.0.0".into(), ..image };
+  assert!(!stale.is_current(7, flags));
+}
+
+#[test]
+fn image_link_relocates() {
+  let bytes = Image::encode(0, Flags::default(), &[], &sample(0), 0).unwrap();
+  let module = crate::compiler::scope::Module::new();
+  let mut module = module.borrow_mut();
+  module.functions.push(Rc::new(LoxFunction::new("existing")));
+  let sym = module.intern("sym");
+
+  let main = Image::decode(&bytes).unwrap().link(&mut module);
+  assert_eq!(main, 2);
+  let code = &module.functions[main].chunk.code;
+  assert!(matches!(code[4], Ins::Closure(1, _)));
+  assert_eq!(code[2], Ins::Constant(2));
+  match &module.functions[main].chunk.constants[2] {
+    Value::Object(obj) => assert!(Rc::ptr_eq(obj, &sym)),
+    other => panic!("expected a symbol constant, got {other:?}"),
+  }
+}
+
+/// One instruction of each kind. The match has no catch-all arm, so a new instruction does not
+/// compile until it is added here too.
+fn every_instruction() -> Vec<Ins> {
+  use Ins::*;
+  let all = vec![
+    Constant(0), True, False, Nil, Add, Subtract, Multiply, Divide, Negate, Not, Equal, Greater, Less,
+    DefGlobal("g".into()), GetGlobal("g".into()), SetGlobal("g".into()), GetLocal(1), SetLocal(1),
+    GetUpval(2), SetUpval(2), CloseUpval, Call(3), Closure(0, Rc::new(vec![(true, 1)])),
+    Class("C".into()), Method("m".into()), Inherit, GetProperty("p".into()), SetProperty("p".into()),
+    GetSuper("m".into()), Jump(4), JumpIfFalse(-4), JumpIfTrue(4), StartTimer, StopTimer, Print, Echo,
+    Pop, PopN(5), Return,
+  ];
+  for ins in &all {
+    match ins {
+      Constant(_) | True | False | Nil | Add | Subtract | Multiply | Divide | Negate | Not | Equal
+      | Greater | Less | DefGlobal(_) | GetGlobal(_) | SetGlobal(_) | GetLocal(_) | SetLocal(_)
+      | GetUpval(_) | SetUpval(_) | CloseUpval | Call(_) | Closure(..) | Class(_) | Method(_) | Inherit
+      | GetProperty(_) | SetProperty(_) | GetSuper(_) | Jump(_) | JumpIfFalse(_) | JumpIfTrue(_)
+      | StartTimer | StopTimer | Print | Echo | Pop | PopN(_) | Return => {}
+    }
+  }
+  all
+}
+
+#[test]
+fn image_version_changes_with_the_encoding() {
+  let mut function = LoxFunction::new("all");
+  for ins in every_instruction() {
+    function.chunk.write(ins, Span::default());
+  }
+  let deps = [("dep.lox".to_string(), 1, 0)];
+  let mut bytes = Image::encode(0, Flags::default(), &deps, &[Rc::new(function)], 0).unwrap();
+  let image = Image::decode(&bytes).unwrap();
+  assert_eq!(image.functions[0].chunk.code, every_instruction());
+
+  // the compiler version changes without the format changing
+  let at = bytes.windows(COMPILER.len()).position(|w| w == COMPILER.as_bytes()).unwrap();
+  bytes.drain(at..at + COMPILER.len());
+  // when this fails, bump `VERSION` in serialize.rs, then pin the new hash
+  assert_eq!((serialize::VERSION, serialize::hash(&bytes)), (6, 0xc3a4_61b0_bcd3_95b6));
+}
+
+#[test]
+fn image_rejects_malformed() {
+  let bytes = Image::encode(0, Flags::default(), &[], &sample(0), 0).unwrap();
+  assert!(Image::decode(&bytes[..bytes.len() - 1]).is_none());
+  assert!(Image::decode(b"LOXD").is_none());
+}
+
+#[test]
+fn builds_are_reproducible() {
+  use crate::compiler::{compile, scope::Module};
+
+  let src = "var a = :zeta; var b = :alpha; var c = :mid;
+fun outer(x) { fun inner() { return x + \"s\"; } return inner; }
+print outer(:omega) == :alpha;";
+  let build = || {
+    let module = Module::new();
+    assert!(compile(src, module.clone(), &Default::default()).is_empty());
+    let module = Rc::try_unwrap(module).unwrap().into_inner();
+    let image = Image::encode(serialize::hash(src.as_bytes()), Flags::default(), &[], &module.functions, 0).unwrap();
+    (image, format!("{module}"), format!("{module:?}"))
+  };
+
+  let (image, listing, dump) = build();
+  let (image2, listing2, dump2) = build();
+  assert_eq!(image, image2);
+  assert_eq!(listing, listing2);
+  assert_eq!(dump, dump2);
+}
+
+#[test]
+fn bundle_roundtrip() {
+  let image = Image::encode(7, Flags::default(), &[], &sample(0), 0).unwrap();
+  let modules = vec!["lib.lox".to_string(), "sub/util.lox".to_string()];
+  let bytes = Bundle::encode("main.lox", &modules, &image);
+  let bundle = Bundle::decode(&bytes).unwrap();
+  assert_eq!(bundle.entry, "main.lox");
+  assert_eq!(bundle.modules, modules);
+  assert_eq!(bundle.image, Image::decode(&image).unwrap());
+
+  assert!(Bundle::decode(&bytes[..bytes.len() - 1]).is_none());
+  // an image is not a bundle
+  assert!(Bundle::decode(&image).is_none());
+}
+
+#[test]
+fn embedded_bundle_is_found_at_the_end() {
+  let mut exe = b"\x7fELF program".to_vec();
+  serialize::embed(&mut exe, b"bundle");
+  let trailer = exe[exe.len() - serialize::TRAILER_LEN..].try_into().unwrap();
+  assert_eq!(serialize::payload_len(&trailer), Some(6));
+  assert_eq!(&exe[exe.len() - serialize::TRAILER_LEN - 6..][..6], b"bundle");
+
+  let plain = [0; serialize::TRAILER_LEN];
+  assert_eq!(serialize::payload_len(&plain), None);
+}
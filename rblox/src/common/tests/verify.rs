@@ -0,0 +1,129 @@
+use std::rc::Rc;
+
+use data::LoxFunction;
+use crate::{
+  common::verify::verify,
+  compiler::{compile, scope::Module},
+};
+
+use super::*;
+
+fn compiled(src: &str) -> Vec<LoxFunction> {
+  let module = Module::new();
+  assert!(compile(src, module.clone(), &Default::default()).is_empty());
+  let module = Rc::try_unwrap(module).unwrap().into_inner();
+  module.functions.into_iter().map(|fun| Rc::try_unwrap(fun).unwrap()).collect()
+}
+
+#[test]
+fn accepts_compiled_code() {
+  let functions = compiled("
+fun counter() { var n = 0; fun inc() { n = n + 1; return n; } return inc; }
+var c = counter();
+for (var i = 0; i < 3; i = i + 1) { if (i > 1 and c() > 0) print i; else print -i; }
+fun add(a, b, c) { var sum = a + b; { var d = c; fun get() { return d; } sum = sum + get(); } return sum; }
+while (false or nil) { var x = 1; print x; }
+print add(1, 2, 3);
+");
+  assert_eq!(verify(&functions), Ok(()));
+}
+
+#[test]
+fn rejects_jump_out_of_chunk() {
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::Jump(-2), Span::dummy(1));
+  main.chunk.write(Ins::Return, Span::dummy(1));
+  let err = verify(&[main]).unwrap_err();
+  assert_eq!(err.offset, 0);
+  assert_eq!(err.to_string(), "in `<script>` at 0000: jump by -2 leaves the chunk");
+}
+
+#[test]
+fn errors_name_the_function_by_index() {
+  // methods of different classes share a name
+  let mut first = LoxFunction::new("init");
+  first.chunk.write(Ins::Nil, Span::dummy(1));
+  first.chunk.write(Ins::Return, Span::dummy(1));
+  let mut second = LoxFunction::new("init");
+  second.chunk.write(Ins::Jump(5), Span::dummy(2));
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::Nil, Span::dummy(3));
+  main.chunk.write(Ins::Return, Span::dummy(3));
+  let err = verify(&[first, second, main]).unwrap_err();
+  assert_eq!((err.function.as_str(), err.index), ("init", 1));
+}
+
+#[test]
+fn rejects_bad_closures() {
+  let mut inner = LoxFunction::new("inner");
+  inner.upvalues = 1;
+  inner.chunk.write(Ins::GetUpval(1), Span::dummy(1));
+  let main = LoxFunction::new("<script>");
+  assert!(verify(&[inner, main]).unwrap_err().message.contains("upvalue 1 is out of range"));
+
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::Closure(5, Rc::new(vec![])), Span::dummy(1));
+  assert!(verify(&[main]).unwrap_err().message.contains("function 5 does not exist"));
+
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::Closure(0, Rc::new(vec![])), Span::dummy(1));
+  let mut inner = LoxFunction::new("inner");
+  inner.upvalues = 1;
+  assert!(verify(&[inner, main]).unwrap_err().message.contains("captures 1 upvalues, but 0 are given"));
+}
+
+#[test]
+fn rejects_unbalanced_stack() {
+  // one branch pushes a value the other does not
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::True, Span::dummy(1));
+  main.chunk.write(Ins::JumpIfFalse(1), Span::dummy(1));
+  main.chunk.write(Ins::Nil, Span::dummy(1));
+  main.chunk.write(Ins::Return, Span::dummy(1));
+  let err = verify(&[main]).unwrap_err();
+  assert_eq!(err.offset, 3);
+  assert!(err.message.contains("on one path and"), "{err}");
+
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::Pop, Span::dummy(1));
+  assert!(verify(&[main]).unwrap_err().message.contains("pops 1 values, but only 0"));
+}
+
+#[test]
+fn rejects_missing_locals() {
+  // a function's frame holds the callee and its arguments
+  let mut add = LoxFunction::new("add");
+  add.arity = 2;
+  add.chunk.write(Ins::GetLocal(2), Span::dummy(1));
+  add.chunk.write(Ins::SetLocal(4), Span::dummy(1));
+  let main = LoxFunction::new("<script>");
+  let err = verify(&[add, main]).unwrap_err();
+  assert_eq!((err.offset, err.message.as_str()), (1, "local slot 4 is out of range"));
+}
+
+#[test]
+fn rejects_bad_arity() {
+  let mut main = LoxFunction::new("<script>");
+  main.arity = 1;
+  assert!(verify(&[main]).unwrap_err().message.contains("takes no parameters"));
+
+  let mut main = LoxFunction::new("<script>");
+  main.chunk.write(Ins::Call(300), Span::dummy(1));
+  assert!(verify(&[main]).unwrap_err().message.contains("300 arguments exceed the limit of 255"));
+}
+
+#[test]
+fn rejects_bad_upvalue_counts() {
+  // the script body is wrapped in a closure with no upvalues before it runs
+  let mut main = LoxFunction::new("<script>");
+  main.upvalues = 1 << 40;
+  assert!(verify(&[main]).unwrap_err().message.contains("captures nothing"));
+
+  // a function no closure is made of is still bounded
+  let mut unused = LoxFunction::new("unused");
+  unused.upvalues = 1 << 40;
+  let main = LoxFunction::new("<script>");
+  let err = verify(&[unused, main]).unwrap_err();
+  assert_eq!(err.index, 0);
+  assert!(err.message.contains("exceed the limit of 512"));
+}
@@ -1,10 +1,11 @@
 use std::{
+  any::Any,
   fmt::{Debug, Display},
   ops::{Neg, Not},
   rc::Rc
 };
 
-use crate::common::data::LoxObject;
+use crate::common::data::{Foreign, LoxClass, LoxInstance, LoxObject};
 
 #[derive(Clone, PartialEq)]
 pub enum Value {
@@ -37,17 +38,75 @@ impl Value {
   }
 
   /// Checks if two `LoxValue`s are equal. No type coercion is performed so both types must be equal.
+  ///
+  /// `nil` equals only `nil`. Booleans, numbers and strings compare by value, so `NaN` is never
+  /// equal to anything, itself included. Everything else compares by identity, except that bound
+  /// methods are equal when they bind the same method to the same instance, so `a.m == a.m`.
+  /// `LoxValue::equals` of the tree-walker follows the same rules.
   pub fn equals(&self, other: &Self) -> bool {
     use Value::*;
     match (self, other) {
       (Boolean(a), Boolean(b)) => a == b,
       (Number(a), Number(b)) => a == b,
       (Nil, Nil) => true,
-      (Object(a), Object(b)) => a == b,
+      (Object(a), Object(b)) => match (a.as_ref(), b.as_ref()) {
+        // symbols are interned, so identity is equality
+        (LoxObject::Symbol(_), _) | (_, LoxObject::Symbol(_)) => Rc::ptr_eq(a, b),
+        _ => a == b
+      },
       _ => false,
     }
   }
 
+  /// Wraps a host resource in a handle tagged `tag`, for natives to return to scripts.
+  pub fn foreign<T: Any>(tag: &str, data: T) -> Self {
+    Self::from(LoxObject::Foreign(Foreign::new(tag, data)))
+  }
+
+  /// The resource of a handle tagged `tag`, if this is one and it holds a `T`.
+  pub fn as_foreign<T: Any>(&self, tag: &str) -> Option<&T> {
+    match self {
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::Foreign(handle) if handle.tag == tag => handle.downcast(),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /// The text, if this is a string.
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::String(s) => Some(s),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /// The class, if this is one.
+  pub fn as_class(&self) -> Option<&Rc<LoxClass>> {
+    match self {
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::Class(class) => Some(class),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /// The instance, if this is one.
+  pub fn as_instance(&self) -> Option<&Rc<LoxInstance>> {
+    match self {
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::Instance(instance) => Some(instance),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
   /// Creates a copy if literal, else clones the LoxObject pointer
   pub fn copy(&self) -> Self {
     use Value::*;
@@ -103,10 +162,7 @@ impl Not for Value {
   type Output = bool;
 
   fn not(self) -> Self::Output {
-    match self {
-      Value::Nil => false,
-      val => !val.truth()
-    }
+    !self.truth()
   }
 }
 
@@ -115,3 +171,9 @@ impl From<f64> for Value {
     Self::Number(value)
   }
 }
+
+impl From<LoxObject> for Value {
+  fn from(value: LoxObject) -> Self {
+    Self::Object(Rc::new(value))
+  }
+}
@@ -0,0 +1,228 @@
+//! Structural checks on bytecode that did not come straight from the compiler, such as cached
+//! images read back from disk.
+//!
+//! The VM reports bad operands as runtime errors rather than panicking, but a chunk that fails
+//! these checks is rejected before any of it runs. Besides operand ranges, every reachable
+//! instruction is walked with the depth of the frame's stack, so that no instruction pops below
+//! the callee slot or reads a local that is not there, and control flow only meets at equal depths.
+
+use std::{
+  borrow::Borrow,
+  fmt::{self, Display},
+};
+
+use crate::common::{data::LoxFunction, Ins};
+
+/// Largest number of parameters, and of arguments to a call, that the compiler accepts.
+pub const MAX_ARITY: usize = u8::MAX as usize;
+
+/// Largest number of upvalues a function captures, as the compiler limits its locals.
+pub const MAX_UPVALUES: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+  /// Name of the function holding the bad instruction.
+  pub function: String,
+  /// Index of that function in the functions given to [`verify`].
+  pub index: usize,
+  /// Index of the instruction in its chunk.
+  pub offset: usize,
+  pub message: String,
+}
+
+impl Display for VerifyError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "in `{}` at {:04}: {}", self.function, self.offset, self.message)
+  }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Checks every function of an image or module. Closure indices are relative to `functions`, and
+/// the last function is the script body.
+pub fn verify<F: Borrow<LoxFunction>>(functions: &[F]) -> Result<(), VerifyError> {
+  if let Some(script) = functions.last().map(Borrow::borrow) {
+    if script.arity != 0 {
+      return Err(VerifyError {
+        function: script.name.clone(),
+        index: functions.len() - 1,
+        offset: 0,
+        message: format!("the script body takes no parameters, but declares {}", script.arity),
+      });
+    }
+    if script.upvalues != 0 {
+      return Err(VerifyError {
+        function: script.name.clone(),
+        index: functions.len() - 1,
+        offset: 0,
+        message: format!("the script body captures nothing, but declares {} upvalues", script.upvalues),
+      });
+    }
+  }
+  functions.iter().map(Borrow::borrow).enumerate().try_for_each(|(index, function)| {
+    verify_operands(function, functions)
+      .and_then(|_| verify_stack(function))
+      .map_err(|err| VerifyError { index, ..err })
+  })
+}
+
+/// An error in `function`, whose index `verify` fills in.
+fn error(function: &LoxFunction, offset: usize, message: impl Into<String>) -> VerifyError {
+  VerifyError {
+    function: function.name.clone(),
+    index: 0,
+    offset,
+    message: message.into(),
+  }
+}
+
+/// Index of the instruction a jump at `offset` lands on. Offsets are relative to the next
+/// instruction, and landing just past the end returns.
+fn jump_target(offset: usize, jump: isize, len: usize) -> Option<usize> {
+  (offset + 1).checked_add_signed(jump).filter(|target| *target <= len)
+}
+
+fn verify_operands<F: Borrow<LoxFunction>>(function: &LoxFunction, functions: &[F]) -> Result<(), VerifyError> {
+  if function.arity > MAX_ARITY {
+    return Err(error(function, 0, format!(
+      "{} parameters exceed the limit of {MAX_ARITY}",
+      function.arity
+    )));
+  }
+  if function.upvalues > MAX_UPVALUES {
+    return Err(error(function, 0, format!(
+      "{} upvalues exceed the limit of {MAX_UPVALUES}",
+      function.upvalues
+    )));
+  }
+
+  let len = function.chunk.len();
+  for (offset, ins) in function.chunk.code.iter().enumerate() {
+    let error = |message: String| error(function, offset, message);
+
+    match ins {
+      Ins::Jump(jump) | Ins::JumpIfFalse(jump) | Ins::JumpIfTrue(jump) if jump_target(offset, *jump, len).is_none() => {
+        return Err(error(format!("jump by {jump} leaves the chunk")));
+      }
+      Ins::Constant(idx) if *idx >= function.chunk.constants.len() => {
+        return Err(error(format!("constant {idx} does not exist")));
+      }
+      Ins::GetUpval(slot) | Ins::SetUpval(slot) if *slot >= function.upvalues => {
+        return Err(error(format!(
+          "upvalue {slot} is out of range, the function has {}",
+          function.upvalues
+        )));
+      }
+      Ins::Call(args) if *args > MAX_ARITY => {
+        return Err(error(format!("{args} arguments exceed the limit of {MAX_ARITY}")));
+      }
+      Ins::Closure(idx, upvals) => {
+        let Some(target) = functions.get(*idx).map(Borrow::borrow) else {
+          return Err(error(format!("function {idx} does not exist")));
+        };
+        if upvals.len() != target.upvalues {
+          return Err(error(format!(
+            "`{}` captures {} upvalues, but {} are given",
+            target.name,
+            target.upvalues,
+            upvals.len()
+          )));
+        }
+        if let Some((_, idx)) = upvals.iter().find(|(is_local, idx)| !is_local && *idx >= function.upvalues) {
+          return Err(error(format!("captured upvalue {idx} is out of range")));
+        }
+      }
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+/// Values an instruction pops, and the values it pushes back.
+fn stack_effect(ins: &Ins) -> (usize, usize) {
+  use Ins::*;
+  match ins {
+    Constant(_) | True | False | Nil | GetGlobal(_) | GetLocal(_) | GetUpval(_) | Closure(..) | Class(_) => (0, 1),
+    Add | Subtract | Multiply | Divide | Equal | Greater | Less => (2, 1),
+    // the class stays below the method or subclass, and the instance below the value
+    Method(_) | Inherit | SetProperty(_) | GetSuper(_) => (2, 1),
+    Negate | Not | GetProperty(_) => (1, 1),
+    SetGlobal(_) | SetLocal(_) | SetUpval(_) | JumpIfFalse(_) | JumpIfTrue(_) => (1, 1),
+    DefGlobal(_) | CloseUpval | Print | Echo | Pop => (1, 0),
+    PopN(n) => (*n, 0),
+    Call(args) => (args + 1, 1),
+    Jump(_) => (0, 0),
+    StartTimer => (0, 2),
+    // the start is popped with the locals of the block
+    StopTimer => (2, 2),
+    Return => (1, 0),
+  }
+}
+
+fn verify_stack(function: &LoxFunction) -> Result<(), VerifyError> {
+  stack_depths(function).map(|_| ())
+}
+
+/// Walks every path through `function`, tracking how many values its frame holds. The frame
+/// starts with the callee and its arguments.
+///
+/// Returns the depth before each instruction, or `None` for instructions no path reaches.
+pub fn stack_depths(function: &LoxFunction) -> Result<Vec<Option<usize>>, VerifyError> {
+  let code = &function.chunk.code;
+  let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+  let mut pending = vec![(0, function.arity + 1)];
+
+  while let Some((offset, depth)) = pending.pop() {
+    let Some(ins) = code.get(offset) else {
+      // ran off the end, which returns like an empty `Return`
+      continue;
+    };
+    match depths[offset] {
+      Some(seen) if seen == depth => continue,
+      Some(seen) => {
+        return Err(error(function, offset, format!(
+          "reached with {depth} values on the stack on one path and {seen} on another"
+        )));
+      }
+      None => depths[offset] = Some(depth),
+    }
+
+    // locals are read from below the values they are computed with
+    match ins {
+      Ins::GetLocal(slot) | Ins::SetLocal(slot) if *slot >= depth => {
+        return Err(error(function, offset, format!("local slot {slot} is out of range")));
+      }
+      Ins::Closure(_, upvals) => {
+        if let Some((_, slot)) = upvals.iter().find(|(is_local, slot)| *is_local && *slot >= depth) {
+          return Err(error(function, offset, format!("captured local slot {slot} is out of range")));
+        }
+      }
+      _ => {}
+    }
+
+    let (pops, pushes) = stack_effect(ins);
+    // the callee slot is never popped; the frame drops it on return
+    if depth < pops + 1 {
+      return Err(error(function, offset, format!(
+        "pops {pops} values, but only {} are on the stack",
+        depth - 1
+      )));
+    }
+    let depth = depth - pops + pushes;
+
+    match ins {
+      Ins::Return => {}
+      Ins::Jump(jump) | Ins::JumpIfFalse(jump) | Ins::JumpIfTrue(jump) => {
+        let Some(target) = jump_target(offset, *jump, code.len()) else {
+          return Err(error(function, offset, format!("jump by {jump} leaves the chunk")));
+        };
+        pending.push((target, depth));
+        if matches!(ins, Ins::JumpIfFalse(_) | Ins::JumpIfTrue(_)) {
+          pending.push((offset + 1, depth));
+        }
+      }
+      _ => pending.push((offset + 1, depth)),
+    }
+  }
+  Ok(depths)
+}
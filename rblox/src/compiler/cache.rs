@@ -0,0 +1,46 @@
+//! On-disk cache of compiled imports.
+
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use crate::common::{serialize::{self, Flags, Image}, verify};
+
+/// Returns the cache file for `source` inside `dir`. The name is keyed on the source path so that
+/// files with the same name in different directories do not collide.
+pub fn path_for(dir: &Path, source: &Path) -> PathBuf {
+  let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+  let key = serialize::hash(source.to_string_lossy().as_bytes());
+  dir.join(format!("{stem}-{key:016x}.loxc"))
+}
+
+/// Loads a cached image, provided it is still what compiling the source with `hash` and `flags`
+/// would give, and its bytecode passes verification.
+pub fn load(path: &Path, hash: u64, flags: Flags) -> Option<Image> {
+  let image = Image::decode(&fs::read(path).ok()?)?;
+  if !is_fresh(&image, hash, flags) {
+    return None;
+  }
+  if let Err(err) = verify::verify(&image.functions) {
+    // the source is still there, so a bad image only costs a recompile
+    eprintln!("Ignoring invalid bytecode in `{}`: {err}", path.display());
+    return None;
+  }
+  Some(image)
+}
+
+/// Whether `image` was compiled by this compiler with `flags` from a source with `hash`, and none
+/// of the files it imported have changed since.
+pub fn is_fresh(image: &Image, hash: u64, flags: Flags) -> bool {
+  image.is_current(hash, flags) && image.deps.iter().all(|(dep, dep_hash, _)| {
+    fs::read(dep).is_ok_and(|src| serialize::hash(&src) == *dep_hash)
+  })
+}
+
+pub fn store(path: &Path, bytes: &[u8]) -> io::Result<()> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  fs::write(path, bytes)
+}
@@ -0,0 +1,169 @@
+//! Reference grammar of the language accepted by the compiler, printed by `--grammar`.
+//!
+//! Declarations and statements are read from the `grammar:` lines in the doc comments of the
+//! parser functions that implement them. Expressions are generated from the `ParseRule` table,
+//! so operators and precedence levels are listed exactly as the Pratt parser sees them.
+
+use std::rc::Rc;
+
+use super::{
+  parser::rules::{ParseFn, ParseRule, Precedence},
+  scanner::token::TokenType,
+};
+
+const PARSER_SRC: &str = include_str!("parser/mod.rs");
+
+/// A grammar rule, `name → body ;`.
+pub struct Production {
+  pub name: String,
+  pub body: String,
+}
+
+/// Returns the grammar as one production per line.
+pub fn grammar() -> String {
+  let productions = productions();
+  let width = productions.iter().map(|p| p.name.len()).max().unwrap_or(0);
+  productions
+    .iter()
+    .map(|p| format!("{:width$} → {} ;\n", p.name, p.body))
+    .collect()
+}
+
+/// Annotated productions in source order, followed by the generated expression productions.
+pub fn productions() -> Vec<Production> {
+  let mut productions = annotated(PARSER_SRC);
+  let generated = expressions()
+    .into_iter()
+    .filter(|p| !productions.iter().any(|q| q.name == p.name))
+    .collect::<Vec<_>>();
+  productions.extend(generated);
+  productions
+}
+
+/// Collects the `/// grammar: name → body ;` lines of `src`.
+fn annotated(src: &str) -> Vec<Production> {
+  src
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("/// grammar:"))
+    .filter_map(|rule| {
+      let (name, body) = rule.split_once('→')?;
+      let body = body.trim().trim_end_matches(';').trim_end();
+      Some(Production { name: name.trim().into(), body: body.into() })
+    })
+    .collect()
+}
+
+/// Every token kind the parser sees, with placeholder payloads. A test checks it against the
+/// declaration of [`TokenType`].
+pub(crate) fn token_kinds() -> Vec<TokenType> {
+  use TokenType::*;
+  let text: Rc<str> = "".into();
+  vec![
+    LeftParen, RightParen, LeftBrace, RightBrace, Comma, Dot, Minus, Plus, Semicolon, Star,
+    Slash, Bang, BangEqual, Equal, EqualEqual, Greater, GreaterEqual, Less, LessEqual,
+    Identifier(text.clone()), String(text.clone()), Symbol(text), Number(0.0),
+    And, Class, Else, False, Fun, For, If, Import, Native, Nil, Or, Print, Return, Super, This, True,
+    Var, While, Break, Continue, EOF,
+  ]
+}
+
+/// Spelling of a token in the grammar: literals by class, everything else quoted.
+fn terminal(kind: &TokenType) -> String {
+  use TokenType::*;
+  match kind {
+    Identifier(_) => "IDENTIFIER".into(),
+    String(_) => "STRING".into(),
+    Symbol(_) => "SYMBOL".into(),
+    Number(_) => "NUMBER".into(),
+    kind => format!("\"{}\"", kind),
+  }
+}
+
+/// Name of the production parsing operands at `prec` and above.
+fn level_name(prec: &Precedence) -> &'static str {
+  use Precedence::*;
+  match prec {
+    None => "expression",
+    Sequence => "sequence",
+    Assignment => "assignment",
+    Or => "or",
+    And => "and",
+    Equality => "equality",
+    Comparision => "comparison",
+    Term => "term",
+    Factor => "factor",
+    Unary => "unary",
+    Call => "call",
+    Primary => "primary",
+  }
+}
+
+fn alternatives(terms: &[String]) -> String {
+  match terms {
+    [term] => term.clone(),
+    terms => format!("( {} )", terms.join(" | ")),
+  }
+}
+
+/// Productions implied by the `ParseRule` table, from `expression` down to `primary`.
+fn expressions() -> Vec<Production> {
+  let rules: Vec<(TokenType, ParseRule)> = token_kinds()
+    .into_iter()
+    .map(|kind| {
+      let rule = ParseRule::from(&kind);
+      (kind, rule)
+    })
+    .collect();
+  let terminals = |select: &dyn Fn(&ParseRule) -> bool| -> Vec<String> {
+    rules.iter().filter(|(_, rule)| select(rule)).map(|(kind, _)| terminal(kind)).collect()
+  };
+
+  let mut productions = vec![Production {
+    name: "expression".into(),
+    body: level_name(&Precedence::Assignment).into(),
+  }];
+
+  // binary levels: each parses the next level, then any of its operators followed by the next level
+  let mut prec = Precedence::Sequence;
+  while prec < Precedence::Unary {
+    let next = prec.update(1);
+    let ops = terminals(&|rule| rule.2 == prec && matches!(rule.1, ParseFn::Binary | ParseFn::And | ParseFn::Or));
+    let body = match ops.is_empty() {
+      true => level_name(&next).to_string(),
+      false => format!("{0} ( {1} {0} )*", level_name(&next), alternatives(&ops)),
+    };
+    productions.push(Production { name: level_name(&prec).into(), body });
+    prec = next;
+  }
+
+  let unary = terminals(&|rule| matches!(rule.0, ParseFn::Unary));
+  productions.push(Production {
+    name: "unary".into(),
+    body: format!("{} unary | call", alternatives(&unary)),
+  });
+
+  let calls = terminals(&|rule| matches!(rule.1, ParseFn::Call));
+  let dots = terminals(&|rule| matches!(rule.1, ParseFn::Dot));
+  productions.push(Production {
+    name: "call".into(),
+    body: format!(
+      "primary ( {} arguments? \")\" | {} IDENTIFIER )*",
+      alternatives(&calls),
+      alternatives(&dots)
+    ),
+  });
+
+  let primary = rules
+    .iter()
+    .filter_map(|(kind, rule)| match rule.0 {
+      ParseFn::Group => Some(format!("{} sequence \")\"", terminal(kind))),
+      ParseFn::Number | ParseFn::Literal | ParseFn::String | ParseFn::Symbol | ParseFn::Variable
+      | ParseFn::This => Some(terminal(kind)),
+      ParseFn::Super => Some(format!("{} \".\" IDENTIFIER", terminal(kind))),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  productions.push(Production { name: "primary".into(), body: primary.join(" | ") });
+
+  productions
+}
@@ -0,0 +1,88 @@
+//! Hoisting of loads out of loops.
+//!
+//! A loop that reads a global or a captured variable looks it up again on every iteration. When
+//! nothing can assign to the variable while the loop runs, it is loaded once before the loop into
+//! a hidden local, and the loop reads that local instead. The local is popped when the loop ends.
+//!
+//! A global qualifies if the source declares it at the top level before the loop, so that it is
+//! defined by the time the loop runs, and never assigns to it. A captured variable qualifies if
+//! the source never assigns to its name. Sources that import other files hoist nothing, since
+//! those may assign to anything.
+
+use std::{collections::{HashMap, HashSet}, rc::Rc};
+
+use crate::{
+  common::{Ins, Span},
+  compiler::{scanner::{token::TokenType, Scanner}, Compiler},
+};
+
+/// Variables that loops of one source may load once.
+#[derive(Debug, Default)]
+pub struct Hoister {
+  /// Names assigned to anywhere in the source.
+  assigned: HashSet<String>,
+}
+
+impl Hoister {
+  /// Returns a hoister for `src`, scanned with `defines`, or `None` if nothing in it may be hoisted.
+  pub fn new(src: &str, defines: &HashMap<String, String>) -> Option<Self> {
+    let mut assigned = HashSet::new();
+    let mut prev = TokenType::EOF;
+    let mut scanner = Scanner::new(src);
+    scanner.set_defines(defines.clone());
+    let mut tokens = scanner.map(|token| token.kind).peekable();
+    while let Some(kind) = tokens.next() {
+      match (&prev, &kind) {
+        (_, TokenType::Import) => return None,
+        // the initializer of a declaration is not an assignment
+        (TokenType::Var, _) => {}
+        (_, TokenType::Identifier(name)) if tokens.peek() == Some(&TokenType::Equal) => {
+          assigned.insert(name.to_string());
+        }
+        _ => {}
+      }
+      prev = kind;
+    }
+    Some(Self { assigned })
+  }
+
+  /// Hoists the loads of the loop that `compiler` compiled from offset `start`, and pops the
+  /// hidden locals at `end`. `globals` are the globals declared before the loop.
+  pub fn hoist(&self, compiler: &mut Compiler, start: usize, globals: &[String], end: Span) {
+    let mut loads: Vec<(Ins, Span)> = Vec::new();
+    for (ins, span) in compiler.function.chunk.iter_zip().skip(start) {
+      let invariant = match ins {
+        Ins::GetGlobal(name) => globals.contains(name) && !self.assigned.contains(name),
+        Ins::GetUpval(idx) => !self.assigned.contains(&compiler.upvalue_names[*idx]),
+        _ => false,
+      };
+      if invariant && !loads.iter().any(|(load, _)| load == ins) {
+        loads.push((ins.clone(), *span));
+      }
+    }
+    if loads.is_empty() {
+      return
+    }
+
+    // the hidden locals sit below the locals of the loop body, which move up to make room
+    let base = compiler.locals.len();
+    let hidden = loads.len();
+    let shift = |slot: usize| if slot >= base { slot + hidden } else { slot };
+    for ins in &mut compiler.function.chunk.code[start..] {
+      *ins = match &*ins {
+        Ins::GetLocal(slot) => Ins::GetLocal(shift(*slot)),
+        Ins::SetLocal(slot) => Ins::SetLocal(shift(*slot)),
+        Ins::Closure(fun, upvals) => {
+          let upvals = upvals.iter().map(|&(local, idx)| (local, if local { shift(idx) } else { idx }));
+          Ins::Closure(*fun, Rc::new(upvals.collect()))
+        }
+        load => match loads.iter().position(|(hoisted, _)| hoisted == load) {
+          Some(idx) => Ins::GetLocal(base + idx),
+          None => continue,
+        },
+      };
+    }
+    compiler.chunk().insert(start, loads);
+    compiler.emit(Ins::PopN(hidden), end);
+  }
+}
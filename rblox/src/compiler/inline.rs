@@ -0,0 +1,155 @@
+//! Inlining of small functions.
+//!
+//! A top-level function whose body loads each of its parameters once, in order, and then only
+//! combines them with literals and operators is recorded when it is declared. Later calls to it
+//! by name compile to its body instead of a call: the arguments are left on the stack exactly
+//! where the body expects its parameters, so the body runs unchanged and leaves the result where
+//! the call would have.
+//!
+//! The global holding the function must not change, so functions whose name the source declares
+//! again, declares with `var` or `class` or assigns to are never inlined. Neither are functions of
+//! a source that imports other files, since those may assign to its globals. A later source run on
+//! the same VM that reassigns the function does not affect calls that are already inlined.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+  common::{data::LoxFunction, Inlined, Ins, Span, Value},
+  compiler::{scanner::{token::TokenType, Scanner}, Compiler},
+};
+
+/// Body of a function that calls can be replaced with.
+#[derive(Debug)]
+pub struct Inline {
+  name: String,
+  pub arity: usize,
+  /// Instructions after the parameters are loaded, without the final `Return`.
+  body: Vec<(Ins, Span)>,
+  /// Constants of the function, which the body refers to by index.
+  constants: Vec<Value>,
+  /// Calls inlined into the body, at offsets into it.
+  inlined: Vec<Inlined>,
+}
+
+impl Inline {
+  /// Most instructions a body may have, counting the loads of its parameters.
+  pub const MAX_LEN: usize = 8;
+
+  /// Returns the body of `fun` if it is small and simple enough to inline.
+  pub fn of(fun: &LoxFunction) -> Option<Self> {
+    if fun.upvalues > 0 {
+      return None
+    }
+    let code = &fun.chunk.code;
+    let end = code.iter().position(|ins| *ins == Ins::Return)?;
+    // an explicit `return` is followed by the implicit one at the end of every function
+    if end > Self::MAX_LEN || !matches!(&code[end + 1..], [] | [Ins::Nil, Ins::Return]) {
+      return None
+    }
+
+    let n = fun.arity;
+    let loads_params = (0..n).all(|i| code.get(i) == Some(&Ins::GetLocal(i + 1)));
+    if !loads_params {
+      return None
+    }
+
+    let mut depth = n;
+    for ins in &code[n..end] {
+      use Ins::*;
+      depth = match ins {
+        Constant(_) | True | False | Nil => depth + 1,
+        Negate | Not if depth >= 1 => depth,
+        Add | Subtract | Multiply | Divide | Equal | Greater | Less if depth >= 2 => depth - 1,
+        _ => return None,
+      };
+    }
+    if depth != 1 {
+      return None
+    }
+
+    let body = fun.chunk.iter_zip().take(end).skip(n).map(|(ins, span)| (ins.clone(), *span)).collect();
+    let inlined = fun.chunk.inlined.iter()
+      .map(|call| Inlined { code: call.code.start - n..call.code.end - n, ..call.clone() })
+      .collect();
+    Some(Self {
+      name: fun.name.clone(),
+      arity: n,
+      body,
+      constants: fun.chunk.constants.clone(),
+      inlined
+    })
+  }
+
+  /// Compiles the body into `compiler`, after the arguments of the call at `span`. The call is
+  /// recorded so that stack traces still show it.
+  pub fn splice(&self, compiler: &mut Compiler, span: Span) {
+    let start = compiler.chunk().len();
+    for (ins, span) in &self.body {
+      match ins {
+        Ins::Constant(idx) => compiler.emit_constant(self.constants[*idx].clone(), *span),
+        ins => compiler.emit(ins.clone(), *span),
+      };
+    }
+    let chunk = compiler.chunk();
+    let nested = self.inlined.iter()
+      .map(|call| Inlined { code: call.code.start + start..call.code.end + start, ..call.clone() });
+    chunk.inlined.extend(nested);
+    chunk.inlined.push(Inlined { code: start..chunk.len(), name: self.name.clone(), span });
+  }
+}
+
+/// Functions of one source that can be inlined.
+#[derive(Debug, Default)]
+pub struct Inliner {
+  unstable: HashSet<String>,
+  functions: HashMap<String, Inline>,
+}
+
+impl Inliner {
+  /// Returns an inliner for `src`, scanned with `defines`, or `None` if nothing in it may be inlined.
+  pub fn new(src: &str, defines: &HashMap<String, String>) -> Option<Self> {
+    let mut declared = HashSet::new();
+    let mut unstable = HashSet::new();
+    let mut prev = TokenType::EOF;
+    let mut scanner = Scanner::new(src);
+    scanner.set_defines(defines.clone());
+    let mut tokens = scanner.map(|token| token.kind).peekable();
+    while let Some(kind) = tokens.next() {
+      match (&prev, &kind) {
+        (_, TokenType::Import) => return None,
+        (TokenType::Fun, TokenType::Identifier(name)) if !declared.insert(name.clone()) => {
+          unstable.insert(name.to_string());
+        }
+        (TokenType::Var | TokenType::Class, TokenType::Identifier(name)) => {
+          unstable.insert(name.to_string());
+        }
+        (_, TokenType::Identifier(name)) if tokens.peek() == Some(&TokenType::Equal) => {
+          unstable.insert(name.to_string());
+        }
+        _ => {}
+      }
+      prev = kind;
+    }
+    Some(Self { unstable, functions: HashMap::new() })
+  }
+
+  /// Records the function just declared as `name`, if calls to it can be inlined.
+  pub fn declare(&mut self, name: &str, fun: &LoxFunction) {
+    if self.unstable.contains(name) {
+      return
+    }
+    if let Some(inline) = Inline::of(fun) {
+      self.functions.insert(name.into(), inline);
+    }
+  }
+
+  /// Whether calls to the global `name` may be inlined, given the right number of arguments.
+  pub fn has(&self, name: &str) -> bool {
+    self.functions.contains_key(name)
+  }
+
+  /// Body to compile a call to the global `name` with `args` arguments to, if any.
+  pub fn get(&self, name: &str, args: usize) -> Option<&Inline> {
+    self.functions.get(name).filter(|inline| inline.arity == args)
+  }
+}
@@ -1,13 +1,14 @@
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, io::Read, rc::Rc};
 
 use scope::Module;
 
 use crate::{
-  common::{data::{LoxFunction, LoxObject}, error::ErrorLevel, Chunk, Ins, Span},
+  common::{data::{LoxFunction, LoxObject}, error::ErrorLevel, Chunk, Ins, Span, Value},
   compiler::{
     parser::{
       error::ParseError,
+      state::ParserOptions,
       PResult, Parser, ParserOutcome
     },
     scope::Local
@@ -21,9 +22,21 @@ pub mod scanner;
 pub mod parser;
 
 pub mod scope;
+pub mod inline;
+pub mod hoist;
+pub mod cache;
+pub mod grammar;
+
+pub fn compile(src: &str, module: Rc<RefCell<Module>>, options: &ParserOptions) -> ParserOutcome {
+  let mut parser = Parser::new(src, module);
+  parser.options = options.clone();
+  parser.parse()
+}
 
-pub fn compile(src: &str, module: Rc<RefCell<Module>>) -> ParserOutcome {
-  let parser = Parser::new(src, module);
+/// Like [`compile`], but reads the source from `reader` as it is compiled rather than all at once.
+pub fn compile_reader(reader: impl Read, module: Rc<RefCell<Module>>, options: &ParserOptions) -> ParserOutcome {
+  let mut parser = Parser::from_reader(reader, module);
+  parser.options = options.clone();
   parser.parse()
 }
 
@@ -32,13 +45,30 @@ pub struct Compiler {
   pub fun_type: FunctionType,
   pub locals: Vec<Local>,
   scope_depth: i32,
-  enclosing: Option<Box<RefCell<Compiler>>>,
   upvalues: Vec<(bool, usize)>,
+  /// Name of the variable each upvalue captures.
+  upvalue_names: Vec<String>,
+  /// Loops enclosing the code being compiled, innermost last.
+  loops: Vec<Loop>,
+}
+
+/// A loop being compiled, which `break` and `continue` leave.
+struct Loop {
+  /// Offset that `continue` jumps back to.
+  start: usize,
+  /// Scope depth around the body. Locals deeper than this are popped on leaving the body.
+  depth: i32,
+  /// `break` jumps to patch once the end of the loop is known.
+  breaks: Vec<(usize, Span)>,
 }
 
 #[derive(PartialEq)]
 pub enum FunctionType {
   Function,
+  /// A method, whose slot 0 holds `this`.
+  Method,
+  /// The `init` method, which always returns `this`.
+  Initializer,
   Native,
   Script,
 }
@@ -52,8 +82,12 @@ impl Compiler {
 
   fn build(name: &str, fun_type: FunctionType) -> Self {
     let mut locals = Vec::with_capacity(Self::LOCALS_MIN);
+    let slot = match fun_type {
+      FunctionType::Method | FunctionType::Initializer => "this",
+      _ => name,
+    };
     locals.push(Local {
-      name: name.into(),
+      name: slot.into(),
       span: Span::new(0,0,0),
       depth: 0,
       captured: false
@@ -64,8 +98,9 @@ impl Compiler {
       fun_type,
       locals,
       scope_depth: 0,
-      enclosing: None,
-      upvalues: Vec::new()
+      upvalues: Vec::new(),
+      upvalue_names: Vec::new(),
+      loops: Vec::new(),
     }
   }
 
@@ -73,27 +108,49 @@ impl Compiler {
     &mut self.function.chunk
   }
 
+  /// Whether the code emitted since `start` may do more than compute a value, such as assign,
+  /// call or print. An inlined call counts as a call, whatever its body does.
+  fn has_effects(&self, start: usize) -> bool {
+    use Ins::*;
+    let chunk = &self.function.chunk;
+    chunk.inlined.iter().any(|call| call.code.end > start) || chunk.code[start..].iter().any(|ins| !matches!(ins,
+      Constant(_) | True | False | Nil | GetGlobal(_) | GetLocal(_) | GetUpval(_) |
+      Add | Subtract | Multiply | Divide | Negate | Not | Equal | Greater | Less |
+      Jump(_) | JumpIfFalse(_) | JumpIfTrue(_) | Pop
+    ))
+  }
+
   fn begin_scope(&mut self) {
     self.scope_depth += 1;
   }
 
   fn end_scope(&mut self, span: Span) {
     self.scope_depth -= 1;
+    self.discard_locals(self.scope_depth, span);
+    while self.locals.last().is_some_and(|local| local.depth > self.scope_depth) {
+      self.locals.pop();
+    }
+  }
+
+  /// Emits the pops of the locals deeper than `depth`, closing the captured ones, but keeps
+  /// compiling with them in scope.
+  fn discard_locals(&mut self, depth: i32, span: Span) {
+    let captured: Vec<bool> = self.locals.iter().rev()
+      .take_while(|local| local.depth > depth)
+      .map(|local| local.captured)
+      .collect();
 
     let mut pops = 0;
-    while self.locals.len() > 0 && 
-    self.locals.last().unwrap().depth > self.scope_depth {
-      if self.locals.last().unwrap().captured {
+    for captured in captured {
+      if captured {
         if pops > 0 {
           self.emit(Ins::PopN(pops), span);
           pops = 0;
         }
         self.emit(Ins::CloseUpval, span);
-
       } else {
-      pops += 1;
+        pops += 1;
       }
-      self.locals.pop();
     }
 
     if pops > 0 {
@@ -101,6 +158,46 @@ impl Compiler {
     }
   }
 
+  /// Starts a loop whose body is compiled next, and which `continue` resumes at `start`.
+  fn begin_loop(&mut self, start: usize) {
+    self.loops.push(Loop { start, depth: self.scope_depth, breaks: Vec::new() });
+  }
+
+  /// Ends the innermost loop, so that its `break`s jump to the next instruction.
+  fn end_loop(&mut self) -> PResult<()> {
+    let lp = self.loops.pop().expect("a loop to end");
+    lp.breaks.into_iter().try_for_each(|(offset, span)| self.patch_jump(offset, span))
+  }
+
+  /// Compiles a `break` out of the innermost loop.
+  fn emit_break(&mut self, span: Span) -> PResult<()> {
+    let Some(depth) = self.loops.last().map(|lp| lp.depth) else {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: "Can't use `break` outside of a loop".into(),
+        span
+      })
+    };
+    self.discard_locals(depth, span);
+    let offset = self.emit(Ins::Jump(-1), span);
+    self.loops.last_mut().unwrap().breaks.push((offset, span));
+    Ok(())
+  }
+
+  /// Compiles a `continue` of the innermost loop.
+  fn emit_continue(&mut self, span: Span) -> PResult<()> {
+    let Some((start, depth)) = self.loops.last().map(|lp| (lp.start, lp.depth)) else {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: "Can't use `continue` outside of a loop".into(),
+        span
+      })
+    };
+    self.discard_locals(depth, span);
+    self.emit_loop(start, span)?;
+    Ok(())
+  }
+
   fn declare_variable(&mut self, ident: &LoxObject, span: Span) -> PResult<()> {
     if self.scope_depth == 0 {
       return Ok(())
@@ -111,7 +208,7 @@ impl Compiler {
       _ => unreachable!()
     };
 
-    if self.locals.len() == 0 {
+    if self.locals.is_empty() {
       self.add_local(name, span)?;
       return Ok(())
     }
@@ -128,7 +225,7 @@ impl Compiler {
           level: ErrorLevel::Warning, 
           message: format!("Variable `{name}` is already declared in this scope"), 
           span
-        });
+        }.with_label(local.span, "previous declaration here"));
         break;
       }
     }
@@ -165,7 +262,7 @@ impl Compiler {
   }
 
   fn resolve_local(&self, name: &str) -> PResult<Option<usize>> {
-    if self.locals.len() == 0 {
+    if self.locals.is_empty() {
       return Ok(None)
     }
     for (i, local) in self.locals.iter().enumerate().rev() {
@@ -183,38 +280,7 @@ impl Compiler {
     Ok(None)
   }
 
-  fn resolve_upvalue(&mut self, name: &str, span: Span) -> PResult<Option<usize>> {
-    let local = if let Some(enc) = &self.enclosing {
-      let mut enc = enc.borrow_mut();
-      if let Some(local) = enc.resolve_local(name)? {
-        Some((true, local))
-      } else if let Some(upv) = enc.resolve_upvalue(name, span)? {
-        Some((false, upv))
-      } else {
-        None
-      }
-    } else {
-      None
-    };
-
-    match local {
-      Some((is_loc, idx)) => {
-        if is_loc {
-          if let Some(enc) = &self.enclosing {
-            let mut enc = enc.as_ref().borrow_mut();
-            let val = enc.locals.get_mut(idx).unwrap();
-            val.captured = true;
-          } else {
-            unreachable!("Enclosing compiler should exist since `is_loc` is true")
-          };
-        }
-        Ok(Some(self.add_upvalue((is_loc, idx), span)?))
-      },
-      None => Ok(None)
-    }
-  }
-
-  fn add_upvalue(&mut self, local: (bool, usize), span: Span) -> PResult<usize> {
+  fn add_upvalue(&mut self, name: &str, local: (bool, usize), span: Span) -> PResult<usize> {
     let count = self.function.upvalues;
 
     for (off, pair) in self.upvalues.iter().enumerate() {
@@ -230,35 +296,50 @@ impl Compiler {
     }
 
     self.upvalues.push(local);
+    self.upvalue_names.push(name.into());
     self.function.upvalues += 1;
 
     Ok(count)
   }
 
-  fn bind(&mut self, enclosing: Compiler) {
-    self.enclosing = Some(Box::new(RefCell::new(enclosing)));
-  }
-
-  fn unbind(&mut self) -> Compiler {
-    let enclosing = match self.enclosing.take() {
-      Some(enc) => enc.into_inner(),
-      None => unreachable!("`unbind` should always be called after `bind`.")
-    };
+}
 
-    enclosing
+/// Resolves `name` as an upvalue of the innermost compiler in `stack`, capturing it through every
+/// enclosing function between the one that declares it and the innermost one.
+fn resolve_upvalue(stack: &mut [Compiler], name: &str, span: Span) -> PResult<Option<usize>> {
+  let Some((current, enclosing)) = stack.split_last_mut() else {
+    return Ok(None)
+  };
+  let Some(parent) = enclosing.last_mut() else {
+    return Ok(None)
+  };
+
+  if let Some(idx) = parent.resolve_local(name)? {
+    parent.locals[idx].captured = true;
+    return current.add_upvalue(name, (true, idx), span).map(Some)
   }
 
+  match resolve_upvalue(enclosing, name, span)? {
+    Some(idx) => current.add_upvalue(name, (false, idx), span).map(Some),
+    None => Ok(None)
+  }
 }
 
 /// Chunk writers
 impl Compiler {
-  const JUMP_MAX: usize = std::u16::MAX as usize;
+  const JUMP_MAX: usize = u16::MAX as usize;
   fn emit(&mut self, ins: Ins, span: Span) -> usize {
     let chunk = self.chunk();
     chunk.write(ins, span);
     chunk.len() - 1
   }
 
+  fn emit_constant(&mut self, value: Value, span: Span) -> usize {
+    let chunk = self.chunk();
+    chunk.write_constant(value, span);
+    chunk.len() - 1
+  }
+
   fn patch_jump(&mut self, offset: usize, span: Span) -> PResult<()> {
     let chunk = self.chunk();
 
@@ -274,12 +355,13 @@ impl Compiler {
     let ins = match chunk.get(offset).unwrap() {
       (Ins::Jump(_), _) => Ins::Jump(jump as isize),
       (Ins::JumpIfFalse(_), _) => Ins::JumpIfFalse(jump as isize),
+      (Ins::JumpIfTrue(_), _) => Ins::JumpIfTrue(jump as isize),
       (unexpected, span) => return Err(ParseError::InvalidJump { 
         message: format!("Not a jump instruction. Got {unexpected:?}"),
         span: *span
       })
     };
-    chunk.code[offset] = ins;
+    chunk.replace(offset, ins);
     Ok(())
   }
 
@@ -294,10 +376,11 @@ impl Compiler {
 
     let offset = chunk.len() + 1 - start;
     if offset > Self::JUMP_MAX {
+      let loop_span = chunk.get(start).map_or(span, |(_, span)| *span);
       return Err(ParseError::InvalidJump { 
         message: "Loop body too large".into(), 
         span 
-      })
+      }.with_label(loop_span, "loop started here"))
     }
 
     Ok(self.emit(Ins::Jump(-(offset as isize)), span))
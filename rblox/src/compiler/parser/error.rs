@@ -5,7 +5,7 @@ use std::{
 
 use crate::{
   common::{
-    error::{LoxError, ErrorLevel, ErrorType}, 
+    error::{LoxError, ErrorLevel, ErrorType, Label}, 
     Span,
   }, 
   compiler::scanner::{
@@ -43,6 +43,14 @@ pub enum ParseError {
     span: Span 
   },
 
+  /// An error with other spans that explain it, see [`ParseError::with_label`], or notes on how
+  /// to fix it, see [`ParseError::with_note`].
+  Labeled {
+    error: Box<ParseError>,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+  },
+
   _DetectedLambda,
 }
 
@@ -56,7 +64,7 @@ impl Display for ParseError {
       }
 
       ScanError { error, span } => {
-        write!(f, "{}; at position {}", error, span)
+        write!(f, "[{}] {}; at position {}", error.code(), error, span)
       }
 
       UnexpectedToken {
@@ -75,6 +83,8 @@ impl Display for ParseError {
 
       InvalidJump { message, span } => write!(f, "illegal jump - {message}; at position {span}"),
 
+      Labeled { error, .. } => error.fmt(f),
+
       _DetectedLambda => unreachable!(),
     }
   }
@@ -86,6 +96,7 @@ impl LoxError for ParseError {
   fn get_level(&self) -> ErrorLevel {
     match self {
       Self::Error { level, ..} => level.clone(),
+      Self::Labeled { error, .. } => error.get_level(),
       _ => ErrorLevel::Error
     }
   }
@@ -97,6 +108,20 @@ impl LoxError for ParseError {
   fn get_span(&self) -> Span {
     self.primary_span()
   }
+
+  fn labels(&self) -> Vec<Label> {
+    match self {
+      Self::Labeled { labels, .. } => labels.clone(),
+      _ => Vec::new(),
+    }
+  }
+
+  fn notes(&self) -> Vec<String> {
+    match self {
+      Self::Labeled { notes, .. } => notes.clone(),
+      _ => Vec::new(),
+    }
+  }
 }
 
 impl ParseError {
@@ -110,10 +135,34 @@ impl ParseError {
       StackOverflow { span, .. }
       => *span,
       UnexpectedToken { offending, .. } => offending.span,
+      Labeled { error, .. } => error.primary_span(),
       _DetectedLambda => unreachable!(),
     }
   }
 
+  /// Adds a label pointing at `span`.
+  pub fn with_label(self, span: Span, message: impl Into<String>) -> Self {
+    let label = Label::new(span, message);
+    match self {
+      ParseError::Labeled { error, mut labels, notes } => {
+        labels.push(label);
+        ParseError::Labeled { error, labels, notes }
+      }
+      error => ParseError::Labeled { error: Box::new(error), labels: vec![label], notes: Vec::new() },
+    }
+  }
+
+  /// Adds a note, reported after the error rather than as part of its message.
+  pub fn with_note(self, note: impl Into<String>) -> Self {
+    match self {
+      ParseError::Labeled { error, labels, mut notes } => {
+        notes.push(note.into());
+        ParseError::Labeled { error, labels, notes }
+      }
+      error => ParseError::Labeled { error: Box::new(error), labels: Vec::new(), notes: vec![note.into()] },
+    }
+  }
+
   #[allow(dead_code)]
   /// Checks if the error allows REPL continuation (aka. "..." prompt).
   pub fn allows_continuation(&self) -> bool {
@@ -121,6 +170,7 @@ impl ParseError {
     match self {
       UnexpectedToken { offending, .. } if offending.kind == TokenType::EOF => true,
       ScanError { error, .. } if error.allows_continuation() => true,
+      Labeled { error, .. } => error.allows_continuation(),
       _DetectedLambda => unreachable!(),
       _ => false,
     }
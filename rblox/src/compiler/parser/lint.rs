@@ -0,0 +1,90 @@
+//! Warnings that `// lox-ignore: <name>, ...` comments silence. The compiler makes a single pass
+//! without building statements, so a comment on a line of its own silences the named warnings
+//! on the line of the token after it, which starts the statement it leads.
+
+use std::mem;
+
+/// Kinds of warning, by the name a `lox-ignore` comment silences them with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+  NoEffect,
+  InitReturn,
+  AssignmentCondition,
+  ShadowedNative,
+  RedeclaredVariable,
+}
+
+impl Lint {
+  pub const ALL: [Lint; 5] = [
+    Lint::NoEffect,
+    Lint::InitReturn,
+    Lint::AssignmentCondition,
+    Lint::ShadowedNative,
+    Lint::RedeclaredVariable,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Lint::NoEffect => "no-effect",
+      Lint::InitReturn => "init-return",
+      Lint::AssignmentCondition => "assignment-condition",
+      Lint::ShadowedNative => "shadowed-native",
+      Lint::RedeclaredVariable => "redeclared-variable",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|lint| lint.name() == name)
+  }
+
+  /// Whether `name` is a warning of either engine, so that a script run on both can silence the
+  /// tree-walker's warnings without this one complaining.
+  pub fn is_known(name: &str) -> bool {
+    Self::from_name(name).is_some() || TREE_WALKER_ONLY.contains(&name)
+  }
+}
+
+/// Warnings that only the tree-walker's resolver raises.
+const TREE_WALKER_ONLY: [&str; 5] = [
+  "unused-variable",
+  "constructor-arity",
+  "unknown-variant",
+  "duplicate-case",
+  "non-exhaustive-switch",
+];
+
+/// Names listed by a `lox-ignore` comment, or `None` for any other comment.
+pub fn ignored_names(text: &str) -> Option<Vec<String>> {
+  let names = text.strip_prefix("//")?.trim_start().strip_prefix("lox-ignore:")?;
+  Some(names.split(',').map(str::trim).filter(|name| !name.is_empty()).map(Into::into).collect())
+}
+
+/// The `lox-ignore` comments scanned so far.
+#[derive(Debug, Default)]
+pub struct Ignores {
+  /// Names listed by comments that no token has followed yet.
+  pending: Vec<String>,
+  /// Line that comments silence warnings on, with the names they list.
+  lines: Vec<(u32, Vec<String>)>,
+}
+
+impl Ignores {
+  /// Adds the names of a comment, which apply from the next token on.
+  pub fn add(&mut self, names: Vec<String>) {
+    self.pending.extend(names);
+  }
+
+  /// Applies the comments scanned since the last token to `line`, the line of the next one.
+  pub fn next_token(&mut self, line: u32) {
+    if !self.pending.is_empty() {
+      self.lines.push((line, mem::take(&mut self.pending)));
+    }
+  }
+
+  /// Whether a comment silences `lint` on `line`.
+  pub fn silences(&self, lint: Lint, line: u32) -> bool {
+    self.lines.iter()
+      .filter(|(ignored, _)| *ignored == line)
+      .any(|(_, names)| names.iter().any(|name| name == lint.name()))
+  }
+}
@@ -1,31 +1,39 @@
 #[cfg(test)]
 mod tests;
 
-use std::{borrow::Borrow, cell::{RefCell, RefMut}, mem, rc::Rc};
+use std::{borrow::Borrow, cell::RefCell, collections::VecDeque, env, fs, io::Read, mem, path::{Path, PathBuf}, rc::Rc};
 
 use rules::ParseFn;
 
 use crate::{
   common::{
     data::{LoxObject, Push}, 
-    error::{ErrorLevel, LoxError}, 
-    Ins, Span
+    error::{ErrorLevel, LoxError, Message}, 
+    serialize::{self, Flags, Image},
+    Ins, Span, Value
   },
   compiler::{
     parser::{
       error::ParseError,
+      lint::{ignored_names, Ignores, Lint},
       rules::{ParseRule, Precedence},
-      state::ParserOptions
+      state::{ParserOptions, MAX_STRING_LEN}
     }, 
     scanner::{
+      error::ScanError,
+      identifier::MAX_IDENTIFIER_LEN,
       token::{Token, TokenType}, Scanner
     }, 
+    cache,
+    hoist::Hoister,
+    inline::Inliner,
     scope::Module,
-    Compiler, FunctionType
+    resolve_upvalue, Compiler, FunctionType
   }
 };
 
 pub mod error;
+pub mod lint;
 pub mod state;
 pub mod rules;
 
@@ -35,42 +43,96 @@ pub type PResult<T> = Result<T, ParseError>;
 pub type ParserOutcome = Vec<ParseError>;
 
 pub struct Parser<'src> {
+  /// The whole source, unless it is read from a stream.
+  src: Option<&'src str>,
   scanner: Scanner<'src>,
+  /// Tokens scanned ahead by `next_is`, which `advance` takes before scanning more.
+  lookahead: VecDeque<Token>,
   pub current_token: Token,
   pub prev_token: Token,
   panic_mode: bool,
   diagnostics: Vec<ParseError>,
-  pub _options: ParserOptions,
-  compiler: RefCell<Compiler>,
-  module: Rc<RefCell<Module>>
+  /// Blocks whose declarations are being parsed. Recovering from an error inside one stops at
+  /// the `}` that closes it.
+  open_blocks: usize,
+
+  pub options: ParserOptions,
+  /// Compilers of the functions being compiled, innermost last. The first is the script.
+  compilers: Vec<Compiler>,
+  module: Rc<RefCell<Module>>,
+  /// Globals declared by this source, added to the module once it compiles.
+  globals: Vec<String>,
+  /// Globals used inside functions, which may be declared after the function.
+  deferred_globals: Vec<(String, Span)>,
+  /// Arity of each native declared by this source.
+  natives: Vec<(String, usize)>,
+  /// Native declarations that the host registered no native for.
+  unbound_natives: Vec<(String, Span)>,
+  /// Top-level functions that calls are compiled to the body of, unless inlining is off.
+  inliner: Option<Inliner>,
+  /// Loads that loops make once before they start, unless hoisting is off.
+  hoister: Option<Hoister>,
+  /// Classes being compiled, innermost last, with whether each has a superclass.
+  classes: Vec<bool>,
+  /// Span of the last assignment compiled, which ends where its value does.
+  assignment: Option<Span>,
+  /// `lox-ignore` comments scanned so far, unless the source is read from a stream.
+  ignores: Ignores,
+  /// For an imported source, the `In module` prefix naming each import that led to it and the
+  /// span of the outermost one, where its warnings are reported.
+  imported_at: Option<(String, Span)>,
 }
 
 impl Parser<'_> {
   const MAX_ARGS: usize = 255;
   pub fn parse(mut self) -> ParserOutcome {
+    let options = &self.options;
+    // code that later sources may change the globals of is compiled as written
+    let optimize = !options.repl_mode && options.importing.is_empty();
+    // both look ahead through the whole source, which a stream can't be read twice for
+    if let (true, false, Some(src)) = (optimize, options.no_inline, self.src) {
+      self.inliner = Inliner::new(src, &options.defines);
+    }
+    if let (true, false, Some(src)) = (optimize, options.no_hoist, self.src) {
+      self.hoister = Hoister::new(src, &options.defines);
+    }
     self.parse_program();
     self.emit_return();
+    self.check_deferred_globals();
+    self.check_unbound_natives();
+    if self.diagnostics.is_empty() {
+      self.module.borrow_mut().globals.extend(self.globals.drain(..));
+    }
 
-    let main = self.compiler.into_inner().function;
+    let main = self.compilers.pop().unwrap().function;
     self.module.borrow_mut().push(main);
     self.diagnostics
   }
 
+  /// grammar: program → declaration* EOF ;
   fn parse_program(&mut self) {
+    // the scanner reads directives with the defines of the options
+    self.scanner.set_defines(self.options.defines.clone());
+    self.advance(); // The first advancement.
     while !self.is_at_end() {
       self.declaration();
     }
   }
 
+  /// grammar: declaration → varDecl | funDecl | classDecl | nativeDecl | importDecl | statement ;
   fn declaration(&mut self) {
     use TokenType::*;
     let res = match self.current_token.kind {
       Var => self.var_decl(),
       Fun => self.fun_decl(),
+      Class => self.class_decl(),
+      Native => self.native_decl(),
+      Import => self.import(),
       _ => self.statement()
     };
     if let Err(err) = res {
       self.diagnostics.push(err);
+      self.panic_mode = true;
     }
 
     if self.panic_mode {
@@ -78,19 +140,11 @@ impl Parser<'_> {
     }
   }
 
+  /// grammar: varDecl → "var" IDENTIFIER ( "=" expression )? ";" ;
   fn var_decl(&mut self) -> PResult<()> {
     use TokenType::*;
     let var_span = self.consume(Var, S_MUST)?.span;
-    let (ident, ident_span) = self.consume_ident("Expected variable name")?;
-
-    if let Err(err) = self.current().declare_variable(&ident, ident_span) {
-      if err.get_level() > ErrorLevel::Warning {
-        return Err(err)
-      } else {
-        err.report()
-      }
-    };
-
+    let (ident, ident_span) = self.consume_var("Expected variable name")?;
 
     match self.current_token.kind {
       Equal => {
@@ -115,36 +169,211 @@ impl Parser<'_> {
         self.current().mark_init();
         return
       }
+      // a later definition of the name is no longer the native
+      self.natives.retain(|(native, _)| *native != name);
+      self.globals.push(name.clone());
       self.current().emit(Ins::DefGlobal(name), span);
     } else {
       unreachable!()
     }
   }
 
+  /// grammar: funDecl → "fun" IDENTIFIER function ;
   fn fun_decl(&mut self) -> PResult<()> {
     use TokenType::*;
     let fun_span = self.consume(Fun, S_MUST)?.span;
     let (ident, ident_span) = self.consume_var("Expected function name")?;
 
     self.current().mark_init();
-    self.function(ident.data(), FunctionType::Function, fun_span)?;
+    let func = self.function(ident.data(), FunctionType::Function, fun_span)?;
+    if let (Some(inliner), 1, 0) = (&mut self.inliner, self.compilers.len(), self.compilers[0].scope_depth) {
+      inliner.declare(ident.data(), &(*self.module).borrow().functions[func]);
+    }
     self.define_var(ident, ident_span);
 
 
     Ok(())
   }
 
-  /// Parse function params and body
-  fn function(&mut self, name: impl Into<String>, kind: FunctionType, span: Span) -> PResult<()> {
-    let name = name.into();
-    let enclosing = self.compiler.replace(
-      Compiler::build(&name, kind)
+  /// Compiles a class declaration into a class that each method is added to in turn. A subclass
+  /// copies the methods of its superclass first, and its methods capture the superclass as the
+  /// local `super`.
+  ///
+  /// grammar: classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" method* "}" ;
+  fn class_decl(&mut self) -> PResult<()> {
+    use TokenType::*;
+    self.consume(Class, S_MUST)?;
+    let (ident, ident_span) = self.consume_var("Expected class name")?;
+    let name = ident.data().to_string();
+    self.current().emit(Ins::Class(name.clone()), ident_span);
+    self.define_var(ident, ident_span);
+
+    let has_super = self.take(Less);
+    if has_super {
+      let (superclass, super_span) = self.consume_ident("Expected superclass name")?;
+      if *superclass.data() == name {
+        return Err(ParseError::Error {
+          level: ErrorLevel::Error,
+          message: "A class cannot inherit itself".into(),
+          span: super_span
+        })
+      }
+      self.named_variable(superclass.data(), super_span, false)?;
+      self.current().begin_scope();
+      self.current().add_local("super", super_span)?;
+      self.current().mark_init();
+      self.named_variable(&name, ident_span, false)?;
+      self.current().emit(Ins::Inherit, super_span);
+    }
+
+    self.classes.push(has_super);
+    self.named_variable(&name, ident_span, false)?;
+    // the class and `super` are popped even if the body fails to parse
+    let body = self.paired_spanned(
+      LeftBrace,
+      "Expected `{` before class body",
+      "Expected `}` after class body",
+      |this| {
+        while !this.is(RightBrace) && !this.is_at_end() {
+          this.method()?;
+        }
+        Ok(())
+      },
     );
-    self.compiler.borrow_mut().bind(enclosing);
+    self.classes.pop();
+    let span = self.prev_token.span;
+    self.current().emit(Ins::Pop, span);
+    if has_super {
+      self.current().end_scope(span);
+    }
+    body?;
+    Ok(())
+  }
+
+  /// grammar: method → IDENTIFIER function ;
+  fn method(&mut self) -> PResult<()> {
+    let (ident, span) = self.consume_ident("Expected method name")?;
+    let name = ident.data().to_string();
+    let kind = match name.as_str() {
+      "init" => FunctionType::Initializer,
+      _ => FunctionType::Method,
+    };
+    self.function(&name, kind, span)?;
+    self.current().emit(Ins::Method(name), span);
+    Ok(())
+  }
+
+  /// Binds a native the host registered to a global, checking the declared parameters against it.
+  ///
+  /// grammar: nativeDecl → "native" "fun" IDENTIFIER "(" parameters? ")" ";" ;
+  fn native_decl(&mut self) -> PResult<()> {
+    use TokenType::*;
+    let native_span = self.consume(Native, S_MUST)?.span;
+    self.consume(Fun, "Expected `fun` after `native`")?;
+    let (ident, ident_span) = self.consume_ident("Expected native function name")?;
+    let arity = self.paired(
+      LeftParen,
+      "Expected `(` after native function name",
+      "Expected `)` after parameters",
+      |this| {
+        let mut arity = 0;
+        while !this.is(RightParen) {
+          this.consume_ident("Expected parameter name")?;
+          arity += 1;
+          if !this.take(Comma) {
+            break;
+          }
+        }
+        Ok(arity)
+      },
+    )?;
+    let semicolon = self.consume(Semicolon, "Expected `;` after native function declaration")?.span;
+    let span = native_span.to(semicolon);
+
+    if self.current().fun_type != FunctionType::Script || self.current().scope_depth > 0 {
+      // the declaration itself parsed, so there is nothing to skip
+      self.diagnostics.push(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: "Natives can only be declared at the top level".into(),
+        span
+      });
+      return Ok(())
+    }
+
+    let name = ident.data().to_string();
+    let registered = (*self.module).borrow().registered.get(&name).cloned();
+    match registered {
+      None => {
+        self.unbound_natives.push((name.clone(), ident_span));
+        self.current().emit(Ins::Nil, span);
+      }
+      Some(native) if native.arity != arity => {
+        return Err(ParseError::Error {
+          level: ErrorLevel::Error,
+          message: format!("Native `{name}` takes {} arguments, but is declared with {arity}", native.arity),
+          span
+        })
+      }
+      Some(native) => {
+        let idx = {
+          let mut module = self.module.borrow_mut();
+          match module.natives.iter().position(|bound| Rc::ptr_eq(bound, &native)) {
+            Some(idx) => idx,
+            None => {
+              module.natives.push(native);
+              module.natives.len() - 1
+            }
+          }
+        };
+        let native = Value::Object(Rc::new(LoxObject::Native(name.clone(), idx)));
+        self.current().emit_constant(native, span);
+      }
+    }
+    self.define_var(ident, span);
+    self.natives.push((name, arity));
+    Ok(())
+  }
+
+  /// Reports every native declaration the host did not register a native for, in one error
+  /// before anything runs.
+  fn check_unbound_natives(&mut self) {
+    let Some((_, span)) = self.unbound_natives.first() else { return };
+    let names: Vec<String> = self.unbound_natives.iter().map(|(name, _)| format!("`{name}`")).collect();
+    self.diagnostics.push(ParseError::Error {
+      level: ErrorLevel::Error,
+      message: format!("Declared natives are not registered: {}", names.join(", ")),
+      span: *span
+    });
+  }
+
+  /// Parse function params and body, returning the index of the function in the module.
+  fn function(&mut self, name: impl Into<String>, kind: FunctionType, span: Span) -> PResult<usize> {
+    let name = name.into();
+    self.compilers.push(Compiler::build(&name, kind));
+    // the name was just consumed
+    self.current().function.span = self.prev_token.span;
     // does not have a corresponding `end_scope` because the enclosed compiler
     // ends after the function body is parsed
     self.current().begin_scope();
 
+    // the function's compiler is popped even if its body fails to parse
+    let body = self.function_body();
+    self.emit_return();
+    let enclosed = self.compilers.pop().unwrap();
+    let block_span = body?;
+
+    let func = self.module.borrow_mut().push(enclosed.function);
+    let upvals = Rc::new(enclosed.upvalues);
+    self.current().emit(Ins::Closure(func, upvals), span.to(block_span));
+
+    Ok(func)
+  }
+
+  /// Parse function params and body into the current compiler
+  ///
+  /// grammar: function → "(" parameters? ")" block ;
+  /// grammar: parameters → IDENTIFIER ( "," IDENTIFIER )* ","? ;
+  fn function_body(&mut self) -> PResult<Span> {
     self.paired(
       TokenType::LeftParen, 
       "Expected `(` after function name", 
@@ -166,39 +395,144 @@ impl Parser<'_> {
           let (param, span) = this.consume_var("Expected parameter name")?;
           this.define_var(param, span);
 
-          if !this.take(TokenType::Comma) {
+          if !this.take(TokenType::Comma) || this.is(TokenType::RightParen) {
             break;
           }
         }
         Ok(())
       },
     )?;
-    let block_span = self.parse_block()?;
-
-    
-    let (clos, upvals) = {
-      self.emit_return();
-      let enclosing = self.compiler.borrow_mut().unbind();
-      let enclosed = self.compiler.replace(enclosing);
-      
-      let func = enclosed.function;
-      let func = self.module.borrow_mut().push(func);
+    self.parse_block()
+  }
 
-      (func, Rc::new(enclosed.upvalues))
+  /// Compiles `import "file";` into a call to the imported file's script function.
+  /// A file is only run the first time it is imported.
+  ///
+  /// grammar: importDecl → "import" STRING ";" ;
+  fn import(&mut self) -> PResult<()> {
+    use TokenType::*;
+    let import_span = self.consume(Import, S_MUST)?.span;
+    let name = match &self.current_token.kind {
+      String(name) => name.clone(),
+      _ => return Err(self.unexpected("Expected module path after `import`", Some(String("<path>".into())))),
     };
+    self.advance();
+    let span = import_span.to(self.consume(Semicolon, "Expected `;` after import")?.span);
+
+    if self.current().fun_type != FunctionType::Script || self.current().scope_depth > 0 {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: "Imports are only allowed at the top level".into(),
+        span
+      })
+    }
 
-    self.current().emit(Ins::Closure(clos, upvals), span.to(block_span));
-    
+    let dir = self.options.file.as_deref()
+      .and_then(Path::parent)
+      .map(Path::to_path_buf)
+      .unwrap_or_else(|| env::current_dir().unwrap_or_default());
+    let Ok(path) = dir.join(&*name).canonicalize() else {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: format!("Cannot find module `{name}`"),
+        span
+      })
+    };
+    if self.options.importing.contains(&path) {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: format!("Cyclic import of `{name}`"),
+        span
+      })
+    }
+    if (*self.module).borrow().imported.contains_key(&path) {
+      self.module.borrow_mut().skipped_imports += 1;
+      return Ok(())
+    }
 
+    let main = self.load_module(path, &name, span)?;
+    self.current().emit(Ins::Closure(main, Rc::new(Vec::new())), span);
+    self.current().emit(Ins::Call(0), span);
+    self.current().emit(Ins::Pop, span);
     Ok(())
   }
 
+  /// Adds the script function of `path` to the module, from the cache if it is still valid,
+  /// and returns its index.
+  fn load_module(&mut self, path: PathBuf, name: &str, span: Span) -> PResult<usize> {
+    let src = fs::read_to_string(&path).map_err(|err| ParseError::Error {
+      level: ErrorLevel::Error,
+      message: format!("Cannot import `{name}`: {err}"),
+      span
+    })?;
+    let hash = serialize::hash(src.as_bytes());
+    // images don't record the defines they were compiled with, so modules compiled with some
+    // are not cached
+    let cache_file = match self.options.defines.is_empty() {
+      true => self.options.cache_dir.as_deref().map(|dir| cache::path_for(dir, &path)),
+      false => None,
+    };
+
+    let flags = Flags::from(&self.options);
+    if let Some(image) = cache_file.as_deref().and_then(|file| cache::load(file, hash, flags)) {
+      let mut module = self.module.borrow_mut();
+      module.sources.push((path.clone(), hash));
+      module.sources.extend(image.deps.iter().map(|(dep, hash, _)| (PathBuf::from(dep), *hash)));
+      let main = image.link(&mut module);
+      module.imported.insert(path, main);
+      return Ok(main)
+    }
+
+    let (base, sources, skipped) = {
+      let mut module = self.module.borrow_mut();
+      module.sources.push((path.clone(), hash));
+      (module.functions.len(), module.sources.len(), module.skipped_imports)
+    };
+
+    let mut parser = Parser::new(&src, self.module.clone());
+    parser.options = self.options.clone();
+    parser.options.file = Some(path.clone());
+    parser.options.importing.push(path.clone());
+    parser.imported_at = Some(match &self.imported_at {
+      Some((prefix, outer)) => (format!("{prefix}In module `{name}`: "), *outer),
+      None => (format!("In module `{name}`: "), span),
+    });
+    let errors = parser.parse();
+    if !errors.is_empty() {
+      let prefix = format!("In module `{name}`: ");
+      self.diagnostics.extend(errors.into_iter().map(|err| anchored(err, &prefix, span)));
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: format!("Could not compile module `{name}`"),
+        span
+      })
+    }
+
+    let mut module = self.module.borrow_mut();
+    let main = module.functions.len() - 1;
+    module.imported.insert(path, main);
+
+    // An image that skipped an import would not run that file when loaded elsewhere.
+    if let (Some(file), true) = (cache_file, module.skipped_imports == skipped) {
+      let deps = module.deps(sources, base);
+      if let Some(bytes) = Image::encode(hash, flags, &deps, &module.functions[base..], base) {
+        // Caching is best-effort; failing to write only costs a recompile next time.
+        let _ = cache::store(&file, &bytes);
+      }
+    }
+    Ok(main)
+  }
+
   //
   // Statements
   //
 
+  /// grammar: statement → block | ifStmt | whileStmt | forStmt | breakStmt | continueStmt | timeStmt | printStmt | returnStmt | exprStmt ;
   fn statement(&mut self) -> PResult<()> {
     use TokenType::*;
+    if self.is_time_block() {
+      return self.parse_time();
+    }
     match &self.current_token.kind {
       LeftBrace => {
         self.current().begin_scope();
@@ -209,6 +543,8 @@ impl Parser<'_> {
       If => self.parse_if_stmt(),
       While => self.parse_while(),
       For => self.parse_for(),
+      Break => self.parse_break(),
+      Continue => self.parse_continue(),
       Print => self.parse_print(),
       Return => self.parse_return(),
       _ => self.expression()
@@ -216,15 +552,19 @@ impl Parser<'_> {
   }
 
   /// Parse a block scope
+  ///
+  /// grammar: block → "{" declaration* "}" ;
   fn parse_block(&mut self) -> PResult<Span> {
     let (_, span) = self.paired_spanned(
       TokenType::LeftBrace, 
       "Expected block to be opened", 
       "Expected block to be closed", 
       |this| {
+        this.open_blocks += 1;
         while !this.is(TokenType::RightBrace) && !this.is_at_end() {
           this.declaration();
         }
+        this.open_blocks -= 1;
         Ok(())
       },
     )?;
@@ -232,6 +572,8 @@ impl Parser<'_> {
   }
 
   /// Parse an if statement
+  ///
+  /// grammar: ifStmt → "if" "(" expression ")" statement ( "else" statement )? ;
   fn parse_if_stmt(&mut self) -> PResult<()> {
     use TokenType::*;
     let if_span = self.consume(If, S_MUST)?.span;
@@ -239,7 +581,7 @@ impl Parser<'_> {
       TokenType::LeftParen,
       "Expected `(` after `if`.",
       "Expected `)` after condition.",
-      |this| this.parse_expr(),
+      |this| this.parse_condition(),
     )?;
 
     let then_jmp = self.current().emit(Ins::JumpIfFalse(-1), if_span.to(cond_span));
@@ -269,37 +611,42 @@ impl Parser<'_> {
   }
 
   /// Parse a while statement
+  ///
+  /// grammar: whileStmt → "while" "(" expression ")" statement ;
   fn parse_while(&mut self) -> PResult<()> {
     use TokenType::*;
     let loop_start = chunk!(self).len();
+    let globals = self.globals.len();
     let while_span = self.consume(While, S_MUST)?.span;
 
     let (_, cond_span) = self.paired_spanned(
       TokenType::LeftParen,
       "Expected `(` after `while`.",
       "Expected `)` after condition.",
-      |this| this.parse_expr(),
+      |this| this.parse_condition(),
     )?;
 
     let exit_jmp = self.current().emit(Ins::JumpIfFalse(-1), while_span.to(cond_span));
     self.current().emit(Ins::Pop, cond_span);
-    let span = self.spanned(
-      |this| this.statement()
-    )?;
+    let span = self.loop_body(loop_start)?;
     self.current().emit_loop(loop_start, span)?;
 
     self.current().patch_jump(exit_jmp, span)?;
     self.current().emit(Ins::Pop, span);
+    self.current().end_loop()?;
+    self.hoist_loads(loop_start, globals, span);
     Ok(())
   }
 
   /// Parse a for statement
+  ///
+  /// grammar: forStmt → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" sequence? ")" statement ;
   fn parse_for(&mut self) -> PResult<()> {
     self.current().begin_scope();
     use TokenType::*;
     let for_span = self.consume(For, S_MUST)?.span;
 
-    let (loop_start, exit_jmp) = self.paired(
+    let (cond_start, globals, loop_start, exit_jmp) = self.paired(
       LeftParen,
       "Expected `(` after `for`",
       "Expected `)` to close `for` group",
@@ -314,12 +661,14 @@ impl Parser<'_> {
         };
 
         let mut loop_start = chunk!(this).len();
+        let cond_start = loop_start;
+        let globals = this.globals.len();
 
         // condition
         let exit_jmp = match this.current_token.kind {
           Semicolon => None,
           _ => {
-            let span = this.parse_expr()?;
+            let span = this.parse_condition()?;
 
             let jmp = this.current().emit(Ins::JumpIfFalse(-1), span);
             this.current().emit(Ins::Pop, span);
@@ -335,7 +684,7 @@ impl Parser<'_> {
             let span = this.current_token.span;
             let body_jmp = this.current().emit(Ins::Jump(-1), span);
             let inc_start = chunk!(this).len();
-            let inc_span = this.parse_expr()?;
+            let inc_span = this.parse_sequence()?;
             this.current().emit(Ins::Pop, inc_span);
 
             this.current().emit_loop(loop_start, inc_span)?;
@@ -344,11 +693,11 @@ impl Parser<'_> {
           },
         };
 
-        Ok((loop_start, exit_jmp))
+        Ok((cond_start, globals, loop_start, exit_jmp))
       },
     )?;
 
-    self.statement()?;
+    self.loop_body(loop_start)?;
     let span = self.current_token.span;
     self.current().emit_loop(
       loop_start, 
@@ -358,12 +707,97 @@ impl Parser<'_> {
       self.current().patch_jump(offset, span)?;
       self.current().emit(Ins::Pop, span);
     }
+    self.current().end_loop()?;
+    self.hoist_loads(cond_start, globals, span);
+
+    self.current().end_scope(span);
+    Ok(())
+  }
+
+  /// Parses the condition of an `if` or a loop, warning when it is an assignment, which is more
+  /// likely a mistyped `==`. An assignment wrapped in parentheses is taken to be meant.
+  fn parse_condition(&mut self) -> PResult<Span> {
+    let span = self.parse_expr()?;
+    // an assignment binds loosest, so it is the whole condition if both end on the same token
+    let end = self.prev_token.span.1;
+    if let Some(span) = self.assignment.take().filter(|assign| assign.1 == end) {
+      self.warn(Lint::AssignmentCondition, ParseError::Error {
+        level: ErrorLevel::Warning,
+        message: "Assignment used as a condition; did you mean `==`? Wrap it in parentheses if not".into(),
+        span
+      });
+    }
+    Ok(span)
+  }
+
+  /// Parses the body of a loop that `continue` resumes at `start`. The caller ends the loop once
+  /// it has emitted the exit, which is where `break` jumps to.
+  fn loop_body(&mut self, start: usize) -> PResult<Span> {
+    self.current().begin_loop(start);
+    let body = self.spanned(|this| this.statement());
+    if body.is_err() {
+      self.current().loops.pop();
+    }
+    body
+  }
+
+  /// Parse a break statement
+  ///
+  /// grammar: breakStmt → "break" ";" ;
+  fn parse_break(&mut self) -> PResult<()> {
+    let break_span = self.consume(TokenType::Break, S_MUST)?.span;
+    let semicolon_span = self.consume(TokenType::Semicolon, "Expected `;` after `break`")?.span;
+    self.current().emit_break(break_span.to(semicolon_span))
+  }
+
+  /// Parse a continue statement
+  ///
+  /// grammar: continueStmt → "continue" ";" ;
+  fn parse_continue(&mut self) -> PResult<()> {
+    let continue_span = self.consume(TokenType::Continue, S_MUST)?.span;
+    let semicolon_span = self.consume(TokenType::Semicolon, "Expected `;` after `continue`")?.span;
+    self.current().emit_continue(continue_span.to(semicolon_span))
+  }
+
+  /// Whether the current token starts a time statement. `time` is only a keyword right before a
+  /// block, so programs can still use it as a name.
+  fn is_time_block(&mut self) -> bool {
+    matches!(&self.current_token.kind, TokenType::Identifier(name) if &**name == "time")
+      && self.next_is(TokenType::LeftBrace)
+  }
+
+  /// Parse a time statement, which prints how long its block took to run
+  ///
+  /// grammar: timeStmt → "time" block ;
+  fn parse_time(&mut self) -> PResult<()> {
+    let time_span = self.advance().span;
+    self.current().begin_scope();
+    self.current().emit(Ins::StartTimer, time_span);
+    // the start is kept in two hidden locals, which `break` and `continue` pop like any other
+    for _ in 0..2 {
+      self.current().add_local(" time", time_span)?;
+      self.current().mark_init();
+    }
 
+    self.current().begin_scope();
+    let span = self.parse_block()?;
+    self.current().end_scope(span);
+    self.current().emit(Ins::StopTimer, time_span);
     self.current().end_scope(span);
     Ok(())
   }
 
+  /// Loads what the loop compiled from `start` reads but cannot change once, before the loop.
+  /// The first `globals` globals of the source are declared by then.
+  fn hoist_loads(&mut self, start: usize, globals: usize, end: Span) {
+    if let Some(hoister) = &self.hoister {
+      hoister.hoist(self.compilers.last_mut().unwrap(), start, &self.globals[..globals], end);
+    }
+  }
+
   /// Parse a print statement
+  ///
+  /// grammar: printStmt → "print" expression ";" ;
   fn parse_print(&mut self) -> PResult<()> {
     use TokenType::*;
     let print_span = self.consume(Print, S_MUST)?.span;
@@ -378,6 +812,8 @@ impl Parser<'_> {
   }
 
   /// Parse a return statement
+  ///
+  /// grammar: returnStmt → "return" expression? ";" ;
   fn parse_return(&mut self) -> PResult<()> {
     use TokenType::*;
     let return_span = self.consume(Return, S_MUST)?.span;
@@ -391,6 +827,20 @@ impl Parser<'_> {
 
     if self.take(Semicolon) {
       self.emit_return();
+    } else if self.current().fun_type == FunctionType::Initializer {
+      let start = chunk!(self).len();
+      self.parse_expr()?;
+      let span = return_span.to(self.consume(Semicolon, "Expected `;` after return value")?.span);
+      if chunk!(self).code[start..] != [Ins::GetLocal(0)] {
+        self.warn(Lint::InitReturn, ParseError::Error {
+          level: ErrorLevel::Warning,
+          message: "Initializer returns a value that is not `this`".into(),
+          span
+        });
+      }
+      // an initializer returns `this` whatever it is given
+      self.current().emit(Ins::Pop, span);
+      self.emit_return();
     } else {
       self.parse_expr()?;
       let span = self.consume(Semicolon, "Expected `;` after return value")?.span;
@@ -401,10 +851,27 @@ impl Parser<'_> {
   }
 
   /// Parse and consume an expression statement
+  ///
+  /// grammar: exprStmt → sequence ";" ;
   fn expression(&mut self) -> PResult<()> {
-    let start = self.parse_expr()?;
+    let code_start = chunk!(self).len();
+    let start = self.parse_sequence()?;
+
+    // QOL: In repl mode, expressions that do not end with a
+    // `;` are evaluated, printed and bound to `_`
+    if self.options.repl_mode && self.is_at_end() {
+      self.current().emit(Ins::Echo, start);
+      return Ok(());
+    }
 
     let semicolon = self.consume(TokenType::Semicolon, "Expected end of expression")?.span;
+    if !self.current().has_effects(code_start) {
+      self.warn(Lint::NoEffect, ParseError::Error {
+        level: ErrorLevel::Warning,
+        message: "Expression statement has no effect; did you mean to `print` or assign it?".into(),
+        span: start.to(semicolon)
+      });
+    }
 
     self.current().emit(Ins::Pop, start.to(semicolon));
     Ok(())
@@ -412,18 +879,24 @@ impl Parser<'_> {
 
   /// Parse an expression
   fn parse_expr(&mut self) -> PResult<Span> {
+    self.parse_precedence(Precedence::Assignment)
+  }
+
+  /// Parse an expression that may be a comma sequence. Only allowed where a list could not be
+  /// meant: expression statements, `for` increments and parentheses.
+  fn parse_sequence(&mut self) -> PResult<Span> {
     self.parse_precedence(Precedence::Sequence)
   }
 
   fn parse_number(&mut self) -> PResult<()> {
-    let prev = self.prev_token.clone();
+    let span = self.prev_token.span;
 
-    if let TokenType::Number(n) = prev.kind {
-      self.current().emit(Ins::from(n), prev.span);
+    if let TokenType::Number(n) = self.prev_token.kind {
+      self.current().emit_constant(Value::from(n), span);
     } else {
       return Err(ParseError::UnexpectedToken { 
         message: "Expected a number".into(), 
-        offending: prev, 
+        offending: self.prev_token.clone(), 
         expected: Some(TokenType::Number(0.0)) 
       })
     }
@@ -432,36 +905,54 @@ impl Parser<'_> {
   }
 
   fn parse_literal(&mut self) -> PResult<()> {
-    let prev = self.prev_token.clone();
     use TokenType::*;
-    let ins = match prev.kind {
+    let ins = match self.prev_token.kind {
       True => Ins::True,
       False => Ins::False,
       Nil => Ins::Nil,
       _ => unreachable!()
     };
 
-    self.current().emit(ins, prev.span);
+    let span = self.prev_token.span;
+    self.current().emit(ins, span);
     Ok(())
   }
 
   fn parse_string(&mut self) -> PResult<()> {
-    let prev = self.prev_token.clone();
-    match prev.kind {
-      TokenType::String(s) => self.current().emit(
-        Ins::from(LoxObject::String(s)), 
-        prev.span, 
-      ),
+    let value = match &self.prev_token.kind {
+      TokenType::String(s) => s.to_string(),
       _ => unreachable!()
     };
+    let span = self.prev_token.span;
+    let max = self.options.max_string_len.unwrap_or(MAX_STRING_LEN);
+    if value.len() > max {
+      // still compile the literal, so the rest of the script is checked too
+      let error = ScanError::StringTooLong { len: value.len(), max };
+      self.diagnostics.push(ParseError::ScanError { error, span });
+    }
+    let value = Value::from(LoxObject::String(value));
+    self.current().emit_constant(value, span);
+    Ok(())
+  }
+
+  fn parse_symbol(&mut self) -> PResult<()> {
+    let symbol = match &self.prev_token.kind {
+      TokenType::Symbol(s) => self.module.borrow_mut().intern(s),
+      _ => unreachable!()
+    };
+    let span = self.prev_token.span;
+    self.current().emit_constant(Value::Object(symbol), span);
     Ok(())
   }
 
   fn parse_variable(&mut self, can_assign: bool) -> PResult<()> {
+    if self.pseudo_constant() {
+      return Ok(())
+    }
     match &self.prev_token.kind {
       TokenType::Identifier(name) => {
         self.named_variable(
-          name.to_owned(), 
+          name.to_string(), 
           self.prev_token.span,
           can_assign
         )?
@@ -476,6 +967,23 @@ impl Parser<'_> {
     Ok(())
   }
 
+  /// Compiles the identifier just consumed to its value if it is `__line__` or `__file__`, and
+  /// returns whether it was. `__file__` is `nil` for source that is not read from a file.
+  fn pseudo_constant(&mut self) -> bool {
+    let span = self.prev_token.span;
+    let value = match &self.prev_token.kind {
+      TokenType::Identifier(name) if &**name == "__line__" => Value::from(span.2 as f64),
+      TokenType::Identifier(name) if &**name == "__file__" => match &self.options.file {
+        Some(file) => Value::from(LoxObject::String(file.display().to_string())),
+        None => Value::Nil,
+      },
+      _ => return false
+    };
+    self.current().emit_constant(value, span);
+    true
+  }
+
+  /// grammar: assignment → ( call "." )? IDENTIFIER "=" assignment | or ;
   fn named_variable(&mut self, name: impl Into<String>, span: Span, can_assign: bool) -> PResult<()> {
     let name = name.into();
 
@@ -485,11 +993,20 @@ impl Parser<'_> {
       None => (false, None)
     };
     if !is_loc {
-      arg = self.current().resolve_upvalue(&name, span)?;
+      arg = resolve_upvalue(&mut self.compilers, &name, span)?;
+    }
+
+    if arg.is_none() && self.options.strict_globals {
+      if self.compilers.len() > 1 {
+        self.deferred_globals.push((name.clone(), span));
+      } else if !self.declares_global(&name) {
+        return Err(Self::undeclared_global(&name, span))
+      }
     }
 
     let ins = if can_assign && self.take(TokenType::Equal) {
       self.parse_precedence(Precedence::Assignment)?;
+      self.assignment = Some(span.to(self.prev_token.span));
       match (is_loc, arg) {
         (true, Some(n)) => Ins::SetLocal(n),
         (_, Some(n)) => Ins::SetUpval(n),
@@ -507,13 +1024,119 @@ impl Parser<'_> {
     Ok(())
   }
 
+  /// Compiles a property access, or an assignment to the property if one may appear here.
+  fn parse_dot(&mut self, can_assign: bool) -> PResult<()> {
+    let (name, span) = self.consume_ident("Expected property name after `.`")?;
+    let name = name.data().to_string();
+    if can_assign && self.take(TokenType::Equal) {
+      self.parse_precedence(Precedence::Assignment)?;
+      self.assignment = Some(span.to(self.prev_token.span));
+      self.current().emit(Ins::SetProperty(name), span);
+    } else {
+      self.current().emit(Ins::GetProperty(name), span);
+    }
+    Ok(())
+  }
+
+  /// Loads the instance a method runs on, from its slot 0.
+  fn parse_this(&mut self) -> PResult<()> {
+    let span = self.prev_token.span;
+    if self.classes.is_empty() {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: "Illegal `this`: can't use `this` outside of a class".into(),
+        span
+      })
+    }
+    self.named_variable("this", span, false)
+  }
+
+  /// Binds a method of the superclass to `this`, skipping any override in the class.
+  fn parse_super(&mut self) -> PResult<()> {
+    let span = self.prev_token.span;
+    let message = match self.classes.last() {
+      None => Some("Illegal `super`: can't use `super` outside of a class"),
+      Some(false) => Some("Illegal `super`: can't use `super` within a class with no superclass"),
+      Some(true) => None,
+    };
+    if let Some(message) = message {
+      return Err(ParseError::Error { level: ErrorLevel::Error, message: message.into(), span })
+    }
+    self.consume(TokenType::Dot, "Expected `.` after `super`")?;
+    let (method, method_span) = self.consume_ident("Expected a superclass method name")?;
+    self.named_variable("this", span, false)?;
+    self.named_variable("super", span, false)?;
+    self.current().emit(Ins::GetSuper(method.data().to_string()), span.to(method_span));
+    Ok(())
+  }
+
+  /// Whether the global `name` is declared by this source so far, or by code run before it.
+  fn declares_global(&self, name: &str) -> bool {
+    self.globals.iter().any(|global| global == name) || (*self.module).borrow().declares(name)
+  }
+
+  /// Reports the globals used inside functions that the whole source never declares.
+  fn check_deferred_globals(&mut self) {
+    for (name, span) in mem::take(&mut self.deferred_globals) {
+      if !self.declares_global(&name) {
+        self.diagnostics.push(Self::undeclared_global(&name, span));
+      }
+    }
+  }
+
+  fn undeclared_global(name: &str, span: Span) -> ParseError {
+    ParseError::Error {
+      level: ErrorLevel::Error,
+      message: format!("Undeclared global `{name}`"),
+      span
+    }
+  }
+
   fn parse_call(&mut self) -> PResult<()> {
     let open = self.prev_token.span;
+    // the callee was just compiled, so a native called by name was the last thing loaded
+    let native = match self.compilers.last().and_then(|compiler| compiler.function.chunk.code.last()) {
+      Some(Ins::GetGlobal(name)) => self.natives.iter().find(|(native, _)| native == name).cloned(),
+      _ => None
+    };
+    let callee = self.inlined_callee();
     let (args, close) = self.argument_list()?;
+    if let Some((name, arity)) = native.filter(|&(_, arity)| arity != args) {
+      return Err(ParseError::Error {
+        level: ErrorLevel::Error,
+        message: format!("Native `{name}` expects {arity} arguments, but got {args}"),
+        span: open.to(close)
+      })
+    }
+
+    let inline = callee.and_then(|(name, offset)| {
+      let inline = self.inliner.as_ref()?.get(&name, args)?;
+      Some((inline, offset))
+    });
+    if let Some((inline, offset)) = inline {
+      // the arguments take the place of the callee on the stack
+      self.compilers.last_mut().unwrap().function.chunk.remove(offset);
+      inline.splice(self.compilers.last_mut().unwrap(), open.to(close));
+      return Ok(())
+    }
     self.current().emit(Ins::Call(args), open.to(close));
     Ok(())
   }
 
+  /// The global called by the call being compiled and where it is loaded, if the call may be
+  /// inlined.
+  fn inlined_callee(&self) -> Option<(String, usize)> {
+    let inliner = self.inliner.as_ref()?;
+    let chunk = &self.compilers.last()?.function.chunk;
+    let Some(Ins::GetGlobal(name)) = chunk.code.last() else {
+      return None
+    };
+    // code jumping past the load, as in `(a and f)(x)`, would land in the arguments
+    let inlinable = inliner.has(name) && !chunk.jumps_to(chunk.len()) && !(*self.module).borrow().declares(name);
+    inlinable.then(|| (name.clone(), chunk.len() - 1))
+  }
+
+  /// grammar: arguments → assignment ( "," assignment )* ","? ;
   fn argument_list(&mut self) -> PResult<(usize, Span)> {
     let start = self.prev_token.span;
     let mut count = 0;
@@ -528,7 +1151,7 @@ impl Parser<'_> {
           })
         }
         count += 1;
-        if !self.take(TokenType::Comma) {
+        if !self.take(TokenType::Comma) || self.is(TokenType::RightParen) {
           break;
         }
       }
@@ -552,9 +1175,7 @@ impl Parser<'_> {
 
   fn parse_or(&mut self) -> PResult<()> {
     let span = self.prev_token.span;
-    let else_jmp = self.current().emit(Ins::JumpIfFalse(-1), span);
-    let end_jmp = self.current().emit(Ins::Jump(-1), span);
-    self.current().patch_jump(else_jmp, span)?;
+    let end_jmp = self.current().emit(Ins::JumpIfTrue(-1), span);
     self.current().emit(Ins::Pop, span);
 
     let end_span = self.spanned(
@@ -566,38 +1187,42 @@ impl Parser<'_> {
   }
 
   fn parse_group(&mut self) -> PResult<()> {
-    self.parse_expr()?;
-    self.consume(TokenType::RightParen, "Expected `)` after expression")?;
+    let open_span = self.prev_token.span;
+    self.parse_sequence()?;
+    self.consume(TokenType::RightParen, "Expected `)` after expression")
+      .map_err(|error| error.with_label(open_span, "`(` opened here"))?;
     Ok(())
   }
 
   fn parse_unary(&mut self) -> PResult<()> {
-    let op = self.prev_token.clone();
-    self.parse_precedence(Precedence::Unary)?;
-    
-    let ins = match op.kind {
+    let span = self.prev_token.span;
+    let ins = match self.prev_token.kind {
       TokenType::Minus => Ins::Negate,
       TokenType::Bang => Ins::Not,
       _ => unreachable!()
     };
 
-    self.current().emit(ins, op.span);
+    self.parse_precedence(Precedence::Unary)?;
+    self.current().emit(ins, span);
 
     Ok(())
   }
 
-  fn parse_binary(&mut self, can_seq: bool) -> PResult<()> {
+  fn parse_binary(&mut self) -> PResult<()> {
     use TokenType::*;
     let op = self.prev_token.clone();
 
     let rule = ParseRule::from(&op.kind);
-    if can_seq && op.kind == Comma {
+    if op.kind == Comma {
+      // a sequence evaluates to its last operand, so the left one is discarded first
+      self.current().emit(Ins::Pop, op.span);
+      self.parse_precedence(rule.2.update(1))?;
       return Ok(())
     }
     self.parse_precedence(rule.2.update(1))?;
     
     match op.kind {
-      Comma => unreachable!(),
+      Plus if self.fold_concat() => return Ok(()),
       Plus => self.current().emit(Ins::Add, op.span),
       Minus => self.current().emit(Ins::Subtract, op.span),
       Star => self.current().emit(Ins::Multiply, op.span),
@@ -625,61 +1250,104 @@ impl Parser<'_> {
     Ok(())
   }
 
+  /// Replaces the operands of a `+` with their concatenation, if the left one is a string
+  /// constant and the right one a constant too, so `"a" + "b" + name` only concatenates once at
+  /// runtime. Returns whether it did.
+  fn fold_concat(&mut self) -> bool {
+    let chunk = &chunk!(self);
+    let len = chunk.len();
+    let [.., Ins::Constant(left), right] = &chunk.code[..] else {
+      return false
+    };
+    let Value::Object(left) = &chunk.constants[*left] else {
+      return false
+    };
+    let LoxObject::String(left) = left.as_ref() else {
+      return false
+    };
+    let right = match right {
+      Ins::Constant(idx) => chunk.constants[*idx].clone(),
+      Ins::True => Value::Boolean(true),
+      Ins::False => Value::Boolean(false),
+      Ins::Nil => Value::Nil,
+      _ => return false
+    };
+    // code that jumps between the operands, as in `("a" and "b") + "c"`, must keep them apart
+    let split = chunk.jumps_to(len - 1) || chunk.jumps_to(len)
+      || chunk.inlined.iter().any(|call| call.code.end > len - 2);
+    if split {
+      return false
+    }
+
+    let text = left.to_owned() + &right.to_string();
+    let span = chunk.get(len - 2).unwrap().1.to(*chunk.get(len - 1).unwrap().1);
+    let folded = self.module.borrow_mut().intern_string(text);
+    let chunk = &mut chunk!(self);
+    chunk.remove(len - 1);
+    chunk.remove(len - 2);
+    chunk.write_constant(Value::Object(folded), span);
+    true
+  }
+
   fn parse_precedence(&mut self, prec: Precedence) -> PResult<Span> {
-    let prev = self.advance().clone();
+    let prev = self.advance();
     let rule = ParseRule::from(&prev.kind);
     let start = prev.span;
 
     // prefix parser
-    self.parse_rule(
-      &rule.0, 
-      &prec,
-      Err(ParseError::UnexpectedToken { 
-      message: "Expected expression".into(), offending: prev, expected: None 
-    }))?;
+    if matches!(rule.0, ParseFn::None) {
+      let message = match prev.kind.is_binary_op() {
+        true => format!("Expected left operand before `{}`", prev.kind),
+        false => "Expected expression".into(),
+      };
+      return Err(ParseError::UnexpectedToken { 
+        message, offending: self.prev_token.clone(), expected: None 
+      })
+    }
+    self.parse_rule(&rule.0, &prec)?;
 
     // infix parser
     let mut other = ParseRule::from(&self.current_token.kind);
     while prec <= other.2 {
       let prev = self.advance();
       let infix = ParseRule::from(&prev.kind).1;
-      self.parse_rule(&infix, &prec, Ok(()))?;
+      self.parse_rule(&infix, &prec)?;
 
       other = ParseRule::from(&self.current_token.kind);
     }
 
+    // a valid target would have consumed the `=` itself, in `named_variable`
     if prec <= Precedence::Assignment && self.is(TokenType::Equal) {
-      return Err(ParseError::Error { 
+      let err = ParseError::Error { 
         message: "Invalid assignment target".into(), 
-        span: self.current_token.span, 
+        span: start.to(self.prev_token.span), 
         level: ErrorLevel::Error
-      })
+      };
+      return Err(err.with_note("only variables and properties can be assigned to"))
     };
 
-    if prec <= Precedence::Sequence && self.prev_token.kind == TokenType::Comma {
-      let span = self.prev_token.span;
-      self.current().emit(Ins::Pop, span);
-      self.parse_expr()?;
-    }
-
     Ok(start.to(self.current_token.span))
   }
 
   /// Parse according to given rule.
-  fn parse_rule(&mut self, rule: &ParseFn, prec: &Precedence, none_return: PResult<()>) -> PResult<()> {
+  fn parse_rule(&mut self, rule: &ParseFn, prec: &Precedence) -> PResult<()> {
     use ParseFn as F;
     match rule {
       F::Group => self.parse_group(),
-      F::Binary => self.parse_binary(*prec <= Precedence::Sequence),
+      F::Binary => self.parse_binary(),
       F::Unary => self.parse_unary(),
       F::Number => self.parse_number(),
       F::Literal => self.parse_literal(),
       F::String => self.parse_string(),
+      F::Symbol => self.parse_symbol(),
       F::Variable => self.parse_variable(*prec <= Precedence::Assignment),
       F::Call => self.parse_call(),
+      F::Dot => self.parse_dot(*prec <= Precedence::Assignment),
+      F::This => self.parse_this(),
+      F::Super => self.parse_super(),
       F::And => self.parse_and(),
       F::Or => self.parse_or(),
-      F::None => none_return
+      F::None => Ok(())
     }
   }
 
@@ -689,25 +1357,51 @@ impl Parser<'_> {
 impl<'src> Parser<'src> {
   /// Creates a new parser.
   pub fn new(src: &'src str, module: Rc<RefCell<Module>>) -> Self {
-    let mut parser = Self {
-      scanner: Scanner::new(src),
+    Self::with(Some(src), Scanner::new(src), module)
+  }
+
+  /// Creates a parser that reads the source from `reader` as it goes, see
+  /// [`Scanner::from_reader`]. Calls are not inlined and loads are not hoisted.
+  pub fn from_reader(reader: impl Read + 'src, module: Rc<RefCell<Module>>) -> Self {
+    Self::with(None, Scanner::from_reader(reader), module)
+  }
+
+  fn with(src: Option<&'src str>, scanner: Scanner<'src>, module: Rc<RefCell<Module>>) -> Self {
+    Self {
+      src,
+      scanner,
+      lookahead: VecDeque::new(),
       current_token: Token::dummy(),
       prev_token: Token::dummy(),
       panic_mode: false,
       diagnostics: Vec::new(),
-      _options: ParserOptions::default(),
-      compiler: RefCell::new(Compiler::new()),
-      module
-    };
-    parser.advance(); // The first advancement.
-    parser
+      open_blocks: 0,
+      options: ParserOptions::default(),
+      compilers: vec![Compiler::new()],
+      module,
+      globals: Vec::new(),
+      deferred_globals: Vec::new(),
+      natives: Vec::new(),
+      unbound_natives: Vec::new(),
+      inliner: None,
+      hoister: None,
+      classes: Vec::new(),
+      assignment: None,
+      ignores: Ignores::default(),
+      imported_at: None,
+    }
   }
 
   /// Advances the parser and returns a reference to the `prev_token` field.
   fn advance(&mut self) -> &Token {
     use TokenType::*;
+    // stay on EOF, e.g. when an expression is cut short by the end of the source
+    if self.is_at_end() {
+      self.prev_token = self.current_token.clone();
+      return &self.prev_token;
+    }
     let next = loop {
-      let maybe_next = self.scanner.next().expect("Cannot advance past EOF.");
+      let maybe_next = self.lookahead.pop_front().or_else(|| self.scanner.next()).expect("Cannot advance past EOF.");
       match maybe_next.kind {
         // Report and ignore tokens with the `Error` kind:
         Error(error) => {
@@ -720,15 +1414,57 @@ impl<'src> Parser<'src> {
             span: maybe_next.span,
           });
         }
+        // a comment after code on its line is about that code, so it silences nothing
+        Comment if maybe_next.span.2 > self.current_token.span.3 => {
+          let text = self.src.map(|src| &src[maybe_next.span.0..maybe_next.span.1]);
+          if let Some(names) = text.and_then(ignored_names) {
+            for name in names.iter().filter(|name| !Lint::is_known(name)) {
+              self.report_warning(ParseError::Error {
+                level: ErrorLevel::Warning,
+                message: format!("Unknown warning `{name}` in `lox-ignore` comment"),
+                span: maybe_next.span
+              }, None);
+            }
+            self.ignores.add(names);
+          }
+        }
         // Handle other common ignored kinds
-        Comment(_) | BlockComment(_, _) | Whitespace(_) => continue,
+        kind if kind.is_trivia() => continue,
+        // Report literals and identifiers over the limits, but keep them so the rest still parses
+        Number(n) if n.is_infinite() => {
+          let error = ScanError::NumberOutOfRange;
+          self.diagnostics.push(ParseError::ScanError { error, span: maybe_next.span });
+          break maybe_next;
+        }
+        Identifier(ref name) | Symbol(ref name) if name.len() > MAX_IDENTIFIER_LEN => {
+          let error = ScanError::IdentifierTooLong { len: name.len(), max: MAX_IDENTIFIER_LEN };
+          self.diagnostics.push(ParseError::ScanError { error, span: maybe_next.span });
+          break maybe_next;
+        }
         _ => break maybe_next,
       };
     };
+    self.ignores.next_token(next.span.2);
     self.prev_token = mem::replace(&mut self.current_token, next);
     &self.prev_token
   }
 
+  /// Checks if the token after the current one, skipping trivia and scan errors, matches the kind
+  /// of the given one. The tokens scanned are left for `advance` to report and skip.
+  fn next_is(&mut self, expected: TokenType) -> bool {
+    loop {
+      match self.lookahead.back() {
+        Some(token) if !token.kind.is_trivia() && !matches!(token.kind, TokenType::Error(_)) => {
+          return mem::discriminant(&token.kind) == mem::discriminant(&expected);
+        }
+        _ => match self.scanner.next() {
+          Some(token) => self.lookahead.push_back(token),
+          None => return false,
+        },
+      }
+    }
+  }
+
   /// Checks if the current token matches the kind of the given one.
   #[inline]
   fn is(&mut self, expected: impl Borrow<TokenType>) -> bool {
@@ -759,14 +1495,12 @@ impl<'src> Parser<'src> {
   /// Checks if the current token is an identifier. In such case advances and returns `Ok(_)` with
   /// the parsed identifier. Otherwise returns an expectation error with the provided message.
   fn consume_ident(&mut self, msg: impl Into<String>) -> PResult<(LoxObject, Span)> {
-    let expected = TokenType::Identifier("<ident>".into());
-    if self.is(&expected) {
-      let token = self.advance().clone();
-      let span = token.span;
-      let obj = LoxObject::try_from(token)?;
+    if let TokenType::Identifier(name) = &self.current_token.kind {
+      let obj = LoxObject::Identifier(name.to_string());
+      let span = self.advance().span;
       Ok((obj, span))
     } else {
-      Err(self.unexpected(msg, Some(expected)))
+      Err(self.unexpected(msg, Some(TokenType::Identifier("<ident>".into()))))
     }
   }
 
@@ -778,12 +1512,58 @@ impl<'src> Parser<'src> {
       if err.get_level() > ErrorLevel::Warning {
         return Err(err)
       } else {
-        err.report()
+        self.warn(Lint::RedeclaredVariable, err)
       }
     };
+    self.warn_native_shadow(ident.data(), ident_span);
     Ok((ident, ident_span))
   }
 
+  /// Warns that declaring `name` hides the native of that name, which calls would otherwise reach.
+  fn warn_native_shadow(&self, name: &str, span: Span) {
+    let module = (*self.module).borrow();
+    let Some(native) = module.natives.iter().find(|native| native.name == name) else {
+      return
+    };
+    let params = vec!["_"; native.arity].join(", ");
+    self.warn(Lint::ShadowedNative, ParseError::Error {
+      level: ErrorLevel::Warning,
+      message: format!("`{name}` shadows the native function `{name}({params})`"),
+      span
+    });
+  }
+
+  /// Reports `warning`, unless a `lox-ignore` comment names `lint` on its line. A silenced
+  /// warning is only printed with `list_suppressed`, marked as such.
+  fn warn(&self, lint: Lint, warning: ParseError) {
+    if !self.ignores.silences(lint, warning.get_span().2) {
+      self.report_warning(warning, Some(lint));
+    } else if self.options.list_suppressed {
+      eprintln!("{} (suppressed: {})", self.anchored(warning).message(), lint.name());
+    }
+  }
+
+  /// Prints a warning, or collects it if the options ask for that.
+  fn report_warning(&self, warning: ParseError, lint: Option<Lint>) {
+    let warning = self.anchored(warning);
+    match &self.options.warnings {
+      Some(warnings) => {
+        let lint = lint.map(Lint::name);
+        warnings.borrow_mut().push(Message { lint, ..Message::from(&warning) })
+      }
+      None => warning.report(),
+    }
+  }
+
+  /// Moves a warning of an imported source to the outermost import, see
+  /// [`Parser::imported_at`].
+  fn anchored(&self, warning: ParseError) -> ParseError {
+    match &self.imported_at {
+      Some((prefix, span)) => anchored(warning, prefix, *span),
+      None => warning,
+    }
+  }
+
   /// Get span of parsed section
   fn spanned<I, R>(
     &mut self,
@@ -835,7 +1615,7 @@ impl<'src> Parser<'src> {
     let end_span = match self.consume(delim_start.get_pair(), delim_end_expectation) {
       Ok(token) => token.span,
       Err(error) => {
-        return Err(error);
+        return Err(error.with_label(start_span, format!("`{}` opened here", delim_start)));
       }
     };
     Ok((ret, start_span.to(end_span)))
@@ -851,20 +1631,18 @@ impl<'src> Parser<'src> {
     }
   }
 
-  /// Synchronizes parser state to the next statement boundary (generally denoted by `;`)
-  ///
-  /// TODO: Refactor token types into groups
+  /// Synchronizes parser state to the next statement boundary: just past a `;`, or at a keyword
+  /// that starts a statement.
   fn sync(&mut self) {
-    use TokenType::*;
     while !self.is_at_end() {
       match &self.current_token.kind {
-        Semicolon => {
+        TokenType::Semicolon => {
           self.advance();
-          return;
-        }
-        Class | For | Fun | If | Print | Return | Var | While => {
-          return;
+          break;
         }
+        // the block's loop takes it; at the top level it is skipped like any other token
+        TokenType::RightBrace if self.open_blocks > 0 => break,
+        kind if kind.is_statement_start() => break,
         _ => self.advance(),
       };
     }
@@ -884,7 +1662,7 @@ impl<'src> Parser<'src> {
 /// Get a mutable reference to the current chunk
 macro_rules! chunk {
   ($self:ident) => {
-    &mut $self.current().function.chunk
+    $self.current().function.chunk
   };
 }
 
@@ -894,14 +1672,17 @@ use chunk;
 impl Parser<'_> {
 
   #[inline]
-  fn current(&mut self) -> RefMut<Compiler> {
-    self.compiler.borrow_mut()
+  fn current(&mut self) -> &mut Compiler {
+    self.compilers.last_mut().unwrap()
   }
 
-  /// Emit an implicit return `nil` at the end of a function body
+  /// Emit an implicit return at the end of a function body: `nil`, or `this` from an initializer
   fn emit_return(&mut self) {
     let span = self.prev_token.span;
-    self.current().emit(Ins::Nil, span);
+    match self.current().fun_type {
+      FunctionType::Initializer => self.current().emit(Ins::GetLocal(0), span),
+      _ => self.current().emit(Ins::Nil, span),
+    };
     self.current().emit(Ins::Return, span);
   }
 
@@ -910,3 +1691,10 @@ impl Parser<'_> {
 
 /// (String Must) Indicates the parser to emit a parser error (i.e. the parser is bugged) message.
 const S_MUST: &str = "Parser bug. Unexpected token";
+
+/// Reports a diagnostic of an imported source at `span` of the source that imports it, as hosts
+/// render diagnostics against the source they compiled. Its labels are dropped, as they point
+/// into the imported source too.
+fn anchored(err: ParseError, prefix: &str, span: Span) -> ParseError {
+  ParseError::Error { level: err.get_level(), message: format!("{prefix}{err}"), span }
+}
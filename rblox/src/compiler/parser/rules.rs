@@ -52,46 +52,63 @@ pub enum ParseFn {
   Number,
   Literal,
   String,
+  Symbol,
   Variable,
   Call,
+  Dot,
+  This,
+  Super,
   And, Or
 }
 
 pub struct ParseRule(pub ParseFn, pub ParseFn, pub Precedence);
 
-impl From<&TokenType> for ParseRule {
-  fn from(value: &TokenType) -> Self {
-    use TokenType as T;
-    use ParseFn  as F;
-    use Precedence as P;
-    match value {
-      T::EOF => Self(F::None, F::None, P::None),
-      T::LeftParen => Self(F::Group, F::Call, P::Call),
-
-      T::Minus => Self(F::Unary, F::Binary, P::Term),
-      T::Plus => Self(F::None, F::Binary, P::Term),
-      T::Slash | T::Star
-      => Self(F::None, F::Binary, P::Factor),
+/// Builds `ParseRule::from(&TokenType)` from a table of `tokens => (prefix, infix, precedence)`
+/// entries. The generated `match` has no fallback arm, so adding a token without deciding its rule
+/// is a compile error rather than a silent "Expected expression" at parse time.
+macro_rules! parse_rules {
+  ($($tokens:pat => ($prefix:ident, $infix:ident, $prec:ident),)*) => {
+    impl From<&TokenType> for ParseRule {
+      fn from(value: &TokenType) -> Self {
+        use TokenType::*;
+        match value {
+          $($tokens => Self(ParseFn::$prefix, ParseFn::$infix, Precedence::$prec),)*
+        }
+      }
+    }
+  };
+}
 
-      T::Bang => Self(F::Unary, F::None, P::None),
-      T::BangEqual | T::EqualEqual 
-      => Self(F::None, F::Binary, P::Equality),
+parse_rules! {
+  LeftParen => (Group, Call, Call),
+  Dot => (None, Dot, Call),
+  Minus => (Unary, Binary, Term),
+  Plus => (None, Binary, Term),
+  Slash | Star => (None, Binary, Factor),
 
-      T::Greater | T::GreaterEqual |
-      T::Less | T::LessEqual 
-      => Self(F::None, F::Binary, P::Comparision),
+  Bang => (Unary, None, None),
+  BangEqual | EqualEqual => (None, Binary, Equality),
+  Greater | GreaterEqual | Less | LessEqual => (None, Binary, Comparision),
 
-      T::And => Self(F::None, F::And, Precedence::And),
-      T::Or => Self(F::None, F::Or, Precedence::Or),
+  And => (None, And, And),
+  Or => (None, Or, Or),
 
-      T::Number(_) => Self(F::Number, F::None, P::None),
-      T::True | T::False | T::Nil => Self(F::Literal, F::None, P::None),
-      T::String(_) => Self(F::String, F::None, P::None),
-      T::Identifier(_) => Self(F::Variable, F::None, P::None),
+  Number(_) => (Number, None, None),
+  True | False | Nil => (Literal, None, None),
+  String(_) => (String, None, None),
+  Symbol(_) => (Symbol, None, None),
+  Identifier(_) => (Variable, None, None),
+  This => (This, None, None),
+  Super => (Super, None, None),
 
-      T::Comma => Self(F::None, F::Binary, P::Sequence),
+  Comma => (None, Binary, Sequence),
 
-      _ => Self(F::None, F::None, P::None),
-    }
-  }
-}
\ No newline at end of file
+  // punctuation that only appears inside statements
+  RightParen | LeftBrace | RightBrace | Semicolon | Equal => (None, None, None),
+  // keywords that start statements or are not implemented yet
+  Class | Else | Fun | For | If | Import | Native | Print | Return | Var | While
+  | Break | Continue => (None, None, None),
+  // never reach the parser as the current token
+  Comment | BlockComment(_) | Whitespace | Dummy | Error(_) => (None, None, None),
+  EOF => (None, None, None),
+}
@@ -1,5 +1,35 @@
+use std::{cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc};
+
+use crate::common::error::Message;
+
+/// Default for [`ParserOptions::max_string_len`].
+pub const MAX_STRING_LEN: usize = 1 << 20;
+
 #[derive(Debug, Default, Clone)]
 pub struct ParserOptions {
-  pub _repl_mode: bool,
+  /// Print the value of an expression statement missing its `;` at the end of the source.
+  pub repl_mode: bool,
   pub _display_ast: bool,
+  /// Source file being compiled. Imports are resolved relative to its directory.
+  pub file: Option<PathBuf>,
+  /// Where compiled imports are cached. Caching is off when unset.
+  pub cache_dir: Option<PathBuf>,
+  /// Chain of files currently being imported, used to reject import cycles.
+  pub importing: Vec<PathBuf>,
+  /// Reject reads and assignments of globals that the program never declares, and top-level
+  /// ones that run before the declaration.
+  pub strict_globals: bool,
+  /// Compile calls to small top-level functions as calls instead of splicing in their body.
+  pub no_inline: bool,
+  /// Load globals and captured variables that a loop cannot change on every iteration, instead
+  /// of once before the loop.
+  pub no_hoist: bool,
+  /// Longest string literal allowed, in bytes. Defaults to [`MAX_STRING_LEN`].
+  pub max_string_len: Option<usize>,
+  /// Print the warnings that `lox-ignore` comments silenced, instead of dropping them.
+  pub list_suppressed: bool,
+  /// Where warnings are collected, instead of being printed as they are found. Imports share it.
+  pub warnings: Option<Rc<RefCell<Vec<Message>>>>,
+  /// Names that `#if` directives test, with their values. Imports are compiled with the same.
+  pub defines: HashMap<String, String>,
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::common::error::Label;
 
 #[test]
 fn can_cast_precedence_from_usize() {
@@ -15,6 +16,17 @@ fn correct_precedence_update() {
   assert_eq!(Precedence::Primary.update(1), Precedence::None);
 }
 
+#[test]
+fn infix_rules_have_precedence() {
+  for kind in crate::compiler::grammar::token_kinds() {
+    let ParseRule(_, infix, prec) = ParseRule::from(&kind);
+    assert_eq!(
+      matches!(infix, ParseFn::None), prec == Precedence::None,
+      "`{kind}` has infix rule {infix:?} at precedence {prec:?}"
+    );
+  }
+}
+
 #[test]
 fn emits_correct_tokens() {
   let source = "( )  {} ,.-+;
@@ -27,6 +39,7 @@ comment */
 /* inline block*/
 forest varied\0";
   let mut parser = Parser::new(source, Module::new());
+  parser.advance(); // The first advancement.
 
   assert_eq!(parser.advance(), &Token::new(TokenType::LeftParen, Span::new(0, 1, 1)));
   assert_eq!(parser.advance(), &Token::new(TokenType::RightParen, Span::new(2, 3, 1)));
@@ -76,9 +89,9 @@ forest varied\0";
   assert_eq!(parser.advance(), &Token::new(TokenType::While, Span::new(137, 142, 5)));
 
   // comments are skipped over
-  // assert_eq!(parser.advance(), &Token::new(TokenType::Comment(" comment".into()), Span::new(143, 153, 5)));
-  // assert_eq!(parser.advance(), &Token::new(TokenType::BlockComment(" block\ncomment ".into(), 6), Span::new(154, 173, 6)));
-  // assert_eq!(parser.advance(), &Token::new(TokenType::BlockComment(" inline block".into(), 8), Span::new(174, 191, 8)));
+  // assert_eq!(parser.advance(), &Token::new(TokenType::Comment, Span::new(143, 153, 5)));
+  // assert_eq!(parser.advance(), &Token::new(TokenType::BlockComment(6), Span::new(154, 173, 6)));
+  // assert_eq!(parser.advance(), &Token::new(TokenType::BlockComment(8), Span::new(174, 191, 8)));
 
   assert_eq!(parser.advance(), &Token::new(TokenType::Identifier("forest".into()), Span::new(192, 198, 9)));
   assert_eq!(parser.advance(), &Token::new(TokenType::Identifier("varied".into()), Span::new(199, 205, 9)));
@@ -87,3 +100,369 @@ forest varied\0";
 
 }
 
+
+#[test]
+fn captures_through_nested_functions() {
+  let module = Module::new();
+  let errors = Parser::new("fun a() { var x = 1; fun b() { fun c() { return x; } } }", module.clone()).parse();
+  assert!(errors.is_empty());
+
+  // c captures b's upvalue, which in turn captures a's local
+  let module = RefCell::borrow(&module);
+  let find = |name: &str| module.functions.iter().find(|f| f.name == name).unwrap().upvalues;
+  assert_eq!((find("a"), find("b"), find("c")), (0, 1, 1));
+}
+
+#[test]
+fn recovers_after_error_in_function() {
+  let module = Module::new();
+  let errors = Parser::new("fun f(a { }\nvar x = 1;", module.clone()).parse();
+  assert!(!errors.is_empty());
+
+  // `x` is still a global of the script, not a local of `f`
+  let module = RefCell::borrow(&module);
+  let script = module.functions.last().unwrap();
+  assert!(script.chunk.code.contains(&Ins::DefGlobal("x".into())));
+}
+
+#[test]
+fn shares_equal_constants() {
+  let module = Module::new();
+  let errors = Parser::new("print 1; print \"x\"; print 1; print \"x\"; print 2; print :x;", module.clone()).parse();
+  assert!(errors.is_empty());
+
+  let module = RefCell::borrow(&module);
+  let script = module.functions.last().unwrap();
+  let loads: Vec<usize> = script.chunk.code.iter().filter_map(|ins| match ins {
+    Ins::Constant(idx) => Some(*idx),
+    _ => None,
+  }).collect();
+  assert_eq!(loads, vec![0, 1, 0, 1, 2, 3]);
+  assert_eq!(script.chunk.constants.len(), 4);
+}
+
+/// Instructions of the script body compiled from `src`.
+fn script_code(src: &str) -> Vec<Ins> {
+  let module = Module::new();
+  let errors = Parser::new(src, module.clone()).parse();
+  assert!(errors.is_empty());
+  let module = RefCell::borrow(&module);
+  module.functions.last().unwrap().chunk.code.clone()
+}
+
+#[test]
+fn repl_mode_prints_trailing_expression() {
+  let module = Module::new();
+  let mut parser = Parser::new("1; 2", module.clone());
+  parser.options.repl_mode = true;
+  assert!(parser.parse().is_empty());
+  let code = RefCell::borrow(&module).functions.last().unwrap().chunk.code.clone();
+  assert_eq!(code[..4], [Ins::Constant(0), Ins::Pop, Ins::Constant(1), Ins::Echo]);
+
+  // without repl mode, the missing `;` is an error
+  assert!(!Parser::new("1; 2", Module::new()).parse().is_empty());
+}
+
+#[test]
+fn or_jumps_over_right_operand_when_true() {
+  assert_eq!(script_code("a or b;")[..4], [
+    Ins::GetGlobal("a".into()),
+    Ins::JumpIfTrue(2),
+    Ins::Pop,
+    Ins::GetGlobal("b".into()),
+  ]);
+}
+
+#[test]
+fn and_jumps_over_right_operand_when_false() {
+  assert_eq!(script_code("a and b;")[..4], [
+    Ins::GetGlobal("a".into()),
+    Ins::JumpIfFalse(2),
+    Ins::Pop,
+    Ins::GetGlobal("b".into()),
+  ]);
+}
+
+#[test]
+fn chained_or_jumps_to_end() {
+  // the right operand holds the rest of the chain, so a true `a` skips all of it
+  assert_eq!(script_code("a or b or c;")[..7], [
+    Ins::GetGlobal("a".into()),
+    Ins::JumpIfTrue(5),
+    Ins::Pop,
+    Ins::GetGlobal("b".into()),
+    Ins::JumpIfTrue(2),
+    Ins::Pop,
+    Ins::GetGlobal("c".into()),
+  ]);
+}
+
+#[test]
+fn inlines_calls_to_small_functions() {
+  let code = script_code("fun add(a, b) { return a + b; } print add(1, 2);");
+  assert_eq!(code[2..6], [Ins::Constant(0), Ins::Constant(1), Ins::Add, Ins::Print]);
+
+  // a function made of inlined calls is small enough too
+  let code = script_code("fun neg(a) { return -a; } fun pos(a) { return neg(neg(a)); } print pos(1);");
+  assert_eq!(code[4..8], [Ins::Constant(0), Ins::Negate, Ins::Negate, Ins::Print]);
+}
+
+#[test]
+fn keeps_calls_that_cannot_be_inlined() {
+  let calls = |src: &str| script_code(src).iter().any(|ins| matches!(ins, Ins::Call(_)));
+  assert!(!calls("fun f(a) { return a + 1; } f(1);"));
+
+  // wrong arity, parameters out of order, statements and captures
+  assert!(calls("fun f(a) { return a + 1; } f(1, 2);"));
+  assert!(calls("fun f(a, b) { return b - a; } f(1, 2);"));
+  assert!(calls("fun f(a) { print a; } f(1);"));
+  assert!(calls("fun f() { return 1 + 2 + 3 + 4 + 5; } f();"));
+  assert!(calls("fun f() { var a = 1; fun g() { return a; } return g(); } f();"));
+
+  // the global may hold something else by the time of the call
+  assert!(calls("fun f(a) { return a; } f = nil; f(1);"));
+  assert!(calls("fun f(a) { return a; } fun f(a) { return -a; } f(1);"));
+  assert!(calls("var x = false; fun f() { return 1; } (x and f)();"));
+
+  let module = Module::new();
+  let mut parser = Parser::new("fun f(a) { return a; } f(1);", module.clone());
+  parser.options.no_inline = true;
+  assert!(parser.parse().is_empty());
+  let module = RefCell::borrow(&module);
+  assert!(module.functions.last().unwrap().chunk.code.contains(&Ins::Call(1)));
+}
+
+#[test]
+fn folds_concatenation_of_constant_prefix() {
+  let module = Module::new();
+  let src = "fun f() { return \"a\" + \"b\"; } print \"a\" + \"b\" + name; print \"n\" + 1 + true;";
+  assert!(Parser::new(src, module.clone()).parse().is_empty());
+  let module = RefCell::borrow(&module);
+  let script = &module.functions.last().unwrap().chunk;
+  assert_eq!(script.code[2..6], [Ins::Constant(2), Ins::GetGlobal("name".into()), Ins::Add, Ins::Print]);
+  assert_eq!(script.constants[2].to_string(), "ab");
+  assert_eq!(script.code[6..8], [Ins::Constant(6), Ins::Print]);
+  assert_eq!(script.constants[6].to_string(), "n1true");
+
+  // both chunks load the same string object
+  let Value::Object(ab) = &module.functions[0].chunk.constants[2] else { panic!() };
+  assert!(Rc::ptr_eq(ab, &module.strings["ab"]));
+  assert!(matches!(&script.constants[2], Value::Object(obj) if Rc::ptr_eq(obj, ab)));
+}
+
+#[test]
+fn keeps_concatenation_that_cannot_fold() {
+  let adds = |src: &str| script_code(src).iter().filter(|ins| **ins == Ins::Add).count();
+  assert_eq!(adds("name + \"a\" + \"b\";"), 2);
+  assert_eq!(adds("1 + \"a\";"), 1);
+  assert_eq!(adds("(\"a\" and \"b\") + \"c\";"), 1);
+  assert_eq!(adds("\"a\" + (\"b\" or \"c\");"), 1);
+}
+
+/// Instructions of each function compiled from `src`, the script body last, with loads hoisted
+/// out of loops or not.
+fn hoisted_code(src: &str, hoist: bool) -> Vec<Vec<Ins>> {
+  let module = Module::new();
+  let mut parser = Parser::new(src, module.clone());
+  parser.options.no_hoist = !hoist;
+  assert!(parser.parse().is_empty());
+  let module = RefCell::borrow(&module);
+  module.functions.iter().map(|fun| fun.chunk.code.clone()).collect()
+}
+
+#[test]
+fn hoists_invariant_loads_out_of_loops() {
+  let src = "var n = 3; var i = 0; while (i < n) i = i + 1;";
+  let (before, after) = (&hoisted_code(src, false)[0], &hoisted_code(src, true)[0]);
+  assert_eq!(before[4..7], [Ins::GetGlobal("i".into()), Ins::GetGlobal("n".into()), Ins::Less]);
+  // `n` is loaded once into a hidden local, popped after the loop; `i` changes
+  assert_eq!(after[4..8], [
+    Ins::GetGlobal("n".into()),
+    Ins::GetGlobal("i".into()),
+    Ins::GetLocal(1),
+    Ins::Less,
+  ]);
+  assert_eq!(after[after.len() - 4..], [Ins::Pop, Ins::PopN(1), Ins::Nil, Ins::Return]);
+  assert_eq!(after.len(), before.len() + 2);
+
+  // the locals of the body move up past the hidden local, those outside the loop stay
+  let src = "fun f(n) { fun g() { for (var i = 0; i < n; i = i + 1) { var j = i; print j + i; } } }";
+  let before = &hoisted_code(src, false)[0];
+  let after = &hoisted_code(src, true)[0];
+  assert_eq!(before[1..4], [Ins::GetLocal(1), Ins::GetUpval(0), Ins::Less]);
+  assert_eq!(after[1..5], [Ins::GetUpval(0), Ins::GetLocal(1), Ins::GetLocal(2), Ins::Less]);
+  // `var j = i; print j + i;`
+  assert_eq!(before[13..16], [Ins::GetLocal(1), Ins::GetLocal(2), Ins::GetLocal(1)]);
+  assert_eq!(after[14..17], [Ins::GetLocal(1), Ins::GetLocal(3), Ins::GetLocal(1)]);
+}
+
+#[test]
+fn keeps_loads_that_may_change() {
+  let loads = |src: &str, load: Ins| {
+    hoisted_code(src, true).iter().flatten().filter(|ins| **ins == load).count()
+  };
+  let n = || Ins::GetGlobal("n".into());
+  assert_eq!(loads("var n = 3; while (n) print n;", n()), 1);
+
+  // assigned anywhere, even outside the loop
+  assert_eq!(loads("var n = 3; fun f() { n = 0; } while (n) print n;", n()), 2);
+  assert_eq!(loads("fun f(n) { fun g() { while (n) n = n - 1; } }", Ins::GetUpval(0)), 2);
+  // not declared yet, so the loop may not run at all
+  assert_eq!(loads("fun f() { while (false) print n; } var n = 1;", n()), 1);
+  assert_eq!(loads("while (false) print n; var n = 1;", n()), 1);
+}
+
+#[test]
+fn compiles_classes() {
+  let code = hoisted_code("class A < B { f() { return super.f(); } init() { return; } }", false);
+  let global = |name: &str| Ins::GetGlobal(name.into());
+  // methods capture the superclass as the hidden local `super`
+  assert_eq!(code[0], [
+    Ins::GetLocal(0), Ins::GetUpval(0), Ins::GetSuper("f".into()), Ins::Call(0), Ins::Return,
+    Ins::Nil, Ins::Return,
+  ]);
+  // initializers return `this`
+  assert_eq!(code[1], [Ins::GetLocal(0), Ins::Return, Ins::GetLocal(0), Ins::Return]);
+  let script = &code[2];
+  assert_eq!(script[..6], [
+    Ins::Class("A".into()), Ins::DefGlobal("A".into()),
+    global("B"), global("A"), Ins::Inherit, global("A"),
+  ]);
+  assert_eq!(script[7], Ins::Method("f".into()));
+  assert_eq!(script[9..], [Ins::Method("init".into()), Ins::Pop, Ins::CloseUpval, Ins::Nil, Ins::Return]);
+}
+
+#[test]
+fn rejects_this_and_super_outside_methods() {
+  assert!(parse_errors("print this;")[0].starts_with("Illegal `this`"));
+  assert!(parse_errors("class A { f() { super.f(); } }")[0].contains("no superclass"));
+  assert!(parse_errors("class A < A {}")[0].starts_with("A class cannot inherit itself"));
+  assert!(parse_errors("class A {} A().x = 1;").is_empty());
+  let errors = Parser::new("class A {} A().x + 1 = 1;", Module::new()).parse();
+  assert!(errors[0].notes().iter().any(|note| note.contains("variables and properties")));
+}
+
+/// Messages of the errors reported while parsing `src`.
+fn parse_errors(src: &str) -> Vec<String> {
+  Parser::new(src, Module::new()).parse().iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn token_groups_agree_with_rules() {
+  for kind in crate::compiler::grammar::token_kinds() {
+    let ParseRule(prefix, infix, _) = ParseRule::from(&kind);
+    if kind.is_binary_op() {
+      assert!(matches!(infix, ParseFn::Binary | ParseFn::And | ParseFn::Or), "`{kind}` has infix {infix:?}");
+    }
+    assert_eq!(kind.is_unary_op(), prefix == ParseFn::Unary, "`{kind}` has prefix {prefix:?}");
+    if kind.is_literal() {
+      assert!(!matches!(prefix, ParseFn::None | ParseFn::Unary | ParseFn::Group), "`{kind}` has prefix {prefix:?}");
+    }
+  }
+}
+
+/// Messages of the errors reported while parsing `src` with `--strict-globals`.
+fn strict_errors(src: &str, module: Rc<RefCell<Module>>) -> Vec<String> {
+  let mut parser = Parser::new(src, module);
+  parser.options.strict_globals = true;
+  parser.parse().iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn strict_globals_need_a_declaration() {
+  let src = "fun f() { return g() + later; } fun g() {} var later = 1; print f();";
+  assert_eq!(strict_errors(src, Module::new()), Vec::<String>::new());
+
+  let errors = strict_errors("print a; var a = 1; b = 2; fun f() { return c; }", Module::new());
+  assert_eq!(errors.len(), 3, "{errors:?}");
+  assert!(errors[0].starts_with("Undeclared global `a`"), "{errors:?}");
+  assert!(errors[1].starts_with("Undeclared global `b`"), "{errors:?}");
+  assert!(errors[2].starts_with("Undeclared global `c`"), "{errors:?}");
+}
+
+#[test]
+fn strict_globals_remember_earlier_runs() {
+  let module = Module::new();
+  assert!(strict_errors("var a = 1;", module.clone()).is_empty());
+  assert!(strict_errors("print a;", module.clone()).is_empty());
+  // a failed run declares nothing
+  assert_eq!(strict_errors("var b = 1; print c;", module.clone()).len(), 1);
+  assert_eq!(strict_errors("print b;", module).len(), 1);
+}
+
+#[test]
+fn break_pops_body_locals_and_jumps_past_the_exit() {
+  assert_eq!(script_code("while (true) { var a = 1; break; }")[..10], [
+    Ins::True,
+    Ins::JumpIfFalse(6),
+    Ins::Pop,
+    Ins::Constant(0),
+    Ins::PopN(1),
+    Ins::Jump(3),
+    Ins::PopN(1),
+    Ins::Jump(-8),
+    Ins::Pop,
+    Ins::Nil,
+  ]);
+}
+
+#[test]
+fn continue_resumes_at_the_increment() {
+  let code = script_code("for (var i = 0; i < 3; i = i + 1) continue;");
+  // the increment starts at 7, after the jump over it at 6
+  assert_eq!(code[7], Ins::GetLocal(1));
+  // the `continue` at 13 and the end of the body at 14 both loop back to it
+  assert_eq!(code[13..16], [Ins::Jump(-7), Ins::Jump(-8), Ins::Pop]);
+}
+
+#[test]
+fn string_literals_over_the_limit_are_reported() {
+  let mut parser = Parser::new("print \"abcdef\"; print \"abc\";", Module::new());
+  parser.options.max_string_len = Some(4);
+  let errors = parser.parse();
+  assert_eq!(errors.len(), 1, "{errors:?}");
+  let message = errors[0].to_string();
+  assert!(message.contains("[E0006] String literal is 6 bytes long, over the limit of 4"), "{message}");
+  assert!(message.ends_with("at position 6..14"), "{message}");
+}
+
+#[test]
+fn ignore_comments_cover_the_line_of_the_next_token() {
+  let src = "// lox-ignore: no-effect\n\n1;\n// lox-ignore: shadowed-native, redeclared-variable\nvar clock;\n2; // lox-ignore: no-effect\n3;";
+  let mut parser = Parser::new(src, Module::new());
+  parser.parse_program();
+  assert!(parser.diagnostics.is_empty(), "{:?}", parser.diagnostics);
+  assert!(parser.ignores.silences(Lint::NoEffect, 3));
+  assert!(!parser.ignores.silences(Lint::ShadowedNative, 3));
+  assert!(parser.ignores.silences(Lint::ShadowedNative, 5));
+  assert!(parser.ignores.silences(Lint::RedeclaredVariable, 5));
+  // a comment after code on the same line silences nothing
+  assert!(!parser.ignores.silences(Lint::NoEffect, 7));
+}
+
+#[test]
+fn errors_label_the_spans_that_explain_them() {
+  let errors = Parser::new("if (true print 1;", Module::new()).parse();
+  assert_eq!(errors.len(), 1, "{errors:?}");
+  assert_eq!(errors[0].labels(), [Label::new(Span::new(3, 4, 1), "`(` opened here")]);
+
+  let errors = Parser::new("print (1 + 2;", Module::new()).parse();
+  assert_eq!(errors[0].labels(), [Label::new(Span::new(6, 7, 1), "`(` opened here")]);
+
+  let mut compiler = Compiler::new();
+  compiler.begin_scope();
+  let name = LoxObject::Identifier("a".into());
+  compiler.declare_variable(&name, Span::new(6, 7, 1)).unwrap();
+  let warning = compiler.declare_variable(&name, Span::new(19, 20, 2)).unwrap_err();
+  assert_eq!(warning.get_level(), ErrorLevel::Warning);
+  assert_eq!(warning.labels(), [Label::new(Span::new(6, 7, 1), "previous declaration here")]);
+}
+
+#[test]
+fn reports_sources_cut_short_instead_of_panicking() {
+  // an operand or initializer missing at the very end makes the parser look past it
+  for src in ["print 1 +", "print -", "var a ="] {
+    let errors = Parser::new(src, Module::new()).parse();
+    assert!(!errors.is_empty(), "{src}");
+  }
+}
@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod tests;
 
-use std::{iter::Peekable, str::CharIndices};
+use std::{collections::{HashMap, HashSet}, io::Read, iter::Peekable, rc::Rc, str::CharIndices};
+
+use lox_common::{directive::Directives, stream::Stream};
 
 use crate::{
   common::Span,
@@ -17,12 +19,27 @@ pub mod error;
 pub mod identifier;
 
 pub struct Scanner<'src> {
-  src: &'src str,
-  chars: Peekable<CharIndices<'src>>,
+  input: Input<'src>,
   current: (usize, char),
   lexeme_start: usize,
+  /// Line the current lexeme starts on.
+  lexeme_line: u32,
   line: u32,
   emitted_eof: bool,
+  /// Whether only whitespace precedes the current character on its line, so that a `#` there
+  /// starts a directive.
+  line_start: bool,
+  directives: Directives<Span>,
+  /// Text of identifiers and literals seen so far, so that repeats share an allocation.
+  names: HashSet<Rc<str>>,
+}
+
+/// Where the scanner reads characters from.
+enum Input<'src> {
+  /// The whole source, which lexemes are sliced from.
+  Str(&'src str, Peekable<CharIndices<'src>>),
+  /// A reader, whose lexemes are copied out as they are scanned.
+  Stream(Stream<'src>),
 }
 
 /// Token iterator
@@ -40,10 +57,14 @@ impl Iterator for Scanner<'_> {
     loop {
       // Ensures the next token starts with a new span.
       self.lexeme_start = self.current.0;
+      self.lexeme_line = self.line;
+      if let Input::Stream(stream) = &mut self.input {
+        stream.lexeme.clear();
+      }
       kind = self.scan_token();
       match kind {
-        TT::Whitespace(_) => continue, 
-        // TT::Comment(_) | TT::BlockComment(_) => continue,
+        TT::Whitespace => continue, 
+        // TT::Comment | TT::BlockComment(_) => continue,
         TT::Error(_) => break, // emit errors to be reported in compiler
         TT::Dummy => unreachable!("Source code should not contain dummy tokens."),
         _ => break
@@ -51,32 +72,28 @@ impl Iterator for Scanner<'_> {
     }
 
     if kind == TT::EOF {
+      if let Some(span) = self.directives.close_unended() {
+        return Some(Token { kind: TT::Error(ScanError::UnendedIf), span });
+      }
       self.emitted_eof = true;
     }
 
-    let span = match &kind {
-      TT::BlockComment(_, line) => {
-        let mut span = self.lex_span();
-        span.2 = *line;
-        span
-      },
-      _ => self.lex_span()
-    };
-
     Some(Token {
       kind,
-      span,
+      span: self.lex_span(),
     })
   }
 }
 
 // The scanner implementation.
-impl Scanner<'_> {
+impl<'src> Scanner<'src> {
   /// Tries to scan the current character.
   fn scan_token(&mut self) -> TokenType {
     use TokenType::*;
-    match self.advance() {
-      '\0' => EOF,
+    let line_start = self.line_start;
+    let kind = match self.advance() {
+      '\0' => return EOF,
+      '#' if line_start => return self.directive(),
       '(' => LeftParen,
       ')' => RightParen,
       '{' => LeftBrace,
@@ -92,6 +109,7 @@ impl Scanner<'_> {
       '-' => Minus,
       '*' => Star,
       '"' => self.string(),
+      ':' => self.symbol(),
       '/' => self.comment_or_slash(),
       c if c.is_ascii_digit() => self.number(),
       '\n' => {
@@ -101,17 +119,47 @@ impl Scanner<'_> {
       c if c.is_ascii_whitespace() => self.whitespace(),
       c if is_valid_identifier_start(c) => self.identifier_or_keyword(),
       unexpected => Error(ScanError::UnexpectedChar(unexpected)),
+    };
+    match self.directives.kept() {
+      true => kind,
+      false => Whitespace,
+    }
+  }
+
+  /// Scans a directive to the end of its line and applies it.
+  fn directive(&mut self) -> TokenType {
+    self.consume_until('\n');
+    let span = self.lex_span();
+    let text = Self::slice(&self.input, span, 1, 0);
+    match self.directives.apply(text, span) {
+      Ok(()) => TokenType::Whitespace,
+      Err(error) => TokenType::Error(error),
     }
   }
 
   /// Tries to scan a string.
   fn string(&mut self) -> TokenType {
-    self.consume_until('"');
+    while self.current.1 != '"' && !self.is_at_end() {
+      if self.advance() == '\n' {
+        self.line += 1;
+      }
+    }
     if self.is_at_end() {
       return TokenType::Error(ScanError::UnterminatedString);
     }
     self.advance(); // The closing `"`
-    TokenType::String(self.lex(1, -1).into())
+    TokenType::String(self.intern(1, -1))
+  }
+
+  /// Tries to scan a symbol literal, e.g. `:name`.
+  fn symbol(&mut self) -> TokenType {
+    if !is_valid_identifier_start(self.current.1) {
+      return TokenType::Error(ScanError::UnexpectedChar(':'));
+    }
+    while is_valid_identifier_tail(self.current.1) {
+      self.advance();
+    }
+    TokenType::Symbol(self.intern(1, 0))
   }
 
   /// Tries to scan a comment or a slash.
@@ -127,7 +175,7 @@ impl Scanner<'_> {
   fn comment(&mut self) -> TokenType {
     self.consume_until('\n');
 
-    TokenType::Comment(self.lex(2, 0).into())
+    TokenType::Comment
   }
 
   /// Tries to scan a block comment
@@ -149,7 +197,7 @@ impl Scanner<'_> {
     if self.is_at_end() {
       return TokenType::Error(ScanError::UnterminatedComment);
     }
-    TokenType::BlockComment(self.lex(2, -2).into(), line)
+    TokenType::BlockComment(line)
   }
 
   /// Tries to scan a number.
@@ -177,7 +225,7 @@ impl Scanner<'_> {
       }
       self.advance();
     }
-    TokenType::Whitespace(self.lex(0, 0).into())
+    TokenType::Whitespace
   }
 
   /// Scans a keyword or an identifier.
@@ -189,7 +237,20 @@ impl Scanner<'_> {
     if name == "NaN" {
       return TokenType::Number(f64::NAN);
     }
-    TokenType::from(name)
+    TokenType::keyword(name).unwrap_or_else(|| TokenType::Identifier(self.intern(0, 0)))
+  }
+
+  /// Returns the shared copy of the lexeme slice, allocating it on first use.
+  fn intern(&mut self, lo: isize, hi: isize) -> Rc<str> {
+    let text = Self::slice(&self.input, self.lex_span(), lo, hi);
+    match self.names.get(text) {
+      Some(name) => name.clone(),
+      None => {
+        let name: Rc<str> = text.into();
+        self.names.insert(name.clone());
+        name
+      }
+    }
   }
 }
 
@@ -197,33 +258,63 @@ impl Scanner<'_> {
 impl<'src> Scanner<'src> {
   /// Creates a new scanner.
   pub fn new(src: &'src str) -> Self {
+    Self::with(Input::Str(src, src.char_indices().peekable()))
+  }
+
+  /// Creates a scanner that reads the source from `reader` a chunk at a time, for sources too
+  /// large to hold in memory at once. Tokens and spans are the same as for the whole source.
+  pub fn from_reader(reader: impl Read + 'src) -> Self {
+    Self::with(Input::Stream(Stream::new(reader)))
+  }
+
+  fn with(input: Input<'src>) -> Self {
     let mut scanner = Self {
-      src,
-      chars: src.char_indices().peekable(),
+      input,
       current: (0, '\0'),
       lexeme_start: 0,
+      lexeme_line: 1,
       line: 1,
       emitted_eof: false,
+      line_start: false,
+      directives: Directives::default(),
+      names: HashSet::new(),
     };
     scanner.advance(); // First advancement to set current char
+    scanner.line_start = true;
     scanner
   }
 
+  /// Sets the names that `#if` directives test. Must be called before the first token is
+  /// scanned.
+  pub fn set_defines(&mut self, defines: HashMap<String, String>) {
+    self.directives = Directives::new(defines);
+  }
+
   /// Peeks at the next character tuple.
   #[inline]
   fn peek(&mut self) -> (usize, char) {
-    self
-      .chars
-      .peek()
-      .unwrap_or(&(self.src.len(), '\0'))
-      .to_owned()
+    match &mut self.input {
+      Input::Str(src, chars) => chars.peek().copied().unwrap_or((src.len(), '\0')),
+      Input::Stream(stream) => stream.peek().unwrap_or((stream.end(), '\0')),
+    }
   }
 
   /// Returns the current character and advances `current` cursor.
   #[inline]
   fn advance(&mut self) -> char {
     let curr = self.current.1;
-    self.current = self.chars.next().unwrap_or((self.src.len(), '\0'));
+    match curr {
+      '\n' => self.line_start = true,
+      c if !c.is_ascii_whitespace() => self.line_start = false,
+      _ => {}
+    }
+    self.current = match &mut self.input {
+      Input::Str(src, chars) => chars.next().unwrap_or((src.len(), '\0')),
+      Input::Stream(stream) => {
+        stream.lexeme.push(curr);
+        stream.next().unwrap_or((stream.end(), '\0'))
+      }
+    };
     curr
   }
 
@@ -251,14 +342,27 @@ impl<'src> Scanner<'src> {
   /// Returns the current lexeme span.
   #[inline]
   fn lex_span(&self) -> Span {
-    Span::new(self.lexeme_start, self.current.0, self.line)
+    Span::lines(self.lexeme_start, self.current.0, self.lexeme_line, self.line)
   }
 
   /// Returns a lexeme slice.
   #[inline]
-  fn lex(&self, lo: isize, hi: isize) -> &'src str {
-    let span = self.lex_span().updated(lo, hi);
-    &self.src[span.0..span.1]
+  fn lex(&self, lo: isize, hi: isize) -> &str {
+    Self::slice(&self.input, self.lex_span(), lo, hi)
+  }
+
+  /// Returns the part of the lexeme at `span` from `lo` bytes after its start to `hi` bytes after
+  /// its end.
+  fn slice<'a>(input: &'a Input, span: Span, lo: isize, hi: isize) -> &'a str {
+    match input {
+      Input::Str(src, _) => {
+        let span = span.updated(lo, hi);
+        &src[span.0..span.1]
+      }
+      Input::Stream(stream) => {
+        &stream.lexeme[lo as usize..(stream.lexeme.len() as isize + hi) as usize]
+      }
+    }
   }
 
   /// Checks if the scanner has finished.
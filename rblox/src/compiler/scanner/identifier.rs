@@ -1,3 +1,6 @@
+/// Longest identifier allowed, in bytes.
+pub const MAX_IDENTIFIER_LEN: usize = 255;
+
 /// Checks if the given char is valid as an identifier's start character.
 #[inline]
 pub fn is_valid_identifier_start(c: char) -> bool {
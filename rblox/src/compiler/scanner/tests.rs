@@ -60,13 +60,173 @@ forest varied\0";
   assert_eq!(scanner.next(), Some(Token::new(TokenType::True, Span::new(128, 132, 5))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::Var, Span::new(133, 136, 5))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::While, Span::new(137, 142, 5))));
-  assert_eq!(scanner.next(), Some(Token::new(TokenType::Comment(" comment".into()), Span::new(143, 153, 5))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::Comment, Span::new(143, 153, 5))));
 
-  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(" block\ncomment ".into(), 6), Span::new(154, 173, 6))));
-  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(" inline block".into(), 8), Span::new(174, 191, 8))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(6), Span::lines(154, 173, 6, 7))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(8), Span::new(174, 191, 8))));
 
   assert_eq!(scanner.next(), Some(Token::new(TokenType::Identifier("forest".into()), Span::new(192, 198, 9))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::Identifier("varied".into()), Span::new(199, 205, 9))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::EOF, Span::new(205, 206, 9))));
 
 }
+
+#[test]
+fn emits_symbol_tokens() {
+  let mut scanner = Scanner::new(":red : blue");
+
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::Symbol("red".into()), Span::new(0, 4, 1))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::Error(ScanError::UnexpectedChar(':')), Span::new(5, 6, 1))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::Identifier("blue".into()), Span::new(7, 11, 1))));
+}
+
+#[test]
+fn repeated_text_shares_allocation() {
+  let texts: Vec<_> = Scanner::new("a b a \"a\" :a")
+    .filter_map(|token| match token.kind {
+      TokenType::Identifier(s) | TokenType::String(s) | TokenType::Symbol(s) => Some(s),
+      _ => None,
+    })
+    .collect();
+
+  assert_eq!(texts.len(), 5);
+  assert!(!std::rc::Rc::ptr_eq(&texts[0], &texts[1]));
+  for text in &texts[2..] {
+    assert!(std::rc::Rc::ptr_eq(&texts[0], text));
+  }
+}
+
+#[test]
+fn multiline_tokens_span_their_lines() {
+  let mut scanner = Scanner::new("\"one\ntwo\" /* a\n\nb */ x");
+
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::String("one\ntwo".into()), Span::lines(0, 9, 1, 2))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(2), Span::lines(10, 20, 2, 4))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::Identifier("x".into()), Span::new(21, 22, 4))));
+}
+
+#[test]
+fn joined_spans_cover_both_lines() {
+  let first = Span::new(0, 3, 1);
+  let second = Span::lines(5, 9, 2, 3);
+
+  assert_eq!(first.to(second), Span::lines(0, 9, 1, 3));
+  assert_eq!(second.to(first), Span::lines(0, 9, 1, 3));
+}
+
+#[test]
+fn dots_around_numbers_are_separate_tokens() {
+  let kinds: Vec<_> = Scanner::new("4.5. 4. .5 1.2.3").map(|token| token.kind).collect();
+
+  use TokenType::*;
+  assert_eq!(
+    kinds[..10],
+    [Number(4.5), Dot, Number(4.0), Dot, Dot, Number(5.0), Number(1.2), Dot, Number(3.0), EOF]
+  );
+}
+
+/// Literal-like strings of digits and dots, from a fixed xorshift sequence so failures reproduce.
+fn number_like_strings(count: usize) -> Vec<String> {
+  let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+  let mut next = move || {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+  };
+  (0..count)
+    .map(|_| {
+      let len = 1 + next() % 12;
+      (0..len)
+        .map(|_| match next() % 5 {
+          0 => '.',
+          _ => char::from(b'0' + (next() % 10) as u8),
+        })
+        .collect()
+    })
+    .collect()
+}
+
+#[test]
+fn numbers_match_std_parse() {
+  for src in number_like_strings(2000) {
+    let mut pos = 0;
+    for token in Scanner::new(&src) {
+      let lexeme = &src[token.span.0..token.span.1];
+      assert_eq!(token.span.0, pos, "gap before {lexeme:?} in {src:?}");
+      pos = token.span.1;
+      match token.kind {
+        TokenType::Number(n) => {
+          assert!(!lexeme.starts_with('.') && !lexeme.ends_with('.'), "{lexeme:?} in {src:?}");
+          assert!(lexeme.matches('.').count() <= 1, "{lexeme:?} in {src:?}");
+          assert_eq!(Ok(n), lexeme.parse::<f64>(), "{lexeme:?} in {src:?}");
+        }
+        TokenType::Dot => {}
+        TokenType::EOF => break,
+        other => panic!("unexpected {other:?} in {src:?}"),
+      }
+    }
+    assert_eq!(pos, src.len(), "{src:?} was not scanned to the end");
+  }
+}
+
+/// Reads one byte per call, so that every character and lexeme is split between reads.
+struct Trickle<'a>(&'a [u8]);
+
+impl std::io::Read for Trickle<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let Some((first, rest)) = self.0.split_first() else { return Ok(0) };
+    buf[0] = *first;
+    self.0 = rest;
+    Ok(1)
+  }
+}
+
+#[test]
+fn streamed_source_scans_the_same() {
+  let src = "var café = \"naïve\nstring\" + :sym;\n/* ✓ block\ncomment */ print café / 2.5; // end\n\"open";
+  let whole: Vec<Token> = Scanner::new(src).collect();
+  assert_eq!(Scanner::from_reader(Trickle(src.as_bytes())).collect::<Vec<_>>(), whole);
+  assert_eq!(Scanner::from_reader(src.as_bytes()).collect::<Vec<_>>(), whole);
+}
+
+#[test]
+fn streamed_source_replaces_invalid_utf8() {
+  let tokens: Vec<_> = Scanner::from_reader(&b"a \xff b"[..]).map(|token| token.kind).collect();
+  let expected = [
+    TokenType::Identifier("a".into()),
+    TokenType::Error(ScanError::UnexpectedChar(char::REPLACEMENT_CHARACTER)),
+    TokenType::Identifier("b".into()),
+    TokenType::EOF,
+  ];
+  assert_eq!(tokens, expected);
+}
+
+#[test]
+fn directives_leave_out_code_but_not_strings() {
+  let src = "var a = \"x\n#if DEBUG\n\";\n  #if DEBUG\nprint a;\n#else\nprint 1;\n#end\n";
+  let kinds = |defines: &[&str]| {
+    let mut scanner = Scanner::new(src);
+    scanner.set_defines(defines.iter().map(|name| (name.to_string(), "true".into())).collect());
+    scanner.map(|token| token.kind).collect::<Vec<_>>()
+  };
+  let decl = [
+    TokenType::Var,
+    TokenType::Identifier("a".into()),
+    TokenType::Equal,
+    TokenType::String("x\n#if DEBUG\n".into()),
+    TokenType::Semicolon,
+  ];
+  let with = [TokenType::Print, TokenType::Identifier("a".into()), TokenType::Semicolon, TokenType::EOF];
+  let without = [TokenType::Print, TokenType::Number(1.0), TokenType::Semicolon, TokenType::EOF];
+  assert_eq!(kinds(&["DEBUG"]), [&decl[..], &with].concat());
+  assert_eq!(kinds(&[]), [&decl[..], &without].concat());
+}
+
+#[test]
+fn unended_if_is_reported_at_the_directive() {
+  let tokens: Vec<_> = Scanner::new("print 1;\n#if DEBUG\nprint 2;\n").collect();
+  let error = &tokens[tokens.len() - 2];
+  assert_eq!(error.kind, TokenType::Error(ScanError::UnendedIf));
+  assert_eq!(error.span, Span::new(9, 18, 2));
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use std::fmt::{self, Display};
+use std::{fmt::{self, Display}, rc::Rc};
 
 use crate::{
   common::Span,
@@ -12,6 +12,7 @@ use crate::{
 
 
 #[derive(Debug, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenType {
   // single character
   LeftParen,
@@ -27,8 +28,9 @@ pub enum TokenType {
 
   // one, two chars
   Slash,
-  Comment(String),
-  BlockComment(String, u32),
+  Comment,
+  /// Block comment, with the line it starts on.
+  BlockComment(u32),
   Bang,
   BangEqual,
   Equal,
@@ -38,11 +40,12 @@ pub enum TokenType {
   Less,
   LessEqual,
 
-  // literals
-  Identifier(String),
-  String(String),
+  // literals, sharing one allocation per distinct text
+  Identifier(Rc<str>),
+  String(Rc<str>),
+  Symbol(Rc<str>),
   Number(f64),
-  Whitespace(String),
+  Whitespace,
 
   // keywords
   And,
@@ -52,6 +55,8 @@ pub enum TokenType {
   Fun,
   For,
   If,
+  Import,
+  Native,
   Nil,
   Or,
   Print,
@@ -92,12 +97,44 @@ impl TokenType {
     }
   }
 
-}
+  /// Comments and whitespace, which the parser skips.
+  pub fn is_trivia(&self) -> bool {
+    use TokenType::*;
+    matches!(self, Comment | BlockComment(_) | Whitespace)
+  }
 
-impl From<&str> for TokenType {
-  fn from(value: &str) -> Self {
+  /// Keywords that begin a declaration or statement. The parser resynchronizes on these after an
+  /// error.
+  pub fn is_statement_start(&self) -> bool {
+    use TokenType::*;
+    matches!(self, Break | Class | Continue | For | Fun | If | Import | Native | Print | Return | Var | While)
+  }
+
+  /// Operators written between their two operands.
+  pub fn is_binary_op(&self) -> bool {
+    use TokenType::*;
+    matches!(
+      self,
+      Minus | Plus | Slash | Star | BangEqual | EqualEqual |
+      Greater | GreaterEqual | Less | LessEqual | And | Or
+    )
+  }
+
+  /// Operators written before their operand.
+  pub fn is_unary_op(&self) -> bool {
+    matches!(self, TokenType::Bang | TokenType::Minus)
+  }
+
+  /// Tokens that stand for a value on their own.
+  pub fn is_literal(&self) -> bool {
+    use TokenType::*;
+    matches!(self, Number(_) | String(_) | Symbol(_) | True | False | Nil)
+  }
+
+  /// Returns the keyword spelled by `text`, if any.
+  pub fn keyword(text: &str) -> Option<Self> {
     use TokenType::*;
-    match value {
+    let keyword = match text {
       "nil" => Nil,
       "true" => True,
       "false" => False,
@@ -107,6 +144,8 @@ impl From<&str> for TokenType {
       "and" => And,
       "or" => Or,
       "if" => If,
+      "import" => Import,
+      "native" => Native,
       "else" => Else,
       "return" => Return,
       "fun" => Fun,
@@ -118,8 +157,15 @@ impl From<&str> for TokenType {
       "print" => Print,
       // "typeof" => Typeof,
       // "show" => Show,
-      identifier => Identifier(identifier.to_string()),
-    }
+      _ => return None,
+    };
+    Some(keyword)
+  }
+}
+
+impl From<&str> for TokenType {
+  fn from(value: &str) -> Self {
+    Self::keyword(value).unwrap_or_else(|| Self::Identifier(value.into()))
   }
 }
 
@@ -130,6 +176,7 @@ impl Display for TokenType {
       // literals
       Identifier(s) => s.fmt(f),
       String(s) => write!(f, "\"{}\"", s),
+      Symbol(s) => write!(f, ":{}", s),
       Number(n) => n.fmt(f),
 
       // symbols
@@ -163,6 +210,8 @@ impl Display for TokenType {
       Fun => f.write_str("fun"),
       For => f.write_str("for"),
       If => f.write_str("if"),
+      Import => f.write_str("import"),
+      Native => f.write_str("native"),
       Nil => f.write_str("nil"),
       Or => f.write_str("or"),
       Print => f.write_str("print"),
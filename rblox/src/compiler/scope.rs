@@ -1,7 +1,10 @@
 
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::{cell::RefCell, collections::{BTreeMap, BTreeSet}, fmt::Display, path::PathBuf, rc::Rc};
 
-use crate::common::{data::{LoxClosure, LoxFunction, LoxUpvalue, NativeFunction, Push}, Span};
+use crate::{
+  common::{data::{LoxClosure, LoxFunction, LoxObject, LoxUpvalue, NativeFunction, Push}, Span},
+  gc::usage::{self, MemUsage},
+};
 
 pub struct Local {
   pub name : String,
@@ -15,13 +18,68 @@ pub struct Module {
   pub functions: Vec<Rc<LoxFunction>>,
   pub natives: Vec<Rc<NativeFunction>>,
   pub closures: Vec<Rc<RefCell<LoxClosure>>>,
-  pub upvals: Vec<Rc<RefCell<LoxUpvalue>>>
+  pub upvals: Vec<Rc<RefCell<LoxUpvalue>>>,
+  /// Interned symbols. Ordered so that module dumps are reproducible.
+  pub symbols: BTreeMap<String, Rc<LoxObject>>,
+  /// Strings the compiler built by folding constants, shared by every chunk that loads them.
+  pub strings: BTreeMap<String, Rc<LoxObject>>,
+  /// Script function of each imported file.
+  pub imported: BTreeMap<PathBuf, usize>,
+  /// Files compiled or loaded into this module, with their source hashes.
+  pub sources: Vec<(PathBuf, u64)>,
+  /// Number of imports skipped because the file was already imported.
+  pub skipped_imports: usize,
+  /// Natives the host registered, which scripts bind with `native fun` declarations.
+  pub registered: BTreeMap<String, Rc<NativeFunction>>,
+  /// Globals declared by the code compiled into this module, which `--strict-globals` checks
+  /// references against.
+  pub globals: BTreeSet<String>,
 }
 
 impl Module {
   pub fn new() -> Rc<RefCell<Self>> {
     Rc::new(RefCell::new(Self::default()))
   }
+
+  /// Returns the unique symbol object for `name`, creating it on first use.
+  pub fn intern(&mut self, name: &str) -> Rc<LoxObject> {
+    self.symbols
+      .entry(name.into())
+      .or_insert_with(|| Rc::new(LoxObject::Symbol(name.into())))
+      .clone()
+  }
+
+  /// Returns the string object for `text` built at compile time, creating it on first use.
+  pub fn intern_string(&mut self, text: String) -> Rc<LoxObject> {
+    self.strings
+      .entry(text)
+      .or_insert_with_key(|text| Rc::new(LoxObject::String(text.clone())))
+      .clone()
+  }
+
+  /// The files loaded from `sources` on, as an image compiled from the functions from `base` on
+  /// records them: with their source hashes and the index of their script function after `base`.
+  pub fn deps(&self, sources: usize, base: usize) -> Vec<(String, u64, usize)> {
+    self.sources[sources..].iter()
+      .map(|(dep, hash)| (dep.to_string_lossy().into_owned(), *hash, self.imported[dep] - base))
+      .collect()
+  }
+
+  /// Whether `name` is a global declared by code of this module or a native.
+  pub fn declares(&self, name: &str) -> bool {
+    self.globals.contains(name) || self.natives.iter().any(|native| native.name == name)
+  }
+
+  /// Approximate bytes held by the module's functions, closures and upvalues.
+  pub fn usage(&self) -> MemUsage {
+    MemUsage {
+      strings: 0,
+      functions: self.functions.iter().map(|fun| usage::function_size(fun)).sum(),
+      closures: self.closures.iter().map(|closure| usage::closure_size(&closure.borrow())).sum(),
+      upvalues: self.upvals.len() * usage::upvalue_size(),
+      foreign: 0,
+    }
+  }
 }
 
 impl Display for Module {
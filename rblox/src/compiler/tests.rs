@@ -1,8 +1,13 @@
 use crate::common::Span;
 
-use super::scanner::{
-  Scanner, 
-  token::{Token, TokenType}
+use std::collections::BTreeSet;
+
+use super::{
+  grammar::{grammar, productions, token_kinds},
+  scanner::{
+    Scanner, 
+    token::{Token, TokenType}
+  },
 };
 
 
@@ -67,13 +72,70 @@ forest varied\0";
   assert_eq!(scanner.next(), Some(Token::new(TokenType::True, Span::new(128, 132, 5))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::Var, Span::new(133, 136, 5))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::While, Span::new(137, 142, 5))));
-  assert_eq!(scanner.next(), Some(Token::new(TokenType::Comment(" comment".into()), Span::new(143, 153, 5))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::Comment, Span::new(143, 153, 5))));
 
-  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(" block\ncomment ".into(), 6), Span::new(154, 173, 6))));
-  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(" inline block".into(), 8), Span::new(174, 191, 8))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(6), Span::lines(154, 173, 6, 7))));
+  assert_eq!(scanner.next(), Some(Token::new(TokenType::BlockComment(8), Span::new(174, 191, 8))));
 
   assert_eq!(scanner.next(), Some(Token::new(TokenType::Identifier("forest".into()), Span::new(192, 198, 9))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::Identifier("varied".into()), Span::new(199, 205, 9))));
   assert_eq!(scanner.next(), Some(Token::new(TokenType::EOF, Span::new(205, 206, 9))));
 
 }
+
+/// Names of the productions referenced by a rule body, skipping quoted terminals.
+fn nonterminals(body: &str) -> Vec<&str> {
+  body
+    .split('"')
+    .step_by(2)
+    .flat_map(|part| part.split(|c: char| !c.is_ascii_alphabetic()))
+    .filter(|word| word.starts_with(|c: char| c.is_ascii_lowercase()))
+    .collect()
+}
+
+#[test]
+fn grammar_is_closed() {
+  let productions = productions();
+  let defined: Vec<&str> = productions.iter().map(|p| p.name.as_str()).collect();
+  for production in &productions {
+    for name in nonterminals(&production.body) {
+      assert!(defined.contains(&name), "`{}` refers to undefined `{name}`", production.name);
+    }
+    let used = productions.iter().any(|p| nonterminals(&p.body).contains(&production.name.as_str()));
+    assert!(used || production.name == "program", "`{}` is never used", production.name);
+  }
+}
+
+/// Name of a variant as `Debug` or its declaration spells it, without its payload.
+fn variant_name(spelling: &str) -> &str {
+  spelling.split('(').next().unwrap().trim()
+}
+
+#[test]
+fn token_kinds_cover_every_token() {
+  // the variants are read off the declaration, so that one added there is noticed here
+  let src = include_str!("scanner/token.rs");
+  let decl = &src[src.find("pub enum TokenType {").unwrap()..];
+  let decl = &decl[..decl.find("\n}").unwrap()];
+  let declared: BTreeSet<&str> = decl
+    .lines()
+    .skip(1)
+    .flat_map(|line| line.split("//").next().unwrap().split(','))
+    .map(variant_name)
+    .filter(|name| !name.is_empty())
+    .collect();
+
+  let kinds: Vec<String> = token_kinds().iter().map(|kind| format!("{kind:?}")).collect();
+  let mut covered: BTreeSet<&str> = kinds.iter().map(|kind| variant_name(kind)).collect();
+  // trivia and scan errors never reach the parser
+  covered.extend(["Comment", "BlockComment", "Whitespace", "Dummy", "Error"]);
+  assert_eq!(declared, covered);
+}
+
+#[test]
+fn grammar_follows_rule_table() {
+  let grammar = grammar();
+  assert!(grammar.contains(r#"factor       → unary ( ( "*" | "/" ) unary )* ;"#));
+  assert!(grammar.contains(r#"unary        → ( "-" | "!" ) unary | call ;"#));
+  assert!(grammar.contains(r#"printStmt    → "print" expression ";" ;"#));
+}
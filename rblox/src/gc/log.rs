@@ -0,0 +1,95 @@
+//! Structured record of garbage collections.
+
+use std::{fmt::Display, time::Duration};
+
+/// Why a collection ran.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+  /// Bytes held by tracked objects passed the collection threshold.
+  Threshold(usize),
+  /// The REPL is waiting for input.
+  Idle,
+}
+
+impl Display for Trigger {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Threshold(_) => write!(f, "threshold"),
+      Self::Idle => write!(f, "idle"),
+    }
+  }
+}
+
+/// Output format of the GC log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+  Text,
+  /// One JSON object per line.
+  Json,
+}
+
+/// A single collection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GcEvent {
+  pub trigger: Trigger,
+  /// Objects examined by the sweep.
+  pub scanned: usize,
+  /// Objects released by the sweep.
+  pub freed: usize,
+  /// Approximate bytes released by the sweep.
+  pub freed_bytes: usize,
+  pub duration: Duration,
+}
+
+impl GcEvent {
+  pub fn format(&self, format: LogFormat) -> String {
+    let live = self.scanned - self.freed;
+    let micros = self.duration.as_micros();
+    match format {
+      LogFormat::Text => format!(
+        "[gc] {}: scanned {}, freed {} ({} B), {live} live in {micros}us",
+        self.trigger, self.scanned, self.freed, self.freed_bytes
+      ),
+      LogFormat::Json => {
+        let threshold = match self.trigger {
+          Trigger::Threshold(limit) => format!(",\"threshold_bytes\":{limit}"),
+          Trigger::Idle => String::new(),
+        };
+        format!(
+          "{{\"event\":\"gc\",\"trigger\":\"{}\"{threshold},\"scanned\":{},\"freed\":{},\"freed_bytes\":{},\"live\":{live},\"duration_us\":{micros}}}",
+          self.trigger, self.scanned, self.freed, self.freed_bytes
+        )
+      }
+    }
+  }
+}
+
+/// Running totals over all collections.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GcStats {
+  pub collections: usize,
+  pub scanned: usize,
+  pub freed: usize,
+  pub freed_bytes: usize,
+  pub duration: Duration,
+}
+
+impl GcStats {
+  pub fn record(&mut self, event: &GcEvent) {
+    self.collections += 1;
+    self.scanned += event.scanned;
+    self.freed += event.freed;
+    self.freed_bytes += event.freed_bytes;
+    self.duration += event.duration;
+  }
+}
+
+impl Display for GcStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "[gc] {} collections, {} objects scanned, {} freed ({} B), {}us total",
+      self.collections, self.scanned, self.freed, self.freed_bytes, self.duration.as_micros()
+    )
+  }
+}
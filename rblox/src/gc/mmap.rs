@@ -1,19 +1,104 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{collections::HashMap, rc::Rc, time::Instant};
 
-use crate::common::data::LoxObject;
+use crate::{
+  common::data::LoxObject,
+  gc::{
+    log::{GcEvent, GcStats, LogFormat, Trigger},
+    usage::{object_size, MemUsage},
+  },
+  vm::events::{Event, Shared},
+};
 
 
 pub struct MemManager {
   objects: Vec<Rc<LoxObject>>,
-  strings: HashMap<String, Rc<LoxObject>>
+  strings: HashMap<String, Rc<LoxObject>>,
+  /// Handles returned by natives, by address. Collecting one drops the resource unless a script
+  /// still holds it.
+  foreign: HashMap<*const LoxObject, Rc<LoxObject>>,
+  /// Approximate bytes held by `objects` and `foreign`.
+  bytes: usize,
+  /// Byte count that triggers the next collection.
+  next_gc: usize,
+  /// Where collections are logged to stderr, if at all.
+  pub log: Option<LogFormat>,
+  pub stats: GcStats,
+  /// Told about each collection, when set.
+  pub subscriber: Option<Shared>,
 }
 
 impl MemManager {
+  const GC_MIN: usize = 64 * 1024;
+  const GC_GROWTH: usize = 2;
+
   pub fn new() -> Self {
     Self {
       objects: Vec::new(),
-      strings: HashMap::new()
+      strings: HashMap::new(),
+      foreign: HashMap::new(),
+      bytes: 0,
+      next_gc: Self::GC_MIN,
+      log: None,
+      stats: GcStats::default(),
+      subscriber: None,
+    }
+  }
+
+  /// Releases objects that are only referenced by the manager itself.
+  pub fn collect(&mut self, trigger: Trigger) -> GcEvent {
+    let start = Instant::now();
+    let scanned = self.objects.len() + self.foreign.len();
+
+    // interned strings are held once by the table and once by `objects`
+    self.strings.retain(|_, obj| Rc::strong_count(obj) > 2);
+    self.objects.retain(|obj| Rc::strong_count(obj) > 1);
+    self.foreign.retain(|_, obj| Rc::strong_count(obj) > 1);
+    let live = self.objects.iter().chain(self.foreign.values()).map(|obj| object_size(obj)).sum();
+
+    let event = GcEvent {
+      trigger,
+      scanned,
+      freed: scanned - self.objects.len() - self.foreign.len(),
+      freed_bytes: self.bytes - live,
+      duration: start.elapsed(),
+    };
+    self.bytes = live;
+    self.next_gc = Self::GC_MIN.max(live * Self::GC_GROWTH);
+    self.stats.record(&event);
+    if let Some(format) = self.log {
+      eprintln!("{}", event.format(format));
+    }
+    if let Some(subscriber) = &self.subscriber {
+      subscriber.borrow_mut().on_event(&Event::Gc(&event));
+    }
+    event
+  }
+
+  fn maybe_collect(&mut self) {
+    if self.bytes > self.next_gc {
+      self.collect(Trigger::Threshold(self.next_gc));
+    }
+  }
+
+  /// Approximate bytes held by tracked objects.
+  pub fn bytes(&self) -> usize {
+    self.bytes
+  }
+
+  /// Approximate bytes held by tracked objects, by kind.
+  pub fn usage(&self) -> MemUsage {
+    let foreign = self.foreign.values().map(|obj| object_size(obj)).sum();
+    MemUsage { strings: self.bytes() - foreign, foreign, ..Default::default() }
+  }
+
+  /// Tracks a handle returned by a native, unless it is tracked already.
+  pub fn add_foreign(&mut self, obj: &Rc<LoxObject>) {
+    if self.foreign.contains_key(&Rc::as_ptr(obj)) {
+      return;
     }
+    self.maybe_collect();
+    self.bytes += object_size(obj);
+    self.foreign.insert(Rc::as_ptr(obj), obj.clone());
   }
 
   pub fn _alloc_obj(&mut self, obj: LoxObject) -> Rc<LoxObject> {
@@ -23,6 +108,7 @@ impl MemManager {
   }
 
   pub fn _push(&mut self, obj: &Rc<LoxObject>) {
+    self.bytes += object_size(obj);
     self.objects.push(obj.clone());
   }
 
@@ -32,7 +118,9 @@ impl MemManager {
       None => {
         let obj = Rc::new(LoxObject::String(str.into()));
         
+        self.maybe_collect();
         self.strings.insert(str.into(), obj.clone());
+        self.bytes += object_size(&obj);
         self.objects.push(obj.clone());
         
         obj
@@ -40,7 +128,7 @@ impl MemManager {
     }
   }
 
-  pub fn take_string(&mut self, str: &str) -> Rc<LoxObject> {
+  pub fn _take_string(&mut self, str: &str) -> Rc<LoxObject> {
     match self.strings.get(str) {
       Some(_) => {
         self.strings.remove(str).unwrap()
@@ -49,7 +137,7 @@ impl MemManager {
     }
   }
 
-  pub fn find_string(&mut self, str: &str) -> Option<Rc<LoxObject>> {
+  pub fn _find_string(&mut self, str: &str) -> Option<Rc<LoxObject>> {
     self.strings.get(str).cloned()
   }
 }
\ No newline at end of file
@@ -1,2 +1,6 @@
+pub mod mmap;
+pub mod log;
+pub mod usage;
 
-pub mod mmap;
\ No newline at end of file
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,57 @@
+use std::{rc::Rc, time::Duration};
+
+use crate::common::data::LoxObject;
+
+use super::{
+  usage,
+  log::{GcEvent, LogFormat, Trigger},
+  mmap::MemManager,
+};
+
+#[test]
+fn collects_unreferenced_strings() {
+  let mut mem = MemManager::new();
+  let kept = mem.add_string("kept");
+  mem.add_string("dropped");
+
+  let before = mem.bytes();
+  let event = mem.collect(Trigger::Idle);
+  assert_eq!((event.scanned, event.freed), (2, 1));
+  assert_eq!(event.freed_bytes, usage::object_size(&LoxObject::String("dropped".into())));
+  assert_eq!(mem.bytes(), before - event.freed_bytes);
+  assert!(mem._find_string("dropped").is_none());
+  assert!(Rc::ptr_eq(&mem._find_string("kept").unwrap(), &kept));
+
+  mem.collect(Trigger::Idle);
+  assert_eq!(mem.stats.collections, 2);
+  assert_eq!((mem.stats.scanned, mem.stats.freed), (3, 1));
+}
+
+#[test]
+fn collects_past_threshold() {
+  let mut mem = MemManager::new();
+  for i in 0..5000 {
+    mem.add_string(&i.to_string());
+  }
+  assert!(mem.stats.collections > 0);
+  assert!(mem.stats.freed > 0);
+}
+
+#[test]
+fn formats_events() {
+  let event = GcEvent {
+    trigger: Trigger::Threshold(1024),
+    scanned: 1025,
+    freed: 1000,
+    freed_bytes: 50000,
+    duration: Duration::from_micros(42),
+  };
+  assert_eq!(
+    event.format(LogFormat::Text),
+    "[gc] threshold: scanned 1025, freed 1000 (50000 B), 25 live in 42us"
+  );
+  assert_eq!(
+    event.format(LogFormat::Json),
+    "{\"event\":\"gc\",\"trigger\":\"threshold\",\"threshold_bytes\":1024,\"scanned\":1025,\"freed\":1000,\"freed_bytes\":50000,\"live\":25,\"duration_us\":42}"
+  );
+}
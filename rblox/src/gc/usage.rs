@@ -0,0 +1,82 @@
+//! Approximate accounting of heap memory by object kind.
+
+use std::{cell::RefCell, fmt::Display, mem::size_of};
+
+use crate::common::{
+  data::{LoxClosure, LoxFunction, LoxObject, LoxUpvalue},
+  Ins, Span, Value,
+};
+
+/// Reference counts stored alongside every `Rc` allocation.
+const RC_HEADER: usize = 2 * size_of::<usize>();
+
+/// Approximate bytes held by each kind of object.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MemUsage {
+  pub strings: usize,
+  pub functions: usize,
+  pub closures: usize,
+  pub upvalues: usize,
+  pub foreign: usize,
+}
+
+impl MemUsage {
+  pub fn total(&self) -> usize {
+    self.strings + self.functions + self.closures + self.upvalues + self.foreign
+  }
+}
+
+impl std::ops::Add for MemUsage {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self {
+    Self {
+      strings: self.strings + rhs.strings,
+      functions: self.functions + rhs.functions,
+      closures: self.closures + rhs.closures,
+      upvalues: self.upvalues + rhs.upvalues,
+      foreign: self.foreign + rhs.foreign,
+    }
+  }
+}
+
+impl Display for MemUsage {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "{:<10}{:>10} B", "strings", self.strings)?;
+    writeln!(f, "{:<10}{:>10} B", "functions", self.functions)?;
+    writeln!(f, "{:<10}{:>10} B", "closures", self.closures)?;
+    writeln!(f, "{:<10}{:>10} B", "upvalues", self.upvalues)?;
+    writeln!(f, "{:<10}{:>10} B", "foreign", self.foreign)?;
+    write!(f, "{:<10}{:>10} B", "total", self.total())
+  }
+}
+
+/// Size of a heap object tracked by the memory manager, including its payload.
+pub fn object_size(obj: &LoxObject) -> usize {
+  let payload = match obj {
+    LoxObject::String(s) | LoxObject::Symbol(s) | LoxObject::Identifier(s) => s.len(),
+    LoxObject::Function(s, _) | LoxObject::Native(s, _) | LoxObject::Closure(s, _) => s.len(),
+    LoxObject::Class(class) => class.name.len(),
+    LoxObject::Instance(instance) => instance.fields.borrow().len() * size_of::<Value>(),
+    LoxObject::BoundMethod(bound) => bound.name.len(),
+    LoxObject::Foreign(handle) => handle.tag.len() + handle.size(),
+  };
+  RC_HEADER + size_of::<LoxObject>() + payload
+}
+
+pub fn function_size(fun: &LoxFunction) -> usize {
+  RC_HEADER
+    + size_of::<LoxFunction>()
+    + fun.name.len()
+    + fun.chunk.name.len()
+    + fun.chunk.len() * (size_of::<Ins>() + size_of::<Span>())
+    + fun.chunk.constants.len() * size_of::<Value>()
+}
+
+pub fn closure_size(closure: &LoxClosure) -> usize {
+  RC_HEADER + size_of::<RefCell<LoxClosure>>() + closure.upvalues.capacity() * size_of::<usize>()
+}
+
+pub fn upvalue_size() -> usize {
+  RC_HEADER + size_of::<RefCell<LoxUpvalue>>()
+}
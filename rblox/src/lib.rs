@@ -4,28 +4,247 @@ mod compiler;
 mod gc;
 mod user;
 
-use std::str;
+use std::{path::Path, str};
 
-pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(), &'static str> {
-  args.next();
+pub use user::Failure;
 
-  let file_path = match args.next() {
-    Some(arg) => arg,
-    None => {
-      user::run_repl();
-      return Ok(());
+pub use common::{convert::{FromLox, ToLox}, data::NativeFunction, error::{ErrorType, Label, Message}, Span, Value};
+pub use vm::{
+  clock::{Clock, MockClock, SystemClock},
+  error::RuntimeError,
+  events::{Event, Level, Subscriber},
+  hooks::Hooks,
+  VM,
+};
+
+pub use gc::log::{GcEvent, Trigger};
+
+use gc::log::LogFormat;
+
+const USAGE: &str = "Usage: rlox [--watch] [--debug] [--trace-execution[=<function>]] [--trace-stack=<n>] [--gc-log[=json]] [--gc-stats] [--time] [--profile-calls <file>] [--checked-arith] [--strict-globals] [--no-inline] [--define NAME[=VALUE]]... [--max-string-len=<n>] [--verify] [--cache-dir <dir>] [--quiet] [--list-suppressed] [--stream] [--emit=ir|loxc] [--verify-source <file>] [--grammar] [-e <code> | script [args...] | image.loxc|bundle.loxb [args...] | - [args...]]";
+
+/// Why the process should exit with an error.
+#[derive(Debug)]
+pub enum ExitError {
+  /// The command line was not understood.
+  Usage(&'static str),
+  /// The script failed; its diagnostics have already been printed.
+  Failed(Failure),
+}
+
+impl ExitError {
+  /// Exit code for the error, following the book: 64 for usage errors, 65 for compile errors,
+  /// 70 for runtime errors and 74 when the script cannot be read. A script that set its own exit
+  /// code exits with that.
+  pub fn code(&self) -> i32 {
+    match self {
+      ExitError::Usage(_) => 64,
+      ExitError::Failed(Failure::Exit(code)) => *code,
+      ExitError::Failed(Failure::Compile) => 65,
+      ExitError::Failed(Failure::Runtime) => 70,
+      ExitError::Failed(Failure::Io(_)) => 74,
     }
-  };
+  }
 
-  // don't accept extra arguments
-  if let Some(_) = args.next() {
-    return Err("Usage: rlox [script]");
+  /// Message to print before exiting, unless the script already reported its errors.
+  pub fn message(&self) -> Option<String> {
+    match self {
+      ExitError::Usage(message) => Some(format!("Problem parsing arguments: {message}")),
+      ExitError::Failed(failure @ Failure::Io(_)) => Some(failure.to_string()),
+      ExitError::Failed(_) => None,
+    }
   }
+}
 
-  if let Err(err) = user::run_file(&file_path) {
-    eprintln!("{}", err);
-    return Err("Could not run file")
+impl From<&'static str> for ExitError {
+  fn from(message: &'static str) -> Self {
+    ExitError::Usage(message)
+  }
+}
+
+impl From<Failure> for ExitError {
+  fn from(failure: Failure) -> Self {
+    ExitError::Failed(failure)
+  }
+}
+
+const BUNDLE_USAGE: &str = "Usage: rlox bundle <script> -o <bundle.loxb> | build <script> -o <executable>";
+
+/// Whether this is an executable made by `rlox build`, which runs its own script with all of its
+/// arguments, so that other command lines should hand them to [`parse_args`] unread.
+pub fn is_built() -> bool {
+  user::is_built()
+}
+
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<(), ExitError> {
+  let mut args = args.skip(1).peekable();
+  // an executable made by `build` runs its own script, which gets all of the arguments
+  if let Some(bundle) = user::embedded_bundle().map_err(Failure::Io)? {
+    return Ok(user::run_embedded(&bundle, args.collect())?);
+  }
+  match args.peek().map(String::as_str) {
+    Some("bundle") => return bundle(args.skip(1), |script, out, config| user::bundle(script, out, config)),
+    Some("build") => return bundle(args.skip(1), |script, out, config| user::build(script, out, config)),
+    // `run` only names what happens without a command
+    Some("run") => {
+      args.next();
+    }
+    _ => {}
+  }
+
+  let mut config = user::Config::default();
+  let mut file_path = None;
+  let mut snippet = None;
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-e" => snippet = Some(args.next().ok_or(USAGE)?),
+      "--watch" => config.watch = true,
+      "--debug" => config.debug = true,
+      "--trace-execution" => {
+        config.trace.get_or_insert_with(Default::default);
+      }
+      flag if flag.starts_with("--trace-execution=") => {
+        let function = flag["--trace-execution=".len()..].to_string();
+        config.trace.get_or_insert_with(Default::default).function = Some(function);
+      }
+      "--gc-log" => config.gc_log = Some(LogFormat::Text),
+      "--gc-log=json" => config.gc_log = Some(LogFormat::Json),
+      "--gc-stats" => config.gc_stats = true,
+      "--time" => config.time = true,
+      "--profile-calls" => config.profile_calls = Some(args.next().ok_or(USAGE)?.into()),
+      flag if flag.starts_with("--profile-calls=") => {
+        config.profile_calls = Some(flag["--profile-calls=".len()..].into());
+      }
+      "--checked-arith" => config.checked_arith = true,
+      "--strict-globals" => config.strict_globals = true,
+      "--no-inline" => config.no_inline = true,
+      "--define" => {
+        let define = args.next().ok_or(USAGE)?;
+        let (name, value) = define.split_once('=').unwrap_or((&define, "true"));
+        config.defines.insert(name.into(), value.into());
+      }
+      flag if flag.starts_with("--max-string-len=") => {
+        let len = flag["--max-string-len=".len()..].parse().map_err(|_| USAGE)?;
+        config.max_string_len = Some(len);
+      }
+      "--verify" => config.verify = true,
+      "--cache-dir" => config.cache_dir = Some(args.next().ok_or(USAGE)?.into()),
+      flag if flag.starts_with("--cache-dir=") => {
+        config.cache_dir = Some(flag["--cache-dir=".len()..].into());
+      }
+      "--quiet" => config.quiet = true,
+      "--stream" => config.stream = true,
+      "--list-suppressed" => config.list_suppressed = true,
+      "--emit=ir" => config.emit_ir = true,
+      "--emit=loxc" => config.emit_image = true,
+      "--verify-source" => config.verify_source = Some(args.next().ok_or(USAGE)?.into()),
+      flag if flag.starts_with("--verify-source=") => {
+        config.verify_source = Some(flag["--verify-source=".len()..].into());
+      }
+      "--grammar" => {
+        print!("{}", compiler::grammar::grammar());
+        return Ok(());
+      }
+      flag if flag.starts_with("--trace-stack=") => {
+        let top = flag["--trace-stack=".len()..].parse().map_err(|_| USAGE)?;
+        config.trace.get_or_insert_with(Default::default).top = top;
+      }
+      _ if snippet.is_some() => return Err(USAGE.into()),
+      // the rest of the arguments are the script's own
+      _ => {
+        file_path = Some(arg);
+        config.args.extend(args.by_ref());
+      }
+    }
+  }
+
+  let image = file_path.as_deref().is_some_and(is_image);
+  if config.verify_source.is_some() && !image {
+    return Err("`--verify-source` needs a bytecode image to run".into());
+  }
+
+  if config.stream && (config.watch || config.emit_ir || config.emit_image || image) {
+    return Err("`--stream` only runs a script, without `--watch` or `--emit`".into());
+  }
+
+  if let Some(snippet) = snippet {
+    if config.stream {
+      return Err("`--stream` needs a script to read".into());
+    }
+    if file_path.is_some() {
+      return Err(USAGE.into());
+    }
+    if config.watch {
+      return Err("`--watch` needs a script to watch".into());
+    }
+    if config.emit_ir || config.emit_image {
+      return Err("`--emit` needs a script to compile".into());
+    }
+    return Ok(user::run_snippet(&snippet, &config)?);
+  }
+
+  let Some(file_path) = file_path else {
+    if config.watch {
+      return Err("`--watch` needs a script to watch".into());
+    }
+    if config.emit_ir || config.emit_image {
+      return Err("`--emit` needs a script to compile".into());
+    }
+    if config.profile_calls.is_some() {
+      return Err("`--profile-calls` needs a script or `-e`".into());
+    }
+    if config.stream {
+      return Err("`--stream` needs a script to read".into());
+    }
+    user::run_repl(&config);
+    return Ok(());
   };
 
-  Ok(())
+  if config.watch && file_path == user::STDIN {
+    return Err("`--watch` needs a script file, not standard input".into());
+  }
+
+  if image || is_bundle(&file_path) {
+    if config.watch || config.emit_ir || config.emit_image {
+      return Err("A bytecode image can only be run".into());
+    }
+    if image {
+      return Ok(user::run_image(&file_path, &config)?);
+    }
+    return Ok(user::run_bundle(&file_path, &config)?);
+  }
+
+  if config.emit_ir {
+    return Ok(user::emit_ir(&file_path, &config)?);
+  }
+  if config.emit_image {
+    return Ok(user::emit_image(&file_path, &config)?);
+  }
+
+  Ok(user::run_file(&file_path, &config)?)
+}
+
+/// Whether the script at `path` is a bytecode image written by `--emit=loxc`.
+fn is_image(path: &str) -> bool {
+  Path::new(path).extension().is_some_and(|ext| ext == "loxc")
+}
+
+/// Whether the script at `path` is a bundle written by `bundle`.
+fn is_bundle(path: &str) -> bool {
+  Path::new(path).extension().is_some_and(|ext| ext == "loxb")
+}
+
+/// Handles `bundle <script> -o <file>` and `build <script> -o <file>`, with `args` following the
+/// command and `pack` writing its output.
+fn bundle(
+  args: impl Iterator<Item = String>,
+  pack: fn(&str, &str, &user::Config) -> Result<(), Failure>
+) -> Result<(), ExitError> {
+  let args: Vec<_> = args.collect();
+  match &args[..] {
+    [script, flag, out] | [flag, out, script] if flag == "-o" => {
+      Ok(pack(script, out, &user::Config::default())?)
+    }
+    _ => Err(BUNDLE_USAGE.into()),
+  }
 }
@@ -5,8 +5,10 @@ use std::process;
 // mod tests;
 
 fn main() {
-  let _ = rblox::parse_args(env::args()).unwrap_or_else(|err| {
-    eprintln!("Problem parsing arguments: {err}");
-    process::exit(1);
+  rblox::parse_args(env::args()).unwrap_or_else(|err| {
+    if let Some(message) = err.message() {
+      eprintln!("{message}");
+    }
+    process::exit(err.code());
   });
 }
@@ -1,30 +1,424 @@
 use std::{
-  fs,
-  io::{self, Write},
-  path::Path,
+  collections::HashMap,
+  env,
+  error::Error,
+  fmt, fs,
+  io::{self, Read, Seek, SeekFrom, Write},
+  path::{Path, PathBuf},
 };
 
-use crate::vm::VM;
+use crate::{
+  common::{error::{ErrorType, LoxError}, ir, serialize::{self, Bundle, Flags, Image}, verify},
+  compiler::{cache, compile, parser::state::ParserOptions, scope::Module},
+  gc::log::LogFormat,
+  vm::{profile::CallProfile, Trace, VM},
+};
+
+/// Directory for compiled imports: `--cache-dir` if given, else `LOX_CACHE_DIR` if set. Imports
+/// are not cached otherwise.
+fn cache_dir(config: &Config) -> Option<PathBuf> {
+  config.cache_dir.clone().or_else(|| env::var_os("LOX_CACHE_DIR").map(Into::into))
+}
+
+/// Script path that stands for standard input.
+pub const STDIN: &str = "-";
+
+/// Reads the script at `file`, or standard input if `file` is [`STDIN`]. Also returns the path
+/// the script was read from, if any.
+fn read_script(file: &Path) -> io::Result<(String, Option<&Path>)> {
+  if file != Path::new(STDIN) {
+    return Ok((fs::read_to_string(file)?, Some(file)));
+  }
+  let mut src = String::new();
+  io::stdin().read_to_string(&mut src)?;
+  Ok((src, None))
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+  /// Hot-reload changed functions while the script runs.
+  pub watch: bool,
+  /// Define debugging natives such as `dumpStack` in release builds too.
+  pub debug: bool,
+  /// Trace each executed instruction to stderr.
+  pub trace: Option<Trace>,
+  /// Log each garbage collection to stderr.
+  pub gc_log: Option<LogFormat>,
+  /// Print collection totals when the script ends.
+  pub gc_stats: bool,
+  /// Print how long each phase took, and peak memory, when the script ends.
+  pub time: bool,
+  /// Write the time spent in each call stack to this file, in the folded format of flamegraph
+  /// tools.
+  pub profile_calls: Option<PathBuf>,
+  /// Raise a runtime error when arithmetic overflows to `inf` or produces `NaN`.
+  pub checked_arith: bool,
+  /// Reject uses of globals that are not declared before they run.
+  pub strict_globals: bool,
+  /// Compile every call as a call, without inlining small functions.
+  pub no_inline: bool,
+  /// Overrides [`ParserOptions::max_string_len`].
+  pub max_string_len: Option<usize>,
+  /// Sets [`ParserOptions::list_suppressed`].
+  pub list_suppressed: bool,
+  /// Sets [`ParserOptions::defines`], from `--define`.
+  pub defines: HashMap<String, String>,
+  /// Verify compiled bytecode before running it.
+  pub verify: bool,
+  /// Where to cache compiled imports, see [`ParserOptions::cache_dir`].
+  pub cache_dir: Option<PathBuf>,
+  /// Print the compiled module as JSON instead of running it.
+  pub emit_ir: bool,
+  /// Write the compiled script to stdout as a bytecode image instead of running it.
+  pub emit_image: bool,
+  /// Source of the bytecode image being run. The image is recompiled from it if it is stale.
+  pub verify_source: Option<PathBuf>,
+  /// Compile the script as it is read, a chunk at a time, rather than reading it all first. Calls
+  /// are then never inlined nor loads hoisted out of loops, as both look ahead through the whole
+  /// source, so streamed scripts can run slower.
+  pub stream: bool,
+  /// Only print the script's own output and its errors, not the REPL banner or module listings.
+  pub quiet: bool,
+  /// Arguments after the script, which it reads with `process.arg`.
+  pub args: Vec<String>,
+}
+
+/// Why a script did not run to completion. Diagnostics have already been printed.
+#[derive(Debug)]
+pub enum Failure {
+  /// The script could not be read.
+  Io(io::Error),
+  /// The script was rejected before it ran.
+  Compile,
+  /// The script raised an error while running.
+  Runtime,
+  /// The script ran to completion but set a nonzero exit code with `process.setExitCode`.
+  Exit(i32),
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Failure::Io(err) => write!(f, "Could not read script: {err}"),
+      Failure::Compile => f.write_str("Script was rejected by the compiler"),
+      Failure::Runtime => f.write_str("Script raised a runtime error"),
+      Failure::Exit(code) => write!(f, "Script exited with code {code}"),
+    }
+  }
+}
+
+impl Error for Failure {}
+
+impl From<io::Error> for Failure {
+  fn from(err: io::Error) -> Self {
+    Failure::Io(err)
+  }
+}
+
+pub fn run_file(file: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  if config.stream {
+    return run_stream(file.as_ref(), config);
+  }
+  let (src, file) = read_script(file.as_ref())?;
+  let mut vm = VM::new();
+  vm.options.file = file.map(Into::into);
+  if let (true, Some(file)) = (config.watch, file) {
+    vm.watch(file);
+  }
+  configure(&mut vm, config);
+
+  let profile = profile_calls(&mut vm, config);
+  let res = run(&src, &mut vm);
+  report(&vm, config, profile);
+  res.and_then(|()| exited(&vm))
+}
+
+/// Runs a script compiled as it is read, for `--stream`.
+fn run_stream(file: &Path, config: &Config) -> Result<(), Failure> {
+  let mut vm = VM::new();
+  configure(&mut vm, config);
+  let profile = profile_calls(&mut vm, config);
+  let res = match file == Path::new(STDIN) {
+    true => vm.run_reader(io::stdin().lock(), 0),
+    false => {
+      let script = fs::File::open(file)?;
+      let bytes = script.metadata()?.len() as usize;
+      vm.options.file = Some(file.into());
+      vm.run_reader(script, bytes)
+    }
+  };
+  report(&vm, config, profile);
+  res.map_err(failure).and_then(|()| exited(&vm))
+}
+
+/// Compiles a script and prints the resulting module, see [`ir`].
+pub fn emit_ir(file: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  let (src, file) = read_script(file.as_ref())?;
+  let options = ParserOptions {
+    file: file.map(Into::into),
+    no_inline: config.no_inline,
+    max_string_len: config.max_string_len,
+    list_suppressed: config.list_suppressed,
+    defines: config.defines.clone(),
+    ..Default::default()
+  };
+  let module = Module::new();
+  let errors = compile(&src, module.clone(), &options);
+  if !errors.is_empty() {
+    errors.iter().for_each(LoxError::report);
+    return Err(Failure::Compile);
+  }
+  println!("{}", ir::to_json(&module.borrow().functions));
+  Ok(())
+}
+
+/// Compiles a script and writes it to stdout as a bytecode image, which [`run_image`] runs.
+pub fn emit_image(file: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  let (src, file) = read_script(file.as_ref())?;
+  let mut vm = VM::new();
+  vm.options.file = file.map(Into::into);
+  configure(&mut vm, config);
+  match vm.compile_image(&src) {
+    Ok(bytes) => Ok(io::stdout().write_all(&bytes)?),
+    Err(messages) => {
+      messages.iter().for_each(|message| eprintln!("{message}"));
+      Err(Failure::Compile)
+    }
+  }
+}
+
+/// Runs a bytecode image written by [`emit_image`]. If `config.verify_source` names its source,
+/// an image that is no longer what compiling the source would give is recompiled from it instead.
+pub fn run_image(file: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  let file = file.as_ref();
+  let Some(image) = Image::decode(&fs::read(file)?) else {
+    eprintln!("`{}` is not a bytecode image of this version of rblox", file.display());
+    return Err(Failure::Compile);
+  };
+  let mut vm = VM::new();
+  configure(&mut vm, config);
+
+  if let Some(source) = &config.verify_source {
+    let hash = serialize::hash(&fs::read(source)?);
+    if !cache::is_fresh(&image, hash, Flags::from(&vm.options)) {
+      eprintln!("`{}` is stale; recompiling `{}`", file.display(), source.display());
+      return run_file(source, config);
+    }
+  }
+  execute_image(vm, image, file, config)
+}
+
+/// Compiles a script and every file it imports, and packs them into a bundle at `out`, which
+/// [`run_bundle`] runs without the sources.
+pub fn bundle(file: impl AsRef<Path>, out: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  Ok(fs::write(out, pack(file.as_ref(), config)?)?)
+}
+
+/// Like [`bundle`], but appends the bundle to a copy of the running executable, so that `out`
+/// runs the script wherever it is copied to.
+pub fn build(file: impl AsRef<Path>, out: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  let bundle = pack(file.as_ref(), config)?;
+  let exe = env::current_exe()?;
+  let mut bytes = fs::read(&exe)?;
+  serialize::embed(&mut bytes, &bundle);
+  fs::write(&out, bytes)?;
+  Ok(fs::set_permissions(out, fs::metadata(exe)?.permissions())?)
+}
+
+/// Compiles the script at `file` and the files it imports into an encoded bundle.
+fn pack(file: &Path, config: &Config) -> Result<Vec<u8>, Failure> {
+  let src = fs::read_to_string(file)?;
+  let mut vm = VM::new();
+  vm.options.file = Some(file.into());
+  configure(&mut vm, config);
+  let image = vm.compile_image(&src).map_err(|messages| {
+    messages.iter().for_each(|message| eprintln!("{message}"));
+    Failure::Compile
+  })?;
+
+  let dir = file.canonicalize()?.parent().map(Path::to_path_buf).unwrap_or_default();
+  let modules: Vec<_> = vm.imported_files().iter()
+    .map(|path| path.strip_prefix(&dir).unwrap_or(path).display().to_string())
+    .collect();
+  let entry = file.file_name().unwrap_or_default().to_string_lossy();
+  Ok(Bundle::encode(&entry, &modules, &image))
+}
+
+/// Runs a bundle written by [`bundle`].
+pub fn run_bundle(file: impl AsRef<Path>, config: &Config) -> Result<(), Failure> {
+  let file = file.as_ref();
+  execute_bundle(&fs::read(file)?, file, config)
+}
+
+/// The bundle that [`build`] appended to the running executable, if it is one it built.
+pub fn embedded_bundle() -> io::Result<Option<Vec<u8>>> {
+  let exe = env::current_exe()?;
+  let Some(len) = embedded_len(&exe)? else {
+    return Ok(None);
+  };
+  let mut file = fs::File::open(exe)?;
+  file.seek(SeekFrom::End(-((serialize::TRAILER_LEN as u64 + len) as i64)))?;
+  let mut bundle = vec![0; len as usize];
+  file.read_exact(&mut bundle)?;
+  Ok(Some(bundle))
+}
+
+/// Runs the bundle of an executable made by [`build`], which passes all of its arguments to the
+/// script.
+pub fn run_embedded(bundle: &[u8], args: Vec<String>) -> Result<(), Failure> {
+  let config = Config { quiet: true, args, ..Default::default() };
+  execute_bundle(bundle, &env::current_exe()?, &config)
+}
+
+/// Whether the running executable was made by [`build`].
+pub fn is_built() -> bool {
+  env::current_exe().and_then(|exe| embedded_len(&exe)).is_ok_and(|len| len.is_some())
+}
+
+/// Length of the bundle appended to the executable at `exe`, if any.
+fn embedded_len(exe: &Path) -> io::Result<Option<u64>> {
+  let mut file = fs::File::open(exe)?;
+  let mut trailer = [0; serialize::TRAILER_LEN];
+  if file.metadata()?.len() < trailer.len() as u64 {
+    return Ok(None);
+  }
+  file.seek(SeekFrom::End(-(trailer.len() as i64)))?;
+  file.read_exact(&mut trailer)?;
+  Ok(serialize::payload_len(&trailer))
+}
 
-pub fn run_file(file: impl AsRef<Path>) -> io::Result<bool> {
-  let src = &fs::read_to_string(file)?;
+/// Runs an encoded bundle read from `file`.
+fn execute_bundle(bytes: &[u8], file: &Path, config: &Config) -> Result<(), Failure> {
+  let Some(bundle) = Bundle::decode(bytes) else {
+    eprintln!("`{}` is not a bundle of this version of rblox", file.display());
+    return Err(Failure::Compile);
+  };
   let mut vm = VM::new();
-  
-  Ok(run(src, &mut vm))
+  configure(&mut vm, config);
+  execute_image(vm, bundle.image, file, config)
+}
+
+/// Runs an image read from `file`, once its bytecode passes verification.
+fn execute_image(mut vm: VM, image: Image, file: &Path, config: &Config) -> Result<(), Failure> {
+  if let Err(err) = verify::verify(&image.functions) {
+    eprintln!("Invalid bytecode in `{}`: {err}", file.display());
+    return Err(Failure::Compile);
+  }
+
+  let profile = profile_calls(&mut vm, config);
+  let res = vm.run_image(image).map_err(failure);
+  report(&vm, config, profile);
+  res.and_then(|()| exited(&vm))
+}
+
+/// Applies the settings shared by scripts and the REPL.
+fn configure(vm: &mut VM, config: &Config) {
+  if config.debug {
+    vm.enable_debug();
+  }
+  vm.trace = config.trace.clone();
+  vm.checked_arith = config.checked_arith;
+  vm.options.strict_globals = config.strict_globals;
+  vm.options.no_inline = config.no_inline;
+  vm.options.max_string_len = config.max_string_len;
+  vm.options.list_suppressed = config.list_suppressed;
+  vm.options.defines = config.defines.clone();
+  vm.options.cache_dir = cache_dir(config);
+  vm.verify = config.verify;
+  vm.quiet = config.quiet;
+  vm.args = config.args.clone();
+  if let Some(format) = config.gc_log {
+    vm.log_gc(format);
+  }
 }
 
 /// Process Lox source code
-fn run(src: &str, vm: &mut VM) -> bool {
-  match vm.run(src) {
-    Err(_) => false,
-    Ok(_) => true
+fn run(src: &str, vm: &mut VM) -> Result<(), Failure> {
+  vm.run(src).map_err(failure)
+}
+
+/// Failure for a script that stopped with an error of `kind`.
+fn failure(kind: ErrorType) -> Failure {
+  match kind {
+    ErrorType::RuntimeError => Failure::Runtime,
+    _ => Failure::Compile,
   }
 }
 
-/// REPL mode
-pub fn run_repl() {
-  println!("Entering interactive mode...");
+/// Fails with the exit code the script set, if it is not 0.
+fn exited(vm: &VM) -> Result<(), Failure> {
+  match vm.exit_code() {
+    0 => Ok(()),
+    code => Err(Failure::Exit(code)),
+  }
+}
+
+/// VM for code typed at the REPL or passed with `-e`, which prints the value of a trailing
+/// expression without a `;`.
+fn interactive_vm(config: &Config) -> VM {
   let mut vm = VM::new();
+  configure(&mut vm, config);
+  vm.options.repl_mode = true;
+  vm
+}
+
+/// Runs a snippet passed on the command line, as if it was typed at the REPL.
+pub fn run_snippet(src: &str, config: &Config) -> Result<(), Failure> {
+  let mut vm = interactive_vm(config);
+  let profile = profile_calls(&mut vm, config);
+  let res = run(src, &mut vm);
+  report(&vm, config, profile);
+  res.and_then(|()| exited(&vm))
+}
+
+/// Starts a call profile on `vm` if `--profile-calls` asked for one.
+fn profile_calls(vm: &mut VM, config: &Config) -> Option<CallProfile> {
+  config.profile_calls.as_ref()?;
+  let profile = CallProfile::new();
+  vm.hooks = Some(Box::new(profile.clone()));
+  Some(profile)
+}
+
+/// Prints the statistics asked for on the command line, and writes the call profile, once the
+/// script has ended.
+fn report(vm: &VM, config: &Config, profile: Option<CallProfile>) {
+  if config.gc_stats {
+    eprintln!("{}", vm.gc_stats());
+  }
+  if config.time {
+    eprintln!("{}", vm.timings);
+  }
+  if let (Some(profile), Some(path)) = (profile, &config.profile_calls) {
+    if let Err(err) = fs::write(path, profile.finish()) {
+      eprintln!("Could not write call profile to `{}`: {err}", path.display());
+    }
+  }
+}
+
+/// REPL mode
+/// Returns the name in a `:unset <name>` REPL command, which removes a global.
+fn unset_command(line: &str) -> Option<&str> {
+  match line.split_whitespace().collect::<Vec<_>>()[..] {
+    [":unset", name] => Some(name),
+    _ => None,
+  }
+}
+
+/// Prints how the last input changed the globals, for the `:diff` REPL command.
+fn print_diff(diff: &[String]) {
+  match diff {
+    [] => println!("No globals changed"),
+    lines => lines.iter().for_each(|line| println!("{line}")),
+  }
+}
+
+pub fn run_repl(config: &Config) {
+  if !config.quiet {
+    println!("Entering interactive mode...");
+  }
+  let mut vm = interactive_vm(config);
+  // how the last input changed the globals, for `:diff`
+  let mut diff = Vec::new();
 
   loop {
     let mut line = String::new();
@@ -35,8 +429,19 @@ pub fn run_repl() {
       .read_line(&mut line)
       .expect("Failed to read line");
 
-    if !run(&line, &mut vm) {
+    if let Some(name) = unset_command(&line) {
+      if !vm.unset_global(name) {
+        eprintln!("No global named `{name}`");
+      }
+      continue;
+    }
+    if line.trim() == ":diff" {
+      print_diff(&diff);
       continue;
-    };
+    }
+    let before = vm.globals_snapshot();
+    let _ = run(&line, &mut vm);
+    diff = vm.globals_diff(&before);
+    vm.collect_garbage();
   }
 }
@@ -1,7 +1,7 @@
 use std::{error::Error, fmt::{self, Display}};
 
 use crate::common::{
-  error::{LoxError, ErrorLevel, ErrorType},
+  error::{LoxError, ErrorLevel, ErrorType, Label},
   Span
 };
 
@@ -12,10 +12,19 @@ pub enum RuntimeError {
   UnsupportedType { message: String, span: Span, level: ErrorLevel },
 
   UndefinedVariable { name: String, span: Span },
-  // UndefinedProperty { ident: LoxIdent },
+  UndefinedProperty { name: String, span: Span },
   ZeroDivision(Span),
-  // EmptyStack(Span),
-  StackOverflow(Span) // TODO: distinguish between call stack and vm stack
+  /// Arithmetic produced `inf` or `NaN` while running with `checked_arith`.
+  NotFinite { value: f64, span: Span },
+  EmptyStack(Span),
+  /// The bytecode refers to a stack slot, upvalue, function or jump target that does not exist.
+  CorruptChunk { message: String, span: Span },
+  StackOverflow(Span), // TODO: distinguish between call stack and vm stack
+  /// The host called into the VM while it was running.
+  Reentrant(Span),
+  /// A call at `span` passed `got` arguments to a callee taking `expected`, which was declared at
+  /// `defined` if it is written in Lox.
+  Arity { expected: usize, got: usize, span: Span, defined: Option<Span> },
 }
 
 impl Display for RuntimeError {
@@ -34,24 +43,37 @@ impl Display for RuntimeError {
         )
       }
 
-      // UndefinedProperty { ident } => {
-      //   write!(
-      //     f,
-      //     "Undefined property `{}`; at position {}",
-      //     ident.name, ident.span
-      //   )
-      // }
+      UndefinedProperty { name, span } => {
+        write!(
+          f,
+          "Undefined property `{}`; at position {}",
+          name, span
+        )
+      }
 
       ZeroDivision(span) => {
         write!(f, "Division by zero; at position {}", span)
       },
 
-      // EmptyStack(span) => {
-      //   write!(f, "Cannot pop from an empty stack; at position {}", span)
-      // },
+      NotFinite { value, span } => {
+        write!(f, "Arithmetic result `{}` is not a finite number; at position {}", value, span)
+      },
+
+      EmptyStack(span) => {
+        write!(f, "Cannot pop from an empty stack; at position {}", span)
+      },
+      CorruptChunk { message, span } => {
+        write!(f, "Corrupt bytecode: {}; at position {}", message, span)
+      },
       StackOverflow(span) => {
         write!(f, "stack overflow; at position {}", span)
       }
+      Reentrant(span) => {
+        write!(f, "Cannot call into the VM while it is running; at position {}", span)
+      }
+      Arity { expected, got, span, .. } => {
+        write!(f, "Expected {} arguments, but got {}; at position {}", expected, got, span)
+      }
     }
   }
 }
@@ -63,11 +85,15 @@ impl RuntimeError {
     match self {
       UnsupportedType { span, .. } 
       | UndefinedVariable { span, ..}
+      | UndefinedProperty { span, .. }
       | ZeroDivision(span) 
-      // | EmptyStack(span)
+      | NotFinite { span, .. }
+      | EmptyStack(span)
+      | CorruptChunk { span, .. }
       | StackOverflow(span)
+      | Reentrant(span)
+      | Arity { span, .. }
       => *span,
-      // UndefinedProperty { ident }=> ident.span,
     }
   }
 }
@@ -79,10 +105,15 @@ impl LoxError for RuntimeError {
     use RuntimeError::*;
     match self {
       UnsupportedType {level, ..} => level.clone(),
-      ZeroDivision(_) => ErrorLevel::Warning,
-      // EmptyStack(_)
+      ZeroDivision(_)
+      | NotFinite { .. }
+      | EmptyStack(_)
+      | CorruptChunk { .. }
       | StackOverflow(_)
+      | Reentrant(_)
+      | Arity { .. }
       | UndefinedVariable {..}
+      | UndefinedProperty {..}
       => ErrorLevel::Error,
     }
   }
@@ -94,4 +125,12 @@ impl LoxError for RuntimeError {
   fn get_span(&self) -> Span {
     self.primary_span()
   }
+
+  fn labels(&self) -> Vec<Label> {
+    match self {
+      RuntimeError::Arity { expected, defined: Some(defined), .. } =>
+        vec![Label::new(*defined, format!("defined here with {expected} parameters"))],
+      _ => Vec::new(),
+    }
+  }
 }
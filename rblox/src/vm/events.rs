@@ -0,0 +1,97 @@
+//! Structured events about what the VM is doing, for embedders to forward to their own logging.
+//!
+//! Each event has a name, a level and key-value fields, the shape that `tracing`-style consumers
+//! expect, so a subscriber can pass them on without knowing every kind of event.
+
+use std::{cell::RefCell, fmt::Display, path::Path, rc::Rc};
+
+use crate::{common::Span, gc::log::GcEvent};
+
+/// How much an event matters, from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Trace,
+  Debug,
+  Info,
+  Warn,
+  Error,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+  /// A script is about to be compiled and run.
+  ScriptStarted { file: Option<&'a Path>, bytes: usize },
+  /// A function finished compiling.
+  FunctionCompiled { name: &'a str, arity: usize, instructions: usize },
+  /// The garbage collector ran.
+  Gc(&'a GcEvent),
+  /// The script stopped with a runtime error.
+  RuntimeError { message: &'a str, span: Span },
+}
+
+impl Event<'_> {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Event::ScriptStarted { .. } => "script_started",
+      Event::FunctionCompiled { .. } => "function_compiled",
+      Event::Gc(_) => "gc",
+      Event::RuntimeError { .. } => "runtime_error",
+    }
+  }
+
+  pub fn level(&self) -> Level {
+    match self {
+      Event::ScriptStarted { .. } => Level::Info,
+      Event::FunctionCompiled { .. } | Event::Gc(_) => Level::Debug,
+      Event::RuntimeError { .. } => Level::Error,
+    }
+  }
+
+  /// The data of the event, as key-value pairs.
+  pub fn fields(&self) -> Vec<(&'static str, String)> {
+    match self {
+      Event::ScriptStarted { file, bytes } => {
+        let file = file.map_or_else(|| "-".into(), |file| file.display().to_string());
+        vec![("file", file), ("bytes", bytes.to_string())]
+      }
+      Event::FunctionCompiled { name, arity, instructions } => vec![
+        ("name", name.to_string()),
+        ("arity", arity.to_string()),
+        ("instructions", instructions.to_string()),
+      ],
+      Event::Gc(gc) => vec![
+        ("trigger", gc.trigger.to_string()),
+        ("scanned", gc.scanned.to_string()),
+        ("freed", gc.freed.to_string()),
+        ("freed_bytes", gc.freed_bytes.to_string()),
+        ("duration_us", gc.duration.as_micros().to_string()),
+      ],
+      Event::RuntimeError { message, span } => {
+        vec![("message", message.to_string()), ("span", span.to_string())]
+      }
+    }
+  }
+}
+
+/// Formats the event as a line of `key=value` pairs, quoting values that contain spaces.
+impl Display for Event<'_> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:?} {}", self.level(), self.name())?;
+    for (key, value) in self.fields() {
+      if value.contains(char::is_whitespace) || value.contains('"') {
+        write!(f, " {key}={value:?}")?;
+      } else {
+        write!(f, " {key}={value}")?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Receives the VM's events. Install with [`VM::subscribe`](super::VM::subscribe).
+pub trait Subscriber {
+  fn on_event(&mut self, event: &Event);
+}
+
+/// A subscriber shared by the VM and its memory manager.
+pub type Shared = Rc<RefCell<dyn Subscriber>>;
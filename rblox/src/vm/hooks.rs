@@ -0,0 +1,18 @@
+//! Callbacks for tools that follow a running program, such as profilers, debuggers or teaching
+//! visualizations, without changing the dispatch loop.
+
+use crate::common::{Span, Value};
+
+/// Observes execution. Install with [`VM::hooks`](super::VM::hooks); every method does nothing
+/// unless overridden.
+pub trait Hooks {
+  /// Called when execution reaches a new source line, with the span of its first instruction.
+  /// Bytecode does not mark where statements start, so lines stand in for them.
+  fn on_stmt(&mut self, _span: Span) {}
+
+  /// Called when a function, native or not, is called with `args`.
+  fn on_call(&mut self, _name: &str, _args: &[Value]) {}
+
+  /// Called when the innermost call returns `value`. Calls that fail do not return.
+  fn on_return(&mut self, _value: &Value) {}
+}
@@ -1,23 +1,33 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{any::Any, cell::{Cell, RefCell}, collections::HashMap, io::Read, path::PathBuf, rc::Rc, time::Instant};
+
+use lox_common::timings::Timings;
 
 use crate::{
   common::{
-    data::{LoxClosure, LoxObject, LoxUpvalue, Push}, error::{ErrorLevel, ErrorType, LoxError, LoxResult}, 
-    Ins, Span, Value
+    convert::FromLox, data::{BoundMethod, LoxClass, LoxClosure, LoxFunction, LoxInstance, LoxObject, LoxUpvalue, NativeFunction, Push}, error::{ErrorLevel, ErrorType, LoxError, LoxResult, Message}, 
+    serialize::{self, Flags, Image}, verify::verify, Ins, Span, Value
   }, 
-  compiler::{compile, scope::Module, FunctionType},
-  gc::mmap::MemManager,
-  vm::error::RuntimeError
+  compiler::{compile, compile_reader, parser::{state::ParserOptions, ParserOutcome}, scope::Module, FunctionType},
+  gc::{log::{GcEvent, GcStats, LogFormat, Trigger}, mmap::MemManager, usage::MemUsage},
+  vm::{clock::{Clock, SystemClock}, error::RuntimeError, events::{Event, Shared, Subscriber}, hooks::Hooks, reload::{Reloads, Watcher}}
 };
 
 #[cfg(test)]
-use crate::common::{Chunk, data::LoxFunction};
+use crate::common::Chunk;
 
 #[cfg(test)]
 mod tests;
 
+pub use lox_common::clock;
 pub mod error;
+pub mod events;
+pub mod hooks;
 pub mod native;
+pub mod profile;
+mod reload;
+mod trace;
+
+pub use trace::Trace;
 
 struct CallFrame {
   function: Rc<RefCell<LoxClosure>>,
@@ -26,13 +36,32 @@ struct CallFrame {
   start: usize, 
 }
 
-impl Display for CallFrame {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-      let func = self.function.borrow();
-      let (_, span) = func.fun.chunk.get(self.ip - 1).unwrap();
-      write!(f, "[line {}] in {}; at position {}", span.2, func.fun.name, span)?;
+impl CallFrame {
+  /// Span of the instruction being executed, or an empty span before the first one.
+  fn span(&self) -> Span {
+    let function = self.function.borrow();
+    self.ip
+      .checked_sub(1)
+      .and_then(|ip| function.fun.chunk.get(ip))
+      .map_or_else(Span::default, |(_, span)| *span)
+  }
 
-      Ok(())
+  /// Stack trace lines of the frame, with their spans. Calls inlined into the function are listed
+  /// as if they had frames of their own, innermost first.
+  fn notes(&self) -> Vec<Message> {
+    let note = |name: &str, span: Span| Message::new(format!("[line {}] in {name}; at position {span}", span.2), span);
+    let function = self.function.borrow();
+    let chunk = &function.fun.chunk;
+    let mut span = self.span();
+    let mut notes = Vec::new();
+    if let Some(ip) = self.ip.checked_sub(1) {
+      for call in chunk.inlined.iter().filter(|call| call.code.contains(&ip)) {
+        notes.push(note(&call.name, span));
+        span = call.span;
+      }
+    }
+    notes.push(note(&function.fun.name, span));
+    notes
   }
 }
 
@@ -42,24 +71,158 @@ pub struct VM {
   globals: HashMap<String, Value>,
   objects: MemManager,
   span: Span,
-  module: Rc<RefCell<Module>>
+  module: Rc<RefCell<Module>>,
+  pub options: ParserOptions,
+  watcher: Option<Watcher>,
+  reloads: Reloads,
+  pub trace: Option<Trace>,
+  /// Treat `inf` and `NaN` results of arithmetic as runtime errors.
+  pub checked_arith: bool,
+  /// Check the stack effects and operands of freshly compiled bytecode before running it.
+  pub verify: bool,
+  /// Skip the module listing that debug builds print before running.
+  pub quiet: bool,
+  /// Number of instructions run, which `time` blocks report.
+  executed: u64,
+  /// How long each phase of the last run took. Scanning and parsing happen in the same pass as
+  /// code generation, so they are part of `compile`.
+  pub timings: Timings,
+  /// Where `clock()` and `time` blocks read the time from.
+  pub clock: Rc<dyn Clock>,
+  /// Arguments given to the script, which `process.arg` reads.
+  pub args: Vec<String>,
+  /// Code to exit with once the script ends, as set by `process.setExitCode`.
+  pub(crate) exit_code: Cell<i32>,
+  /// Lines printed by the script, when captured instead of written to stdout.
+  output: Option<Vec<String>>,
+  /// Values of the expressions echoed at the REPL, oldest first.
+  results: Vec<Value>,
+  /// Told about each line, call and return, when set.
+  pub hooks: Option<Box<dyn Hooks>>,
+  /// Receives structured events, when set.
+  subscriber: Option<Shared>,
 }
 
 impl VM {
   pub fn run(&mut self, src: &str) -> LoxResult<ErrorType> {
-    let compile_errors = compile(src, self.module.clone());
+    self.execute(src).map_err(|(kind, messages)| {
+      for message in messages {
+        eprintln!("{message}");
+      }
+      kind
+    })
+  }
+
+  /// Compiles and runs `src`, returning the error messages and their spans instead of printing
+  /// them. A runtime error is followed by its stack trace.
+  pub fn execute(&mut self, src: &str) -> Result<(), (ErrorType, Vec<Message>)> {
+    self.execute_with(src.len(), |module, options| compile(src, module, options))
+  }
+
+  /// Like [`VM::run`], but compiles the source as it is read from `reader`, so that very large
+  /// scripts need not be held in memory. `bytes` is the size of the source, for subscribers.
+  pub fn run_reader(&mut self, reader: impl Read, bytes: usize) -> LoxResult<ErrorType> {
+    self.execute_with(bytes, |module, options| compile_reader(reader, module, options))
+      .map_err(|(kind, messages)| {
+        for message in messages {
+          eprintln!("{message}");
+        }
+        kind
+      })
+  }
+
+  /// Compiles a source of `bytes` with `compile` and runs it.
+  fn execute_with(
+    &mut self,
+    bytes: usize,
+    compile: impl FnOnce(Rc<RefCell<Module>>, &ParserOptions) -> ParserOutcome
+  ) -> Result<(), (ErrorType, Vec<Message>)> {
+    self.timings = Timings::default();
+    self.emit(Event::ScriptStarted { file: self.options.file.as_deref(), bytes });
+    let compiled = self.module.borrow().functions.len();
+    let start = Instant::now();
+    let mut options = self.options.clone();
+    // inlined calls would be missed by hooks and traces, and keep running reloaded functions
+    options.no_inline |= self.hooks.is_some() || self.trace.is_some() || self.watcher.is_some();
+    // as would a function loaded once before a loop
+    options.no_hoist |= self.watcher.is_some();
+    let compile_errors = compile(self.module.clone(), &options);
+    self.timings.record("compile", start.elapsed());
+
+    if !compile_errors.is_empty() {
+      let messages = compile_errors.iter().map(Message::from).collect();
+      return Err((ErrorType::CompileError, messages))
+    }
+    self.execute_main(compiled)
+  }
+
+  /// Compiles `src` without running it, returning the error messages if it does not compile.
+  /// Warnings are printed or captured as usual.
+  pub fn check(&mut self, src: &str) -> Result<(), Vec<Message>> {
+    let errors = compile(src, self.module.clone(), &self.options);
+    match errors.is_empty() {
+      true => Ok(()),
+      false => Err(errors.iter().map(Message::from).collect()),
+    }
+  }
 
-    if compile_errors.len() > 0 {
-      // report errors and exit
-      for err in compile_errors {
-        err.report();
+  /// Compiles `src` without running it, and encodes the functions it compiled to as an image
+  /// that [`VM::run_image`] runs. Returns the error messages and their spans if it does not
+  /// compile.
+  pub fn compile_image(&mut self, src: &str) -> Result<Vec<u8>, Vec<Message>> {
+    let (compiled, sources) = {
+      let module = self.module.borrow();
+      (module.functions.len(), module.sources.len())
+    };
+    let errors = compile(src, self.module.clone(), &self.options);
+    if !errors.is_empty() {
+      return Err(errors.iter().map(Message::from).collect())
+    }
+
+    let module = self.module.borrow();
+    let deps = module.deps(sources, compiled);
+    let hash = serialize::hash(src.as_bytes());
+    Image::encode(hash, Flags::from(&self.options), &deps, &module.functions[compiled..], compiled)
+      .ok_or_else(|| vec![Message::new("Could not encode the compiled script", Span::default())])
+  }
+
+  /// Files imported by the code compiled so far, in the order they were first imported.
+  pub fn imported_files(&self) -> Vec<PathBuf> {
+    self.module.borrow().sources.iter().map(|(path, _)| path.clone()).collect()
+  }
+
+  /// Runs a compiled image, like [`VM::run`] runs source.
+  pub fn run_image(&mut self, image: Image) -> LoxResult<ErrorType> {
+    self.timings = Timings::default();
+    let compiled = self.module.borrow().functions.len();
+    image.link(&mut self.module.borrow_mut());
+    self.execute_main(compiled).map_err(|(kind, messages)| {
+      for message in messages {
+        eprintln!("{message}");
+      }
+      kind
+    })
+  }
+
+  /// Runs the script function last added to the module, after the functions from `compiled` on
+  /// were compiled.
+  fn execute_main(&mut self, compiled: usize) -> Result<(), (ErrorType, Vec<Message>)> {
+    if self.subscriber.is_some() {
+      for fun in &self.module.borrow().functions[compiled..] {
+        self.emit(Event::FunctionCompiled { name: &fun.name, arity: fun.arity, instructions: fun.chunk.len() });
       }
-      return Err(ErrorType::CompileError)
     }
 
-    if cfg!(debug_assertions) {
+    if cfg!(debug_assertions) && !self.quiet {
       println!("{}", self.module.borrow());
     }
+
+    if self.verify {
+      let start = Instant::now();
+      let verified = self.verify_module();
+      self.timings.record("verify", start.elapsed());
+      verified.map_err(|messages| (ErrorType::CompileError, messages))?;
+    }
     
     let main = self.module.clone().borrow_mut().functions.last().unwrap().clone();
 
@@ -69,42 +232,67 @@ impl VM {
       start: 0
     });
 
-    match self.interpret() {
-      Err(err) => {
-        err.report();
-        self.stack_trace();
-        Err(ErrorType::RuntimeError)
-      },
-      Ok(_) => Ok(())
-    }
+    let start = Instant::now();
+    let res = self.interpret();
+    self.timings.record("execute", start.elapsed());
+    res.map_err(|err| {
+      self.emit(Event::RuntimeError { message: &err.to_string(), span: err.get_span() });
+      let mut messages = vec![Message::from(&err)];
+      messages.extend(self.frames.iter().rev().flat_map(CallFrame::notes));
+      // unwind whatever the error interrupted, so the next run starts clean
+      self.frames.clear();
+      self.stack.truncate(1);
+      (ErrorType::RuntimeError, messages)
+    })
   }
 
   pub fn interpret(&mut self) -> LoxResult<RuntimeError> {
     use Ins::*;
     use Value as V;
 
-    loop {
-      let (mut ip, inst, span) = match self.advance() {
-        None => break,
-        Some(res) => res
+    // Instructions are borrowed from the running function rather than cloned out of the frame.
+    // The handle is refreshed whenever a call or return changes the top frame.
+    let mut function = self.frame_function()?;
+    // line of the previous instruction, for `Hooks::on_stmt`
+    let mut line = None;
+    while let Some(frame) = self.frames.last_mut() {
+      let Some((inst, &span)) = function.chunk.get(frame.ip) else {
+        break
       };
-
-      // if cfg!(features = "debug-step") {
-      // if cfg!(debug_assertions) {
-      //   display_instr(&self.stack, &inst);
-      // }
+      frame.ip += 1;
+      let mut ip = frame.ip;
+      self.span = span;
+      self.executed += 1;
+      let depth = self.frames.len();
+
+      if self.trace.is_some() {
+        if let Some(line) = self.trace_line(inst, span) {
+          eprintln!("{line}");
+        }
+      }
+      if let Some(hooks) = &mut self.hooks {
+        if line.replace(span.2) != Some(span.2) {
+          hooks.on_stmt(span);
+        }
+      }
       let mut jumped = false;
 
       match inst {
-        Constant(n) => self.push(n.clone())?,
+        Constant(idx) => match function.chunk.constants.get(*idx) {
+          Some(val) => self.push(val.clone())?,
+          None => return Err(self.corrupt(format!("constant {idx} does not exist"))),
+        },
         True => self.push(Value::Boolean(true))?,
         False => self.push(Value::Boolean(false))?,
         Nil => self.push(Value::Nil)?,
 
         Negate => {
-          let val = self.pop();
+          let val = self.pop()?;
           match val {
-            V::Number(_) => self.push(-val)?,
+            V::Number(n) => {
+              let out = self.number(-n)?;
+              self.push(out)?
+            },
             unexpected => return Err(
               RuntimeError::UnsupportedType {
                 level: ErrorLevel::Error,
@@ -118,29 +306,20 @@ impl VM {
           };
         },
         Add => {
-          let b = self.pop();
-          let a = self.pop();
+          let b = self.pop()?;
+          let a = self.pop()?;
 
           use Value::*;
-          use LoxObject as L;
           let out = match (a, b) {
-            (Number(a), Number(b)) => Number(a + b),
-            (Object(a), b) if a.is_type(L::String("".into()))
-            => {
-              match &*a {
-                L::String(a) => {
-                  let obj = self.objects.add_string(
-                    &(a.to_owned() + &b.to_string())
-                  );
-                  Object(obj)
-                },
-                _ => unreachable!()
-              }
+            (Number(a), Number(b)) => self.number(a + b)?,
+            // a string on either side joins the other operand as it would be printed
+            (a, b) if a.as_str().is_some() || b.as_str().is_some() => {
+              Object(self.objects.add_string(&format!("{a}{b}")))
             },
             (a, b) => return Err(RuntimeError::UnsupportedType {
               level: ErrorLevel::Error,
               message: format!(
-                "Binary `+` operator can only operate over two numbers or strings. \
+                "Binary `+` operator can only operate over two numbers, or a string and any value. \
                 Got types `{}` and `{}`",
                 a.type_name(),
                 b.type_name()
@@ -153,17 +332,16 @@ impl VM {
         Subtract => bin_num_op!(self, -),
         Multiply => bin_num_op!(self, *),
         Divide => {
-          let b = self.pop();
-          let a = self.pop();
+          let b = self.pop()?;
+          let a = self.pop()?;
 
           use Value::*;
           let out = match (a, b) {
             (Number(a), Number(b)) => {
               if b == 0.0 {
-                let warn = RuntimeError::ZeroDivision(self.span);
-                warn.report();
+                return Err(RuntimeError::ZeroDivision(span))
               }
-              Number(a / b)
+              self.number(a / b)?
             },
             (a, b) => return Err(RuntimeError::UnsupportedType {
               level: ErrorLevel::Error,
@@ -177,106 +355,140 @@ impl VM {
             })
           };
           self.push(out)?;          
-        }, // TODO:  Raise ZeroDivision error
+        },
 
         Equal => {
-          let a = self.pop();
-          let b = self.pop();
+          let a = self.pop()?;
+          let b = self.pop()?;
           self.push(Value::Boolean(a.equals(&b)))?;
         }
         Greater => bin_cmp_op!(self, >),
         Less => bin_cmp_op!(self, <),
 
         Not => {
-          let val = self.pop();
+          let val = self.pop()?;
           self.push(Value::Boolean(!val))?
         },
 
         Print => {
-          println!("{}", self.pop())
+          let val = self.pop()?;
+          match &mut self.output {
+            Some(lines) => lines.push(val.to_string()),
+            None => println!("{val}"),
+          }
+        }
+        Echo => {
+          let val = self.pop()?;
+          match &mut self.output {
+            Some(lines) => lines.push(val.to_string()),
+            None => println!("{val}"),
+          }
+          self.record_result(val);
+        }
+        StartTimer => {
+          self.push(Value::Number(self.clock.now()))?;
+          self.push(Value::Number(self.executed as f64))?;
+        }
+        StopTimer => {
+          let (&V::Number(start), &V::Number(count)) = (self.peek(1)?, self.peek(0)?) else {
+            return Err(self.corrupt("`time` block has no start"))
+          };
+          let elapsed = self.clock.now() - start;
+          // neither timer instruction counts as part of the block
+          let executed = self.executed as f64 - count - 1.0;
+          let line = format!("time: {:.3} ms, {executed} instructions", elapsed * 1000.0);
+          match &mut self.output {
+            Some(lines) => lines.push(line),
+            None => println!("{line}"),
+          }
         }
-        Pop => { self.pop(); },
+        Pop => { self.pop()?; },
         PopN(n) => { 
-          for _ in 0..n {
-            self.pop(); 
+          for _ in 0..*n {
+            self.pop()?; 
           }
         },
 
         DefGlobal(name) => {
-          let val = self.peek(0).unwrap().to_owned();
-          self.globals.insert(name.to_owned(), val);
-          self.pop();
+          let val = self.pop()?;
+          match self.globals.get_mut(name) {
+            Some(slot) => *slot = val,
+            None => { self.globals.insert(name.clone(), val); }
+          }
         }
         GetGlobal(name) => {
-          match self.globals.get(&name) {
+          match self.globals.get(name) {
             Some(val) => {
               self.push(val.clone())?;
             },
             None => return Err(RuntimeError::UndefinedVariable { 
-              name: name.into(),
+              name: name.clone(),
               span 
             })
           }
         }
         SetGlobal(name) => {
-          if !self.globals.contains_key(&name) {
-            return Err(RuntimeError::UndefinedVariable { 
-              name: name.into(), 
+          let val = self.peek(0)?.to_owned();
+          match self.globals.get_mut(name) {
+            Some(slot) => *slot = val,
+            None => return Err(RuntimeError::UndefinedVariable { 
+              name: name.clone(), 
               span
             })
           }
-
-          let val = self.peek(0).unwrap().to_owned();
-          self.globals.insert(name.into(), val);
         }
 
         GetLocal(slot) => {
-          let val = self.get(slot).clone();
+          let val = self.get(*slot)?.clone();
           self.push(val)?;
         },
         SetLocal(slot) => {
-          let val = self.peek(0).unwrap().clone();
-          self.set(slot, val);
+          let val = self.peek(0)?.clone();
+          self.set(*slot, val)?;
         }
 
         GetUpval(slot) => {
           use LoxUpvalue::*;
-          let val = self.get_upvalue(slot);
+          let val = self.get_upvalue(*slot)?;
           let val = match &*val.borrow() {
-            Open(pos) => self.stack.get(*pos).unwrap().clone(),
+            Open(pos) => self.stack
+              .get(*pos)
+              .ok_or_else(|| self.corrupt(format!("upvalue points past the stack at {pos}")))?
+              .clone(),
             Closed(val) => val.copy()
           };
 
           self.push(val)?;
         },
         SetUpval(slot) => {
-          let val = self.peek(0).unwrap().copy();
-          self.set_upvalue(slot, val);
+          let val = self.peek(0)?.copy();
+          self.set_upvalue(*slot, val)?;
         }
         CloseUpval => {
-          self.close_upvals(self.frames.last().unwrap().start, self.stack.len()-1);
-          self.pop();
+          let start = self.frame()?.start;
+          let last = self.stack.len().checked_sub(1).ok_or(RuntimeError::EmptyStack(span))?;
+          self.close_upvals(start, last)?;
+          self.pop()?;
         }
 
 
         Call(args) => {
-          self.call_value(args)?;
+          self.invoke(*args)?;
         },
 
         Closure(n, upvals) => {
-          let closure = LoxClosure::new(
-            self.module.borrow_mut().functions.get(n).unwrap().clone()
-          );
-          let n = self.module.borrow_mut().push(closure);
+          let fun = self.module.borrow().functions.get(*n).cloned();
+          let fun = fun.ok_or_else(|| self.corrupt(format!("function {n} does not exist")))?;
+          let n = self.module.borrow_mut().push(LoxClosure::new(fun));
 
-          let closure = self.module.borrow().closures.last().unwrap().clone();
+          let closure = self.module.borrow().closures[n].clone();
           let name = closure.borrow().fun.name.clone();
           
           for (is_local, idx) in upvals.iter() {
             let upval = if *is_local {
               self.capture_upval(*idx)?
             } else {
-              self.get_upvalue(*idx)
+              self.get_upvalue(*idx)?
             };
 
             closure.borrow_mut().upvalues.push(upval);
@@ -285,53 +497,183 @@ impl VM {
           self.push(Value::Object(Rc::new(LoxObject::Closure(name, n))))?;
         }
 
+        Class(name) => {
+          let class = LoxObject::Class(Rc::new(LoxClass::new(name)));
+          self.push(Value::Object(Rc::new(class)))?;
+        }
+        Method(name) => {
+          let closure = match self.pop()? {
+            Value::Object(obj) => match obj.as_ref() {
+              LoxObject::Closure(_, idx) => Some(*idx),
+              _ => None,
+            },
+            _ => None,
+          };
+          let closure = closure.ok_or_else(|| self.corrupt(format!("method `{name}` is not a closure")))?;
+          let class = self.class(0)?;
+          class.methods.borrow_mut().insert(name.clone(), closure);
+        }
+        Inherit => {
+          let Some(superclass) = self.peek(1)?.as_class().cloned() else {
+            return Err(RuntimeError::UnsupportedType {
+              message: format!("Superclass must be a class: got {}", self.peek(1)?),
+              span,
+              level: ErrorLevel::Error,
+            })
+          };
+          let class = self.class(0)?;
+          class.methods.borrow_mut().extend(superclass.methods.borrow().clone());
+          self.pop()?;
+        }
+        GetProperty(name) => {
+          let object = self.pop()?;
+          let instance = self.instance(&object)?;
+          let field = instance.fields.borrow().get(name).cloned();
+          let value = match field {
+            Some(value) => value,
+            None => self.bind(object, &instance.class, name)?,
+          };
+          self.push(value)?;
+        }
+        SetProperty(name) => {
+          let value = self.pop()?;
+          let object = self.pop()?;
+          let instance = self.instance(&object)?;
+          instance.fields.borrow_mut().insert(name.clone(), value.clone());
+          self.push(value)?;
+        }
+        GetSuper(name) => {
+          let superclass = self.class(0)?;
+          self.pop()?;
+          let this = self.pop()?;
+          let method = self.bind(this, &superclass, name)?;
+          self.push(method)?;
+        }
+
         Jump(offset) => {
-          ip = ((ip as isize) + offset) as usize;
+          ip = self.jump_target(ip, *offset, function.chunk.len())?;
           jumped = true;
+          if *offset < 0 && self.watcher.is_some() {
+            self.poll_reload();
+          }
         }
         JumpIfFalse(offset) => {
-          if !self.peek(0).unwrap().truth() {
-            ip = ((ip as isize) + offset) as usize;
+          if !self.peek(0)?.truth() {
+            ip = self.jump_target(ip, *offset, function.chunk.len())?;
+            jumped = true;
+          }
+        }
+        JumpIfTrue(offset) => {
+          if self.peek(0)?.truth() {
+            ip = self.jump_target(ip, *offset, function.chunk.len())?;
             jumped = true;
           }
         }
 
         Return => {
-          let result = self.pop();
-          let frame = self.frames.pop().unwrap();
-          if self.frames.len() == 0 {
+          let result = self.pop()?;
+          let Some(frame) = self.frames.pop() else {
+            return Err(self.corrupt("return outside of a call frame"))
+          };
+          // slot 0 belongs to the script, which has no caller to return to
+          if self.frames.is_empty() && frame.start == 0 {
             return Ok(())
           }
-          self.close_upvals(frame.start, frame.start);
+          if let Some(hooks) = &mut self.hooks {
+            hooks.on_return(&result);
+          }
+          self.close_upvals(frame.start, frame.start)?;
           self.pop_to(frame.start);
           self.push(result)?;
+          // back in `call_value`, which takes the result
+          if self.frames.is_empty() {
+            return Ok(())
+          }
 
         },
         // _ => {}
       }
       
       if jumped { self.update(ip); }
+      if self.frames.len() != depth {
+        function = self.frame_function()?;
+      }
     }
     Ok(())
   }
 
-  fn call_value(&mut self, args: usize) -> LoxResult<RuntimeError> {
+  /// Function run by the top frame.
+  fn frame_function(&self) -> Result<Rc<LoxFunction>, RuntimeError> {
+    Ok(self.frame()?.function.borrow().fun.clone())
+  }
+
+  /// Offset `ip` by `offset`, checking that it lands inside a chunk of `len` instructions.
+  fn jump_target(&self, ip: usize, offset: isize, len: usize) -> Result<usize, RuntimeError> {
+    ip.checked_add_signed(offset)
+      .filter(|target| *target <= len)
+      .ok_or_else(|| self.corrupt(format!("jump by {offset} leaves the chunk")))
+  }
+
+  /// The class `distance` values down the stack, which the compiler guarantees is one.
+  fn class(&self, distance: usize) -> Result<Rc<LoxClass>, RuntimeError> {
+    let value = self.peek(distance)?;
+    value.as_class().cloned().ok_or_else(|| self.corrupt(format!("expected a class, found `{value}`")))
+  }
+
+  /// The instance whose property is accessed.
+  fn instance(&self, object: &Value) -> Result<Rc<LoxInstance>, RuntimeError> {
+    object.as_instance().cloned().ok_or_else(|| RuntimeError::UnsupportedType {
+      message: "Only objects can have properties".into(),
+      span: self.span,
+      level: ErrorLevel::Error,
+    })
+  }
+
+  /// Binds the method `name` of `class` to `receiver`.
+  fn bind(&self, receiver: Value, class: &LoxClass, name: &str) -> Result<Value, RuntimeError> {
+    let Some(closure) = class.method(name) else {
+      return Err(RuntimeError::UndefinedProperty { name: name.into(), span: self.span })
+    };
+    let bound = BoundMethod { name: name.into(), receiver, closure };
+    Ok(Value::Object(Rc::new(LoxObject::BoundMethod(bound))))
+  }
+
+  /// Calls the value below the top `args` values of the stack.
+  fn invoke(&mut self, args: usize) -> LoxResult<RuntimeError> {
     use Value::Object;
     use LoxObject as L;
     use FunctionType as F;
 
-    let callee = self.peek(args).unwrap();
+    let callee = self.peek(args)?;
     let (kind, idx) = match callee {
       Object(obj) if obj.is_callable() => {
         match &**obj {
-          L::Function(_, _) => unreachable!("Functions should be wrapped as closures."),
           L::Native(_, idx) => {
             (F::Native, *idx)
           },
           L::Closure(_, idx) => {
             (F::Function, *idx)
           }
-          _ => unreachable!()
+          L::Class(class) => {
+            // the instance takes the place of the class, as `this` of the initializer
+            let class = class.clone();
+            let start = self.stack.len() - args - 1;
+            let instance = L::Instance(Rc::new(LoxInstance::new(class.clone())));
+            self.stack[start] = Value::Object(Rc::new(instance));
+            match class.method("init") {
+              Some(init) => (F::Initializer, init),
+              None if args == 0 => return Ok(()),
+              None => return Err(RuntimeError::Arity { expected: 0, got: args, span: self.span, defined: None })
+            }
+          }
+          L::BoundMethod(bound) => {
+            let (receiver, closure) = (bound.receiver.clone(), bound.closure);
+            let start = self.stack.len() - args - 1;
+            self.stack[start] = receiver;
+            (F::Method, closure)
+          }
+          // functions are always wrapped as closures by the compiler
+          _ => return Err(self.corrupt(format!("cannot call bare `{}`", obj)))
         }
       },
       unexpected => return Err(
@@ -344,18 +686,32 @@ impl VM {
     };
 
     match kind {
-      F::Function => {
-        let function = self.module.clone().borrow_mut().closures.get(idx).unwrap().clone();
+      F::Function | F::Method | F::Initializer => {
+        let function = self.module.borrow().closures.get(idx).cloned();
+        let function = function.ok_or_else(|| self.corrupt(format!("closure {idx} does not exist")))?;
 
         self.call(function, args)?;
       },
       F::Native => {
-        let native = self.module.clone().borrow_mut().natives.get(idx).unwrap().clone();
+        let native = self.module.borrow().natives.get(idx).cloned();
+        let native = native.ok_or_else(|| self.corrupt(format!("native {idx} does not exist")))?;
         
         let start = self.stack.len()-args-1;
-        let args = &self.stack[start..self.stack.len()-1];
+        if let Some(hooks) = &mut self.hooks {
+          hooks.on_call(native.name, &self.stack[start + 1..]);
+        }
+        let vm: &VM = self;
+        let args = &vm.stack[start + 1..];
         
-        let res = native.call(args, self.span)?;
+        let res = native.call(vm, args, vm.span)?;
+        if let Object(obj) = &res {
+          if let L::Foreign(_) = obj.as_ref() {
+            self.objects.add_foreign(obj);
+          }
+        }
+        if let Some(hooks) = &mut self.hooks {
+          hooks.on_return(&res);
+        }
         self.pop_to(start);
         self.push(res)?;
       }
@@ -366,16 +722,12 @@ impl VM {
   }
 
   fn call(&mut self, closure: Rc<RefCell<LoxClosure>>, args: usize) -> LoxResult<RuntimeError> {
-    if args != closure.borrow().fun.arity {
-      return Err(RuntimeError::UnsupportedType {  
-        message: format!(
-          "Expected {} arguments, but got {}",
-          closure.borrow().fun.arity,
-          args
-        ), 
-        span: self.span, 
-        level: ErrorLevel::Error
-      })
+    let (arity, defined) = {
+      let fun = &closure.borrow().fun;
+      (fun.arity, fun.span)
+    };
+    if args != arity {
+      return Err(RuntimeError::Arity { expected: arity, got: args, span: self.span, defined: Some(defined) })
     }
 
     if self.frames.len() == Self::FRAMES_MAX {
@@ -383,6 +735,9 @@ impl VM {
     }
 
     let start = self.stack.len()-args-1;
+    if let Some(hooks) = &mut self.hooks {
+      hooks.on_call(&closure.borrow().fun.name, &self.stack[start + 1..]);
+    }
     self.frames.push(CallFrame {
       function: closure.clone(),
       ip: 0,
@@ -393,10 +748,16 @@ impl VM {
 
 }
 
+impl Default for VM {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 /// Stack operations
 impl VM {
   const FRAMES_MAX: usize = 64;
-  const STACK_MAX: usize = Self::FRAMES_MAX * std::u8::MAX as usize;
+  const STACK_MAX: usize = Self::FRAMES_MAX * u8::MAX as usize;
   const STACK_MIN: usize = 64;
   pub fn new() -> Self {
     let mut vm = Self {
@@ -405,12 +766,31 @@ impl VM {
       globals: HashMap::new(),
       objects: MemManager::new(),
       span: Span::new(0, 0, 0),
-      module: Module::new()
+      module: Module::new(),
+      options: ParserOptions::default(),
+      watcher: None,
+      reloads: Reloads::default(),
+      trace: None,
+      checked_arith: false,
+      verify: false,
+      quiet: false,
+      timings: Timings::default(),
+      clock: Rc::new(SystemClock),
+      args: Vec::new(),
+      exit_code: Cell::new(0),
+      output: None,
+      results: Vec::new(),
+      executed: 0,
+      hooks: None,
+      subscriber: None,
     };
 
     vm.stack.push(Value::Object(Rc::new(LoxObject::Function("<main>".into(), 0))));
 
     attach(&mut vm);
+    if cfg!(debug_assertions) {
+      attach_debug(&mut vm);
+    }
     vm
   }
 
@@ -424,63 +804,84 @@ impl VM {
   }
 
   /// Pop value from stack.
-  fn pop(&mut self) -> Value {
-    // should not panic due to correctness of parser
-    self.stack.pop().unwrap()
+  fn pop(&mut self) -> Result<Value, RuntimeError> {
+    self.stack.pop().ok_or(RuntimeError::EmptyStack(self.span))
   }
 
   /// Pop from stack until a target size
   fn pop_to(&mut self, offset: usize) {
-    while self.stack.len() > offset {
-      self.pop();
-    }
+    self.stack.truncate(offset);
   }
 
   /// Peek at value a relative distance from the top of stack.
-  fn peek(&mut self, distance: usize) -> Option<&Value> {
-    if self.stack.len()-1 < distance {
-      None
-    } else {
-      Some(&self.stack[self.stack.len()-1-distance])
+  fn peek(&self, distance: usize) -> Result<&Value, RuntimeError> {
+    self.stack
+      .len()
+      .checked_sub(distance + 1)
+      .map(|idx| &self.stack[idx])
+      .ok_or(RuntimeError::EmptyStack(self.span))
+  }
+
+  /// Error for bytecode that does not fit the running VM state.
+  fn corrupt(&self, message: impl Into<String>) -> RuntimeError {
+    RuntimeError::CorruptChunk { message: message.into(), span: self.span }
+  }
+
+  /// The running call frame.
+  fn frame(&self) -> Result<&CallFrame, RuntimeError> {
+    self.frames.last().ok_or_else(|| self.corrupt("no active call frame"))
+  }
+
+  /// Stack index of a local slot in the top frame.
+  fn slot(&self, slot: usize) -> Result<usize, RuntimeError> {
+    let idx = self.frame()?.start + slot;
+    match idx < self.stack.len() {
+      true => Ok(idx),
+      false => Err(self.corrupt(format!("local slot {slot} is out of range"))),
     }
   }
 
   /// Get value from stack relative to start of top frame
-  fn get(&mut self, slot: usize) -> &Value {
-    let frame = self.frames.last().unwrap();
-    self.stack.get(frame.start+slot).unwrap()
+  fn get(&self, slot: usize) -> Result<&Value, RuntimeError> {
+    let idx = self.slot(slot)?;
+    Ok(&self.stack[idx])
   }
 
   /// Set value in stack relative to start of top frame
-  fn set(&mut self, slot: usize, value: Value) {
-    let frame = self.frames.last().unwrap();
-    let val = self.stack.get_mut(frame.start+slot).unwrap();
-    *val = value;
+  fn set(&mut self, slot: usize, value: Value) -> LoxResult<RuntimeError> {
+    let idx = self.slot(slot)?;
+    self.stack[idx] = value;
+    Ok(())
   }
 
   /// Get upvalue in top frame
-  fn get_upvalue(&mut self, slot: usize) -> Rc<RefCell<LoxUpvalue>> {
-    let frame = self.frames.last().unwrap();
-    frame.function.borrow().upvalues.get(slot).unwrap().clone()
+  fn get_upvalue(&self, slot: usize) -> Result<Rc<RefCell<LoxUpvalue>>, RuntimeError> {
+    self.frame()?
+      .function
+      .borrow()
+      .upvalues
+      .get(slot)
+      .cloned()
+      .ok_or_else(|| self.corrupt(format!("upvalue {slot} is out of range")))
   }
 
   /// Set indexed upvalue to a value
-  fn set_upvalue(&mut self, slot: usize, value: Value) {
-    let fun = self.frames.last_mut().unwrap().function.borrow_mut();
-    
+  fn set_upvalue(&mut self, slot: usize, value: Value) -> LoxResult<RuntimeError> {
+    let upval = self.get_upvalue(slot)?;
+    let mut upval = upval.borrow_mut();
+
     // if Open, update stack, else, update upval.
-    let mut upval = fun.upvalues.get(slot).unwrap().borrow_mut();
-    let updated = match &*upval {
+    match &*upval {
       LoxUpvalue::Open(pos) => {
-        let val = self.stack.get_mut(*pos).unwrap();
-        *val = value;
-        return;
+        let pos = *pos;
+        match self.stack.get_mut(pos) {
+          Some(val) => *val = value,
+          None => return Err(self.corrupt(format!("upvalue points past the stack at {pos}"))),
+        }
       },
-      LoxUpvalue::Closed(_) => LoxUpvalue::from(value)
-    };
-
-    *upval = updated
-
+      LoxUpvalue::Closed(_) => *upval = LoxUpvalue::from(value),
+    }
+    Ok(())
   }
 
   /// Capture local variable as an upvalue.
@@ -488,9 +889,7 @@ impl VM {
   /// If stack slot has already been captured as an upvalue, return the reference to that upvalue.
   /// Otherwise, create a new upvalue.
   fn capture_upval(&mut self, idx: usize) -> Result<Rc<RefCell<LoxUpvalue>>, RuntimeError> {
-    // let val = self.get(idx).clone();
-    let slot = self.frames.last().unwrap().start + idx;
-    // let mut prev = None;
+    let slot = self.slot(idx)?;
 
     for upval in self.module.borrow().upvals.iter().rev() {
       let upv = upval.borrow();
@@ -498,7 +897,6 @@ impl VM {
         LoxUpvalue::Open(pos) => {
           if *pos == slot { return Ok(upval.clone())}
           if *pos < slot { break; }
-          // prev = Some(upval.clone());
         }
         LoxUpvalue::Closed(_) => continue,
       }
@@ -511,52 +909,292 @@ impl VM {
     Ok(upval)
   }
 
-  fn close_upvals(&mut self, start: usize, last: usize) {
-    assert!(last < self.stack.len());
-    assert!(start <= last);
+  fn close_upvals(&mut self, start: usize, last: usize) -> LoxResult<RuntimeError> {
+    if start > last || last >= self.stack.len() {
+      return Err(RuntimeError::EmptyStack(self.span))
+    }
     let last = last - start;
 
     for upval in self.module.borrow_mut().upvals.iter_mut().rev() {
       let closed = match &*upval.borrow() {
-        LoxUpvalue::Open(slot) if *slot >= last => {
-          let val = self.stack.get(*slot).unwrap().clone();
-          LoxUpvalue::from(val)
-        }
+        LoxUpvalue::Open(slot) if *slot >= last => match self.stack.get(*slot) {
+          Some(val) => LoxUpvalue::from(val.clone()),
+          None => continue,
+        },
         _ => continue
       };
       let mut upval = upval.borrow_mut();
       *upval = closed;
     }
+    Ok(())
+  }
 
+  /// Update ip
+  fn update(&mut self, ip: usize) {
+    if let Some(frame) = self.frames.last_mut() {
+      frame.ip = ip
+    }
   }
 
-  /// Advance ip
-  fn advance(&mut self) -> Option<(usize, Ins, Span)> {
-    let frame = self.frames.last_mut().unwrap();
-    let chunk = &frame.function.borrow().fun.chunk;
+  /// Formats the operand stack and the call frames, innermost frame first.
+  pub fn dump_stack(&self) -> String {
+    let slots: Vec<String> = self.stack.iter().map(|slot| format!("{slot:?}")).collect();
+    let mut out = format!("[ {} ]\n", slots.join(", "));
+    for note in self.frames.iter().rev().flat_map(CallFrame::notes) {
+      out += &format!("{note}\n");
+    }
+    out
+  }
 
-    match chunk.get(frame.ip) {
-      None => None,
-      Some((ins, span)) => {
-        frame.ip += 1;
-        self.span = *span;
-        Some((frame.ip, ins.clone(), *span))
-      }
+  /// Where the running function was called from, as the name of the calling function and the line
+  /// of the call, e.g. `main:3`. The top level is named `script`. `None` outside of functions.
+  pub fn caller_info(&self) -> Option<String> {
+    let [.., caller, _] = self.frames.as_slice() else {
+      return None
+    };
+    let line = caller.span().2;
+    let function = caller.function.borrow();
+    let name = match function.fun.name.as_str() {
+      "<script>" => "script",
+      name => name,
+    };
+    Some(format!("{name}:{line}"))
+  }
+
+  /// Disassembly of the chunk that calling `value` runs, which must be a function or a bound
+  /// method.
+  pub fn disassemble(&self, value: &Value) -> Result<String, RuntimeError> {
+    let module = self.module.borrow();
+    let fun = match value {
+      Value::Object(obj) => match obj.as_ref() {
+        LoxObject::Function(_, idx) => module.functions.get(*idx).cloned(),
+        LoxObject::Closure(_, idx) => module.closures.get(*idx).map(|closure| closure.borrow().fun.clone()),
+        LoxObject::BoundMethod(bound) => module.closures.get(bound.closure).map(|closure| closure.borrow().fun.clone()),
+        _ => None,
+      },
+      _ => None,
+    };
+    let fun = fun.ok_or_else(|| RuntimeError::UnsupportedType {
+      message: format!("Can only disassemble functions, but got `{}`", value.type_name()),
+      span: self.span,
+      level: ErrorLevel::Error,
+    })?;
+    Ok(fun.chunk.to_string().trim_end().into())
+  }
+
+  /// Runs the bytecode verifier over every function of the module, so that a compiler bug shows up
+  /// as an error at the offending instruction instead of corrupting the stack later on.
+  fn verify_module(&self) -> Result<(), Vec<Message>> {
+    let module = self.module.borrow();
+    verify(&module.functions).map_err(|err| {
+      let span = module.functions.get(err.index)
+        .and_then(|fun| fun.chunk.get(err.offset))
+        .map_or_else(Span::default, |(_, span)| *span);
+      let message = format!(
+        "[{:?} line {}] {:?}: Bytecode failed verification {err}",
+        ErrorLevel::Error, span.2, ErrorType::CompileError
+      );
+      vec![Message::new(message, span)]
+    })
+  }
+
+  /// Collects the output of `print` instead of writing it to stdout.
+  pub fn capture_output(&mut self) {
+    self.output.get_or_insert_with(Vec::new);
+  }
+
+  /// Writes the output of `print` to stdout again, dropping anything not yet taken.
+  pub fn print_output(&mut self) {
+    self.output = None;
+  }
+
+  /// Returns the lines printed since the last call, if output is captured.
+  pub fn take_output(&mut self) -> Vec<String> {
+    self.output.as_mut().map(std::mem::take).unwrap_or_default()
+  }
+
+  /// Collects the compiler's warnings instead of printing them.
+  pub fn capture_warnings(&mut self) {
+    self.options.warnings.get_or_insert_with(Default::default);
+  }
+
+  /// Returns the warnings raised since the last call, if warnings are captured.
+  pub fn take_warnings(&mut self) -> Vec<Message> {
+    self.options.warnings.as_ref().map(|warnings| warnings.take()).unwrap_or_default()
+  }
+
+  /// Runs a collection now, e.g. while the REPL waits for input.
+  pub fn collect_garbage(&mut self) -> GcEvent {
+    self.objects.collect(Trigger::Idle)
+  }
+
+  /// Calls a Lox function or native with `args`, e.g. a callback that a script handed to the
+  /// host, and returns its result. Natives cannot call back into the VM while it runs.
+  pub fn call_value(&mut self, callee: &Value, args: &[Value]) -> Result<Value, RuntimeError> {
+    if !self.frames.is_empty() {
+      return Err(RuntimeError::Reentrant(self.span))
     }
+
+    let base = self.stack.len();
+    let res = self.push(callee.clone())
+      .and_then(|()| args.iter().try_for_each(|arg| self.push(arg.clone())))
+      .and_then(|()| self.invoke(args.len()))
+      // a native has already returned, while a function runs until its frame returns
+      .and_then(|()| if self.frames.is_empty() { Ok(()) } else { self.interpret() })
+      .and_then(|()| self.pop());
+    self.frames.clear();
+    self.stack.truncate(base);
+    res
   }
 
-  /// Update ip
-  fn update(&mut self, ip: usize) {
-    let frame = self.frames.last_mut().unwrap();
-    frame.ip = ip
+  /// Sends structured events to `subscriber` from now on, replacing any previous one.
+  pub fn subscribe(&mut self, subscriber: impl Subscriber + 'static) {
+    let subscriber: Shared = Rc::new(RefCell::new(subscriber));
+    self.objects.subscriber = Some(subscriber.clone());
+    self.subscriber = Some(subscriber);
   }
 
-  fn stack_trace(&mut self) {
-    for frame in self.frames.iter().rev() {
-      eprintln!("{}", frame)
+  fn emit(&self, event: Event) {
+    if let Some(subscriber) = &self.subscriber {
+      subscriber.borrow_mut().on_event(&event);
     }
   }
 
+  /// Registers a native for scripts to bind with `native fun name(...);`. Unlike the built-in
+  /// natives, it is not a global until a script declares it.
+  pub fn register_native(
+    &mut self,
+    name: &'static str,
+    arity: usize,
+    fn_ptr: fn(&VM, &[Value]) -> Result<Value, RuntimeError>,
+  ) {
+    self.register(NativeFunction { name, arity, fn_ptr });
+  }
+
+  /// Registers a native made with `native_fn!`, like `register_native`.
+  pub fn register(&mut self, native: NativeFunction) {
+    self.module.borrow_mut().registered.insert(native.name.into(), Rc::new(native));
+  }
+
+  /// Converts argument `i` of a native, or returns an error for the native to return if it has the
+  /// wrong type.
+  pub fn arg<T: FromLox>(&self, args: &[Value], i: usize) -> Result<T, RuntimeError> {
+    let arg = args.get(i).unwrap_or(&Value::Nil);
+    T::from_lox(arg).ok_or_else(|| RuntimeError::UnsupportedType {
+      message: format!("Expected `{}` for argument {}, but got `{}`", T::TYPE, i + 1, arg.type_name()),
+      span: self.span,
+      level: ErrorLevel::Error,
+    })
+  }
+
+  /// The resource of a handle tagged `tag` passed to a native, or an error for the native to
+  /// return if `arg` is anything else.
+  pub fn foreign_arg<'a, T: Any>(&self, arg: &'a Value, tag: &str) -> Result<&'a T, RuntimeError> {
+    arg.as_foreign(tag).ok_or_else(|| {
+      let got = match arg {
+        Value::Object(obj) => match obj.as_ref() {
+          LoxObject::Foreign(handle) => format!("a `{}` handle", handle.tag),
+          _ => format!("`{}`", arg.type_name()),
+        },
+        _ => format!("`{}`", arg.type_name()),
+      };
+      RuntimeError::UnsupportedType {
+        message: format!("Expected a `{tag}` handle, but got {got}"),
+        span: self.span,
+        level: ErrorLevel::Error,
+      }
+    })
+  }
+
+  /// Removes a global, returning whether it was defined. Functions that use it fail with an
+  /// undefined variable error from then on, until it is defined again.
+  pub fn unset_global(&mut self, name: &str) -> bool {
+    self.module.borrow_mut().globals.remove(name);
+    self.globals.remove(name).is_some()
+  }
+
+  /// Values of the expressions echoed at the REPL so far, oldest first.
+  pub fn results(&self) -> &[Value] {
+    &self.results
+  }
+
+  /// Binds an echoed value to `_`, and to `_1`, `_2`, ... in the order the values were echoed.
+  fn record_result(&mut self, val: Value) {
+    self.results.push(val.clone());
+    let numbered = format!("_{}", self.results.len());
+    let mut module = self.module.borrow_mut();
+    module.globals.insert(numbered.clone());
+    module.globals.insert("_".into());
+    self.globals.insert(numbered, val.clone());
+    self.globals.insert("_".into(), val);
+  }
+
+  /// The value of a global, e.g. a function for the host to call with `call_value`.
+  pub fn global(&self, name: &str) -> Option<&Value> {
+    self.globals.get(name)
+  }
+
+  /// Code the process should exit with once the script ends, 0 unless the script set another.
+  pub fn exit_code(&self) -> i32 {
+    self.exit_code.get()
+  }
+
+  /// The globals and their values, for [`VM::globals_diff`] to compare with later.
+  pub fn globals_snapshot(&self) -> HashMap<String, Value> {
+    self.globals.clone()
+  }
+
+  /// How the globals changed since `before` was taken, one line per global in sorted order:
+  /// `+ name = value` for a new global, `~ name = value` for one holding another value, and
+  /// `- name` for one that was removed.
+  pub fn globals_diff(&self, before: &HashMap<String, Value>) -> Vec<String> {
+    let mut names: Vec<&String> = self.globals.keys().chain(before.keys()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names.into_iter().filter_map(|name| match (before.get(name), self.globals.get(name)) {
+      (None, Some(value)) => Some(format!("+ {name} = {value}")),
+      (Some(old), Some(value)) if !old.equals(value) => Some(format!("~ {name} = {value}")),
+      (Some(_), None) => Some(format!("- {name}")),
+      _ => None,
+    }).collect()
+  }
+
+  /// Names of the defined globals, natives included, in sorted order so that listings are stable.
+  pub fn global_names(&self) -> Vec<&str> {
+    let mut names: Vec<&str> = self.globals.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    names
+  }
+
+  pub fn gc_stats(&self) -> &GcStats {
+    &self.objects.stats
+  }
+
+  /// Approximate bytes currently held by each kind of object.
+  pub fn memory_usage(&self) -> MemUsage {
+    self.module.borrow().usage() + self.objects.usage()
+  }
+
+  /// Logs each collection to stderr in the given format.
+  pub fn log_gc(&mut self, format: LogFormat) {
+    self.objects.log = Some(format);
+  }
+
+  /// Defines the debugging natives, such as `dumpStack`. Always on in debug builds.
+  pub fn enable_debug(&mut self) {
+    if !self.globals.contains_key("dumpStack") {
+      attach_debug(self);
+    }
+  }
+
+  /// Wraps the result of an arithmetic instruction, trapping `inf` and `NaN` in checked mode.
+  fn number(&self, n: f64) -> Result<Value, RuntimeError> {
+    if self.checked_arith && !n.is_finite() {
+      return Err(RuntimeError::NotFinite { value: n, span: self.span })
+    }
+    Ok(Value::Number(n))
+  }
+
   #[cfg(test)]
   fn add_chunk(&mut self, chunk: Chunk) {
     let function = Rc::new(RefCell::new(
@@ -565,7 +1203,8 @@ impl VM {
           name: chunk.name.clone(),
           arity: 0,
           chunk,
-          upvalues: 0
+          upvalues: 0,
+          span: Span::default(),
         }
       )
     ));
@@ -579,22 +1218,13 @@ impl VM {
 
 }
 
-#[allow(dead_code)]
-fn display_instr(stack: &[Value], inst: &Ins) {
-  print!("[ ");
-  for slot in stack.iter() {
-    print!("{slot:?}, ");
-  }
-  println!("]\n{:?}", inst);
-}
-
 macro_rules! bin_num_op {
   ($self:expr, $op:tt) => {{
-    let b = $self.pop();
-    let a = $self.pop();
+    let b = $self.pop()?;
+    let a = $self.pop()?;
     use Value::*;
     let out = match (a, b) {
-      (Number(a), Number(b)) => Number(a $op b),
+      (Number(a), Number(b)) => $self.number(a $op b)?,
       (a, b) => return Err(
         RuntimeError::UnsupportedType {
           level: ErrorLevel::Error,
@@ -615,16 +1245,18 @@ use bin_num_op;
 
 macro_rules! bin_cmp_op {
   ($self:expr, $op:tt) => {{
-    let b = $self.pop();
-    let a = $self.pop();
+    let b = $self.pop()?;
+    let a = $self.pop()?;
     use Value::*;
-    let out = match (a, b) {
-      (Number(a), Number(b)) => Boolean(a $op b),
-      (a, b) => return Err(
+    let out = match (&a, &b, a.as_str(), b.as_str()) {
+      (Number(a), Number(b), ..) => Boolean(a $op b),
+      // strings compare by their bytes, as in the tree-walker
+      (.., Some(a), Some(b)) => Boolean(a $op b),
+      (a, b, ..) => return Err(
         RuntimeError::UnsupportedType {
           level: ErrorLevel::Error,
           message: format!(
-            "Binary `{}` operator can only compare two numbers. \
+            "Binary `{}` operator can only compare two numbers or two strings. \
             Got types `{}` and `{}`",
             stringify!($op),
             a.type_name(),
@@ -637,4 +1269,4 @@ macro_rules! bin_cmp_op {
   }}
 }
 use bin_cmp_op;
-use native::attach;
\ No newline at end of file
+use native::{attach, attach_debug};
\ No newline at end of file
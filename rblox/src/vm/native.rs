@@ -1,45 +1,190 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  env,
+  io::{self, BufRead, Write},
+  rc::Rc,
+};
 
 use crate::{
-  common::{data::{LoxObject, NativeFunction, Push}, Value},
+  common::{data::{LoxClass, LoxInstance, LoxObject, NativeFunction, Push}, Value},
   compiler::scope::Module,
-  vm::{error::RuntimeError, VM}
+  native_fn,
+  vm::VM
 };
 
 /// Define native functions as globals to vm
 pub fn attach(vm: &mut VM) {
   let mut module = Module::default();
 
-  def_native!(
-    vm.module.clock / 0,
-    fn clock(_: &[Value]) -> Result<Value, RuntimeError> {
-      use std::time::{SystemTime, UNIX_EPOCH};
-      let start = SystemTime::now();
-      let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
-      Ok(Value::Number(since_the_epoch))
+  define(vm, &mut module, native_fn! {
+    fn clock(vm) -> f64 {
+      vm.clock.now()
+    }
+  });
+
+  define(vm, &mut module, native_fn! {
+    #[allow(non_snake_case)]
+    fn gcStats(vm) -> String {
+      let stats = vm.gc_stats();
+      format!(
+        "collections: {}, freed: {} ({} B), heap: {} B",
+        stats.collections,
+        stats.freed,
+        stats.freed_bytes,
+        vm.memory_usage().total()
+      )
+    }
+  });
+
+  define(vm, &mut module, native_fn! {
+    fn globals(vm) -> String {
+      vm.global_names().join(", ")
+    }
+  });
+
+  define(vm, &mut module, native_fn! {
+    #[allow(non_snake_case)]
+    fn callerInfo(vm) -> Option<String> {
+      vm.caller_info()
     }
-  );
+  });
 
+  // the symbol `:name`, for names that are not identifiers or are only known at run time
+  define(vm, &mut module, native_fn! {
+    #[allow(non_snake_case)]
+    fn Symbol(vm, name: String) -> Value {
+      Value::Object(vm.module.borrow_mut().intern(&name))
+    }
+  });
+
+  attach_process(vm, &mut module);
   vm.module = Rc::new(RefCell::new(module));
 }
 
-macro_rules! def_native {
-  ($vm:ident . $module:ident . $name:ident / $arity:expr  , $fn:item) => {
-    $fn
-    let name = stringify!($name);
-    let n = $module.push(NativeFunction {
-      name,
-      fn_ptr: $name,
-      arity: $arity
-    });
-
-    $vm.globals.insert(
-      name.into(),
-      Value::Object(Rc::new(
-        LoxObject::Native(name.into(), n)
-      ))
-    );
-  };
+/// Binds `process`, whose members let a script reach the host: its arguments, the environment,
+/// standard input and output, and the code the process exits with. It is an instance of a class
+/// without methods, with the natives in its fields.
+fn attach_process(vm: &mut VM, module: &mut Module) {
+  let members = [
+    native_fn! {
+      fn argc(vm) -> usize {
+        vm.args.len()
+      }
+    },
+    native_fn! {
+      fn arg(vm, n: usize) -> Option<String> {
+        vm.args.get(n).cloned()
+      }
+    },
+    native_fn! {
+      fn getenv(_, name: String) -> Option<String> {
+        env::var(name).ok()
+      }
+    },
+    // `nil` at the end of the input
+    native_fn! {
+      #[allow(non_snake_case)]
+      fn readLine(_) -> Option<String> {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+          Ok(0) | Err(_) => None,
+          Ok(_) => Some(line.trim_end_matches(['\r', '\n']).into()),
+        }
+      }
+    },
+    // goes to stdout even when the output of `print` is captured
+    native_fn! {
+      fn write(_, text: String) -> () {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush());
+      }
+    },
+    native_fn! {
+      #[allow(non_snake_case)]
+      fn setExitCode(vm, code: i32) -> () {
+        vm.exit_code.set(code);
+      }
+    },
+  ];
+
+  let process = LoxInstance::new(Rc::new(LoxClass::new("process")));
+  for native in members {
+    let name = native.name;
+    let n = module.push(native);
+    process.fields.borrow_mut().insert(name.into(), Value::Object(Rc::new(LoxObject::Native(name.into(), n))));
+  }
+  vm.globals.insert("process".into(), Value::Object(Rc::new(LoxObject::Instance(Rc::new(process)))));
+}
+
+/// Define natives for inspecting the VM from a running script
+pub fn attach_debug(vm: &mut VM) {
+  let module = vm.module.clone();
+  let mut module = module.borrow_mut();
+
+  define(vm, &mut module, native_fn! {
+    #[allow(non_snake_case)]
+    fn dumpStack(vm) -> () {
+      print!("{}", vm.dump_stack());
+    }
+  });
+
+  define(vm, &mut module, native_fn! {
+    #[allow(non_snake_case)]
+    fn dumpHeap(vm) -> () {
+      println!("{}", vm.memory_usage());
+    }
+  });
+
+  define(vm, &mut module, native_fn! {
+    fn disassemble(vm, fun: Value) -> String {
+      vm.disassemble(&fun)?
+    }
+  });
 }
 
-use def_native;
\ No newline at end of file
+/// Adds `native` to the module and binds it to a global of the same name.
+fn define(vm: &mut VM, module: &mut Module, native: NativeFunction) {
+  let name = native.name;
+  let n = module.push(native);
+  let globals: &mut HashMap<String, Value> = &mut vm.globals;
+  globals.insert(name.into(), Value::Object(Rc::new(LoxObject::Native(name.into(), n))));
+}
+
+/// Declares a native from a Rust function over typed arguments, which are converted with
+/// `FromLox` before the body runs. A mistyped argument fails the call at its span. The body
+/// evaluates to the result, which is converted with `ToLox`, and may return errors early with
+/// `?`. The first parameter names the VM, or is `_` if the body does not need it.
+///
+/// ```ignore
+/// vm.register(native_fn! {
+///   fn repeat(_, s: String, times: usize) -> String {
+///     s.repeat(times)
+///   }
+/// });
+/// ```
+#[macro_export]
+macro_rules! native_fn {
+  (
+    $(#[$attr:meta])*
+    fn $name:ident($vm:tt $(, $arg:ident : $ty:ty)* $(,)?) -> $ret:ty $body:block
+  ) => {{
+    $(#[$attr])*
+    fn $name(
+      vm: &$crate::VM,
+      _args: &[$crate::Value],
+    ) -> ::std::result::Result<$crate::Value, $crate::RuntimeError> {
+      let _next = &mut (0..);
+      $(let $arg: $ty = vm.arg(_args, _next.next().unwrap())?;)*
+      let $vm = vm;
+      let result: $ret = $body;
+      Ok($crate::ToLox::to_lox(result))
+    }
+
+    $crate::NativeFunction {
+      name: stringify!($name),
+      arity: <[&str]>::len(&[$(stringify!($arg)),*]),
+      fn_ptr: $name,
+    }
+  }};
+}
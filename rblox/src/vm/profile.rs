@@ -0,0 +1,15 @@
+//! Call profiles written by `--profile-calls`, gathered through the VM's [`Hooks`].
+
+pub use lox_common::profile::CallProfile;
+
+use crate::{common::Value, vm::hooks::Hooks};
+
+impl Hooks for CallProfile {
+  fn on_call(&mut self, name: &str, _args: &[Value]) {
+    self.enter(name);
+  }
+
+  fn on_return(&mut self, _value: &Value) {
+    self.leave();
+  }
+}
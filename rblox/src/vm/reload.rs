@@ -0,0 +1,216 @@
+//! Hot reloading of top-level functions, used by `--watch`.
+//!
+//! The watched file is polled on loop back-edges. When it changes, it is recompiled on the side and
+//! only the `fun` declarations whose code differs are swapped into the live globals; every other
+//! global keeps its current value and top-level statements are not re-run.
+//!
+//! Only the swapped functions are added to the module. Reloading one again overwrites the closure
+//! of its previous version, and its old functions are reused for later reloads once nothing runs
+//! them, so a long watch session does not grow the module with every save.
+
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  fs,
+  path::PathBuf,
+  rc::Rc,
+  time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+  common::{
+    data::{LoxClosure, LoxFunction, LoxObject, Push},
+    error::{ErrorType, LoxError},
+    serialize::relink,
+    Ins, Value,
+  },
+  compiler::{compile, scope::Module},
+};
+
+use super::VM;
+
+pub struct Watcher {
+  path: PathBuf,
+  modified: Option<SystemTime>,
+  checked: Instant,
+}
+
+impl Watcher {
+  /// Minimum time between two checks of the file.
+  const INTERVAL: Duration = Duration::from_millis(200);
+
+  pub fn new(path: PathBuf) -> Self {
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    Self { path, modified, checked: Instant::now() }
+  }
+
+  /// Returns the new source if the file was modified since the last poll.
+  fn poll(&mut self) -> Option<String> {
+    if self.checked.elapsed() < Self::INTERVAL {
+      return None;
+    }
+    self.checked = Instant::now();
+
+    let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+    if self.modified == Some(modified) {
+      return None;
+    }
+    self.modified = Some(modified);
+    fs::read_to_string(&self.path).ok()
+  }
+}
+
+/// Where the functions swapped in by reloads live in the module.
+#[derive(Default)]
+pub struct Reloads {
+  /// Closure slot and function slots of the current version of each reloaded function.
+  current: HashMap<String, (usize, Vec<usize>)>,
+  /// Function slots of replaced versions, which may still be running.
+  retired: Vec<Vec<usize>>,
+  /// Function slots of replaced versions that nothing runs anymore.
+  free: Vec<usize>,
+}
+
+impl Reloads {
+  /// Frees the retired versions that no closure holds anymore.
+  fn collect(&mut self, module: &Module) {
+    let (idle, retired) = std::mem::take(&mut self.retired)
+      .into_iter()
+      .partition(|slots: &Vec<usize>| slots.iter().all(|&n| Rc::strong_count(&module.functions[n]) == 1));
+    self.retired = retired;
+    self.free.extend(idle.into_iter().flatten());
+  }
+
+  /// A function slot to link a new function into.
+  fn slot(&mut self, module: &mut Module) -> usize {
+    self.free.pop().unwrap_or_else(|| {
+      module.functions.push(Rc::new(LoxFunction::new("")));
+      module.functions.len() - 1
+    })
+  }
+}
+
+impl VM {
+  /// Watches `path` and hot-reloads its functions while the script runs.
+  pub fn watch(&mut self, path: impl Into<PathBuf>) {
+    self.watcher = Some(Watcher::new(path.into()));
+  }
+
+  /// Called between loop iterations.
+  pub(super) fn poll_reload(&mut self) {
+    let Some(src) = self.watcher.as_mut().and_then(Watcher::poll) else {
+      return;
+    };
+    match self.reload(&src) {
+      Ok(names) if !names.is_empty() => eprintln!("[watch] reloaded {}", names.join(", ")),
+      Ok(_) => {}
+      Err(_) => eprintln!("[watch] keeping previous code"),
+    }
+  }
+
+  /// Recompiles `src` and rebinds each top-level function whose code changed.
+  /// Returns the names of the functions that were swapped in.
+  pub fn reload(&mut self, src: &str) -> Result<Vec<String>, ErrorType> {
+    let scratch = Module::new();
+    let errors = compile(src, scratch.clone(), &self.options);
+    if !errors.is_empty() {
+      for err in errors {
+        err.report();
+      }
+      return Err(ErrorType::CompileError);
+    }
+
+    let scratch = Rc::try_unwrap(scratch).map_err(|_| ErrorType::CompileError)?.into_inner();
+    let main = scratch.functions.last().unwrap();
+
+    // top-level `fun` declarations compile to a closure stored straight into a global
+    let changed: Vec<(String, usize)> = main.chunk.code
+      .windows(2)
+      .filter_map(|pair| match pair {
+        [Ins::Closure(idx, upvals), Ins::DefGlobal(name)] if upvals.is_empty() => Some((name.clone(), *idx)),
+        _ => None,
+      })
+      .filter(|(name, idx)| match self.live_function(name) {
+        Some(live) => !same_code(&scratch, &scratch.functions[*idx], &self.module.borrow(), &live),
+        None => true,
+      })
+      .collect();
+
+    if changed.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let mut functions: Vec<Option<LoxFunction>> = scratch.functions
+      .into_iter()
+      .map(|fun| Rc::try_unwrap(fun).map_err(|_| ErrorType::CompileError).map(Some))
+      .collect::<Result<_, _>>()?;
+    let mut module = self.module.borrow_mut();
+    self.reloads.collect(&module);
+
+    let mut names = Vec::with_capacity(changed.len());
+    for (name, idx) in changed {
+      // the function and the ones nested in it, which are all that it needs from the scratch module
+      let mut tree = vec![idx];
+      let mut i = 0;
+      while let Some(&n) = tree.get(i) {
+        for ins in functions[n].as_ref().map_or(&[][..], |fun| &fun.chunk.code[..]) {
+          if let Ins::Closure(idx, _) = ins {
+            if !tree.contains(idx) {
+              tree.push(*idx);
+            }
+          }
+        }
+        i += 1;
+      }
+      let slots: HashMap<usize, usize> = tree.iter().map(|&n| (n, self.reloads.slot(&mut module))).collect();
+      for (&n, &slot) in &slots {
+        let fun = functions[n].take().ok_or(ErrorType::CompileError)?;
+        module.functions[slot] = Rc::new(relink(fun, &mut module, |idx| slots[&idx]));
+      }
+
+      let closure = LoxClosure::new(module.functions[slots[&idx]].clone());
+      let n = match self.reloads.current.remove(&name) {
+        Some((n, old)) => {
+          // frames still running the old version keep their own reference to it
+          module.closures[n] = Rc::new(RefCell::new(closure));
+          self.reloads.retired.push(old);
+          n
+        }
+        None => module.push(closure),
+      };
+      self.reloads.current.insert(name.clone(), (n, tree.iter().map(|n| slots[n]).collect()));
+      module.globals.insert(name.clone());
+      self.globals.insert(name.clone(), Value::Object(Rc::new(LoxObject::Closure(name.clone(), n))));
+      names.push(name);
+    }
+    Ok(names)
+  }
+
+  /// Function currently bound to the global `name`, if it holds a closure.
+  fn live_function(&self, name: &str) -> Option<Rc<LoxFunction>> {
+    let Some(Value::Object(obj)) = self.globals.get(name) else {
+      return None;
+    };
+    let LoxObject::Closure(_, n) = obj.as_ref() else {
+      return None;
+    };
+    let closure: Rc<RefCell<LoxClosure>> = self.module.borrow().closures.get(*n)?.clone();
+    let fun = closure.borrow().fun.clone();
+    Some(fun)
+  }
+}
+
+/// Compares two functions instruction by instruction, ignoring spans and following nested closures
+/// into their own modules.
+fn same_code(a_mod: &Module, a: &LoxFunction, b_mod: &Module, b: &LoxFunction) -> bool {
+  if a.name != b.name || a.arity != b.arity || a.upvalues != b.upvalues || a.chunk.len() != b.chunk.len()
+    || a.chunk.constants != b.chunk.constants {
+    return false;
+  }
+  a.chunk.code.iter().zip(b.chunk.code.iter()).all(|pair| match pair {
+    (Ins::Closure(a_idx, a_up), Ins::Closure(b_idx, b_up)) => {
+      a_up == b_up && same_code(a_mod, &a_mod.functions[*a_idx], b_mod, &b_mod.functions[*b_idx])
+    }
+    (a, b) => a == b,
+  })
+}
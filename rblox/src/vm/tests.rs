@@ -7,15 +7,26 @@ mod challenges;
 mod variables;
 mod sequence;
 mod functions;
+mod classes;
+mod symbols;
+mod logical;
+mod imports;
+mod reload;
+mod debug;
+mod hooks;
+mod natives;
+mod embed;
+mod events;
+mod bench;
 
 #[test]
 fn correct_arith() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("test chunk");
-  chunk.write(Ins::Constant(Value::Number(1.2)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(3.4)), Span::dummy(2));
+  chunk.write_constant(Value::Number(1.2), Span::dummy(1));
+  chunk.write_constant(Value::Number(3.4), Span::dummy(2));
   chunk.write(Ins::Add, Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(5.6)), Span::dummy(2));
+  chunk.write_constant(Value::Number(5.6), Span::dummy(2));
   chunk.write(Ins::Divide, Span::dummy(3));
   chunk.write(Ins::Negate, Span::dummy(3));
   chunk.write(Ins::Return, Span::dummy(3));
@@ -24,48 +35,53 @@ fn correct_arith() {
 }
 
 #[test]
-fn process_arith() {
-  let source = "print 1+2-3*-4/(5-6);";
-  let mut vm = VM::new();
-
-  if let Err(err) = vm.run(source) {
-    eprintln!("{err:?}")
+fn malformed_chunks_are_errors() {
+  let run = |code: Vec<Ins>| {
+    let mut vm = VM::new();
+    let mut chunk = Chunk::new("bad chunk");
+    for ins in code {
+      chunk.write(ins, Span::dummy(1));
+    }
+    vm.add_chunk(chunk);
+    vm.interpret().unwrap_err()
   };
-}
 
-#[test]
-fn process_literals() {
-  let mut vm = VM::new();
-
-  if let Err(err) = vm.run("true;") {
-    eprintln!("{err:?}")
-  };
-
-  if let Err(err) = vm.run("false;") {
-    eprintln!("{err:?}")
-  };
-
-  if let Err(err) = vm.run("nil;") {
-    eprintln!("{err:?}")
-  };
+  assert!(matches!(run(vec![Ins::Pop, Ins::Pop]), RuntimeError::EmptyStack(_)));
+  assert!(matches!(run(vec![Ins::GetLocal(9)]), RuntimeError::CorruptChunk { .. }));
+  assert!(matches!(run(vec![Ins::GetUpval(0)]), RuntimeError::CorruptChunk { .. }));
+  assert!(matches!(run(vec![Ins::Jump(-5)]), RuntimeError::CorruptChunk { .. }));
+  assert!(matches!(run(vec![Ins::Closure(99, Default::default())]), RuntimeError::CorruptChunk { .. }));
 }
 
 #[test]
-fn process_types() {
-  let source = "!(5 - 4 > 3 * 2 == !nil)";
+fn verify_checks_compiled_module() {
   let mut vm = VM::new();
+  vm.verify = true;
+  assert!(vm.execute("fun f(a) { return a or 1; } print f(nil) and f(2);").is_ok());
 
-  if let Err(err) = vm.run(source) {
-    eprintln!("{err:?}")
-  };
+  let mut bad = LoxFunction::new("bad");
+  bad.chunk.write(Ins::Pop, Span::new(3, 4, 7));
+  vm.module.borrow_mut().functions.push(Rc::new(bad));
+  let (kind, messages) = vm.execute("print 1;").unwrap_err();
+  assert!(matches!(kind, ErrorType::CompileError));
+  assert_eq!(messages, vec![Message::new(
+    "[ERROR line 7] Compile Error: Bytecode failed verification in `bad` at 0000: pops 1 values, but only 0 are on the stack",
+    Span::new(3, 4, 7)
+  )]);
 }
 
 #[test]
-fn concat_strings() {
-  let source = "print \"st\" + \"ri\" + \"ng\";";
+fn times_each_phase_of_a_run() {
   let mut vm = VM::new();
+  vm.verify = true;
+  vm.execute("print 1;").unwrap();
+  let phases: Vec<_> = vm.timings.phases.iter().map(|(phase, _)| *phase).collect();
+  assert_eq!(phases, ["compile", "verify", "execute"]);
 
-  if let Err(err) = vm.run(source) {
-    eprintln!("{err:?}")
-  };
+  // a compile error stops before running, and the next run starts afresh
+  vm.verify = false;
+  assert!(vm.execute("print;").is_err());
+  let phases: Vec<_> = vm.timings.phases.iter().map(|(phase, _)| *phase).collect();
+  assert_eq!(phases, ["compile"]);
+  assert!(vm.timings.to_string().starts_with("[time] compile "));
 }
@@ -9,7 +9,5 @@ fn division_by_zero() {
   ";
 
   let mut vm = VM::new();
-  if let Err(err) = vm.run(source) {
-    eprintln!("{err:?}")
-  };
+  assert!(matches!(vm.run(source), Err(ErrorType::RuntimeError)));
 }
\ No newline at end of file
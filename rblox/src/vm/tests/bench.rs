@@ -0,0 +1,50 @@
+//! Micro-benchmarks for the dispatch loop and the compiler.
+//! Run with `cargo test --release -p rblox bench -- --ignored --nocapture`.
+
+use std::time::Instant;
+
+use crate::compiler::{compile, scope::Module};
+
+use super::*;
+
+const LOOP: &str = "var total = 0;
+var name = \"x\";
+fun add(a, b) { return a + b; }
+for (var i = 0; i < 200000; i = i + 1) {
+  total = add(total, i);
+  name = name;
+}";
+
+#[test]
+#[ignore]
+fn bench_dispatch() {
+  let start = Instant::now();
+  let mut vm = VM::new();
+  assert!(vm.run(LOOP).is_ok());
+  println!("dispatch: {:?}", start.elapsed());
+}
+
+#[test]
+#[ignore]
+fn bench_hoisting() {
+  let src = "var limit = 200000; var step = 1; var total = 0;
+for (var i = 0; i < limit; i = i + step) {
+  total = total + step;
+}";
+  for no_hoist in [true, false] {
+    let start = Instant::now();
+    let mut vm = VM::new();
+    vm.options.no_hoist = no_hoist;
+    assert!(vm.run(src).is_ok());
+    println!("hoisting {}: {:?}", if no_hoist { "off" } else { "on" }, start.elapsed());
+  }
+}
+
+#[test]
+#[ignore]
+fn bench_compile() {
+  let src = LOOP.repeat(2000);
+  let start = Instant::now();
+  assert!(compile(&src, Module::new(), &Default::default()).is_empty());
+  println!("compile: {:?}", start.elapsed());
+}
@@ -4,10 +4,10 @@ use super::*;
 fn challenge_1_1() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("challenge 1.1");
-  chunk.write(Ins::Constant(Value::Number(1.0)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(2.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(1.0), Span::dummy(1));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(2));
   chunk.write(Ins::Multiply, Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(3.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(3.0), Span::dummy(2));
   chunk.write(Ins::Add, Span::dummy(3));
   chunk.write(Ins::Return, Span::dummy(3));
   vm.add_chunk(chunk);
@@ -18,9 +18,9 @@ fn challenge_1_1() {
 fn challenge_1_2() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("challenge 1.2");
-  chunk.write(Ins::Constant(Value::Number(1.0)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(2.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(3.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(1.0), Span::dummy(1));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(2));
+  chunk.write_constant(Value::Number(3.0), Span::dummy(2));
   chunk.write(Ins::Multiply, Span::dummy(2));
   chunk.write(Ins::Add, Span::dummy(3));
   chunk.write(Ins::Return, Span::dummy(3));
@@ -32,10 +32,10 @@ fn challenge_1_2() {
 fn challenge_1_3() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("challenge 1.3");
-  chunk.write(Ins::Constant(Value::Number(3.0)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(2.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(3.0), Span::dummy(1));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(2));
   chunk.write(Ins::Subtract, Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(1.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(1.0), Span::dummy(2));
   chunk.write(Ins::Subtract, Span::dummy(3));
   chunk.write(Ins::Return, Span::dummy(3));
   vm.add_chunk(chunk);
@@ -46,13 +46,13 @@ fn challenge_1_3() {
 fn challenge_1_4() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("challenge 1.4");
-  chunk.write(Ins::Constant(Value::Number(1.0)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(2.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(3.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(1.0), Span::dummy(1));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(2));
+  chunk.write_constant(Value::Number(3.0), Span::dummy(2));
   chunk.write(Ins::Multiply, Span::dummy(2));
   chunk.write(Ins::Add, Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(4.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(5.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(4.0), Span::dummy(2));
+  chunk.write_constant(Value::Number(5.0), Span::dummy(2));
   chunk.write(Ins::Negate, Span::dummy(2));
   chunk.write(Ins::Divide, Span::dummy(2));
   chunk.write(Ins::Subtract, Span::dummy(3));
@@ -65,10 +65,10 @@ fn challenge_1_4() {
 fn challenge_2_1() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("challenge 2.1 No Negate");
-  chunk.write(Ins::Constant(Value::Number(4.0)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(3.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(0.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(2.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(4.0), Span::dummy(1));
+  chunk.write_constant(Value::Number(3.0), Span::dummy(2));
+  chunk.write_constant(Value::Number(0.0), Span::dummy(2));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(2));
   chunk.write(Ins::Subtract, Span::dummy(2));
   chunk.write(Ins::Multiply, Span::dummy(2));
   chunk.write(Ins::Subtract, Span::dummy(2));
@@ -81,9 +81,9 @@ fn challenge_2_1() {
 fn challenge_2_2() {
   let mut vm = VM::new();
   let mut chunk = Chunk::new("challenge 2.1 No Subtract");
-  chunk.write(Ins::Constant(Value::Number(4.0)), Span::dummy(1));
-  chunk.write(Ins::Constant(Value::Number(3.0)), Span::dummy(2));
-  chunk.write(Ins::Constant(Value::Number(2.0)), Span::dummy(2));
+  chunk.write_constant(Value::Number(4.0), Span::dummy(1));
+  chunk.write_constant(Value::Number(3.0), Span::dummy(2));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(2));
   chunk.write(Ins::Negate, Span::dummy(2));
   chunk.write(Ins::Multiply, Span::dummy(2));
   chunk.write(Ins::Negate, Span::dummy(2));
@@ -92,13 +92,3 @@ fn challenge_2_2() {
   vm.add_chunk(chunk);
   let _ = vm.interpret();
 }
-
-#[test]
-fn challenge_17_1() {
-  let source = "(-1+2)*3--4";
-  let mut vm = VM::new();
-
-  if let Err(err) = vm.run(source) {
-    eprintln!("{err:?}")
-  };
-}
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn instances_compare_by_identity() {
+  let source = "class A { f() {} }
+var a = A();
+var same = a == a;
+var different = a == A();
+var class_same = A == A;
+var bound = a.f == a.f;";
+  let mut vm = VM::new();
+
+  assert!(vm.run(source).is_ok());
+  assert_eq!(vm.globals.get("same"), Some(&Value::Boolean(true)));
+  assert_eq!(vm.globals.get("different"), Some(&Value::Boolean(false)));
+  assert_eq!(vm.globals.get("class_same"), Some(&Value::Boolean(true)));
+  // each read binds the method afresh, but to the same instance and closure
+  assert_eq!(vm.globals.get("bound"), Some(&Value::Boolean(true)));
+}
+
+#[test]
+fn subclasses_copy_methods_when_declared() {
+  let source = "class A { f() { return 1; } g() { return 2; } }
+class B < A { g() { return 3; } }";
+  let mut vm = VM::new();
+
+  assert!(vm.run(source).is_ok());
+  let class = |name: &str| vm.globals.get(name).and_then(Value::as_class).cloned().unwrap();
+  let (a, b) = (class("A"), class("B"));
+  assert_eq!(a.method("f"), b.method("f"));
+  assert_ne!(a.method("g"), b.method("g"));
+}
+
+#[test]
+fn verified_classes_run() {
+  let mut vm = VM::new();
+  vm.verify = true;
+  vm.capture_output();
+  let source = "class A { init(x) { this.x = x; } get() { return this.x; } }
+class B < A { get() { return super.get() * 2; } }
+print B(21).get();";
+
+  assert!(vm.execute(source).is_ok());
+  assert_eq!(vm.take_output(), ["42"]);
+}
@@ -0,0 +1,74 @@
+use super::*;
+
+#[test]
+fn dump_stack_from_script() {
+  let mut vm = VM::new();
+  vm.enable_debug();
+  assert!(vm.run("fun f(a) { var b = \"two\"; return dumpStack(); }\nvar x = f(1);").is_ok());
+  assert_eq!(vm.globals.get("x"), Some(&Value::Nil));
+}
+
+#[test]
+fn dump_stack_lists_slots_and_frames() {
+  let mut vm = VM::new();
+  let mut chunk = Chunk::new("inner");
+  chunk.write_constant(Value::Number(1.0), Span::dummy(3));
+  chunk.write_constant(Value::Number(2.0), Span::dummy(4));
+  vm.add_chunk(chunk);
+  vm.frames.last_mut().unwrap().ip = 2;
+  vm.stack.push(Value::Number(1.0));
+  vm.stack.push(Value::Boolean(true));
+
+  let dump = vm.dump_stack();
+  let mut lines = dump.lines();
+  assert_eq!(lines.next(), Some("[ <fn <main> 0>, 1, true ]"));
+  assert!(lines.next().unwrap().starts_with("[line 4] in inner"));
+  assert_eq!(lines.next(), None);
+}
+
+#[test]
+fn trace_lines() {
+  let mut vm = VM::new();
+  let mut chunk = Chunk::new("inner");
+  chunk.write(Ins::Add, Span::new(4, 5, 2));
+  vm.add_chunk(chunk);
+  vm.stack.push(Value::Number(1.0));
+  vm.stack.push(Value::Number(2.0));
+  assert_eq!(vm.trace_line(&Ins::Add, Span::new(4, 5, 2)), None);
+
+  vm.trace = Some(Trace { top: 2, function: None });
+  assert_eq!(
+    vm.trace_line(&Ins::Add, Span::new(4, 5, 2)).unwrap(),
+    "1\tinner\t2:4\tOP_ADD\t[1, 2]"
+  );
+  assert_eq!(
+    vm.trace_line(&Ins::DefGlobal("x".into()), Span::new(4, 9, 2)).unwrap(),
+    "1\tinner\t2:4..9\tOP_DEF_GLOB x\t[1, 2]"
+  );
+
+  vm.trace = Some(Trace { top: 1, function: Some("other".into()) });
+  assert_eq!(vm.trace_line(&Ins::Add, Span::new(4, 5, 2)), None);
+  vm.trace = Some(Trace { top: 1, function: Some("inner".into()) });
+  assert_eq!(vm.trace_line(&Ins::Add, Span::new(4, 5, 2)).unwrap(), "1\tinner\t2:4\tOP_ADD\t[2]");
+}
+
+#[test]
+fn memory_usage_by_kind() {
+  let mut vm = VM::new();
+  let empty = vm.memory_usage();
+  assert!(vm.run("fun make() { var x = 1; fun get() { return x; } return get; }
+var get = make();
+var a = \"a\";
+var s = a + \"b\";
+var stats = gcStats();").is_ok());
+
+  let usage = vm.memory_usage();
+  assert!(usage.functions > empty.functions);
+  assert!(usage.closures > empty.closures);
+  assert!(usage.upvalues > empty.upvalues);
+  assert!(usage.strings > empty.strings);
+  assert_eq!(usage.total(), usage.strings + usage.functions + usage.closures + usage.upvalues);
+
+  let Some(Value::Object(stats)) = vm.globals.get("stats") else { panic!() };
+  assert!(stats.to_string().starts_with("collections: 0, freed: 0 (0 B), heap: "));
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+/// A VM that has run `src`.
+fn vm_with(src: &str) -> VM {
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.execute(src).unwrap();
+  vm
+}
+
+#[test]
+fn host_calls_script_functions() {
+  let mut vm = vm_with("
+    var count = 0;
+    fun onEvent(name) {
+      count = count + 1;
+      print name;
+      return count;
+    }
+  ");
+  let handler = vm.global("onEvent").unwrap().clone();
+  let name = Value::from(LoxObject::String("click".into()));
+  assert_eq!(vm.call_value(&handler, std::slice::from_ref(&name)).unwrap(), Value::Number(1.0));
+  assert_eq!(vm.call_value(&handler, &[name]).unwrap(), Value::Number(2.0));
+  assert_eq!(vm.take_output(), ["click", "click"]);
+
+  // the script carries on with the state the calls left behind
+  vm.execute("print count;").unwrap();
+  assert_eq!(vm.take_output(), ["2"]);
+}
+
+#[test]
+fn host_calls_closures_and_natives() {
+  let mut vm = vm_with("
+    fun counter() {
+      var n = 0;
+      fun next() { n = n + 1; return n; }
+      return next;
+    }
+    var next = counter();
+  ");
+  let next = vm.global("next").unwrap().clone();
+  vm.call_value(&next, &[]).unwrap();
+  assert_eq!(vm.call_value(&next, &[]).unwrap(), Value::Number(2.0));
+
+  let clock = vm.global("clock").unwrap().clone();
+  assert!(matches!(vm.call_value(&clock, &[]), Ok(Value::Number(_))));
+}
+
+#[test]
+fn host_calls_report_errors_and_recover() {
+  let mut vm = vm_with("fun fail(x) { return x / 0; } fun id(x) { return x; }");
+  let fail = vm.global("fail").unwrap().clone();
+  let id = vm.global("id").unwrap().clone();
+
+  let err = vm.call_value(&fail, &[Value::Number(1.0)]).unwrap_err();
+  assert!(matches!(err, RuntimeError::ZeroDivision(_)), "{err}");
+  let err = vm.call_value(&id, &[]).unwrap_err();
+  assert!(err.to_string().contains("Expected 1 arguments, but got 0"), "{err}");
+  let err = vm.call_value(&Value::Number(1.0), &[]).unwrap_err();
+  assert!(err.to_string().contains("Can only call functions and classes. Got `number`"), "{err}");
+
+  assert_eq!(vm.call_value(&id, &[Value::Nil]).unwrap(), Value::Nil);
+  assert_eq!(vm.stack.len(), 1);
+}
+
+#[test]
+fn host_calls_are_not_reentrant() {
+  let mut vm = vm_with("fun id(x) { return x; }");
+  let id = vm.global("id").unwrap().clone();
+  let Value::Object(obj) = &id else { panic!("expected a closure") };
+  let LoxObject::Closure(_, n) = obj.as_ref() else { panic!("expected a closure") };
+  let closure = vm.module.borrow().closures[*n].clone();
+
+  // as if a native of a running script called back into the VM
+  vm.frames.push(CallFrame { function: closure, ip: 0, start: 1 });
+  let err = vm.call_value(&id, &[Value::Nil]).unwrap_err();
+  assert!(matches!(err, RuntimeError::Reentrant(_)), "{err}");
+}
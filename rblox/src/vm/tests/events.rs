@@ -0,0 +1,50 @@
+use super::*;
+
+use crate::vm::events::{Event, Level, Subscriber};
+
+/// Records each event as a line of text.
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl Subscriber for Recorder {
+  fn on_event(&mut self, event: &Event) {
+    self.0.borrow_mut().push(event.to_string());
+  }
+}
+
+fn recorded() -> (VM, Rc<RefCell<Vec<String>>>) {
+  let lines = Rc::new(RefCell::new(Vec::new()));
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.subscribe(Recorder(lines.clone()));
+  (vm, lines)
+}
+
+#[test]
+fn reports_scripts_and_compiled_functions() {
+  let (mut vm, lines) = recorded();
+  vm.execute("fun add(a, b) { return a + b; }").unwrap();
+  assert_eq!(*lines.borrow(), [
+    "Info script_started file=- bytes=31",
+    "Debug function_compiled name=add arity=2 instructions=6",
+    "Debug function_compiled name=<script> arity=0 instructions=4",
+  ]);
+}
+
+#[test]
+fn reports_collections_and_runtime_errors() {
+  let (mut vm, lines) = recorded();
+  assert!(vm.execute("print 1 / 0;").is_err());
+  vm.collect_garbage();
+  let lines = lines.borrow();
+  assert_eq!(lines[2], "Error runtime_error message=\"Division by zero; at position 8\" span=8");
+  assert!(lines[3].starts_with("Debug gc trigger=idle scanned=0 freed=0 freed_bytes=0 duration_us="), "{lines:?}");
+}
+
+#[test]
+fn events_have_levels_and_fields() {
+  let event = Event::FunctionCompiled { name: "f", arity: 1, instructions: 3 };
+  assert_eq!((event.name(), event.level()), ("function_compiled", Level::Debug));
+  assert_eq!(event.fields()[0], ("name", "f".to_string()));
+  assert!(Level::Error > Level::Info);
+}
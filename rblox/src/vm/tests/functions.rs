@@ -34,6 +34,60 @@ a();";
   };
 }
 
+#[test]
+fn inlined_calls_keep_their_frames() {
+  let source = "fun half(n) { return n / 2; }
+fun quarter(n) { return half(half(n)); }
+print quarter(10);
+print quarter(\"x\");";
+
+  let mut vm = VM::new();
+  vm.capture_output();
+  let (_, messages) = vm.execute(source).unwrap_err();
+  assert_eq!(vm.take_output(), ["2.5"]);
+  let frames: Vec<&str> = messages[1..].iter().map(|note| note.text.as_str()).collect();
+  assert_eq!(frames, [
+    "[line 1] in half; at position 23",
+    "[line 2] in quarter; at position 63..66",
+    "[line 4] in <script>; at position 103..108",
+  ]);
+}
+
+#[test]
+fn hoisted_loads_give_the_same_results() {
+  let source = "var n = 3;
+fun run() {
+  var k = 10;
+  var total = 0;
+  for (var i = 0; i < n; i = i + 1) {
+    var j = i * k;
+    fun get() { return j + k; }
+    var m = 0;
+    while (m < n) m = m + 1;
+    total = total + get() + m;
+  }
+  return total;
+}
+fun make(step) {
+  fun sum(n) {
+    var t = 0;
+    for (var i = 0; i < n; i = i + 1) { var x = i * step; t = t + x; }
+    return t;
+  }
+  return sum;
+}
+print run();
+print make(2)(4);";
+
+  for no_hoist in [false, true] {
+    let mut vm = VM::new();
+    vm.options.no_hoist = no_hoist;
+    vm.capture_output();
+    vm.execute(source).unwrap();
+    assert_eq!(vm.take_output(), ["69", "12"]);
+  }
+}
+
 #[ignore]
 #[test]
 fn native_clock() {
@@ -0,0 +1,70 @@
+use super::*;
+
+use crate::vm::{hooks::Hooks, profile::CallProfile};
+
+/// Records each event as a line of text.
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl Hooks for Recorder {
+  fn on_stmt(&mut self, span: Span) {
+    self.0.borrow_mut().push(format!("line {}", span.2));
+  }
+
+  fn on_call(&mut self, name: &str, args: &[Value]) {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    self.0.borrow_mut().push(format!("call {name}({})", args.join(", ")));
+  }
+
+  fn on_return(&mut self, value: &Value) {
+    self.0.borrow_mut().push(format!("return {value}"));
+  }
+}
+
+fn record(src: &str) -> Vec<String> {
+  let events = Rc::new(RefCell::new(Vec::new()));
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.hooks = Some(Box::new(Recorder(events.clone())));
+  assert!(vm.run(src).is_ok());
+  events.take()
+}
+
+#[test]
+fn hooks_see_lines_calls_and_returns() {
+  let src = "fun add(a, b) {\n  return a + b;\n}\nvar x = add(1, 2);\nprint x > clock();";
+  let events = record(src);
+  assert_eq!(
+    events[..8],
+    ["line 1", "line 4", "call add(1, 2)", "line 2", "return 3", "line 4", "line 5", "call clock()"]
+  );
+  assert!(events[8].starts_with("return "), "{events:?}");
+  assert_eq!(events.len(), 9);
+}
+
+#[test]
+fn hooks_default_to_doing_nothing() {
+  struct Nothing;
+  impl Hooks for Nothing {}
+
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.hooks = Some(Box::new(Nothing));
+  assert!(vm.run("fun f() { return 1; } print f();").is_ok());
+  assert_eq!(vm.take_output(), ["1"]);
+}
+
+#[test]
+fn call_profile_folds_stacks() {
+  let profile = CallProfile::new();
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.hooks = Some(Box::new(profile.clone()));
+  assert!(vm.run("fun g() { return 1; } fun f() { return g(); } f(); f(); g();").is_ok());
+
+  let folded = profile.finish();
+  let stacks: Vec<&str> = folded.lines().map(|line| line.rsplit_once(' ').unwrap().0).collect();
+  assert_eq!(stacks, ["<script>", "<script>;f", "<script>;f;g", "<script>;g"]);
+  assert!(folded.lines().all(|line| line.rsplit_once(' ').unwrap().1.parse::<u128>().is_ok()));
+}
@@ -0,0 +1,162 @@
+use std::{env, fs, path::{Path, PathBuf}, process};
+
+use crate::{common::serialize::{self, Bundle, Flags}, compiler::cache};
+
+use super::*;
+
+/// Creates an empty scratch directory holding the given files.
+fn scratch(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let dir = env::temp_dir().join(format!("rblox-{name}-{}", process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  for (file, src) in files {
+    let path = dir.join(file);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, src).unwrap();
+  }
+  dir
+}
+
+fn run_in(dir: &Path, src: &str) -> VM {
+  let mut vm = VM::new();
+  vm.options.file = Some(dir.join("main.lox"));
+  vm.options.cache_dir = Some(dir.join("cache"));
+  assert!(vm.run(src).is_ok());
+  vm
+}
+
+#[test]
+fn imports_run_once() {
+  let dir = scratch("imports-once", &[
+    ("lib/counter.lox", "count = count + 1;\nfun bump() { count = count + 10; }"),
+  ]);
+  let vm = run_in(&dir, "var count = 0;
+import \"lib/counter.lox\";
+import \"lib/counter.lox\";
+bump();");
+
+  assert_eq!(vm.globals.get("count"), Some(&Value::Number(11.0)));
+}
+
+#[test]
+fn imports_are_cached() {
+  let dir = scratch("imports-cached", &[
+    ("lib/a.lox", "import \"b.lox\";\nvar a = b + 1;"),
+    ("lib/b.lox", "var b = :b == :b;\nb = 1;"),
+  ]);
+  let src = "import \"lib/a.lox\";";
+  run_in(&dir, src);
+
+  let a = dir.join("lib/a.lox").canonicalize().unwrap();
+  let hash = serialize::hash(fs::read_to_string(&a).unwrap().as_bytes());
+  let cached = cache::path_for(&dir.join("cache"), &a);
+  let image = cache::load(&cached, hash, Flags::default()).expect("compiled import should be cached");
+  assert_eq!(image.deps.len(), 1);
+  // code compiled with other options is not reused
+  assert!(cache::load(&cached, hash, Flags { no_inline: true, ..Default::default() }).is_none());
+
+  let vm = run_in(&dir, src);
+  assert_eq!(vm.globals.get("a"), Some(&Value::Number(2.0)));
+}
+
+#[test]
+fn cached_imports_keep_their_dependencies_imported() {
+  let dir = scratch("imports-cached-deps", &[
+    ("a.lox", "import \"b.lox\";\nruns = runs + 10;"),
+    ("b.lox", "runs = runs + 1;"),
+  ]);
+  let src = "var runs = 0;\nimport \"a.lox\";\nimport \"b.lox\";";
+  run_in(&dir, src);
+
+  // the second run loads `a.lox` from the cache
+  let vm = run_in(&dir, src);
+  assert_eq!(vm.globals.get("runs"), Some(&Value::Number(11.0)));
+}
+
+#[test]
+fn cache_invalidated_on_change() {
+  let dir = scratch("imports-invalidate", &[
+    ("lib/a.lox", "import \"b.lox\";"),
+    ("lib/b.lox", "var b = 1;"),
+  ]);
+  let src = "import \"lib/a.lox\";";
+  run_in(&dir, src);
+
+  // only the nested import changes
+  fs::write(dir.join("lib/b.lox"), "var b = 2;").unwrap();
+  let vm = run_in(&dir, src);
+  assert_eq!(vm.globals.get("b"), Some(&Value::Number(2.0)));
+}
+
+#[test]
+fn rejects_bad_imports() {
+  let dir = scratch("imports-bad", &[
+    ("a.lox", "import \"b.lox\";"),
+    ("b.lox", "import \"a.lox\";"),
+  ]);
+  for src in ["import \"a.lox\";", "import \"missing.lox\";", "{ import \"b.lox\"; }"] {
+    let mut vm = VM::new();
+    vm.options.file = Some(dir.join("main.lox"));
+    assert!(vm.run(src).is_err(), "{src}");
+  }
+}
+
+#[test]
+fn bundles_run_without_their_sources() {
+  let dir = scratch("imports-bundle", &[
+    ("lib/a.lox", "import \"b.lox\";\nfun twice(x) { return x * b; }"),
+    ("lib/b.lox", "var b = 2;"),
+  ]);
+  let mut vm = VM::new();
+  vm.options.file = Some(dir.join("main.lox"));
+  let image = vm.compile_image("import \"lib/a.lox\";\nprint twice(21);").unwrap();
+  let modules: Vec<_> = vm.imported_files().iter()
+    .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+    .collect();
+  assert_eq!(modules, ["a.lox", "b.lox"]);
+  let bytes = Bundle::encode("main.lox", &modules, &image);
+  fs::remove_dir_all(&dir).unwrap();
+
+  let bundle = Bundle::decode(&bytes).unwrap();
+  assert_eq!((bundle.entry.as_str(), bundle.modules), ("main.lox", modules));
+  let mut vm = VM::new();
+  vm.capture_output();
+  assert!(vm.run_image(bundle.image).is_ok());
+  assert_eq!(vm.take_output(), ["42"]);
+}
+
+#[test]
+fn imports_are_compiled_with_the_defines() {
+  let dir = scratch("imports-defines", &[
+    ("lib/log.lox", "#if DEBUG\nvar level = \"debug\";\n#else\nvar level = \"quiet\";\n#end"),
+  ]);
+  let mut vm = VM::new();
+  vm.options.file = Some(dir.join("main.lox"));
+  vm.options.cache_dir = Some(dir.join("cache"));
+  vm.options.defines.insert("DEBUG".into(), "true".into());
+  assert!(vm.run("import \"lib/log.lox\";").is_ok());
+  assert_eq!(vm.globals.get("level").map(Value::to_string).as_deref(), Some("debug"));
+  // what a module compiles to depends on the defines, so it isn't cached
+  assert!(!dir.join("cache").exists());
+}
+
+#[test]
+fn module_diagnostics_point_at_the_import() {
+  let dir = scratch("imports-diagnostics", &[
+    ("lib/outer.lox", "import \"inner.lox\";\nvar = 1;"),
+    ("lib/inner.lox", "1;"),
+  ]);
+  let mut vm = VM::new();
+  vm.options.file = Some(dir.join("main.lox"));
+  vm.capture_warnings();
+  let import = Span::new(9, 32, 2);
+  let errors = vm.check("print 0;\nimport \"lib/outer.lox\";").unwrap_err();
+  assert_eq!(errors[0].span, import);
+  assert!(errors[0].text.contains("In module `lib/outer.lox`: Expected"), "{}", errors[0].text);
+  assert!(errors[0].labels.is_empty());
+
+  // warnings of nested imports point at the outermost one
+  let warnings = vm.take_warnings();
+  assert_eq!(warnings.len(), 1, "{warnings:?}");
+  assert_eq!(warnings[0].span, import);
+  assert!(warnings[0].text.contains("In module `lib/outer.lox`: In module `inner.lox`: "), "{}", warnings[0].text);
+}
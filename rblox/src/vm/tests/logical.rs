@@ -0,0 +1,97 @@
+use std::rc::Rc;
+
+use crate::{
+  common::verify::stack_depths,
+  compiler::{compile, scope::Module},
+};
+
+use super::*;
+
+const OPERANDS: [&str; 4] = ["true", "false", "nil", "1"];
+
+/// Lox truthiness of one of `OPERANDS`.
+fn truthy(operand: &str) -> bool {
+  !matches!(operand, "false" | "nil")
+}
+
+fn and<'a>(a: &'a str, b: &'a str) -> &'a str {
+  if truthy(a) { b } else { a }
+}
+
+fn or<'a>(a: &'a str, b: &'a str) -> &'a str {
+  if truthy(a) { a } else { b }
+}
+
+/// Shapes of logical expressions over `A`, `B` and `C`, with the value each should produce.
+#[allow(clippy::type_complexity)]
+const SHAPES: [(&str, for<'a> fn(&'a str, &'a str, &'a str) -> &'a str); 7] = [
+  ("A and B or C", |a, b, c| or(and(a, b), c)),
+  ("A or B and C", |a, b, c| or(a, and(b, c))),
+  ("(A or B) and C", |a, b, c| and(or(a, b), c)),
+  ("A and (B or C)", |a, b, c| and(a, or(b, c))),
+  ("A and B and C", |a, b, c| and(and(a, b), c)),
+  ("A or B or C", |a, b, c| or(or(a, b), c)),
+  ("(A, B and C) or A", |a, b, c| or(and(b, c), a)),
+];
+
+/// Symbolically executes every function compiled from `src` and asserts that each `Print` sees
+/// exactly one value above the function's locals, i.e. that no path leaves or takes extra values.
+fn assert_balanced(src: &str, locals: usize) {
+  let module = Module::new();
+  assert!(compile(src, module.clone(), &Default::default()).is_empty(), "{src}");
+  let module = Rc::try_unwrap(module).unwrap().into_inner();
+
+  for function in &module.functions {
+    let depths = stack_depths(function).unwrap_or_else(|err| panic!("{src}: {err}"));
+    let base = function.arity + 1 + if function.name == "f" { locals } else { 0 };
+    for (ins, depth) in function.chunk.code.iter().zip(depths) {
+      if *ins == Ins::Print {
+        assert_eq!(depth, Some(base + 1), "{src}:\n{}", function.chunk);
+      }
+    }
+  }
+}
+
+fn output(src: &str) -> Vec<String> {
+  let mut vm = VM::new();
+  vm.capture_output();
+  assert!(vm.run(src).is_ok(), "{src}");
+  vm.take_output()
+}
+
+#[test]
+fn logical_expressions_leave_one_value() {
+  for (shape, expected) in SHAPES {
+    for a in OPERANDS {
+      for b in OPERANDS {
+        for c in OPERANDS {
+          let expr = shape.replace('A', a).replace('B', b).replace('C', c);
+          let src = format!("print {expr};");
+          assert_balanced(&src, 0);
+          assert_eq!(output(&src), vec![expected(a, b, c).to_string()], "{src}");
+        }
+      }
+    }
+  }
+}
+
+#[test]
+fn logical_expressions_keep_locals_in_place() {
+  for (shape, expected) in SHAPES {
+    for a in OPERANDS {
+      let expr = shape.replace('A', a).replace('B', "false").replace('C', "1");
+      // `x` and `y` must stay in their slots around the jumps
+      let src = format!("fun f() {{ var x = {expr}; var y = \"end\"; print x; print y; }} f();");
+      assert_balanced(&src, 2);
+      assert_eq!(output(&src), vec![expected(a, "false", "1").to_string(), "end".into()], "{src}");
+    }
+  }
+}
+
+#[test]
+fn not_negates_truthiness() {
+  for a in OPERANDS {
+    let src = format!("print !{a};");
+    assert_eq!(output(&src), vec![(!truthy(a)).to_string()], "{src}");
+  }
+}
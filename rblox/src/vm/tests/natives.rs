@@ -0,0 +1,215 @@
+use super::*;
+
+use crate::common::convert::{FromLox, ToLox};
+
+fn add(_: &VM, args: &[Value]) -> Result<Value, RuntimeError> {
+  match args {
+    [Value::Number(a), Value::Number(b)] => Ok(Value::Number(a + b)),
+    _ => Ok(Value::Nil),
+  }
+}
+
+/// Runs `src` with `add` registered, returning what it printed or the error messages.
+fn run(src: &str) -> Result<Vec<String>, Vec<String>> {
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.register_native("add", 2, add);
+  match vm.execute(src) {
+    Ok(()) => Ok(vm.take_output()),
+    Err((_, messages)) => Err(messages.into_iter().map(|message| message.text).collect()),
+  }
+}
+
+/// The first error of `src`, which runtime errors follow with a stack trace.
+fn error_of(src: &str) -> String {
+  run(src).unwrap_err().swap_remove(0)
+}
+
+#[test]
+fn declared_native_binds_registered_function() {
+  assert_eq!(run("native fun add(a, b); print add(1, 2);").unwrap(), ["3"]);
+  // declaring it again reuses the bound native
+  assert_eq!(run("native fun add(a, b); native fun add(a, b); print add(2, 2);").unwrap(), ["4"]);
+}
+
+#[test]
+fn registered_natives_are_not_globals_until_declared() {
+  assert!(error_of("print add(1, 2);").contains("Undefined variable `add`"));
+}
+
+#[test]
+fn calls_are_checked_against_the_declaration() {
+  assert!(error_of("native fun add(a, b); print add(1);").contains("Native `add` expects 2 arguments, but got 1"));
+  // a local of the same name, or a later definition, is not the native
+  assert_eq!(run("native fun add(a, b); { fun add(a) { return a; } print add(1); }").unwrap(), ["1"]);
+  assert_eq!(run("native fun add(a, b); fun add(a) { return a; } print add(5);").unwrap(), ["5"]);
+}
+
+#[test]
+fn declarations_must_match_registrations() {
+  assert!(error_of("native fun add(a);").contains("Native `add` takes 2 arguments, but is declared with 1"));
+  let src = "native fun httpGet(url); native fun add(a, b); native fun readFile(path);";
+  assert!(error_of(src).contains("Declared natives are not registered: `httpGet`, `readFile`"));
+  let errors = run("fun f() { native fun add(a, b); }").unwrap_err();
+  assert_eq!(errors.len(), 1, "{errors:?}");
+  assert!(errors[0].contains("Natives can only be declared at the top level"));
+}
+
+thread_local! {
+  static OPEN_FILES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Stands in for a host resource, counting how many are alive.
+struct File(String);
+
+impl Drop for File {
+  fn drop(&mut self) {
+    OPEN_FILES.with(|open| open.set(open.get() - 1));
+  }
+}
+
+fn open(_: &VM, args: &[Value]) -> Result<Value, RuntimeError> {
+  OPEN_FILES.with(|open| open.set(open.get() + 1));
+  Ok(Value::foreign("File", File(args[0].to_string())))
+}
+
+fn path(vm: &VM, args: &[Value]) -> Result<Value, RuntimeError> {
+  let file: &File = vm.foreign_arg(&args[0], "File")?;
+  Ok(LoxObject::String(file.0.clone()).into())
+}
+
+/// A VM with `open` and `path` registered and declared.
+fn files() -> VM {
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.register_native("open", 1, open);
+  vm.register_native("path", 1, path);
+  vm.execute("native fun open(path); native fun path(file);").unwrap();
+  vm
+}
+
+#[test]
+fn foreign_handles_pass_through_scripts() {
+  let mut vm = files();
+  vm.execute("
+    var f = open(\"a.txt\");
+    var g = f;
+    print f;
+    print path(g);
+    print f == g;
+    print f == open(\"a.txt\");
+  ").unwrap();
+  assert_eq!(vm.take_output(), ["<foreign File>", "a.txt", "true", "false"]);
+
+  let Err((_, messages)) = vm.execute("path(1);") else { panic!("expected an error") };
+  assert!(messages[0].text.contains("Expected a `File` handle, but got `number`"), "{messages:?}");
+}
+
+#[test]
+fn collects_unreachable_foreign_handles() {
+  let mut vm = files();
+  vm.execute("var kept = open(\"kept\"); open(\"dropped\");").unwrap();
+  assert_eq!(OPEN_FILES.with(|open| open.get()), 2);
+  assert!(vm.memory_usage().foreign > 0);
+
+  let event = vm.collect_garbage();
+  assert_eq!(event.freed, 1);
+  assert_eq!(OPEN_FILES.with(|open| open.get()), 1);
+
+  vm.unset_global("kept");
+  vm.collect_garbage();
+  assert_eq!(OPEN_FILES.with(|open| open.get()), 0);
+  assert_eq!(vm.memory_usage().foreign, 0);
+}
+
+fn repeat(vm: &VM, args: &[Value]) -> Result<Value, RuntimeError> {
+  let s: String = vm.arg(args, 0)?;
+  let times: Option<usize> = vm.arg(args, 1)?;
+  Ok(s.repeat(times.unwrap_or(2)).to_lox())
+}
+
+#[test]
+fn natives_convert_arguments_and_results() {
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.register_native("repeat", 2, repeat);
+  let src = "native fun repeat(s, times); print repeat(\"ab\", 3); print repeat(\"ab\", nil); repeat(\"ab\", 1.5);";
+  let Err((_, messages)) = vm.execute(src) else { panic!("expected an error") };
+  assert_eq!(vm.take_output(), ["ababab", "abab"]);
+  assert!(messages[0].text.contains("Expected `integer` for argument 2, but got `number`"), "{messages:?}");
+}
+
+#[test]
+fn values_convert_both_ways() {
+  assert_eq!(3.5.to_lox(), Value::Number(3.5));
+  assert_eq!("s".to_lox(), Value::from(LoxObject::String("s".into())));
+  assert_eq!(None::<bool>.to_lox(), Value::Nil);
+  assert_eq!(().to_lox(), Value::Nil);
+
+  assert_eq!(i64::from_lox(&Value::Number(-4.0)), Some(-4));
+  assert_eq!(u32::from_lox(&Value::Number(-4.0)), None);
+  assert_eq!(usize::from_lox(&Value::Number(0.5)), None);
+  assert_eq!(bool::from_lox(&Value::Nil), None);
+  assert_eq!(Option::<bool>::from_lox(&Value::Nil), Some(None));
+  assert_eq!(Option::<String>::from_lox(&"s".to_lox()), Some(Some("s".into())));
+}
+
+#[test]
+fn native_fn_checks_and_converts_arguments() {
+  let pad = crate::native_fn! {
+    fn pad(_, s: String, width: usize) -> String {
+      format!("{s:>width$}")
+    }
+  };
+  assert_eq!((pad.name, pad.arity), ("pad", 2));
+
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.register(pad);
+  let Err((_, messages)) = vm.execute("native fun pad(s, width); print pad(\"ab\", 4); pad(1, 4);") else {
+    panic!("expected an error")
+  };
+  assert_eq!(vm.take_output(), ["  ab"]);
+  assert!(messages[0].text.contains("Expected `string` for argument 1, but got `number`"), "{messages:?}");
+}
+
+#[test]
+fn clock_reads_the_installed_clock() {
+  let clock = crate::MockClock::new(10.0);
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  vm.clock = Rc::new(clock.clone());
+  vm.execute("print clock();").unwrap();
+  clock.advance(0.25);
+  vm.execute("print clock();").unwrap();
+  assert_eq!(vm.take_output(), ["10", "10.25"]);
+}
+
+#[test]
+fn time_blocks_count_the_instructions_they_run() {
+  let clock = crate::MockClock::new(10.0);
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.verify = true;
+  vm.capture_output();
+  vm.clock = Rc::new(clock.clone());
+  // a constant and the pop of its local
+  vm.execute("time { var a = 1; }").unwrap();
+  assert_eq!(vm.take_output(), ["time: 0.000 ms, 2 instructions"]);
+}
+
+#[test]
+fn disassemble_lists_the_chunk_of_a_function() {
+  let src = "fun f(a) { return a + 1; } class A { m() { return this; } } print disassemble(f); print disassemble(A().m);";
+  let output = run(src).unwrap();
+  assert!(output[0].starts_with("===== f =====\n  1 | OP_GET_LOC     1\n"), "{output:?}");
+  assert!(output[0].contains("OP_ADD"), "{output:?}");
+  assert!(output[1].starts_with("===== m ====="), "{output:?}");
+
+  assert!(error_of("disassemble(clock);").contains("Can only disassemble functions, but got `<native fn>`"));
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+#[test]
+fn reload_swaps_changed_functions() {
+  let mut vm = VM::new();
+  assert!(vm.run("var count = 0;
+fun same() { return 1; }
+fun changed() { return 1; }
+fun bump() { count = count + 1; }
+bump();").is_ok());
+
+  let reloaded = vm.reload("var count = 100;
+fun same() { return 1; }
+fun changed() { return 2; }
+fun bump() { count = count + 1; }
+fun added() { return 3; }").unwrap();
+  assert_eq!(reloaded, vec!["changed", "added"]);
+
+  // globals keep their state and top-level statements are not re-run
+  assert!(vm.run("bump(); var a = changed(); var b = added(); var c = same();").is_ok());
+  assert_eq!(vm.globals.get("count"), Some(&Value::Number(2.0)));
+  assert_eq!(vm.globals.get("a"), Some(&Value::Number(2.0)));
+  assert_eq!(vm.globals.get("b"), Some(&Value::Number(3.0)));
+  assert_eq!(vm.globals.get("c"), Some(&Value::Number(1.0)));
+}
+
+#[test]
+fn reload_follows_nested_closures() {
+  let mut vm = VM::new();
+  assert!(vm.run("fun outer() { fun inner() { return 1; } return inner(); }").is_ok());
+
+  let reloaded = vm.reload("fun outer() { fun inner() { return 2; } return inner(); }").unwrap();
+  assert_eq!(reloaded, vec!["outer"]);
+  assert!(vm.run("var x = outer();").is_ok());
+  assert_eq!(vm.globals.get("x"), Some(&Value::Number(2.0)));
+}
+
+#[test]
+fn reload_keeps_code_on_errors() {
+  let mut vm = VM::new();
+  assert!(vm.run("fun f() { return 1; }").is_ok());
+
+  assert!(vm.reload("fun f() { return 2 }").is_err());
+  assert!(vm.run("var x = f();").is_ok());
+  assert_eq!(vm.globals.get("x"), Some(&Value::Number(1.0)));
+}
+
+#[test]
+fn reloads_reuse_the_slots_of_replaced_versions() {
+  let mut vm = VM::new();
+  assert!(vm.run("fun f() { fun g() { return 0; } return g(); } fun same() { return 1; }").is_ok());
+
+  let size = |vm: &VM| {
+    let module = vm.module.borrow();
+    (module.functions.len(), module.closures.len())
+  };
+  let mut added = Vec::new();
+  for n in 1..=5 {
+    let before = size(&vm);
+    let reloaded = vm.reload(&format!("fun f() {{ fun g() {{ return {n}; }} return g(); }} fun same() {{ return 1; }}")).unwrap();
+    assert_eq!(reloaded, vec!["f"]);
+    let after = size(&vm);
+    added.push((after.0 - before.0, after.1 - before.1));
+  }
+  // only `f` and `g` are added, and once a reloaded version is replaced, later ones take its place
+  assert_eq!(added, [(2, 1), (2, 0), (0, 0), (0, 0), (0, 0)]);
+  assert!(vm.run("var x = f();").is_ok());
+  assert_eq!(vm.globals.get("x"), Some(&Value::Number(5.0)));
+}
+
+#[test]
+fn reloads_keep_versions_that_still_run() {
+  let mut vm = VM::new();
+  assert!(vm.run("fun f() { return 0; }").is_ok());
+  assert!(vm.reload("fun f() { return 1; }").is_ok());
+  let Some(Value::Object(obj)) = vm.globals.get("f") else { panic!() };
+  let LoxObject::Closure(_, n) = obj.as_ref() else { panic!() };
+  let old = vm.module.borrow().closures[*n].clone();
+
+  // held here as a frame running it would, the first reloaded version keeps its slot
+  assert!(vm.reload("fun f() { return 2; }").is_ok());
+  assert!(vm.reload("fun f() { return 3; }").is_ok());
+  assert!(vm.module.borrow().functions.iter().any(|fun| Rc::ptr_eq(fun, &old.borrow().fun)));
+  assert!(vm.run("var x = f();").is_ok());
+  assert_eq!(vm.globals.get("x"), Some(&Value::Number(3.0)));
+}
@@ -0,0 +1,50 @@
+use super::*;
+
+#[test]
+fn symbols_compare_by_identity() {
+  let source = "var same = :red == :red;
+var different = :red == :blue;
+var not_string = :red == \"red\";";
+  let mut vm = VM::new();
+
+  assert!(vm.run(source).is_ok());
+  assert_eq!(vm.globals.get("same"), Some(&Value::Boolean(true)));
+  assert_eq!(vm.globals.get("different"), Some(&Value::Boolean(false)));
+  assert_eq!(vm.globals.get("not_string"), Some(&Value::Boolean(false)));
+}
+
+#[test]
+fn symbols_are_interned_across_runs() {
+  let mut vm = VM::new();
+
+  assert!(vm.run("var a = :ok;").is_ok());
+  assert!(vm.run("var b = :ok;").is_ok());
+  match (vm.globals.get("a"), vm.globals.get("b")) {
+    (Some(Value::Object(a)), Some(Value::Object(b))) => assert!(Rc::ptr_eq(a, b)),
+    other => panic!("expected two symbols, got {other:?}")
+  }
+}
+
+#[test]
+fn symbols_display_with_colon() {
+  let mut vm = VM::new();
+
+  assert!(vm.run("var s = :name;").is_ok());
+  let s = vm.globals.get("s").unwrap();
+  assert_eq!(s.to_string(), ":name");
+  assert_eq!(s.type_name(), "symbol");
+}
+
+#[test]
+fn symbol_constructor_interns_its_name() {
+  let source = "var same = Symbol(\"red\") == :red;
+var spaced = Symbol(\"two words\");
+var other = Symbol(\"red\") == Symbol(\"blue\");";
+  let mut vm = VM::new();
+
+  assert!(vm.run(source).is_ok());
+  assert_eq!(vm.globals.get("same"), Some(&Value::Boolean(true)));
+  assert_eq!(vm.globals.get("other"), Some(&Value::Boolean(false)));
+  assert_eq!(vm.globals.get("spaced").unwrap().to_string(), ":two words");
+  assert!(vm.run("Symbol(1);").is_err());
+}
@@ -93,4 +93,32 @@ fn cannot_init_local_to_self() {
   if let Err(err) = vm.run(source) {
     eprintln!("{err:?}")
   };
-}
\ No newline at end of file
+}
+#[test]
+fn globals_lists_names_in_sorted_order() {
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.capture_output();
+  assert!(vm.run("var b = 1; fun a() {} { var local = 2; } print globals();").is_ok());
+  let output = vm.take_output();
+  let names = vm.global_names();
+  assert!(names.windows(2).all(|pair| pair[0] < pair[1]), "{names:?}");
+  assert_eq!(names[..4], ["Symbol", "a", "b", "callerInfo"]);
+  assert_eq!(output, [names.join(", ")]);
+}
+
+#[test]
+fn repl_binds_echoed_values() {
+  let mut vm = VM::new();
+  vm.quiet = true;
+  vm.options.repl_mode = true;
+  vm.options.strict_globals = true;
+  vm.capture_output();
+  for line in ["1 + 2", "var x = 10;", "_ * x", "print _1 + _2;", "_1"] {
+    assert!(vm.run(line).is_ok(), "{line}");
+  }
+  assert_eq!(vm.take_output(), ["3", "30", "33", "3"]);
+  assert_eq!(vm.results(), [Value::Number(3.), Value::Number(30.), Value::Number(3.)]);
+  assert_eq!(vm.global("_"), Some(&Value::Number(3.)));
+  assert_eq!(vm.global("_3"), Some(&Value::Number(3.)));
+}
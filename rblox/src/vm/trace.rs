@@ -0,0 +1,46 @@
+//! Instruction tracing, used by `--trace-execution`.
+
+use crate::common::{Ins, Span};
+
+use super::VM;
+
+/// Settings for tracing each executed instruction to stderr.
+#[derive(Debug, Clone)]
+pub struct Trace {
+  /// Number of values to show from the top of the stack.
+  pub top: usize,
+  /// Only trace instructions executed inside functions with this name.
+  pub function: Option<String>,
+}
+
+impl Default for Trace {
+  fn default() -> Self {
+    Self { top: 3, function: None }
+  }
+}
+
+impl VM {
+  /// Formats a trace line for `ins`, which is about to run, or `None` if it is filtered out.
+  ///
+  /// Columns are tab-separated: frame depth, function name, `line:span`, the instruction, and the
+  /// topmost stack values with the top of the stack last.
+  pub(super) fn trace_line(&self, ins: &Ins, span: Span) -> Option<String> {
+    let trace = self.trace.as_ref()?;
+    let frame = self.frames.last()?;
+    let name = frame.function.borrow().fun.name.clone();
+    if trace.function.as_ref().is_some_and(|f| *f != name) {
+      return None;
+    }
+
+    let ins = frame.function.borrow().fun.chunk.describe(ins);
+    let ins = ins.split_whitespace().collect::<Vec<_>>().join(" ");
+    let top = self.stack.len().saturating_sub(trace.top);
+    let slots: Vec<String> = self.stack[top..].iter().map(|slot| format!("{slot:?}")).collect();
+    Some(format!(
+      "{}\t{name}\t{}:{span}\t{ins}\t[{}]",
+      self.frames.len(),
+      span.2,
+      slots.join(", ")
+    ))
+  }
+}
@@ -0,0 +1,346 @@
+//! Single entry point that runs Lox on either the tree-walker (`rtlox`) or the bytecode VM
+//! (`rblox`), reporting errors the same way for both.
+
+use std::{
+  collections::HashMap,
+  fs,
+  io::{self, Write},
+  path::PathBuf,
+  str::FromStr,
+};
+
+use harness::{LoxEngine, Stage, TreeWalker, VM};
+use lox_common::{render::Sink, sarif, Diagnostic};
+
+
+const USAGE: &str = "Usage: rlox [--engine=tree|vm] [--checked-arith] [--quiet] [--define NAME[=VALUE]]... [--max-errors N] [--output-format=text|sarif] [engine options] [-e <code> | script [args...] | - [args...]] | bundle|build <script> -o <file>";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Engine {
+  Tree,
+  #[default]
+  Vm,
+}
+
+impl FromStr for Engine {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "tree" => Ok(Engine::Tree),
+      "vm" => Ok(Engine::Vm),
+      _ => Err("`--engine` must be `tree` or `vm`"),
+    }
+  }
+}
+
+/// How diagnostics are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// Rendered with the source lines they point at, to stderr, as the script runs.
+  #[default]
+  Text,
+  /// As a SARIF log on stdout, after checking the script without running it.
+  Sarif,
+}
+
+impl FromStr for OutputFormat {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(OutputFormat::Text),
+      "sarif" => Ok(OutputFormat::Sarif),
+      _ => Err("`--output-format` must be `text` or `sarif`"),
+    }
+  }
+}
+
+/// Settings understood by both engines.
+#[derive(Debug, Default)]
+pub struct Config {
+  pub engine: Engine,
+  /// Raise a runtime error when arithmetic overflows to `inf` or produces `NaN`.
+  pub checked_arith: bool,
+  /// Only print the script's own output and its errors.
+  pub quiet: bool,
+  /// Names that `#if` directives in the script test, with their values.
+  pub defines: HashMap<String, String>,
+  /// Errors reported before the rest are left out of the output.
+  pub max_errors: Option<usize>,
+  pub output_format: OutputFormat,
+  pub script: Option<PathBuf>,
+  /// Arguments after the script, which it reads with `process.arg`.
+  pub args: Vec<String>,
+}
+
+/// Why the process should exit with an error, with the same exit codes as the engines' own
+/// binaries.
+#[derive(Debug)]
+pub enum ExitError {
+  /// The command line was not understood.
+  Usage(&'static str),
+  /// The script could not be read.
+  Io(io::Error),
+  /// The script was rejected before it ran; its diagnostics have already been printed.
+  Compile,
+  /// The script raised an error while running, which has already been printed.
+  Runtime,
+  /// The script ran to completion but set a nonzero exit code with `process.setExitCode`.
+  Exit(i32),
+}
+
+impl ExitError {
+  /// Exit code for the error, following the book: 64 for usage errors, 65 for compile errors,
+  /// 70 for runtime errors and 74 when the script cannot be read. A script that set its own exit
+  /// code exits with that.
+  pub fn code(&self) -> i32 {
+    match self {
+      ExitError::Usage(_) => 64,
+      ExitError::Compile => 65,
+      ExitError::Runtime => 70,
+      ExitError::Io(_) => 74,
+      ExitError::Exit(code) => *code,
+    }
+  }
+
+  /// Message to print before exiting, unless the script already reported its errors.
+  pub fn message(&self) -> Option<String> {
+    match self {
+      ExitError::Usage(message) => Some(format!("Problem parsing arguments: {message}")),
+      ExitError::Io(err) => Some(format!("Could not read script: {err}")),
+      ExitError::Compile | ExitError::Runtime | ExitError::Exit(_) => None,
+    }
+  }
+}
+
+impl From<&'static str> for ExitError {
+  fn from(message: &'static str) -> Self {
+    ExitError::Usage(message)
+  }
+}
+
+impl From<rtlox::ExitError> for ExitError {
+  fn from(err: rtlox::ExitError) -> Self {
+    use rtlox::user::Failure;
+    match err {
+      rtlox::ExitError::Usage(message) => ExitError::Usage(message),
+      rtlox::ExitError::Failed(Failure::Io(err)) => ExitError::Io(err),
+      rtlox::ExitError::Failed(Failure::Compile) => ExitError::Compile,
+      rtlox::ExitError::Failed(Failure::Runtime) => ExitError::Runtime,
+      rtlox::ExitError::Failed(Failure::Exit(code)) => ExitError::Exit(code),
+    }
+  }
+}
+
+impl From<rblox::ExitError> for ExitError {
+  fn from(err: rblox::ExitError) -> Self {
+    use rblox::Failure;
+    match err {
+      rblox::ExitError::Usage(message) => ExitError::Usage(message),
+      rblox::ExitError::Failed(Failure::Io(err)) => ExitError::Io(err),
+      rblox::ExitError::Failed(Failure::Compile) => ExitError::Compile,
+      rblox::ExitError::Failed(Failure::Runtime) => ExitError::Runtime,
+      rblox::ExitError::Failed(Failure::Exit(code)) => ExitError::Exit(code),
+    }
+  }
+}
+
+/// Options other than the ones in [`Config`] only exist on one engine, so they are handed to that
+/// engine's own command line, along with everything else but `--engine`.
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(), ExitError> {
+  if rblox::is_built() {
+    return Ok(rblox::parse_args(args)?);
+  }
+  let mut config = Config::default();
+  let mut forwarded: Vec<String> = args.next().into_iter().collect();
+  let mut engine_specific = false;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      flag if flag.starts_with("--engine=") => {
+        config.engine = flag["--engine=".len()..].parse()?;
+        continue;
+      }
+      "--define" => {
+        let define = args.next().ok_or(USAGE)?;
+        let (name, value) = define.split_once('=').unwrap_or((&define, "true"));
+        config.defines.insert(name.into(), value.into());
+        forwarded.extend([arg, define]);
+        continue;
+      }
+      flag if flag.starts_with("--output-format=") => {
+        config.output_format = flag["--output-format=".len()..].parse()?;
+        continue;
+      }
+      "--max-errors" => {
+        let max = args.next().ok_or(USAGE)?;
+        config.max_errors = Some(max.parse().map_err(|_| "`--max-errors` must be a number")?);
+        continue;
+      }
+      // packing scripts for distribution is done by the VM
+      "bundle" | "build" if forwarded.len() == 1 => {
+        forwarded.push(arg);
+        forwarded.extend(args);
+        return Ok(rblox::parse_args(forwarded.into_iter())?);
+      }
+      "--checked-arith" => config.checked_arith = true,
+      "--quiet" => config.quiet = true,
+      // `-` is standard input, not an option
+      flag if flag.starts_with('-') && flag != "-" => engine_specific = true,
+      // could be the value of an engine option
+      _ if engine_specific => {}
+      // the rest of the arguments are the script's own
+      _ => {
+        config.script = Some(arg.clone().into());
+        config.args.extend(args.by_ref());
+        forwarded.push(arg);
+        forwarded.extend(config.args.iter().cloned());
+        break;
+      }
+    }
+    forwarded.push(arg);
+  }
+
+  // bytecode images and bundles only run on the VM
+  let image = config.script.as_ref()
+    .and_then(|script| script.extension())
+    .is_some_and(|ext| ext == "loxc" || ext == "loxb");
+  if image && config.engine == Engine::Vm {
+    engine_specific = true;
+  }
+  if engine_specific && config.max_errors.is_some() {
+    return Err("`--max-errors` can't be combined with options of one engine".into());
+  }
+  if engine_specific && config.output_format != OutputFormat::Text {
+    return Err("`--output-format` can't be combined with options of one engine".into());
+  }
+  match (engine_specific, config.engine) {
+    (true, Engine::Tree) => Ok(rtlox::parse_args(forwarded.into_iter())?),
+    (true, Engine::Vm) => Ok(rblox::parse_args(forwarded.into_iter())?),
+    (false, Engine::Tree) => run::<TreeWalker>(&config),
+    (false, Engine::Vm) => run::<VM>(&config),
+  }
+}
+
+fn run<E: LoxEngine>(config: &Config) -> Result<(), ExitError> {
+  let mut engine = E::new();
+  engine.stream_output();
+  engine.set_checked_arith(config.checked_arith);
+  engine.set_quiet(config.quiet);
+  engine.set_args(&config.args);
+  engine.set_defines(&config.defines);
+
+  let Some(path) = &config.script else {
+    if config.output_format == OutputFormat::Sarif {
+      return Err("`--output-format=sarif` needs a script".into());
+    }
+    repl(&mut engine, config);
+    return Ok(());
+  };
+
+  let src = match path.as_os_str() == "-" {
+    true => io::read_to_string(io::stdin()),
+    false => fs::read_to_string(path),
+  };
+  let src = src.map_err(ExitError::Io)?;
+  let uri = sarif::uri(path);
+  if path.as_os_str() != "-" {
+    engine.set_file(path);
+  }
+  if config.output_format == OutputFormat::Sarif {
+    return check(&mut engine, &uri, &src);
+  }
+  let mut sink = Sink::new(config.max_errors);
+  let result = execute(&mut engine, &src, &mut sink, config.quiet);
+  if let Some(summary) = sink.summary().filter(|_| !config.quiet) {
+    eprintln!("{summary}");
+  }
+  result.map_err(|stage| match stage {
+    Stage::Compile => ExitError::Compile,
+    Stage::Runtime => ExitError::Runtime,
+  })?;
+  match engine.exit_code() {
+    0 => Ok(()),
+    code => Err(ExitError::Exit(code)),
+  }
+}
+
+/// Runs `src`, writing its errors, and its warnings unless `quiet`, to stderr through `sink`.
+/// Returns the stage that failed, if any.
+pub fn execute<E: LoxEngine>(engine: &mut E, src: &str, sink: &mut Sink, quiet: bool) -> Result<(), Stage> {
+  let result = engine.run(src);
+
+  let mut out = String::new();
+  let warnings = engine.take_warnings();
+  if !quiet {
+    for warning in &warnings {
+      sink.render(&mut out, src, warning);
+    }
+  }
+  if let Err(err) = &result {
+    for message in &err.messages {
+      sink.render(&mut out, src, message);
+    }
+  }
+  eprint!("{out}");
+  result.map(|_| ()).map_err(|err| err.stage)
+}
+
+/// Checks `src` without running it and prints its errors and warnings as a SARIF log on stdout.
+fn check<E: LoxEngine>(engine: &mut E, uri: &str, src: &str) -> Result<(), ExitError> {
+  let result = engine.check(src);
+  let mut diagnostics: Vec<Diagnostic> = engine.take_warnings();
+  let failed = result.is_err();
+  if let Err(err) = result {
+    diagnostics.extend(err.messages);
+  }
+  println!("{}", sarif::to_sarif(uri, src, &diagnostics));
+  match failed {
+    true => Err(ExitError::Compile),
+    false => Ok(()),
+  }
+}
+
+/// Returns the name in a `:unset <name>` REPL command, which removes a global.
+fn unset_command(line: &str) -> Option<&str> {
+  match line.split_whitespace().collect::<Vec<_>>()[..] {
+    [":unset", name] => Some(name),
+    _ => None,
+  }
+}
+
+/// REPL mode, until end of input. Each line is capped at `config.max_errors` errors of its own.
+fn repl<E: LoxEngine>(engine: &mut E, config: &Config) {
+  if !config.quiet {
+    println!("Entering interactive mode ({})...", E::NAME);
+  }
+  // how the last input changed the globals, for `:diff`
+  let mut diff = Vec::new();
+  loop {
+    let mut line = String::new();
+    print!("> ");
+    io::stdout().flush().unwrap();
+
+    match io::stdin().read_line(&mut line) {
+      Ok(0) => break,
+      Ok(_) => match unset_command(&line) {
+        Some(name) if !engine.unset_global(name) => eprintln!("No global named `{name}`"),
+        Some(_) => {}
+        None if line.trim() == ":diff" => match &diff[..] {
+          [] => println!("No globals changed"),
+          lines => lines.iter().for_each(|line| println!("{line}")),
+        },
+        None => {
+          let before = engine.globals_snapshot();
+          let _ = execute(engine, &line, &mut Sink::new(config.max_errors), config.quiet);
+          diff = engine.globals_diff(&before);
+        }
+      },
+      Err(err) => {
+        eprintln!("{err}");
+        break;
+      }
+    }
+  }
+}
@@ -0,0 +1,20 @@
+use std::env;
+use std::process;
+use std::thread;
+
+use rtlox::interpreter::STACK_SIZE;
+
+fn main() {
+  // deep Lox recursion on the tree-walker needs more stack than the main thread has
+  let run = thread::Builder::new()
+    .stack_size(STACK_SIZE)
+    .spawn(|| rlox::parse_args(env::args()))
+    .expect("Failed to start the interpreter thread");
+
+  run.join().unwrap().unwrap_or_else(|err| {
+    if let Some(message) = err.message() {
+      eprintln!("{message}");
+    }
+    process::exit(err.code());
+  });
+}
@@ -0,0 +1,340 @@
+use std::{
+  fs,
+  io::Write,
+  path::PathBuf,
+  process::{Command, Output, Stdio},
+};
+
+/// Writes `src` to a scratch file and runs the `rlox` binary on it with `args`.
+fn rlox(name: &str, src: &str, args: &[&str]) -> Output {
+  let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+  fs::write(&path, src).unwrap();
+  Command::new(env!("CARGO_BIN_EXE_rlox"))
+    .args(args)
+    .arg(&path)
+    .output()
+    .unwrap()
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+  String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect()
+}
+
+#[test]
+fn runs_on_either_engine() {
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("either.lox", "print 1 + 2;", &[engine]);
+    assert!(output.status.success(), "{engine}: {output:?}");
+    // debug builds of the VM list the compiled module first
+    assert_eq!(stdout_lines(&output).last().map(String::as_str), Some("3"), "{engine}");
+  }
+}
+
+#[test]
+fn renders_errors_with_source() {
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("error.lox", "print 1;\nprint -\"a\";\n", &[engine]);
+    assert_eq!(output.status.code(), Some(70), "{engine}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("error: "), "{engine}: {stderr}");
+    assert!(stderr.contains("2 | print -\"a\";\n  |       ^\n"), "{engine}: {stderr}");
+  }
+}
+
+#[test]
+fn passes_shared_options_to_the_engine() {
+  let src = "var x = 1; for (var i = 0; i < 400; i = i + 1) x = x * 10; print x;";
+  for engine in ["--engine=tree", "--engine=vm"] {
+    assert!(rlox("overflow.lox", src, &[engine]).status.success(), "{engine}");
+    assert!(!rlox("overflow.lox", src, &[engine, "--checked-arith"]).status.success(), "{engine}");
+  }
+}
+
+#[test]
+fn rejects_unknown_engine() {
+  let output = rlox("unknown.lox", "print 1;", &["--engine=jit"]);
+  assert_eq!(output.status.code(), Some(64));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("`--engine` must be `tree` or `vm`"));
+}
+
+#[test]
+fn evaluates_snippets_on_either_engine() {
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+      .args([engine, "-e", "var a = 6; print a; a * 7"])
+      .output()
+      .unwrap();
+    assert!(output.status.success(), "{engine}: {output:?}");
+    assert_eq!(stdout_lines(&output).last().map(String::as_str), Some("42"), "{engine}");
+    assert!(stdout_lines(&output).contains(&"6".to_string()), "{engine}");
+  }
+}
+
+#[test]
+fn reads_script_from_stdin() {
+  // the last two are handed to the engine's own command line
+  let cases: [&[&str]; 4] = [
+    &["--engine=tree", "-"],
+    &["--engine=vm", "-"],
+    &["--engine=tree", "--module-path=.", "-"],
+    &["--engine=vm", "--verify", "-"],
+  ];
+  for args in cases {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .spawn()
+      .unwrap();
+    child.stdin.take().unwrap().write_all(b"print 1 + 2;").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{args:?}: {output:?}");
+    assert_eq!(stdout_lines(&output).last().map(String::as_str), Some("3"), "{args:?}");
+  }
+}
+
+#[test]
+fn exit_codes_follow_the_book() {
+  // the last two are handed to the engine's own command line
+  let engines: [&[&str]; 4] = [&["--engine=tree"], &["--engine=vm"], &["--engine=tree", "--module-path=."], &["--engine=vm", "--verify"]];
+  for engine in engines {
+    let code = |src: &str| rlox("exit.lox", src, engine).status.code();
+    assert_eq!(code("print 1;"), Some(0), "{engine:?}");
+    assert_eq!(code("print 1"), Some(65), "{engine:?}");
+    assert_eq!(code("print -nil;"), Some(70), "{engine:?}");
+
+    let missing = Command::new(env!("CARGO_BIN_EXE_rlox")).args(engine).arg("missing.lox").output().unwrap();
+    assert_eq!(missing.status.code(), Some(74), "{engine:?}");
+    let usage = Command::new(env!("CARGO_BIN_EXE_rlox")).args(engine).arg("-e").output().unwrap();
+    assert_eq!(usage.status.code(), Some(64), "{engine:?}");
+  }
+}
+
+#[test]
+fn quiet_prints_only_output_and_errors() {
+  let src = "{ var unused = 1; }\nprint 2;";
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("quiet.lox", src, &[engine, "--quiet"]);
+    assert!(output.status.success(), "{engine}: {output:?}");
+    assert_eq!(stdout_lines(&output), ["2"], "{engine}");
+    assert!(output.stderr.is_empty(), "{engine}: {}", String::from_utf8_lossy(&output.stderr));
+  }
+
+  let loud = rlox("quiet.lox", src, &["--engine=tree"]);
+  assert!(String::from_utf8_lossy(&loud.stderr).contains("warning"), "{loud:?}");
+}
+
+#[test]
+fn time_reports_each_phase() {
+  let cases = [("--engine=tree", "[time] parse "), ("--engine=vm", "[time] compile ")];
+  for (engine, summary) in cases {
+    let output = rlox("time.lox", "print 1;", &[engine, "--time"]);
+    assert!(output.status.success(), "{engine}: {output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with(summary), "{engine}: {stderr}");
+    assert!(stderr.contains("execute "), "{engine}: {stderr}");
+  }
+}
+
+#[test]
+fn profile_calls_writes_folded_stacks() {
+  let src = "fun f() { return 1; } f();";
+  for engine in ["tree", "vm"] {
+    let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("profile-{engine}.folded"));
+    let engine_flag = format!("--engine={engine}");
+    let out_flag = out.to_str().unwrap();
+    let output = rlox("profile.lox", src, &[&engine_flag, "--profile-calls", out_flag]);
+    assert!(output.status.success(), "{engine}: {output:?}");
+    let folded = fs::read_to_string(&out).unwrap();
+    assert!(folded.lines().any(|line| line.starts_with("<script>;f ")), "{engine}: {folded}");
+  }
+}
+
+#[test]
+fn strict_globals_reject_undeclared_names() {
+  for engine in ["--engine=tree", "--engine=vm"] {
+    assert_eq!(rlox("loose.lox", "x = 1;", &[engine]).status.code(), Some(70), "{engine}");
+    let output = rlox("strict.lox", "x = 1;", &[engine, "--strict-globals"]);
+    assert_eq!(output.status.code(), Some(65), "{engine}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undeclared global `x`"), "{engine}");
+  }
+}
+
+#[test]
+fn warns_when_shadowing_natives() {
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("shadow.lox", "var clock = 1;\nprint clock;", &[engine]);
+    assert!(output.status.success(), "{engine}: {output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`clock` shadows the native function `clock()`"), "{engine}: {stderr}");
+  }
+}
+
+#[test]
+fn defines_select_conditional_lines() {
+  let src = "print 1;\n#if DEBUG\nprint 2;\n#else\nprint 3;\n#end\nprint -\"a\";\n";
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("defines.lox", src, &[engine, "--define", "DEBUG=true"]);
+    assert_eq!(output.status.code(), Some(70), "{engine}");
+    assert_eq!(stdout_lines(&output).iter().rev().take(2).collect::<Vec<_>>(), ["2", "1"], "{engine}");
+    // positions still point at the script as written
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("7 | print -\"a\";\n  |       ^\n"), "{engine}: {stderr}");
+
+    let output = rlox("defines.lox", src, &[engine, "--define", "DEBUG=false"]);
+    assert_eq!(stdout_lines(&output).iter().rev().take(2).collect::<Vec<_>>(), ["3", "1"], "{engine}");
+  }
+}
+
+#[test]
+fn defines_combine_with_engine_options() {
+  // a `#` line inside a string is not a directive
+  let src = "var a = \"x\n#end\";\n#if DEBUG\nprint a;\n#end\n";
+  for flag in ["--strict-globals", "--list-suppressed"] {
+    for engine in ["--engine=tree", "--engine=vm"] {
+      let output = rlox("engine_defines.lox", src, &[engine, flag, "--define", "DEBUG"]);
+      assert!(output.status.success(), "{engine} {flag}: {output:?}");
+      assert_eq!(stdout_lines(&output).iter().rev().take(2).collect::<Vec<_>>(), ["#end", "x"], "{engine}");
+    }
+  }
+}
+
+#[test]
+fn rejects_unbalanced_directives() {
+  let output = rlox("unbalanced.lox", "#if DEBUG\nprint 1;\n", &[]);
+  assert_eq!(output.status.code(), Some(65));
+  assert!(String::from_utf8_lossy(&output.stderr).contains("`#if` without an `#end`"));
+}
+
+#[test]
+fn summarizes_diagnostics_and_caps_errors() {
+  let src = "var a = ;\nvar b = ;\nvar c = ;\n";
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("broken.lox", src, &[engine]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.ends_with("\n3 errors, 0 warnings emitted\n"), "{engine}: {stderr}");
+
+    let output = rlox("broken.lox", src, &[engine, "--max-errors", "1"]);
+    assert_eq!(output.status.code(), Some(65), "{engine}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("error: ").count(), 1, "{engine}: {stderr}");
+    assert!(stderr.ends_with("3 errors, 0 warnings emitted (2 not shown after the first 1 error)\n"), "{engine}: {stderr}");
+  }
+
+  let output = rlox("warned.lox", "var clock = 1;", &["--engine=vm"]);
+  assert!(String::from_utf8_lossy(&output.stderr).ends_with("0 errors, 1 warning emitted\n"), "{output:?}");
+}
+
+#[test]
+fn writes_diagnostics_as_sarif_without_running() {
+  let src = "print 1;\nvar clock = 1;\n";
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = rlox("sarif.lox", src, &[engine, "--output-format=sarif"]);
+    assert!(output.status.success(), "{engine}: {output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("{\"version\":\"2.1.0\""), "{engine}: {stdout}");
+    assert!(!stdout.contains("\n1\n"), "{engine}: {stdout}");
+    assert!(stdout.contains("\"level\":\"warning\""), "{engine}: {stdout}");
+    assert!(stdout.contains("\"ruleId\":\"shadowed-native\""), "{engine}: {stdout}");
+    assert!(stdout.contains("\"region\":{\"startLine\":2,\"startColumn\":5,\"endLine\":2,\"endColumn\":10"), "{engine}: {stdout}");
+
+    assert!(stdout.contains("\"message\":{\"text\":\"`clock` shadows the native function `clock()`\"}"), "{engine}: {stdout}");
+
+    let output = rlox("sarif_error.lox", "print -;", &[engine, "--output-format=sarif"]);
+    assert_eq!(output.status.code(), Some(65), "{engine}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"level\":\"error\""), "{engine}");
+  }
+}
+
+#[test]
+fn sarif_points_at_the_importing_script_by_relative_uri() {
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("sarif");
+  fs::create_dir_all(&dir).unwrap();
+  fs::write(dir.join("bad.lox"), "var a = 1;\nvar a = 2;\n").unwrap();
+  fs::write(dir.join("main script.lox"), "\nimport \"bad.lox\";\n").unwrap();
+  let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+    .args(["--engine=tree", "--output-format=sarif", "sarif/main script.lox"])
+    .current_dir(env!("CARGO_TARGET_TMPDIR"))
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(65), "{output:?}");
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  assert!(stdout.contains("\"uri\":\"sarif/main%20script.lox\""), "{stdout}");
+  // the error in the module is reported at the import
+  assert!(stdout.contains("\"region\":{\"startLine\":2,\"startColumn\":1,\"endLine\":2,\"endColumn\":18"), "{stdout}");
+  assert!(!stdout.contains("relatedLocations"), "{stdout}");
+}
+
+#[test]
+fn scripts_get_their_arguments_and_set_the_exit_code() {
+  let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("process.lox");
+  fs::write(&path, "print process.arg(0); print process.arg(1); process.setExitCode(3);").unwrap();
+  for engine in ["--engine=tree", "--engine=vm"] {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+      .args([engine, path.to_str().unwrap(), "--quiet"])
+      .output()
+      .unwrap();
+    assert_eq!(output.status.code(), Some(3), "{engine}: {output:?}");
+    let lines = stdout_lines(&output);
+    assert_eq!(lines[lines.len() - 2..], ["--quiet", "nil"], "{engine}");
+  }
+}
+
+#[test]
+fn runs_bytecode_images_and_recompiles_stale_ones() {
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+  let (source, image) = (dir.join("image.lox"), dir.join("image.loxc"));
+  let rlox = |args: &[&str], script: &PathBuf| {
+    Command::new(env!("CARGO_BIN_EXE_rlox")).arg("--quiet").args(args).arg(script).output().unwrap()
+  };
+  fs::write(&source, "print 1;").unwrap();
+  let emitted = rlox(&["--engine=vm", "--emit=loxc"], &source);
+  assert!(emitted.status.success(), "{emitted:?}");
+  fs::write(&image, emitted.stdout).unwrap();
+  assert_eq!(stdout_lines(&rlox(&[], &image)), ["1"]);
+
+  fs::write(&source, "print 2;").unwrap();
+  assert_eq!(stdout_lines(&rlox(&[], &image)), ["1"]);
+  let output = rlox(&["--verify-source", source.to_str().unwrap()], &image);
+  assert_eq!(stdout_lines(&output), ["2"]);
+  assert!(String::from_utf8_lossy(&output.stderr).contains("is stale"), "{output:?}");
+}
+
+#[test]
+fn builds_executables_that_run_without_their_sources() {
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("build");
+  fs::create_dir_all(&dir).unwrap();
+  let (main, lib, app) = (dir.join("main.lox"), dir.join("lib.lox"), dir.join("app"));
+  fs::write(&main, "import \"lib.lox\";\nprint greet(process.arg(0));").unwrap();
+  fs::write(&lib, "fun greet(name) { return \"hi \" + name; }").unwrap();
+  let built = Command::new(env!("CARGO_BIN_EXE_rlox")).arg("build").arg(&main).arg("-o").arg(&app)
+    .output().unwrap();
+  assert!(built.status.success(), "{built:?}");
+
+  fs::remove_file(&lib).unwrap();
+  // every argument goes to the script, even ones `rlox` would read
+  let output = Command::new(&app).args(["--help", "x"]).output().unwrap();
+  assert_eq!(stdout_lines(&output), ["hi --help"], "{output:?}");
+}
+
+#[test]
+fn caches_imports_only_when_asked() {
+  let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cache");
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  let (main, cache) = (dir.join("main.lox"), dir.join("compiled"));
+  fs::write(&main, "import \"lib.lox\";\nprint two;").unwrap();
+  fs::write(dir.join("lib.lox"), "var two = 2;").unwrap();
+  let rlox = |args: &[&str]| {
+    Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).arg(&main).env_remove("LOX_CACHE_DIR")
+      .output().unwrap()
+  };
+
+  for args in [&["--engine=vm", "--quiet"][..], &["--engine=vm", "--emit=ir"]] {
+    assert!(rlox(args).status.success(), "{args:?}");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "{args:?}");
+  }
+  let output = rlox(&["--engine=vm", "--cache-dir", cache.to_str().unwrap()]);
+  assert_eq!(stdout_lines(&output).last().map(String::as_str), Some("2"));
+  assert_eq!(fs::read_dir(&cache).unwrap().count(), 1);
+}
@@ -37,6 +37,8 @@ pub struct Call {
   pub span: Span,
   pub callee: Box<Expr>,
   pub args: Vec<Expr>,
+  /// Line of the opening parenthesis, which `callerInfo()` reports the call at.
+  pub line: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -102,6 +104,43 @@ pub struct Logical {
   pub right: Box<Expr>,
 }
 
+// Dropping a long chain like `a + b + c` would recurse once per operator, so the left operands
+// are unlinked and dropped one at a time instead.
+
+impl Drop for Binary {
+  fn drop(&mut self) {
+    drop_left_chain(&mut self.left);
+  }
+}
+
+impl Drop for Logical {
+  fn drop(&mut self) {
+    drop_left_chain(&mut self.left);
+  }
+}
+
+fn drop_left_chain(left: &mut Expr) {
+  let mut next = take(left);
+  loop {
+    next = match &mut next {
+      Expr::Binary(binary) => take(&mut binary.left),
+      Expr::Logical(logical) => take(&mut logical.left),
+      _ => break,
+    };
+  }
+}
+
+/// Swaps `expr` for a placeholder, returning it.
+fn take(expr: &mut Expr) -> Expr {
+  std::mem::replace(
+    expr,
+    Expr::from(Lit {
+      span: Span::default(),
+      value: LoxValue::Nil,
+    }),
+  )
+}
+
 //
 // Some other utilities.
 //
@@ -113,7 +152,7 @@ impl From<Token> for Lit {
     Lit {
       span: token.span,
       value: match token.kind {
-        T::String(string) => L::String(string),
+        T::String(string) => L::String(string.to_string()),
         T::Number(number) => L::Number(number),
         T::Nil => L::Nil,
         T::True => L::Boolean(true),
@@ -134,13 +173,13 @@ impl fmt::Display for Expr {
       Self::Lit(token) => write!(f, "{}", token.value),
       Self::Group(node) => write!(f, "(group {})", node.expr),
       Self::Binary(bin) => {
-        return write!(f, "({} {} {})", bin.operator, bin.left, bin.right)
+        write!(f, "({} {} {})", bin.operator, bin.left, bin.right)
       },
       Self::Logical(logical) => {
-        return write!(f, "({} {} {})", logical.operator, logical.left, logical.right)
+        write!(f, "({} {} {})", logical.operator, logical.left, logical.right)
       },
       Self::Unary(unary) => {
-        return write!(f, "({} {})", unary.operator, unary.operand)
+        write!(f, "({} {})", unary.operator, unary.operand)
       },
       Self::Assignment(assign) => write!(f, "(= {} {})", assign.name, assign.value),
       Self::Call(call) => write!(f, "(call {} {})", call.callee, display_vec(&call.args)),
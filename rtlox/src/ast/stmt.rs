@@ -1,10 +1,10 @@
-use std::fmt::Display;
+use std::{fmt::Display, rc::Rc};
 
-use crate::{ast::expr, data::LoxIdent, disp::{display_option, display_vec}, span::Span};
+use crate::{ast::expr, data::{LoxIdent, LoxIdentId}, disp::{display_option, display_vec}, span::Span};
 
 make_ast_enum!(
   Stmt,
-  [VarDecl, FunDecl, ClassDecl, If, While, Print, Return, Block, Expr, Dummy]
+  [VarDecl, FunDecl, NativeDecl, ClassDecl, EnumDecl, Namespace, Import, Export, If, Switch, While, Break, Continue, Time, Print, Return, Block, Expr, Dummy]
 );
 
 #[derive(Debug, Clone)]
@@ -19,7 +19,17 @@ pub struct FunDecl {
   pub span: Span,
   pub name: LoxIdent,
   pub params: Vec<LoxIdent>,
-  pub body: Vec<Stmt>,
+  /// Shared with every function value made from the declaration rather than copied into each,
+  /// since copying clones its expressions recursively and a long chain in it would overflow.
+  pub body: Rc<[Stmt]>,
+}
+
+/// A function the host provides, checked against the natives it registered.
+#[derive(Debug, Clone)]
+pub struct NativeDecl {
+  pub span: Span,
+  pub name: LoxIdent,
+  pub params: Vec<LoxIdent>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +40,35 @@ pub struct ClassDecl {
   pub methods: Vec<FunDecl>,
 }
 
+#[derive(Debug, Clone)]
+pub struct EnumDecl {
+  pub span: Span,
+  pub name: LoxIdent,
+  pub variants: Vec<LoxIdent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Namespace {
+  pub span: Span,
+  pub name: LoxIdent,
+  pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+  pub span: Span,
+  /// Key of the loaded module, as recorded by the resolver.
+  pub id: LoxIdentId,
+  pub path: String,
+}
+
+/// A top-level declaration made visible to importers of its file.
+#[derive(Debug, Clone)]
+pub struct Export {
+  pub span: Span,
+  pub decl: Box<Stmt>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Return {
   pub span: Span,
@@ -45,23 +84,64 @@ pub struct If {
   pub else_branch: Option<Box<Stmt>>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Switch {
+  pub span: Span,
+  pub subject: expr::Expr,
+  pub cases: Vec<Case>,
+  pub default: Option<Vec<Stmt>>,
+}
+
+/// A single `case` arm of a `switch`. Arms do not fall through.
+#[derive(Debug, Clone)]
+pub struct Case {
+  pub span: Span,
+  pub value: expr::Expr,
+  pub body: Vec<Stmt>,
+}
+
 #[derive(Debug, Clone)]
 pub struct While {
   pub span: Span,
   pub cond: expr::Expr,
   pub body: Box<Stmt>,
+  /// Increment of a desugared `for` loop, which runs after the body even when `continue` cuts
+  /// the body short.
+  pub incr: Option<expr::Expr>,
+}
+
+/// Runs a block and prints how long it took.
+#[derive(Debug, Clone)]
+pub struct Time {
+  pub span: Span,
+  pub body: Box<Stmt>,
+}
+
+/// Leaves the innermost enclosing loop.
+#[derive(Debug, Clone)]
+pub struct Break {
+  pub span: Span,
+}
+
+/// Skips the rest of the body of the innermost enclosing loop.
+#[derive(Debug, Clone)]
+pub struct Continue {
+  pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Print {
   pub span: Span,
   pub expr: expr::Expr,
+  /// Echoes the value of a trailing expression at the REPL, which also binds it to `_`.
   pub debug: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Block {
   pub span: Span,
+  /// Key the resolver marks the block with when it declares nothing and so needs no scope.
+  pub id: LoxIdentId,
   pub stmts: Vec<Stmt>,
 }
 
@@ -77,14 +157,39 @@ pub struct Dummy {
   pub span: Span,
 }
 
+impl Stmt {
+  /// Returns the name bound by a declaration statement.
+  pub fn declared_name(&self) -> Option<&LoxIdent> {
+    use Stmt::*;
+    match self {
+      VarDecl(var) => Some(&var.name),
+      FunDecl(fun) => Some(&fun.name),
+      NativeDecl(native) => Some(&native.name),
+      ClassDecl(class) => Some(&class.name),
+      EnumDecl(decl) => Some(&decl.name),
+      Namespace(ns) => Some(&ns.name),
+      Export(export) => export.decl.declared_name(),
+      _ => None,
+    }
+  }
+}
+
 impl Display for Stmt {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     use Stmt::*;
     match self {
       Block(block) => write!(f, "Block ( {} )", display_vec(&block.stmts)),
       ClassDecl(class) => write!(f, "Class ( {} {{ \n {:?}\n }}", class.name, class.methods),
+      Import(import) => write!(f, "Import( {:?} )", import.path),
+      Export(export) => write!(f, "Export( {} )", export.decl),
+      Namespace(ns) => write!(f, "Namespace ( {} {{ {} }} )", ns.name, display_vec(&ns.body)),
+      EnumDecl(decl) => write!(f, "Enum ( {} {{ {} }} )", decl.name, display_vec(&decl.variants)),
       FunDecl(fun) => write!(f, "Fun( {} <{}>  {{ \n {}\n }} )", fun.name, display_vec(&fun.params), display_vec(&fun.body)),
+      NativeDecl(native) => write!(f, "Native( {} <{}> )", native.name, display_vec(&native.params)),
       Return(ret) => write!(f, "Return( {} )", display_option(&ret.value)),
+      Time(time) => write!(f, "Time( {} )", time.body),
+      Break(_) => f.write_str("Break"),
+      Continue(_) => f.write_str("Continue"),
 
       If(if_stmt) => write!(f, "If( {} ? {} : {} )", if_stmt.cond, if_stmt.then_branch, display_option(&if_stmt.else_branch)),
       Print(print) => write!(f, "Print( {} )", print.expr),
@@ -1,11 +1,12 @@
 use std::{
-  cell::RefCell, collections::HashMap, fmt::{self, Debug, Display}, rc::Rc, sync::atomic::{self, AtomicUsize}
+  any::Any, cell::RefCell, collections::HashMap, fmt::{self, Debug, Display}, rc::Rc, sync::atomic::{self, AtomicUsize}
 };
 
 use crate::{
   ast::stmt::FunDecl,
   interpreter::{control_flow::ControlFlow, environment::Environment, error::RuntimeError, CFResult, Interpreter},
   span::Span,
+  symbol::Symbol,
   token::{Token, TokenType},
 };
 
@@ -14,11 +15,14 @@ pub enum LoxValue {
   Function(Rc<dyn LoxCallable>),
   Class(Rc<LoxClass>),
   Object(Rc<LoxInstance>),
+  Enum(Rc<LoxEnum>),
+  Variant(Rc<LoxVariant>),
+  Namespace(Rc<LoxNamespace>),
+  Foreign(Rc<LoxForeign>),
   Boolean(bool),
   Number(f64),
   String(String),
   Nil,
-  Unset,
 }
 
 impl LoxValue {
@@ -33,7 +37,10 @@ impl LoxValue {
       Function(_) => "<func>",
       Class(_) => "<class>",
       Object(_) => "<instance>",
-      Unset => "<unset>",
+      Enum(_) => "<enum>",
+      Variant(_) => "<variant>",
+      Namespace(_) => "<namespace>",
+      Foreign(_) => "<foreign>",
     }
   }
 
@@ -43,13 +50,16 @@ impl LoxValue {
     match self {
       Boolean(inner) => *inner,
       Number(_) | String(_) | Function(_) | 
-      Class(_) | Object(_) => true,
+      Class(_) | Object(_) | Enum(_) | Variant(_) | Namespace(_) | Foreign(_) => true,
       Nil => false,
-      Unset => unreachable!("Invalid access of unset variable."),
     }
   }
 
   /// Checks if two `LoxValue`s are equal. No type coercion is performed so both types must be equal.
+  ///
+  /// `nil` equals only `nil`. Booleans, numbers and strings compare by value, so `NaN` is never
+  /// equal to anything, itself included. Everything else compares by identity, with bound methods
+  /// comparing by the method and instance they were bound from, so `a.m == a.m`.
   pub fn equals(&self, other: &Self) -> bool {
     use LoxValue::*;
     match (self, other) {
@@ -57,10 +67,33 @@ impl LoxValue {
       (Number(a), Number(b)) => a == b,
       (String(a), String(b)) => a == b,
       (Nil, Nil) => true,
+      (Function(a), Function(b)) => {
+        Rc::ptr_eq(a, b) || a.as_function().zip(b.as_function()).is_some_and(|(a, b)| a.same_binding(b))
+      }
+      (Class(a), Class(b)) => Rc::ptr_eq(a, b),
+      (Object(a), Object(b)) => Rc::ptr_eq(a, b),
+      (Enum(a), Enum(b)) => Rc::ptr_eq(a, b),
+      (Namespace(a), Namespace(b)) => Rc::ptr_eq(a, b),
+      (Variant(a), Variant(b)) => Rc::ptr_eq(a, b),
+      (Foreign(a), Foreign(b)) => Rc::ptr_eq(a, b),
       _ => false,
     }
   }
 
+  /// Wraps a host resource in a handle tagged `tag`, for natives to return to scripts.
+  pub fn foreign<T: Any>(tag: &'static str, data: T) -> Self {
+    LoxValue::Foreign(Rc::new(LoxForeign { tag, data: Box::new(data) }))
+  }
+
+  /// Returns the resource of a handle tagged `tag` if the given `LoxValue` is one and it holds a
+  /// `T`. Otherwise None.
+  pub fn as_foreign<T: Any>(&self, tag: &str) -> Option<&T> {
+    match self {
+      LoxValue::Foreign(handle) if handle.tag == tag => handle.downcast(),
+      _ => None,
+    }
+  }
+
   /// Returns the `Rc<LoxClass>` pointer if the given `LoxValue` is a class. Otherwise None.
   pub fn as_class(self) -> Option<Rc<LoxClass>> {
     match self {
@@ -86,6 +119,10 @@ impl Display for LoxValue {
       Function(fun) => Display::fmt(fun, f),
       Class(class) => Display::fmt(class, f),
       Object(instance) => Display::fmt(instance, f),
+      Enum(decl) => Display::fmt(decl, f),
+      Variant(variant) => Display::fmt(variant, f),
+      Namespace(namespace) => Display::fmt(namespace, f),
+      Foreign(handle) => Display::fmt(handle, f),
       Boolean(boolean) => Display::fmt(boolean, f),
       Number(number) => {
         if number.floor() == *number {
@@ -97,7 +134,6 @@ impl Display for LoxValue {
       }
       String(string) => f.write_str(string),
       Nil => f.write_str("nil"),
-      Unset => f.write_str("<unset>"),
     }
   }
 }
@@ -115,7 +151,7 @@ impl Debug for LoxValue {
 #[derive(Debug, Clone)]
 pub struct LoxIdent {
   pub id: LoxIdentId,
-  pub name: String,
+  pub name: Symbol,
   pub span: Span,
 }
 
@@ -131,8 +167,14 @@ impl LoxIdentId {
   }
 }
 
+impl Default for LoxIdentId {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl LoxIdent {
-  pub fn new(span: Span, name: impl Into<String>) -> Self {
+  pub fn new(span: Span, name: impl Into<Symbol>) -> Self {
     LoxIdent {
       id: LoxIdentId::new(),
       name: name.into(),
@@ -145,7 +187,7 @@ impl LoxIdent {
     let id = LoxIdentId::new();
     LoxIdent {
       id,
-      name: format!("<lambda {}>", id.0),
+      name: format!("<lambda {}>", id.0).into(),
       span,
     }
   }
@@ -154,7 +196,7 @@ impl LoxIdent {
 impl From<Token> for LoxIdent {
   fn from(Token { kind, span }: Token) -> Self {
     match kind {
-      TokenType::Identifier(name) => LoxIdent::new(span, name),
+      TokenType::Identifier(name) => LoxIdent::new(span, &*name),
       unexpected => unreachable!(
         "Invalid `Token` ({:?}) to `LoxIdent` conversion.",
         unexpected
@@ -169,12 +211,24 @@ impl AsRef<str> for LoxIdent {
   }
 }
 
-impl From<LoxIdent> for String {
+impl From<LoxIdent> for Symbol {
   fn from(ident: LoxIdent) -> Self {
     ident.name
   }
 }
 
+impl From<&LoxIdent> for Symbol {
+  fn from(ident: &LoxIdent) -> Self {
+    ident.name
+  }
+}
+
+impl From<LoxIdent> for String {
+  fn from(ident: LoxIdent) -> Self {
+    ident.name.to_string()
+  }
+}
+
 impl Display for LoxIdent {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     f.write_str(&self.name)
@@ -184,6 +238,16 @@ impl Display for LoxIdent {
 pub trait LoxCallable: Display + Debug {
   fn call(self: Rc<Self>, interpreter: &mut Interpreter, args: &[LoxValue]) -> CFResult<LoxValue>;
   fn arity(&self) -> usize;
+
+  /// The function, if this is one written in Lox rather than a native or a class.
+  fn as_function(&self) -> Option<&LoxFunction> {
+    None
+  }
+
+  /// Where the callee was declared, if it is written in Lox.
+  fn decl_span(&self) -> Option<Span> {
+    None
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -191,22 +255,63 @@ pub struct LoxFunction {
   pub decl: Rc<FunDecl>,
   pub closure: Environment,
   pub is_class_init: bool,
+  /// The method and instance a bound method was made from.
+  pub bound: Option<(Rc<LoxFunction>, Rc<LoxInstance>)>,
 }
 
 impl LoxFunction {
-  pub fn bind(&self, instance: &Rc<LoxInstance>) -> Rc<Self> {
+  pub fn bind(self: &Rc<Self>, instance: &Rc<LoxInstance>) -> Rc<Self> {
     let mut env = Environment::new_enclosed(&self.closure);
     env.define("this", LoxValue::Object(instance.clone()));
     Rc::new(LoxFunction {
         decl: self.decl.clone(),
         closure: env,
         is_class_init: self.is_class_init,
+        bound: Some((self.clone(), instance.clone())),
     })
   }
+
+  /// Whether both are the same method bound to the same instance.
+  pub fn same_binding(&self, other: &LoxFunction) -> bool {
+    match (&self.bound, &other.bound) {
+      (Some((method, this)), Some((other_method, other_this))) => {
+        Rc::ptr_eq(method, other_method) && Rc::ptr_eq(this, other_this)
+      }
+      _ => false,
+    }
+  }
 }
 
 impl LoxCallable for LoxFunction {
   fn call(self: Rc<Self>, interpreter: &mut Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
+    if interpreter.calls.len() >= interpreter.max_call_depth {
+      return Err(RuntimeError::StackOverflow {
+        ident: self.decl.name.clone(),
+        limit: interpreter.max_call_depth,
+      }.into());
+    }
+    interpreter.calls.push((self.decl.name.name, interpreter.call_line));
+    interpreter.hook_call(&self.decl.name.name, args);
+    let res = self.call_body(interpreter, args);
+    interpreter.calls.pop();
+    interpreter.hook_return(res)
+  }
+
+  fn arity(&self) -> usize {
+    self.decl.params.len()
+  }
+
+  fn as_function(&self) -> Option<&LoxFunction> {
+    Some(self)
+  }
+
+  fn decl_span(&self) -> Option<Span> {
+    Some(self.decl.name.span)
+  }
+}
+
+impl LoxFunction {
+  fn call_body(&self, interpreter: &mut Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
     let mut env = Environment::new_enclosed(&self.closure);
 
     for (param, value) in self.decl.params.iter().zip(args) {
@@ -220,15 +325,12 @@ impl LoxCallable for LoxFunction {
     };
 
     if self.is_class_init {
-      Ok(self.closure.read_at(0, "this"))
+      // `this` is the only local of the scope made by `bind`
+      Ok(self.closure.read_at(0, 0).unwrap())
     } else {
       Ok(res)
     }
   }
-
-  fn arity(&self) -> usize {
-    self.decl.params.len()
-  }
 }
 
 impl Display for LoxFunction {
@@ -239,13 +341,15 @@ impl Display for LoxFunction {
 
 pub struct NativeFunction {
   pub name: &'static str,
-  pub fn_ptr: fn(args: &[LoxValue]) -> CFResult<LoxValue>,
+  pub fn_ptr: fn(interpreter: &Interpreter, args: &[LoxValue]) -> CFResult<LoxValue>,
   pub arity: usize,
 }
 
 impl LoxCallable for NativeFunction {
-  fn call(self: Rc<Self>, _: &mut Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
-    (self.fn_ptr)(args)
+  fn call(self: Rc<Self>, interpreter: &mut Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
+    interpreter.hook_call(self.name, args);
+    let res = (self.fn_ptr)(interpreter, args);
+    interpreter.hook_return(res)
   }
 
   fn arity(&self) -> usize {
@@ -272,18 +376,19 @@ impl Debug for NativeFunction {
 #[derive(Debug, Clone)]
 pub struct LoxClass {
   pub name: LoxIdent,
-  pub methods: HashMap<String, Rc<LoxFunction>>,
+  pub methods: HashMap<Symbol, Rc<LoxFunction>>,
   pub super_class: Option<Rc<LoxClass>>,
 }
 
 impl LoxClass {
-  pub fn get_method(&self, ident: impl AsRef<str>) -> Option<Rc<LoxFunction>> {
+  pub fn get_method(&self, name: impl Into<Symbol>) -> Option<Rc<LoxFunction>> {
+    let name = name.into();
     self.methods
-        .get(ident.as_ref())
+        .get(&name)
         .cloned()
         .or_else(|| 
           self.super_class.as_ref()
-          .and_then(|s| s.get_method(ident))
+          .and_then(|s| s.get_method(name))
         )
   }
 }
@@ -303,7 +408,7 @@ impl LoxCallable for LoxClass {
     let instance = Rc::new(LoxInstance {
       name: LoxIdent::new(
         Span::new(0,0), 
-        self.name.name.clone()
+        self.name.name
       ),
       constructor: self,
       properties: RefCell::new(HashMap::new()),
@@ -322,13 +427,19 @@ impl LoxCallable for LoxClass {
       0
     }
   }
+
+  /// The initializer, which takes the arguments, or else the class.
+  fn decl_span(&self) -> Option<Span> {
+    let init = self.get_method("init");
+    Some(init.map_or(self.name.span, |init| init.decl.name.span))
+  }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct LoxInstance {
   pub constructor: Rc<LoxClass>,
   pub name: LoxIdent,
-  properties: RefCell<HashMap<String, LoxValue>>
+  properties: RefCell<HashMap<Symbol, LoxValue>>,
 }
 
 impl LoxInstance {
@@ -342,7 +453,7 @@ impl LoxInstance {
       return Ok(value.clone());
     }
 
-    if let Some(method) = self.get_bound_method(ident) {
+    if let Some(method) = self.get_bound_method(ident.name) {
       return Ok(LoxValue::Function(method));
     }
 
@@ -354,13 +465,11 @@ impl LoxInstance {
   pub fn set(&self, ident: &LoxIdent, value: LoxValue) {
     self.properties
       .borrow_mut()
-      .insert(ident.name.clone(), value);
+      .insert(ident.name, value);
   }
 
-  pub fn get_bound_method(self: &Rc<Self>, ident: impl AsRef<str>) -> Option<Rc<LoxFunction>> {
-    self.constructor
-      .get_method(ident)
-      .map(|unbound| unbound.bind(self))
+  pub fn get_bound_method(self: &Rc<Self>, name: impl Into<Symbol>) -> Option<Rc<LoxFunction>> {
+    Some(self.constructor.get_method(name)?.bind(self))
   }
 }
 
@@ -368,4 +477,108 @@ impl Display for LoxInstance {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "<instance {}>", self.name)
   }
-}
\ No newline at end of file
+}
+#[derive(Debug)]
+pub struct LoxEnum {
+  pub name: LoxIdent,
+  pub variants: Vec<Rc<LoxVariant>>,
+}
+
+impl LoxEnum {
+  pub fn new(name: LoxIdent, variants: &[LoxIdent]) -> Self {
+    let variants = variants.iter()
+      .enumerate()
+      .map(|(ordinal, variant)| Rc::new(LoxVariant {
+        enum_name: name.name,
+        name: variant.name,
+        ordinal,
+      }))
+      .collect();
+    Self { name, variants }
+  }
+
+  pub fn get(&self, ident: &LoxIdent) -> Result<LoxValue, RuntimeError> {
+    self.variants.iter()
+      .find(|variant| variant.name == ident.name)
+      .map(|variant| LoxValue::Variant(variant.clone()))
+      .ok_or_else(|| RuntimeError::UndefinedProperty {
+        ident: ident.clone(),
+      })
+  }
+}
+
+impl Display for LoxEnum {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<enum {}>", self.name)
+  }
+}
+
+/// A member of a `LoxEnum`. Each variant is allocated once, so equality is identity.
+#[derive(Debug)]
+pub struct LoxVariant {
+  pub enum_name: Symbol,
+  pub name: Symbol,
+  pub ordinal: usize,
+}
+
+impl Display for LoxVariant {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}", self.enum_name, self.name)
+  }
+}
+
+#[derive(Debug)]
+pub struct LoxNamespace {
+  pub name: LoxIdent,
+  pub members: Environment,
+}
+
+impl LoxNamespace {
+  pub fn get(&self, ident: &LoxIdent) -> Result<LoxValue, RuntimeError> {
+    self.members
+      .get_local(ident.name)
+      .ok_or_else(|| RuntimeError::UndefinedProperty {
+        ident: ident.clone(),
+      })
+  }
+}
+
+impl Display for LoxNamespace {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<namespace {}>", self.name)
+  }
+}
+
+/// An opaque handle to a host resource. Natives create and accept them, while scripts can only
+/// pass them around. Copies share the resource, so equality is identity.
+pub struct LoxForeign {
+  /// Names the kind of resource, for messages and for checking handles passed back to natives.
+  pub tag: &'static str,
+  data: Box<dyn Any>,
+}
+
+impl LoxForeign {
+  /// Whether the handle holds a `T`.
+  pub fn is<T: Any>(&self) -> bool {
+    self.data.is::<T>()
+  }
+
+  /// Returns the resource if it is a `T`. Otherwise None.
+  pub fn downcast<T: Any>(&self) -> Option<&T> {
+    self.data.downcast_ref()
+  }
+}
+
+impl Display for LoxForeign {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "<foreign {}>", self.tag)
+  }
+}
+
+impl Debug for LoxForeign {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("LoxForeign")
+      .field("tag", &self.tag)
+      .finish_non_exhaustive()
+  }
+}
@@ -2,6 +2,10 @@ use std::error::Error;
 
 pub enum ControlFlow<R, E> {
   Return(R),
+  /// Leaves the innermost loop.
+  Break,
+  /// Ends the current iteration of the innermost loop.
+  Continue,
   Err(E),
 }
 
@@ -0,0 +1,79 @@
+//! Conversions between Rust values and Lox values, so that natives and hosts need not match on
+//! `LoxValue` themselves. The conversions are shared with the VM through
+//! [`lox_common::convert`]; these traits fix them to `LoxValue`.
+
+use lox_common::convert;
+
+use crate::data::LoxValue;
+
+/// A Rust value that can be handed to Lox code. Implement [`convert::ToLox<LoxValue>`] to add one.
+pub trait ToLox {
+  fn to_lox(self) -> LoxValue;
+}
+
+/// A Rust value that can be read from Lox code. Implement [`convert::FromLox<LoxValue>`] to add
+/// one.
+pub trait FromLox: Sized {
+  /// Name of the expected Lox type, for error messages.
+  const TYPE: &'static str;
+
+  /// Returns the Rust value, or None if `value` does not hold one.
+  fn from_lox(value: &LoxValue) -> Option<Self>;
+}
+
+impl<T: convert::ToLox<LoxValue>> ToLox for T {
+  fn to_lox(self) -> LoxValue {
+    convert::ToLox::to_lox(self)
+  }
+}
+
+impl<T: convert::FromLox<LoxValue>> FromLox for T {
+  const TYPE: &'static str = <T as convert::FromLox<LoxValue>>::TYPE;
+
+  fn from_lox(value: &LoxValue) -> Option<Self> {
+    convert::FromLox::from_lox(value)
+  }
+}
+
+impl convert::Value for LoxValue {
+  fn nil() -> Self {
+    LoxValue::Nil
+  }
+
+  fn boolean(b: bool) -> Self {
+    LoxValue::Boolean(b)
+  }
+
+  fn number(n: f64) -> Self {
+    LoxValue::Number(n)
+  }
+
+  fn string(s: String) -> Self {
+    LoxValue::String(s)
+  }
+
+  fn is_nil(&self) -> bool {
+    matches!(self, LoxValue::Nil)
+  }
+
+  fn as_boolean(&self) -> Option<bool> {
+    match self {
+      LoxValue::Boolean(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  fn as_number(&self) -> Option<f64> {
+    match self {
+      LoxValue::Number(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  fn as_string(&self) -> Option<&str> {
+    match self {
+      LoxValue::String(s) => Some(s),
+      _ => None,
+    }
+  }
+}
@@ -3,15 +3,27 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use crate::{
   data::{LoxIdent, LoxValue},
   interpreter::error::RuntimeError,
+  symbol::Symbol,
 };
 
-#[derive(Debug, Default)]
+/// Variables of one scope. A value of `None` is a variable declared without an initializer and
+/// not assigned yet.
+#[derive(Debug)]
+enum Vars {
+  /// The global scope, which the resolver does not track, so it is searched by name.
+  Named(HashMap<Symbol, Option<LoxValue>>),
+  /// A local scope, in the order its variables were defined. The resolver gives each local the
+  /// same index, so reads need neither hashing nor a search.
+  Slots(Vec<(Symbol, Option<LoxValue>)>),
+}
+
+#[derive(Debug)]
 struct EnvironmentInner {
   enclosing: Option<Environment>,
-  locals: HashMap<String, LoxValue>,
+  vars: Vars,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct Environment {
   inner: Rc<RefCell<EnvironmentInner>>,
 }
@@ -19,16 +31,17 @@ pub struct Environment {
 impl Environment {
   /// Creates a new `Environment` with one scope (i.e. the global scope).
   pub fn new() -> Self {
-    Default::default()
+    Self::with(None, Vars::Named(HashMap::new()))
   }
 
   /// Returns a new environment that is enclosed by the given env
   pub fn new_enclosed(enclosing: &Self) -> Self {
+    Self::with(Some(enclosing.clone()), Vars::Slots(Vec::new()))
+  }
+
+  fn with(enclosing: Option<Environment>, vars: Vars) -> Self {
     Self {
-      inner: Rc::new(RefCell::new(EnvironmentInner {
-        enclosing: Some(enclosing.clone()),
-        locals: HashMap::new(),
-      })),
+      inner: Rc::new(RefCell::new(EnvironmentInner { enclosing, vars })),
     }
   }
 
@@ -37,17 +50,41 @@ impl Environment {
     self.inner.borrow().enclosing.clone()
   }
 
-  /// Defines a variable
-  pub fn define(&mut self, name: impl Into<String>, value: LoxValue) {
-    self.inner.borrow_mut().locals.insert(name.into(), value);
+  /// Defines a variable. In a local scope it takes the next slot.
+  pub fn define(&mut self, name: impl Into<Symbol>, value: LoxValue) {
+    self.insert(name.into(), Some(value));
+  }
+
+  /// Declares a variable without a value. Reading it before it is assigned is an error.
+  pub fn declare(&mut self, name: impl Into<Symbol>) {
+    self.insert(name.into(), None);
+  }
+
+  fn insert(&mut self, name: Symbol, value: Option<LoxValue>) {
+    match &mut self.inner.borrow_mut().vars {
+      Vars::Named(vars) => {
+        vars.insert(name, value);
+      }
+      Vars::Slots(slots) => slots.push((name, value)),
+    }
   }
 
-  /// Assigns a variable
+  /// Removes a variable defined directly in this scope, returning whether there was one. Only
+  /// global variables can be removed, since the resolver has fixed the slots of locals.
+  pub fn remove(&mut self, name: impl Into<Symbol>) -> bool {
+    match &mut self.inner.borrow_mut().vars {
+      Vars::Named(vars) => vars.remove(&name.into()).is_some(),
+      Vars::Slots(_) => false,
+    }
+  }
+
+  /// Assigns a variable by name, searching enclosing scopes.
   pub fn assign(&mut self, ident: &LoxIdent, value: LoxValue) -> Result<LoxValue, RuntimeError> {
     let mut inner = self.inner.borrow_mut();
-    match inner.locals.get_mut(&ident.name) {
+    let inner = &mut *inner;
+    match inner.vars.get_mut(ident.name) {
       Some(var) => {
-        *var = value.clone();
+        *var = Some(value.clone());
         Ok(value)
       }
       None => match &mut inner.enclosing {
@@ -61,27 +98,24 @@ impl Environment {
     }
   }
 
-  /// Assigns to a variable in a distant scope.
-  pub fn assign_at(&mut self, dist: usize, ident: &LoxIdent, value: LoxValue) -> LoxValue {
+  /// Assigns to the local in `slot` of a distant scope.
+  pub fn assign_at(&mut self, dist: usize, slot: usize, value: LoxValue) -> LoxValue {
     // This should never panic due to the semantic verifications that the resolver performs.
-    *self
-      .ancestor(dist)
-      .inner
-      .borrow_mut()
-      .locals
-      .get_mut(&ident.name)
-      .unwrap() = value.clone();
+    match &mut self.ancestor(dist).inner.borrow_mut().vars {
+      Vars::Slots(slots) => slots[slot].1 = Some(value.clone()),
+      Vars::Named(_) => unreachable!("locals are never global"),
+    }
     value
   }
 
-  /// Reads a variable.
+  /// Reads a variable by name, searching enclosing scopes.
   pub fn read(&self, ident: &LoxIdent) -> Result<LoxValue, RuntimeError> {
     let inner = self.inner.borrow();
-    match inner.locals.get(&ident.name) {
-      Some(LoxValue::Unset) => Err(RuntimeError::UnsetVariable {
+    match inner.vars.get(ident.name) {
+      Some(None) => Err(RuntimeError::UnsetVariable {
         ident: ident.clone(),
       }),
-      Some(var) => Ok(var.clone()),
+      Some(Some(var)) => Ok(var.clone()),
       None => match &inner.enclosing {
         Some(enclosing) => enclosing.read(ident),
         None => Err(RuntimeError::UndefinedVariable {
@@ -91,16 +125,52 @@ impl Environment {
     }
   }
 
-  /// Reads a variable in a distant scope.
-  pub fn read_at(&self, dist: usize, ident: impl AsRef<str>) -> LoxValue {
-    self
-      .ancestor(dist)
-      .inner
-      .borrow()
-      .locals
-      .get(ident.as_ref())
-      .unwrap()
-      .clone()
+  /// Reads a variable defined directly in this scope, without walking enclosing scopes.
+  /// Returns `None` if it is not defined or not assigned yet.
+  pub fn get_local(&self, name: impl Into<Symbol>) -> Option<LoxValue> {
+    self.inner.borrow().vars.get(name.into()).cloned().flatten()
+  }
+
+  /// Names of the variables defined directly in this scope, sorted so that listings are stable.
+  pub fn names(&self) -> Vec<String> {
+    let mut names: Vec<String> = match &self.inner.borrow().vars {
+      Vars::Named(vars) => vars.keys().map(ToString::to_string).collect(),
+      Vars::Slots(slots) => slots.iter().map(|(name, _)| name.to_string()).collect(),
+    };
+    names.sort_unstable();
+    names.dedup();
+    names
+  }
+
+  /// Variables defined directly in this scope with their values, or `None` if not assigned yet.
+  /// Globals are sorted by name and locals are in the order they were defined.
+  pub fn bindings(&self) -> Vec<(String, Option<LoxValue>)> {
+    match &self.inner.borrow().vars {
+      Vars::Named(vars) => {
+        let mut vars: Vec<_> = vars.iter().map(|(name, value)| (name.to_string(), value.clone())).collect();
+        vars.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        vars
+      }
+      Vars::Slots(slots) => slots.iter().map(|(name, value)| (name.to_string(), value.clone())).collect(),
+    }
+  }
+
+  /// Address of the scope, which is the same for every handle to it.
+  pub(crate) fn addr(&self) -> *const () {
+    Rc::as_ptr(&self.inner).cast()
+  }
+
+  /// Whether this scope defines `name`, assigned or not.
+  pub fn contains(&self, name: impl Into<Symbol>) -> bool {
+    self.inner.borrow().vars.get(name.into()).is_some()
+  }
+
+  /// Reads the local in `slot` of a distant scope, or `None` if it is not assigned yet.
+  pub fn read_at(&self, dist: usize, slot: usize) -> Option<LoxValue> {
+    match &self.ancestor(dist).inner.borrow().vars {
+      Vars::Slots(slots) => slots[slot].1.clone(),
+      Vars::Named(_) => unreachable!("locals are never global"),
+    }
   }
 
   fn ancestor(&self, dist: usize) -> Environment {
@@ -112,3 +182,26 @@ impl Environment {
     curr
   }
 }
+
+impl Default for Environment {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Vars {
+  fn get(&self, name: Symbol) -> Option<&Option<LoxValue>> {
+    match self {
+      Vars::Named(vars) => vars.get(&name),
+      // the latest definition wins, as it would in a map
+      Vars::Slots(slots) => slots.iter().rev().find(|(n, _)| *n == name).map(|(_, v)| v),
+    }
+  }
+
+  fn get_mut(&mut self, name: Symbol) -> Option<&mut Option<LoxValue>> {
+    match self {
+      Vars::Named(vars) => vars.get_mut(&name),
+      Vars::Slots(slots) => slots.iter_mut().rev().find(|(n, _)| *n == name).map(|(_, v)| v),
+    }
+  }
+}
@@ -3,7 +3,7 @@ use std::{
   fmt::{self, Display},
 };
 
-use crate::{data::LoxIdent, span::Span};
+use crate::{data::LoxIdent, span::{Label, Span}};
 
 #[derive(Debug, Clone)]
 pub enum RuntimeError {
@@ -13,6 +13,17 @@ pub enum RuntimeError {
   UnsetVariable { ident: LoxIdent },
   UndefinedProperty { ident: LoxIdent },
   ZeroDivision { span: Span },
+  /// Arithmetic produced `inf` or `NaN` while running with `checked_arith`.
+  NotFinite { value: f64, span: Span },
+  /// Reached a statement the parser replaced after a syntax error.
+  Unparsed { span: Span },
+  /// A `native fun` declaration names a native that the host did not register.
+  UnboundNative { ident: LoxIdent },
+  /// A call to `ident` went deeper than the interpreter's `max_call_depth`.
+  StackOverflow { ident: LoxIdent, limit: usize },
+  /// A call at `span` passed `got` arguments to a callee taking `expected`, which was declared at
+  /// `defined` if it is written in Lox.
+  Arity { expected: usize, got: usize, span: Span, defined: Option<Span> },
 }
 
 impl Display for RuntimeError {
@@ -50,6 +61,34 @@ impl Display for RuntimeError {
       ZeroDivision { span } => {
         write!(f, "Can not divide by zero; at position {}", span)
       }
+
+      NotFinite { value, span } => {
+        write!(f, "Arithmetic result `{}` is not a finite number; at position {}", value, span)
+      }
+
+      Unparsed { span } => {
+        write!(f, "Cannot run a statement that failed to parse; at position {}", span)
+      }
+
+      UnboundNative { ident } => {
+        write!(
+          f,
+          "Native function `{}` is not registered; at position {}",
+          ident.name, ident.span
+        )
+      }
+
+      StackOverflow { ident, limit } => {
+        write!(
+          f,
+          "Stack overflow: calling `{}` nests more than {} calls; at position {}",
+          ident.name, limit, ident.span
+        )
+      }
+
+      Arity { expected, got, span, .. } => {
+        write!(f, "Expected {} arguments, but got {}; at position {}", expected, got, span)
+      }
     }
   }
 }
@@ -59,9 +98,20 @@ impl RuntimeError {
   pub fn primary_span(&self) -> Span {
     use RuntimeError::*;
     match self {
-      UnsupportedType { span, .. } | ZeroDivision { span } => *span,
+      UnsupportedType { span, .. } | ZeroDivision { span } | NotFinite { span, .. }
+      | Unparsed { span } | Arity { span, .. } => *span,
       UndefinedVariable { ident } | UnsetVariable { ident } |
-      UndefinedProperty { ident }=> ident.span,
+      UndefinedProperty { ident } | UnboundNative { ident } | StackOverflow { ident, .. } => ident.span,
+    }
+  }
+
+  /// Other spans that explain the error.
+  pub fn labels(&self) -> Vec<Label> {
+    match self {
+      RuntimeError::Arity { expected, defined: Some(defined), .. } => {
+        vec![Label::new(*defined, format!("defined here with {} parameters", expected))]
+      }
+      _ => Vec::new(),
     }
   }
 }
@@ -0,0 +1,107 @@
+//! Graphviz drawings of scopes and closures, for seeing how closures capture the scopes they are
+//! declared in.
+
+use std::{collections::{HashMap, VecDeque}, fmt::Write};
+
+use crate::{data::{LoxFunction, LoxValue}, interpreter::environment::Environment};
+
+/// Draws `current` and the scopes that enclose it, up to `globals`, in the DOT language. Each
+/// scope is a box listing its variables. A variable holding a function points to it, and the
+/// function points to the scope it captured, which is drawn too. The built-in globals are left
+/// out.
+pub fn env_graph(current: &Environment, globals: &Environment) -> String {
+  let mut graph = Graph::default();
+  let mut out = String::from("digraph env {\n  node [shape=record];\n");
+  let start = graph.scope(current);
+  while let Some((id, env)) = graph.queue.pop_front() {
+    let title = match (id == start, env.addr() == globals.addr()) {
+      (true, true) => "globals (current)",
+      (true, false) => "current",
+      (false, true) => "globals",
+      (false, false) => "scope",
+    };
+    let mut rows = vec![title.to_string()];
+    let mut edges = Vec::new();
+    let bindings = env.bindings().into_iter()
+      .filter(|(name, value)| env.addr() != globals.addr() || !is_builtin(name, value));
+    for (row, (name, value)) in bindings.enumerate() {
+      let Some(value) = value else {
+        rows.push(format!("<v{row}> {} (unset)", escape(&name)));
+        continue;
+      };
+      rows.push(format!("<v{row}> {} = {}", escape(&name), escape(&format!("{value:?}"))));
+      if let Some(fun) = function(&value) {
+        let (fun_id, new) = graph.function(fun);
+        edges.push(format!("  scope{id}:v{row} -> fun{fun_id};"));
+        if new {
+          let closure = graph.scope(&fun.closure);
+          let label = escape(&format!("fun {}", fun.decl.name));
+          let _ = writeln!(out, "  fun{fun_id} [shape=ellipse, label=\"{label}\"];");
+          let _ = writeln!(out, "  fun{fun_id} -> scope{closure} [style=dashed, label=\"captures\"];");
+        }
+      }
+    }
+    let _ = writeln!(out, "  scope{id} [label=\"{{{}}}\"];", rows.join("|"));
+    if let Some(enclosing) = env.enclosed() {
+      let enclosing = graph.scope(&enclosing);
+      let _ = writeln!(out, "  scope{id} -> scope{enclosing} [label=\"enclosing\"];");
+    }
+    edges.iter().for_each(|edge| out.push_str(&format!("{edge}\n")));
+  }
+  out.push_str("}\n");
+  out
+}
+
+/// Numbers of the scopes and functions drawn so far, in the order they were found.
+#[derive(Default)]
+struct Graph {
+  scopes: HashMap<*const (), usize>,
+  functions: HashMap<*const LoxFunction, usize>,
+  /// Scopes numbered but not drawn yet.
+  queue: VecDeque<(usize, Environment)>,
+}
+
+impl Graph {
+  /// Number of `env`, which is queued to be drawn the first time it is seen.
+  fn scope(&mut self, env: &Environment) -> usize {
+    let next = self.scopes.len();
+    *self.scopes.entry(env.addr()).or_insert_with(|| {
+      self.queue.push_back((next, env.clone()));
+      next
+    })
+  }
+
+  /// Number of `fun`, and whether this is the first time it is seen.
+  fn function(&mut self, fun: &LoxFunction) -> (usize, bool) {
+    let next = self.functions.len();
+    let id = *self.functions.entry(fun).or_insert(next);
+    (id, id == next)
+  }
+}
+
+fn function(value: &LoxValue) -> Option<&LoxFunction> {
+  match value {
+    LoxValue::Function(callable) => callable.as_function(),
+    _ => None,
+  }
+}
+
+/// Whether a global is one the interpreter defines: a native or `process`.
+fn is_builtin(name: &str, value: &Option<LoxValue>) -> bool {
+  match value {
+    Some(LoxValue::Function(callable)) => callable.as_function().is_none(),
+    Some(LoxValue::Namespace(_)) => name == "process",
+    _ => false,
+  }
+}
+
+/// Escapes the characters that are special in the labels of `record` nodes.
+fn escape(text: &str) -> String {
+  text.chars().fold(String::new(), |mut out, c| {
+    if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+      out.push('\\');
+    }
+    out.push(c);
+    out
+  })
+}
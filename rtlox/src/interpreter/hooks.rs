@@ -0,0 +1,35 @@
+//! Callbacks for tools that follow a running program, such as profilers, debuggers or teaching
+//! visualizations, without changing the interpreter itself.
+
+use std::fmt;
+
+use crate::{ast::{expr::Expr, stmt::Stmt}, data::LoxValue};
+
+/// Observes execution. Install with [`Interpreter::hooks`](super::Interpreter::hooks); every
+/// method does nothing unless overridden.
+pub trait Hooks {
+  /// Called before each statement runs.
+  fn on_stmt(&mut self, _stmt: &Stmt) {}
+
+  /// Called once a statement has run, however it ended.
+  fn on_stmt_end(&mut self, _stmt: &Stmt) {}
+
+  /// Called before each expression is evaluated, including the ones inside it.
+  fn on_expr(&mut self, _expr: &Expr) {}
+
+  /// Called once an expression has been evaluated, with its value, or `None` if it raised an
+  /// error.
+  fn on_expr_end(&mut self, _expr: &Expr, _value: Option<&LoxValue>) {}
+
+  /// Called when a function, native or not, is called with `args`.
+  fn on_call(&mut self, _name: &str, _args: &[LoxValue]) {}
+
+  /// Called when the innermost call returns `value`. Calls that fail do not return.
+  fn on_return(&mut self, _value: &LoxValue) {}
+}
+
+impl fmt::Debug for dyn Hooks {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("<hooks>")
+  }
+}
@@ -1,37 +1,91 @@
-use std::{collections::HashMap, mem, rc::Rc};
+use std::{any::Any, cell::Cell, collections::HashMap, mem, rc::Rc};
 
 use crate::{
   ast::{
     expr::{self, Expr},
     stmt::{self, Stmt},
   },
-  data::{LoxClass, LoxFunction, LoxIdent, LoxIdentId, LoxValue, LoxInstance},
-  interpreter::{control_flow::ControlFlow, environment::Environment, error::RuntimeError},
+  data::{LoxCallable, LoxClass, LoxEnum, LoxFunction, LoxNamespace, LoxIdent, LoxValue, LoxInstance, NativeFunction},
+  interpreter::{
+    clock::{Clock, SystemClock}, control_flow::ControlFlow, convert::FromLox, environment::Environment, error::RuntimeError, hooks::Hooks,
+    module::ModuleLoader,
+  },
+  resolver::table::ResolutionTable,
   span::Span,
+  symbol::Symbol,
   token::TokenType,
 };
 
+pub use lox_common::clock;
 pub mod control_flow;
+pub mod convert;
 pub mod environment;
 pub mod error;
+pub mod graph;
+pub mod hooks;
+pub mod module;
 
 mod native;
 
+/// Default for [`Interpreter::max_call_depth`].
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// Rust stack the binaries give the interpreter. Each nested Lox call takes several KiB in a
+/// debug build, which is more than the main thread can hold at [`MAX_CALL_DEPTH`].
+pub const STACK_SIZE: usize = 64 << 20;
+
 #[derive(Debug)]
 pub struct Interpreter {
-  locals: HashMap<LoxIdentId, usize>,
+  /// What the resolver found out about the code run so far.
+  resolution: ResolutionTable,
+  pub loader: ModuleLoader,
   pub globals: Environment,
+  /// Arity of each native function, by name.
+  pub(crate) natives: HashMap<Symbol, usize>,
+  /// Natives the host registered, which scripts bind with `native fun` declarations, starting
+  /// with `envGraph`.
+  pub(crate) registered: HashMap<Symbol, Rc<NativeFunction>>,
   env: Environment,
+  /// Treat `inf` and `NaN` results of arithmetic as runtime errors.
+  pub checked_arith: bool,
+  /// Have the resolver reject reads and assignments of globals that the program never declares,
+  /// and top-level ones that run before the declaration.
+  pub strict_globals: bool,
+  /// Span of the latest call, which natives report their errors at.
+  call_span: Span,
+  /// Line of the latest call.
+  pub(crate) call_line: usize,
+  /// Name of each Lox function call currently running and the line it was made on, innermost
+  /// last.
+  pub(crate) calls: Vec<(Symbol, usize)>,
+  /// Deepest nesting of Lox function calls before a `StackOverflow` error, which keeps deep
+  /// recursion from overflowing the Rust stack.
+  pub max_call_depth: usize,
+  /// Where `clock()` and `time` blocks read the time from.
+  pub clock: Rc<dyn Clock>,
+  /// Arguments given to the script, which `process.arg` reads.
+  pub args: Vec<String>,
+  /// Code to exit with once the script ends, as set by `process.setExitCode`.
+  exit_code: Cell<i32>,
+  /// Lines printed by the script, when captured instead of written to stdout.
+  output: Option<Vec<String>>,
+  /// Values of the expressions echoed at the REPL, oldest first.
+  results: Vec<LoxValue>,
+  /// Told about each statement, call and return, when set.
+  pub hooks: Option<Box<dyn Hooks>>,
 }
 
 impl Interpreter {
   // Note that `CFResult` must not be exposed to the interpreter caller.
   // It is an implementation detail.
   pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+    // an error raised inside calls leaves them unfinished
+    self.calls.clear();
     match self.eval_stmts(stmts) {
       Ok(()) => Ok(()),
       Err(ControlFlow::Err(err)) => Err(err),
-      Err(ControlFlow::Return(_)) => unreachable!(),
+      // the resolver rejects these outside of functions and loops
+      Err(ControlFlow::Return(_) | ControlFlow::Break | ControlFlow::Continue) => unreachable!(),
     }
   }
 
@@ -47,45 +101,70 @@ impl Interpreter {
   }
 
   fn eval_stmt(&mut self, stmt: &Stmt) -> CFResult<()> {
+    if let Some(hooks) = &mut self.hooks {
+      hooks.on_stmt(stmt);
+    }
+    let result = self.eval_stmt_node(stmt);
+    if let Some(hooks) = &mut self.hooks {
+      hooks.on_stmt_end(stmt);
+    }
+    result
+  }
+
+  fn eval_stmt_node(&mut self, stmt: &Stmt) -> CFResult<()> {
     use Stmt::*;
     match &stmt {
       VarDecl(var) => self.eval_var_decl(var),
       FunDecl(fun) => self.eval_fun_decl(fun),
+      NativeDecl(native) => self.eval_native_decl(native),
       ClassDecl(class) => self.eval_class_decl(class),
+      EnumDecl(decl) => self.eval_enum_decl(decl),
+      Namespace(ns) => self.eval_namespace(ns),
+      Import(import) => self.eval_import(import),
+      Export(export) => self.eval_stmt(&export.decl),
       If(if_stmt) => self.eval_if_stmt(if_stmt),
+      Switch(switch) => self.eval_switch_stmt(switch),
       While(while_stmt) => self.eval_while_stmt(while_stmt),
+      Break(_) => Err(ControlFlow::Break),
+      Continue(_) => Err(ControlFlow::Continue),
+      Time(time) => self.eval_time_stmt(time),
       Print(print) => self.eval_print_stmt(print),
       Return(ret) => self.eval_return_stmt(ret),
+      Block(block) if self.resolution.is_flat(block.id) => self.eval_stmts(&block.stmts),
       Block(block) => self.eval_block(&block.stmts, Environment::new_enclosed(&self.env)),
       Expr(expr) => self.eval_expr(&expr.expr).map(drop),
-      Dummy(_) => unreachable!(),
+      Dummy(dummy) => Err(RuntimeError::Unparsed { span: dummy.span }.into()),
       // _ => Ok(()),
     }
   }
 
   fn eval_var_decl(&mut self, var: &stmt::VarDecl) -> CFResult<()> {
-    let mut value = LoxValue::Unset;
-    if let Some(init) = &var.init {
-      value = self.eval_expr(init)?;
+    match &var.init {
+      Some(init) => {
+        let value = self.eval_expr(init)?;
+        self.env.define(var.name.clone(), value);
+      }
+      None => self.env.declare(var.name.clone()),
     }
 
-    self.env.define(var.name.clone(), value);
-
     Ok(())
   }
 
   fn eval_fun_decl(&mut self, fun: &stmt::FunDecl) -> CFResult<()> {
-    self.env.define(
-      fun.name.clone(),
-      LoxValue::Function(Rc::new(LoxFunction {
-        decl: Rc::new(fun.clone()),
-        closure: self.env.clone(),
-        is_class_init: false,
-      })),
-    );
+    let function = self.function(fun);
+    self.env.define(fun.name.clone(), function);
     Ok(())
   }
 
+  fn function(&self, fun: &stmt::FunDecl) -> LoxValue {
+    LoxValue::Function(Rc::new(LoxFunction {
+      decl: Rc::new(fun.clone()),
+      closure: self.env.clone(),
+      is_class_init: false,
+      bound: None,
+    }))
+  }
+
   fn eval_class_decl(&mut self, decl: &stmt::ClassDecl) -> CFResult<()> {
     let super_class = decl.super_name.as_ref()
       .map(|name| {
@@ -109,11 +188,12 @@ impl Interpreter {
     let methods = decl.methods.iter().cloned()
       .map(|decl| {
         (
-          decl.name.name.clone(),
+          decl.name.name,
           Rc::new(LoxFunction {
             is_class_init: decl.name.name == "init",
             decl: Rc::new(decl),
-            closure: self.env.clone()
+            closure: self.env.clone(),
+            bound: None,
           })
         )
       }).collect();
@@ -134,6 +214,60 @@ impl Interpreter {
     Ok(())
   }
 
+  fn eval_enum_decl(&mut self, decl: &stmt::EnumDecl) -> CFResult<()> {
+    self.env.define(
+      decl.name.clone(),
+      LoxValue::Enum(Rc::new(LoxEnum::new(decl.name.clone(), &decl.variants))),
+    );
+    Ok(())
+  }
+
+  fn eval_namespace(&mut self, ns: &stmt::Namespace) -> CFResult<()> {
+    let members = Environment::new_enclosed(&self.env);
+    self.eval_block(&ns.body, members.clone())?;
+    self.env.define(
+      ns.name.clone(),
+      LoxValue::Namespace(Rc::new(LoxNamespace {
+        name: ns.name.clone(),
+        members,
+      })),
+    );
+    Ok(())
+  }
+
+  fn eval_native_decl(&mut self, decl: &stmt::NativeDecl) -> CFResult<()> {
+    let Some(native) = self.registered.get(&decl.name.name) else {
+      return Err(RuntimeError::UnboundNative { ident: decl.name.clone() }.into());
+    };
+    let native = LoxValue::Function(native.clone());
+    self.env.define(decl.name.name, native);
+    Ok(())
+  }
+
+  fn eval_import(&mut self, import: &stmt::Import) -> CFResult<()> {
+    // The resolver loads every module before evaluation starts.
+    let module = self.resolution.module(import.id).expect("imports are resolved").clone();
+
+    // Modules run once; later imports only re-bind their exports.
+    let cached = module.env.borrow().clone();
+    let module_env = match cached {
+      Some(env) => env,
+      None => {
+        let env = Environment::new_enclosed(&self.globals);
+        *module.env.borrow_mut() = Some(env.clone());
+        self.eval_block(&module.stmts, env.clone())?;
+        env
+      }
+    };
+
+    for name in &module.exports {
+      match module_env.get_local(name.name) {
+        Some(value) => self.env.define(name.name, value),
+        None => self.env.declare(name.name),
+      }
+    }
+    Ok(())
+  }
 
   fn eval_if_stmt(&mut self, stmt: &stmt::If) -> CFResult<()> {
     if self.eval_expr(&stmt.cond)?.truth() {
@@ -144,18 +278,57 @@ impl Interpreter {
     Ok(())
   }
 
+  fn eval_switch_stmt(&mut self, stmt: &stmt::Switch) -> CFResult<()> {
+    let subject = self.eval_expr(&stmt.subject)?;
+    for case in &stmt.cases {
+      if self.eval_expr(&case.value)?.equals(&subject) {
+        return self.eval_block(&case.body, Environment::new_enclosed(&self.env));
+      }
+    }
+    if let Some(body) = &stmt.default {
+      self.eval_block(body, Environment::new_enclosed(&self.env))?;
+    }
+    Ok(())
+  }
+
   fn eval_while_stmt(&mut self, stmt: &stmt::While) -> CFResult<()> {
     while self.eval_expr(&stmt.cond)?.truth() {
-      self.eval_stmt(&stmt.body)?;
+      match self.eval_stmt(&stmt.body) {
+        Ok(()) | Err(ControlFlow::Continue) => {}
+        Err(ControlFlow::Break) => break,
+        Err(other) => return Err(other),
+      }
+      if let Some(incr) = &stmt.incr {
+        self.eval_expr(incr)?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Runs the body, then prints how long it took. A body left early prints nothing.
+  fn eval_time_stmt(&mut self, time: &stmt::Time) -> CFResult<()> {
+    let start = self.clock.now();
+    self.eval_stmt(&time.body)?;
+    let line = format!("time: {:.3} ms", (self.clock.now() - start) * 1000.0);
+    match &mut self.output {
+      Some(lines) => lines.push(line),
+      None => println!("{}", line),
     }
     Ok(())
   }
 
   fn eval_print_stmt(&mut self, print: &stmt::Print) -> CFResult<()> {
     let val = self.eval_expr(&print.expr)?;
-    match print.debug {
-      true => println!("{:?}", val),
-      false => println!("{}", val),
+    let line = match print.debug {
+      true => format!("{:?}", val),
+      false => format!("{}", val),
+    };
+    if print.debug {
+      self.record_result(val);
+    }
+    match &mut self.output {
+      Some(lines) => lines.push(line),
+      None => println!("{}", line),
     }
     Ok(())
   }
@@ -171,12 +344,24 @@ impl Interpreter {
 
   pub(crate) fn eval_block(&mut self, block: &[Stmt], new_env: Environment) -> CFResult<()> {
     let old_env = mem::replace(&mut self.env, new_env);
-    let result = self.eval_stmts(&block);
+    let result = self.eval_stmts(block);
     self.env = old_env;
     result
   }
 
   fn eval_expr(&mut self, expr: &Expr) -> CFResult<LoxValue> {
+    let Some(hooks) = &mut self.hooks else {
+      return self.eval_expr_node(expr);
+    };
+    hooks.on_expr(expr);
+    let result = self.eval_expr_node(expr);
+    if let Some(hooks) = &mut self.hooks {
+      hooks.on_expr_end(expr, result.as_ref().ok());
+    }
+    result
+  }
+
+  fn eval_expr_node(&mut self, expr: &Expr) -> CFResult<LoxValue> {
     use Expr::*;
     match &expr {
       Var(var) => self.eval_var_expr(var),
@@ -196,11 +381,10 @@ impl Interpreter {
   }
 
   fn eval_var_expr(&mut self, var: &expr::Var) -> CFResult<LoxValue> {
-    Ok(self.lookup_variable(&var.name)?)
+    self.lookup_variable(&var.name)
   }
 
   fn eval_call_expr(&mut self, call: &expr::Call) -> CFResult<LoxValue> {
-    use LoxValue::*;
     let callee = self.eval_expr(&call.callee)?;
 
     let args = call
@@ -209,36 +393,46 @@ impl Interpreter {
       .map(|expr| self.eval_expr(expr))
       .collect::<Result<Vec<_>, _>>()?;
 
-    let callable = match callee {
-      Function(callable) => callable,
-      Class(class) => class,
+    let callable = self.callable(callee, args.len(), call.span)?;
+    self.call_span = call.span;
+    self.call_line = call.line;
+    callable.call(self, &args)
+  }
+
+  /// Checks that `callee` can be called with `argc` arguments.
+  fn callable(&self, callee: LoxValue, argc: usize, span: Span) -> CFResult<Rc<dyn LoxCallable>> {
+    let callable: Rc<dyn LoxCallable> = match callee {
+      LoxValue::Function(callable) => callable,
+      LoxValue::Class(class) => class,
       _ => {
         return Err(ControlFlow::from(RuntimeError::UnsupportedType {
           message: format!(
             "Type `{}` is not callable. Can only call functions",
             callee.type_name()
           ),
-          span: call.span,
+          span,
         }))
       }
     };
 
-    if callable.arity() != args.len() {
-      return Err(ControlFlow::from(RuntimeError::UnsupportedType {
-        message: format!(
-          "Expected {} arguments, but got {}",
-          callable.arity(),
-          args.len()
-        ),
-        span: call.span,
+    if callable.arity() != argc {
+      return Err(ControlFlow::from(RuntimeError::Arity {
+        expected: callable.arity(),
+        got: argc,
+        span,
+        defined: callable.decl_span(),
       }));
     }
-
-    callable.call(self, &args)
+    Ok(callable)
   }
 
   fn eval_get_expr(&mut self, get: &expr::Get) -> CFResult<LoxValue> {
     let maybe_obj = self.eval_expr(&get.obj)?;
+    match maybe_obj {
+      LoxValue::Enum(decl) => return Ok(decl.get(&get.name)?),
+      LoxValue::Namespace(ns) => return Ok(ns.get(&get.name)?),
+      _ => {}
+    }
     let obj  = Self::ensure_object(maybe_obj, get.name.span)?;
     Ok(obj.get(&get.name)?)
   }
@@ -253,20 +447,20 @@ impl Interpreter {
 
   fn eval_super_expr(&mut self, sup: &expr::Super) -> CFResult<LoxValue> {
     // FOllowing two unwraps should never fail due to semantic verification
-    let dist = self.locals.get(&sup.super_ident.id).unwrap();
+    let (dist, slot) = self.resolution.local(&sup.super_ident).unwrap();
     let super_class = self.env
-      .read_at(*dist, "super")
-      .as_class()
+      .read_at(dist, slot)
+      .and_then(LoxValue::as_class)
       .unwrap();
 
     // The environment where "this" is defined is always bound immediately inside the
     // environment that defined "super" (the "this env" encloses the "super env").
     let this = self.env
-      .read_at(dist - 1, "this")
-      .as_object()
+      .read_at(dist - 1, 0)
+      .and_then(LoxValue::as_object)
       .unwrap();
 
-      match super_class.get_method(&sup.method) {
+      match super_class.get_method(sup.method.name) {
         Some(method) => Ok(
           LoxValue::Function(
           method.bind(&this))
@@ -291,7 +485,7 @@ impl Interpreter {
     let operand = self.eval_expr(&unary.operand)?;
     match &unary.operator.kind {
       TokenType::Minus => match operand {
-        LoxValue::Number(n) => Ok(LoxValue::Number(-n)),
+        LoxValue::Number(n) => self.number(-n, unary.operator.span),
         unexpected => Err(
           RuntimeError::UnsupportedType {
             message: format!(
@@ -308,11 +502,32 @@ impl Interpreter {
     }
   }
 
+  /// Wraps the result of an arithmetic operator, trapping `inf` and `NaN` in checked mode.
+  fn number(&self, n: f64, span: Span) -> CFResult<LoxValue> {
+    if self.checked_arith && !n.is_finite() {
+      return Err(RuntimeError::NotFinite { value: n, span }.into());
+    }
+    Ok(LoxValue::Number(n))
+  }
+
   fn eval_binary_expr(&mut self, binary: &expr::Binary) -> CFResult<LoxValue> {
-    use LoxValue::*;
-    let left = self.eval_expr(&binary.left)?;
-    let right = self.eval_expr(&binary.right)?;
+    // a chain like `a + b + c` nests to the left; walk it with a loop so that its length does
+    // not grow the host stack
+    let mut chain = vec![binary];
+    while let Expr::Binary(inner) = &*chain[chain.len() - 1].left {
+      chain.push(inner);
+    }
 
+    let mut left = self.eval_expr(&chain[chain.len() - 1].left)?;
+    for binary in chain.into_iter().rev() {
+      let right = self.eval_expr(&binary.right)?;
+      left = self.binary_op(binary, left, right)?;
+    }
+    Ok(left)
+  }
+
+  fn binary_op(&mut self, binary: &expr::Binary, left: LoxValue, right: LoxValue) -> CFResult<LoxValue> {
+    use LoxValue::*;
     match &binary.operator.kind {
       TokenType::EqualEqual => Ok(LoxValue::Boolean(left.equals(&right))),
       TokenType::BangEqual => Ok(LoxValue::Boolean(!left.equals(&right))),
@@ -322,10 +537,9 @@ impl Interpreter {
       TokenType::Less => bin_cmp_op!(left < right, binary.operator),
       TokenType::LessEqual => bin_cmp_op!(left <= right, binary.operator),
 
-      TokenType::Minus => bin_num_op!(left - right, binary.operator),
-      TokenType::Star => bin_num_op!(left * right, binary.operator),
+      TokenType::Minus => bin_num_op!(self, left - right, binary.operator),
+      TokenType::Star => bin_num_op!(self, left * right, binary.operator),
       TokenType::Slash => {
-        // TODO: enable/disable division by zero with env var
         if let Number(divisor) = right {
           if divisor == 0.0 {
             return Err(
@@ -336,18 +550,19 @@ impl Interpreter {
             );
           }
         }
-        bin_num_op!(left / right, binary.operator)
+        bin_num_op!(self, left / right, binary.operator)
       }
 
       TokenType::Plus => match (left, right) {
-        (Number(left), Number(right)) => Ok(Number(left + right)),
+        (Number(left), Number(right)) => self.number(left + right, binary.operator.span),
         (String(left), String(right)) => Ok(String(left + &right)),
-        // extended string concat
+        // a string on either side joins the other operand as it would be printed
         (String(left), right) => Ok(String(left + &right.to_string())),
+        (left, String(right)) => Ok(String(left.to_string() + &right)),
         (left, right) => Err(
           RuntimeError::UnsupportedType {
             message: format!(
-              "Binary `+` operator can only operate over two numbers or two strings. \
+              "Binary `+` operator can only operate over two numbers, or a string and any value. \
             Got types `{}` and `{}`",
               left.type_name(),
               right.type_name()
@@ -364,29 +579,41 @@ impl Interpreter {
   }
 
   fn eval_logical_expr(&mut self, logical: &expr::Logical) -> CFResult<LoxValue> {
-    let left = self.eval_expr(&logical.left)?;
-    match &logical.operator.kind {
-      TokenType::And if !left.truth() => Ok(left),
-      TokenType::Or if left.truth() => Ok(left),
-      _ => self.eval_expr(&logical.right),
+    // like binary chains, `a or b or c` is walked with a loop
+    let mut chain = vec![logical];
+    while let Expr::Logical(inner) = &*chain[chain.len() - 1].left {
+      chain.push(inner);
     }
+
+    let mut left = self.eval_expr(&chain[chain.len() - 1].left)?;
+    for logical in chain.into_iter().rev() {
+      left = match &logical.operator.kind {
+        TokenType::And if !left.truth() => left,
+        TokenType::Or if left.truth() => left,
+        _ => self.eval_expr(&logical.right)?,
+      };
+    }
+    Ok(left)
   }
 
   fn eval_assignment(&mut self, assign: &expr::Assignment) -> CFResult<LoxValue> {
     let value = self.eval_expr(&assign.value)?;
 
-    if let Some(dist) = self.locals.get(&assign.name.id) {
-      Ok(self.env.assign_at(*dist, &assign.name, value))
+    if let Some((dist, slot)) = self.resolution.local(&assign.name) {
+      Ok(self.env.assign_at(dist, slot, value))
     } else {
       Ok(self.globals.assign(&assign.name, value)?)
     }
   }
 
   fn eval_lambda(&mut self, lambda: &expr::Lambda) -> CFResult<LoxValue> {
-    self.eval_fun_decl(&lambda.decl)?;
+    Ok(self.function(&lambda.decl))
+  }
+}
 
-    // return identifier to function
-    Ok(self.env.read(&lambda.decl.name)?)
+impl Default for Interpreter {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
@@ -394,21 +621,202 @@ impl Interpreter {
   pub fn new() -> Self {
     let mut globals = Environment::new();
     native::attach(&mut globals);
+    let natives = globals.names().into_iter()
+      .filter_map(|name| match globals.get_local(&name) {
+        Some(LoxValue::Function(native)) => Some((name.into(), native.arity())),
+        _ => None,
+      })
+      .collect();
 
     Self {
       env: globals.clone(),
       globals,
-      locals: HashMap::new(),
+      natives,
+      registered: native::teaching(),
+      resolution: ResolutionTable::new(),
+      loader: ModuleLoader::default(),
+      checked_arith: false,
+      strict_globals: false,
+      call_span: Span::default(),
+      call_line: 0,
+      calls: Vec::new(),
+      max_call_depth: MAX_CALL_DEPTH,
+      clock: Rc::new(SystemClock),
+      args: Vec::new(),
+      exit_code: Cell::new(0),
+      output: None,
+      results: Vec::new(),
+      hooks: None,
+    }
+  }
+
+  /// Collects the output of `print` instead of writing it to stdout.
+  pub fn capture_output(&mut self) {
+    self.output.get_or_insert_with(Vec::new);
+  }
+
+  /// Writes the output of `print` to stdout again, dropping anything not yet taken.
+  pub fn print_output(&mut self) {
+    self.output = None;
+  }
+
+  /// Returns the lines printed since the last call, if output is captured.
+  pub fn take_output(&mut self) -> Vec<String> {
+    self.output.as_mut().map(std::mem::take).unwrap_or_default()
+  }
+
+  /// Code the process should exit with once the script ends, 0 unless the script set another.
+  pub fn exit_code(&self) -> i32 {
+    self.exit_code.get()
+  }
+
+  /// The scopes in reach of the code running now and the scopes its closures captured, as a
+  /// Graphviz drawing. See [`graph::env_graph`].
+  pub fn env_graph(&self) -> String {
+    graph::env_graph(&self.env, &self.globals)
+  }
+
+  /// Where the running function was called from, as the name of the calling function and the line
+  /// of the call, e.g. `main:3`. The top level is named `script`. `None` outside of functions.
+  pub fn caller_info(&self) -> Option<String> {
+    let [.., (_, line)] = self.calls.as_slice() else {
+      return None;
+    };
+    let caller = match self.calls.as_slice() {
+      [.., (caller, _), _] => caller.as_str(),
+      _ => "script",
+    };
+    Some(format!("{caller}:{line}"))
+  }
+
+  /// Values of the expressions echoed at the REPL so far, oldest first.
+  pub fn results(&self) -> &[LoxValue] {
+    &self.results
+  }
+
+  /// Binds an echoed value to `_`, and to `_1`, `_2`, ... in the order the values were echoed.
+  fn record_result(&mut self, val: LoxValue) {
+    self.results.push(val.clone());
+    self.globals.define(format!("_{}", self.results.len()), val.clone());
+    self.globals.define("_", val);
+  }
+
+  /// Registers a native for scripts to bind with `native fun name(...);`. Unlike the built-in
+  /// natives, it is not a global until a script declares it.
+  pub fn register_native(
+    &mut self,
+    name: &'static str,
+    arity: usize,
+    fn_ptr: fn(&Interpreter, &[LoxValue]) -> CFResult<LoxValue>,
+  ) {
+    self.register(NativeFunction { name, fn_ptr, arity });
+  }
+
+  /// Registers a native made with `native_fn!`, like `register_native`.
+  pub fn register(&mut self, native: NativeFunction) {
+    self.registered.insert(native.name.into(), Rc::new(native));
+  }
+
+  /// Calls a Lox function or class with `args`, e.g. a callback that a script handed to the host,
+  /// and returns its result.
+  pub fn call_value(&mut self, callee: &LoxValue, args: &[LoxValue]) -> Result<LoxValue, RuntimeError> {
+    self.call_span = Span::default();
+    self.call_line = 0;
+    let call = self.callable(callee.clone(), args.len(), self.call_span)
+      .and_then(|callable| callable.call(self, args));
+    match call {
+      Ok(value) => Ok(value),
+      Err(ControlFlow::Err(err)) => Err(err),
+      // the resolver rejects these outside of functions and loops
+      Err(ControlFlow::Return(_) | ControlFlow::Break | ControlFlow::Continue) => unreachable!(),
     }
   }
 
-  pub fn resolve_local(&mut self, ident: &LoxIdent, depth: usize) {
-    self.locals.insert(ident.id, depth);
+  /// Converts argument `i` of a native, or returns an error for the native to return if it has
+  /// the wrong type.
+  pub fn arg<T: FromLox>(&self, args: &[LoxValue], i: usize) -> CFResult<T> {
+    let arg = args.get(i).unwrap_or(&LoxValue::Nil);
+    T::from_lox(arg).ok_or_else(|| {
+      let message = format!(
+        "Expected `{}` for argument {}, but got `{}`",
+        T::TYPE,
+        i + 1,
+        arg.type_name()
+      );
+      RuntimeError::UnsupportedType { message, span: self.call_span }.into()
+    })
+  }
+
+  /// Returns the resource of a handle tagged `tag` passed to a native, or an error for the native
+  /// to return if `arg` is anything else.
+  pub fn foreign_arg<'a, T: Any>(&self, arg: &'a LoxValue, tag: &str) -> CFResult<&'a T> {
+    arg.as_foreign(tag).ok_or_else(|| {
+      let got = match arg {
+        LoxValue::Foreign(handle) => format!("a `{}` handle", handle.tag),
+        other => format!("`{}`", other.type_name()),
+      };
+      let message = format!("Expected a `{tag}` handle, but got {got}");
+      RuntimeError::UnsupportedType { message, span: self.call_span }.into()
+    })
+  }
+
+  /// The globals and their values, for [`Interpreter::globals_diff`] to compare with later. Globals
+  /// declared without a value hold `nil`.
+  pub fn globals_snapshot(&self) -> HashMap<String, LoxValue> {
+    self.globals.names().into_iter()
+      .map(|name| {
+        let value = self.globals.get_local(&name).unwrap_or(LoxValue::Nil);
+        (name, value)
+      })
+      .collect()
+  }
+
+  /// How the globals changed since `before` was taken, one line per global in sorted order:
+  /// `+ name = value` for a new global, `~ name = value` for one holding another value, and
+  /// `- name` for one that was removed.
+  pub fn globals_diff(&self, before: &HashMap<String, LoxValue>) -> Vec<String> {
+    let after = self.globals_snapshot();
+    let mut names: Vec<&String> = after.keys().chain(before.keys()).collect();
+    names.sort_unstable();
+    names.dedup();
+    names.into_iter().filter_map(|name| match (before.get(name), after.get(name)) {
+      (None, Some(value)) => Some(format!("+ {name} = {value}")),
+      (Some(old), Some(value)) if !old.equals(value) => Some(format!("~ {name} = {value}")),
+      (Some(_), None) => Some(format!("- {name}")),
+      _ => None,
+    }).collect()
+  }
+
+  /// Removes a global, returning whether it was defined. Functions that use it fail with an
+  /// undefined variable error from then on, until it is defined again.
+  pub fn unset_global(&mut self, name: &str) -> bool {
+    self.globals.remove(name)
+  }
+
+  /// Adds what the resolver found out about code that is about to run.
+  pub fn add_resolution(&mut self, table: ResolutionTable) {
+    self.resolution.extend(table);
+  }
+
+  pub(crate) fn hook_call(&mut self, name: &str, args: &[LoxValue]) {
+    if let Some(hooks) = &mut self.hooks {
+      hooks.on_call(name, args);
+    }
+  }
+
+  /// Passes `res` through, telling the hooks if the call returned.
+  pub(crate) fn hook_return(&mut self, res: CFResult<LoxValue>) -> CFResult<LoxValue> {
+    if let (Some(hooks), Ok(value)) = (&mut self.hooks, &res) {
+      hooks.on_return(value);
+    }
+    res
   }
 
   fn lookup_variable(&self, ident: &LoxIdent) -> CFResult<LoxValue> {
-    if let Some(distance) = self.locals.get(&ident.id) {
-      Ok(self.env.read_at(*distance, ident))
+    if let Some((dist, slot)) = self.resolution.local(ident) {
+      self.env
+        .read_at(dist, slot)
+        .ok_or_else(|| RuntimeError::UnsetVariable { ident: ident.clone() }.into())
     } else {
       Ok(self.globals.read(ident)?)
     }
@@ -431,9 +839,9 @@ impl Interpreter {
 pub type CFResult<T> = Result<T, ControlFlow<LoxValue, RuntimeError>>;
 
 macro_rules! bin_num_op {
-  ( $left:tt $op:tt $right:tt, $op_token:expr ) => {
+  ( $self:expr, $left:tt $op:tt $right:tt, $op_token:expr ) => {
     match ($left, $right) {
-      (Number(left), Number(right)) => Ok(Number(left $op right)),
+      (Number(left), Number(right)) => $self.number(left $op right, $op_token.span),
       (left, right) => Err(RuntimeError::UnsupportedType {
         message: format!(
           "Binary `{}` operator can only operate over two numbers. \
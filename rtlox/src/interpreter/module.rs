@@ -0,0 +1,56 @@
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  path::{Path, PathBuf},
+  rc::Rc,
+};
+
+use crate::{ast::stmt::Stmt, data::LoxIdent, interpreter::environment::Environment};
+
+/// A source file loaded by an `import`, already parsed and resolved.
+#[derive(Debug)]
+pub struct LoxModule {
+  /// Canonical path of the source file.
+  pub path: PathBuf,
+  pub stmts: Vec<Stmt>,
+  /// Top-level bindings visible to the importer. Everything else stays private to the file.
+  pub exports: Vec<LoxIdent>,
+  /// Module scope, set once the module has been evaluated.
+  pub env: RefCell<Option<Environment>>,
+}
+
+/// Locates module files and caches loaded modules by canonical path.
+#[derive(Debug, Default)]
+pub struct ModuleLoader {
+  /// Directories searched after the importing file's directory.
+  pub search_paths: Vec<PathBuf>,
+  /// Names that `#if` directives in modules test.
+  pub defines: HashMap<String, String>,
+  modules: HashMap<PathBuf, Rc<LoxModule>>,
+}
+
+impl ModuleLoader {
+  /// Finds the canonical path of `spec`, trying the importing file's directory first and then
+  /// each search path in order.
+  pub fn find(&self, spec: &str, importer_dir: &Path) -> Option<PathBuf> {
+    let spec = Path::new(spec);
+    if spec.is_absolute() {
+      return spec.canonicalize().ok();
+    }
+
+    std::iter::once(importer_dir)
+      .chain(self.search_paths.iter().map(PathBuf::as_path))
+      .find_map(|dir| {
+        let candidate = dir.join(spec);
+        candidate.is_file().then(|| candidate.canonicalize().ok()).flatten()
+      })
+  }
+
+  pub fn get(&self, path: &Path) -> Option<Rc<LoxModule>> {
+    self.modules.get(path).cloned()
+  }
+
+  pub fn insert(&mut self, module: Rc<LoxModule>) {
+    self.modules.insert(module.path.clone(), module);
+  }
+}
@@ -1,35 +1,150 @@
-use std::rc::Rc;
+use std::{
+  collections::HashMap,
+  env,
+  io::{self, BufRead, Write},
+  rc::Rc,
+};
 
 use crate::{
-  data::{LoxIdent, LoxValue, NativeFunction},
-  interpreter::{environment::Environment, CFResult},
+  data::{LoxIdent, LoxNamespace, LoxValue, NativeFunction},
+  interpreter::environment::Environment,
+  native_fn,
   span::Span,
+  symbol::Symbol,
 };
 
-pub fn attach(globals: &mut Environment) {
-  def_native!(
-    globals.clock / 0,
-    fn clock(_: &[LoxValue]) -> CFResult<LoxValue> {
-      use std::time::{SystemTime, UNIX_EPOCH};
-      let start = SystemTime::now();
-      let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
-      Ok(LoxValue::Number(since_the_epoch))
+pub fn attach(env: &mut Environment) {
+  define(env, native_fn! {
+    fn clock(interpreter) -> f64 {
+      interpreter.clock.now()
     }
-  );
+  });
+
+  define(env, native_fn! {
+    fn globals(interpreter) -> String {
+      interpreter.globals.names().join(", ")
+    }
+  });
+
+  define(env, native_fn! {
+    #[allow(non_snake_case)]
+    fn callerInfo(interpreter) -> Option<String> {
+      interpreter.caller_info()
+    }
+  });
+
+  attach_process(env);
 }
 
-macro_rules! def_native {
-  ($globals:ident . $name:ident / $arity:expr  , $fn:item) => {
-    $fn
-    $globals.define(
-      LoxIdent::new(Span::new(0, 0), stringify!($name)),
-      LoxValue::Function(Rc::new(NativeFunction {
-        name: stringify!($name),
-        fn_ptr: $name,
-        arity: $arity
-      })),
-    );
-  };
+/// Natives for learners, which scripts bind with `native fun` so that they are not globals.
+pub fn teaching() -> HashMap<Symbol, Rc<NativeFunction>> {
+  let natives = [
+    native_fn! {
+      #[allow(non_snake_case)]
+      fn envGraph(interpreter) -> String {
+        interpreter.env_graph()
+      }
+    },
+  ];
+  natives.into_iter().map(|native| (native.name.into(), Rc::new(native))).collect()
 }
 
-use def_native;
+/// Binds `process`, whose members let a script reach the host: its arguments, the environment,
+/// standard input and output, and the code the process exits with.
+fn attach_process(env: &mut Environment) {
+  let mut members = Environment::new();
+
+  define(&mut members, native_fn! {
+    fn argc(interpreter) -> usize {
+      interpreter.args.len()
+    }
+  });
+
+  define(&mut members, native_fn! {
+    fn arg(interpreter, n: usize) -> Option<String> {
+      interpreter.args.get(n).cloned()
+    }
+  });
+
+  define(&mut members, native_fn! {
+    fn getenv(_, name: String) -> Option<String> {
+      env::var(name).ok()
+    }
+  });
+
+  // `nil` at the end of the input
+  define(&mut members, native_fn! {
+    #[allow(non_snake_case)]
+    fn readLine(_) -> Option<String> {
+      let mut line = String::new();
+      match io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\r', '\n']).into()),
+      }
+    }
+  });
+
+  // goes to stdout even when the output of `print` is captured
+  define(&mut members, native_fn! {
+    fn write(_, text: String) -> () {
+      let mut stdout = io::stdout();
+      let _ = stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush());
+    }
+  });
+
+  define(&mut members, native_fn! {
+    #[allow(non_snake_case)]
+    fn setExitCode(interpreter, code: i32) -> () {
+      interpreter.exit_code.set(code);
+    }
+  });
+
+  let name = LoxIdent::new(Span::new(0, 0), "process");
+  env.define(name.clone(), LoxValue::Namespace(Rc::new(LoxNamespace { name, members })));
+}
+
+/// Binds `native` to a global of the same name.
+fn define(env: &mut Environment, native: NativeFunction) {
+  env.define(
+    LoxIdent::new(Span::new(0, 0), native.name),
+    LoxValue::Function(Rc::new(native)),
+  );
+}
+
+/// Declares a native from a Rust function over typed arguments, which are converted with
+/// `FromLox` before the body runs. A mistyped argument fails the call at its span. The body
+/// evaluates to the result, which is converted with `ToLox`, and may return errors early with
+/// `?`. The first parameter names the interpreter, or is `_` if the body does not need it.
+///
+/// ```ignore
+/// interpreter.register(native_fn! {
+///   fn repeat(_, s: String, times: usize) -> String {
+///     s.repeat(times)
+///   }
+/// });
+/// ```
+#[macro_export]
+macro_rules! native_fn {
+  (
+    $(#[$attr:meta])*
+    fn $name:ident($interpreter:tt $(, $arg:ident : $ty:ty)* $(,)?) -> $ret:ty $body:block
+  ) => {{
+    $(#[$attr])*
+    fn $name(
+      interpreter: &$crate::interpreter::Interpreter,
+      _args: &[$crate::data::LoxValue],
+    ) -> $crate::interpreter::CFResult<$crate::data::LoxValue> {
+      let _next = &mut (0..);
+      $(let $arg: $ty = interpreter.arg(_args, _next.next().unwrap())?;)*
+      let $interpreter = interpreter;
+      let result: $ret = $body;
+      Ok($crate::interpreter::convert::ToLox::to_lox(result))
+    }
+
+    $crate::data::NativeFunction {
+      name: stringify!($name),
+      fn_ptr: $name,
+      arity: <[&str]>::len(&[$(stringify!($arg)),*]),
+    }
+  }};
+}
@@ -3,35 +3,175 @@ pub mod interpreter;
 pub mod parser;
 pub mod resolver;
 pub mod token;
+pub mod transpile;
 
 pub mod data;
 pub mod span;
+pub mod symbol;
 pub mod user;
 
 pub mod disp;
 
 use std::str;
 
-pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(), &'static str> {
+use user::Failure;
+
+const USAGE: &str = "Usage rlox [--module-path <dir>]... [--define NAME[=VALUE]]... [--checked-arith] [--strict-globals] [--quiet] [--list-suppressed] [--stream] [--time] [--profile-calls <file>] [--explain] [--env-graph <file>] [--max-call-depth=<n>] [--max-string-len=<n>] [--run-valid-prefix] [--emit=rust|js] [--grammar] [-e <code> | script [args...] | - [args...]]";
+
+/// Why the process should exit with an error.
+#[derive(Debug)]
+pub enum ExitError {
+  /// The command line was not understood.
+  Usage(&'static str),
+  /// The script failed; its diagnostics have already been printed.
+  Failed(Failure),
+}
+
+impl ExitError {
+  /// Exit code for the error, following the book: 64 for usage errors, 65 for compile errors,
+  /// 70 for runtime errors and 74 when the script cannot be read. A script that set its own exit
+  /// code exits with that.
+  pub fn code(&self) -> i32 {
+    match self {
+      ExitError::Usage(_) => 64,
+      ExitError::Failed(Failure::Exit(code)) => *code,
+      ExitError::Failed(Failure::Compile) => 65,
+      ExitError::Failed(Failure::Runtime) => 70,
+      ExitError::Failed(Failure::Io(_)) => 74,
+    }
+  }
+
+  /// Message to print before exiting, unless the script already reported its errors.
+  pub fn message(&self) -> Option<String> {
+    match self {
+      ExitError::Usage(message) => Some(format!("Problem parsing arguments: {message}")),
+      ExitError::Failed(failure @ Failure::Io(_)) => Some(failure.to_string()),
+      ExitError::Failed(_) => None,
+    }
+  }
+}
+
+impl From<&'static str> for ExitError {
+  fn from(message: &'static str) -> Self {
+    ExitError::Usage(message)
+  }
+}
+
+impl From<Failure> for ExitError {
+  fn from(failure: Failure) -> Self {
+    ExitError::Failed(failure)
+  }
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(), ExitError> {
   args.next();
 
-  let file_path = match args.next() {
-    Some(arg) => arg,
-    None => {
-      user::run_repl();
-      return Ok(());
+  let mut config = user::Config::default();
+  let mut file_path = None;
+  let mut snippet = None;
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-e" => snippet = Some(args.next().ok_or(USAGE)?),
+      "--module-path" => {
+        let dir = args.next().ok_or(USAGE)?;
+        config.module_paths.push(dir.into());
+      }
+      flag if flag.starts_with("--module-path=") => {
+        config.module_paths.push(flag["--module-path=".len()..].into());
+      }
+      "--define" => {
+        let define = args.next().ok_or(USAGE)?;
+        let (name, value) = define.split_once('=').unwrap_or((&define, "true"));
+        config.defines.insert(name.into(), value.into());
+      }
+      "--checked-arith" => config.checked_arith = true,
+      "--strict-globals" => config.strict_globals = true,
+      "--run-valid-prefix" => config.run_valid_prefix = true,
+      "--quiet" => config.quiet = true,
+      "--stream" => config.stream = true,
+      "--list-suppressed" => config.list_suppressed = true,
+      "--time" => config.time = true,
+      "--profile-calls" => config.profile_calls = Some(args.next().ok_or(USAGE)?.into()),
+      flag if flag.starts_with("--profile-calls=") => {
+        config.profile_calls = Some(flag["--profile-calls=".len()..].into());
+      }
+      "--explain" => config.explain = true,
+      "--env-graph" => config.env_graph = Some(args.next().ok_or(USAGE)?.into()),
+      flag if flag.starts_with("--env-graph=") => {
+        config.env_graph = Some(flag["--env-graph=".len()..].into());
+      }
+      flag if flag.starts_with("--max-call-depth=") => {
+        let depth = flag["--max-call-depth=".len()..].parse().map_err(|_| USAGE)?;
+        config.max_call_depth = Some(depth);
+      }
+      flag if flag.starts_with("--max-string-len=") => {
+        let len = flag["--max-string-len=".len()..].parse().map_err(|_| USAGE)?;
+        config.max_string_len = Some(len);
+      }
+      flag if flag.starts_with("--emit=") => config.emit = Some(flag["--emit=".len()..].parse()?),
+      "--grammar" => {
+        print!("{}", parser::grammar::grammar());
+        return Ok(());
+      }
+      _ if snippet.is_some() => return Err(USAGE.into()),
+      // the rest of the arguments are the script's own
+      _ => {
+        file_path = Some(arg);
+        config.args.extend(args.by_ref());
+      }
     }
-  };
+  }
 
-  // don't accept extra arguments
-  if let Some(_) = args.next() {
-    return Err("Usage rlox [script]");
+  if config.explain && config.profile_calls.is_some() {
+    return Err("`--explain` can't be combined with `--profile-calls`".into());
   }
 
-  if let Err(err) = user::run_file(&file_path) {
-    eprintln!("{}", err);
-    return Err("Could not run file")
+  if let Some(snippet) = snippet {
+    if config.stream {
+      return Err("`--stream` needs a script to read".into());
+    }
+    if file_path.is_some() {
+      return Err(USAGE.into());
+    }
+    if config.emit.is_some() {
+      return Err("`--emit` needs a script to translate".into());
+    }
+    if config.run_valid_prefix {
+      return Err("`--run-valid-prefix` only applies to the REPL".into());
+    }
+    return Ok(user::run_snippet(&snippet, &config)?);
+  }
+
+  let Some(file_path) = file_path else {
+    if config.emit.is_some() {
+      return Err("`--emit` needs a script to translate".into());
+    }
+    if config.profile_calls.is_some() {
+      return Err("`--profile-calls` needs a script or `-e`".into());
+    }
+    if config.explain {
+      return Err("`--explain` needs a script or `-e`".into());
+    }
+    if config.env_graph.is_some() {
+      return Err("`--env-graph` needs a script or `-e`".into());
+    }
+    if config.stream {
+      return Err("`--stream` needs a script to read".into());
+    }
+    user::run_repl(&config);
+    return Ok(());
   };
+  if config.run_valid_prefix {
+    return Err("`--run-valid-prefix` only applies to the REPL".into());
+  }
+
+  if config.stream && config.emit.is_some() {
+    return Err("`--stream` only runs a script, without `--emit`".into());
+  }
+
+  if let Some(target) = config.emit {
+    return Ok(user::transpile_file(&file_path, target, &config)?);
+  }
 
-  Ok(())
+  Ok(user::run_file_with(&file_path, &config, &mut interpreter::Interpreter::new())?)
 }
@@ -1,12 +1,23 @@
 use std::env;
 use std::process;
+use std::thread;
+
+use rtlox::interpreter::STACK_SIZE;
 
 #[cfg(test)]
 mod tests;
 
 fn main() {
-  let _ = rtlox::parse_args(env::args()).unwrap_or_else(|err| {
-    eprintln!("Problem parsing arguments: {err}");
-    process::exit(1);
+  // deep Lox recursion needs more stack than the main thread has
+  let run = thread::Builder::new()
+    .stack_size(STACK_SIZE)
+    .spawn(|| rtlox::parse_args(env::args()))
+    .expect("Failed to start the interpreter thread");
+
+  run.join().unwrap().unwrap_or_else(|err| {
+    if let Some(message) = err.message() {
+      eprintln!("{message}");
+    }
+    process::exit(err.code());
   });
 }
@@ -5,7 +5,7 @@ use std::{
 
 use crate::{
   parser::scanner::error::ScanError,
-  span::Span,
+  span::{Label, Span},
   token::{Token, TokenType},
 };
 
@@ -27,6 +27,14 @@ pub enum ParseError {
     expected: Option<TokenType>,
   },
 
+  /// An error with other spans that explain it, see [`ParseError::with_label`], or notes on how
+  /// to fix it, see [`ParseError::with_note`].
+  Labeled {
+    error: Box<ParseError>,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+  },
+
   DetectedLambda,
 }
 
@@ -39,7 +47,7 @@ impl Display for ParseError {
       }
 
       ScanError { error, span } => {
-        write!(f, "{}; at position {}", error, span)
+        write!(f, "[{}] {}; at position {}", error.code(), error, span)
       }
 
       UnexpectedToken {
@@ -56,6 +64,8 @@ impl Display for ParseError {
         Ok(())
       }
 
+      Labeled { error, .. } => error.fmt(f),
+
       DetectedLambda => unreachable!(),
     }
   }
@@ -70,16 +80,57 @@ impl ParseError {
     match self {
       Error { span, .. } | ScanError { span, .. } => *span,
       UnexpectedToken { offending, .. } => offending.span,
+      Labeled { error, .. } => error.primary_span(),
       DetectedLambda => unreachable!(),
     }
   }
 
+  /// Other spans that explain the error.
+  pub fn labels(&self) -> &[Label] {
+    match self {
+      ParseError::Labeled { labels, .. } => labels,
+      _ => &[],
+    }
+  }
+
+  /// Notes on how to fix the error.
+  pub fn notes(&self) -> &[String] {
+    match self {
+      ParseError::Labeled { notes, .. } => notes,
+      _ => &[],
+    }
+  }
+
+  /// Adds a label pointing at `span`.
+  pub fn with_label(self, span: Span, message: impl Into<String>) -> Self {
+    let label = Label::new(span, message);
+    match self {
+      ParseError::Labeled { error, mut labels, notes } => {
+        labels.push(label);
+        ParseError::Labeled { error, labels, notes }
+      }
+      error => ParseError::Labeled { error: Box::new(error), labels: vec![label], notes: Vec::new() },
+    }
+  }
+
+  /// Adds a note, reported after the error rather than as part of its message.
+  pub fn with_note(self, note: impl Into<String>) -> Self {
+    match self {
+      ParseError::Labeled { error, labels, mut notes } => {
+        notes.push(note.into());
+        ParseError::Labeled { error, labels, notes }
+      }
+      error => ParseError::Labeled { error: Box::new(error), labels: Vec::new(), notes: vec![note.into()] },
+    }
+  }
+
   /// Checks if the error allows REPL continuation (aka. "..." prompt).
   pub fn allows_continuation(&self) -> bool {
     use ParseError::*;
     match self {
       UnexpectedToken { offending, .. } if offending.kind == TokenType::EOF => true,
       ScanError { error, .. } if error.allows_continuation() => true,
+      Labeled { error, .. } => error.allows_continuation(),
       DetectedLambda => unreachable!(),
       _ => false,
     }
@@ -0,0 +1,35 @@
+//! Reference grammar of the language accepted by the parser, printed by `--grammar`.
+//!
+//! Each production is read from a `grammar:` line in the doc comment of the parser function that
+//! implements it, so the listing is kept next to the code it describes.
+
+const PARSER_SRC: &str = include_str!("mod.rs");
+
+/// A grammar rule, `name → body ;`.
+pub struct Production {
+  pub name: String,
+  pub body: String,
+}
+
+/// Returns the grammar as one production per line.
+pub fn grammar() -> String {
+  let productions = productions();
+  let width = productions.iter().map(|p| p.name.len()).max().unwrap_or(0);
+  productions
+    .iter()
+    .map(|p| format!("{:width$} → {} ;\n", p.name, p.body))
+    .collect()
+}
+
+/// The annotated productions, in source order.
+pub fn productions() -> Vec<Production> {
+  PARSER_SRC
+    .lines()
+    .filter_map(|line| line.trim().strip_prefix("/// grammar:"))
+    .filter_map(|rule| {
+      let (name, body) = rule.split_once('→')?;
+      let body = body.trim().trim_end_matches(';').trim_end();
+      Some(Production { name: name.trim().into(), body: body.into() })
+    })
+    .collect()
+}
@@ -1,30 +1,55 @@
-use std::{borrow::Borrow, mem};
+use std::{borrow::Borrow, collections::VecDeque, io::Read, mem};
 
 use crate::{
   ast::{
     expr::{self, Expr},
     stmt::{self, Stmt},
   },
-  data::{LoxIdent, LoxValue},
-  parser::{error::ParseError, scanner::Scanner, state::ParserOptions},
+  data::{LoxIdent, LoxIdentId, LoxValue},
+  parser::{
+    error::ParseError,
+    scanner::{error::ScanError, identifier::MAX_IDENTIFIER_LEN, Scanner},
+    state::{ParserOptions, MAX_STRING_LEN},
+    trivia::{Comment, Comments, Ignore},
+  },
   span::Span,
   token::{Token, TokenType},
 };
 
 pub mod error;
+pub mod grammar;
 pub mod scanner;
 pub mod state;
+pub mod trivia;
 
 /// Parse result
 type PResult<T> = Result<T, ParseError>;
 
 pub type ParserOutcome = (Vec<Stmt>, Vec<ParseError>);
 
+/// Leading statements that end before the first syntax error, and so parsed completely.
+pub fn valid_prefix<'a>(stmts: &'a [Stmt], errors: &[ParseError]) -> &'a [Stmt] {
+  let Some(first) = errors.iter().map(|err| err.primary_span().0).min() else {
+    return stmts;
+  };
+  let valid = stmts.iter().take_while(|stmt| stmt.span().1 <= first).count();
+  &stmts[..valid]
+}
+
 pub struct Parser<'src> {
+  /// The whole source, unless it is read from a stream.
+  src: Option<&'src str>,
   scanner: Scanner<'src>,
+  /// Tokens scanned ahead by `next_is`, which `advance` takes before scanning more.
+  lookahead: VecDeque<Token>,
   current_token: Token,
   prev_token: Token,
   diagnostics: Vec<ParseError>,
+  /// Blocks whose declarations are being parsed. Recovering from an error inside one stops at
+  /// the `}` that closes it.
+  open_blocks: usize,
+  /// Comments skipped so far, kept for `parse_with_comments` and `parse_with_ignores`.
+  comments: Vec<Comment>,
   pub options: ParserOptions,
 }
 
@@ -33,7 +58,33 @@ impl Parser<'_> {
     (self.parse_program(), self.diagnostics)
   }
 
+  /// Parses like `parse`, and if `options.keep_comments` is set, attaches the comments of the
+  /// source to the statements around them. A source read from a stream keeps no comments.
+  pub fn parse_with_comments(mut self) -> (ParserOutcome, Comments) {
+    let stmts = self.parse_program();
+    let comments = match (self.options.keep_comments, self.src) {
+      (true, Some(src)) => trivia::attach(src, &stmts, mem::take(&mut self.comments)),
+      _ => Comments::default(),
+    };
+    ((stmts, self.diagnostics), comments)
+  }
+
+  /// Parses like `parse`, and collects the `lox-ignore` comments of the source, whatever
+  /// `options.keep_comments` is. A source read from a stream has none.
+  pub fn parse_with_ignores(mut self) -> (ParserOutcome, Vec<Ignore>) {
+    let stmts = self.parse_program();
+    let ignores = match self.src {
+      Some(src) => trivia::ignores(src, &stmts, mem::take(&mut self.comments)),
+      None => Vec::new(),
+    };
+    ((stmts, self.diagnostics), ignores)
+  }
+
+  /// grammar: program → declaration* EOF ;
   fn parse_program(&mut self) -> Vec<Stmt> {
+    // the scanner reads directives with the defines of the options
+    self.scanner.set_defines(self.options.defines.clone());
+    self.advance(); // The first advancement.
     let mut stmts = Vec::new();
     while !self.is_at_end() {
       stmts.push(self.parse_decl());
@@ -45,12 +96,18 @@ impl Parser<'_> {
   // Declarations
   //
 
+  /// grammar: declaration → varDecl | funDecl | nativeDecl | classDecl | enumDecl | namespaceDecl | importDecl | exportDecl | statement ;
   fn parse_decl(&mut self) -> Stmt {
     use TokenType::*;
     let res = match self.current_token.kind {
       Var => self.parse_var_decl(),
       Fun => self.parse_fun_decl(),
+      Native => self.parse_native_decl(),
       Class => self.parse_class_decl(),
+      Enum => self.parse_enum_decl(),
+      Namespace => self.parse_namespace_decl(),
+      Import => self.parse_import(),
+      Export => self.parse_export_decl(),
       _ => self.parse_stmt(),
     };
 
@@ -67,6 +124,7 @@ impl Parser<'_> {
     }
   }
 
+  /// grammar: varDecl → "var" IDENTIFIER ( "=" expression )? ";" ;
   fn parse_var_decl(&mut self) -> PResult<Stmt> {
     use TokenType::*;
     let var_span = self.consume(Var, S_MUST)?.span;
@@ -85,6 +143,7 @@ impl Parser<'_> {
     }))
   }
 
+  /// grammar: funDecl → "fun" function | "fun" lambdaDecl ;
   fn parse_fun_decl(&mut self) -> PResult<Stmt> {
     use TokenType::*;
     let fun_span = self.consume(Fun, S_MUST)?.span;
@@ -96,6 +155,24 @@ impl Parser<'_> {
     Ok(Stmt::from(fun))
   }
 
+  /// grammar: nativeDecl → "native" "fun" IDENTIFIER "(" parameters? ")" ";" ;
+  fn parse_native_decl(&mut self) -> PResult<Stmt> {
+    use TokenType::*;
+    let native_span = self.consume(Native, S_MUST)?.span;
+    self.consume(Fun, "Expected `fun` after `native`")?;
+
+    let name = self.consume_ident("Expected native function name")?;
+    let params = self.parse_params("native function")?;
+    let semicolon_span = self.consume(Semicolon, "Expected `;` after native function declaration")?.span;
+
+    Ok(Stmt::from(stmt::NativeDecl {
+      span: native_span.to(semicolon_span),
+      name,
+      params,
+    }))
+  }
+
+  /// grammar: classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
   fn parse_class_decl(&mut self) -> PResult<Stmt> {
     use TokenType::*;
     let class_span = self.consume(Class, S_MUST)?.span;
@@ -131,6 +208,98 @@ impl Parser<'_> {
 
   }
   
+  /// grammar: enumDecl → "enum" IDENTIFIER "{" ( IDENTIFIER ( "," IDENTIFIER )* ","? )? "}" ;
+  fn parse_enum_decl(&mut self) -> PResult<Stmt> {
+    use TokenType::*;
+    let enum_span = self.consume(Enum, S_MUST)?.span;
+
+    let name = self.consume_ident("Expected enum name")?;
+
+    let (variants, body_span) = self.paired_spanned(
+      LeftBrace,
+      "Expected `{` before enum body",
+      "Expected `}` after enum body",
+      |this| {
+        let mut variants = Vec::new();
+        while !this.is(RightBrace) && !this.is_at_end() {
+          variants.push(this.consume_ident("Expected variant name")?);
+          if !this.take(Comma) {
+            break;
+          }
+        }
+        Ok(variants)
+      }
+    )?;
+
+    Ok(Stmt::from(stmt::EnumDecl {
+      span: enum_span.to(body_span),
+      name,
+      variants,
+    }))
+  }
+
+  /// grammar: namespaceDecl → "namespace" IDENTIFIER block ;
+  fn parse_namespace_decl(&mut self) -> PResult<Stmt> {
+    let namespace_span = self.consume(TokenType::Namespace, S_MUST)?.span;
+
+    let name = self.consume_ident("Expected namespace name")?;
+    let (body, body_span) = self.parse_block()?;
+
+    Ok(Stmt::from(stmt::Namespace {
+      span: namespace_span.to(body_span),
+      name,
+      body,
+    }))
+  }
+
+  /// grammar: importDecl → "import" STRING ";" ;
+  fn parse_import(&mut self) -> PResult<Stmt> {
+    use TokenType::*;
+    let import_span = self.consume(Import, S_MUST)?.span;
+
+    let path = match &self.current_token.kind {
+      String(path) => path.to_string(),
+      _ => return Err(self.unexpected("Expected module path after `import`", Some(String("<path>".into())))),
+    };
+    self.advance();
+
+    let semicolon_span = self.consume(Semicolon, "Expected `;` after import")?.span;
+
+    Ok(Stmt::from(stmt::Import {
+      span: import_span.to(semicolon_span),
+      id: LoxIdentId::new(),
+      path,
+    }))
+  }
+
+  /// grammar: exportDecl → "export" ( varDecl | funDecl | classDecl | enumDecl | namespaceDecl ) ;
+  fn parse_export_decl(&mut self) -> PResult<Stmt> {
+    use TokenType::*;
+    let export_span = self.consume(Export, S_MUST)?.span;
+
+    let decl = match self.current_token.kind {
+      Var => self.parse_var_decl()?,
+      Fun => self.parse_fun_decl()?,
+      Class => self.parse_class_decl()?,
+      Enum => self.parse_enum_decl()?,
+      Namespace => self.parse_namespace_decl()?,
+      _ => return Err(self.unexpected("Expected declaration after `export`", None)),
+    };
+
+    if decl.declared_name().is_none() {
+      return Err(ParseError::Error {
+        message: "Only named declarations can be exported".into(),
+        span: decl.span(),
+      });
+    }
+
+    Ok(Stmt::from(stmt::Export {
+      span: export_span.to(decl.span()),
+      decl: decl.into(),
+    }))
+  }
+
+  /// grammar: lambdaDecl → "(" parameters? ")" block ( "(" arguments? ")" )* ";" ;
   fn parse_lambda_decl(&mut self, fun: stmt::FunDecl) -> PResult<Stmt> {
     use TokenType::*;
     let start = fun.span;
@@ -153,15 +322,15 @@ impl Parser<'_> {
       .span;
 
     let span = start.to(semicolon_span);
-    return Ok(Stmt::from(stmt::Expr { span, expr }));
+    Ok(Stmt::from(stmt::Expr { span, expr }))
   }
 
+  /// grammar: function → IDENTIFIER "(" parameters? ")" block ;
   fn parse_fun_params(
     &mut self,
     kind: &'static str,
     start: Option<Span>,
   ) -> PResult<stmt::FunDecl> {
-    use TokenType::*;
     let name = match (
       kind,
       start,
@@ -173,6 +342,22 @@ impl Parser<'_> {
       (_, _, Err(err)) => Err(err)?,
     };
 
+    let params = self.parse_params(kind)?;
+    let (body, body_span) = self.parse_block()?;
+
+    Ok(stmt::FunDecl {
+      span: start.unwrap_or(name.span).to(body_span),
+      name,
+      params,
+      body: body.into(),
+    })
+  }
+
+  /// Parses the parenthesized parameter list of a function of the given `kind`.
+  ///
+  /// grammar: parameters → IDENTIFIER ( "," IDENTIFIER )* ","? ;
+  fn parse_params(&mut self, kind: &str) -> PResult<Vec<LoxIdent>> {
+    use TokenType::*;
     let (params, param_span) = self.paired_spanned(
       TokenType::LeftParen,
       format!("Expected '(' after {} name", kind),
@@ -183,7 +368,7 @@ impl Parser<'_> {
           loop {
             let param = this.consume_ident("Expected parameter name")?;
             params.push(param);
-            if !this.take(Comma) {
+            if !this.take(Comma) || this.is(RightParen) {
               break;
             }
           }
@@ -199,37 +384,37 @@ impl Parser<'_> {
         span: param_span,
       })
     }
-
-    let (body, body_span) = self.parse_block()?;
-
-    Ok(stmt::FunDecl {
-      span: start.unwrap_or(name.span).to(body_span),
-      name,
-      params,
-      body,
-    })
+    Ok(params)
   }
 
   //
   // Statements
   //
 
+  /// grammar: statement → ifStmt | switchStmt | whileStmt | forStmt | breakStmt | continueStmt | timeStmt | printStmt | returnStmt | block | exprStmt ;
   fn parse_stmt(&mut self) -> PResult<Stmt> {
     use TokenType::*;
+    if self.is_time_block() {
+      return self.parse_time_stmt();
+    }
     match self.current_token.kind {
       If => self.parse_if_stmt(),
+      Switch => self.parse_switch_stmt(),
       While => self.parse_while_stmt(),
       For => self.parse_for_stmt(),
+      Break => self.parse_break_stmt(),
+      Continue => self.parse_continue_stmt(),
       Print => self.parse_print_stmt(),
       Return => self.parse_return_stmt(),
       LeftBrace => {
         let (stmts, span) = self.parse_block()?;
-        Ok(Stmt::from(stmt::Block { span, stmts }))
+        Ok(Stmt::from(stmt::Block { span, id: LoxIdentId::new(), stmts }))
       }
       _ => self.parse_expr_stmt(),
     }
   }
 
+  /// grammar: ifStmt → "if" "(" expression ")" statement ( "else" statement )? ;
   fn parse_if_stmt(&mut self) -> PResult<Stmt> {
     let if_span = self.consume(TokenType::If, S_MUST)?.span;
     let (cond, _span) = self.paired_spanned(
@@ -256,6 +441,71 @@ impl Parser<'_> {
     }))
   }
 
+  /// grammar: switchStmt → "switch" "(" expression ")" "{" ( "case" expression ":" declaration* | "default" ":" declaration* )* "}" ;
+  fn parse_switch_stmt(&mut self) -> PResult<Stmt> {
+    use TokenType::*;
+    let switch_span = self.consume(Switch, S_MUST)?.span;
+    let subject = self.paired(
+      LeftParen,
+      "Expected `(` after `switch`",
+      "Expected `)` after switch subject",
+      |this| this.parse_expr(),
+    )?;
+
+    let ((cases, default), body_span) = self.paired_spanned(
+      LeftBrace,
+      "Expected `{` before switch body",
+      "Expected `}` after switch body",
+      |this| {
+        let mut cases = Vec::new();
+        let mut default = None;
+        while !this.is(RightBrace) && !this.is_at_end() {
+          if this.is(Default) {
+            let default_span = this.advance().span;
+            this.consume(Colon, "Expected `:` after `default`")?;
+            if default.is_some() {
+              this.diagnostics.push(ParseError::Error {
+                message: "Switch can't have more than one `default` arm".into(),
+                span: default_span,
+              });
+            }
+            default = Some(this.parse_case_body());
+            continue;
+          }
+
+          let case_span = this.consume(Case, "Expected `case` or `default` in switch body")?.span;
+          let value = this.parse_expr()?;
+          let colon_span = this.consume(Colon, "Expected `:` after case value")?.span;
+          cases.push(stmt::Case {
+            span: case_span.to(colon_span),
+            value,
+            body: this.parse_case_body(),
+          });
+        }
+        Ok((cases, default))
+      },
+    )?;
+
+    Ok(Stmt::from(stmt::Switch {
+      span: switch_span.to(body_span),
+      subject,
+      cases,
+      default,
+    }))
+  }
+
+  fn parse_case_body(&mut self) -> Vec<Stmt> {
+    use TokenType::*;
+    let mut stmts = Vec::new();
+    self.open_blocks += 1;
+    while !matches!(self.current_token.kind, Case | Default | RightBrace | EOF) {
+      stmts.push(self.parse_decl());
+    }
+    self.open_blocks -= 1;
+    stmts
+  }
+
+  /// grammar: whileStmt → "while" "(" expression ")" statement ;
   fn parse_while_stmt(&mut self) -> PResult<Stmt> {
     let while_span = self.consume(TokenType::While, S_MUST)?.span;
     let (cond, _span) = self.paired_spanned(
@@ -270,10 +520,13 @@ impl Parser<'_> {
       span: while_span.to(body.span()),
       cond,
       body: body.into(),
+      incr: None,
     }))
   }
 
   /// Desugars `for` loop syntax into other known statements
+  ///
+  /// grammar: forStmt → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" sequence? ")" statement ;
   fn parse_for_stmt(&mut self) -> PResult<Stmt> {
     use TokenType::*;
     let for_span = self.consume(For, S_MUST)?.span;
@@ -307,40 +560,28 @@ impl Parser<'_> {
 
         let incr = match this.current_token.kind {
           RightParen => None,
-          _ => Some(this.parse_expr()?),
+          _ => Some(this.parse_sequence()?),
         };
 
         Ok((init, cond, incr))
       },
     )?;
 
-    let mut body = self.parse_stmt()?;
-
-    // Desugar increment
-    if let Some(incr) = incr {
-      body = Stmt::from(stmt::Block {
-        span: body.span(),
-        stmts: vec![
-          body,
-          Stmt::from(stmt::Expr {
-            span: incr.span(),
-            expr: incr,
-          }),
-        ],
-      })
-    }
+    let body = self.parse_stmt()?;
 
-    // while
-    body = Stmt::from(stmt::While {
+    // while, which runs the increment after each iteration
+    let mut body = Stmt::from(stmt::While {
       span: for_span.to(body.span()),
       cond,
       body: body.into(),
+      incr,
     });
 
     // initializer
     if let Some(init) = init {
       body = Stmt::from(stmt::Block {
         span: body.span(),
+        id: LoxIdentId::new(),
         stmts: vec![init, body],
       })
     }
@@ -348,6 +589,7 @@ impl Parser<'_> {
     Ok(body)
   }
 
+  /// grammar: printStmt → "print" expression ";" ;
   fn parse_print_stmt(&mut self) -> PResult<Stmt> {
     let print_token_span = self.consume(TokenType::Print, S_MUST)?.span;
     let expr = self.parse_expr()?;
@@ -362,6 +604,38 @@ impl Parser<'_> {
     }))
   }
 
+  /// grammar: breakStmt → "break" ";" ;
+  fn parse_break_stmt(&mut self) -> PResult<Stmt> {
+    let break_span = self.consume(TokenType::Break, S_MUST)?.span;
+    let semicolon_span = self.consume(TokenType::Semicolon, "Expected `;` after `break`")?.span;
+    Ok(Stmt::from(stmt::Break { span: break_span.to(semicolon_span) }))
+  }
+
+  /// grammar: continueStmt → "continue" ";" ;
+  fn parse_continue_stmt(&mut self) -> PResult<Stmt> {
+    let continue_span = self.consume(TokenType::Continue, S_MUST)?.span;
+    let semicolon_span = self.consume(TokenType::Semicolon, "Expected `;` after `continue`")?.span;
+    Ok(Stmt::from(stmt::Continue { span: continue_span.to(semicolon_span) }))
+  }
+
+  /// Whether the current token starts a `time` block. `time` is only a keyword right before a
+  /// block, so programs can still use it as a name.
+  fn is_time_block(&mut self) -> bool {
+    matches!(&self.current_token.kind, TokenType::Identifier(name) if &**name == "time")
+      && self.next_is(TokenType::LeftBrace)
+  }
+
+  /// grammar: timeStmt → "time" block ;
+  fn parse_time_stmt(&mut self) -> PResult<Stmt> {
+    let time_span = self.advance().span;
+    let (stmts, span) = self.parse_block()?;
+    Ok(Stmt::from(stmt::Time {
+      span: time_span.to(span),
+      body: Stmt::from(stmt::Block { span, id: LoxIdentId::new(), stmts }).into(),
+    }))
+  }
+
+  /// grammar: returnStmt → "return" expression? ";" ;
   fn parse_return_stmt(&mut self) -> PResult<Stmt> {
     use TokenType::*;
     let return_span = self.consume(Return, S_MUST)?.span;
@@ -379,6 +653,7 @@ impl Parser<'_> {
     }))
   }
 
+  /// grammar: block → "{" declaration* "}" ;
   fn parse_block(&mut self) -> PResult<(Vec<Stmt>, Span)> {
     self.paired_spanned(
       TokenType::LeftBrace,
@@ -386,19 +661,22 @@ impl Parser<'_> {
       "Expected block to be closed",
       |this| {
         let mut stmts = Vec::new();
+        this.open_blocks += 1;
         while !this.is(TokenType::RightBrace) && !this.is_at_end() {
           stmts.push(this.parse_decl());
         }
+        this.open_blocks -= 1;
         Ok(stmts)
       },
     )
   }
 
+  /// grammar: exprStmt → sequence ";" ;
   fn parse_expr_stmt(&mut self) -> PResult<Stmt> {
-    let expr = self.parse_expr()?;
+    let expr = self.parse_sequence()?;
 
     // QOL: In repl mode, expressions that do not end with a
-    // `;` are evaluated and printed
+    // `;` are evaluated, printed and bound to `_`
     if self.options.repl_mode && self.is_at_end() {
       return Ok(Stmt::from(stmt::Print {
         span: expr.span(),
@@ -420,16 +698,21 @@ impl Parser<'_> {
   // Expressions
   //
 
+  /// grammar: expression → assignment ;
   fn parse_expr(&mut self) -> PResult<Expr> {
-    self.parse_sequence()
+    self.parse_assignment()
   }
 
+  /// Parses an expression that may be a comma sequence. Only allowed where a list could not be
+  /// meant: expression statements, `for` increments and parentheses.
+  ///
+  /// grammar: sequence → assignment ( "," assignment )* ;
   fn parse_sequence(&mut self) -> PResult<Expr> {
     let mut expr = self.parse_assignment()?;
     loop {
       if self.take(TokenType::Comma) {
         let operator = self.prev_token.clone();
-        let right = self.parse_expr()?;
+        let right = self.parse_assignment()?;
         expr = Expr::from(expr::Binary {
           span: operator.span,
           left: expr.into(),
@@ -442,6 +725,7 @@ impl Parser<'_> {
     }
   }
 
+  /// grammar: assignment → ( call "." )? IDENTIFIER "=" assignment | or ;
   fn parse_assignment(&mut self) -> PResult<Expr> {
     let left = self.parse_or()?;
 
@@ -467,10 +751,8 @@ impl Parser<'_> {
           }))
         },
         _ => {
-          Err(ParseError::Error {
-            message: "Invalid assignment target ".into(),
-            span: left.span(),
-          })
+          let err = ParseError::Error { message: "Invalid assignment target".into(), span: left.span() };
+          Err(err.with_note("only variables and properties can be assigned to"))
         }
       }
     }
@@ -478,6 +760,7 @@ impl Parser<'_> {
     Ok(left)
   }
 
+  /// grammar: or → and ( "or" and )* ;
   fn parse_or(&mut self) -> PResult<Expr> {
     bin_expr!(
       self,
@@ -487,6 +770,7 @@ impl Parser<'_> {
     )
   }
 
+  /// grammar: and → equality ( "and" equality )* ;
   fn parse_and(&mut self) -> PResult<Expr> {
     bin_expr!(
       self,
@@ -496,6 +780,7 @@ impl Parser<'_> {
     )
   }
 
+  /// grammar: equality → comparison ( ( "==" | "!=" ) comparison )* ;
   fn parse_equality(&mut self) -> PResult<Expr> {
     bin_expr!(
       self,
@@ -505,6 +790,7 @@ impl Parser<'_> {
     )
   }
 
+  /// grammar: comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
   fn parse_comparison(&mut self) -> PResult<Expr> {
     bin_expr!(
       self,
@@ -514,6 +800,7 @@ impl Parser<'_> {
     )
   }
 
+  /// grammar: term → factor ( ( "+" | "-" ) factor )* ;
   fn parse_term(&mut self) -> PResult<Expr> {
     bin_expr!(
       self,
@@ -523,6 +810,7 @@ impl Parser<'_> {
     )
   }
 
+  /// grammar: factor → unary ( ( "*" | "/" ) unary )* ;
   fn parse_factor(&mut self) -> PResult<Expr> {
     bin_expr!(
       self,
@@ -532,6 +820,7 @@ impl Parser<'_> {
     )
   }
 
+  /// grammar: unary → ( "!" | "-" ) unary | call ;
   fn parse_unary(&mut self) -> PResult<Expr> {
     use TokenType::*;
     if let Bang | Minus = self.current_token.kind {
@@ -546,6 +835,7 @@ impl Parser<'_> {
     self.parse_call()
   }
 
+  /// grammar: call → lambda ( "(" arguments? ")" | "." IDENTIFIER )* ;
   fn parse_call(&mut self) -> PResult<Expr> {
     use TokenType::*;
     let mut expr = self.parse_lambda()?;
@@ -575,6 +865,7 @@ impl Parser<'_> {
     Ok(expr)
   }
 
+  /// grammar: arguments → assignment ( "," assignment )* ","? ;
   fn finish_call(&mut self, callee: Expr) -> PResult<Expr> {
     use TokenType::*;
     let (args, call_span) =
@@ -583,7 +874,7 @@ impl Parser<'_> {
         if !this.is(RightParen) {
           loop {
             args.push(this.parse_assignment()?);
-            if !this.take(Comma) {
+            if !this.take(Comma) || this.is(RightParen) {
               break;
             }
           }
@@ -603,9 +894,11 @@ impl Parser<'_> {
       span: callee.span().to(call_span),
       callee: callee.into(),
       args,
+      line: self.line_at(call_span.0),
     }))
   }
 
+  /// grammar: lambda → "fun" "(" parameters? ")" block | primary ;
   fn parse_lambda(&mut self) -> PResult<Expr> {
     use TokenType::*;
     if self.is(TokenType::Fun) {
@@ -630,15 +923,20 @@ impl Parser<'_> {
     self.parse_primary()
   }
 
+  /// grammar: primary → NUMBER | STRING | "true" | "false" | "nil" | IDENTIFIER | "this" | "super" "." IDENTIFIER | "(" sequence ")" ;
   fn parse_primary(&mut self) -> PResult<Expr> {
     use TokenType::*;
     match &self.current_token.kind {
-      String(_) | Number(_) | True | False | Nil => {
-        let token = self.advance();
-        Ok(Expr::from(expr::Lit::from(token.clone())))
+      kind if kind.is_literal() => {
+        let token = self.advance().clone();
+        self.check_string_len(&token);
+        Ok(Expr::from(expr::Lit::from(token)))
       }
       Identifier(_) => {
         let name = self.consume_ident(S_MUST)?;
+        if let Some(value) = self.pseudo_constant(&name) {
+          return Ok(Expr::from(expr::Lit { span: name.span, value }));
+        }
         Ok(Expr::from(expr::Var {
           span: name.span,
           name,
@@ -664,13 +962,17 @@ impl Parser<'_> {
       LeftParen => {
         let (expr, span) =
           self.paired_spanned(LeftParen, S_MUST, "Expected group to be closed", |this| {
-            this.parse_expr()
+            this.parse_sequence()
           })?;
         Ok(Expr::from(expr::Group {
           span,
           expr: expr.into(),
         }))
       }
+      kind if kind.is_binary_op() => {
+        let message = format!("Expected left operand before `{}`", kind);
+        Err(self.unexpected(message, None))
+      }
       _ => Err(self.unexpected("Expected any expression", None)),
     }
   }
@@ -680,22 +982,34 @@ impl Parser<'_> {
 impl<'src> Parser<'src> {
   /// Creates a new parser.
   pub fn new(src: &'src str) -> Self {
-    let mut parser = Self {
-      scanner: Scanner::new(src),
+    Self::with(Some(src), Scanner::new(src))
+  }
+
+  /// Creates a parser that reads the source from `reader` as it goes, see
+  /// [`Scanner::from_reader`].
+  pub fn from_reader(reader: impl Read + 'src) -> Self {
+    Self::with(None, Scanner::from_reader(reader))
+  }
+
+  fn with(src: Option<&'src str>, scanner: Scanner<'src>) -> Self {
+    Self {
+      src,
+      scanner,
+      lookahead: VecDeque::new(),
       current_token: Token::dummy(),
       prev_token: Token::dummy(),
       diagnostics: Vec::new(),
+      open_blocks: 0,
+      comments: Vec::new(),
       options: ParserOptions::default(),
-    };
-    parser.advance(); // The first advancement.
-    parser
+    }
   }
 
   /// Advances the parser and returns a reference to the `prev_token` field.
   fn advance(&mut self) -> &Token {
     use TokenType::*;
     let next = loop {
-      let maybe_next = self.scanner.next().expect("Cannot advance past EOF.");
+      let maybe_next = self.lookahead.pop_front().or_else(|| self.scanner.next()).expect("Cannot advance past EOF.");
       match maybe_next.kind {
         // Report and ignore tokens with the `Error` kind:
         Error(error) => {
@@ -704,8 +1018,29 @@ impl<'src> Parser<'src> {
             span: maybe_next.span,
           });
         }
+        Comment | BlockComment => {
+          if let Some(src) = self.src {
+            let span = maybe_next.span;
+            let text = &src[span.range()];
+            // without `keep_comments`, only the `lox-ignore` comments are ever used
+            if self.options.keep_comments || trivia::ignored_names(text).is_some() {
+              self.comments.push(trivia::Comment { span, text: text.into() });
+            }
+          }
+        }
         // Handle other common ignored kinds:
-        Comment(_) | Whitespace(_) => continue,
+        kind if kind.is_trivia() => continue,
+        // Report literals and identifiers over the limits, but keep them so the rest still parses:
+        Number(n) if n.is_infinite() => {
+          let error = ScanError::NumberOutOfRange;
+          self.diagnostics.push(ParseError::ScanError { error, span: maybe_next.span });
+          break maybe_next;
+        }
+        Identifier(ref name) if name.len() > MAX_IDENTIFIER_LEN => {
+          let error = ScanError::IdentifierTooLong { len: name.len(), max: MAX_IDENTIFIER_LEN };
+          self.diagnostics.push(ParseError::ScanError { error, span: maybe_next.span });
+          break maybe_next;
+        }
         _ => break maybe_next,
       };
     };
@@ -713,6 +1048,54 @@ impl<'src> Parser<'src> {
     &self.prev_token
   }
 
+  /// Reports `token` if it is a string literal longer than the options allow. The literal is
+  /// still parsed.
+  fn check_string_len(&mut self, token: &Token) {
+    let max = self.options.max_string_len.unwrap_or(MAX_STRING_LEN);
+    if let TokenType::String(s) = &token.kind {
+      if s.len() > max {
+        let error = ScanError::StringTooLong { len: s.len(), max };
+        self.diagnostics.push(ParseError::ScanError { error, span: token.span });
+      }
+    }
+  }
+
+  /// Value that `__line__` or `__file__` at `name` expands to. `__file__` is `nil` for source that
+  /// is not read from a file.
+  fn pseudo_constant(&self, name: &LoxIdent) -> Option<LoxValue> {
+    match name.name.as_str() {
+      "__line__" => Some(LoxValue::Number(self.line_at(name.span.0) as f64)),
+      "__file__" => Some(self.options.file.as_ref().map_or(LoxValue::Nil, |file| {
+        LoxValue::String(file.display().to_string())
+      })),
+      _ => None,
+    }
+  }
+
+  /// Line of the source that byte `pos` is on, counting from 1.
+  fn line_at(&self, pos: usize) -> usize {
+    match self.src {
+      Some(src) => src[..pos].matches('\n').count() + 1,
+      None => self.scanner.line_at(pos).unwrap_or(1),
+    }
+  }
+
+  /// Checks if the token after the current one, skipping trivia and scan errors, matches the kind
+  /// of the given one. The tokens scanned are left for `advance` to report and skip.
+  fn next_is(&mut self, expected: TokenType) -> bool {
+    loop {
+      match self.lookahead.back() {
+        Some(token) if !token.kind.is_trivia() && !matches!(token.kind, TokenType::Error(_)) => {
+          return mem::discriminant(&token.kind) == mem::discriminant(&expected);
+        }
+        _ => match self.scanner.next() {
+          Some(token) => self.lookahead.push_back(token),
+          None => return false,
+        },
+      }
+    }
+  }
+
   /// Checks if the current token matches the kind of the given one.
   #[inline]
   fn is(&mut self, expected: impl Borrow<TokenType>) -> bool {
@@ -790,7 +1173,7 @@ impl<'src> Parser<'src> {
     let end_span = match self.consume(delim_start.get_pair(), delim_end_expectation) {
       Ok(token) => token.span,
       Err(error) => {
-        return Err(error);
+        return Err(error.with_label(start_span, format!("`{}` opened here", delim_start)));
       }
     };
     Ok((ret, start_span.to(end_span)))
@@ -806,20 +1189,18 @@ impl<'src> Parser<'src> {
     }
   }
 
-  /// Synchronizes parser state to the next statement boundary (generally denoted by `;`)
-  ///
-  /// TODO: Refactor token types into groups
+  /// Synchronizes parser state to the next statement boundary: just past a `;`, or at a keyword
+  /// that starts a statement.
   fn sync(&mut self) {
-    use TokenType::*;
     while !self.is_at_end() {
       match &self.current_token.kind {
-        Semicolon => {
+        TokenType::Semicolon => {
           self.advance();
           return;
         }
-        Class | For | Fun | If | Print | Return | Var | While => {
-          return;
-        }
+        // the block's loop takes it; at the top level it is skipped like any other token
+        TokenType::RightBrace if self.open_blocks > 0 => return,
+        kind if kind.is_statement_start() => return,
         _ => self.advance(),
       };
     }
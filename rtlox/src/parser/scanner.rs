@@ -1,8 +1,12 @@
 // #[cfg(test)]
 // mod tests;
 
+use std::{collections::{HashMap, HashSet}, io::Read, iter::Peekable, rc::Rc, str::CharIndices};
+
+use lox_common::directive::Directives;
+
 use crate::{
-  parser::scanner::{error::ScanError, identifier::*},
+  parser::scanner::{error::ScanError, identifier::*, stream::Stream},
   span::Span,
   token::{Token, TokenType},
   // error::{Error, LoxError, Type}
@@ -10,13 +14,28 @@ use crate::{
 
 pub mod error;
 pub mod identifier;
+mod stream;
 
 pub struct Scanner<'src> {
-  src: &'src str,
-  chars: Vec<(usize, char)>, // Start byte index and char
-  cursor: usize,
+  input: Input<'src>,
+  /// The character under the cursor, or `None` at the end.
+  current: Option<(usize, char)>,
   lex_span_start: usize,
   emitted_eof: bool,
+  /// Whether only whitespace precedes the current character on its line, so that a `#` there
+  /// starts a directive.
+  line_start: bool,
+  directives: Directives<Span>,
+  /// Text of identifiers and strings seen so far, so that repeats share an allocation.
+  names: HashSet<Rc<str>>,
+}
+
+/// Where the scanner reads characters from.
+enum Input<'src> {
+  /// The whole source, which lexemes are sliced from.
+  Str(&'src str, Peekable<CharIndices<'src>>),
+  /// A reader, whose lexemes are copied out as they are scanned.
+  Stream(Stream<'src>),
 }
 
 impl Iterator for Scanner<'_> {
@@ -27,9 +46,15 @@ impl Iterator for Scanner<'_> {
       return None;
     }
     // Ensures the next token starts with a new span.
-    self.lex_span_start = self.peek(0).0;
+    self.lex_span_start = self.pos();
+    if let Input::Stream(stream) = &mut self.input {
+      stream.lexeme.clear();
+    }
     let kind = self.scan_token();
     if kind == TokenType::EOF {
+      if let Some(span) = self.directives.close_unended() {
+        return Some(Token { kind: TokenType::Error(ScanError::UnendedIf), span });
+      }
       self.emitted_eof = true;
     }
     Some(Token {
@@ -40,17 +65,20 @@ impl Iterator for Scanner<'_> {
 }
 
 // The scanner implementation.
-impl Scanner<'_> {
+impl<'src> Scanner<'src> {
   /// Tries to scan the current character.
   fn scan_token(&mut self) -> TokenType {
     use TokenType::*;
-    match self.advance() {
-      '\0' => EOF,
+    let line_start = self.line_start;
+    let kind = match self.advance() {
+      '\0' => return EOF,
+      '#' if line_start => return self.directive(),
       '(' => LeftParen,
       ')' => RightParen,
       '{' => LeftBrace,
       '}' => RightBrace,
       ';' => Semicolon,
+      ':' => Colon,
       ',' => Comma,
       '.' => Dot,
       '!' => self.take_select('=', BangEqual, Bang),
@@ -66,6 +94,21 @@ impl Scanner<'_> {
       c if c.is_ascii_whitespace() => self.whitespace(),
       c if is_valid_identifier_start(c) => self.identifier_or_keyword(),
       unexpected => Error(ScanError::UnexpectedChar(unexpected)),
+    };
+    match self.directives.kept() {
+      true => kind,
+      false => Whitespace,
+    }
+  }
+
+  /// Scans a directive to the end of its line and applies it.
+  fn directive(&mut self) -> TokenType {
+    self.consume_until('\n');
+    let span = self.lex_span();
+    let text = Self::slice(&self.input, span, 1, 0);
+    match self.directives.apply(text, span) {
+      Ok(()) => TokenType::Whitespace,
+      Err(error) => TokenType::Error(error),
     }
   }
 
@@ -76,12 +119,12 @@ impl Scanner<'_> {
       return TokenType::Error(ScanError::UnterminatedString);
     }
     self.advance(); // The closing `"`
-    TokenType::String(self.lex(1, -1).into())
+    TokenType::String(self.intern(1, -1))
   }
 
   /// Tries to scan a comment or a slash.
   fn comment_or_slash(&mut self) -> TokenType {
-    match self.peek(0).1 {
+    match self.current() {
       '/' => self.comment(),
       '*' => self.block_comment(),
       _ => TokenType::Slash,
@@ -92,7 +135,7 @@ impl Scanner<'_> {
   fn comment(&mut self) -> TokenType {
     self.consume_until('\n');
 
-    TokenType::Comment(self.lex(2, 0).into())
+    TokenType::Comment
   }
 
   /// Tries to scan a block comment
@@ -100,21 +143,12 @@ impl Scanner<'_> {
     self.advance(); // consume first *
 
     while !self.is_at_end() {
-      match self.advance() {
-        '*' => {
-          if self.take('/') {
-            // end of block
-            break;
-          }
-        }
-        _ => continue,
+      // the closing `*/`
+      if self.advance() == '*' && self.take('/') {
+        return TokenType::BlockComment;
       }
     }
-    if self.is_at_end() {
-      return TokenType::Error(ScanError::UnterminatedComment);
-    }
-    self.advance(); // The closing `/`
-    TokenType::BlockComment(self.lex(2, 0).into())
+    TokenType::Error(ScanError::UnterminatedComment)
   }
 
   /// Tries to scan a number.
@@ -122,7 +156,7 @@ impl Scanner<'_> {
     while self.current().is_ascii_digit() {
       self.advance();
     }
-    if self.current() == '.' && self.peek(1).1.is_ascii_digit() {
+    if self.current() == '.' && self.next_char().is_ascii_digit() {
       self.advance(); // The `.` separator
       while self.current().is_ascii_digit() {
         self.advance();
@@ -139,7 +173,7 @@ impl Scanner<'_> {
     while self.current().is_ascii_whitespace() {
       self.advance();
     }
-    TokenType::Whitespace(self.lex(0, 0).into())
+    TokenType::Whitespace
   }
 
   /// Scans a keyword or an identifier.
@@ -151,7 +185,20 @@ impl Scanner<'_> {
     if name == "NaN" {
       return TokenType::Number(f64::NAN);
     }
-    TokenType::from(name)
+    TokenType::keyword(name).unwrap_or_else(|| TokenType::Identifier(self.intern(0, 0)))
+  }
+
+  /// Returns the shared copy of the lexeme slice, allocating it on first use.
+  fn intern(&mut self, lo: isize, hi: isize) -> Rc<str> {
+    let text = Self::slice(&self.input, self.lex_span(), lo, hi);
+    match self.names.get(text) {
+      Some(name) => name.clone(),
+      None => {
+        let name: Rc<str> = text.into();
+        self.names.insert(name.clone());
+        name
+      }
+    }
   }
 }
 
@@ -159,36 +206,97 @@ impl Scanner<'_> {
 impl<'src> Scanner<'src> {
   /// Creates a new scanner.
   pub fn new(src: &'src str) -> Self {
+    Self::with(Input::Str(src, src.char_indices().peekable()))
+  }
+
+  /// Creates a scanner that reads the source from `reader` a chunk at a time, for sources too
+  /// large to hold in memory at once. Tokens and spans are the same as for the whole source.
+  pub fn from_reader(reader: impl Read + 'src) -> Self {
+    Self::with(Input::Stream(Stream::new(reader)))
+  }
+
+  fn with(mut input: Input<'src>) -> Self {
     Self {
-      src,
-      chars: src.char_indices().collect(),
-      cursor: 0,
+      current: Self::read(&mut input),
+      input,
       lex_span_start: 0,
       emitted_eof: false,
+      line_start: true,
+      directives: Directives::default(),
+      names: HashSet::new(),
+    }
+  }
+
+  /// Sets the names that `#if` directives test. Must be called before the first token is
+  /// scanned.
+  pub fn set_defines(&mut self, defines: HashMap<String, String>) {
+    self.directives = Directives::new(defines);
+  }
+
+  /// Line of the byte at `pos`, for a source read from a stream, whose text is gone. `pos` must
+  /// have been scanned already.
+  pub fn line_at(&self, pos: usize) -> Option<usize> {
+    match &self.input {
+      Input::Str(..) => None,
+      Input::Stream(stream) => Some(stream.line_at(pos)),
+    }
+  }
+
+  /// Reads the next character tuple from the input.
+  #[inline]
+  fn read(input: &mut Input) -> Option<(usize, char)> {
+    match input {
+      Input::Str(_, chars) => chars.next(),
+      Input::Stream(stream) => stream.next(),
+    }
+  }
+
+  /// Byte offset of the end of the input, once it has been reached.
+  #[inline]
+  fn end(&self) -> usize {
+    match &self.input {
+      Input::Str(src, _) => src.len(),
+      Input::Stream(stream) => stream.end(),
     }
   }
 
-  /// Peeks a character tuple with the given offset from the cursor.
+  /// Byte offset of the current character.
   #[inline]
-  fn peek(&self, offset: isize) -> (usize, char) {
-    self
-      .chars
-      .get((self.cursor as isize + offset) as usize)
-      .copied()
-      .unwrap_or((self.src.len(), '\0'))
+  fn pos(&self) -> usize {
+    self.current.map_or_else(|| self.end(), |(at, _)| at)
   }
 
   /// Peeks into the current character (not yet consumed).
   #[inline]
   fn current(&self) -> char {
-    self.peek(0).1
+    self.current.map_or('\0', |(_, c)| c)
+  }
+
+  /// Peeks into the character after the current one.
+  #[inline]
+  fn next_char(&mut self) -> char {
+    let next = match &mut self.input {
+      _ if self.current.is_none() => None,
+      Input::Str(_, chars) => chars.peek().copied(),
+      Input::Stream(stream) => stream.peek(),
+    };
+    next.map_or('\0', |(_, c)| c)
   }
 
   /// Returns the current character and advances the `current` cursor.
   #[inline]
   fn advance(&mut self) -> char {
-    self.cursor += 1;
-    self.peek(-1).1
+    let Some((_, c)) = self.current.take() else { return '\0' };
+    match c {
+      '\n' => self.line_start = true,
+      c if !c.is_ascii_whitespace() => self.line_start = false,
+      _ => {}
+    }
+    if let Input::Stream(stream) = &mut self.input {
+      stream.lexeme.push(c);
+    }
+    self.current = Self::read(&mut self.input);
+    c
   }
 
   /// Checks if the current character matches the given one. In such case advances and returns
@@ -215,20 +323,33 @@ impl<'src> Scanner<'src> {
   /// Returns the current lexeme span.
   #[inline]
   fn lex_span(&self) -> Span {
-    Span::new(self.lex_span_start, self.peek(0).0)
+    Span::new(self.lex_span_start, self.pos())
   }
 
   /// Returns a lexeme slice.
   #[inline]
-  fn lex(&self, lo: isize, hi: isize) -> &'src str {
-    let span = self.lex_span().updated(lo, hi);
-    &self.src[span.0..span.1]
+  fn lex(&self, lo: isize, hi: isize) -> &str {
+    Self::slice(&self.input, self.lex_span(), lo, hi)
+  }
+
+  /// Returns the part of the lexeme at `span` from `lo` bytes after its start to `hi` bytes after
+  /// its end.
+  fn slice<'a>(input: &'a Input, span: Span, lo: isize, hi: isize) -> &'a str {
+    match input {
+      Input::Str(src, _) => {
+        let span = span.updated(lo, hi);
+        &src[span.0..span.1]
+      }
+      Input::Stream(stream) => {
+        &stream.lexeme[lo as usize..(stream.lexeme.len() as isize + hi) as usize]
+      }
+    }
   }
 
   /// Checks if the scanner has finished.
   #[inline]
   fn is_at_end(&self) -> bool {
-    self.cursor >= self.chars.len()
+    self.current.is_none()
   }
 
   /// Scans until before a matched character, or end of file
@@ -3,6 +3,8 @@ use std::{
   fmt::{self, Display},
 };
 
+use lox_common::directive::DirectiveError;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ScanError {
   UnexpectedChar(char),
@@ -11,6 +13,23 @@ pub enum ScanError {
   UnterminatedComment,
 
   InvalidNumberLiteral,
+  /// A number literal too large to represent, which reads as `inf`.
+  NumberOutOfRange,
+
+  /// A string literal longer than [`max_string_len`](crate::parser::state::ParserOptions::max_string_len)
+  /// bytes.
+  StringTooLong { len: usize, max: usize },
+  /// An identifier longer than [`MAX_IDENTIFIER_LEN`](crate::parser::scanner::identifier::MAX_IDENTIFIER_LEN) bytes.
+  IdentifierTooLong { len: usize, max: usize },
+
+  /// A second `#else` for the same `#if`.
+  ExtraElse,
+  ElseWithoutIf,
+  EndWithoutIf,
+  /// A line starting with `#` that is not `#if NAME`, `#else` or `#end`.
+  InvalidDirective,
+  /// An `#if` still open at the end of the source.
+  UnendedIf,
 }
 
 impl Display for ScanError {
@@ -21,6 +40,18 @@ impl Display for ScanError {
       UnterminatedString => f.write_str("Unterminated string"),
       UnterminatedComment => f.write_str("Unterminated block comment"),
       InvalidNumberLiteral => f.write_str("Unparseable number literal"),
+      NumberOutOfRange => f.write_str("Number literal is too large to represent"),
+      StringTooLong { len, max } => {
+        write!(f, "String literal is {len} bytes long, over the limit of {max}")
+      }
+      IdentifierTooLong { len, max } => {
+        write!(f, "Identifier is {len} bytes long, over the limit of {max}")
+      }
+      ExtraElse => f.write_str("`#if` can't have more than one `#else`"),
+      ElseWithoutIf => f.write_str("`#else` without an `#if`"),
+      EndWithoutIf => f.write_str("`#end` without an `#if`"),
+      InvalidDirective => f.write_str("Expected `#if NAME`, `#else` or `#end`"),
+      UnendedIf => f.write_str("`#if` without an `#end`"),
     }
   }
 }
@@ -28,8 +59,38 @@ impl Display for ScanError {
 impl Error for ScanError {}
 
 impl ScanError {
+  /// Code that identifies the kind of error, the same in both engines and across releases.
+  pub fn code(&self) -> &'static str {
+    use ScanError::*;
+    match self {
+      UnexpectedChar(_) => "E0001",
+      UnterminatedString => "E0002",
+      UnterminatedComment => "E0003",
+      InvalidNumberLiteral => "E0004",
+      NumberOutOfRange => "E0005",
+      StringTooLong { .. } => "E0006",
+      IdentifierTooLong { .. } => "E0007",
+      ExtraElse => "E0008",
+      ElseWithoutIf => "E0009",
+      EndWithoutIf => "E0010",
+      InvalidDirective => "E0011",
+      UnendedIf => "E0012",
+    }
+  }
+
   /// Checks if the error allows REPL continuation (aka. "..." prompt).
   pub fn allows_continuation(&self) -> bool {
-    matches!(self, ScanError::UnterminatedString)
+    matches!(self, ScanError::UnterminatedString | ScanError::UnendedIf)
+  }
+}
+
+impl From<DirectiveError> for ScanError {
+  fn from(error: DirectiveError) -> Self {
+    match error {
+      DirectiveError::ExtraElse => ScanError::ExtraElse,
+      DirectiveError::ElseWithoutIf => ScanError::ElseWithoutIf,
+      DirectiveError::EndWithoutIf => ScanError::EndWithoutIf,
+      DirectiveError::InvalidDirective => ScanError::InvalidDirective,
+    }
   }
 }
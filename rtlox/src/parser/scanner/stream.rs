@@ -0,0 +1,46 @@
+use std::{
+  io::Read,
+  ops::{Deref, DerefMut},
+};
+
+/// A [`lox_common::stream::Stream`] that remembers where its lines start, since the tree-walker's
+/// spans have no room for lines.
+pub struct Stream<'src> {
+  inner: lox_common::stream::Stream<'src>,
+  /// Offsets of the line breaks read so far.
+  newlines: Vec<usize>,
+}
+
+impl<'src> Stream<'src> {
+  pub fn new(reader: impl Read + 'src) -> Self {
+    Self { inner: lox_common::stream::Stream::new(reader), newlines: Vec::new() }
+  }
+
+  /// Returns the next character and its offset, and moves past it.
+  pub fn next(&mut self) -> Option<(usize, char)> {
+    let (at, c) = self.inner.next()?;
+    if c == '\n' {
+      self.newlines.push(at);
+    }
+    Some((at, c))
+  }
+
+  /// Line of the byte at `pos`, which must have been read already.
+  pub fn line_at(&self, pos: usize) -> usize {
+    self.newlines.partition_point(|&newline| newline < pos) + 1
+  }
+}
+
+impl<'src> Deref for Stream<'src> {
+  type Target = lox_common::stream::Stream<'src>;
+
+  fn deref(&self) -> &Self::Target {
+    &self.inner
+  }
+}
+
+impl DerefMut for Stream<'_> {
+  fn deref_mut(&mut self) -> &mut Self::Target {
+    &mut self.inner
+  }
+}
@@ -1,5 +1,18 @@
+use std::{collections::HashMap, path::PathBuf};
+
+/// Default for [`ParserOptions::max_string_len`].
+pub const MAX_STRING_LEN: usize = 1 << 20;
+
 #[derive(Debug, Default, Clone)]
 pub struct ParserOptions {
   pub repl_mode: bool,
   pub display_ast: bool,
+  /// Attach comments to the AST in `Parser::parse_with_comments`.
+  pub keep_comments: bool,
+  /// Source file being parsed, which `__file__` expands to.
+  pub file: Option<PathBuf>,
+  /// Longest string literal allowed, in bytes. Defaults to [`MAX_STRING_LEN`].
+  pub max_string_len: Option<usize>,
+  /// Names that `#if` directives test, with their values.
+  pub defines: HashMap<String, String>,
 }
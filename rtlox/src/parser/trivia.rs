@@ -0,0 +1,170 @@
+//! Comments kept alongside the AST, for tools that print source back out.
+//!
+//! Statements do not carry their comments. Instead each comment is keyed by the span of the
+//! statement it belongs to: a comment on the same line as the end of a statement trails it, and any
+//! other comment leads the next statement in the same body. Comments with neither, such as one
+//! closing a block, are dangling.
+//!
+//! A `// lox-ignore: <name>, ...` comment leading a statement silences the named warnings on the
+//! first line of that statement, see [`ignores`].
+
+use std::collections::HashMap;
+
+use crate::{ast::stmt::Stmt, span::Span};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+  pub span: Span,
+  /// Source text, including the `//` or `/* */` delimiters.
+  pub text: String,
+}
+
+/// Comments attached to a single statement, in source order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Trivia {
+  pub leading: Vec<Comment>,
+  pub trailing: Vec<Comment>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Comments {
+  attached: HashMap<Span, Trivia>,
+  pub dangling: Vec<Comment>,
+}
+
+impl Comments {
+  /// Comments of the statement with `span`, if it has any.
+  pub fn get(&self, span: Span) -> Option<&Trivia> {
+    self.attached.get(&span)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.attached.is_empty() && self.dangling.is_empty()
+  }
+}
+
+/// Attaches `comments`, scanned from `src`, to the statements of `stmts` and their bodies.
+pub fn attach(src: &str, stmts: &[Stmt], comments: Vec<Comment>) -> Comments {
+  // parents come before their children, and the sorts are stable, so ties go to the outermost
+  // statement
+  let mut by_start = Vec::new();
+  collect_spans(stmts, &mut by_start);
+  let mut by_end = by_start.clone();
+  by_start.sort_by_key(|span| span.0);
+  by_end.sort_by_key(|span| span.1);
+  let starts_between = |lo: usize, hi: usize| {
+    by_start.get(by_start.partition_point(|span| span.0 < lo)).is_some_and(|span| span.0 < hi)
+  };
+
+  let mut attached = Comments::default();
+  for comment in comments {
+    let Span(lo, hi) = comment.span;
+
+    // the statement ending last before the comment, if nothing but spaces comes in between
+    let trailed = by_end
+      .partition_point(|span| span.1 <= lo)
+      .checked_sub(1)
+      .map(|last| &by_end[by_end.partition_point(|span| span.1 < by_end[last].1)])
+      .filter(|span| !src[span.1..lo].contains('\n') && !starts_between(span.1, lo));
+    if let Some(span) = trailed {
+      attached.attached.entry(*span).or_default().trailing.push(comment);
+      continue;
+    }
+
+    let led = by_start
+      .get(by_start.partition_point(|span| span.0 < hi))
+      // a statement that ends in between closes the comment's body, e.g. the `}` of a block
+      .filter(|next| by_end.get(by_end.partition_point(|span| span.1 <= hi)).is_none_or(|span| span.1 > next.0));
+    match led {
+      Some(span) => attached.attached.entry(*span).or_default().leading.push(comment),
+      None => attached.dangling.push(comment),
+    }
+  }
+  attached
+}
+
+/// A `lox-ignore` comment, with the line it silences warnings on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ignore {
+  pub comment: Span,
+  /// The first line of the statement the comment leads, without its line break.
+  pub line: Span,
+  /// Names of the warnings to silence, as written.
+  pub names: Vec<String>,
+}
+
+/// Names listed by a `lox-ignore` comment, or `None` for any other comment.
+pub(crate) fn ignored_names(text: &str) -> Option<Vec<String>> {
+  let names = text.strip_prefix("//")?.trim_start().strip_prefix("lox-ignore:")?;
+  Some(names.split(',').map(str::trim).filter(|name| !name.is_empty()).map(Into::into).collect())
+}
+
+/// The `lox-ignore` comments among `comments`, in source order. Each silences warnings on the
+/// first line of the statement it leads, so a comment that leads none, such as one trailing a
+/// statement, silences nothing and is left out.
+pub fn ignores(src: &str, stmts: &[Stmt], comments: Vec<Comment>) -> Vec<Ignore> {
+  // only these are attached, which keeps this cheap for sources with many other comments
+  let comments: Vec<_> = comments
+    .into_iter()
+    .filter(|comment| ignored_names(&comment.text).is_some())
+    .collect();
+  if comments.is_empty() {
+    return Vec::new();
+  }
+
+  let mut ignores: Vec<_> = attach(src, stmts, comments)
+    .attached
+    .into_iter()
+    .flat_map(|(span, trivia)| {
+      let end = src[span.0..].find('\n').map_or(src.len(), |i| span.0 + i);
+      trivia.leading.into_iter().map(move |comment| Ignore {
+        comment: comment.span,
+        line: Span(span.0, end),
+        names: ignored_names(&comment.text).unwrap_or_default(),
+      })
+    })
+    .collect();
+  ignores.sort_by_key(|ignore| ignore.comment.0);
+  ignores
+}
+
+fn collect_spans(stmts: &[Stmt], spans: &mut Vec<Span>) {
+  for stmt in stmts {
+    collect_stmt(stmt, spans);
+  }
+}
+
+fn collect_stmt(stmt: &Stmt, spans: &mut Vec<Span>) {
+  use Stmt::*;
+  spans.push(stmt.span());
+  match stmt {
+    FunDecl(fun) => collect_spans(&fun.body, spans),
+    ClassDecl(class) => {
+      for method in &class.methods {
+        spans.push(method.span);
+        collect_spans(&method.body, spans);
+      }
+    }
+    Namespace(ns) => collect_spans(&ns.body, spans),
+    Export(export) => collect_stmt(&export.decl, spans),
+    If(if_stmt) => {
+      collect_stmt(&if_stmt.then_branch, spans);
+      if let Some(else_branch) = &if_stmt.else_branch {
+        collect_stmt(else_branch, spans);
+      }
+    }
+    Switch(switch) => {
+      for case in &switch.cases {
+        collect_spans(&case.body, spans);
+      }
+      if let Some(default) = &switch.default {
+        collect_spans(default, spans);
+      }
+    }
+    While(while_stmt) => collect_stmt(&while_stmt.body, spans),
+    Time(time) => collect_stmt(&time.body, spans),
+    Block(block) => collect_spans(&block.stmts, spans),
+    VarDecl(_) | NativeDecl(_) | EnumDecl(_) | Import(_) | Print(_) | Return(_) | Break(_) | Continue(_)
+    | Expr(_) | Dummy(_) => {}
+  }
+}
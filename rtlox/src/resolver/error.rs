@@ -1,15 +1,76 @@
 
-use crate::span::Span;
+use crate::span::{Label, Span};
 
 #[derive(Debug)]
 pub enum ErrorType {
   Error,
-  Warning
+  Warning,
+  /// A warning that a `lox-ignore` comment silenced, kept for `--list-suppressed`.
+  Suppressed,
 }
 
+/// Kinds of warning, by the name a `// lox-ignore: <name>` comment silences them with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+  UnusedVariable,
+  NoEffect,
+  InitReturn,
+  ConstructorArity,
+  UnknownVariant,
+  DuplicateCase,
+  NonExhaustiveSwitch,
+  AssignmentCondition,
+  ShadowedNative,
+}
+
+impl Lint {
+  pub const ALL: [Lint; 9] = [
+    Lint::UnusedVariable,
+    Lint::NoEffect,
+    Lint::InitReturn,
+    Lint::ConstructorArity,
+    Lint::UnknownVariant,
+    Lint::DuplicateCase,
+    Lint::NonExhaustiveSwitch,
+    Lint::AssignmentCondition,
+    Lint::ShadowedNative,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Lint::UnusedVariable => "unused-variable",
+      Lint::NoEffect => "no-effect",
+      Lint::InitReturn => "init-return",
+      Lint::ConstructorArity => "constructor-arity",
+      Lint::UnknownVariant => "unknown-variant",
+      Lint::DuplicateCase => "duplicate-case",
+      Lint::NonExhaustiveSwitch => "non-exhaustive-switch",
+      Lint::AssignmentCondition => "assignment-condition",
+      Lint::ShadowedNative => "shadowed-native",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|lint| lint.name() == name)
+  }
+
+  /// Whether `name` is a warning of either engine, so that a script run on both can silence the
+  /// VM's warnings without this one complaining.
+  pub fn is_known(name: &str) -> bool {
+    Self::from_name(name).is_some() || VM_ONLY.contains(&name)
+  }
+}
+
+/// Warnings that only the VM's compiler raises.
+const VM_ONLY: [&str; 1] = ["redeclared-variable"];
+
 #[derive(Debug)]
 pub struct ResolveError {
   pub kind: ErrorType,
+  /// Which warning this is, for those that can be silenced.
+  pub lint: Option<Lint>,
   pub message: String,
   pub span: Span,
-}
\ No newline at end of file
+  /// Other spans that explain the error.
+  pub labels: Vec<Label>,
+}
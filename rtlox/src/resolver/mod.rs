@@ -1,5 +1,5 @@
 use std::{
-  collections::{hash_map::Entry, HashMap}, mem
+  cell::RefCell, collections::{hash_map::Entry, HashMap, HashSet}, env, fs, mem, path::{Path, PathBuf}, rc::Rc
 };
 
 use crate::{
@@ -8,25 +8,75 @@ use crate::{
     stmt::{self, Stmt},
   },
   data::LoxIdent,
-  interpreter::Interpreter,
-  resolver::error::{ErrorType, ResolveError},
-  span::Span,
+  interpreter::{module::LoxModule, Interpreter},
+  parser::{trivia::Ignore, Parser},
+  resolver::{
+    error::{ErrorType, Lint, ResolveError},
+    table::ResolutionTable,
+  },
+  span::{Label, Span},
+  symbol::Symbol,
 };
 
 pub mod error;
+pub mod table;
 
 #[derive(Debug)]
 pub struct Resolver<'i> {
   interpreter: &'i mut Interpreter,
+  /// What has been found out so far, see [`Resolver::resolve_to_table`].
+  table: ResolutionTable,
   state: ResolverState,
-  scopes: Vec<HashMap<String, BindingState>>,
+  scopes: Vec<Scope>,
+  /// Locals declared without an initializer that nothing has assigned to yet, with the index of
+  /// their scope.
+  unassigned: Vec<(Symbol, usize)>,
+  /// Variants of the enums declared at the top level. Enums of inner scopes are kept with the
+  /// scope, see [`Resolver::enum_variants`].
+  enums: HashMap<Symbol, Vec<Symbol>>,
+  /// Classes declared in this file whose constructor arity is known, with the index of the scope
+  /// declaring them (`None` for globals).
+  classes: HashMap<Symbol, (Option<usize>, usize)>,
+  /// Arity of each native declared at the top level of this file.
+  natives: HashMap<Symbol, usize>,
+  /// Native declarations that the host registered no native for.
+  unbound_natives: Vec<LoxIdent>,
+  /// Globals declared so far in this file.
+  globals: HashSet<Symbol>,
+  /// Globals used inside functions, which may be declared after the function.
+  deferred_globals: Vec<LoxIdent>,
+  /// Scope depth of top-level declarations: 0 for scripts, 1 for imported modules.
+  top_level: usize,
+  /// Directory that relative imports are resolved against.
+  dir: PathBuf,
+  /// Modules currently being loaded, used to reject import cycles.
+  loading: Vec<PathBuf>,
+  /// `lox-ignore` comments of the source, see [`Resolver::set_ignores`].
+  ignores: Vec<Ignore>,
   errors: Vec<ResolveError>,
 }
 
 impl Resolver<'_> {
+  /// Resolves `stmts` and hands what was found to the interpreter, which can then run them.
   pub fn resolve(mut self, stmts: &[Stmt]) -> (bool, Vec<ResolveError>) {
+    self.resolve_program(stmts);
+    self.interpreter.add_resolution(mem::take(&mut self.table));
+    let ok = self.errors.iter().all(|err| matches!(err.kind, ErrorType::Suppressed));
+    (ok, self.errors)
+  }
+
+  /// Resolves `stmts` without handing the result to the interpreter, for tools that check or
+  /// analyse a program without running it. The interpreter is only read, for the natives and
+  /// globals it defines, apart from caching the modules imported.
+  pub fn resolve_to_table(mut self, stmts: &[Stmt]) -> (ResolutionTable, Vec<ResolveError>) {
+    self.resolve_program(stmts);
+    (self.table, self.errors)
+  }
+
+  fn resolve_program(&mut self, stmts: &[Stmt]) {
     self.resolve_stmts(stmts);
-    (self.errors.is_empty(), self.errors)
+    self.check_deferred_globals();
+    self.check_unbound_natives();
   }
 
   fn resolve_stmts(&mut self, stmts: &[Stmt]) {
@@ -44,6 +94,9 @@ impl Resolver<'_> {
           self.resolve_expr(init);
         }
         self.define(&var.name);
+        if var.init.is_none() && !self.scopes.is_empty() {
+          self.unassigned.push((var.name.name, self.scopes.len() - 1));
+        }
       }
       FunDecl(fun) => {
         self.declare(&fun.name);
@@ -51,7 +104,30 @@ impl Resolver<'_> {
 
         self.resolve_fun(fun, FunctionState::Function);
       }
+      NativeDecl(native) => self.resolve_native(native),
       ClassDecl(class) => self.resolve_class(class),
+      EnumDecl(decl) => self.resolve_enum(decl),
+      Namespace(ns) => {
+        self.declare(&ns.name);
+        self.define(&ns.name);
+
+        // Members are exported through the namespace, so they are never reported as unused.
+        self.begin_scope();
+        self.resolve_stmts(&ns.body);
+        self.end_scope();
+      }
+      Import(import) => {
+        if self.scopes.len() != self.top_level {
+          self.error(ErrorType::Error, import.span, "Imports are only allowed at the top level");
+        }
+        self.resolve_import(import);
+      }
+      Export(export) => {
+        if self.scopes.len() != self.top_level {
+          self.error(ErrorType::Error, export.span, "Exports are only allowed at the top level");
+        }
+        self.resolve_stmt(&export.decl);
+      }
       Return(stmt) => {
         match (self.state.function, &stmt.value) {
           (FunctionState::None, _) => {
@@ -59,8 +135,8 @@ impl Resolver<'_> {
           }
           (FunctionState::Init, Some(expr::Expr::This(_))) => {},
           (FunctionState::Init, Some(_)) => {
-            self.error(
-              ErrorType::Warning, stmt.return_span, 
+            self.warn(
+              Lint::InitReturn, stmt.return_span, 
               "Initializer returns a value that is not `this`"
             );
           }
@@ -72,20 +148,62 @@ impl Resolver<'_> {
         }
       }
       If(if_stmt) => {
+        self.warn_assignment_condition(&if_stmt.cond);
         self.resolve_expr(&if_stmt.cond);
         self.resolve_stmt(&if_stmt.then_branch);
         if let Some(br) = &if_stmt.else_branch {
           self.resolve_stmt(br);
         };
       }
+      Switch(switch) => {
+        self.resolve_expr(&switch.subject);
+        for case in &switch.cases {
+          self.resolve_expr(&case.value);
+          self.scoped(|this| this.resolve_stmts(&case.body));
+        }
+        if let Some(body) = &switch.default {
+          self.scoped(|this| this.resolve_stmts(body));
+        }
+        self.check_exhaustive(switch);
+      }
       While(while_stmt) => {
+        let old_loop_scope = mem::replace(&mut self.state.loop_scope, self.scopes.len());
+        let old_in_loop = mem::replace(&mut self.state.in_loop, true);
+        self.warn_assignment_condition(&while_stmt.cond);
         self.resolve_expr(&while_stmt.cond);
         self.resolve_stmt(&while_stmt.body);
+        if let Some(incr) = &while_stmt.incr {
+          self.resolve_expr(incr);
+        }
+        self.state.loop_scope = old_loop_scope;
+        self.state.in_loop = old_in_loop;
+      }
+      Break(stmt) if !self.state.in_loop => {
+        self.error(ErrorType::Error, stmt.span, "Can't use `break` outside of a loop");
+      }
+      Continue(stmt) if !self.state.in_loop => {
+        self.error(ErrorType::Error, stmt.span, "Can't use `continue` outside of a loop");
+      }
+      Break(_) | Continue(_) => {}
+      Time(time) => self.resolve_stmt(&time.body),
+      Block(block) if !declares_any(&block.stmts) => {
+        // nothing to scope, so the block runs in the enclosing scope
+        self.table.flatten_block(block.id);
+        self.resolve_stmts(&block.stmts);
       }
       Block(block) => self.scoped(|this| this.resolve_stmts(&block.stmts)),
-      Expr(expr) => self.resolve_expr(&expr.expr),
+      Expr(expr) => {
+        if !has_effects(&expr.expr) {
+          self.warn(
+            Lint::NoEffect, expr.span,
+            "Expression statement has no effect; did you mean to `print` or assign it?"
+          );
+        }
+        self.resolve_expr(&expr.expr);
+      }
       Print(print) => self.resolve_expr(&print.expr),
-      Dummy(_) => unreachable!()
+      // left for the interpreter to reject
+      Dummy(_) => {}
     };
   }
 
@@ -94,6 +212,7 @@ impl Resolver<'_> {
 
     self.declare(&class.name);
     self.define(&class.name);
+    self.record_class(class);
 
     if let Some(super_name) = &class.super_name {
       if class.name.name == super_name.name {
@@ -121,7 +240,7 @@ impl Resolver<'_> {
         } else {
           FunctionState::Method
         };
-        this.resolve_fun(&method, state);
+        this.resolve_fun(method, state);
       }
     });
 
@@ -132,6 +251,277 @@ impl Resolver<'_> {
     self.state.class = old_class_state;
   }
 
+  /// Checks a native declaration against the native the host registered under its name.
+  fn resolve_native(&mut self, decl: &stmt::NativeDecl) {
+    if self.scopes.len() != self.top_level {
+      self.error(ErrorType::Error, decl.span, "Natives can only be declared at the top level");
+    }
+    self.declare(&decl.name);
+    self.define(&decl.name);
+
+    let declared = decl.params.len();
+    match self.interpreter.registered.get(&decl.name.name) {
+      None => self.unbound_natives.push(decl.name.clone()),
+      Some(native) if native.arity != declared => self.error(
+        ErrorType::Error, decl.span,
+        format!(
+          "Native `{}` takes {} arguments, but is declared with {}",
+          decl.name, native.arity, declared
+        )
+      ),
+      Some(_) => {}
+    }
+    self.natives.insert(decl.name.name, declared);
+  }
+
+  /// Reports every native declaration the host did not register a native for, in one error
+  /// before anything runs.
+  fn check_unbound_natives(&mut self) {
+    let Some(first) = self.unbound_natives.first() else { return };
+    let span = first.span;
+    let names: Vec<String> = self.unbound_natives.iter().map(|name| format!("`{name}`")).collect();
+    self.error(
+      ErrorType::Error, span,
+      format!("Declared natives are not registered: {}", names.join(", "))
+    );
+  }
+
+  /// Rejects calls to a declared native with the wrong number of arguments.
+  fn check_native_arity(&mut self, call: &expr::Call) {
+    let Expr::Var(var) = call.callee.as_ref() else { return };
+    let Some(&arity) = self.natives.get(&var.name.name) else { return };
+    // a local of the same name hides the native
+    if self.scope_of(&var.name) != self.top_level.checked_sub(1) {
+      return;
+    }
+    if arity != call.args.len() {
+      self.error(
+        ErrorType::Error, call.span,
+        format!("Native `{}` expects {} arguments, but got {}", var.name, arity, call.args.len())
+      );
+    }
+  }
+
+  /// Checks that `class` declares `init` at most once and remembers how many arguments its
+  /// constructor takes, when that is known here.
+  fn record_class(&mut self, class: &stmt::ClassDecl) {
+    let mut inits = class.methods.iter().filter(|method| method.name.name == "init");
+    let init = inits.next();
+    for duplicate in inits {
+      self.error(
+        ErrorType::Error, duplicate.name.span,
+        format!("Class `{}` declares `init` more than once", class.name)
+      );
+    }
+
+    let arity = match (init, &class.super_name) {
+      (Some(init), _) => Some(init.params.len()),
+      (None, None) => Some(0),
+      (None, Some(super_name)) => self.class_arity(super_name),
+    };
+    if let Some(arity) = arity {
+      let scope = self.scopes.len().checked_sub(1);
+      self.classes.insert(class.name.name, (scope, arity));
+    }
+  }
+
+  /// Constructor arity of the class `ident` refers to here, if it is one recorded in this file.
+  fn class_arity(&self, ident: &LoxIdent) -> Option<usize> {
+    let &(scope, arity) = self.classes.get(&ident.name)?;
+    // a closer declaration of the same name hides the class
+    (self.scope_of(ident) == scope).then_some(arity)
+  }
+
+  /// Warns about constructing a class from this file with the wrong number of arguments, which
+  /// fails when the call runs.
+  fn check_constructor_arity(&mut self, call: &expr::Call) {
+    let Expr::Var(var) = call.callee.as_ref() else { return };
+    let Some(arity) = self.class_arity(&var.name) else { return };
+    if arity != call.args.len() {
+      self.warn(
+        Lint::ConstructorArity, call.span,
+        format!(
+          "Constructing `{}` expects {} arguments, but got {}",
+          var.name, arity, call.args.len()
+        )
+      );
+    }
+  }
+
+  fn resolve_import(&mut self, import: &stmt::Import) {
+    let Some(path) = self.interpreter.loader.find(&import.path, &self.dir) else {
+      self.error(
+        ErrorType::Error, import.span,
+        format!("Cannot find module `{}`", import.path)
+      );
+      return;
+    };
+    if self.loading.contains(&path) {
+      self.error(
+        ErrorType::Error, import.span,
+        format!("Cyclic import of `{}`", import.path)
+      );
+      return;
+    }
+
+    let module = match self.interpreter.loader.get(&path) {
+      Some(module) => module,
+      None => match self.load_module(import, path) {
+        Some(module) => module,
+        None => return,
+      }
+    };
+
+    for name in &module.exports {
+      self.declare(name);
+      self.define(name);
+    }
+    self.table.add_module(import.id, module);
+  }
+
+  /// Parses and resolves the module at `path`, caching it in the interpreter's loader.
+  fn load_module(&mut self, import: &stmt::Import, path: PathBuf) -> Option<Rc<LoxModule>> {
+    let src = match fs::read_to_string(&path) {
+      Ok(src) => src,
+      Err(err) => {
+        self.error(
+          ErrorType::Error, import.span,
+          format!("Cannot import `{}`: {}", import.path, err)
+        );
+        return None;
+      }
+    };
+
+    let mut parser = Parser::new(&src);
+    parser.options.file = Some(path.clone());
+    parser.options.defines = self.interpreter.loader.defines.clone();
+    let ((stmts, parse_errors), ignores) = parser.parse_with_ignores();
+    if !parse_errors.is_empty() {
+      for err in parse_errors {
+        self.error(ErrorType::Error, import.span, format!("In module `{}`: {}", import.path, err));
+      }
+      return None;
+    }
+
+    // Module top-levels live in their own scope, so that only exports leak out.
+    let mut resolver = Resolver::new(self.interpreter);
+    resolver.top_level = 1;
+    resolver.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    resolver.loading = self.loading.clone();
+    resolver.loading.push(path.clone());
+    resolver.set_ignores(ignores);
+    resolver.begin_scope();
+    resolver.resolve_stmts(&stmts);
+    resolver.end_scope();
+    resolver.check_deferred_globals();
+    resolver.check_unbound_natives();
+    self.table.extend(resolver.table);
+    let mut failed = false;
+    for err in resolver.errors {
+      failed |= matches!(err.kind, ErrorType::Error);
+      // the spans and labels of the error point into the module, not the importing source
+      self.errors.push(ResolveError {
+        message: format!("In module `{}`: {}; at position {}", import.path, err.message, err.span),
+        span: import.span,
+        labels: Vec::new(),
+        ..err
+      });
+    }
+    if failed {
+      return None;
+    }
+
+    let exports: Vec<LoxIdent> = stmts.iter()
+      .filter(|stmt| matches!(stmt, Stmt::Export(_)))
+      .filter_map(|stmt| stmt.declared_name().cloned())
+      .collect();
+
+    let module = Rc::new(LoxModule { path, stmts, exports, env: RefCell::new(None) });
+    self.interpreter.loader.insert(module.clone());
+    Some(module)
+  }
+
+  fn resolve_enum(&mut self, decl: &stmt::EnumDecl) {
+    self.declare(&decl.name);
+    self.define(&decl.name);
+
+    let mut variants: Vec<Symbol> = Vec::new();
+    for variant in &decl.variants {
+      if variants.contains(&variant.name) {
+        self.error(
+          ErrorType::Error, variant.span,
+          format!("Duplicate variant `{}` in enum `{}`", variant.name, decl.name)
+        );
+        continue;
+      }
+      variants.push(variant.name);
+    }
+    match self.scopes.last_mut() {
+      Some(scope) => scope.enums.insert(decl.name.name, variants),
+      None => self.enums.insert(decl.name.name, variants),
+    };
+  }
+
+  /// Variants of the enum that `name` refers to here, unless it names something else.
+  fn enum_variants(&self, name: Symbol) -> Option<&Vec<Symbol>> {
+    for scope in self.scopes.iter().rev() {
+      if let Some(variants) = scope.enums.get(&name) {
+        return Some(variants);
+      }
+      if scope.bindings.contains_key(&name) {
+        return None;
+      }
+    }
+    self.enums.get(&name)
+  }
+
+  /// Warns when a `switch` without a `default` arm matches on variants of a single enum,
+  /// but does not cover all of them.
+  fn check_exhaustive(&mut self, switch: &stmt::Switch) {
+    let mut enum_name: Option<Symbol> = None;
+    let mut covered: Vec<Symbol> = Vec::new();
+    for case in &switch.cases {
+      let Expr::Get(get) = &case.value else { return };
+      let Expr::Var(var) = get.obj.as_ref() else { return };
+      let Some(variants) = self.enum_variants(var.name.name) else { return };
+      match enum_name {
+        Some(name) if name != var.name.name => return,
+        _ => enum_name = Some(var.name.name),
+      }
+
+      let variant = get.name.name;
+      if !variants.contains(&variant) {
+        self.warn(
+          Lint::UnknownVariant, get.name.span,
+          format!("Enum `{}` has no variant `{}`", var.name, variant)
+        );
+      } else if covered.contains(&variant) {
+        self.warn(
+          Lint::DuplicateCase, case.span,
+          format!("Duplicate case `{}.{}`", var.name, variant)
+        );
+      } else {
+        covered.push(variant);
+      }
+    }
+
+    let Some(name) = enum_name else { return };
+    if switch.default.is_some() {
+      return;
+    }
+    let Some(variants) = self.enum_variants(name) else { return };
+    let missing: Vec<String> = variants.iter()
+      .filter(|v| !covered.contains(v))
+      .map(|v| format!("`{}`", v))
+      .collect();
+    if !missing.is_empty() {
+      self.warn(
+        Lint::NonExhaustiveSwitch, switch.span,
+        format!("Non-exhaustive switch over enum `{}`: missing {}", name, missing.join(", "))
+      );
+    }
+  }
+
   fn resolve_expr(&mut self, expr: &Expr) {
     use Expr::*;
     match &expr {
@@ -146,7 +536,13 @@ impl Resolver<'_> {
               var.name
             ),
           )
-        };
+        } else if self.is_unassigned(&var.name) {
+          self.error(
+            ErrorType::Error,
+            var.name.span,
+            format!("Cannot read local variable `{}` before it is assigned", var.name),
+          )
+        }
         self.resolve_binding(&var.name);
       }
       Call(call) => {
@@ -154,6 +550,8 @@ impl Resolver<'_> {
         for arg in &call.args {
           self.resolve_expr(arg);
         }
+        self.check_constructor_arity(call);
+        self.check_native_arity(call);
       },
       Get(get) => {
         self.resolve_expr(&get.obj);
@@ -190,24 +588,36 @@ impl Resolver<'_> {
       }
       Assignment(assign) => {
         self.resolve_expr(&assign.value);
+        if let Some(idx) = self.scope_of(&assign.name) {
+          self.unassigned.retain(|(name, scope)| !(*scope == idx && *name == assign.name.name));
+        }
         self.resolve_binding(&assign.name);
       },
-      Binary(binary) => {
-        self.resolve_expr(&binary.left);
-        self.resolve_expr(&binary.right);
-      },
-      Logical(logical) => {
-        self.resolve_expr(&logical.left);
-        self.resolve_expr(&logical.right);
+      Binary(_) | Logical(_) => {
+        // walk the left-nested operands of a long chain with a loop instead of recursing
+        let mut rights = Vec::new();
+        let mut left = expr;
+        loop {
+          match left {
+            Binary(binary) => {
+              rights.push(&binary.right);
+              left = &binary.left;
+            },
+            Logical(logical) => {
+              rights.push(&logical.right);
+              left = &logical.left;
+            },
+            _ => break,
+          }
+        }
+        self.resolve_expr(left);
+        for right in rights.into_iter().rev() {
+          self.resolve_expr(right);
+        }
       },
       Unary(unary) => self.resolve_expr(&unary.operand),
       Group(group) => self.resolve_expr(&group.expr),
-      Lambda(lambda) => {
-        self.declare(&lambda.decl.name);
-        self.define(&lambda.decl.name);
-
-        self.resolve_fun(&lambda.decl, FunctionState::Function);
-      }
+      Lambda(lambda) => self.resolve_fun(&lambda.decl, FunctionState::Function),
       // _ => {}
     }
   }
@@ -217,55 +627,106 @@ impl<'i> Resolver<'i> {
   pub fn new(interpreter: &'i mut Interpreter) -> Self {
     Self {
       interpreter,
+      table: ResolutionTable::new(),
       state: ResolverState::default(),
       scopes: Vec::new(),
+      unassigned: Vec::new(),
+      enums: HashMap::new(),
+      classes: HashMap::new(),
+      natives: HashMap::new(),
+      unbound_natives: Vec::new(),
+      globals: HashSet::new(),
+      deferred_globals: Vec::new(),
+      top_level: 0,
+      dir: env::current_dir().unwrap_or_default(),
+      loading: Vec::new(),
+      ignores: Vec::new(),
       errors: Vec::new(),
     }
   }
 
+  /// Marks `file` as the source being resolved, so that its imports are resolved relative to it.
+  pub fn set_file(&mut self, file: impl AsRef<Path>) {
+    let path = file.as_ref().canonicalize().unwrap_or_else(|_| file.as_ref().into());
+    if let Some(dir) = path.parent() {
+      self.dir = dir.into();
+    }
+    self.loading = vec![path];
+  }
+
+  /// Silences the warnings that the `lox-ignore` comments of the source name, on the lines they
+  /// lead. Those warnings are still returned, as [`ErrorType::Suppressed`]. Names that are not
+  /// warnings are warned about.
+  pub fn set_ignores(&mut self, ignores: Vec<Ignore>) {
+    for ignore in &ignores {
+      for name in ignore.names.iter().filter(|name| !Lint::is_known(name)) {
+        self.error(
+          ErrorType::Warning, ignore.comment,
+          format!("Unknown warning `{name}` in `lox-ignore` comment")
+        );
+      }
+    }
+    self.ignores = ignores;
+  }
+
   fn declare(&mut self, ident: &LoxIdent) {
+    self.warn_native_shadow(ident);
     if self.scopes.is_empty() {
+      // globals are not tracked, so a redefinition replaces any class of that name
+      self.classes.remove(&ident.name);
+      self.natives.remove(&ident.name);
+      self.globals.insert(ident.name);
       return;
     }
     let Some(scope) = self.scopes.last_mut() else {
       unreachable!();
     };
 
-    match scope.entry(ident.name.clone()) {
+    match scope.bindings.entry(ident.name) {
       Entry::Vacant(entry) => {
-        entry.insert(BindingState::Declared(ident.span));
+        let state = BindingState::Declared(ident.span);
+        entry.insert(Binding { state, slot: None, span: Some(ident.span) });
       }
-      Entry::Occupied(_) => {
-        self.error(
-          ErrorType::Error,
-          ident.span,
-          format!("Cannot shadow `{}` in the same scope", ident.name),
-        );
+      Entry::Occupied(entry) => {
+        let labels = entry.get().span.map(|span| Label::new(span, "previous declaration here"));
+        self.errors.push(ResolveError {
+          kind: ErrorType::Error,
+          lint: None,
+          message: format!("Cannot shadow `{}` in the same scope", ident.name),
+          span: ident.span,
+          labels: labels.into_iter().collect(),
+        });
       }
     };
   }
 
-  fn define(&mut self, ident: &LoxIdent) {
-    if self.scopes.is_empty() {
-      return;
-    }
-    let Some(scope) = self.scopes.last_mut() else {
-      unreachable!();
+  /// Warns when the condition of an `if` or a loop is an assignment, which is more likely a mistyped
+  /// `==`. An assignment wrapped in parentheses is taken to be meant.
+  fn warn_assignment_condition(&mut self, cond: &Expr) {
+    let span = match cond {
+      Expr::Assignment(assign) => assign.span,
+      Expr::Set(set) => set.span,
+      _ => return,
     };
+    self.warn(
+      Lint::AssignmentCondition, span,
+      "Assignment used as a condition; did you mean `==`? Wrap it in parentheses if not"
+    );
+  }
 
-    match scope.get_mut(&ident.name) {
-      Some(binding) => *binding = BindingState::Initialized(ident.span),
-      None => {
-        self.error(
-          ErrorType::Error,
-          ident.span,
-          format!("Binding `{}` is not defined", ident.name),
-        );
-      }
+  /// Warns that declaring `ident` hides the native of that name, which calls would otherwise reach.
+  fn warn_native_shadow(&mut self, ident: &LoxIdent) {
+    let Some(&arity) = self.interpreter.natives.get(&ident.name) else {
+      return;
     };
+    let params = vec!["_"; arity].join(", ");
+    self.warn(
+      Lint::ShadowedNative, ident.span,
+      format!("`{0}` shadows the native function `{0}({params})`", ident.name)
+    );
   }
 
-  fn access(&mut self, ident: &LoxIdent) {
+  fn define(&mut self, ident: &LoxIdent) {
     if self.scopes.is_empty() {
       return;
     }
@@ -273,8 +734,15 @@ impl<'i> Resolver<'i> {
       unreachable!();
     };
 
-    match scope.get_mut(&ident.name) {
-      Some(binding) => *binding = BindingState::Accessed,
+    match scope.bindings.get_mut(&ident.name) {
+      Some(binding) => {
+        binding.state = BindingState::Initialized(ident.span);
+        // the interpreter adds locals to a scope as their definitions run
+        if binding.slot.is_none() {
+          binding.slot = Some(scope.defined);
+          scope.defined += 1;
+        }
+      }
       None => {
         self.error(
           ErrorType::Error,
@@ -285,35 +753,89 @@ impl<'i> Resolver<'i> {
     };
   }
 
-  fn initialize(&mut self, ident: impl Into<String>) {
-    self
-      .scopes
-      .last_mut()
-      .unwrap()
-      .insert(ident.into(), BindingState::Accessed);
+  /// Defines a binding the interpreter adds itself, such as `this`, as already used.
+  fn initialize(&mut self, ident: impl Into<Symbol>) {
+    let scope = self.scopes.last_mut().unwrap();
+    let binding = Binding { state: BindingState::Accessed, slot: Some(scope.defined), span: None };
+    scope.bindings.insert(ident.into(), binding);
+    scope.defined += 1;
   }
 
   fn query(&mut self, ident: &LoxIdent, expected: BindingState) -> bool {
-    self.scopes.last().and_then(|scope| scope.get(&ident.name)) == Some(&expected)
+    self.scopes.last().and_then(|scope| scope.bindings.get(&ident.name))
+      .is_some_and(|binding| binding.state == expected)
+  }
+
+  /// Index of the innermost scope declaring `ident`, if it is a local.
+  fn scope_of(&self, ident: &LoxIdent) -> Option<usize> {
+    self.scopes.iter().rposition(|scope| scope.bindings.contains_key(&ident.name))
+  }
+
+  /// Whether reading `ident` here would certainly find it unassigned: it was declared without an
+  /// initializer in the running function, nothing assigned to it before this point, and no loop
+  /// started since its declaration could have assigned it on an earlier iteration.
+  fn is_unassigned(&self, ident: &LoxIdent) -> bool {
+    let Some(idx) = self.scope_of(ident) else {
+      return false;
+    };
+    idx >= self.state.function_scope
+      && idx >= self.state.loop_scope
+      && self.unassigned.iter().any(|(name, scope)| *scope == idx && *name == ident.name)
   }
 
   fn resolve_binding(&mut self, ident: &LoxIdent) {
-    let mut accessed = false;
-    for (depth, scope) in self.scopes.iter_mut().rev().enumerate() {
-      if scope.contains_key(&ident.name) {
-        if depth == 0 { accessed = true; }
-        self.interpreter.resolve_local(ident, depth);
-      }
+    let Some(idx) = self.scope_of(ident) else {
+      // not a local, so left for the globals
+      self.check_global(ident);
+      return;
+    };
+    let binding = self.scopes[idx].bindings.get_mut(&ident.name).unwrap();
+    binding.state = BindingState::Accessed;
+    // a binding read in its own initializer has no slot yet, which is already an error
+    if let Some(slot) = binding.slot {
+      self.table.resolve_local(ident, self.scopes.len() - 1 - idx, slot);
+    }
+  }
+
+  /// Under `--strict-globals`, rejects a global that is not declared before this point. Inside a
+  /// function the check waits for the end of the file, since a call may run after a later
+  /// declaration.
+  fn check_global(&mut self, ident: &LoxIdent) {
+    if !self.interpreter.strict_globals || self.declares_global(ident.name) {
+      return;
     }
-    if accessed {
-      self.access(ident);
+    if self.state.function == FunctionState::None {
+      self.undeclared_global(ident);
+    } else {
+      self.deferred_globals.push(ident.clone());
     }
   }
 
+  fn check_deferred_globals(&mut self) {
+    for ident in mem::take(&mut self.deferred_globals) {
+      if !self.declares_global(ident.name) {
+        self.undeclared_global(&ident);
+      }
+    }
+  }
+
+  /// Whether `name` is a global declared in this file so far, or by code run before it.
+  fn declares_global(&self, name: Symbol) -> bool {
+    self.globals.contains(&name) || self.interpreter.globals.contains(name)
+  }
+
+  fn undeclared_global(&mut self, ident: &LoxIdent) {
+    self.error(ErrorType::Error, ident.span, format!("Undeclared global `{}`", ident.name));
+  }
+
   fn resolve_fun(&mut self, decl: &stmt::FunDecl, state: FunctionState) {
     let old_function_state = mem::replace(&mut self.state.function, state);
+    let old_function_scope = self.state.function_scope;
+    // a loop around the declaration is not one that the body can leave
+    let old_in_loop = mem::replace(&mut self.state.in_loop, false);
 
     self.scoped(|this| {
+      this.state.function_scope = this.scopes.len() - 1;
       for param in &decl.params {
         this.declare(param);
         this.define(param);
@@ -323,17 +845,22 @@ impl<'i> Resolver<'i> {
     });
 
     self.state.function = old_function_state;
+    self.state.function_scope = old_function_scope;
+    self.state.in_loop = old_in_loop;
   }
 
   /// One should ideally use `scoped`. Callers of `begin_scope` must also call `end_scope`.
   #[inline]
   fn begin_scope(&mut self) {
-    self.scopes.push(HashMap::new());
+    self.scopes.push(Scope::default());
   }
 
   #[inline]
   fn end_scope(&mut self) {
     self.scopes.pop();
+    let depth = self.scopes.len();
+    self.unassigned.retain(|(_, scope)| *scope < depth);
+    self.classes.retain(|_, (scope, _)| scope.is_none_or(|scope| scope < depth));
   }
 
   fn scoped<I>(&mut self, inner: I)
@@ -341,35 +868,84 @@ impl<'i> Resolver<'i> {
     I: FnOnce(&mut Self),
   {
     self.begin_scope();
-    let res = inner(self);
+    inner(self);
     self.check_unused();
     self.end_scope();
-    res
   }
 
   /// Reports any unused local variables
   fn check_unused(&mut self) {
     use BindingState::*;
-    if let Some(scope) = self.scopes.last() {
-      for (key, state) in scope.iter() {
-        match state {
-          Declared(span) | Initialized (span) => {
-            self.errors.push(ResolveError {
-              kind: ErrorType::Warning,
-              message: format!("Unused variable `{}`", key),
-              span: *span,
-            })
-          }
-          _ => continue
-        }
-      }
+    let Some(scope) = self.scopes.last() else {
+      return;
+    };
+    let unused: Vec<_> = scope.bindings.iter()
+      .filter_map(|(key, binding)| match binding.state {
+        Declared(span) | Initialized(span) => Some((*key, span)),
+        _ => None,
+      })
+      .collect();
+    for (key, span) in unused {
+      self.warn(Lint::UnusedVariable, span, format!("Unused variable `{}`", key));
     }
   }
 
   fn error(&mut self, kind: ErrorType, span: Span, message: impl Into<String>) {
     let message = message.into();
-    self.errors.push(ResolveError { span, message, kind });
+    self.errors.push(ResolveError { span, message, kind, lint: None, labels: Vec::new() });
+  }
+
+  /// Reports a warning, as [`ErrorType::Suppressed`] if a `lox-ignore` comment on its line names
+  /// it.
+  fn warn(&mut self, lint: Lint, span: Span, message: impl Into<String>) {
+    let ignored = self.ignores.iter().any(|ignore| {
+      ignore.line.contains_p(span.0) && ignore.names.iter().any(|name| name == lint.name())
+    });
+    let kind = if ignored { ErrorType::Suppressed } else { ErrorType::Warning };
+    let message = message.into();
+    self.errors.push(ResolveError { span, message, kind, lint: Some(lint), labels: Vec::new() });
+  }
+}
+
+/// Whether `stmts` bind any name in their own scope. Imports count, so that a block holding one
+/// is still scoped and reported as not being at the top level.
+fn declares_any(stmts: &[Stmt]) -> bool {
+  stmts.iter().any(|stmt| matches!(stmt, Stmt::Import(_)) || stmt.declared_name().is_some())
+}
+
+/// Whether evaluating `expr` may do more than compute a value, such as assign, call or print.
+fn has_effects(expr: &Expr) -> bool {
+  // walked with a stack of its own, as operator chains nest too deep to recurse on
+  let mut pending = vec![expr];
+  while let Some(expr) = pending.pop() {
+    match expr {
+      Expr::Lit(_) | Expr::Var(_) => {}
+      Expr::Group(group) => pending.push(&group.expr),
+      Expr::Unary(unary) => pending.push(&unary.operand),
+      Expr::Binary(bin) => pending.extend([&*bin.left, &*bin.right]),
+      Expr::Logical(logical) => pending.extend([&*logical.left, &*logical.right]),
+      _ => return true,
+    }
   }
+  false
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+  bindings: HashMap<Symbol, Binding>,
+  /// Number of bindings defined so far, which is the slot the next one takes.
+  defined: usize,
+  /// Variants of the enums declared in the scope.
+  enums: HashMap<Symbol, Vec<Symbol>>,
+}
+
+#[derive(Debug)]
+struct Binding {
+  state: BindingState,
+  /// Index of the variable in its scope at runtime, once it is defined.
+  slot: Option<usize>,
+  /// Name in the declaration, unless the interpreter adds the binding itself.
+  span: Option<Span>,
 }
 
 #[derive(Debug, Copy, Clone, Eq)]
@@ -382,12 +958,10 @@ enum BindingState {
 impl PartialEq for BindingState {
   fn eq(&self, other: &Self) -> bool {
     use BindingState::*;
-    match (self, other) {
-      (Declared(_), Declared(_)) => true,
-      (Initialized(_), Initialized(_)) => true,
-      (Accessed, Accessed) => true,
-      _ => false
-    }
+    matches!(
+      (self, other),
+      (Declared(_), Declared(_)) | (Initialized(_), Initialized(_)) | (Accessed, Accessed)
+    )
   }
 }
 
@@ -395,6 +969,12 @@ impl PartialEq for BindingState {
 struct ResolverState {
   function: FunctionState,
   class: ClassState,
+  /// Index of the scope holding the parameters of the current function.
+  function_scope: usize,
+  /// Number of scopes open when the innermost loop began.
+  loop_scope: usize,
+  /// Whether the current function is inside a loop, where `break` and `continue` are allowed.
+  in_loop: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -0,0 +1,65 @@
+use std::{
+  collections::{HashMap, HashSet},
+  rc::Rc,
+};
+
+use crate::{
+  data::{LoxIdent, LoxIdentId},
+  interpreter::module::LoxModule,
+};
+
+/// What the resolver found out about a program, keyed by the ids of its AST nodes. The
+/// interpreter runs a program with the table resolved for it, but the table does not depend on
+/// the interpreter, so tools can resolve a program without running it.
+#[derive(Debug, Default, Clone)]
+pub struct ResolutionTable {
+  /// Scope distance and slot of each local variable use.
+  locals: HashMap<LoxIdentId, (usize, usize)>,
+  /// Blocks that declare nothing, which run in the enclosing scope instead of a new one.
+  flat_blocks: HashSet<LoxIdentId>,
+  /// Module each import loads.
+  modules: HashMap<LoxIdentId, Rc<LoxModule>>,
+}
+
+impl ResolutionTable {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records that `ident` reads the local in `slot` of the scope `depth` levels out.
+  pub fn resolve_local(&mut self, ident: &LoxIdent, depth: usize, slot: usize) {
+    self.locals.insert(ident.id, (depth, slot));
+  }
+
+  /// Scope distance and slot of the local `ident` refers to, or `None` for a global.
+  pub fn local(&self, ident: &LoxIdent) -> Option<(usize, usize)> {
+    self.locals.get(&ident.id).copied()
+  }
+
+  /// Records that the block `id` declares nothing, so it needs no scope of its own.
+  pub fn flatten_block(&mut self, id: LoxIdentId) {
+    self.flat_blocks.insert(id);
+  }
+
+  /// Whether the block `id` runs in the enclosing scope.
+  pub fn is_flat(&self, id: LoxIdentId) -> bool {
+    self.flat_blocks.contains(&id)
+  }
+
+  /// Records the module that the import `id` loads.
+  pub fn add_module(&mut self, id: LoxIdentId, module: Rc<LoxModule>) {
+    self.modules.insert(id, module);
+  }
+
+  /// Module the import `id` loads, once it has been resolved.
+  pub fn module(&self, id: LoxIdentId) -> Option<&Rc<LoxModule>> {
+    self.modules.get(&id)
+  }
+
+  /// Adds the entries of `other`, such as those of a module or of the next line at the REPL.
+  pub fn extend(&mut self, other: ResolutionTable) {
+    self.locals.extend(other.locals);
+    self.flat_blocks.extend(other.flat_blocks);
+    self.modules.extend(other.modules);
+  }
+}
@@ -15,7 +15,7 @@ impl Span {
     Span(min(lo, hi), max(lo, hi))
   }
 
-  /// Create a new span encompassing `self` and `other`.
+  /// Create a new span encompassing `self` and `other`, in either order.
   pub fn to(&self, other: Span) -> Span {
     Span::new(min(self.0, other.0), max(self.1, other.1))
   }
@@ -43,6 +43,26 @@ impl Span {
   }
 }
 
+/// Another span a diagnostic points at, with what it shows there, such as the declaration that a
+/// name clashes with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+  pub span: Span,
+  pub message: String,
+}
+
+impl Label {
+  pub fn new(span: Span, message: impl Into<String>) -> Self {
+    Self { span, message: message.into() }
+  }
+}
+
+impl Display for Label {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}; at position {}", self.message, self.span)
+  }
+}
+
 impl Display for Span {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     if (self.1 - self.0) <= 1 {
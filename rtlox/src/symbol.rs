@@ -0,0 +1,104 @@
+//! Interned names. Identifiers are compared and used as map keys many times while a program
+//! is resolved and run, so each distinct name is stored once and referred to by a number.
+
+use std::{
+  collections::HashMap,
+  fmt::{self, Debug, Display},
+  ops::Deref,
+  sync::{Mutex, OnceLock, PoisonError},
+};
+
+/// A name interned for the rest of the process. Equal names get equal symbols, so comparing
+/// and hashing a symbol is as cheap as for an integer. Symbols order by when they were first
+/// interned, not by their text.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+  symbols: HashMap<&'static str, Symbol>,
+  names: Vec<&'static str>,
+}
+
+/// The interner shared by every interpreter. Names are never freed, as there are only as many
+/// as distinct identifiers in the programs run.
+fn interner() -> &'static Mutex<Interner> {
+  static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+  INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+  /// Returns the symbol for `name`, adding it on first use.
+  pub fn intern(name: &str) -> Self {
+    let mut interner = interner().lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(&symbol) = interner.symbols.get(name) {
+      return symbol;
+    }
+    let symbol = Symbol(interner.names.len() as u32);
+    let name: &'static str = Box::leak(name.into());
+    interner.names.push(name);
+    interner.symbols.insert(name, symbol);
+    symbol
+  }
+
+  /// Text of the name.
+  pub fn as_str(self) -> &'static str {
+    interner().lock().unwrap_or_else(PoisonError::into_inner).names[self.0 as usize]
+  }
+}
+
+impl From<&Symbol> for Symbol {
+  fn from(symbol: &Symbol) -> Self {
+    *symbol
+  }
+}
+
+impl From<&str> for Symbol {
+  fn from(name: &str) -> Self {
+    Symbol::intern(name)
+  }
+}
+
+impl From<&String> for Symbol {
+  fn from(name: &String) -> Self {
+    Symbol::intern(name)
+  }
+}
+
+impl From<String> for Symbol {
+  fn from(name: String) -> Self {
+    Symbol::intern(&name)
+  }
+}
+
+impl Deref for Symbol {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl PartialEq<str> for Symbol {
+  fn eq(&self, other: &str) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl PartialEq<&str> for Symbol {
+  fn eq(&self, other: &&str) -> bool {
+    self.as_str() == *other
+  }
+}
+
+impl Display for Symbol {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl Debug for Symbol {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    Debug::fmt(self.as_str(), f)
+  }
+}
@@ -1,13 +1,63 @@
 // use super::*;
 
-use std::{fs, error::Error, path::Path};
+use std::{fs, error::Error, io, path::{Path, PathBuf}};
 
-use rtlox::user::run_file;
+use rtlox::{
+  ast::stmt::Stmt,
+  interpreter::{error::RuntimeError, Interpreter},
+  parser::Parser,
+  resolver::{
+    error::{ErrorType, ResolveError},
+    Resolver,
+  },
+  user::run_file,
+};
 
 mod challenges;
+mod enums;
+mod grammar;
+mod recovery;
+mod namespaces;
+mod modules;
+mod unset;
+mod trivia;
+mod transpile;
+mod call_depth;
+mod long_chains;
+mod scopes;
+mod bench;
+mod classes;
+mod hooks;
+mod natives;
+mod embed;
+mod stream;
 
 const TEST_DIR: &str = "../tests/";
 
+/// Parses `src`, which must have no syntax errors, and resolves it for `interpreter`. Returns the
+/// statements and everything the resolver reported.
+pub fn resolve_on(interpreter: &mut Interpreter, src: &str) -> (Vec<Stmt>, Vec<ResolveError>) {
+  let (stmts, errors) = Parser::new(src).parse();
+  assert!(errors.is_empty(), "{errors:?}");
+  let (_, errors) = Resolver::new(interpreter).resolve(&stmts);
+  (stmts, errors)
+}
+
+/// Runs `src` on `interpreter` with its output captured, and returns what it printed. The source
+/// must resolve with at most warnings. Output printed before a runtime error is left for the
+/// caller to take.
+pub fn run_capture_on(interpreter: &mut Interpreter, src: &str) -> Result<Vec<String>, RuntimeError> {
+  interpreter.capture_output();
+  let (stmts, errors) = resolve_on(interpreter, src);
+  assert!(errors.iter().all(|err| !matches!(err.kind, ErrorType::Error)), "{errors:?}");
+  interpreter.interpret(&stmts).map(|()| interpreter.take_output())
+}
+
+/// Runs `src` on a new interpreter, see [`run_capture_on`].
+pub fn run_capture(src: &str) -> Result<Vec<String>, RuntimeError> {
+  run_capture_on(&mut Interpreter::new(), src)
+}
+
 
 #[test]  
 fn recursion() -> Result<(), Box<dyn Error>> {
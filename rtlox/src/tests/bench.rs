@@ -0,0 +1,128 @@
+//! Micro-benchmarks for scanning and variable access in the tree-walker.
+//! Run with `cargo test --release -p rtlox bench -- --ignored --nocapture`.
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  sync::atomic::{AtomicUsize, Ordering},
+  time::Instant,
+};
+
+use rtlox::{
+  interpreter::Interpreter,
+  parser::{scanner::Scanner, Parser},
+  resolver::Resolver,
+};
+
+/// Counts the bytes allocated, so that benchmarks can report their peak memory use.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    let now = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+    PEAK.fetch_max(now, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+/// Runs `f`, returning how long it took and the most memory it held at once beyond what was
+/// allocated before it. Other tests running at the same time add to the count, so run the
+/// benchmarks on their own.
+fn measure(f: impl FnOnce()) -> (std::time::Duration, usize) {
+  let before = ALLOCATED.load(Ordering::Relaxed);
+  PEAK.store(before, Ordering::Relaxed);
+  let start = Instant::now();
+  f();
+  (start.elapsed(), PEAK.load(Ordering::Relaxed) - before)
+}
+
+/// A few lines of everything the scanner reads, repeated to make a large source.
+const SCAN_CHUNK: &str = "// a comment
+class Point < Base {
+  init(x, y) { this.x = x; this.y = y; } /* block */
+  len() { return sqrt(this.x * this.x + this.y * this.y); }
+}
+var label = \"point number\"; var n = 123.456;
+if (n >= 10 and label != nil) print label + \" \" + n;
+";
+
+/// Reads and writes locals a few scopes out from a hot loop.
+const LOCALS: &str = "{
+  var a = 0; var b = 1; var c = 2; var d = 3;
+  {
+    var total = 0;
+    for (var i = 0; i < 200000; i = i + 1) {
+      total = total + a + b + c + d;
+    }
+    print total;
+  }
+}";
+
+const FIB: &str = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+print fib(22);";
+
+const CLOSURES: &str = "fun counter() {
+  var count = 0;
+  fun inc() { count = count + 1; return count; }
+  return inc;
+}
+var inc = counter();
+for (var i = 0; i < 100000; i = i + 1) inc();
+print inc();";
+
+fn bench(name: &str, src: &str) {
+  let (stmts, errors) = Parser::new(src).parse();
+  assert!(errors.is_empty(), "{errors:?}");
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  Resolver::new(&mut interpreter).resolve(&stmts);
+
+  let start = Instant::now();
+  interpreter.interpret(&stmts).unwrap();
+  println!("{name}: {:?}", start.elapsed());
+}
+
+#[test]
+#[ignore]
+fn bench_locals() {
+  bench("locals", LOCALS);
+}
+
+#[test]
+#[ignore]
+fn bench_calls() {
+  bench("calls", FIB);
+}
+
+#[test]
+#[ignore]
+fn bench_closures() {
+  bench("closures", CLOSURES);
+}
+
+#[test]
+#[ignore]
+fn bench_scan() {
+  let src = SCAN_CHUNK.repeat(50_000);
+  let (time, peak) = measure(|| {
+    let tokens = Scanner::new(&src).count();
+    assert!(tokens > 0);
+  });
+  println!("scan {} KB: {time:?}, peak {} KB", src.len() / 1024, peak / 1024);
+  // the char buffer the scanner used to build before reading any tokens
+  let (time, peak) = measure(|| {
+    let chars: Vec<(usize, char)> = src.char_indices().collect();
+    assert_eq!(chars.len(), src.len());
+  });
+  println!("collect char_indices: {time:?}, peak {} KB", peak / 1024);
+}
@@ -0,0 +1,33 @@
+use std::thread;
+
+use rtlox::interpreter::{error::RuntimeError, Interpreter, STACK_SIZE};
+
+use super::run_capture_on as run;
+
+const COUNT_DOWN: &str = "fun f(n) { if (n == 0) return 0; return f(n - 1) + 1; }";
+
+#[test]
+fn deep_recursion_is_a_runtime_error() {
+  // the same stack the binary runs the interpreter with
+  let err = thread::Builder::new()
+    .stack_size(STACK_SIZE)
+    .spawn(|| run(&mut Interpreter::new(), &format!("{COUNT_DOWN} print f(100000);")).unwrap_err())
+    .unwrap()
+    .join()
+    .unwrap();
+
+  assert!(matches!(err, RuntimeError::StackOverflow { limit: 1000, .. }), "{err}");
+  assert_eq!(err.to_string(), "Stack overflow: calling `f` nests more than 1000 calls; at position 4");
+}
+
+#[test]
+fn call_depth_limit_is_configurable() {
+  let mut interpreter = Interpreter::new();
+  interpreter.max_call_depth = 10;
+  run(&mut interpreter, COUNT_DOWN).unwrap();
+
+  assert_eq!(run(&mut interpreter, "print f(9);").unwrap(), ["9"]);
+  assert!(matches!(run(&mut interpreter, "print f(10);"), Err(RuntimeError::StackOverflow { .. })));
+  // the overflow does not count against later runs
+  assert_eq!(run(&mut interpreter, "print f(9);").unwrap(), ["9"]);
+}
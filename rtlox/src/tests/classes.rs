@@ -0,0 +1,133 @@
+use std::rc::Rc;
+
+use rtlox::{data::LoxValue, interpreter::Interpreter, parser::Parser};
+
+use super::{resolve_on, run_capture, run_capture_on};
+
+/// Messages of everything the resolver reports for `src`.
+fn diagnostics(src: &str) -> Vec<String> {
+  resolve_on(&mut Interpreter::new(), src).1.into_iter().map(|err| err.message).collect()
+}
+
+/// Resolves and runs `src`, returning what it printed.
+fn run(src: &str) -> Vec<String> {
+  run_capture(src).unwrap()
+}
+
+#[test]
+fn bound_methods_keep_their_instance() {
+  let src = "
+    class Box { init(v) { this.v = v; } get() { return this.v; } }
+    var a = Box(1);
+    var b = Box(2);
+    var get = a.get;
+    print a.get() + b.get();
+    a.v = 10;
+    print get();
+    print b.get();";
+  assert_eq!(run(src), ["3", "10", "2"]);
+}
+
+#[test]
+fn fields_shadow_methods_already_called() {
+  let src = "
+    class A { name() { return \"method\"; } }
+    var a = A();
+    print a.name();
+    a.name = fun () { return \"field\"; };
+    print a.name();";
+  assert_eq!(run(src), ["method", "field"]);
+}
+
+#[test]
+fn methods_are_inherited_through_the_chain() {
+  let src = "
+    class A { a() { return \"A.a\"; } both() { return \"A.both\"; } }
+    class B < A { b() { return \"B.b\"; } }
+    class C < B { both() { return \"C.both\"; } }
+    var c = C();
+    print c.a();
+    print c.b();
+    print c.both();
+    print B().both();";
+  assert_eq!(run(src), ["A.a", "B.b", "C.both", "A.both"]);
+}
+
+#[test]
+fn super_dispatches_from_the_defining_class() {
+  let src = "
+    class A { name() { return \"A\"; } }
+    class B < A { name() { return \"B\" + super.name(); } }
+    class C < B { name() { return \"C\" + super.name(); } }
+    class D < C {}
+    print D().name();";
+  assert_eq!(run(src), ["CBA"]);
+}
+
+#[test]
+fn inherited_init_runs_for_subclasses() {
+  let src = "
+    class A { init(x) { this.x = x; } }
+    class B < A { double() { return this.x * 2; } }
+    print B(21).double();";
+  assert_eq!(run(src), ["42"]);
+}
+
+#[test]
+fn init_declared_twice_is_rejected() {
+  let src = "class A { init() {} init(a) { print a; } }";
+  assert_eq!(diagnostics(src), ["Class `A` declares `init` more than once"]);
+}
+
+#[test]
+fn this_cannot_be_a_parameter() {
+  let (_, errors) = Parser::new("class A { m(this) { return this; } }").parse();
+  assert!(!errors.is_empty());
+}
+
+#[test]
+fn constructor_arity_is_checked() {
+  let src = "
+    class A { init(x) { this.x = x; } }
+    class B < A {}
+    class C {}
+    A(1);
+    B();
+    C(1, 2);";
+  assert_eq!(
+    diagnostics(src),
+    [
+      "Constructing `B` expects 1 arguments, but got 0",
+      "Constructing `C` expects 0 arguments, but got 2",
+    ]
+  );
+}
+
+#[test]
+fn constructor_arity_follows_scoping() {
+  let src = "
+    class A { init(x) { print x; } }
+    fun f(A) { return A(1, 2); }
+    { class A {} A(); }
+    A(1);
+    var A = f;
+    A(1);";
+  assert!(diagnostics(src).is_empty(), "{:?}", diagnostics(src));
+}
+
+#[test]
+fn instances_are_dropped_once_unreachable() {
+  let src = "class A { init() { this.x = 1; } m() { return this.x; } }
+    var a = A();
+    print a.m() + a.m();
+    print a.m == a.m;";
+  let mut interpreter = Interpreter::new();
+  assert_eq!(run_capture_on(&mut interpreter, src).unwrap(), ["2", "true"]);
+
+  let instance = match interpreter.globals_snapshot().remove("a") {
+    Some(LoxValue::Object(instance)) => Rc::downgrade(&instance),
+    other => panic!("expected an instance, got {other:?}"),
+  };
+  assert!(interpreter.unset_global("a"));
+  assert!(instance.upgrade().is_none(), "the instance outlived its last reference");
+}
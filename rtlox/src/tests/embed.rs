@@ -0,0 +1,67 @@
+use rtlox::{
+  data::LoxValue,
+  interpreter::{error::RuntimeError, Interpreter},
+};
+
+use super::run_capture_on;
+
+/// An interpreter that has run `src`.
+fn interpreter_with(src: &str) -> Interpreter {
+  let mut interpreter = Interpreter::new();
+  run_capture_on(&mut interpreter, src).unwrap();
+  interpreter
+}
+
+fn global(interpreter: &Interpreter, name: &str) -> LoxValue {
+  interpreter.globals.get_local(name).unwrap()
+}
+
+#[test]
+fn host_calls_script_functions() {
+  let mut interpreter = interpreter_with("
+    var count = 0;
+    fun onEvent(name) {
+      count = count + 1;
+      print name;
+      return count;
+    }
+    class Point {
+      init(x) { this.x = x; }
+    }
+    fun counter() {
+      var n = 0;
+      fun next() { n = n + 1; return n; }
+      return next;
+    }
+    var next = counter();
+  ");
+  let handler = global(&interpreter, "onEvent");
+  let name = LoxValue::String("click".into());
+  interpreter.call_value(&handler, std::slice::from_ref(&name)).unwrap();
+  let count = interpreter.call_value(&handler, &[name]).unwrap();
+  assert!(count.equals(&LoxValue::Number(2.0)));
+  assert_eq!(interpreter.take_output(), ["click", "click"]);
+
+  let point = interpreter.call_value(&global(&interpreter, "Point"), &[LoxValue::Number(1.0)]);
+  assert_eq!(point.unwrap().to_string(), "<instance Point>");
+
+  let next = global(&interpreter, "next");
+  interpreter.call_value(&next, &[]).unwrap();
+  assert!(interpreter.call_value(&next, &[]).unwrap().equals(&LoxValue::Number(2.0)));
+}
+
+#[test]
+fn host_calls_report_errors() {
+  let mut interpreter = interpreter_with("fun fail(x) { return x / 0; } fun id(x) { return x; }");
+  let fail = global(&interpreter, "fail");
+  let id = global(&interpreter, "id");
+
+  let err = interpreter.call_value(&fail, &[LoxValue::Number(1.0)]).unwrap_err();
+  assert!(matches!(err, RuntimeError::ZeroDivision { .. }), "{err}");
+  let err = interpreter.call_value(&id, &[]).unwrap_err();
+  assert!(err.to_string().starts_with("Expected 1 arguments, but got 0"), "{err}");
+  let err = interpreter.call_value(&LoxValue::Nil, &[]).unwrap_err();
+  assert!(err.to_string().starts_with("Type `nil` is not callable"), "{err}");
+
+  assert!(interpreter.call_value(&id, &[LoxValue::Nil]).unwrap().equals(&LoxValue::Nil));
+}
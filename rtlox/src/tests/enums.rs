@@ -0,0 +1,84 @@
+use super::*;
+
+use rtlox::{interpreter::Interpreter, resolver::error::ResolveError};
+
+fn resolve(src: &str) -> Vec<ResolveError> {
+  resolve_on(&mut Interpreter::new(), src).1
+}
+
+#[test]
+fn enum_switch() -> Result<(), Box<dyn Error>> {
+  let path = Path::new("../custom_tests").join("enums").join("switch.lox");
+  assert!(run_file(path)?);
+
+  Ok(())
+}
+
+#[test]
+fn warns_on_non_exhaustive_switch() {
+  let errors = resolve("enum Color { Red, Green, Blue }
+switch (Color.Red) {
+  case Color.Red: print 1;
+  case Color.Blue: print 2;
+}");
+  assert_eq!(errors.len(), 1);
+  assert_eq!(errors[0].message, "Non-exhaustive switch over enum `Color`: missing `Green`");
+}
+
+#[test]
+fn default_arm_is_exhaustive() {
+  let errors = resolve("enum Color { Red, Green }
+switch (Color.Red) {
+  case Color.Red: print 1;
+  default: print 2;
+}");
+  assert!(errors.is_empty());
+}
+
+#[test]
+fn warns_on_unknown_variant() {
+  let errors = resolve("enum Color { Red }
+switch (Color.Red) {
+  case Color.Red: print 1;
+  case Color.Purple: print 2;
+}");
+  assert_eq!(errors.len(), 1);
+  assert_eq!(errors[0].message, "Enum `Color` has no variant `Purple`");
+}
+
+#[test]
+fn rejects_duplicate_variants() {
+  let errors = resolve("enum Color { Red, Red }");
+  assert_eq!(errors.len(), 1);
+  assert_eq!(errors[0].message, "Duplicate variant `Red` in enum `Color`");
+}
+
+#[test]
+fn inner_enums_shadow_only_their_scope() {
+  let errors = resolve("enum Color { Red, Green, Blue }
+fun f() {
+  enum Color { Red, Green }
+  switch (Color.Red) {
+    case Color.Red: print 1;
+    case Color.Green: print 2;
+  }
+}
+switch (Color.Red) {
+  case Color.Red: print 1;
+  case Color.Green: print 2;
+  case Color.Blue: print 3;
+}");
+  assert!(errors.is_empty(), "{errors:?}");
+}
+
+#[test]
+fn locals_hide_enums_of_outer_scopes() {
+  let errors = resolve("enum Color { Red, Green }
+{
+  var Color = nil;
+  switch (Color) {
+    case Color.Red: print 1;
+  }
+}");
+  assert!(errors.is_empty(), "{errors:?}");
+}
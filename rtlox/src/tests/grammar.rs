@@ -0,0 +1,24 @@
+use rtlox::parser::grammar::productions;
+
+/// Names of the productions referenced by a rule body, skipping quoted terminals.
+fn nonterminals(body: &str) -> Vec<&str> {
+  body
+    .split('"')
+    .step_by(2)
+    .flat_map(|part| part.split(|c: char| !c.is_ascii_alphabetic()))
+    .filter(|word| word.starts_with(|c: char| c.is_ascii_lowercase()))
+    .collect()
+}
+
+#[test]
+fn grammar_is_closed() {
+  let productions = productions();
+  let defined: Vec<&str> = productions.iter().map(|p| p.name.as_str()).collect();
+  for production in &productions {
+    for name in nonterminals(&production.body) {
+      assert!(defined.contains(&name), "`{}` refers to undefined `{name}`", production.name);
+    }
+    let used = productions.iter().any(|p| nonterminals(&p.body).contains(&production.name.as_str()));
+    assert!(used || production.name == "program", "`{}` is never used", production.name);
+  }
+}
@@ -0,0 +1,106 @@
+use std::{cell::RefCell, io, rc::Rc};
+
+use rtlox::{
+  ast::stmt::Stmt,
+  data::LoxValue,
+  interpreter::{hooks::Hooks, Interpreter},
+  user::{explain::Explain, profile::CallProfile},
+};
+
+use super::run_capture_on;
+
+/// Records each event as a line of text.
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl Hooks for Recorder {
+  fn on_stmt(&mut self, stmt: &Stmt) {
+    self.0.borrow_mut().push(format!("stmt {}", stmt.span()));
+  }
+
+  fn on_call(&mut self, name: &str, args: &[LoxValue]) {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    self.0.borrow_mut().push(format!("call {name}({})", args.join(", ")));
+  }
+
+  fn on_return(&mut self, value: &LoxValue) {
+    self.0.borrow_mut().push(format!("return {value}"));
+  }
+}
+
+#[test]
+fn hooks_see_statements_calls_and_returns() {
+  let src = "fun add(a, b) { return a + b; } print add(1, 2);";
+  let events = Rc::new(RefCell::new(Vec::new()));
+  let mut interpreter = Interpreter::new();
+  interpreter.hooks = Some(Box::new(Recorder(events.clone())));
+
+  assert_eq!(run_capture_on(&mut interpreter, src).unwrap(), ["3"]);
+  assert_eq!(
+    events.take(),
+    [
+      "stmt 0..31",
+      "stmt 32..48",
+      "call add(1, 2)",
+      "stmt 16..29",
+      "return 3",
+    ]
+  );
+}
+
+#[test]
+fn call_profile_folds_stacks() {
+  let src = "fun g() { return 1; } fun f() { return g(); } f(); f(); g();";
+  let profile = CallProfile::new();
+  let mut interpreter = Interpreter::new();
+  interpreter.hooks = Some(Box::new(profile.clone()));
+  run_capture_on(&mut interpreter, src).unwrap();
+
+  let folded = profile.finish();
+  let stacks: Vec<&str> = folded.lines().map(|line| line.rsplit_once(' ').unwrap().0).collect();
+  assert_eq!(stacks, ["<script>", "<script>;f", "<script>;f;g", "<script>;g"]);
+  assert!(folded.lines().all(|line| line.rsplit_once(' ').unwrap().1.parse::<u128>().is_ok()));
+}
+
+/// Collects what is written to it, for reading while another clone is installed.
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for Buffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn explain_indents_each_step() {
+  let src = "fun add(a, b) { return a + b; } var x; x = add(1, 2);";
+  let buffer = Buffer::default();
+  let mut interpreter = Interpreter::new();
+  interpreter.hooks = Some(Box::new(Explain::new(Box::new(buffer.clone()))));
+  run_capture_on(&mut interpreter, src).unwrap();
+
+  let trace = String::from_utf8(buffer.0.take()).unwrap();
+  assert_eq!(trace.lines().collect::<Vec<_>>(), [
+    "fun add(a, b)",
+    "var x",
+    "x = add(1, 2)",
+    "  Assign x = add(1, 2)",
+    "    Call add(1, 2)",
+    "      Variable add => <fun add>",
+    "      Literal 1 => 1",
+    "      Literal 2 => 2",
+    "      call add(1, 2)",
+    "        return a + b",
+    "          Binary a + b",
+    "            Variable a => 1",
+    "            Variable b => 2",
+    "          => 3",
+    "      add returned 3",
+    "    => 3",
+    "  => 3, so x = 3",
+  ]);
+}
@@ -0,0 +1,57 @@
+use std::thread;
+
+use rtlox::{interpreter::Interpreter, parser::Parser, resolver::Resolver};
+
+/// Terms in each chain; nesting this deep overflows a small stack when walked recursively.
+const TERMS: usize = 100_000;
+
+/// Parses, resolves and runs `src` on a thread with a small stack, returning what it printed.
+fn run_on_small_stack(src: String) -> Vec<String> {
+  thread::Builder::new()
+    .stack_size(256 << 10)
+    .spawn(move || {
+      let (stmts, errors) = Parser::new(&src).parse();
+      assert!(errors.is_empty(), "{errors:?}");
+      let mut interpreter = Interpreter::new();
+      interpreter.capture_output();
+      Resolver::new(&mut interpreter).resolve(&stmts);
+      interpreter.interpret(&stmts).unwrap();
+      interpreter.take_output()
+    })
+    .unwrap()
+    .join()
+    .unwrap()
+}
+
+#[test]
+fn long_binary_chain() {
+  let chain = vec!["1"; TERMS].join(" + ");
+  assert_eq!(run_on_small_stack(format!("print {chain};")), [TERMS.to_string()]);
+}
+
+#[test]
+fn long_mixed_binary_chain() {
+  let chain = vec!["2 * 3 - 5"; TERMS].join(" + ");
+  assert_eq!(run_on_small_stack(format!("print {chain};")), [TERMS.to_string()]);
+}
+
+#[test]
+fn long_chain_as_expression_statement() {
+  let chain = vec!["1"; TERMS].join(" + ");
+  assert_eq!(run_on_small_stack(format!("{chain}; print \"done\";")), ["done"]);
+}
+
+#[test]
+fn long_logical_chain() {
+  let chain = vec!["false"; TERMS].join(" or ");
+  assert_eq!(run_on_small_stack(format!("print {chain} or \"last\";")), ["last"]);
+  let chain = vec!["true"; TERMS].join(" and ");
+  assert_eq!(run_on_small_stack(format!("var n = 0; print {chain} and n;")), ["0"]);
+}
+
+#[test]
+fn long_chain_in_function_body() {
+  let chain = vec!["n"; TERMS].join(" + ");
+  let src = format!("fun f(n) {{ return {chain}; }} print f(2);");
+  assert_eq!(run_on_small_stack(src), [(2 * TERMS).to_string()]);
+}
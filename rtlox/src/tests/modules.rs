@@ -0,0 +1,70 @@
+use super::*;
+
+use rtlox::{data::LoxValue, interpreter::Interpreter, user::{run_file_with, Config}};
+
+fn module_path(name: &str) -> PathBuf {
+  Path::new("../custom_tests").join("modules").join(name)
+}
+
+fn module_test(name: &str) -> io::Result<bool> {
+  let path = module_path(name);
+  println!("\n{:?}", path);
+  run_file(path)
+}
+
+#[test]
+fn imports_exported_bindings() -> Result<(), Box<dyn Error>> {
+  assert!(module_test("main.lox")?);
+  Ok(())
+}
+
+#[test]
+fn private_bindings_are_not_imported() -> Result<(), Box<dyn Error>> {
+  assert!(!module_test("private.lox")?);
+  Ok(())
+}
+
+#[test]
+fn rejects_nested_export() -> Result<(), Box<dyn Error>> {
+  assert!(!module_test("nested_export.lox")?);
+  Ok(())
+}
+
+#[test]
+fn rejects_import_cycle() -> Result<(), Box<dyn Error>> {
+  assert!(!module_test("cycle_a.lox")?);
+  Ok(())
+}
+
+#[test]
+fn modules_run_once() -> Result<(), Box<dyn Error>> {
+  let mut interpreter = Interpreter::new();
+  run_file_with(module_path("twice.lox"), &Config::default(), &mut interpreter)?;
+  assert!(matches!(interpreter.globals.get_local("count"), Some(LoxValue::Number(n)) if n == 1.0));
+  Ok(())
+}
+
+#[test]
+fn module_search_path() -> Result<(), Box<dyn Error>> {
+  assert!(!module_test("search_path.lox")?);
+
+  let config = Config { module_paths: vec![module_path("lib")], ..Config::default() };
+  let mut interpreter = Interpreter::new();
+  run_file_with(module_path("search_path.lox"), &config, &mut interpreter)?;
+  assert!(matches!(interpreter.globals.get_local("loaded"), Some(LoxValue::Boolean(true))));
+  Ok(())
+}
+
+#[test]
+fn modules_are_parsed_with_the_defines() -> Result<(), Box<dyn Error>> {
+  let seen = |config: &Config| -> Result<Option<LoxValue>, Box<dyn Error>> {
+    let mut interpreter = Interpreter::new();
+    run_file_with(module_path("defines.lox"), config, &mut interpreter)?;
+    Ok(interpreter.globals.get_local("seen"))
+  };
+  let defines = [("DEBUG".to_string(), "true".to_string())].into();
+  let config = Config { defines, ..Config::default() };
+  assert!(matches!(seen(&config)?, Some(LoxValue::String(s)) if s == "debug"));
+  assert!(matches!(seen(&Config::default())?, Some(LoxValue::String(s)) if s == "quiet"));
+  Ok(())
+}
@@ -0,0 +1,17 @@
+use super::*;
+
+#[test]
+fn namespace_members() -> Result<(), Box<dyn Error>> {
+  let path = Path::new("../custom_tests").join("namespaces").join("math.lox");
+  assert!(run_file(path)?);
+
+  Ok(())
+}
+
+#[test]
+fn namespace_undefined_member() -> Result<(), Box<dyn Error>> {
+  let path = Path::new("../custom_tests").join("namespaces").join("undefined_member.lox");
+  assert!(!run_file(path)?);
+
+  Ok(())
+}
@@ -0,0 +1,184 @@
+use std::rc::Rc;
+
+use rtlox::{
+  data::LoxValue,
+  interpreter::{
+    clock::MockClock,
+    convert::{FromLox, ToLox},
+    error::RuntimeError,
+    CFResult, Interpreter,
+  },
+  parser::Parser,
+};
+
+use super::{resolve_on, run_capture_on};
+
+fn add(_: &Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
+  match args {
+    [LoxValue::Number(a), LoxValue::Number(b)] => Ok(LoxValue::Number(a + b)),
+    _ => {
+      let message = "add takes numbers".into();
+      Err(RuntimeError::UnsupportedType { message, span: Default::default() }.into())
+    }
+  }
+}
+
+/// Resolves and runs `src` with `add` registered, returning what it printed or the resolver's
+/// errors.
+fn run(src: &str) -> Result<Vec<String>, Vec<String>> {
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  interpreter.register_native("add", 2, add);
+  let (stmts, errors) = resolve_on(&mut interpreter, src);
+  if !errors.is_empty() {
+    return Err(errors.into_iter().map(|err| err.message).collect());
+  }
+  interpreter.interpret(&stmts).unwrap();
+  Ok(interpreter.take_output())
+}
+
+#[test]
+fn declared_native_binds_registered_function() {
+  let output = run("native fun add(a, b); print add(1, 2); print add;").unwrap();
+  assert_eq!(output, ["3", "<fun (native) add>"]);
+}
+
+#[test]
+fn registered_natives_are_not_globals_until_declared() {
+  let mut interpreter = Interpreter::new();
+  interpreter.register_native("add", 2, add);
+  assert!(run_capture_on(&mut interpreter, "print add(1, 2);").is_err());
+}
+
+#[test]
+fn calls_are_checked_against_the_declaration() {
+  assert_eq!(
+    run("native fun add(a, b); print add(1);"),
+    Err(vec!["Native `add` expects 2 arguments, but got 1".into()])
+  );
+  // a local of the same name is not the native
+  let output = run("native fun add(a, b); { fun add(a) { return a; } print add(1); }").unwrap();
+  assert_eq!(output, ["1"]);
+}
+
+#[test]
+fn declarations_must_match_registrations() {
+  assert_eq!(
+    run("native fun add(a);"),
+    Err(vec!["Native `add` takes 2 arguments, but is declared with 1".into()])
+  );
+  assert_eq!(
+    run("native fun httpGet(url); native fun add(a, b); native fun readFile(path);"),
+    Err(vec!["Declared natives are not registered: `httpGet`, `readFile`".into()])
+  );
+  assert_eq!(
+    run("fun f() { native fun add(a, b); return add; }"),
+    Err(vec!["Natives can only be declared at the top level".into()])
+  );
+}
+
+/// Stands in for a host resource.
+struct File(String);
+
+fn open(_: &Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
+  Ok(LoxValue::foreign("File", File(args[0].to_string())))
+}
+
+fn path(interpreter: &Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
+  let file: &File = interpreter.foreign_arg(&args[0], "File")?;
+  Ok(LoxValue::String(file.0.clone()))
+}
+
+/// Runs `src` with `open` and `path` declared.
+fn run_files(src: &str) -> Result<Vec<String>, RuntimeError> {
+  let src = format!("native fun open(path); native fun path(file); {src}");
+  let mut interpreter = Interpreter::new();
+  interpreter.register_native("open", 1, open);
+  interpreter.register_native("path", 1, path);
+  run_capture_on(&mut interpreter, &src)
+}
+
+#[test]
+fn foreign_handles_pass_through_scripts() {
+  let output = run_files("
+    var f = open(\"a.txt\");
+    var g = f;
+    print f;
+    print path(g);
+    print f == g;
+    print f == open(\"a.txt\");
+  ");
+  assert_eq!(output.unwrap(), ["<foreign File>", "a.txt", "true", "false"]);
+}
+
+#[test]
+fn natives_check_the_tag_of_handles() {
+  let err = run_files("print path(1);").unwrap_err();
+  assert_eq!(err.to_string(), "Expected a `File` handle, but got `number`; at position 52..59");
+
+  let handle = LoxValue::foreign("Socket", File("a.txt".into()));
+  assert!(handle.as_foreign::<File>("File").is_none());
+  assert!(handle.as_foreign::<String>("Socket").is_none());
+  assert_eq!(handle.as_foreign::<File>("Socket").unwrap().0, "a.txt");
+}
+
+fn repeat(interpreter: &Interpreter, args: &[LoxValue]) -> CFResult<LoxValue> {
+  let s: String = interpreter.arg(args, 0)?;
+  let times: Option<usize> = interpreter.arg(args, 1)?;
+  Ok(s.repeat(times.unwrap_or(2)).to_lox())
+}
+
+#[test]
+fn natives_convert_arguments_and_results() {
+  let src = "native fun repeat(s, times); print repeat(\"ab\", 3); print repeat(\"ab\", nil); repeat(\"ab\", 1.5);";
+  let mut interpreter = Interpreter::new();
+  interpreter.register_native("repeat", 2, repeat);
+  let err = run_capture_on(&mut interpreter, src).unwrap_err();
+  assert_eq!(interpreter.take_output(), ["ababab", "abab"]);
+  assert!(err.to_string().starts_with("Expected `integer` for argument 2, but got `number`"), "{err}");
+}
+
+#[test]
+fn values_convert_both_ways() {
+  assert!(3.5.to_lox().equals(&LoxValue::Number(3.5)));
+  assert!("s".to_lox().equals(&LoxValue::String("s".into())));
+  assert!(None::<bool>.to_lox().equals(&LoxValue::Nil));
+  assert!(().to_lox().equals(&LoxValue::Nil));
+
+  assert_eq!(i64::from_lox(&LoxValue::Number(-4.0)), Some(-4));
+  assert_eq!(u32::from_lox(&LoxValue::Number(-4.0)), None);
+  assert_eq!(usize::from_lox(&LoxValue::Number(0.5)), None);
+  assert_eq!(bool::from_lox(&LoxValue::Nil), None);
+  assert_eq!(Option::<bool>::from_lox(&LoxValue::Nil), Some(None));
+  assert_eq!(Option::<String>::from_lox(&LoxValue::String("s".into())), Some(Some("s".into())));
+}
+
+#[test]
+fn native_fn_checks_and_converts_arguments() {
+  let pad = rtlox::native_fn! {
+    fn pad(_, s: String, width: usize) -> String {
+      format!("{s:>width$}")
+    }
+  };
+  assert_eq!((pad.name, pad.arity), ("pad", 2));
+
+  let src = "native fun pad(s, width); print pad(\"ab\", 4); pad(1, 4);";
+  let mut interpreter = Interpreter::new();
+  interpreter.register(pad);
+  let err = run_capture_on(&mut interpreter, src).unwrap_err();
+  assert_eq!(interpreter.take_output(), ["  ab"]);
+  assert_eq!(err.to_string(), "Expected `string` for argument 1, but got `number`; at position 46..55");
+}
+
+#[test]
+fn clock_reads_the_installed_clock() {
+  let clock = MockClock::new(10.0);
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  interpreter.clock = Rc::new(clock.clone());
+  let (stmts, _) = Parser::new("print clock();").parse();
+  interpreter.interpret(&stmts).unwrap();
+  clock.advance(0.25);
+  interpreter.interpret(&stmts).unwrap();
+  assert_eq!(interpreter.take_output(), ["10", "10.25"]);
+}
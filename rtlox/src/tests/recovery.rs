@@ -0,0 +1,52 @@
+use rtlox::{
+  interpreter::{error::RuntimeError, Interpreter},
+  parser::{valid_prefix, Parser},
+  span::{Label, Span},
+};
+
+/// Messages of the errors reported while parsing `src`.
+fn parse_errors(src: &str) -> Vec<String> {
+  Parser::new(src).parse().1.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn skips_block_comments() {
+  let errors = parse_errors("print 1; /* note */ print 2;");
+  assert!(errors.is_empty(), "{errors:?}");
+}
+
+#[test]
+fn unclosed_delimiters_label_where_they_opened() {
+  let (_, errors) = Parser::new("if (true print 1;").parse();
+  assert_eq!(errors.len(), 1, "{errors:?}");
+  assert_eq!(errors[0].labels(), [Label::new(Span::new(3, 4), "`(` opened here")]);
+}
+
+#[test]
+fn valid_prefix_stops_at_first_error() {
+  let (stmts, errors) = Parser::new("print 1; { print 2; print ; } print 3;").parse();
+  assert_eq!(errors.len(), 1, "{errors:?}");
+  // the block holding the error is cut along with everything after it
+  assert_eq!(valid_prefix(&stmts, &errors).len(), 1);
+  assert_eq!(valid_prefix(&stmts, &[]).len(), stmts.len());
+}
+
+#[test]
+fn unparsed_statement_is_rejected() {
+  let (stmts, errors) = Parser::new("print 1; print ;").parse();
+  assert_eq!(errors.len(), 1, "{errors:?}");
+
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  let err = interpreter.interpret(&stmts).unwrap_err();
+  assert!(matches!(err, RuntimeError::Unparsed { .. }), "{err}");
+  assert_eq!(interpreter.take_output(), ["1"]);
+}
+
+#[test]
+fn string_literals_over_the_limit_are_reported() {
+  let mut parser = Parser::new("print \"abcdef\"; print \"abc\";");
+  parser.options.max_string_len = Some(4);
+  let errors: Vec<_> = parser.parse().1.iter().map(ToString::to_string).collect();
+  assert_eq!(errors, ["[E0006] String literal is 6 bytes long, over the limit of 4; at position 6..14"]);
+}
@@ -0,0 +1,234 @@
+use rtlox::{
+  interpreter::Interpreter,
+  parser::{state::ParserOptions, Parser},
+  resolver::Resolver,
+  symbol::Symbol,
+};
+
+use super::{resolve_on, run_capture};
+
+/// Resolves and runs `src`, returning what it printed and the resolver's warnings.
+fn run(src: &str) -> (Vec<String>, Vec<String>) {
+  let output = run_capture(src).unwrap();
+  let (_, warnings) = resolve_on(&mut Interpreter::new(), src);
+  (output, warnings.into_iter().map(|err| err.message).collect())
+}
+
+#[test]
+fn inner_declaration_shadows_outer() {
+  let (output, _) = run("{ var a = 1; { var a = 2; print a; a = 3; print a; } print a; }");
+  assert_eq!(output, ["2", "3", "1"]);
+}
+
+#[test]
+fn locals_keep_their_slots() {
+  // the lambda in the initializer is defined before `b` itself
+  let src = "{
+    var a = \"a\";
+    var b = fun () { return a; };
+    var c = \"c\";
+    print a + b() + c;
+    c = \"C\";
+    print c;
+  }";
+  assert_eq!(run(src).0, ["aac", "C"]);
+}
+
+#[test]
+fn closures_read_their_enclosing_slots() {
+  let src = "
+    fun counter() {
+      var unused = nil;
+      var count = 0;
+      fun inc() { count = count + 1; return count; }
+      return inc;
+    }
+    var c = counter();
+    c();
+    print c();";
+  assert_eq!(run(src).0, ["2"]);
+}
+
+#[test]
+fn this_and_super_resolve_to_slots() {
+  let src = "
+    class A { name() { return \"A\"; } }
+    class B < A {
+      init(suffix) { this.suffix = suffix; }
+      name() { return super.name() + this.suffix; }
+    }
+    print B(\"!\").name();
+    print B(\"?\").init(\".\").suffix;";
+  assert_eq!(run(src).0, ["A!", "."]);
+}
+
+#[test]
+fn reads_from_nested_scopes_count_as_uses() {
+  let (output, warnings) = run("fun f() { var a = 1; { print a; } var b = 2; } f();");
+  assert_eq!(output, ["1"]);
+  assert_eq!(warnings, ["Unused variable `b`"]);
+}
+
+#[test]
+fn blocks_without_declarations_share_the_enclosing_scope() {
+  let src = "{
+    var a = 1;
+    { { a = a + 1; } print a; }
+    for (; a < 4;) { a = a + 1; }
+    print a;
+  }";
+  assert_eq!(run(src).0, ["2", "4"]);
+}
+
+#[test]
+fn loop_bodies_with_declarations_get_a_scope_per_iteration() {
+  let src = "
+    var first = nil;
+    for (var i = 0; i < 3; i = i + 1) {
+      var j = i;
+      fun get() { return j; }
+      if (first == nil) first = get;
+    }
+    print first();";
+  assert_eq!(run(src).0, ["0"]);
+}
+
+#[test]
+fn globals_lists_names_in_sorted_order() {
+  let (output, _) = run("var b = 1; fun a() {} var c; { var local = 2; } print globals();");
+  assert_eq!(output, ["a, b, c, callerInfo, clock, globals, process"]);
+}
+
+/// Messages of the resolver's errors for `src` under `--strict-globals`.
+fn strict_errors(src: &str, interpreter: &mut Interpreter) -> Vec<String> {
+  interpreter.strict_globals = true;
+  let (stmts, errors) = resolve_on(interpreter, src);
+  if errors.is_empty() {
+    interpreter.interpret(&stmts).unwrap();
+  }
+  errors.into_iter().map(|err| err.message).collect()
+}
+
+#[test]
+fn strict_globals_need_a_declaration() {
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  let src = "fun f() { return g() + later; } fun g() { return 1; } var later = 1; print f();";
+  assert_eq!(strict_errors(src, &mut interpreter), Vec::<String>::new());
+  assert_eq!(interpreter.take_output(), ["2"]);
+
+  let src = "print a; var a = 1; b = 2; fun f() { return c; }";
+  assert_eq!(
+    strict_errors(src, &mut Interpreter::new()),
+    ["Undeclared global `a`", "Undeclared global `b`", "Undeclared global `c`"]
+  );
+}
+
+#[test]
+fn strict_globals_remember_earlier_runs() {
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  assert!(strict_errors("var a = 1;", &mut interpreter).is_empty());
+  assert!(strict_errors("print a + clock() * 0;", &mut interpreter).is_empty());
+  assert_eq!(interpreter.take_output(), ["1"]);
+}
+
+#[test]
+fn repl_binds_echoed_values() {
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  interpreter.strict_globals = true;
+  for line in ["1 + 2", "var x = 10;", "_ * x", "print _1 + _2;", "_1"] {
+    let mut parser = Parser::new(line);
+    parser.options = ParserOptions { repl_mode: true, ..Default::default() };
+    let (stmts, errors) = parser.parse();
+    assert!(errors.is_empty(), "{errors:?}");
+    let (_, errors) = Resolver::new(&mut interpreter).resolve(&stmts);
+    assert!(errors.is_empty(), "{errors:?}");
+    interpreter.interpret(&stmts).unwrap();
+  }
+  assert_eq!(interpreter.take_output(), ["3", "30", "33", "3"]);
+  assert_eq!(interpreter.results().len(), 3);
+  assert_eq!(interpreter.globals.get_local("_3").map(|val| val.to_string()), Some("3".into()));
+}
+
+#[test]
+fn declarations_warn_when_they_shadow_natives() {
+  let (output, warnings) = run("var clock = 1; print clock; fun f(globals) { return globals; } f(2);");
+  assert_eq!(output, ["1"]);
+  assert_eq!(
+    warnings,
+    ["`clock` shadows the native function `clock()`", "`globals` shadows the native function `globals()`"]
+  );
+}
+
+#[test]
+fn assignments_in_conditions_warn_unless_parenthesized() {
+  let src = "var a = 1; if (a = 2) print a; while ((a = nil)) {} for (; a = false;) {} if (a or (a = 3)) print a;";
+  let (output, warnings) = run(src);
+  assert_eq!(output, ["2", "3"]);
+  let message = "Assignment used as a condition; did you mean `==`? Wrap it in parentheses if not";
+  assert_eq!(warnings, [message, message]);
+}
+
+#[test]
+fn expression_statements_without_effects_warn() {
+  let src = "var a = 1; a + 1; (a, -a); !a or a == nil; a = 2; fun f() { return a; } f(); print a;";
+  let (output, warnings) = run(src);
+  assert_eq!(output, ["2"]);
+  assert_eq!(warnings, ["Expression statement has no effect; did you mean to `print` or assign it?"; 3]);
+}
+
+#[test]
+fn env_graph_draws_captured_scopes() {
+  let src = "
+    native fun envGraph();
+    fun makeCounter() {
+      var i = 0;
+      fun count() { i = i + 1; return i; }
+      return count;
+    }
+    var counter = makeCounter();
+    counter();
+    print envGraph();";
+  let (output, _) = run(src);
+  let graph: Vec<&str> = output[0].lines().collect();
+  assert_eq!(graph[..2], ["digraph env {", "  node [shape=record];"]);
+  for line in [
+    "  fun0 -> scope1 [style=dashed, label=\"captures\"];",
+    "  scope0 [label=\"{globals (current)|<v0> counter = \\<fun count\\>|<v1> makeCounter = \\<fun makeCounter\\>}\"];",
+    "  scope0:v0 -> fun0;",
+    "  scope1 [label=\"{scope|<v0> i = 1|<v1> count = \\<fun count\\>}\"];",
+    "  scope1 -> scope0 [label=\"enclosing\"];",
+    "  scope1:v1 -> fun0;",
+  ] {
+    assert!(graph.contains(&line), "{line}\n{}", output[0]);
+  }
+}
+
+#[test]
+fn equal_names_intern_to_one_symbol() {
+  let name = Symbol::intern("counter");
+  assert_eq!(name, Symbol::from(String::from("counter")));
+  assert_ne!(name, Symbol::intern("count"));
+  assert_eq!(name.to_string(), "counter");
+  assert_eq!(format!("{name:?}"), "\"counter\"");
+}
+
+#[test]
+fn resolution_table_runs_on_another_interpreter() {
+  let (stmts, errors) = Parser::new("{ var a = 1; { print a + 1; } }").parse();
+  assert!(errors.is_empty(), "{errors:?}");
+  let (table, errors) = Resolver::new(&mut Interpreter::new()).resolve_to_table(&stmts);
+  assert!(errors.is_empty(), "{errors:?}");
+
+  // without the table, `a` is looked up as a global
+  let mut interpreter = Interpreter::new();
+  assert!(interpreter.interpret(&stmts).is_err());
+
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  interpreter.add_resolution(table);
+  interpreter.interpret(&stmts).unwrap();
+  assert_eq!(interpreter.take_output(), ["2"]);
+}
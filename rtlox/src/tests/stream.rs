@@ -0,0 +1,41 @@
+use std::io::{self, Read};
+
+use rtlox::{
+  interpreter::Interpreter,
+  parser::{scanner::Scanner, Parser},
+  resolver::Resolver,
+  token::Token,
+};
+
+/// Reads one byte per call, so that every character and lexeme is split between reads.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let Some((first, rest)) = self.0.split_first() else { return Ok(0) };
+    buf[0] = *first;
+    self.0 = rest;
+    Ok(1)
+  }
+}
+
+const SRC: &str = "var cafe = \"naïve\nstring\";\n/* ✓ block\ncomment */ print cafe + \"!\"; // end\nprint __line__;\n";
+
+#[test]
+fn streamed_source_scans_the_same() {
+  let whole: Vec<Token> = Scanner::new(SRC).collect();
+  assert_eq!(Scanner::from_reader(Trickle(SRC.as_bytes())).collect::<Vec<_>>(), whole);
+  let open = "print 1; \"open";
+  assert_eq!(Scanner::from_reader(open.as_bytes()).collect::<Vec<_>>(), Scanner::new(open).collect::<Vec<_>>());
+}
+
+#[test]
+fn streamed_source_runs_the_same() {
+  let (stmts, errors) = Parser::from_reader(Trickle(SRC.as_bytes())).parse();
+  assert!(errors.is_empty(), "{errors:?}");
+  let mut interpreter = Interpreter::new();
+  interpreter.capture_output();
+  Resolver::new(&mut interpreter).resolve(&stmts);
+  interpreter.interpret(&stmts).unwrap();
+  assert_eq!(interpreter.take_output(), ["naïve\nstring!", "5"]);
+}
@@ -0,0 +1,145 @@
+use std::{env, fs, path::PathBuf, process::{Command, Output}};
+
+use rtlox::{
+  parser::Parser,
+  transpile::{js, rust, TranspileError},
+};
+
+use super::run_capture;
+
+const PROGRAM: &str = r#"
+fun counter() {
+  var count = 0;
+  fun inc() {
+    count = count + 1;
+    return count;
+  }
+  return inc;
+}
+var c = counter();
+c();
+print c();
+
+class Shape {
+  init(name) { this.name = name; }
+  describe() { return this.name + " with area " + this.area(); }
+  area() { return 0; }
+}
+class Square < Shape {
+  init(side) {
+    super.init("square");
+    this.side = side;
+  }
+  area() { return this.side * this.side; }
+}
+print Square(1.5).describe();
+
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+for (var i = 0; i < 8; i = i + 1) {
+  if (i == 3 or i == 5) print fib(i);
+}
+for (var i = 0; i < 8; i = i + 1) {
+  if (i == 1) continue;
+  if (i == 4) break;
+  print i;
+}
+var n = 0;
+while (true) {
+  n = n + 1;
+  if (n < 3) continue;
+  print n;
+  break;
+}
+var none;
+none = nil;
+print !none and "truthy";
+
+print 1000000000000000000000 * 10;
+print 1 / 3;
+print 0.0000001;
+print -0;
+print "a" + 1.5 + nil;
+print nil + "b" + 2;
+print globals();
+"#;
+
+fn transpile(src: &str) -> Result<String, TranspileError> {
+  let (stmts, errors) = Parser::new(src).parse();
+  assert!(errors.is_empty(), "{errors:?}");
+  rust::transpile(&stmts)
+}
+
+fn transpile_js(src: &str) -> Result<String, TranspileError> {
+  let (stmts, errors) = Parser::new(src).parse();
+  assert!(errors.is_empty(), "{errors:?}");
+  js::transpile(&stmts)
+}
+
+fn lines(stdout: Vec<u8>) -> Vec<String> {
+  String::from_utf8(stdout).unwrap().lines().map(String::from).collect()
+}
+
+#[test]
+fn translated_program_matches_interpreter() {
+  let out = transpile(PROGRAM).unwrap();
+
+  let dir = env::temp_dir().join(format!("rtlox-transpile-{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let source = dir.join("main.rs");
+  let binary: PathBuf = dir.join("main");
+  fs::write(&source, out).unwrap();
+
+  let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+  let build = Command::new(rustc).arg("--edition=2021").arg("-o").arg(&binary).arg(&source).output().unwrap();
+  assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+
+  let run = Command::new(&binary).output().unwrap();
+  fs::remove_dir_all(&dir).unwrap();
+  assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+
+  assert_eq!(lines(run.stdout), run_capture(PROGRAM).unwrap());
+}
+
+/// Runs a translated script with Node, or the runtime named by `NODE`.
+fn run_js(src: String, name: &str) -> Output {
+  let path = env::temp_dir().join(format!("rtlox-{name}-{}.js", std::process::id()));
+  fs::write(&path, src).unwrap();
+  let node = env::var("NODE").unwrap_or_else(|_| "node".into());
+  let run = Command::new(node).arg(&path).output();
+  fs::remove_file(&path).unwrap();
+  run.expect("a JavaScript runtime to run the translated script")
+}
+
+#[test]
+#[ignore = "needs a JavaScript runtime; run with `--ignored`"]
+fn translated_js_matches_interpreter() {
+  let run = run_js(transpile_js(PROGRAM).unwrap(), "program");
+  assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+  assert_eq!(lines(run.stdout), run_capture(PROGRAM).unwrap());
+}
+
+#[test]
+#[ignore = "needs a JavaScript runtime; run with `--ignored`"]
+fn translated_js_reports_runtime_errors() {
+  let run = run_js(transpile_js("var a;\nprint a;").unwrap(), "error");
+  assert_eq!(run.status.code(), Some(70));
+  assert_eq!(
+    String::from_utf8_lossy(&run.stderr).trim(),
+    "Variable `a` uninitialized before access; at position 13"
+  );
+}
+
+#[test]
+fn unsupported_features_are_reported() {
+  let err = transpile("enum Color { Red }").unwrap_err();
+  assert!(matches!(err, TranspileError::Unsupported { what: "enums", .. }), "{err}");
+
+  let err = transpile("namespace N { var a; }").unwrap_err();
+  assert!(err.to_string().starts_with("Cannot translate namespaces yet"), "{err}");
+
+  let err = transpile_js("fun f() { print this; }").unwrap_err();
+  assert!(matches!(err, TranspileError::Unsupported { what: "`this` outside of a method", .. }), "{err}");
+}
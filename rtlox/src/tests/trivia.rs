@@ -0,0 +1,104 @@
+use rtlox::{
+  ast::stmt::Stmt,
+  interpreter::Interpreter,
+  parser::{trivia::Comments, Parser},
+  resolver::{error::{ErrorType, Lint}, Resolver},
+  span::Span,
+};
+
+fn parse(src: &str) -> (Vec<Stmt>, Comments) {
+  let mut parser = Parser::new(src);
+  parser.options.keep_comments = true;
+  let ((stmts, errors), comments) = parser.parse_with_comments();
+  assert!(errors.is_empty(), "{errors:?}");
+  (stmts, comments)
+}
+
+fn texts(comments: &[rtlox::parser::trivia::Comment]) -> Vec<&str> {
+  comments.iter().map(|c| c.text.as_str()).collect()
+}
+
+#[test]
+fn comments_lead_and_trail_statements() {
+  let src = "// first\n/* also first */\nvar a = 1; // after a\nprint a; /* after print */";
+  let (stmts, comments) = parse(src);
+
+  let a = comments.get(stmts[0].span()).unwrap();
+  assert_eq!(texts(&a.leading), ["// first", "/* also first */"]);
+  assert_eq!(texts(&a.trailing), ["// after a"]);
+
+  let print = comments.get(stmts[1].span()).unwrap();
+  assert!(print.leading.is_empty());
+  assert_eq!(texts(&print.trailing), ["/* after print */"]);
+  assert!(comments.dangling.is_empty());
+}
+
+#[test]
+fn comments_attach_inside_bodies() {
+  let src = "fun f() { // opens f\n  // about return\n  return 1;\n  // closes f\n}\n// about g\nvar g;";
+  let (stmts, comments) = parse(src);
+  let Stmt::FunDecl(f) = &stmts[0] else { panic!("expected a function") };
+
+  let ret = comments.get(f.body[0].span()).unwrap();
+  assert_eq!(texts(&ret.leading), ["// opens f", "// about return"]);
+  assert!(comments.get(f.span).is_none());
+  assert_eq!(texts(&comments.get(stmts[1].span()).unwrap().leading), ["// about g"]);
+  assert_eq!(texts(&comments.dangling), ["// closes f"]);
+}
+
+#[test]
+fn trailing_comment_goes_to_outermost_statement() {
+  let (stmts, comments) = parse("{ print 1; } // block");
+  assert_eq!(texts(&comments.get(stmts[0].span()).unwrap().trailing), ["// block"]);
+}
+
+#[test]
+fn comments_are_dropped_by_default() {
+  let ((stmts, _), comments) = Parser::new("// note\nprint 1;").parse_with_comments();
+  assert_eq!(stmts.len(), 1);
+  assert!(comments.is_empty());
+}
+
+#[test]
+fn block_comments_end_at_their_delimiter() {
+  let (stmts, comments) = parse("/* a */print 1;/* b */");
+  assert_eq!(stmts.len(), 1);
+  let print = comments.get(stmts[0].span()).unwrap();
+  assert_eq!(texts(&print.leading), ["/* a */"]);
+  assert_eq!(texts(&print.trailing), ["/* b */"]);
+}
+
+#[test]
+fn ignore_comments_cover_the_first_line_of_the_statement_they_lead() {
+  let src = "// lox-ignore: no-effect, unused-variable\nfun f() {\n  1;\n}\nvar a; // lox-ignore: no-effect\n";
+  let ((_, errors), ignores) = Parser::new(src).parse_with_ignores();
+  assert!(errors.is_empty(), "{errors:?}");
+  assert_eq!(ignores.len(), 1, "{ignores:?}");
+  assert_eq!(ignores[0].line, Span(42, 51));
+  assert_eq!(ignores[0].names, ["no-effect", "unused-variable"]);
+}
+
+#[test]
+fn ignore_comments_suppress_the_warnings_they_name() {
+  let src = "{\n  // lox-ignore: unused-variable\n  var a;\n  var b;\n  // lox-ignore: no-effect, unknown\n  1;\n}";
+  let ((stmts, _), ignores) = Parser::new(src).parse_with_ignores();
+  let mut interpreter = Interpreter::new();
+  let mut resolver = Resolver::new(&mut interpreter);
+  resolver.set_ignores(ignores);
+  let (ok, errors) = resolver.resolve(&stmts);
+  assert!(!ok);
+
+  let mut suppressed: Vec<_> = errors.iter()
+    .filter(|err| matches!(err.kind, ErrorType::Suppressed))
+    .map(|err| err.lint)
+    .collect();
+  suppressed.sort_by_key(|lint| lint.map(Lint::name));
+  assert_eq!(suppressed, [Some(Lint::NoEffect), Some(Lint::UnusedVariable)]);
+
+  let mut warnings: Vec<_> = errors.iter()
+    .filter(|err| matches!(err.kind, ErrorType::Warning))
+    .map(|err| err.message.as_str())
+    .collect();
+  warnings.sort();
+  assert_eq!(warnings, ["Unknown warning `unknown` in `lox-ignore` comment", "Unused variable `b`"]);
+}
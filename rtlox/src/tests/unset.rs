@@ -0,0 +1,51 @@
+use rtlox::{
+  interpreter::{error::RuntimeError, Interpreter},
+  resolver::error::ErrorType,
+};
+
+use super::{resolve_on, run_capture as run};
+
+/// Messages of the resolver errors (not warnings) reported for `src`.
+fn resolve_errors(src: &str) -> Vec<String> {
+  resolve_on(&mut Interpreter::new(), src)
+    .1
+    .into_iter()
+    .filter(|err| matches!(err.kind, ErrorType::Error))
+    .map(|err| err.message)
+    .collect()
+}
+
+#[test]
+fn read_before_assignment_is_rejected() {
+  let errors = resolve_errors("{ var a; print a; }");
+  assert_eq!(errors, ["Cannot read local variable `a` before it is assigned"]);
+}
+
+#[test]
+fn assignment_before_read_is_accepted() {
+  assert!(resolve_errors("{ var a; a = 1; print a; }").is_empty());
+  // any assignment counts, even one that may not run
+  assert!(resolve_errors("{ var a; if (true) a = 1; print a; }").is_empty());
+}
+
+#[test]
+fn loops_and_closures_are_left_to_runtime() {
+  // a later iteration may read the value assigned by an earlier one
+  assert!(resolve_errors("{ var a; for (var i = 0; i < 2; i = i + 1) { if (i > 0) print a; a = i; } }").is_empty());
+  // the function may be called after `a` is assigned
+  assert!(resolve_errors("{ var a; fun f() { print a; } a = 1; f(); }").is_empty());
+  // a fresh variable in each iteration is still checked
+  assert_eq!(resolve_errors("while (false) { var a; print a; }").len(), 1);
+}
+
+#[test]
+fn unassigned_read_is_a_runtime_error() {
+  let err = run("{ var a; fun f() { print a; } f(); }").unwrap_err();
+  assert!(matches!(err, RuntimeError::UnsetVariable { .. }), "{err}");
+
+  // globals are only checked at runtime
+  let err = run("var b; if (b) print 1;").unwrap_err();
+  assert!(matches!(err, RuntimeError::UnsetVariable { .. }), "{err}");
+
+  assert!(run("var c; c = 1; if (c) print c;").is_ok());
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use std::fmt::{self, Display};
+use std::{fmt::{self, Display}, rc::Rc};
 
 use crate::parser::scanner::error::ScanError;
 use crate::span::Span;
@@ -8,7 +8,8 @@ use crate::span::Span;
 // #[cfg(test)]
 // mod tests;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenType {
   // single character
   LeftParen,
@@ -20,12 +21,13 @@ pub enum TokenType {
   Minus,
   Plus,
   Semicolon,
+  Colon,
   Star,
 
   // one, two chars
   Slash,
-  Comment(String),
-  BlockComment(String),
+  Comment,
+  BlockComment,
   Bang,
   BangEqual,
   Equal,
@@ -35,25 +37,36 @@ pub enum TokenType {
   Less,
   LessEqual,
 
-  // literals
-  Identifier(String),
-  String(String),
+  // literals, sharing one allocation per distinct text
+  Identifier(Rc<str>),
+  String(Rc<str>),
   Number(f64),
-  Whitespace(String),
+  Whitespace,
 
   // keywords
   And,
+  Break,
+  Case,
   Class,
+  Continue,
+  Default,
   Else,
+  Enum,
+  Export,
   False,
   Fun,
   For,
   If,
+  Import,
+  Namespace,
+  Native,
+  #[default]
   Nil,
   Or,
   Print,
   Return,
   Super,
+  Switch,
   This,
   True,
   Var,
@@ -67,7 +80,7 @@ pub enum TokenType {
 
 impl TokenType {
   pub fn new() -> Self {
-    Self::Nil
+    Self::default()
   }
 
   pub fn lexeme(&self) -> String {
@@ -87,18 +100,63 @@ impl TokenType {
       ),
     }
   }
-}
 
-impl From<&str> for TokenType {
-  fn from(value: &str) -> Self {
+  /// Comments and whitespace, which the parser skips.
+  pub fn is_trivia(&self) -> bool {
+    use TokenType::*;
+    matches!(self, Comment | BlockComment | Whitespace)
+  }
+
+  /// Keywords that begin a declaration or statement. The parser resynchronizes on these after an
+  /// error.
+  pub fn is_statement_start(&self) -> bool {
+    use TokenType::*;
+    matches!(
+      self,
+      Break | Class | Continue | Enum | Export | For | Fun | If | Import | Namespace | Native | Print | Return |
+        Switch | Var | While
+    )
+  }
+
+  /// Operators written between their two operands.
+  pub fn is_binary_op(&self) -> bool {
+    use TokenType::*;
+    matches!(
+      self,
+      Minus | Plus | Slash | Star | BangEqual | EqualEqual |
+      Greater | GreaterEqual | Less | LessEqual | And | Or
+    )
+  }
+
+  /// Operators written before their operand.
+  pub fn is_unary_op(&self) -> bool {
+    matches!(self, TokenType::Bang | TokenType::Minus)
+  }
+
+  /// Tokens that stand for a value on their own.
+  pub fn is_literal(&self) -> bool {
+    use TokenType::*;
+    matches!(self, Number(_) | String(_) | True | False | Nil)
+  }
+
+  /// Returns the keyword spelled by `text`, if any.
+  pub fn keyword(text: &str) -> Option<Self> {
     use TokenType::*;
-    match value {
+    let keyword = match text {
       "nil" => Nil,
       "true" => True,
       "false" => False,
       "this" => This,
       "super" => Super,
       "class" => Class,
+      "namespace" => Namespace,
+      "native" => Native,
+      "import" => Import,
+      "export" => Export,
+      "enum" => Enum,
+      "switch" => Switch,
+      "case" => Case,
+      "default" => Default,
       "and" => And,
       "or" => Or,
       "if" => If,
@@ -107,12 +165,21 @@ impl From<&str> for TokenType {
       "fun" => Fun,
       "for" => For,
       "while" => While,
+      "break" => Break,
+      "continue" => Continue,
       "var" => Var,
       "print" => Print,
       // "typeof" => Typeof,
       // "show" => Show,
-      identifier => Identifier(identifier.to_string()),
-    }
+      _ => return None,
+    };
+    Some(keyword)
+  }
+}
+
+impl From<&str> for TokenType {
+  fn from(value: &str) -> Self {
+    Self::keyword(value).unwrap_or_else(|| Self::Identifier(value.into()))
   }
 }
 
@@ -135,6 +202,7 @@ impl Display for TokenType {
       Minus => f.write_str("-"),
       Plus => f.write_str("+"),
       Semicolon => f.write_str(";"),
+      Colon => f.write_str(":"),
       Slash => f.write_str("/"),
       Star => f.write_str("*"),
       Bang => f.write_str("!"),
@@ -148,17 +216,27 @@ impl Display for TokenType {
 
       // keywords
       And => f.write_str("and"),
+      Break => f.write_str("break"),
+      Case => f.write_str("case"),
       Class => f.write_str("class"),
+      Continue => f.write_str("continue"),
+      Default => f.write_str("default"),
       Else => f.write_str("else"),
+      Enum => f.write_str("enum"),
+      Export => f.write_str("export"),
       False => f.write_str("false"),
       Fun => f.write_str("fun"),
       For => f.write_str("for"),
       If => f.write_str("if"),
+      Import => f.write_str("import"),
+      Namespace => f.write_str("namespace"),
+      Native => f.write_str("native"),
       Nil => f.write_str("nil"),
       Or => f.write_str("or"),
       Print => f.write_str("print"),
       Return => f.write_str("return"),
       Super => f.write_str("super"),
+      Switch => f.write_str("switch"),
       This => f.write_str("this"),
       True => f.write_str("true"),
       Var => f.write_str("var"),
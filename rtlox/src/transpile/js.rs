@@ -0,0 +1,338 @@
+//! Translates a parsed program to JavaScript, for running scripts in a browser.
+//!
+//! Where JavaScript disagrees with Lox, such as which values are truthy, how numbers are printed or
+//! what `+` accepts, the generated code calls into the runtime in `prelude.js` instead of using the
+//! operator directly. Lox values map onto JavaScript ones: `nil` is `null`, numbers, strings and
+//! booleans are themselves, and functions, classes and instances are objects of the runtime.
+//!
+//! Scoping follows [`super::rust`]: globals are bound late through a runtime map, locals become
+//! `let` bindings that closures capture as they are, and reading a variable before it is assigned
+//! is a runtime error.
+
+use std::fmt::Write;
+
+use crate::{
+  ast::{
+    expr::Expr,
+    stmt::{self, Stmt},
+  },
+  data::LoxValue,
+  span::Span,
+  token::TokenType,
+  transpile::{unsupported, untranslated, Code, Emit, FunKind, TranspileError},
+};
+
+const PRELUDE: &str = include_str!("prelude.js");
+
+/// Returns the JavaScript source of a program running `stmts`.
+pub fn transpile(stmts: &[Stmt]) -> Result<String, TranspileError> {
+  let mut emitter = Emitter::default();
+  emitter.stmts(stmts)?;
+
+  let mut out = String::from(PRELUDE);
+  out.push_str("\nfunction script() {\n");
+  out.push_str(&emitter.code.out);
+  out.push_str("}\n\nrun();\n");
+  Ok(out)
+}
+
+#[derive(Default)]
+struct Emitter {
+  code: Code,
+}
+
+impl Emit for Emitter {
+  fn code(&self) -> &Code {
+    &self.code
+  }
+
+  fn code_mut(&mut self) -> &mut Code {
+    &mut self.code
+  }
+}
+
+impl Emitter {
+  /// Emits `body` inside a JavaScript block that is also a Lox scope, opened by `head`.
+  fn block(
+    &mut self,
+    head: impl AsRef<str>,
+    body: impl FnOnce(&mut Self) -> Result<(), TranspileError>,
+  ) -> Result<(), TranspileError> {
+    self.scoped(format!("{}{{", head.as_ref()), "}", body)
+  }
+
+  fn stmts(&mut self, stmts: &[Stmt]) -> Result<(), TranspileError> {
+    stmts.iter().try_for_each(|stmt| self.stmt(stmt))
+  }
+
+  fn stmt(&mut self, stmt: &Stmt) -> Result<(), TranspileError> {
+    use Stmt::*;
+    match stmt {
+      VarDecl(var) => {
+        let value = match &var.init {
+          Some(init) => self.expr(init)?,
+          None => "UNSET".into(),
+        };
+        self.define(&var.name.name, value);
+      }
+      FunDecl(fun) => {
+        // declared first, so that the function can call itself
+        self.declare(&fun.name.name);
+        let value = self.function(fun, FunKind::Function)?;
+        self.define(&fun.name.name, value);
+      }
+      ClassDecl(class) => self.class(class)?,
+      If(if_stmt) => {
+        let cond = self.expr(&if_stmt.cond)?;
+        self.block(format!("if (truthy({cond})) "), |this| this.stmt(&if_stmt.then_branch))?;
+        if let Some(else_branch) = &if_stmt.else_branch {
+          self.block("else ", |this| this.stmt(else_branch))?;
+        }
+      }
+      Switch(switch) => {
+        let subject = self.expr(&switch.subject)?;
+        self.block("", |this| {
+          this.line(format!("const subject = {subject};"));
+          for (i, case) in switch.cases.iter().enumerate() {
+            let value = this.expr(&case.value)?;
+            let keyword = if i == 0 { "if" } else { "else if" };
+            this.block(format!("{keyword} (equals(subject, {value})) "), |this| this.stmts(&case.body))?;
+          }
+          if let Some(default) = &switch.default {
+            let keyword = if switch.cases.is_empty() { "" } else { "else " };
+            this.block(keyword, |this| this.stmts(default))?;
+          }
+          Ok(())
+        })?;
+      }
+      While(while_stmt) => {
+        let cond = self.expr(&while_stmt.cond)?;
+        let head = match &while_stmt.incr {
+          Some(incr) => format!("for (; truthy({cond}); {}) ", self.expr(incr)?),
+          None => format!("while (truthy({cond})) "),
+        };
+        self.block(head, |this| this.stmt(&while_stmt.body))?;
+      }
+      Break(_) => self.line("break;"),
+      Continue(_) => self.line("continue;"),
+      Print(print) => {
+        let value = self.expr(&print.expr)?;
+        self.line(format!("print({value});"));
+      }
+      Return(ret) => {
+        let value = match (&ret.value, self.code.kind) {
+          (_, FunKind::Initializer) => "v_this".to_string(),
+          (Some(value), _) => self.expr(value)?,
+          (None, _) => "null".into(),
+        };
+        match self.code.kind {
+          FunKind::Script => return Err(unsupported("`return` outside of a function", ret.span)),
+          _ => self.line(format!("return {value};")),
+        }
+      }
+      Block(block) => self.block("", |this| this.stmts(&block.stmts))?,
+      Expr(expr) => {
+        let value = self.expr(&expr.expr)?;
+        self.line(format!("{value};"));
+      }
+      EnumDecl(_) | Time(_) | Namespace(_) | Import(_) | NativeDecl(_) | Export(_) | Dummy(_) => {
+        return Err(untranslated(stmt))
+      }
+    }
+    Ok(())
+  }
+
+  /// Binds `name` to `value` in the current scope, or as a global at the top level.
+  fn define(&mut self, name: &str, value: String) {
+    match self.code.scopes.is_empty() {
+      true => self.line(format!("defineGlobal({}, {value});", string(name))),
+      false => {
+        self.line(format!("let v_{name} = {value};"));
+        self.declare(name);
+      }
+    }
+  }
+
+  /// Emits the body of a function into a separate buffer, with its parameters in a new scope.
+  fn body(&mut self, params: &[String], body: &[Stmt], kind: FunKind) -> Result<String, TranspileError> {
+    self.function_body(params.to_vec(), kind, |this| {
+      this.stmts(body)?;
+      let ret = match kind {
+        FunKind::Initializer => "v_this",
+        _ => "null",
+      };
+      this.line(format!("return {ret};"));
+      Ok(())
+    })
+  }
+
+  /// Returns an arrow function taking `params`, prefixed with `v_`.
+  fn arrow(&mut self, params: &[String], body: &[Stmt], kind: FunKind) -> Result<String, TranspileError> {
+    let body = self.body(params, body, kind)?;
+    let params: Vec<String> = params.iter().map(|param| format!("v_{param}")).collect();
+    let pad = self.code.pad();
+    Ok(format!("({}) => {{\n{body}{pad}}}", params.join(", ")))
+  }
+
+  fn function(&mut self, fun: &stmt::FunDecl, kind: FunKind) -> Result<String, TranspileError> {
+    let params: Vec<String> = fun.params.iter().map(|p| p.name.to_string()).collect();
+    let body = self.arrow(&params, &fun.body, kind)?;
+    Ok(format!("new LoxFunction({}, {}, {body})", string(&fun.name.name), params.len()))
+  }
+
+  fn class(&mut self, class: &stmt::ClassDecl) -> Result<(), TranspileError> {
+    let name = &class.name.name;
+    let global = self.code.scopes.is_empty();
+    if !global {
+      self.line(format!("let v_{name};"));
+      self.declare(name);
+    }
+    let superclass = match &class.super_name {
+      Some(super_name) => {
+        let value = self.variable(&super_name.name, super_name.span);
+        Some(format!("superclass({value}, {})", string(&super_name.span.to_string())))
+      }
+      None => None,
+    };
+
+    self.block("", |this| {
+      if let Some(superclass) = &superclass {
+        this.line(format!("const v_super = {superclass};"));
+        this.declare("super");
+      }
+      this.line("const methods = new Map();");
+      for method in &class.methods {
+        let kind = match method.name.name == "init" {
+          true => FunKind::Initializer,
+          false => FunKind::Function,
+        };
+        // `this` comes in as the first argument
+        let mut params = vec!["this".to_string()];
+        params.extend(method.params.iter().map(|p| p.name.to_string()));
+        let body = this.arrow(&params, &method.body, kind)?;
+        this.line(format!(
+          "methods.set({}, new LoxMethod({}, {body}));",
+          string(&method.name.name),
+          method.params.len()
+        ));
+      }
+      let superclass = match superclass {
+        Some(_) => "v_super",
+        None => "null",
+      };
+      let value = format!("new LoxClass({}, {superclass}, methods)", string(name));
+      match global {
+        true => this.line(format!("defineGlobal({}, {value});", string(name))),
+        false => this.line(format!("v_{name} = {value};")),
+      }
+      Ok(())
+    })
+  }
+
+  fn variable(&self, name: &str, span: Span) -> String {
+    let at = string(&span.to_string());
+    match self.is_local(name) {
+      true => format!("load(v_{name}, {}, {at})", string(name)),
+      false => format!("getGlobal({}, {at})", string(name)),
+    }
+  }
+
+  fn expr(&mut self, expr: &Expr) -> Result<String, TranspileError> {
+    use Expr::*;
+    let code = match expr {
+      Var(var) => self.variable(&var.name.name, var.name.span),
+      Assignment(assign) => {
+        let value = self.expr(&assign.value)?;
+        let name = &assign.name.name;
+        match self.is_local(name) {
+          true => format!("(v_{name} = {value})"),
+          false => format!("setGlobal({}, {value}, {})", string(name), string(&assign.name.span.to_string())),
+        }
+      }
+      Lambda(lambda) => self.function(&lambda.decl, FunKind::Function)?,
+      Call(call) => {
+        let callee = self.expr(&call.callee)?;
+        let args = call.args.iter().map(|arg| self.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        format!("call({callee}, [{}], {}, {})", args.join(", "), string(&call.span.to_string()), call.line)
+      }
+      Get(get) => {
+        let obj = self.expr(&get.obj)?;
+        format!("get({obj}, {}, {})", string(&get.name.name), string(&get.name.span.to_string()))
+      }
+      Set(set) => {
+        let obj = self.expr(&set.obj)?;
+        let value = self.expr(&set.value)?;
+        format!("set({obj}, {}, {value}, {})", string(&set.name.name), string(&set.name.span.to_string()))
+      }
+      This(this) => match self.is_local("this") {
+        true => "v_this".into(),
+        false => return Err(unsupported("`this` outside of a method", this.span)),
+      },
+      Super(sup) => match self.is_local("super") {
+        true => format!(
+          "getSuper(v_super, {}, v_this, {})",
+          string(&sup.method.name),
+          string(&sup.method.span.to_string())
+        ),
+        false => return Err(unsupported("`super` outside of a subclass", sup.span)),
+      },
+      Lit(lit) => match &lit.value {
+        LoxValue::Nil => "null".into(),
+        LoxValue::Boolean(b) => b.to_string(),
+        LoxValue::Number(n) => format!("{n:?}"),
+        LoxValue::String(s) => string(s),
+        _ => return Err(unsupported("this literal", lit.span)),
+      },
+      Group(group) => self.expr(&group.expr)?,
+      Unary(unary) => {
+        let operand = self.expr(&unary.operand)?;
+        match unary.operator.kind {
+          TokenType::Minus => format!("negate({operand}, {})", string(&unary.operator.span.to_string())),
+          _ => format!("!truthy({operand})"),
+        }
+      }
+      Binary(binary) => {
+        let left = self.expr(&binary.left)?;
+        let right = self.expr(&binary.right)?;
+        let at = string(&binary.operator.span.to_string());
+        use TokenType as T;
+        match &binary.operator.kind {
+          T::EqualEqual => format!("equals({left}, {right})"),
+          T::BangEqual => format!("!equals({left}, {right})"),
+          T::Plus => format!("add({left}, {right}, {at})"),
+          T::Comma => format!("({left}, {right})"),
+          op @ (T::Minus | T::Star | T::Slash) => format!("arith(\"{op}\", {left}, {right}, {at})"),
+          op => format!("compare(\"{op}\", {left}, {right}, {at})"),
+        }
+      }
+      Logical(logical) => {
+        let left = self.expr(&logical.left)?;
+        let right = self.expr(&logical.right)?;
+        match logical.operator.kind {
+          TokenType::And => format!("((left) => (truthy(left) ? {right} : left))({left})"),
+          _ => format!("((left) => (truthy(left) ? left : {right}))({left})"),
+        }
+      }
+    };
+    Ok(code)
+  }
+}
+
+/// A JavaScript string literal holding `s`.
+fn string(s: &str) -> String {
+  let mut out = String::with_capacity(s.len() + 2);
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
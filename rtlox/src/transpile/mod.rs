@@ -0,0 +1,176 @@
+//! Experimental backends that translate a parsed program to another language instead of running
+//! it. Features without a translation are reported rather than skipped.
+
+use std::{
+  error::Error,
+  fmt::{self, Display, Write},
+  mem,
+  str::FromStr,
+};
+
+use crate::{ast::stmt::Stmt, span::Span};
+
+pub mod js;
+pub mod rust;
+
+/// Language to translate to, chosen with `--emit=<target>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+  Rust,
+  Js,
+}
+
+impl FromStr for Target {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "rust" => Ok(Target::Rust),
+      "js" => Ok(Target::Js),
+      _ => Err("`--emit` must be `rust` or `js`"),
+    }
+  }
+}
+
+/// Returns the source of a program in `target` running `stmts`.
+pub fn transpile(stmts: &[Stmt], target: Target) -> Result<String, TranspileError> {
+  match target {
+    Target::Rust => rust::transpile(stmts),
+    Target::Js => js::transpile(stmts),
+  }
+}
+
+/// What `return` means in the function being emitted.
+#[derive(Clone, Copy, PartialEq)]
+enum FunKind {
+  Script,
+  Function,
+  Initializer,
+}
+
+/// The code an emitter has generated so far, and the Lox scopes open at its end.
+struct Code {
+  out: String,
+  indent: usize,
+  /// Names of the locals in each open scope, innermost last.
+  scopes: Vec<Vec<String>>,
+  kind: FunKind,
+}
+
+impl Default for Code {
+  fn default() -> Self {
+    Self { out: String::new(), indent: 1, scopes: Vec::new(), kind: FunKind::Script }
+  }
+}
+
+impl Code {
+  fn pad(&self) -> String {
+    "  ".repeat(self.indent)
+  }
+
+  fn is_local(&self, name: &str) -> bool {
+    self.scopes.iter().any(|scope| scope.iter().any(|local| local == name))
+  }
+}
+
+/// Indentation, scopes and function bodies, shared by the emitters of every target.
+trait Emit: Sized {
+  fn code(&self) -> &Code;
+
+  fn code_mut(&mut self) -> &mut Code;
+
+  fn line(&mut self, code: impl AsRef<str>) {
+    let out = self.code_mut();
+    let pad = out.pad();
+    writeln!(out.out, "{pad}{}", code.as_ref()).unwrap();
+  }
+
+  fn is_local(&self, name: &str) -> bool {
+    self.code().is_local(name)
+  }
+
+  fn declare(&mut self, name: &str) {
+    if let Some(scope) = self.code_mut().scopes.last_mut() {
+      scope.push(name.into());
+    }
+  }
+
+  /// Emits `body` between the lines `open` and `close`, in a new Lox scope.
+  fn scoped(
+    &mut self,
+    open: impl AsRef<str>,
+    close: &str,
+    body: impl FnOnce(&mut Self) -> Result<(), TranspileError>,
+  ) -> Result<(), TranspileError> {
+    self.line(open);
+    self.code_mut().indent += 1;
+    self.code_mut().scopes.push(Vec::new());
+    let res = body(self);
+    self.code_mut().scopes.pop();
+    self.code_mut().indent -= 1;
+    self.line(close);
+    res
+  }
+
+  /// Emits the body of a function of `kind` into a separate buffer, one level deeper and in a
+  /// new scope holding `locals`, and returns that buffer.
+  fn function_body(
+    &mut self,
+    locals: Vec<String>,
+    kind: FunKind,
+    body: impl FnOnce(&mut Self) -> Result<(), TranspileError>,
+  ) -> Result<String, TranspileError> {
+    let code = self.code_mut();
+    let out = mem::take(&mut code.out);
+    let outer_kind = mem::replace(&mut code.kind, kind);
+    code.indent += 1;
+    code.scopes.push(locals);
+
+    let res = body(self);
+
+    let code = self.code_mut();
+    code.scopes.pop();
+    code.indent -= 1;
+    code.kind = outer_kind;
+    let body = mem::replace(&mut code.out, out);
+    res.map(|_| body)
+  }
+}
+
+/// The error for a statement that no target can translate yet.
+fn untranslated(stmt: &Stmt) -> TranspileError {
+  use Stmt::*;
+  let what = match stmt {
+    EnumDecl(_) => "enums",
+    Time(_) => "`time` blocks",
+    Namespace(_) => "namespaces",
+    Import(_) => "imports",
+    NativeDecl(_) => "native functions",
+    Export(_) => "exports",
+    Dummy(_) => "statements that failed to parse",
+    _ => "this statement",
+  };
+  unsupported(what, stmt.span())
+}
+
+fn unsupported(what: &'static str, span: Span) -> TranspileError {
+  TranspileError::Unsupported { what, span }
+}
+
+#[derive(Debug, Clone)]
+pub enum TranspileError {
+  Unsupported { what: &'static str, span: Span },
+}
+
+impl Display for TranspileError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use TranspileError::*;
+    match self {
+      Unsupported { what, span } => {
+        write!(f, "Cannot translate {what} yet; at position {span}")
+      }
+    }
+  }
+}
+
+impl Error for TranspileError {}
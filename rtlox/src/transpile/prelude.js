@@ -0,0 +1,295 @@
+// Runtime support for Lox programs translated to JavaScript by rtlox. It is pasted at the top of
+// every generated program, which defines `script()` below it and runs in Node or a browser.
+"use strict";
+
+/** Held by a variable declared without an initializer until it is first assigned. */
+const UNSET = Symbol("unset");
+
+class LoxError extends Error {}
+
+class LoxFunction {
+  constructor(name, arity, body) {
+    this.name = name;
+    this.arity = arity;
+    this.body = body;
+  }
+}
+
+class LoxMethod {
+  constructor(arity, body) {
+    this.arity = arity;
+    this.body = body;
+  }
+}
+
+class LoxClass {
+  constructor(name, superclass, methods) {
+    this.name = name;
+    this.superclass = superclass;
+    this.methods = methods;
+  }
+}
+
+class LoxInstance {
+  constructor(klass) {
+    this.klass = klass;
+    this.fields = new Map();
+  }
+}
+
+function error(message, at) {
+  return new LoxError(`${message}; at position ${at}`);
+}
+
+/** Formats a number like Rust's `Display` for `f64`, which never uses exponents. */
+function formatNumber(n) {
+  if (Number.isNaN(n)) return "NaN";
+  if (!Number.isFinite(n)) return n > 0 ? "inf" : "-inf";
+  if (Object.is(n, -0)) return "-0";
+  const s = String(n);
+  const e = s.indexOf("e");
+  if (e < 0) return s;
+
+  const sign = s.startsWith("-") ? "-" : "";
+  const [int, frac = ""] = s.slice(sign.length, e).split(".");
+  const digits = int + frac;
+  const point = int.length + Number(s.slice(e + 1));
+  if (point <= 0) return `${sign}0.${"0".repeat(-point)}${digits}`;
+  if (point >= digits.length) return sign + digits + "0".repeat(point - digits.length);
+  return `${sign}${digits.slice(0, point)}.${digits.slice(point)}`;
+}
+
+function stringify(value) {
+  if (value === null || value === UNSET) return "nil";
+  if (typeof value === "number") return formatNumber(value);
+  if (value instanceof LoxFunction) return `<fun ${value.name}>`;
+  if (value instanceof LoxClass) return `<class ${value.name}>`;
+  if (value instanceof LoxInstance) return `<instance ${value.klass.name}>`;
+  return String(value);
+}
+
+function typeName(value) {
+  if (value === null || value === UNSET) return "nil";
+  if (typeof value === "boolean") return "boolean";
+  if (typeof value === "number") return "number";
+  if (typeof value === "string") return "string";
+  if (value instanceof LoxFunction) return "<func>";
+  if (value instanceof LoxClass) return "<class>";
+  return "<instance>";
+}
+
+/** Writes a line printed by the script. Replace it to send output elsewhere, e.g. to a page. */
+let writeLine = (line) => console.log(line);
+
+function print(value) {
+  writeLine(stringify(value));
+}
+
+/** Reads a variable of the script, which must have been assigned. */
+function load(value, name, at) {
+  if (value === UNSET) throw error(`Variable \`${name}\` uninitialized before access`, at);
+  return value;
+}
+
+const GLOBALS = new Map();
+
+function defineGlobal(name, value) {
+  GLOBALS.set(name, value);
+}
+
+function getGlobal(name, at) {
+  if (!GLOBALS.has(name)) throw error(`Undefined variable \`${name}\``, at);
+  return load(GLOBALS.get(name), name, at);
+}
+
+function setGlobal(name, value, at) {
+  if (!GLOBALS.has(name)) throw error(`Undefined variable \`${name}\``, at);
+  GLOBALS.set(name, value);
+  return value;
+}
+
+function truthy(value) {
+  return value !== null && value !== false;
+}
+
+function equals(a, b) {
+  return a === b;
+}
+
+function negate(value, at) {
+  if (typeof value !== "number") throw error(`Bad type for unary \`-\` operator: \`${typeName(value)}\``, at);
+  return -value;
+}
+
+function add(a, b, at) {
+  if (typeof a === "number" && typeof b === "number") return a + b;
+  if (typeof a === "string" || typeof b === "string") return stringify(a) + stringify(b);
+  throw error(
+    "Binary `+` operator can only operate over two numbers, or a string and any value. " +
+      `Got types \`${typeName(a)}\` and \`${typeName(b)}\``,
+    at,
+  );
+}
+
+function arith(op, a, b, at) {
+  if (typeof a !== "number" || typeof b !== "number") {
+    throw error(
+      `Binary \`${op}\` operator can only operate over two numbers. ` +
+        `Got types \`${typeName(a)}\` and \`${typeName(b)}\``,
+      at,
+    );
+  }
+  switch (op) {
+    case "-":
+      return a - b;
+    case "*":
+      return a * b;
+    default:
+      if (b === 0) throw error("Can not divide by zero", at);
+      return a / b;
+  }
+}
+
+function compare(op, a, b, at) {
+  const comparable = typeof a === typeof b && (typeof a === "number" || typeof a === "string");
+  if (!comparable) {
+    throw error(
+      `Binary \`${op}\` operator can only compare two numbers or two strings. ` +
+        `Got types \`${typeName(a)}\` and \`${typeName(b)}\``,
+      at,
+    );
+  }
+  switch (op) {
+    case "<":
+      return a < b;
+    case "<=":
+      return a <= b;
+    case ">":
+      return a > b;
+    default:
+      return a >= b;
+  }
+}
+
+function findMethod(klass, name) {
+  for (let current = klass; current !== null; current = current.superclass) {
+    const method = current.methods.get(name);
+    if (method !== undefined) return method;
+  }
+  return undefined;
+}
+
+function bind(klass, name, self) {
+  const method = findMethod(klass, name);
+  if (method === undefined) return undefined;
+  return new LoxFunction(name, method.arity, (...args) => method.body(self, ...args));
+}
+
+function get(object, name, at) {
+  if (!(object instanceof LoxInstance)) throw error("Only objects can have properties", at);
+  if (object.fields.has(name)) return object.fields.get(name);
+  const method = bind(object.klass, name, object);
+  if (method === undefined) throw error(`Undefined property \`${name}\``, at);
+  return method;
+}
+
+function set(object, name, value, at) {
+  if (!(object instanceof LoxInstance)) throw error("Only objects can have properties", at);
+  object.fields.set(name, value);
+  return value;
+}
+
+function getSuper(superclass, name, self, at) {
+  const method = bind(superclass, name, self);
+  if (method === undefined) throw error(`Undefined property \`${name}\``, at);
+  return method;
+}
+
+function superclass(value, at) {
+  if (!(value instanceof LoxClass)) throw error("Superclass must be a class", at);
+  return value;
+}
+
+/** Name of each function call running and the line it was made on, innermost last. */
+const CALLS = [];
+
+function call(callee, args, at, line) {
+  let arity;
+  if (callee instanceof LoxFunction) {
+    arity = callee.arity;
+  } else if (callee instanceof LoxClass) {
+    const init = findMethod(callee, "init");
+    arity = init === undefined ? 0 : init.arity;
+  } else {
+    throw error(`Type \`${typeName(callee)}\` is not callable. Can only call functions`, at);
+  }
+  if (arity !== args.length) throw error(`Expected ${arity} arguments, but got ${args.length}`, at);
+
+  if (callee instanceof LoxFunction) {
+    CALLS.push([callee.name, line]);
+    try {
+      return callee.body(...args);
+    } finally {
+      CALLS.pop();
+    }
+  }
+  const instance = new LoxInstance(callee);
+  const init = bind(callee, "init", instance);
+  if (init !== undefined) call(init, args, at, line);
+  return instance;
+}
+
+defineGlobal("clock", new LoxFunction("clock", 0, () => Date.now() / 1000));
+defineGlobal("globals", new LoxFunction("globals", 0, () => [...GLOBALS.keys()].sort().join(", ")));
+// the innermost call is the one to `callerInfo` itself
+defineGlobal("callerInfo", new LoxFunction("callerInfo", 0, () => {
+  if (CALLS.length < 2) return null;
+  const caller = CALLS.length > 2 ? CALLS[CALLS.length - 3][0] : "script";
+  return `${caller}:${CALLS[CALLS.length - 2][1]}`;
+}));
+
+/** Node's `process`, which the script's own `process` reaches the host through, if there is one. */
+const host = typeof process !== "undefined" ? process : undefined;
+/** Lines of standard input not read yet, read in full on the first `process.readLine()`. */
+let stdinLines;
+
+function readLine() {
+  if (stdinLines === undefined) {
+    stdinLines = host === undefined ? [] : require("fs").readFileSync(0, "utf8").split(/\r?\n/);
+    if (stdinLines[stdinLines.length - 1] === "") stdinLines.pop();
+  }
+  return stdinLines.length > 0 ? stdinLines.shift() : null;
+}
+
+/** Defines `process` as an instance holding its natives in fields, as the VM does. */
+function defineProcess() {
+  const args = host === undefined ? [] : host.argv.slice(2);
+  const members = [
+    new LoxFunction("argc", 0, () => args.length),
+    new LoxFunction("arg", 1, (n) => args[n] ?? null),
+    new LoxFunction("getenv", 1, (name) => host?.env[name] ?? null),
+    new LoxFunction("readLine", 0, readLine),
+    new LoxFunction("write", 1, (text) => {
+      host?.stdout.write(stringify(text));
+      return null;
+    }),
+    new LoxFunction("setExitCode", 1, (code) => {
+      if (host !== undefined) host.exitCode = code;
+      return null;
+    }),
+  ];
+  const instance = new LoxInstance(new LoxClass("process", null, new Map()));
+  for (const member of members) instance.fields.set(member.name, member);
+  defineGlobal("process", instance);
+}
+defineProcess();
+
+function run() {
+  try {
+    script();
+  } catch (err) {
+    if (!(err instanceof LoxError)) throw err;
+    console.error(err.message);
+    if (typeof process !== "undefined") process.exitCode = 70;
+  }
+}
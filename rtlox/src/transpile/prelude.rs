@@ -0,0 +1,401 @@
+// Runtime support for Lox programs translated to Rust by rtlox. This file is not part of the
+// rtlox crate: it is pasted at the top of every generated program, which must build on its own.
+
+#![allow(dead_code, unused_variables, unused_mut, unused_parens, unreachable_code, non_snake_case)]
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+#[derive(Clone)]
+enum Value {
+  Nil,
+  Bool(bool),
+  Num(f64),
+  Str(Rc<str>),
+  Fun(Rc<Function>),
+  Class(Rc<Class>),
+  Instance(Rc<Instance>),
+  /// Held by a variable declared without an initializer until it is first assigned.
+  Unset,
+}
+
+/// A variable, shared with the closures that capture it.
+type Cell = Rc<RefCell<Value>>;
+
+type Body = Rc<dyn Fn(Vec<Value>) -> Result<Value, Error>>;
+type MethodBody = Rc<dyn Fn(Value, Vec<Value>) -> Result<Value, Error>>;
+
+struct Function {
+  name: String,
+  arity: usize,
+  body: Body,
+}
+
+struct Method {
+  arity: usize,
+  body: MethodBody,
+}
+
+struct Class {
+  name: String,
+  superclass: Option<Rc<Class>>,
+  methods: HashMap<String, Method>,
+}
+
+struct Instance {
+  class: Rc<Class>,
+  fields: RefCell<HashMap<String, Value>>,
+}
+
+struct Error(String);
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+fn error<T>(message: String, at: &str) -> Result<T, Error> {
+  Err(Error(format!("{message}; at position {at}")))
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Value::Nil | Value::Unset => f.write_str("nil"),
+      Value::Bool(b) => write!(f, "{b}"),
+      Value::Num(n) if n.floor() == *n => write!(f, "{n:.0}"),
+      Value::Num(n) => write!(f, "{n}"),
+      Value::Str(s) => f.write_str(s),
+      Value::Fun(fun) => write!(f, "<fun {}>", fun.name),
+      Value::Class(class) => write!(f, "<class {}>", class.name),
+      Value::Instance(instance) => write!(f, "<instance {}>", instance.class.name),
+    }
+  }
+}
+
+impl Value {
+  fn type_name(&self) -> &'static str {
+    match self {
+      Value::Nil | Value::Unset => "nil",
+      Value::Bool(_) => "boolean",
+      Value::Num(_) => "number",
+      Value::Str(_) => "string",
+      Value::Fun(_) => "<func>",
+      Value::Class(_) => "<class>",
+      Value::Instance(_) => "<instance>",
+    }
+  }
+}
+
+fn cell(value: Value) -> Cell {
+  Rc::new(RefCell::new(value))
+}
+
+fn read(cell: &Cell) -> Value {
+  cell.borrow().clone()
+}
+
+/// Reads a variable of the script, which must have been assigned.
+fn load(cell: &Cell, name: &str, at: &str) -> Result<Value, Error> {
+  match read(cell) {
+    Value::Unset => error(format!("Variable `{name}` uninitialized before access"), at),
+    value => Ok(value),
+  }
+}
+
+fn assign(cell: &Cell, value: Value) -> Value {
+  *cell.borrow_mut() = value.clone();
+  value
+}
+
+thread_local! {
+  static GLOBALS: RefCell<HashMap<&'static str, Value>> = RefCell::new(HashMap::new());
+  /// Set by `process.setExitCode`.
+  static EXIT_CODE: std::cell::Cell<i32> = const { std::cell::Cell::new(0) };
+  /// Name of each function call running and the line it was made on, innermost last.
+  static CALLS: RefCell<Vec<(String, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn define_global(name: &'static str, value: Value) {
+  GLOBALS.with(|globals| globals.borrow_mut().insert(name, value));
+}
+
+fn get_global(name: &'static str, at: &str) -> Result<Value, Error> {
+  match GLOBALS.with(|globals| globals.borrow().get(name).cloned()) {
+    Some(Value::Unset) => error(format!("Variable `{name}` uninitialized before access"), at),
+    Some(value) => Ok(value),
+    None => error(format!("Undefined variable `{name}`"), at),
+  }
+}
+
+fn set_global(name: &'static str, value: Value, at: &str) -> Result<Value, Error> {
+  GLOBALS.with(|globals| match globals.borrow_mut().get_mut(name) {
+    Some(slot) => {
+      *slot = value.clone();
+      Ok(value)
+    }
+    None => error(format!("Undefined variable `{name}`"), at),
+  })
+}
+
+fn truthy(value: &Value) -> bool {
+  !matches!(value, Value::Nil | Value::Bool(false))
+}
+
+fn equals(a: &Value, b: &Value) -> bool {
+  match (a, b) {
+    (Value::Nil, Value::Nil) => true,
+    (Value::Bool(a), Value::Bool(b)) => a == b,
+    (Value::Num(a), Value::Num(b)) => a == b,
+    (Value::Str(a), Value::Str(b)) => a == b,
+    (Value::Fun(a), Value::Fun(b)) => Rc::ptr_eq(a, b),
+    (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+    (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+    _ => false,
+  }
+}
+
+fn negate(value: Value, at: &str) -> Result<Value, Error> {
+  match value {
+    Value::Num(n) => Ok(Value::Num(-n)),
+    other => error(format!("Bad type for unary `-` operator: `{}`", other.type_name()), at),
+  }
+}
+
+fn add(a: Value, b: Value, at: &str) -> Result<Value, Error> {
+  match (a, b) {
+    (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a + b)),
+    (a @ Value::Str(_), b) | (a, b @ Value::Str(_)) => Ok(Value::Str(format!("{a}{b}").into())),
+    (a, b) => error(
+      format!(
+        "Binary `+` operator can only operate over two numbers, or a string and any value. Got types `{}` and `{}`",
+        a.type_name(),
+        b.type_name()
+      ),
+      at,
+    ),
+  }
+}
+
+fn arith(op: &str, a: Value, b: Value, at: &str) -> Result<Value, Error> {
+  let (a, b) = match (a, b) {
+    (Value::Num(a), Value::Num(b)) => (a, b),
+    (a, b) => {
+      return error(
+        format!(
+          "Binary `{op}` operator can only operate over two numbers. Got types `{}` and `{}`",
+          a.type_name(),
+          b.type_name()
+        ),
+        at,
+      )
+    }
+  };
+  match op {
+    "-" => Ok(Value::Num(a - b)),
+    "*" => Ok(Value::Num(a * b)),
+    "/" if b == 0.0 => Err(Error(format!("Can not divide by zero; at position {at}"))),
+    _ => Ok(Value::Num(a / b)),
+  }
+}
+
+fn compare(op: &str, a: Value, b: Value, at: &str) -> Result<Value, Error> {
+  let ordering = match (&a, &b) {
+    (Value::Num(a), Value::Num(b)) => a.partial_cmp(b),
+    (Value::Str(a), Value::Str(b)) => a.partial_cmp(b),
+    _ => {
+      return error(
+        format!(
+          "Binary `{op}` operator can only compare two numbers or two strings. Got types `{}` and `{}`",
+          a.type_name(),
+          b.type_name()
+        ),
+        at,
+      )
+    }
+  };
+  let holds = match (op, ordering) {
+    (_, None) => false,
+    ("<", Some(o)) => o.is_lt(),
+    ("<=", Some(o)) => o.is_le(),
+    (">", Some(o)) => o.is_gt(),
+    (_, Some(o)) => o.is_ge(),
+  };
+  Ok(Value::Bool(holds))
+}
+
+fn find_method<'c>(class: &'c Rc<Class>, name: &str) -> Option<&'c Method> {
+  let mut class = Some(class);
+  while let Some(current) = class {
+    if let Some(method) = current.methods.get(name) {
+      return Some(method);
+    }
+    class = current.superclass.as_ref();
+  }
+  None
+}
+
+fn bind(class: &Rc<Class>, name: &str, this: &Value) -> Option<Value> {
+  let method = find_method(class, name)?;
+  let body = method.body.clone();
+  let this = this.clone();
+  Some(Value::Fun(Rc::new(Function {
+    name: name.into(),
+    arity: method.arity,
+    body: Rc::new(move |args| body(this.clone(), args)),
+  })))
+}
+
+fn get(object: Value, name: &str, at: &str) -> Result<Value, Error> {
+  let Value::Instance(instance) = &object else {
+    return error("Only objects can have properties".into(), at);
+  };
+  if let Some(value) = instance.fields.borrow().get(name) {
+    return Ok(value.clone());
+  }
+  match bind(&instance.class, name, &object) {
+    Some(method) => Ok(method),
+    None => error(format!("Undefined property `{name}`"), at),
+  }
+}
+
+fn set(object: Value, name: &str, value: Value, at: &str) -> Result<Value, Error> {
+  let Value::Instance(instance) = &object else {
+    return error("Only objects can have properties".into(), at);
+  };
+  instance.fields.borrow_mut().insert(name.into(), value.clone());
+  Ok(value)
+}
+
+fn get_super(superclass: Value, name: &str, this: Value, at: &str) -> Result<Value, Error> {
+  let Value::Class(class) = superclass else {
+    return error("Superclass must be a class".into(), at);
+  };
+  match bind(&class, name, &this) {
+    Some(method) => Ok(method),
+    None => error(format!("Undefined property `{name}`"), at),
+  }
+}
+
+fn superclass(value: Value, at: &str) -> Result<Rc<Class>, Error> {
+  match value {
+    Value::Class(class) => Ok(class),
+    _ => error("Superclass must be a class".into(), at),
+  }
+}
+
+fn call(callee: Value, args: Vec<Value>, at: &str, line: usize) -> Result<Value, Error> {
+  let arity = match &callee {
+    Value::Fun(fun) => fun.arity,
+    Value::Class(class) => find_method(class, "init").map_or(0, |init| init.arity),
+    other => {
+      return error(
+        format!("Type `{}` is not callable. Can only call functions", other.type_name()),
+        at,
+      )
+    }
+  };
+  if arity != args.len() {
+    return error(format!("Expected {arity} arguments, but got {}", args.len()), at);
+  }
+  match callee {
+    Value::Fun(fun) => {
+      CALLS.with(|calls| calls.borrow_mut().push((fun.name.clone(), line)));
+      let result = (fun.body)(args);
+      CALLS.with(|calls| calls.borrow_mut().pop());
+      result
+    }
+    Value::Class(class) => {
+      let instance = Value::Instance(Rc::new(Instance {
+        class: class.clone(),
+        fields: RefCell::new(HashMap::new()),
+      }));
+      if let Some(init) = bind(&class, "init", &instance) {
+        call(init, args, at, line)?;
+      }
+      Ok(instance)
+    }
+    _ => unreachable!(),
+  }
+}
+
+fn native(name: &'static str, arity: usize, body: fn(Vec<Value>) -> Result<Value, Error>) -> Value {
+  Value::Fun(Rc::new(Function { name: name.into(), arity, body: Rc::new(body) }))
+}
+
+fn define_natives() {
+  define_global("clock", native("clock", 0, |_| {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    Ok(Value::Num(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64()))
+  }));
+  define_global("globals", native("globals", 0, |_| {
+    let mut names: Vec<&str> = GLOBALS.with(|globals| globals.borrow().keys().copied().collect());
+    names.sort_unstable();
+    Ok(Value::Str(names.join(", ").into()))
+  }));
+  // the innermost call is the one to `callerInfo` itself
+  define_global("callerInfo", native("callerInfo", 0, |_| {
+    Ok(CALLS.with(|calls| match calls.borrow().as_slice() {
+      [.., (caller, _), (_, line), _] => Value::Str(format!("{caller}:{line}").into()),
+      [(_, line), _] => Value::Str(format!("script:{line}").into()),
+      _ => Value::Nil,
+    }))
+  }));
+  define_process();
+}
+
+/// Arguments given to the program after its own path.
+fn script_args() -> Vec<String> {
+  std::env::args().skip(1).collect()
+}
+
+fn string_or_nil(s: Option<String>) -> Value {
+  s.map_or(Value::Nil, |s| Value::Str(s.into()))
+}
+
+/// Defines `process` as an instance holding its natives in fields, as the VM does.
+fn define_process() {
+  use std::io::{BufRead, Write};
+  let members = [
+    native("argc", 0, |_| Ok(Value::Num(script_args().len() as f64))),
+    native("arg", 1, |args| Ok(match args[0] {
+      Value::Num(n) if n >= 0.0 && n.fract() == 0.0 => string_or_nil(script_args().get(n as usize).cloned()),
+      _ => Value::Nil,
+    })),
+    native("getenv", 1, |args| Ok(string_or_nil(std::env::var(args[0].to_string()).ok()))),
+    native("readLine", 0, |_| {
+      let mut line = String::new();
+      Ok(match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => Value::Nil,
+        Ok(_) => Value::Str(line.trim_end_matches(['\r', '\n']).into()),
+      })
+    }),
+    native("write", 1, |args| {
+      let mut stdout = std::io::stdout();
+      let _ = write!(stdout, "{}", args[0]).and_then(|_| stdout.flush());
+      Ok(Value::Nil)
+    }),
+    native("setExitCode", 1, |args| {
+      if let Value::Num(code) = args[0] {
+        EXIT_CODE.with(|exit_code| exit_code.set(code as i32));
+      }
+      Ok(Value::Nil)
+    }),
+  ];
+  let class = Rc::new(Class { name: "process".into(), superclass: None, methods: HashMap::new() });
+  let fields = members.into_iter().map(|member| match &member {
+    Value::Fun(fun) => (fun.name.clone(), member.clone()),
+    _ => unreachable!(),
+  });
+  let process = Instance { class, fields: RefCell::new(fields.collect()) };
+  define_global("process", Value::Instance(Rc::new(process)));
+}
+
+fn main() {
+  define_natives();
+  if let Err(err) = script() {
+    eprintln!("{err}");
+    std::process::exit(70);
+  }
+  std::process::exit(EXIT_CODE.with(|exit_code| exit_code.get()));
+}
@@ -0,0 +1,357 @@
+//! Translates a parsed program to a standalone Rust program.
+//!
+//! Values stay dynamically typed: the runtime in `prelude.rs` defines a `Value` enum, classes as
+//! structs holding a table of boxed methods, and the operators and calls of the interpreter.
+//! Globals live in a runtime map, so they are bound late like in the interpreter, while locals
+//! become `Rc<RefCell<Value>>` cells that move closures capture by cloning.
+//!
+//! Variables are resolved here rather than by the resolver, with the same rule: a name refers to
+//! the innermost local declared before it, and to a global otherwise. Reading a variable before it
+//! is assigned is a runtime error, as in the interpreter.
+
+use crate::{
+  ast::{
+    expr::Expr,
+    stmt::{self, Stmt},
+  },
+  data::LoxValue,
+  span::Span,
+  token::TokenType,
+  transpile::{unsupported, untranslated, Code, Emit, FunKind, TranspileError},
+};
+
+const PRELUDE: &str = include_str!("prelude.rs");
+
+/// Returns the Rust source of a program running `stmts`.
+pub fn transpile(stmts: &[Stmt]) -> Result<String, TranspileError> {
+  let mut emitter = Emitter::default();
+  emitter.stmts(stmts)?;
+
+  let mut out = String::from(PRELUDE);
+  out.push_str("\nfn script() -> Result<(), Error> {\n");
+  out.push_str(&emitter.code.out);
+  out.push_str("  Ok(())\n}\n");
+  Ok(out)
+}
+
+#[derive(Default)]
+struct Emitter {
+  code: Code,
+}
+
+impl Emit for Emitter {
+  fn code(&self) -> &Code {
+    &self.code
+  }
+
+  fn code_mut(&mut self) -> &mut Code {
+    &mut self.code
+  }
+}
+
+impl Emitter {
+  /// Emits `body` inside a Rust block that is also a Lox scope.
+  fn block(&mut self, body: impl FnOnce(&mut Self) -> Result<(), TranspileError>) -> Result<(), TranspileError> {
+    self.scoped("{", "}", body)
+  }
+
+  fn stmts(&mut self, stmts: &[Stmt]) -> Result<(), TranspileError> {
+    stmts.iter().try_for_each(|stmt| self.stmt(stmt))
+  }
+
+  fn stmt(&mut self, stmt: &Stmt) -> Result<(), TranspileError> {
+    use Stmt::*;
+    match stmt {
+      VarDecl(var) => {
+        let value = match &var.init {
+          Some(init) => self.expr(init)?,
+          None => "Value::Unset".into(),
+        };
+        self.define(&var.name.name, value);
+      }
+      FunDecl(fun) => {
+        // declared first, so that the function can call itself
+        self.declare_early(&fun.name.name);
+        let value = self.function(fun, FunKind::Function)?;
+        self.assign_early(&fun.name.name, value);
+      }
+      ClassDecl(class) => self.class(class)?,
+      If(if_stmt) => {
+        let cond = self.expr(&if_stmt.cond)?;
+        self.line(format!("if truthy(&{cond})"));
+        self.block(|this| this.stmt(&if_stmt.then_branch))?;
+        if let Some(else_branch) = &if_stmt.else_branch {
+          self.line("else");
+          self.block(|this| this.stmt(else_branch))?;
+        }
+      }
+      Switch(switch) => {
+        let subject = self.expr(&switch.subject)?;
+        self.block(|this| {
+          this.line(format!("let subject = {subject};"));
+          for (i, case) in switch.cases.iter().enumerate() {
+            let value = this.expr(&case.value)?;
+            let keyword = if i == 0 { "if" } else { "else if" };
+            this.line(format!("{keyword} equals(&subject, &{value})"));
+            this.block(|this| this.stmts(&case.body))?;
+          }
+          if let Some(default) = &switch.default {
+            if !switch.cases.is_empty() {
+              this.line("else");
+            }
+            this.block(|this| this.stmts(default))?;
+          }
+          Ok(())
+        })?;
+      }
+      While(while_stmt) => {
+        let Some(incr) = &while_stmt.incr else {
+          let cond = self.expr(&while_stmt.cond)?;
+          self.line(format!("while truthy(&{cond})"));
+          self.block(|this| this.stmt(&while_stmt.body))?;
+          return Ok(());
+        };
+        // the increment runs at the top of every iteration but the first, so that `continue`
+        // does not skip it
+        self.block(|this| {
+          this.line("let mut started = false;");
+          this.line("loop");
+          this.block(|this| {
+            let incr = this.expr(incr)?;
+            this.line(format!("if started {{ let _ = {incr}; }}"));
+            this.line("started = true;");
+            let cond = this.expr(&while_stmt.cond)?;
+            this.line(format!("if !truthy(&{cond}) {{ break; }}"));
+            this.stmt(&while_stmt.body)
+          })
+        })?;
+      }
+      Break(_) => self.line("break;"),
+      Continue(_) => self.line("continue;"),
+      Print(print) => {
+        let value = self.expr(&print.expr)?;
+        self.line(format!("println!(\"{{}}\", {value});"));
+      }
+      Return(ret) => {
+        let value = match (&ret.value, self.code.kind) {
+          (_, FunKind::Initializer) => "read(&v_this)".to_string(),
+          (Some(value), _) => self.expr(value)?,
+          (None, _) => "Value::Nil".into(),
+        };
+        match self.code.kind {
+          FunKind::Script => return Err(unsupported("`return` outside of a function", ret.span)),
+          _ => self.line(format!("return Ok({value});")),
+        }
+      }
+      Block(block) => self.block(|this| this.stmts(&block.stmts))?,
+      Expr(expr) => {
+        let value = self.expr(&expr.expr)?;
+        self.line(format!("let _ = {value};"));
+      }
+      EnumDecl(_) | Time(_) | Namespace(_) | Import(_) | NativeDecl(_) | Export(_) | Dummy(_) => {
+        return Err(untranslated(stmt))
+      }
+    }
+    Ok(())
+  }
+
+  /// Binds `name` to `value` in the current scope, or as a global at the top level.
+  fn define(&mut self, name: &str, value: String) {
+    match self.code.scopes.is_empty() {
+      true => self.line(format!("define_global({name:?}, {value});")),
+      false => {
+        self.line(format!("let v_{name} = cell({value});"));
+        self.declare(name);
+      }
+    }
+  }
+
+  /// Declares a local before its value is built, for declarations that refer to themselves.
+  fn declare_early(&mut self, name: &str) {
+    if !self.code.scopes.is_empty() {
+      self.line(format!("let v_{name} = cell(Value::Nil);"));
+      self.declare(name);
+    }
+  }
+
+  fn assign_early(&mut self, name: &str, value: String) {
+    match self.code.scopes.is_empty() {
+      true => self.line(format!("define_global({name:?}, {value});")),
+      false => self.line(format!("assign(&v_{name}, {value});")),
+    }
+  }
+
+  /// Clones every visible local, so that a `move` closure built next captures the same cells.
+  fn captures(&self) -> String {
+    let mut names: Vec<&String> = self.code.scopes.iter().flatten().collect();
+    names.sort();
+    names.dedup();
+    names.iter().map(|name| format!("let v_{name} = v_{name}.clone(); ")).collect()
+  }
+
+  /// Emits the body of a function into a separate buffer, with its parameters in a new scope.
+  fn body(&mut self, params: &[String], body: &[Stmt], kind: FunKind) -> Result<String, TranspileError> {
+    self.function_body(Vec::new(), kind, |this| {
+      this.line("let mut args = args.into_iter();");
+      for param in params {
+        this.line(format!("let v_{param} = cell(args.next().unwrap());"));
+        this.declare(param);
+      }
+      this.stmts(body)?;
+      let ret = match kind {
+        FunKind::Initializer => "read(&v_this)",
+        _ => "Value::Nil",
+      };
+      this.line(format!("Ok({ret})"));
+      Ok(())
+    })
+  }
+
+  fn function(&mut self, fun: &stmt::FunDecl, kind: FunKind) -> Result<String, TranspileError> {
+    let params: Vec<String> = fun.params.iter().map(|p| p.name.to_string()).collect();
+    let captures = self.captures();
+    let body = self.body(&params, &fun.body, kind)?;
+    let pad = self.code.pad();
+    Ok(format!(
+      "Value::Fun(Rc::new(Function {{ name: {:?}.into(), arity: {}, body: {{ {captures}Rc::new(move |args: Vec<Value>| -> Result<Value, Error> {{\n{body}{pad}}}) }} }}))",
+      fun.name.name,
+      params.len()
+    ))
+  }
+
+  fn class(&mut self, class: &stmt::ClassDecl) -> Result<(), TranspileError> {
+    let name = &class.name.name;
+    let global = self.code.scopes.is_empty();
+    self.declare_early(name);
+    let superclass = match &class.super_name {
+      Some(super_name) => {
+        let value = self.variable(&super_name.name, super_name.span);
+        Some(format!("superclass({value}, {:?})?", super_name.span.to_string()))
+      }
+      None => None,
+    };
+
+    self.block(|this| {
+      if let Some(superclass) = &superclass {
+        this.line(format!("let superclass = {superclass};"));
+        this.line("let v_super = cell(Value::Class(superclass.clone()));");
+        this.declare("super");
+      }
+      this.line("let mut methods = HashMap::new();");
+      for method in &class.methods {
+        let kind = match method.name.name == "init" {
+          true => FunKind::Initializer,
+          false => FunKind::Function,
+        };
+        let captures = this.captures();
+        // `this` comes in as the first argument
+        let mut params = vec!["this".to_string()];
+        params.extend(method.params.iter().map(|p| p.name.to_string()));
+        let body = this.body(&params, &method.body, kind)?;
+        let pad = this.code.pad();
+        this.line(format!(
+          "methods.insert({:?}.to_string(), Method {{ arity: {}, body: {{ {captures}Rc::new(move |this: Value, args: Vec<Value>| -> Result<Value, Error> {{\n{pad}  let args = std::iter::once(this).chain(args);\n{body}{pad}}}) }} }});",
+          method.name.name,
+          method.params.len()
+        ));
+      }
+      let superclass = match superclass {
+        Some(_) => "Some(superclass)",
+        None => "None",
+      };
+      let value = format!("Value::Class(Rc::new(Class {{ name: {name:?}.into(), superclass: {superclass}, methods }}))");
+      match global {
+        true => this.line(format!("define_global({name:?}, {value});")),
+        false => this.line(format!("assign(&v_{name}, {value});")),
+      }
+      Ok(())
+    })
+  }
+
+  fn variable(&self, name: &str, span: Span) -> String {
+    match self.is_local(name) {
+      true => format!("load(&v_{name}, {name:?}, {:?})?", span.to_string()),
+      false => format!("get_global({name:?}, {:?})?", span.to_string()),
+    }
+  }
+
+  fn expr(&mut self, expr: &Expr) -> Result<String, TranspileError> {
+    use Expr::*;
+    let code = match expr {
+      Var(var) => self.variable(&var.name.name, var.name.span),
+      Assignment(assign) => {
+        let value = self.expr(&assign.value)?;
+        let name = &assign.name.name;
+        match self.is_local(name) {
+          true => format!("assign(&v_{name}, {value})"),
+          false => format!("set_global({name:?}, {value}, {:?})?", assign.name.span.to_string()),
+        }
+      }
+      Lambda(lambda) => self.function(&lambda.decl, FunKind::Function)?,
+      Call(call) => {
+        let callee = self.expr(&call.callee)?;
+        let args = call.args.iter().map(|arg| self.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        format!("call({callee}, vec![{}], {:?}, {})?", args.join(", "), call.span.to_string(), call.line)
+      }
+      Get(get) => {
+        let obj = self.expr(&get.obj)?;
+        format!("get({obj}, {:?}, {:?})?", get.name.name, get.name.span.to_string())
+      }
+      Set(set) => {
+        let obj = self.expr(&set.obj)?;
+        let value = self.expr(&set.value)?;
+        format!("set({obj}, {:?}, {value}, {:?})?", set.name.name, set.name.span.to_string())
+      }
+      This(this) => match self.is_local("this") {
+        true => "read(&v_this)".into(),
+        false => return Err(unsupported("`this` outside of a method", this.span)),
+      },
+      Super(sup) => match self.is_local("super") {
+        true => format!(
+          "get_super(read(&v_super), {:?}, read(&v_this), {:?})?",
+          sup.method.name,
+          sup.method.span.to_string()
+        ),
+        false => return Err(unsupported("`super` outside of a subclass", sup.span)),
+      },
+      Lit(lit) => match &lit.value {
+        LoxValue::Nil => "Value::Nil".into(),
+        LoxValue::Boolean(b) => format!("Value::Bool({b})"),
+        LoxValue::Number(n) => format!("Value::Num({n:?})"),
+        LoxValue::String(s) => format!("Value::Str(Rc::from({s:?}))"),
+        _ => return Err(unsupported("this literal", lit.span)),
+      },
+      Group(group) => self.expr(&group.expr)?,
+      Unary(unary) => {
+        let operand = self.expr(&unary.operand)?;
+        match unary.operator.kind {
+          TokenType::Minus => format!("negate({operand}, {:?})?", unary.operator.span.to_string()),
+          _ => format!("Value::Bool(!truthy(&{operand}))"),
+        }
+      }
+      Binary(binary) => {
+        let left = self.expr(&binary.left)?;
+        let right = self.expr(&binary.right)?;
+        let at = format!("{:?}", binary.operator.span.to_string());
+        use TokenType as T;
+        match &binary.operator.kind {
+          T::EqualEqual => format!("Value::Bool(equals(&{left}, &{right}))"),
+          T::BangEqual => format!("Value::Bool(!equals(&{left}, &{right}))"),
+          T::Plus => format!("add({left}, {right}, {at})?"),
+          T::Comma => format!("{{ let _ = {left}; {right} }}"),
+          op @ (T::Minus | T::Star | T::Slash) => format!("arith(\"{op}\", {left}, {right}, {at})?"),
+          op => format!("compare(\"{op}\", {left}, {right}, {at})?"),
+        }
+      }
+      Logical(logical) => {
+        let left = self.expr(&logical.left)?;
+        let right = self.expr(&logical.right)?;
+        match logical.operator.kind {
+          TokenType::And => format!("{{ let left = {left}; if truthy(&left) {{ {right} }} else {{ left }} }}"),
+          _ => format!("{{ let left = {left}; if truthy(&left) {{ left }} else {{ {right} }} }}"),
+        }
+      }
+    };
+    Ok(code)
+  }
+}
@@ -0,0 +1,170 @@
+//! Step-by-step traces written by `--explain`, for following how the tree-walker evaluates a
+//! program, as in the book.
+
+use std::io::Write;
+
+use crate::{
+  ast::{expr::Expr, stmt::Stmt},
+  data::{LoxIdent, LoxValue},
+  interpreter::hooks::Hooks,
+};
+
+/// Writes a line for each statement run and each expression evaluated, indented by how deeply
+/// it is nested, with the value it produced. For example, `print a + 2;` gives
+///
+/// ```text
+/// print a + 2
+///   Binary a + 2
+///     Variable a => 1
+///     Literal 2 => 2
+///   => 3
+/// ```
+pub struct Explain {
+  out: Box<dyn Write>,
+  depth: usize,
+  /// Names of the calls still running, innermost last.
+  calls: Vec<String>,
+}
+
+impl Explain {
+  pub fn new(out: Box<dyn Write>) -> Self {
+    Self { out, depth: 0, calls: Vec::new() }
+  }
+
+  fn line(&mut self, text: &str) {
+    // the trace is best-effort and must not stop the program
+    let _ = writeln!(self.out, "{:indent$}{text}", "", indent = self.depth * 2);
+  }
+}
+
+impl Hooks for Explain {
+  fn on_stmt(&mut self, stmt: &Stmt) {
+    self.line(&header(stmt));
+    self.depth += 1;
+  }
+
+  fn on_stmt_end(&mut self, _stmt: &Stmt) {
+    self.depth -= 1;
+  }
+
+  fn on_expr(&mut self, expr: &Expr) {
+    if !is_leaf(expr) {
+      self.line(&format!("{} {}", kind(expr), show(expr)));
+    }
+    self.depth += 1;
+  }
+
+  fn on_expr_end(&mut self, expr: &Expr, value: Option<&LoxValue>) {
+    self.depth -= 1;
+    let Some(value) = value else {
+      return self.line(&format!("{} {} raised an error", kind(expr), show(expr)));
+    };
+    let binding = match expr {
+      Expr::Assignment(assign) => format!(", so {} = {value:?}", assign.name),
+      Expr::Set(set) => format!(", so {}.{} = {value:?}", show(&set.obj), set.name),
+      _ => String::new(),
+    };
+    match is_leaf(expr) {
+      true => self.line(&format!("{} {} => {value:?}", kind(expr), show(expr))),
+      false => self.line(&format!("=> {value:?}{binding}")),
+    }
+  }
+
+  fn on_call(&mut self, name: &str, args: &[LoxValue]) {
+    let args: Vec<_> = args.iter().map(|arg| format!("{arg:?}")).collect();
+    self.line(&format!("call {name}({})", args.join(", ")));
+    self.depth += 1;
+    self.calls.push(name.into());
+  }
+
+  fn on_return(&mut self, value: &LoxValue) {
+    self.depth = self.depth.saturating_sub(1);
+    let name = self.calls.pop().unwrap_or_default();
+    self.line(&format!("{name} returned {value:?}"));
+  }
+}
+
+/// Expressions shown on a single line, with their value.
+fn is_leaf(expr: &Expr) -> bool {
+  matches!(expr, Expr::Lit(_) | Expr::Var(_) | Expr::This(_) | Expr::Super(_) | Expr::Lambda(_))
+}
+
+fn kind(expr: &Expr) -> &'static str {
+  match expr {
+    Expr::Var(_) => "Variable",
+    Expr::Call(_) => "Call",
+    Expr::Get(_) => "Get",
+    Expr::Set(_) => "Set",
+    Expr::This(_) => "This",
+    Expr::Super(_) => "Super",
+    Expr::Lit(_) => "Literal",
+    Expr::Group(_) => "Group",
+    Expr::Unary(_) => "Unary",
+    Expr::Binary(_) => "Binary",
+    Expr::Logical(_) => "Logical",
+    Expr::Assignment(_) => "Assign",
+    Expr::Lambda(_) => "Lambda",
+  }
+}
+
+/// Writes `expr` back as Lox.
+fn show(expr: &Expr) -> String {
+  match expr {
+    Expr::Var(var) => var.name.to_string(),
+    Expr::Call(call) => {
+      let args: Vec<_> = call.args.iter().map(show).collect();
+      format!("{}({})", show(&call.callee), args.join(", "))
+    }
+    Expr::Get(get) => format!("{}.{}", show(&get.obj), get.name),
+    Expr::Set(set) => format!("{}.{} = {}", show(&set.obj), set.name, show(&set.value)),
+    Expr::This(_) => "this".into(),
+    Expr::Super(sup) => format!("super.{}", sup.method),
+    Expr::Lit(lit) => format!("{:?}", lit.value),
+    Expr::Group(group) => format!("({})", show(&group.expr)),
+    Expr::Unary(unary) => format!("{}{}", unary.operator, show(&unary.operand)),
+    Expr::Binary(bin) => format!("{} {} {}", show(&bin.left), bin.operator, show(&bin.right)),
+    Expr::Logical(logical) => {
+      format!("{} {} {}", show(&logical.left), logical.operator, show(&logical.right))
+    }
+    Expr::Assignment(assign) => format!("{} = {}", assign.name, show(&assign.value)),
+    Expr::Lambda(lambda) => format!("fun ({}) {{ ... }}", params(&lambda.decl.params)),
+  }
+}
+
+fn params(params: &[LoxIdent]) -> String {
+  params.iter().map(|param| param.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Writes the part of `stmt` before its body back as Lox.
+fn header(stmt: &Stmt) -> String {
+  match stmt {
+    Stmt::VarDecl(var) => match &var.init {
+      Some(init) => format!("var {} = {}", var.name, show(init)),
+      None => format!("var {}", var.name),
+    },
+    Stmt::FunDecl(fun) => format!("fun {}({})", fun.name, params(&fun.params)),
+    Stmt::NativeDecl(native) => format!("native fun {}({})", native.name, params(&native.params)),
+    Stmt::ClassDecl(class) => match &class.super_name {
+      Some(super_name) => format!("class {} < {super_name}", class.name),
+      None => format!("class {}", class.name),
+    },
+    Stmt::EnumDecl(decl) => format!("enum {}", decl.name),
+    Stmt::Namespace(ns) => format!("namespace {}", ns.name),
+    Stmt::Import(import) => format!("import {:?}", import.path),
+    Stmt::Export(export) => format!("export {}", header(&export.decl)),
+    Stmt::If(if_stmt) => format!("if ({})", show(&if_stmt.cond)),
+    Stmt::Switch(switch) => format!("switch ({})", show(&switch.subject)),
+    Stmt::While(while_stmt) => format!("while ({})", show(&while_stmt.cond)),
+    Stmt::Break(_) => "break".into(),
+    Stmt::Continue(_) => "continue".into(),
+    Stmt::Time(_) => "time".into(),
+    Stmt::Print(print) => format!("print {}", show(&print.expr)),
+    Stmt::Return(ret) => match &ret.value {
+      Some(value) => format!("return {}", show(value)),
+      None => "return".into(),
+    },
+    Stmt::Block(_) => "{ ... }".into(),
+    Stmt::Expr(expr) => show(&expr.expr),
+    Stmt::Dummy(_) => "<invalid statement>".into(),
+  }
+}
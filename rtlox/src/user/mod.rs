@@ -1,79 +1,418 @@
-use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
+use std::{collections::HashMap, env, error::Error, fmt, fs};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::str;
+use std::time::Instant;
+
+use lox_common::timings::Timings;
 
 use crate::{
+  ast::stmt::Stmt,
   interpreter::Interpreter,
-  parser::{Parser, ParserOutcome, state::ParserOptions},
-  resolver::{Resolver, error::ErrorType},
+  parser::{error::ParseError, valid_prefix, Parser, ParserOutcome, state::ParserOptions, trivia::Ignore},
+  resolver::{Resolver, error::ErrorType, table::ResolutionTable},
+  span::Label,
+  transpile::{self, Target},
 };
 
+pub mod explain;
+pub mod profile;
+
+use explain::Explain;
+use profile::CallProfile;
+
+/// Settings taken from the command line.
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+  /// Directories searched by `import`, from `--module-path`.
+  pub module_paths: Vec<PathBuf>,
+  /// Raise a runtime error when arithmetic overflows to `inf` or produces `NaN`.
+  pub checked_arith: bool,
+  /// Reject uses of globals that are not declared before they run.
+  pub strict_globals: bool,
+  /// In the REPL, still run the statements before the first syntax error of a line.
+  pub run_valid_prefix: bool,
+  /// Print the script translated to this language instead of running it.
+  pub emit: Option<Target>,
+  /// Only print the script's own output and its errors, not warnings or the REPL banner.
+  pub quiet: bool,
+  /// Print how long each phase took, and peak memory, when the script ends.
+  pub time: bool,
+  /// Write the time spent in each call stack to this file, in the folded format of flamegraph
+  /// tools.
+  pub profile_calls: Option<PathBuf>,
+  /// Write each step of evaluation to stderr, indented by how deeply it is nested.
+  pub explain: bool,
+  /// Write the scopes left when the script ends, and the closures that captured them, to this
+  /// file as a Graphviz drawing.
+  pub env_graph: Option<PathBuf>,
+  /// Overrides [`Interpreter::max_call_depth`].
+  pub max_call_depth: Option<usize>,
+  /// Overrides [`ParserOptions::max_string_len`].
+  pub max_string_len: Option<usize>,
+  /// Parse the script as it is read, a chunk at a time, rather than reading it all first.
+  pub stream: bool,
+  /// Also print the warnings that `lox-ignore` comments silenced.
+  pub list_suppressed: bool,
+  /// Names given with `--define`, which `#if` directives test, in the script and its imports.
+  pub defines: HashMap<String, String>,
+  /// Arguments after the script, which it reads with `process.arg`.
+  pub args: Vec<String>,
+}
+
+/// Why a script did not run to completion. Diagnostics have already been printed.
+#[derive(Debug)]
+pub enum Failure {
+  /// The script could not be read.
+  Io(io::Error),
+  /// The script was rejected before it ran.
+  Compile,
+  /// The script raised an error while running.
+  Runtime,
+  /// The script ran to completion but set a nonzero exit code with `process.setExitCode`.
+  Exit(i32),
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Failure::Io(err) => write!(f, "Could not read script: {err}"),
+      Failure::Compile => f.write_str("Script was rejected by the compiler"),
+      Failure::Runtime => f.write_str("Script raised a runtime error"),
+      Failure::Exit(code) => write!(f, "Script exited with code {code}"),
+    }
+  }
+}
+
+impl Error for Failure {}
+
+impl From<io::Error> for Failure {
+  fn from(err: io::Error) -> Self {
+    Failure::Io(err)
+  }
+}
+
+impl Config {
+  /// Returns the module search path: `--module-path` entries, then `LOX_PATH` entries.
+  fn search_paths(&self) -> Vec<PathBuf> {
+    let mut paths = self.module_paths.clone();
+    if let Some(lox_path) = env::var_os("LOX_PATH") {
+      paths.extend(env::split_paths(&lox_path));
+    }
+    paths
+  }
+
+  /// Applies the settings shared by scripts and the REPL.
+  fn configure(&self, interpreter: &mut Interpreter) {
+    interpreter.loader.search_paths = self.search_paths();
+    interpreter.loader.defines = self.defines.clone();
+    interpreter.checked_arith = self.checked_arith;
+    interpreter.strict_globals = self.strict_globals;
+    interpreter.args = self.args.clone();
+    if let Some(depth) = self.max_call_depth {
+      interpreter.max_call_depth = depth;
+    }
+  }
+}
+
 fn handle_parser_outcome(
   // src: &str,
   (stmts, errors): &ParserOutcome,
+  ignores: Vec<Ignore>,
   interpreter: &mut Interpreter,
-) -> bool {
+  file: Option<&Path>,
+  config: &Config,
+  timings: &mut Timings,
+) -> Result<(), Failure> {
   // parse errors
   if !errors.is_empty() {
-    for error in errors {
-      eprintln!("{}", error);
+    errors.iter().for_each(report_parse_error);
+    if config.run_valid_prefix {
+      let _ = execute(valid_prefix(stmts, errors), ignores, interpreter, file, config, timings);
     }
-    return false;
+    return Err(Failure::Compile);
   }
 
+  execute(stmts, ignores, interpreter, file, config, timings)
+}
+
+/// Resolves and runs parsed statements, reporting any errors.
+fn execute(
+  stmts: &[Stmt],
+  ignores: Vec<Ignore>,
+  interpreter: &mut Interpreter,
+  file: Option<&Path>,
+  config: &Config,
+  timings: &mut Timings,
+) -> Result<(), Failure> {
+  let start = Instant::now();
+  let resolved = resolve(stmts, ignores, interpreter, file, config);
+  timings.record("resolve", start.elapsed());
+  let Some(table) = resolved else {
+    return Err(Failure::Compile);
+  };
+  interpreter.add_resolution(table);
+
+  let start = Instant::now();
+  let res = interpreter.interpret(stmts);
+  timings.record("execute", start.elapsed());
+  res.map_err(|error| {
+    report(&error, &error.labels());
+    // print_span_window(writer, src, error.primary_span());
+    Failure::Runtime
+  })
+}
+
+/// Checks parsed statements, reporting any diagnostics. Returns what the resolver found, or
+/// `None` if there were errors.
+fn resolve(
+  stmts: &[Stmt],
+  ignores: Vec<Ignore>,
+  interpreter: &mut Interpreter,
+  file: Option<&Path>,
+  config: &Config,
+) -> Option<ResolutionTable> {
   // resolver errors
-  let resolver = Resolver::new(interpreter);
-  let (ok, errors) = resolver.resolve(stmts);
-  if !ok {
-    let mut has_errors = false;
-    for error in errors {
-      eprintln!("{}; at position {}", error.message, error.span);
-      if let ErrorType::Error = error.kind {
-        has_errors = true;
-      };
+  let mut resolver = Resolver::new(interpreter);
+  if let Some(file) = file {
+    resolver.set_file(file);
+  }
+  resolver.set_ignores(ignores);
+  let (table, errors) = resolver.resolve_to_table(stmts);
+  let mut has_errors = false;
+  for error in errors {
+    let shown = match error.kind {
+      ErrorType::Error => true,
+      ErrorType::Warning => !config.quiet,
+      ErrorType::Suppressed => config.list_suppressed,
+    };
+    has_errors |= matches!(error.kind, ErrorType::Error);
+    let message = format!("{}; at position {}", error.message, error.span);
+    match (shown, error.kind, error.lint) {
+      (false, ..) => {}
+      (true, ErrorType::Suppressed, Some(lint)) => {
+        report(format!("{message} (suppressed: {})", lint.name()), &error.labels)
+      }
+      (true, ..) => report(message, &error.labels),
     }
-    if has_errors { return false;}
   }
+  (!has_errors).then_some(table)
+}
 
-  // interpreter
-  if let Err(error) = interpreter.interpret(stmts) {
-    eprintln!("{}", error);
-    // print_span_window(writer, src, error.primary_span());
-    return false;
+/// Prints a diagnostic, followed by the labels that explain it.
+fn report(message: impl fmt::Display, labels: &[Label]) {
+  eprintln!("{message}");
+  for label in labels {
+    eprintln!("  {label}");
+  }
+}
+
+/// Prints a parse error, followed by its labels and notes.
+fn report_parse_error(error: &ParseError) {
+  report(error, error.labels());
+  for note in error.notes() {
+    eprintln!("  note: {note}");
+  }
+}
+
+/// Script path that stands for standard input.
+pub const STDIN: &str = "-";
+
+/// Opens the script at `file`, or standard input for `-`, to be read as it is parsed.
+fn open_script(file: &Path) -> io::Result<Box<dyn Read>> {
+  match file == Path::new(STDIN) {
+    true => Ok(Box::new(io::stdin())),
+    false => Ok(Box::new(fs::File::open(file)?)),
+  }
+}
+
+/// Reads the script at `file`, or standard input if `file` is [`STDIN`]. Also returns the path
+/// the script was read from, if any.
+fn read_script(file: &Path) -> io::Result<(String, Option<&Path>)> {
+  if file != Path::new(STDIN) {
+    return Ok((fs::read_to_string(file)?, Some(file)));
+  }
+  let mut src = String::new();
+  io::stdin().read_to_string(&mut src)?;
+  Ok((src, None))
+}
+
+/// Translates a script to `target` and prints the result.
+pub fn transpile_file(file: impl AsRef<Path>, target: Target, config: &Config) -> Result<(), Failure> {
+  let (src, file) = read_script(file.as_ref())?;
+  let mut parser = Parser::new(&src);
+  parser.options.file = file.map(Path::to_path_buf);
+  parser.options.max_string_len = config.max_string_len;
+  parser.options.defines = config.defines.clone();
+  let ((stmts, errors), ignores) = parser.parse_with_ignores();
+  if !errors.is_empty() {
+    errors.iter().for_each(report_parse_error);
+    return Err(Failure::Compile);
+  }
+  if resolve(&stmts, ignores, &mut Interpreter::new(), file, config).is_none() {
+    return Err(Failure::Compile);
+  }
+
+  match transpile::transpile(&stmts, target) {
+    Ok(out) => {
+      print!("{out}");
+      Ok(())
+    }
+    Err(error) => {
+      eprintln!("{}", error);
+      Err(Failure::Compile)
+    }
   }
-  true
 }
 
+/// Runs a script with the default settings. Returns whether it ran without errors.
 pub fn run_file(file: impl AsRef<Path>) -> io::Result<bool> {
-  let src = &fs::read_to_string(file)?;
-  let mut interpreter = Interpreter::new();
+  match run_file_with(file, &Config::default(), &mut Interpreter::new()) {
+    Ok(()) => Ok(true),
+    Err(Failure::Io(err)) => Err(err),
+    Err(_) => Ok(false),
+  }
+}
+
+pub fn run_file_with(
+  file: impl AsRef<Path>,
+  config: &Config,
+  interpreter: &mut Interpreter,
+) -> Result<(), Failure> {
+  let file = file.as_ref();
+  let src;
+  let parser = match config.stream {
+    true => Parser::from_reader(open_script(file)?),
+    false => {
+      src = read_script(file)?.0;
+      Parser::new(&src)
+    }
+  };
+  let file = (file != Path::new(STDIN)).then_some(file);
+  config.configure(interpreter);
 
-  Ok(run(src, &mut interpreter, ParserOptions {
+  let profile = profile_calls(interpreter, config);
+  let res = run(parser, interpreter, ParserOptions {
     repl_mode: false,
     display_ast: true,
-  }))
+    keep_comments: false,
+    file: None,
+    max_string_len: None,
+    defines: HashMap::new(),
+  }, file, config);
+  write_profile(interpreter, config, profile);
+  write_env_graph(interpreter, config);
+  res.and_then(|()| exited(interpreter))
+}
+
+/// Fails with the exit code the script set, if it is not 0.
+fn exited(interpreter: &Interpreter) -> Result<(), Failure> {
+  match interpreter.exit_code() {
+    0 => Ok(()),
+    code => Err(Failure::Exit(code)),
+  }
+}
+
+/// Starts a call profile on `interpreter` if `--profile-calls` asked for one, or the trace of
+/// `--explain`.
+fn profile_calls(interpreter: &mut Interpreter, config: &Config) -> Option<CallProfile> {
+  if config.explain {
+    interpreter.hooks = Some(Box::new(Explain::new(Box::new(io::stderr()))));
+  }
+  config.profile_calls.as_ref()?;
+  let profile = CallProfile::new();
+  interpreter.hooks = Some(Box::new(profile.clone()));
+  Some(profile)
+}
+
+/// Writes the call profile once the script has ended.
+fn write_profile(interpreter: &mut Interpreter, config: &Config, profile: Option<CallProfile>) {
+  let (Some(profile), Some(path)) = (profile, &config.profile_calls) else {
+    return;
+  };
+  interpreter.hooks = None;
+  if let Err(err) = fs::write(path, profile.finish()) {
+    eprintln!("Could not write call profile to `{}`: {err}", path.display());
+  }
+}
+
+/// Writes the drawing of `--env-graph` once the script has ended.
+fn write_env_graph(interpreter: &Interpreter, config: &Config) {
+  let Some(path) = &config.env_graph else {
+    return;
+  };
+  if let Err(err) = fs::write(path, interpreter.env_graph()) {
+    eprintln!("Could not write environment graph to `{}`: {err}", path.display());
+  }
 }
 
 /// Process Lox source code
-fn run(src: &str, interpreter: &mut Interpreter, options: ParserOptions) -> bool {
-  let mut parser = Parser::new(src);
-  parser.options = options;
+fn run(
+  mut parser: Parser,
+  interpreter: &mut Interpreter,
+  options: ParserOptions,
+  file: Option<&Path>,
+  config: &Config,
+) -> Result<(), Failure> {
+  // the parser pulls tokens from the scanner as it goes, so scanning is part of `parse`
+  let mut timings = Timings::default();
+  parser.options = ParserOptions {
+    file: file.map(Path::to_path_buf),
+    max_string_len: config.max_string_len,
+    defines: config.defines.clone(),
+    ..options
+  };
 
-  let outcome = parser.parse();
+  let start = Instant::now();
+  let (outcome, ignores) = parser.parse_with_ignores();
+  timings.record("parse", start.elapsed());
 
-  handle_parser_outcome(&outcome, interpreter)
+  let res = handle_parser_outcome(&outcome, ignores, interpreter, file, config, &mut timings);
+  if config.time {
+    eprintln!("{timings}");
+  }
+  res
 }
 
-/// REPL mode
-pub fn run_repl() {
-  println!("Entering interactive mode...");
+/// Interpreter for code typed at the REPL or passed with `-e`.
+fn interactive_interpreter(config: &Config) -> Interpreter {
   let mut interpreter = Interpreter::new();
+  config.configure(&mut interpreter);
+  interpreter
+}
 
-  let options = ParserOptions {
+/// Parser options for interactive code, which print the value of a trailing expression without
+/// a `;`.
+fn interactive_options() -> ParserOptions {
+  ParserOptions {
     repl_mode: true,
     display_ast: false,
-  };
+    keep_comments: false,
+    file: None,
+    max_string_len: None,
+    defines: HashMap::new(),
+  }
+}
+
+/// Runs a snippet passed on the command line, as if it was typed at the REPL.
+pub fn run_snippet(src: &str, config: &Config) -> Result<(), Failure> {
+  let mut interpreter = interactive_interpreter(config);
+  let profile = profile_calls(&mut interpreter, config);
+  let res = run(Parser::new(src), &mut interpreter, interactive_options(), None, config);
+  write_profile(&mut interpreter, config, profile);
+  write_env_graph(&interpreter, config);
+  res.and_then(|()| exited(&interpreter))
+}
+
+/// REPL mode
+pub fn run_repl(config: &Config) {
+  if !config.quiet {
+    println!("Entering interactive mode...");
+  }
+  let mut interpreter = interactive_interpreter(config);
+  let options = interactive_options();
+  // how the last input changed the globals, for `:diff`
+  let mut diff = Vec::new();
 
   loop {
     let mut line = String::new();
@@ -84,8 +423,34 @@ pub fn run_repl() {
       .read_line(&mut line)
       .expect("Failed to read line");
 
-    if !run(&line, &mut interpreter, options.clone()) {
+    if let Some(name) = unset_command(&line) {
+      if !interpreter.unset_global(name) {
+        eprintln!("No global named `{name}`");
+      }
       continue;
-    };
+    }
+    if line.trim() == ":diff" {
+      print_diff(&diff);
+      continue;
+    }
+    let before = interpreter.globals_snapshot();
+    let _ = run(Parser::new(&line), &mut interpreter, options.clone(), None, config);
+    diff = interpreter.globals_diff(&before);
+  }
+}
+
+/// Prints how the last input changed the globals, for the `:diff` REPL command.
+fn print_diff(diff: &[String]) {
+  match diff {
+    [] => println!("No globals changed"),
+    lines => lines.iter().for_each(|line| println!("{line}")),
+  }
+}
+
+/// Returns the name in a `:unset <name>` REPL command, which removes a global.
+fn unset_command(line: &str) -> Option<&str> {
+  match line.split_whitespace().collect::<Vec<_>>()[..] {
+    [":unset", name] => Some(name),
+    _ => None,
   }
 }
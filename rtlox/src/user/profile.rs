@@ -0,0 +1,15 @@
+//! Call profiles written by `--profile-calls`, gathered through the interpreter's [`Hooks`].
+
+pub use lox_common::profile::CallProfile;
+
+use crate::{data::LoxValue, interpreter::hooks::Hooks};
+
+impl Hooks for CallProfile {
+  fn on_call(&mut self, name: &str, _args: &[LoxValue]) {
+    self.enter(name);
+  }
+
+  fn on_return(&mut self, _value: &LoxValue) {
+    self.leave();
+  }
+}